@@ -0,0 +1,25 @@
+-- TABLE: audit_log
+-- Append-only record of privileged and money-moving operations
+CREATE TABLE audit_log (
+  audit_id BIGSERIAL PRIMARY KEY,
+  time TIMESTAMPTZ NOT NULL DEFAULT timezone ('utc', now ()),
+  actor TEXT NOT NULL,
+  action TEXT NOT NULL,
+  target TEXT,
+  details JSONB NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX idx_audit_actor ON audit_log (actor);
+
+CREATE INDEX idx_audit_target ON audit_log (target);
+
+CREATE FUNCTION reject_audit_change () RETURNS TRIGGER AS $$
+BEGIN
+  RAISE EXCEPTION 'audit_log is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_immutable BEFORE
+UPDATE
+OR DELETE ON audit_log FOR EACH ROW
+EXECUTE FUNCTION reject_audit_change ();
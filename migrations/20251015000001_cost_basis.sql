@@ -0,0 +1,8 @@
+-- Average cost per share of a holding, kept unrounded. NULL when the basis is unknown, as is the
+-- case for holdings that existed before cost basis was tracked.
+ALTER TABLE holdings
+ADD COLUMN avg_cost NUMERIC CHECK (avg_cost >= 0);
+
+-- Profit or loss realized by the seller of a trade, NULL when their cost basis was unknown
+ALTER TABLE stock_events
+ADD COLUMN realized_pl NUMERIC;
@@ -0,0 +1,37 @@
+-- Orders rest on the book until they are filled or cancelled
+ALTER TABLE orders
+ADD COLUMN remaining INTEGER;
+
+UPDATE orders
+SET
+  remaining = shares;
+
+ALTER TABLE orders
+ALTER COLUMN remaining
+SET NOT NULL,
+ADD CONSTRAINT remaining_bounds CHECK (
+  remaining >= 0
+  AND remaining <= shares
+),
+ADD COLUMN status TEXT NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'filled', 'cancelled')),
+ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT timezone ('utc', now ());
+
+CREATE INDEX idx_orders_book ON orders (ticker, type, price)
+WHERE
+  status = 'open';
+
+CREATE INDEX idx_orders_user ON orders (user_id)
+WHERE
+  status = 'open';
+
+-- Funds and shares reserved by open orders, so users can't place orders they can't cover
+ALTER TABLE users
+ADD COLUMN escrow NUMERIC(16, 2) NOT NULL DEFAULT 0 CHECK (escrow >= 0);
+
+ALTER TABLE holdings
+ADD COLUMN escrow INTEGER NOT NULL DEFAULT 0 CHECK (escrow >= 0);
+
+-- The orders a trade filled
+ALTER TABLE stock_events
+ADD COLUMN buy_order_id INTEGER REFERENCES orders (order_id),
+ADD COLUMN sell_order_id INTEGER REFERENCES orders (order_id);
@@ -0,0 +1,14 @@
+-- Orders may expire, after which the sweeper releases their escrow
+ALTER TABLE orders
+ADD COLUMN expires_at TIMESTAMPTZ;
+
+ALTER TABLE orders
+DROP CONSTRAINT orders_status_check,
+ADD CONSTRAINT orders_status_check CHECK (
+  status IN ('open', 'filled', 'cancelled', 'expired')
+);
+
+CREATE INDEX idx_orders_expiry ON orders (expires_at)
+WHERE
+  status = 'open'
+  AND expires_at IS NOT NULL;
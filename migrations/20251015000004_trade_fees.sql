@@ -0,0 +1,17 @@
+-- Accounts owned by the exchange itself, such as the fee treasury, have no linked player
+ALTER TABLE users
+ADD COLUMN system BOOLEAN NOT NULL DEFAULT FALSE,
+DROP CONSTRAINT users_check,
+ADD CONSTRAINT users_check CHECK (
+  disc_id IS NOT NULL
+  OR mc_id IS NOT NULL
+  OR system
+);
+
+-- Fees a buy order may still owe, reserved alongside its notional value
+ALTER TABLE orders
+ADD COLUMN fee_escrow NUMERIC(16, 2) NOT NULL DEFAULT 0 CHECK (fee_escrow >= 0);
+
+-- The fee the buyer paid on a trade
+ALTER TABLE stock_events
+ADD COLUMN fee NUMERIC(16, 2) NOT NULL DEFAULT 0 CHECK (fee >= 0);
@@ -0,0 +1,12 @@
+-- TABLE: ledger
+-- Every change to a balance that isn't a trade, so users can see where their Kromer went
+CREATE TABLE ledger (
+  ledger_id BIGSERIAL PRIMARY KEY,
+  time TIMESTAMPTZ NOT NULL DEFAULT timezone ('utc', now ()),
+  user_id UUID NOT NULL REFERENCES users (user_id),
+  kind TEXT NOT NULL,
+  delta NUMERIC(16, 2) NOT NULL,
+  ticker VARCHAR(5) REFERENCES stocks (ticker)
+);
+
+CREATE INDEX idx_ledger_user ON ledger (user_id, ledger_id);
@@ -0,0 +1,6 @@
+-- The account that controls a stock, able to pay dividends and change its float. Stocks listed
+-- before ownership existed have none
+ALTER TABLE stocks
+ADD COLUMN owner_id UUID REFERENCES users (user_id);
+
+CREATE INDEX idx_stocks_owner ON stocks (owner_id);
@@ -0,0 +1,2 @@
+-- Looking up a stock's trades by time, for its latest price and its price at a point in the past
+CREATE INDEX idx_stock_events_ticker_time ON stock_events (ticker, time);
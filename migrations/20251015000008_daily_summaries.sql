@@ -0,0 +1,5 @@
+-- Days whose market summary has been posted, so a restart doesn't post one twice
+CREATE TABLE daily_summaries (
+  date DATE PRIMARY KEY,
+  sent_at TIMESTAMPTZ NOT NULL DEFAULT now ()
+);
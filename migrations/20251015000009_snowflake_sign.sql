@@ -0,0 +1,7 @@
+-- Discord snowflakes are unsigned 64 bit integers, stored bit for bit in disc_id. Those above the
+-- largest BIGINT come out negative, so only zero is invalid
+ALTER TABLE users
+DROP CONSTRAINT users_disc_id_check;
+
+ALTER TABLE users
+ADD CONSTRAINT users_disc_id_check CHECK (disc_id <> 0);
@@ -0,0 +1,7 @@
+-- A trade of zero shares records a reference price without anything changing hands, such as the
+-- price a stock is listed at before it first trades
+ALTER TABLE stock_events
+DROP CONSTRAINT stock_events_shares_check;
+
+ALTER TABLE stock_events
+ADD CONSTRAINT stock_events_shares_check CHECK (shares >= 0);
@@ -0,0 +1,3 @@
+-- Whether a user lets others show their portfolio in public replies
+ALTER TABLE users
+ADD COLUMN public_portfolio BOOLEAN NOT NULL DEFAULT FALSE;
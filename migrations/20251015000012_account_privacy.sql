@@ -0,0 +1,5 @@
+-- Who other than the owner may see an account's balance and holdings
+ALTER TABLE users
+ADD COLUMN privacy TEXT NOT NULL DEFAULT 'holdings_only' CHECK (
+  privacy IN ('public', 'holdings_only', 'private')
+);
@@ -0,0 +1,16 @@
+-- TABLE: withdrawal_requests
+-- Kromer users asked to have sent out of the exchange. The amount leaves the balance when the
+-- request is made, so pending requests are the holds on it, and denying one gives it back
+CREATE TABLE withdrawal_requests (
+  withdrawal_id BIGSERIAL PRIMARY KEY,
+  user_id UUID NOT NULL REFERENCES users (user_id),
+  amount NUMERIC(16, 2) NOT NULL CHECK (amount > 0),
+  address CHAR(10) NOT NULL,
+  status TEXT NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'processed', 'denied')),
+  requested_at TIMESTAMPTZ NOT NULL DEFAULT timezone ('utc', now ()),
+  resolved_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_withdrawals_pending ON withdrawal_requests (withdrawal_id)
+WHERE
+  status = 'pending';
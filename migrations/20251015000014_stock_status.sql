@@ -0,0 +1,4 @@
+-- Whether a stock can be traded. Halted and delisted stocks keep their resting orders, which
+-- can't match until trading resumes
+ALTER TABLE stocks
+ADD COLUMN status TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'halted', 'delisted'));
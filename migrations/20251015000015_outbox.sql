@@ -0,0 +1,19 @@
+-- TABLE: outbox
+-- Notifications written in the same transaction as the change they describe, so they survive a
+-- crash before being delivered. Entries are retried with backoff until sent, or parked once they
+-- have failed too many times
+CREATE TABLE outbox (
+  outbox_id BIGSERIAL PRIMARY KEY,
+  payload JSONB NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT timezone ('utc', now ()),
+  attempts INTEGER NOT NULL DEFAULT 0 CHECK (attempts >= 0),
+  next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT timezone ('utc', now ()),
+  last_error TEXT,
+  sent_at TIMESTAMPTZ,
+  parked_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_outbox_due ON outbox (next_attempt_at)
+WHERE
+  sent_at IS NULL
+  AND parked_at IS NULL;
@@ -0,0 +1,6 @@
+-- Details the owner of a stock can set so players know what it is. Rendered in place of the bare
+-- ticker wherever they are set
+ALTER TABLE stocks
+ADD COLUMN name VARCHAR(64),
+ADD COLUMN description VARCHAR(500),
+ADD COLUMN icon_url TEXT CHECK (icon_url LIKE 'https://%');
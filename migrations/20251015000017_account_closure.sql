@@ -0,0 +1,11 @@
+-- Closed accounts keep their row so old trades and ledger entries still point somewhere, but lose
+-- their links so the same player can register again
+ALTER TABLE users
+ADD COLUMN closed_at TIMESTAMPTZ,
+DROP CONSTRAINT users_check,
+ADD CONSTRAINT users_check CHECK (
+  disc_id IS NOT NULL
+  OR mc_id IS NOT NULL
+  OR system
+  OR closed_at IS NOT NULL
+);
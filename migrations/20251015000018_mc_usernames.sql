@@ -0,0 +1,8 @@
+-- Minecraft usernames resolved through Mojang, so players can be named instead of given by UUID.
+-- Usernames change hands, so entries are only trusted for a while after they were resolved
+CREATE TABLE mc_usernames (
+  -- Lowercase, as usernames are case insensitive
+  username TEXT PRIMARY KEY CHECK (username = lower(username)),
+  mc_id UUID NOT NULL,
+  resolved_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
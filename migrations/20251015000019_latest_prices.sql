@@ -0,0 +1,8 @@
+-- The most recent trade of each stock, kept up to date alongside every trade so reads don't have to
+-- search stock_events for it. The event ID breaks ties between trades made at the same time
+CREATE TABLE latest_prices (
+  ticker VARCHAR(5) PRIMARY KEY REFERENCES stocks (ticker),
+  price NUMERIC(16, 2) NOT NULL CHECK (price > 0),
+  updated_at TIMESTAMPTZ NOT NULL,
+  event_id INTEGER NOT NULL REFERENCES stock_events (event_id)
+);
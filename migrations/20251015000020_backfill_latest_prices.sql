@@ -0,0 +1,6 @@
+-- Stocks that traded before latest_prices was kept
+INSERT INTO latest_prices (ticker, price, updated_at, event_id)
+SELECT DISTINCT ON (ticker) ticker, price, time, event_id
+FROM stock_events
+ORDER BY ticker, time DESC, event_id DESC
+ON CONFLICT (ticker) DO NOTHING;
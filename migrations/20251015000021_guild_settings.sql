@@ -0,0 +1,10 @@
+-- What each Discord server changed about how the bot behaves in it. Servers without a row use the
+-- defaults: trading enabled, and nothing else set
+CREATE TABLE guild_settings (
+  guild_id BIGINT PRIMARY KEY CHECK (guild_id <> 0),
+  market_feed_channel BIGINT CHECK (market_feed_channel <> 0),
+  trading_enabled BOOLEAN NOT NULL DEFAULT TRUE,
+  locale TEXT,
+  admin_role BIGINT CHECK (admin_role <> 0),
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,4 @@
+-- Looking up a user's trades, for their history and for reconciling their balance
+CREATE INDEX idx_stock_events_seller ON stock_events (seller_id);
+
+CREATE INDEX idx_stock_events_buyer ON stock_events (buyer_id);
@@ -0,0 +1,11 @@
+-- Every finished bot command invocation, written in batches by the bot
+CREATE TABLE command_stats (
+  id BIGSERIAL PRIMARY KEY,
+  command TEXT NOT NULL,
+  guild_id BIGINT CHECK (guild_id <> 0),
+  success BOOLEAN NOT NULL,
+  duration_ms BIGINT NOT NULL CHECK (duration_ms >= 0),
+  time TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX command_stats_time ON command_stats (time);
@@ -0,0 +1,13 @@
+-- Keys clients sent with requests that change something, and what each request returned, so a
+-- retried request is replayed instead of carried out again. A key is claimed before its request
+-- is carried out, in the same transaction, so the response is only NULL until that commits
+CREATE TABLE idempotency_keys (
+  user_id UUID NOT NULL,
+  key TEXT NOT NULL,
+  request_hash BYTEA NOT NULL,
+  response JSONB,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  PRIMARY KEY (user_id, key)
+);
+
+CREATE INDEX idempotency_keys_created_at ON idempotency_keys (created_at);
@@ -0,0 +1,9 @@
+-- The permissions a Discord server granted to each of its roles, one row per role and permission
+CREATE TABLE guild_role_permissions (
+  guild_id BIGINT NOT NULL REFERENCES guild_settings (guild_id) ON DELETE CASCADE,
+  role_id BIGINT NOT NULL CHECK (role_id <> 0),
+  permission TEXT NOT NULL CHECK (
+    permission IN ('view_admin', 'manage_stocks', 'manage_balances', 'halt')
+  ),
+  PRIMARY KEY (guild_id, role_id, permission)
+);
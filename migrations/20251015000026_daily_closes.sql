@@ -0,0 +1,16 @@
+-- Each stock's last price and the shares traded on each UTC day, written nightly. Price changes
+-- are measured from the latest close, so they don't drift as a rolling window would
+CREATE TABLE daily_closes (
+  ticker VARCHAR(5) NOT NULL REFERENCES stocks (ticker),
+  date DATE NOT NULL,
+  close NUMERIC(16, 2) NOT NULL CHECK (close > 0),
+  volume BIGINT NOT NULL CHECK (volume >= 0),
+  PRIMARY KEY (ticker, date)
+);
+
+-- Backfill every finished day something traded on from the price history
+INSERT INTO daily_closes (ticker, date, close, volume)
+SELECT DISTINCT ON (ticker, day) ticker, day, price, SUM(shares) OVER (PARTITION BY ticker, day)
+FROM (SELECT *, (time AT TIME ZONE 'UTC')::DATE AS day FROM stock_events) events
+WHERE day < (now() AT TIME ZONE 'UTC')::DATE
+ORDER BY ticker, day, time DESC, event_id DESC;
@@ -0,0 +1,11 @@
+-- What each account may spend right now, and what is on hold: escrowed by its open buy orders, or
+-- requested to be withdrawn and waiting on an admin. Read in the same statement as the rest of an
+-- account, so the two always add up to what it owns
+CREATE VIEW account_balances AS
+SELECT users.user_id, users.balance AS available,
+  users.escrow + COALESCE(pending.amount, 0) AS held
+FROM users
+LEFT JOIN (
+  SELECT user_id, SUM(amount) AS amount FROM withdrawal_requests
+  WHERE status = 'pending' GROUP BY user_id
+) pending ON pending.user_id = users.user_id;
@@ -0,0 +1,9 @@
+-- Prices imported from a market's own records, from before it moved onto the exchange. They are
+-- kept as price-only events, as listing prices are, alongside the volume the market reported
+ALTER TABLE stock_events
+ADD COLUMN imported_volume BIGINT CHECK (imported_volume >= 0);
+
+-- A stock has at most one imported price at any time, so an import can be run again without
+-- adding anything twice
+CREATE UNIQUE INDEX idx_stock_events_imported ON stock_events (ticker, time)
+WHERE imported_volume IS NOT NULL;
@@ -0,0 +1,39 @@
+-- Corrections admins make to balances by hand, each with a reason and the ledger entry it made. A
+-- reversal points back at the adjustment it undoes, which can only happen once
+CREATE TABLE balance_adjustments (
+  adjustment_id BIGSERIAL PRIMARY KEY,
+  time TIMESTAMPTZ NOT NULL DEFAULT timezone ('utc', now ()),
+  user_id UUID NOT NULL REFERENCES users (user_id),
+  delta NUMERIC(16, 2) NOT NULL CHECK (delta <> 0),
+  reason TEXT NOT NULL CHECK (
+    reason IN ('refund', 'correction', 'prize', 'penalty')
+  ),
+  actor TEXT NOT NULL,
+  ledger_id BIGINT NOT NULL UNIQUE REFERENCES ledger (ledger_id),
+  reverses BIGINT UNIQUE REFERENCES balance_adjustments (adjustment_id)
+);
+
+CREATE INDEX idx_balance_adjustments_user ON balance_adjustments (user_id);
+
+-- Balances only go below zero when an adjustment made by an admin allowed to overdraw accounts
+-- flags its transaction with `rse.allow_overdraw`. Anything else taking Kromer out of an account
+-- must leave it at zero or above, so an overdrawn account can only be paid into. Raised as a check
+-- violation, as the constraint this replaces was
+ALTER TABLE users
+DROP CONSTRAINT users_balance_check;
+
+CREATE FUNCTION check_balance () RETURNS TRIGGER AS $$
+BEGIN
+  IF NEW.balance < 0
+    AND (TG_OP = 'INSERT' OR NEW.balance < OLD.balance)
+    AND current_setting('rse.allow_overdraw', TRUE) IS DISTINCT FROM 'on' THEN
+    RAISE check_violation USING MESSAGE = 'balance can''t be overdrawn';
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_balance_check BEFORE INSERT
+OR
+UPDATE OF balance ON users FOR EACH ROW
+EXECUTE FUNCTION check_balance ();
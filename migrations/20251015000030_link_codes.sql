@@ -0,0 +1,13 @@
+-- One-time codes an account is given to prove it controls another identity before linking it.
+-- Used codes are kept, marked as used, until they expire and are purged
+CREATE TABLE link_codes (
+  code CHAR(6) PRIMARY KEY,
+  user_id UUID NOT NULL REFERENCES users (user_id),
+  created_at TIMESTAMPTZ NOT NULL DEFAULT timezone ('utc', now ()),
+  expires_at TIMESTAMPTZ NOT NULL,
+  used_at TIMESTAMPTZ
+);
+
+CREATE INDEX idx_link_codes_user ON link_codes (user_id);
+
+CREATE INDEX idx_link_codes_expiry ON link_codes (expires_at);
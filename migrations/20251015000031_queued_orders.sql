@@ -0,0 +1,24 @@
+-- Orders placed while the market is closed may be queued, escrowed but kept off the book until it
+-- opens. They can still be cancelled or expire like open orders
+ALTER TABLE orders
+DROP CONSTRAINT orders_status_check,
+ADD CONSTRAINT orders_status_check CHECK (
+  status IN ('queued', 'open', 'filled', 'cancelled', 'expired')
+);
+
+DROP INDEX idx_orders_user;
+
+CREATE INDEX idx_orders_user ON orders (user_id)
+WHERE
+  status IN ('queued', 'open');
+
+DROP INDEX idx_orders_expiry;
+
+CREATE INDEX idx_orders_expiry ON orders (expires_at)
+WHERE
+  status IN ('queued', 'open')
+  AND expires_at IS NOT NULL;
+
+CREATE INDEX idx_orders_queued ON orders (order_id)
+WHERE
+  status = 'queued';
@@ -0,0 +1,11 @@
+-- Whether a user is sent a receipt when their orders fill
+ALTER TABLE users
+ADD COLUMN receipts BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Receipts for the same order wait in the outbox for a few seconds, so later fills can be added to
+-- them and sent as one. The key is cleared once an entry is claimed, so fills after that start a
+-- new entry rather than changing one being delivered
+ALTER TABLE outbox
+ADD COLUMN coalesce_key TEXT;
+
+CREATE UNIQUE INDEX idx_outbox_coalesce ON outbox (coalesce_key);
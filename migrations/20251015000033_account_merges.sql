@@ -0,0 +1,3 @@
+-- The account a ledger entry was first made on, when it was moved onto another by merging the two
+ALTER TABLE ledger
+ADD COLUMN merged_from UUID REFERENCES users (user_id);
@@ -0,0 +1,9 @@
+-- Each open account's cash and the value of its holdings on each UTC day, written nightly so users
+-- can see how their net worth has changed. Days the exchange was down simply have no row
+CREATE TABLE portfolio_snapshots (
+  user_id UUID NOT NULL REFERENCES users (user_id),
+  date DATE NOT NULL,
+  balance NUMERIC(16, 2) NOT NULL CHECK (balance >= 0),
+  holdings_value NUMERIC NOT NULL CHECK (holdings_value >= 0),
+  PRIMARY KEY (user_id, date)
+);
@@ -0,0 +1,6 @@
+-- Lets stocks be found by a rough guess at their name or description, such as "melon" for a melon
+-- farm, as well as by part of their ticker
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX stocks_name_trgm ON stocks USING GIN (name gin_trgm_ops);
+CREATE INDEX stocks_description_trgm ON stocks USING GIN (description gin_trgm_ops);
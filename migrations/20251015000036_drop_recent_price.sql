@@ -0,0 +1,4 @@
+-- Prices come from the last match, kept in latest_prices. This was never written after the order
+-- book replaced it
+ALTER TABLE stocks
+DROP COLUMN recent_price;
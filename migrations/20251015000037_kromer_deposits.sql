@@ -0,0 +1,12 @@
+-- Kromer sent to the exchange's wallet and credited to an account, keyed by the Kromer transaction
+-- so a transfer is only ever credited once
+CREATE TABLE kromer_deposits (
+  transaction_id BIGINT PRIMARY KEY,
+  time TIMESTAMPTZ NOT NULL,
+  user_id UUID NOT NULL REFERENCES users (user_id),
+  amount NUMERIC(16, 2) NOT NULL CHECK (amount > 0),
+  from_address VARCHAR(10) NOT NULL,
+  ledger_id BIGINT NOT NULL UNIQUE REFERENCES ledger (ledger_id)
+);
+
+CREATE INDEX idx_kromer_deposits_user ON kromer_deposits (user_id);
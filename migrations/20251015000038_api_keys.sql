@@ -0,0 +1,7 @@
+-- The key each account places orders through the REST API with. Only a hash of the key is kept, so
+-- the keys can't be read back out of the database
+CREATE TABLE api_keys (
+  user_id UUID PRIMARY KEY REFERENCES users (user_id),
+  key_hash BYTEA NOT NULL UNIQUE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT timezone ('utc', now ())
+);
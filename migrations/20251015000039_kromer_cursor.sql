@@ -0,0 +1,6 @@
+-- The newest transaction of each watched wallet already scanned for deposits, whether or not it was
+-- one, so a restart picks up where the last run left off instead of skipping what came in between
+CREATE TABLE kromer_cursor (
+  address VARCHAR(10) PRIMARY KEY,
+  transaction_id BIGINT NOT NULL
+);
@@ -0,0 +1,17 @@
+-- Approved withdrawals are paid out from the exchange's wallet. A payout is claimed before it is
+-- sent and never claimed again, so a crash mid-send leaves it for an admin rather than paying twice
+ALTER TABLE withdrawal_requests
+ADD COLUMN payout_claimed_at TIMESTAMPTZ,
+ADD COLUMN payout_transaction BIGINT;
+
+-- Withdrawals approved before payouts existed were sent out by hand
+UPDATE withdrawal_requests
+SET
+  payout_claimed_at = resolved_at
+WHERE
+  status = 'processed';
+
+CREATE INDEX idx_withdrawals_unpaid ON withdrawal_requests (withdrawal_id)
+WHERE
+  status = 'processed'
+  AND payout_claimed_at IS NULL;
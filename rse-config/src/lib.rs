@@ -0,0 +1,1683 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Typed configuration for the `RSE` server. Values are read from a TOML file, whose path is given
+//! by the `RSE_CONFIG` environment variable, and can then be overridden by environment variables.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    net::SocketAddr,
+    num::{NonZeroU16, NonZeroU32, NonZeroU64},
+    path::PathBuf,
+    str::FromStr,
+    time::Duration,
+};
+
+use chrono::{NaiveDate, NaiveTime, Weekday};
+use serde::Deserialize;
+use snafu::{ResultExt, Snafu};
+use uuid::Uuid;
+
+/// Environment variable holding the path of the TOML config file
+pub const CONFIG_VAR: &str = "RSE_CONFIG";
+
+const DEFAULT_HTTP_BIND: &str = "0.0.0.0:8080";
+const DEFAULT_API_BIND: &str = "0.0.0.0:8081";
+const DEFAULT_KROMER_NODE_URL: &str = "https://kromer.reconnected.cc/api/krist";
+const DEFAULT_KROMER_POLL_INTERVAL_SECS: NonZeroU64 = NonZeroU64::new(30).expect("Non zero");
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+/// A fee of 100%
+const MAX_FEE_BPS: u16 = 10_000;
+const DEFAULT_DAILY_ISSUANCE_CAP_PCT: u16 = 10;
+const DEFAULT_RETRY_MAX_ATTEMPTS: NonZeroU32 = NonZeroU32::new(3).expect("Non zero");
+const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 50;
+const DEFAULT_POOL_MAX_CONNECTIONS: NonZeroU32 = NonZeroU32::new(10).expect("Non zero");
+const DEFAULT_POOL_ACQUIRE_TIMEOUT_SECS: NonZeroU64 = NonZeroU64::new(5).expect("Non zero");
+const DEFAULT_POOL_IDLE_TIMEOUT_SECS: NonZeroU64 = NonZeroU64::new(600).expect("Non zero");
+const DEFAULT_POOL_STATEMENT_TIMEOUT_MS: NonZeroU64 = NonZeroU64::new(30_000).expect("Non zero");
+const DEFAULT_TRADES_PER_MINUTE: NonZeroU32 = NonZeroU32::new(30).expect("Non zero");
+const DEFAULT_LOOKUPS_PER_MINUTE: NonZeroU32 = NonZeroU32::new(60).expect("Non zero");
+const DEFAULT_REGISTRATIONS_PER_HOUR: NonZeroU32 = NonZeroU32::new(5).expect("Non zero");
+const DEFAULT_NEW_ACCOUNT_ORDER_CAP: u64 = 100;
+const DEFAULT_PRICE_BAND_LOOKBACK_SECS: NonZeroU64 = NonZeroU64::new(86_400).expect("Non zero");
+const DEFAULT_ORDER_SWEEP_INTERVAL_SECS: NonZeroU64 = NonZeroU64::new(60).expect("Non zero");
+const DEFAULT_RECONCILE_INTERVAL_SECS: NonZeroU64 = NonZeroU64::new(21_600).expect("Non zero");
+const DEFAULT_TRADE_BATCH_WINDOW_SECS: NonZeroU64 = NonZeroU64::new(10).expect("Non zero");
+const DEFAULT_TRADE_BATCH_SIZE: NonZeroU32 = NonZeroU32::new(10).expect("Non zero");
+const DEFAULT_SLOW_QUERY_MS: u64 = 250;
+const DEFAULT_SLOW_COMMAND_MS: u64 = 1000;
+const DEFAULT_IMPORT_MAX_ROWS: NonZeroU32 = NonZeroU32::new(50_000).expect("Non zero");
+const DEFAULT_SNAPSHOT_DIR: &str = "snapshots";
+const DEFAULT_ACTIVITY: &str = "Watching the markets 📈";
+const DEFAULT_CURRENCY: &str = "KRO";
+const DEFAULT_TRADING_DAYS: [Weekday; 5] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+];
+/// Per-user cooldowns, in seconds, applied to commands unless overridden. Listing commands are
+/// cheap but paginate, trades are not
+const DEFAULT_COOLDOWN_SECS: [(&str, u64); 10] = [
+    ("stocks", 2),
+    ("me", 2),
+    ("portfolio", 2),
+    ("orderbook", 2),
+    ("top", 2),
+    ("order list", 2),
+    ("order place", 5),
+    ("order cancel", 5),
+    ("buy", 5),
+    ("sell", 5),
+];
+
+/// Errors thrown while loading a [`Config`]
+#[derive(Debug, Snafu)]
+#[allow(missing_docs, variant_size_differences)]
+pub enum Error {
+    /// The config file could not be read
+    #[snafu(display("Could not read config file {}", path.display()))]
+    Read {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// The config file is not valid TOML, or does not match the expected layout
+    #[snafu(display("Could not parse config file {}", path.display()))]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// One or more fields are missing or invalid. Contains every problem found, not just the first
+    #[snafu(display("Invalid configuration:{}", list_problems(problems)))]
+    Invalid { problems: Vec<Problem> },
+}
+
+/// A single missing or invalid configuration field
+#[derive(Debug, Clone)]
+pub struct Problem {
+    /// The path of the offending field, e.g. `discord.token`
+    pub field: &'static str,
+    /// Why the field was rejected
+    pub reason: String,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "`{}`: {}", self.field, self.reason)
+    }
+}
+
+fn list_problems(problems: &[Problem]) -> String {
+    let mut buff = String::new();
+
+    for problem in problems {
+        write!(buff, "\n  - {problem}").expect("Never fails");
+    }
+
+    buff
+}
+
+/// Configuration for the whole server
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// The `Postgres` connection URL. Overridden by `DATABASE_URL`
+    pub database_url: String,
+    /// Settings for the Discord bot
+    pub discord: DiscordConfig,
+    /// Settings for the HTTP server, which answers health checks on `/healthz` and `/readyz`
+    pub http: HttpConfig,
+    /// Settings for the REST API used by in-game terminals
+    pub api: ApiConfig,
+    /// Settings for taking deposits of Kromer
+    pub kromer: KromerConfig,
+    /// Settings for trading on the exchange
+    pub trading: TradingConfig,
+    /// How reads from the database are retried when it is briefly unavailable
+    pub retry: RetryConfig,
+    /// How connections to the database are pooled
+    pub pool: PoolConfig,
+    /// How often each account may use the exchange
+    pub rate_limits: RateLimitConfig,
+    /// Which subsystems to start
+    pub features: Features,
+    /// How long to wait for background tasks to finish on shutdown before forcing an exit.
+    /// Defaults to 30 seconds, overridden by `RSE_SHUTDOWN_TIMEOUT_SECS`
+    pub shutdown_timeout: Duration,
+    /// How often expired orders are swept off the book. Defaults to 60 seconds, overridden by
+    /// `RSE_ORDER_SWEEP_INTERVAL_SECS`
+    pub order_sweep_interval: Duration,
+    /// How often the books are reconciled. Defaults to 6 hours, overridden by
+    /// `RSE_RECONCILE_INTERVAL_SECS`
+    pub reconcile_interval: Duration,
+    /// How long a database query may take before it is logged as slow. Defaults to 250
+    /// milliseconds, overridden by `RSE_SLOW_QUERY_MS`
+    pub slow_query: Duration,
+}
+
+/// Settings for the Discord bot
+#[derive(Clone)]
+pub struct DiscordConfig {
+    /// The bot token. Overridden by `DISCORD_TOKEN`
+    pub token: String,
+    /// Guilds to register commands in. Commands are registered globally when empty. Overridden by
+    /// a comma separated `RSE_DISCORD_GUILD_IDS`
+    pub guild_ids: Vec<NonZeroU64>,
+    /// Discord users allowed to run privileged commands anywhere, on top of those with each
+    /// server's admin role. Overridden by a comma separated `RSE_DISCORD_ADMIN_IDS`
+    pub admin_ids: Vec<NonZeroU64>,
+    /// A channel market-wide announcements, such as dividends, are posted to, as well as each
+    /// server's own market feed. Overridden by `RSE_DISCORD_MARKET_FEED_CHANNEL`
+    pub market_feed_channel: Option<NonZeroU64>,
+    /// A private channel for admins, where problems such as books that don't reconcile are
+    /// posted. Nothing is posted when unset, overridden by `RSE_DISCORD_ADMIN_CHANNEL`
+    pub admin_channel: Option<NonZeroU64>,
+    /// The hour of the day, in UTC, the previous day's market summary is posted to the market
+    /// feed channel. Defaults to 0, overridden by `RSE_DISCORD_DAILY_SUMMARY_HOUR`
+    pub daily_summary_hour: u8,
+    /// How long trades are collected before a summary of them is posted to the market feed
+    /// channel. Defaults to 10 seconds, overridden by `RSE_DISCORD_TRADE_BATCH_WINDOW_SECS`
+    pub trade_batch_window: Duration,
+    /// How many trades are collected before a summary of them is posted early. Defaults to 10,
+    /// overridden by `RSE_DISCORD_TRADE_BATCH_SIZE`
+    pub trade_batch_size: NonZeroU32,
+    /// The value, in Kromer, at which a trade is posted to the market feed channel on its own
+    /// instead of being batched. Every trade is batched when unset, overridden by
+    /// `RSE_DISCORD_LARGE_TRADE_VALUE`
+    pub large_trade_value: Option<NonZeroU64>,
+    /// How long each user must wait between uses of a command, keyed by the command's full name,
+    /// e.g. `order place`. Commands without an entry have no cooldown, and admins are exempt.
+    /// Configured values are merged over the defaults, with 0 removing a default. Overridden by a
+    /// comma separated `RSE_DISCORD_COOLDOWNS` of `name=seconds` pairs
+    pub cooldowns: BTreeMap<String, Duration>,
+    /// How long a command may take before it is logged as slow. Defaults to a second, overridden
+    /// by `RSE_DISCORD_SLOW_COMMAND_MS`
+    pub slow_command: Duration,
+    /// The most rows a price history file imported with `/admin import-prices` may have. Defaults
+    /// to 50,000, overridden by `RSE_DISCORD_IMPORT_MAX_ROWS`
+    pub import_max_rows: NonZeroU32,
+    /// Where snapshots taken with `/admin snapshot` are written when too large to attach. Defaults
+    /// to `snapshots`, overridden by `RSE_DISCORD_SNAPSHOT_DIR`
+    pub snapshot_dir: PathBuf,
+    /// The activity shown on the bot's profile, such as `Watching the markets`. Starting it with
+    /// `Playing`, `Watching`, `Listening to` or `Competing in` picks the kind of activity, anything
+    /// else is shown as a custom status. Defaults to `Watching the markets 📈`, with an empty
+    /// string showing none. Overridden by `RSE_DISCORD_ACTIVITY`
+    pub activity: Option<String>,
+    /// The online status shown on the bot's profile. Defaults to online, overridden by
+    /// `RSE_DISCORD_STATUS`
+    pub status: BotStatus,
+    /// Whether users are sent a receipt by DM when their orders fill, unless they opted out.
+    /// Defaults to true, overridden by `RSE_DISCORD_RECEIPTS`
+    pub receipts: bool,
+    /// The suffix amounts of Kromer are shown with, such as `12.50 KRO`. Defaults to `KRO`, with an
+    /// empty string showing none. Overridden by `RSE_DISCORD_CURRENCY`
+    pub currency: String,
+}
+
+/// The online status the bot shows
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BotStatus {
+    /// Shown as online
+    #[default]
+    Online,
+    /// Shown as away
+    Idle,
+    /// Shown as do not disturb
+    #[serde(rename = "dnd")]
+    DoNotDisturb,
+    /// Shown as offline, while still connected
+    Invisible,
+}
+
+impl FromStr for BotStatus {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "online" => Ok(Self::Online),
+            "idle" => Ok(Self::Idle),
+            "dnd" => Ok(Self::DoNotDisturb),
+            "invisible" => Ok(Self::Invisible),
+            _ => Err("expected one of `online`, `idle`, `dnd` or `invisible`".to_owned()),
+        }
+    }
+}
+
+impl std::fmt::Debug for DiscordConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DiscordConfig")
+            .field("token", &"<redacted>")
+            .field("guild_ids", &self.guild_ids)
+            .field("admin_ids", &self.admin_ids)
+            .field("market_feed_channel", &self.market_feed_channel)
+            .field("admin_channel", &self.admin_channel)
+            .field("daily_summary_hour", &self.daily_summary_hour)
+            .field("trade_batch_window", &self.trade_batch_window)
+            .field("trade_batch_size", &self.trade_batch_size)
+            .field("large_trade_value", &self.large_trade_value)
+            .field("cooldowns", &self.cooldowns)
+            .field("slow_command", &self.slow_command)
+            .field("import_max_rows", &self.import_max_rows)
+            .field("snapshot_dir", &self.snapshot_dir)
+            .field("activity", &self.activity)
+            .field("status", &self.status)
+            .field("receipts", &self.receipts)
+            .field("currency", &self.currency)
+            .finish()
+    }
+}
+
+/// Settings for the HTTP server, which answers health checks on `/healthz` and `/readyz`
+#[derive(Debug, Clone, Copy)]
+pub struct HttpConfig {
+    /// The address to listen on. Overridden by `RSE_HTTP_BIND`
+    pub bind: SocketAddr,
+}
+
+/// Settings for the REST API used by in-game terminals
+#[derive(Debug, Clone, Copy)]
+pub struct ApiConfig {
+    /// The address to listen on. Defaults to `0.0.0.0:8081`, overridden by `RSE_API_BIND`
+    pub bind: SocketAddr,
+}
+
+/// Settings for taking deposits of Kromer, sent to a metaname of the exchange's name for each
+/// account, and paying out approved withdrawals
+#[derive(Clone)]
+pub struct KromerConfig {
+    /// The Kromer node's Krist API. Defaults to `https://kromer.reconnected.cc/api/krist`,
+    /// overridden by `RSE_KROMER_NODE_URL`
+    pub node_url: String,
+    /// The exchange's wallet, which deposits are sent to. Required when deposits are taken,
+    /// overridden by `RSE_KROMER_ADDRESS`
+    pub address: String,
+    /// The name the wallet owns, without the `.kro`, which deposits are sent to metanames of.
+    /// Required when deposits are taken, overridden by `RSE_KROMER_NAME`
+    pub name: String,
+    /// How often the wallet is checked for new deposits. Defaults to 30 seconds, overridden by
+    /// `RSE_KROMER_POLL_INTERVAL_SECS`
+    pub poll_interval: Duration,
+    /// The wallet's private key, which approved withdrawals are paid out with. Withdrawals are
+    /// left for admins to send by hand when unset, overridden by `RSE_KROMER_PRIVATE_KEY`
+    pub private_key: Option<String>,
+}
+
+impl std::fmt::Debug for KromerConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KromerConfig")
+            .field("node_url", &self.node_url)
+            .field("address", &self.address)
+            .field("name", &self.name)
+            .field("poll_interval", &self.poll_interval)
+            .field(
+                "private_key",
+                &self.private_key.as_ref().map(|_| "<redacted>"),
+            )
+            .finish()
+    }
+}
+
+/// Settings for trading on the exchange
+#[derive(Debug, Clone)]
+pub struct TradingConfig {
+    /// The fee charged to buyers on every trade, in basis points of its value. Defaults to 0,
+    /// overridden by `RSE_TRADING_FEE_BPS`
+    pub fee_bps: u16,
+    /// The account fees are credited to, created on startup if missing. Required when
+    /// `fee_bps` is not 0, overridden by `RSE_TRADING_TREASURY_ACCOUNT`
+    pub treasury_account: Option<Uuid>,
+    /// The most shares an owner may issue per day, as a percentage of the shares outstanding.
+    /// Defaults to 10, overridden by `RSE_TRADING_DAILY_ISSUANCE_CAP_PCT`
+    pub daily_issuance_cap_pct: u16,
+    /// How far, in percent, an order's price may be from the stock's reference price. Admins are
+    /// exempt. No band is enforced when unset, overridden by `RSE_TRADING_PRICE_BAND_PCT`
+    pub price_band_pct: Option<NonZeroU16>,
+    /// How recent a stock's last trade must be to be its reference price, before falling back to
+    /// the previous day's close. Defaults to a day, overridden by
+    /// `RSE_TRADING_PRICE_BAND_LOOKBACK_SECS`
+    pub price_band_lookback: Duration,
+    /// How many hours old an account has to be before it may place orders worth more than
+    /// `new_account_order_cap` or list stocks. Admins are exempt. New accounts aren't held back
+    /// when unset, overridden by `RSE_TRADING_NEW_ACCOUNT_HOURS`
+    pub new_account_hours: Option<NonZeroU32>,
+    /// The most a single order from an account younger than `new_account_hours` may be worth, in
+    /// whole Kromer. Defaults to 100, overridden by `RSE_TRADING_NEW_ACCOUNT_ORDER_CAP`
+    pub new_account_order_cap: u64,
+    /// Words new stocks may not be listed under, anywhere in their ticker and regardless of case
+    /// or simple leetspeak. Added to the built-in reserved tickers, overridden by
+    /// `RSE_TRADING_BLOCKED_TICKERS`
+    pub blocked_tickers: Vec<String>,
+    /// When the market is open for trading. The market never closes when unset
+    pub hours: Option<MarketHours>,
+}
+
+/// When the market is open for trading, in UTC. Orders placed while it is closed are rejected, or
+/// queued until it opens
+#[derive(Debug, Clone)]
+pub struct MarketHours {
+    /// The days of the week the market opens on. Defaults to Monday to Friday, overridden by a
+    /// comma separated `RSE_TRADING_HOURS_DAYS`
+    pub days: Vec<Weekday>,
+    /// The time the market opens each trading day. Required along with `close`, overridden by
+    /// `RSE_TRADING_HOURS_OPEN`
+    pub open: NaiveTime,
+    /// The time the market closes each trading day, after it opens. Required along with `open`,
+    /// overridden by `RSE_TRADING_HOURS_CLOSE`
+    pub close: NaiveTime,
+    /// Dates the market stays closed all day. Overridden by a comma separated
+    /// `RSE_TRADING_HOURS_HOLIDAYS`
+    pub holidays: Vec<NaiveDate>,
+    /// Queue orders placed while the market is closed, matching them once it opens, rather than
+    /// rejecting them. Defaults to `false`, overridden by `RSE_TRADING_HOURS_QUEUE_ORDERS`
+    pub queue_orders: bool,
+}
+
+/// Settings for retrying reads from the database when it is briefly unavailable. Writes are never
+/// retried.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryConfig {
+    /// How many times a read is tried in total before giving up. Defaults to 3, overridden by
+    /// `RSE_RETRY_MAX_ATTEMPTS`
+    pub max_attempts: NonZeroU32,
+    /// The delay before the first retry, doubling with each one after. Defaults to 50
+    /// milliseconds, overridden by `RSE_RETRY_BASE_DELAY_MS`
+    pub base_delay: Duration,
+}
+
+/// Settings for the pool of connections to the database
+#[derive(Debug, Clone, Copy)]
+pub struct PoolConfig {
+    /// The most connections open at once. Defaults to 10, overridden by
+    /// `RSE_POOL_MAX_CONNECTIONS`
+    pub max_connections: NonZeroU32,
+    /// How many connections are kept open even when idle. Defaults to 0, overridden by
+    /// `RSE_POOL_MIN_CONNECTIONS`
+    pub min_connections: u32,
+    /// How long a query waits for a free connection before failing. Defaults to 5 seconds,
+    /// overridden by `RSE_POOL_ACQUIRE_TIMEOUT_SECS`
+    pub acquire_timeout: Duration,
+    /// How long a connection may sit idle before it is closed, down to `min_connections`. Defaults
+    /// to 10 minutes, overridden by `RSE_POOL_IDLE_TIMEOUT_SECS`
+    pub idle_timeout: Duration,
+    /// How long the database lets a single statement run before cancelling it. Defaults to 30
+    /// seconds, overridden by `RSE_POOL_STATEMENT_TIMEOUT_MS`
+    pub statement_timeout: Duration,
+}
+
+/// Per-account limits on how often the exchange may be used. Bursts up to each limit are allowed,
+/// after which it refills steadily over the period.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitConfig {
+    /// How many orders may be placed or cancelled a minute. Defaults to 30, overridden by
+    /// `RSE_RATE_LIMITS_TRADES_PER_MINUTE`
+    pub trades_per_minute: NonZeroU32,
+    /// How many times an account's orders, statement or history may be looked up a minute.
+    /// Defaults to 60, overridden by `RSE_RATE_LIMITS_LOOKUPS_PER_MINUTE`
+    pub lookups_per_minute: NonZeroU32,
+    /// How many accounts may be registered an hour. Defaults to 5, overridden by
+    /// `RSE_RATE_LIMITS_REGISTRATIONS_PER_HOUR`
+    pub registrations_per_hour: NonZeroU32,
+}
+
+/// Toggles for optional subsystems
+#[derive(Debug, Clone, Copy)]
+#[allow(clippy::struct_excessive_bools)] // Each starts a subsystem of its own
+pub struct Features {
+    /// Start the Discord bot. Defaults to `true`, overridden by `RSE_FEATURE_DISCORD`
+    pub discord: bool,
+    /// Start the HTTP server. Defaults to `false`, overridden by `RSE_FEATURE_HTTP`
+    pub http: bool,
+    /// Start the REST API. Defaults to `false`, overridden by `RSE_FEATURE_API`
+    pub api: bool,
+    /// Take deposits of Kromer. Defaults to `false`, overridden by `RSE_FEATURE_KROMER`
+    pub kromer: bool,
+}
+
+impl Config {
+    /// Loads the config from the file at `RSE_CONFIG` if set, then applies environment overrides.
+    ///
+    /// # Errors
+    /// * [`Read`](Error::Read) - `RSE_CONFIG` is set but the file could not be read
+    /// * [`Parse`](Error::Parse) - The file is not a valid config
+    /// * [`Invalid`](Error::Invalid) - A field is missing or invalid after applying overrides
+    pub fn load() -> Result<Self, Error> {
+        Self::load_from(std::env::var_os(CONFIG_VAR).map(PathBuf::from), &|key| {
+            std::env::var(key).ok()
+        })
+    }
+
+    /// Loads the config from the file at `path` if given, then applies overrides looked up by
+    /// `env`, so tests don't have to touch the real environment
+    fn load_from(path: Option<PathBuf>, env: &Env) -> Result<Self, Error> {
+        let mut raw = match path {
+            Some(path) => {
+                let contents = std::fs::read_to_string(&path).context(ReadSnafu { path: &path })?;
+                toml::from_str(&contents).context(ParseSnafu { path })?
+            }
+            None => RawConfig::default(),
+        };
+
+        let mut problems = Vec::new();
+        raw.apply_env(env, &mut problems);
+        raw.validate(problems)
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawConfig {
+    database_url: Option<String>,
+    shutdown_timeout_secs: Option<u64>,
+    order_sweep_interval_secs: Option<NonZeroU64>,
+    reconcile_interval_secs: Option<NonZeroU64>,
+    slow_query_ms: Option<u64>,
+    discord: RawDiscordConfig,
+    http: RawHttpConfig,
+    api: RawApiConfig,
+    kromer: RawKromerConfig,
+    trading: RawTradingConfig,
+    retry: RawRetryConfig,
+    pool: RawPoolConfig,
+    rate_limits: RawRateLimitConfig,
+    features: RawFeatures,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawDiscordConfig {
+    token: Option<String>,
+    guild_ids: Option<Vec<NonZeroU64>>,
+    admin_ids: Option<Vec<NonZeroU64>>,
+    market_feed_channel: Option<NonZeroU64>,
+    admin_channel: Option<NonZeroU64>,
+    daily_summary_hour: Option<u8>,
+    trade_batch_window_secs: Option<NonZeroU64>,
+    trade_batch_size: Option<NonZeroU32>,
+    large_trade_value: Option<NonZeroU64>,
+    cooldowns: Option<BTreeMap<String, u64>>,
+    slow_command_ms: Option<u64>,
+    import_max_rows: Option<NonZeroU32>,
+    snapshot_dir: Option<PathBuf>,
+    activity: Option<String>,
+    status: Option<BotStatus>,
+    receipts: Option<bool>,
+    currency: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawHttpConfig {
+    bind: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawApiConfig {
+    bind: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawKromerConfig {
+    node_url: Option<String>,
+    address: Option<String>,
+    name: Option<String>,
+    poll_interval_secs: Option<NonZeroU64>,
+    private_key: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawTradingConfig {
+    fee_bps: Option<u16>,
+    treasury_account: Option<Uuid>,
+    daily_issuance_cap_pct: Option<u16>,
+    price_band_pct: Option<NonZeroU16>,
+    price_band_lookback_secs: Option<NonZeroU64>,
+    new_account_hours: Option<NonZeroU32>,
+    new_account_order_cap: Option<u64>,
+    blocked_tickers: Option<Vec<String>>,
+    hours: RawMarketHours,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawMarketHours {
+    days: Option<Vec<Weekday>>,
+    open: Option<NaiveTime>,
+    close: Option<NaiveTime>,
+    holidays: Option<Vec<NaiveDate>>,
+    queue_orders: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawRetryConfig {
+    max_attempts: Option<NonZeroU32>,
+    base_delay_ms: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawPoolConfig {
+    max_connections: Option<NonZeroU32>,
+    min_connections: Option<u32>,
+    acquire_timeout_secs: Option<NonZeroU64>,
+    idle_timeout_secs: Option<NonZeroU64>,
+    statement_timeout_ms: Option<NonZeroU64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawRateLimitConfig {
+    trades_per_minute: Option<NonZeroU32>,
+    lookups_per_minute: Option<NonZeroU32>,
+    registrations_per_hour: Option<NonZeroU32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RawFeatures {
+    discord: Option<bool>,
+    http: Option<bool>,
+    api: Option<bool>,
+    kromer: Option<bool>,
+}
+
+impl RawConfig {
+    #[allow(clippy::too_many_lines)]
+    fn apply_env(&mut self, env: &Env, problems: &mut Vec<Problem>) {
+        env_override(
+            env,
+            "DATABASE_URL",
+            "database_url",
+            &mut self.database_url,
+            problems,
+            |v| Ok(v.to_owned()),
+        );
+        env_override(
+            env,
+            "RSE_SHUTDOWN_TIMEOUT_SECS",
+            "shutdown_timeout_secs",
+            &mut self.shutdown_timeout_secs,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_ORDER_SWEEP_INTERVAL_SECS",
+            "order_sweep_interval_secs",
+            &mut self.order_sweep_interval_secs,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_RECONCILE_INTERVAL_SECS",
+            "reconcile_interval_secs",
+            &mut self.reconcile_interval_secs,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_SLOW_QUERY_MS",
+            "slow_query_ms",
+            &mut self.slow_query_ms,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "DISCORD_TOKEN",
+            "discord.token",
+            &mut self.discord.token,
+            problems,
+            |v| Ok(v.to_owned()),
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_GUILD_IDS",
+            "discord.guild_ids",
+            &mut self.discord.guild_ids,
+            problems,
+            parse_list,
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_ADMIN_IDS",
+            "discord.admin_ids",
+            &mut self.discord.admin_ids,
+            problems,
+            parse_list,
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_MARKET_FEED_CHANNEL",
+            "discord.market_feed_channel",
+            &mut self.discord.market_feed_channel,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_ADMIN_CHANNEL",
+            "discord.admin_channel",
+            &mut self.discord.admin_channel,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_DAILY_SUMMARY_HOUR",
+            "discord.daily_summary_hour",
+            &mut self.discord.daily_summary_hour,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_TRADE_BATCH_WINDOW_SECS",
+            "discord.trade_batch_window_secs",
+            &mut self.discord.trade_batch_window_secs,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_TRADE_BATCH_SIZE",
+            "discord.trade_batch_size",
+            &mut self.discord.trade_batch_size,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_LARGE_TRADE_VALUE",
+            "discord.large_trade_value",
+            &mut self.discord.large_trade_value,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_SLOW_COMMAND_MS",
+            "discord.slow_command_ms",
+            &mut self.discord.slow_command_ms,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_IMPORT_MAX_ROWS",
+            "discord.import_max_rows",
+            &mut self.discord.import_max_rows,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_SNAPSHOT_DIR",
+            "discord.snapshot_dir",
+            &mut self.discord.snapshot_dir,
+            problems,
+            |v| Ok(PathBuf::from(v)),
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_ACTIVITY",
+            "discord.activity",
+            &mut self.discord.activity,
+            problems,
+            |v| Ok(v.to_owned()),
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_STATUS",
+            "discord.status",
+            &mut self.discord.status,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_RECEIPTS",
+            "discord.receipts",
+            &mut self.discord.receipts,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_CURRENCY",
+            "discord.currency",
+            &mut self.discord.currency,
+            problems,
+            |v| Ok(v.to_owned()),
+        );
+        env_override(
+            env,
+            "RSE_DISCORD_COOLDOWNS",
+            "discord.cooldowns",
+            &mut self.discord.cooldowns,
+            problems,
+            parse_map,
+        );
+        env_override(
+            env,
+            "RSE_HTTP_BIND",
+            "http.bind",
+            &mut self.http.bind,
+            problems,
+            |v| Ok(v.to_owned()),
+        );
+        env_override(
+            env,
+            "RSE_API_BIND",
+            "api.bind",
+            &mut self.api.bind,
+            problems,
+            |v| Ok(v.to_owned()),
+        );
+        env_override(
+            env,
+            "RSE_KROMER_NODE_URL",
+            "kromer.node_url",
+            &mut self.kromer.node_url,
+            problems,
+            |v| Ok(v.to_owned()),
+        );
+        env_override(
+            env,
+            "RSE_KROMER_ADDRESS",
+            "kromer.address",
+            &mut self.kromer.address,
+            problems,
+            |v| Ok(v.to_owned()),
+        );
+        env_override(
+            env,
+            "RSE_KROMER_NAME",
+            "kromer.name",
+            &mut self.kromer.name,
+            problems,
+            |v| Ok(v.to_owned()),
+        );
+        env_override(
+            env,
+            "RSE_KROMER_POLL_INTERVAL_SECS",
+            "kromer.poll_interval_secs",
+            &mut self.kromer.poll_interval_secs,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_KROMER_PRIVATE_KEY",
+            "kromer.private_key",
+            &mut self.kromer.private_key,
+            problems,
+            |v| Ok(v.to_owned()),
+        );
+        env_override(
+            env,
+            "RSE_TRADING_FEE_BPS",
+            "trading.fee_bps",
+            &mut self.trading.fee_bps,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_TRADING_TREASURY_ACCOUNT",
+            "trading.treasury_account",
+            &mut self.trading.treasury_account,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_TRADING_DAILY_ISSUANCE_CAP_PCT",
+            "trading.daily_issuance_cap_pct",
+            &mut self.trading.daily_issuance_cap_pct,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_TRADING_PRICE_BAND_PCT",
+            "trading.price_band_pct",
+            &mut self.trading.price_band_pct,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_TRADING_PRICE_BAND_LOOKBACK_SECS",
+            "trading.price_band_lookback_secs",
+            &mut self.trading.price_band_lookback_secs,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_TRADING_NEW_ACCOUNT_HOURS",
+            "trading.new_account_hours",
+            &mut self.trading.new_account_hours,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_TRADING_NEW_ACCOUNT_ORDER_CAP",
+            "trading.new_account_order_cap",
+            &mut self.trading.new_account_order_cap,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_TRADING_BLOCKED_TICKERS",
+            "trading.blocked_tickers",
+            &mut self.trading.blocked_tickers,
+            problems,
+            parse_list,
+        );
+        env_override(
+            env,
+            "RSE_TRADING_HOURS_DAYS",
+            "trading.hours.days",
+            &mut self.trading.hours.days,
+            problems,
+            parse_list,
+        );
+        env_override(
+            env,
+            "RSE_TRADING_HOURS_OPEN",
+            "trading.hours.open",
+            &mut self.trading.hours.open,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_TRADING_HOURS_CLOSE",
+            "trading.hours.close",
+            &mut self.trading.hours.close,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_TRADING_HOURS_HOLIDAYS",
+            "trading.hours.holidays",
+            &mut self.trading.hours.holidays,
+            problems,
+            parse_list,
+        );
+        env_override(
+            env,
+            "RSE_TRADING_HOURS_QUEUE_ORDERS",
+            "trading.hours.queue_orders",
+            &mut self.trading.hours.queue_orders,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_RETRY_MAX_ATTEMPTS",
+            "retry.max_attempts",
+            &mut self.retry.max_attempts,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_RETRY_BASE_DELAY_MS",
+            "retry.base_delay_ms",
+            &mut self.retry.base_delay_ms,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_POOL_MAX_CONNECTIONS",
+            "pool.max_connections",
+            &mut self.pool.max_connections,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_POOL_MIN_CONNECTIONS",
+            "pool.min_connections",
+            &mut self.pool.min_connections,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_POOL_ACQUIRE_TIMEOUT_SECS",
+            "pool.acquire_timeout_secs",
+            &mut self.pool.acquire_timeout_secs,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_POOL_IDLE_TIMEOUT_SECS",
+            "pool.idle_timeout_secs",
+            &mut self.pool.idle_timeout_secs,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_POOL_STATEMENT_TIMEOUT_MS",
+            "pool.statement_timeout_ms",
+            &mut self.pool.statement_timeout_ms,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_RATE_LIMITS_TRADES_PER_MINUTE",
+            "rate_limits.trades_per_minute",
+            &mut self.rate_limits.trades_per_minute,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_RATE_LIMITS_LOOKUPS_PER_MINUTE",
+            "rate_limits.lookups_per_minute",
+            &mut self.rate_limits.lookups_per_minute,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_RATE_LIMITS_REGISTRATIONS_PER_HOUR",
+            "rate_limits.registrations_per_hour",
+            &mut self.rate_limits.registrations_per_hour,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_FEATURE_DISCORD",
+            "features.discord",
+            &mut self.features.discord,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_FEATURE_HTTP",
+            "features.http",
+            &mut self.features.http,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_FEATURE_API",
+            "features.api",
+            &mut self.features.api,
+            problems,
+            parse_value,
+        );
+        env_override(
+            env,
+            "RSE_FEATURE_KROMER",
+            "features.kromer",
+            &mut self.features.kromer,
+            problems,
+            parse_value,
+        );
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn validate(self, mut problems: Vec<Problem>) -> Result<Config, Error> {
+        let features = Features {
+            discord: self.features.discord.unwrap_or(true),
+            http: self.features.http.unwrap_or(false),
+            api: self.features.api.unwrap_or(false),
+            kromer: self.features.kromer.unwrap_or(false),
+        };
+
+        let database_url = required("database_url", self.database_url, &mut problems);
+
+        // The token is only needed when we actually start the bot
+        let token = if features.discord {
+            required("discord.token", self.discord.token, &mut problems)
+        } else {
+            self.discord.token.unwrap_or_default()
+        };
+
+        let daily_summary_hour = self.discord.daily_summary_hour.unwrap_or_default();
+
+        if daily_summary_hour > 23 {
+            problems.push(Problem {
+                field: "discord.daily_summary_hour",
+                reason: "must be an hour of the day, from 0 to 23".to_owned(),
+            });
+        }
+
+        let mut cooldowns: BTreeMap<_, _> = DEFAULT_COOLDOWN_SECS
+            .into_iter()
+            .map(|(name, secs)| (name.to_owned(), secs))
+            .collect();
+        cooldowns.extend(self.discord.cooldowns.unwrap_or_default());
+        let cooldowns = cooldowns
+            .into_iter()
+            .filter(|&(_, secs)| secs > 0)
+            .map(|(name, secs)| (name, Duration::from_secs(secs)))
+            .collect();
+
+        let fee_bps = self.trading.fee_bps.unwrap_or_default();
+
+        if fee_bps > MAX_FEE_BPS {
+            problems.push(Problem {
+                field: "trading.fee_bps",
+                reason: format!("must be at most {MAX_FEE_BPS}"),
+            });
+        }
+
+        if fee_bps > 0 && self.trading.treasury_account.is_none() {
+            problems.push(Problem {
+                field: "trading.treasury_account",
+                reason: "is required when fees are charged".to_owned(),
+            });
+        }
+
+        let kromer = self.kromer.validate(features.kromer, &mut problems);
+        let hours = self.trading.hours.validate(&mut problems);
+        let pool = self.pool.validate(&mut problems);
+
+        let bind = self
+            .http
+            .bind
+            .as_deref()
+            .unwrap_or(DEFAULT_HTTP_BIND)
+            .parse::<SocketAddr>()
+            .map_err(|err| {
+                problems.push(Problem {
+                    field: "http.bind",
+                    reason: err.to_string(),
+                });
+            });
+
+        let api_bind = self
+            .api
+            .bind
+            .as_deref()
+            .unwrap_or(DEFAULT_API_BIND)
+            .parse::<SocketAddr>()
+            .map_err(|err| {
+                problems.push(Problem {
+                    field: "api.bind",
+                    reason: err.to_string(),
+                });
+            });
+
+        match (bind, api_bind) {
+            (Ok(bind), Ok(api_bind)) if problems.is_empty() => Ok(Config {
+                database_url,
+                discord: DiscordConfig {
+                    token,
+                    guild_ids: self.discord.guild_ids.unwrap_or_default(),
+                    admin_ids: self.discord.admin_ids.unwrap_or_default(),
+                    market_feed_channel: self.discord.market_feed_channel,
+                    admin_channel: self.discord.admin_channel,
+                    daily_summary_hour,
+                    trade_batch_window: Duration::from_secs(
+                        self.discord
+                            .trade_batch_window_secs
+                            .unwrap_or(DEFAULT_TRADE_BATCH_WINDOW_SECS)
+                            .get(),
+                    ),
+                    trade_batch_size: self
+                        .discord
+                        .trade_batch_size
+                        .unwrap_or(DEFAULT_TRADE_BATCH_SIZE),
+                    large_trade_value: self.discord.large_trade_value,
+                    cooldowns,
+                    slow_command: Duration::from_millis(
+                        self.discord
+                            .slow_command_ms
+                            .unwrap_or(DEFAULT_SLOW_COMMAND_MS),
+                    ),
+                    import_max_rows: self
+                        .discord
+                        .import_max_rows
+                        .unwrap_or(DEFAULT_IMPORT_MAX_ROWS),
+                    snapshot_dir: self
+                        .discord
+                        .snapshot_dir
+                        .unwrap_or_else(|| PathBuf::from(DEFAULT_SNAPSHOT_DIR)),
+                    activity: Some(
+                        self.discord
+                            .activity
+                            .unwrap_or_else(|| DEFAULT_ACTIVITY.to_owned()),
+                    )
+                    .filter(|activity| !activity.trim().is_empty()),
+                    status: self.discord.status.unwrap_or_default(),
+                    receipts: self.discord.receipts.unwrap_or(true),
+                    currency: self
+                        .discord
+                        .currency
+                        .map_or_else(|| DEFAULT_CURRENCY.to_owned(), |v| v.trim().to_owned()),
+                },
+                http: HttpConfig { bind },
+                api: ApiConfig { bind: api_bind },
+                kromer,
+                trading: TradingConfig {
+                    fee_bps,
+                    treasury_account: self.trading.treasury_account,
+                    daily_issuance_cap_pct: self
+                        .trading
+                        .daily_issuance_cap_pct
+                        .unwrap_or(DEFAULT_DAILY_ISSUANCE_CAP_PCT),
+                    price_band_pct: self.trading.price_band_pct,
+                    price_band_lookback: Duration::from_secs(
+                        self.trading
+                            .price_band_lookback_secs
+                            .unwrap_or(DEFAULT_PRICE_BAND_LOOKBACK_SECS)
+                            .get(),
+                    ),
+                    new_account_hours: self.trading.new_account_hours,
+                    new_account_order_cap: self
+                        .trading
+                        .new_account_order_cap
+                        .unwrap_or(DEFAULT_NEW_ACCOUNT_ORDER_CAP),
+                    blocked_tickers: self.trading.blocked_tickers.unwrap_or_default(),
+                    hours,
+                },
+                retry: RetryConfig {
+                    max_attempts: self
+                        .retry
+                        .max_attempts
+                        .unwrap_or(DEFAULT_RETRY_MAX_ATTEMPTS),
+                    base_delay: Duration::from_millis(
+                        self.retry
+                            .base_delay_ms
+                            .unwrap_or(DEFAULT_RETRY_BASE_DELAY_MS),
+                    ),
+                },
+                pool,
+                rate_limits: RateLimitConfig {
+                    trades_per_minute: self
+                        .rate_limits
+                        .trades_per_minute
+                        .unwrap_or(DEFAULT_TRADES_PER_MINUTE),
+                    lookups_per_minute: self
+                        .rate_limits
+                        .lookups_per_minute
+                        .unwrap_or(DEFAULT_LOOKUPS_PER_MINUTE),
+                    registrations_per_hour: self
+                        .rate_limits
+                        .registrations_per_hour
+                        .unwrap_or(DEFAULT_REGISTRATIONS_PER_HOUR),
+                },
+                features,
+                shutdown_timeout: Duration::from_secs(
+                    self.shutdown_timeout_secs
+                        .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS),
+                ),
+                order_sweep_interval: Duration::from_secs(
+                    self.order_sweep_interval_secs
+                        .unwrap_or(DEFAULT_ORDER_SWEEP_INTERVAL_SECS)
+                        .get(),
+                ),
+                reconcile_interval: Duration::from_secs(
+                    self.reconcile_interval_secs
+                        .unwrap_or(DEFAULT_RECONCILE_INTERVAL_SECS)
+                        .get(),
+                ),
+                slow_query: Duration::from_millis(
+                    self.slow_query_ms.unwrap_or(DEFAULT_SLOW_QUERY_MS),
+                ),
+            }),
+            _ => InvalidSnafu { problems }.fail(),
+        }
+    }
+}
+
+impl RawMarketHours {
+    /// Trading hours are only set up once either time is, and then need both
+    fn validate(self, problems: &mut Vec<Problem>) -> Option<MarketHours> {
+        let (open, close) = match (self.open, self.close) {
+            (None, None) => return None,
+            (Some(open), Some(close)) => (open, close),
+            (None, Some(_)) => {
+                problems.push(Problem {
+                    field: "trading.hours.open",
+                    reason: "is required when `close` is set".to_owned(),
+                });
+                return None;
+            }
+            (Some(_), None) => {
+                problems.push(Problem {
+                    field: "trading.hours.close",
+                    reason: "is required when `open` is set".to_owned(),
+                });
+                return None;
+            }
+        };
+
+        if open >= close {
+            problems.push(Problem {
+                field: "trading.hours.close",
+                reason: "must be after `open`".to_owned(),
+            });
+        }
+
+        let days = self.days.unwrap_or_else(|| DEFAULT_TRADING_DAYS.to_vec());
+
+        if days.is_empty() {
+            problems.push(Problem {
+                field: "trading.hours.days",
+                reason: "must name at least one day".to_owned(),
+            });
+        }
+
+        Some(MarketHours {
+            days,
+            open,
+            close,
+            holidays: self.holidays.unwrap_or_default(),
+            queue_orders: self.queue_orders.unwrap_or_default(),
+        })
+    }
+}
+
+impl RawKromerConfig {
+    /// The wallet is only needed when deposits are actually taken
+    fn validate(self, enabled: bool, problems: &mut Vec<Problem>) -> KromerConfig {
+        let (address, name) = if enabled {
+            (
+                required("kromer.address", self.address, problems),
+                required("kromer.name", self.name, problems),
+            )
+        } else {
+            (
+                self.address.unwrap_or_default(),
+                self.name.unwrap_or_default(),
+            )
+        };
+
+        let address = address.trim().to_owned();
+        let name = name.trim().trim_end_matches(".kro").to_owned();
+
+        let valid_address = address.len() == 10
+            && address.starts_with('k')
+            && address
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit());
+
+        if !address.is_empty() && !valid_address {
+            problems.push(Problem {
+                field: "kromer.address",
+                reason: "must be `k` followed by 9 lowercase letters or digits".to_owned(),
+            });
+        }
+
+        if name.len() > 64
+            || !name
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit())
+        {
+            problems.push(Problem {
+                field: "kromer.name",
+                reason: "must be at most 64 lowercase letters or digits".to_owned(),
+            });
+        }
+
+        KromerConfig {
+            node_url: self
+                .node_url
+                .unwrap_or_else(|| DEFAULT_KROMER_NODE_URL.to_owned()),
+            address,
+            name,
+            poll_interval: Duration::from_secs(
+                self.poll_interval_secs
+                    .unwrap_or(DEFAULT_KROMER_POLL_INTERVAL_SECS)
+                    .get(),
+            ),
+            private_key: self.private_key.filter(|key| !key.trim().is_empty()),
+        }
+    }
+}
+
+impl RawPoolConfig {
+    fn validate(self, problems: &mut Vec<Problem>) -> PoolConfig {
+        let max_connections = self.max_connections.unwrap_or(DEFAULT_POOL_MAX_CONNECTIONS);
+        let min_connections = self.min_connections.unwrap_or_default();
+
+        if min_connections > max_connections.get() {
+            problems.push(Problem {
+                field: "pool.min_connections",
+                reason: "must be at most `max_connections`".to_owned(),
+            });
+        }
+
+        PoolConfig {
+            max_connections,
+            min_connections,
+            acquire_timeout: Duration::from_secs(
+                self.acquire_timeout_secs
+                    .unwrap_or(DEFAULT_POOL_ACQUIRE_TIMEOUT_SECS)
+                    .get(),
+            ),
+            idle_timeout: Duration::from_secs(
+                self.idle_timeout_secs
+                    .unwrap_or(DEFAULT_POOL_IDLE_TIMEOUT_SECS)
+                    .get(),
+            ),
+            statement_timeout: Duration::from_millis(
+                self.statement_timeout_ms
+                    .unwrap_or(DEFAULT_POOL_STATEMENT_TIMEOUT_MS)
+                    .get(),
+            ),
+        }
+    }
+}
+
+/// Looks up an environment variable by name
+type Env = dyn Fn(&str) -> Option<String>;
+
+/// Replaces `slot` with the parsed value of the environment variable `key` if it is set. Parsing
+/// failures are pushed onto `problems`, leaving `slot` untouched.
+fn env_override<T>(
+    env: &Env,
+    key: &str,
+    field: &'static str,
+    slot: &mut Option<T>,
+    problems: &mut Vec<Problem>,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) {
+    let Some(value) = env(key) else {
+        return;
+    };
+
+    match parse(value.trim()) {
+        Ok(v) => *slot = Some(v),
+        Err(reason) => problems.push(Problem {
+            field,
+            reason: format!("invalid value in `{key}`: {reason}"),
+        }),
+    }
+}
+
+fn required(field: &'static str, value: Option<String>, problems: &mut Vec<Problem>) -> String {
+    match value {
+        Some(v) if !v.trim().is_empty() => v,
+        _ => {
+            problems.push(Problem {
+                field,
+                reason: "is required but was not set".to_owned(),
+            });
+            String::new()
+        }
+    }
+}
+
+fn parse_value<T: FromStr>(v: &str) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    v.parse().map_err(|err: T::Err| err.to_string())
+}
+
+fn parse_list<T: FromStr>(v: &str) -> Result<Vec<T>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    v.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| parse_value(s).map_err(|err| format!("\"{s}\": {err}")))
+        .collect()
+}
+
+fn parse_map<T: FromStr>(v: &str) -> Result<BTreeMap<String, T>, String>
+where
+    T::Err: std::fmt::Display,
+{
+    v.split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            let (key, value) = s
+                .split_once('=')
+                .ok_or_else(|| format!("\"{s}\": expected `name=value`"))?;
+            let value = parse_value(value.trim()).map_err(|err| format!("\"{s}\": {err}"))?;
+
+            Ok((key.trim().to_owned(), value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Writes `contents` to a config file of its own in the temp directory
+    fn file(name: &str, contents: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("rse-config-{}-{name}.toml", std::process::id()));
+        std::fs::write(&path, contents).expect("Written");
+        path
+    }
+
+    fn load(path: Option<PathBuf>, vars: &[(&str, &str)]) -> Result<Config, Error> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|&(key, value)| (key.to_owned(), value.to_owned()))
+            .collect();
+
+        Config::load_from(path, &move |key| vars.get(key).cloned())
+    }
+
+    fn problem_fields(err: Error) -> Vec<&'static str> {
+        match err {
+            Error::Invalid { problems } => problems.iter().map(|p| p.field).collect(),
+            err => panic!("Expected invalid config, got {err}"),
+        }
+    }
+
+    const MINIMAL: &str = r#"
+        database_url = "postgres://localhost/rse"
+
+        [discord]
+        token = "secret"
+    "#;
+
+    #[test]
+    fn toml_files_are_loaded_over_defaults() {
+        let path = file(
+            "loaded",
+            r#"
+            database_url = "postgres://localhost/rse"
+            shutdown_timeout_secs = 5
+
+            [discord]
+            token = "secret"
+            guild_ids = [1, 2]
+            cooldowns = { "order place" = 0, find = 3 }
+
+            [trading]
+            fee_bps = 25
+            treasury_account = "00000000-0000-0000-0000-000000000001"
+
+            [features]
+            api = true
+            "#,
+        );
+
+        let config = load(Some(path), &[]).expect("Valid");
+
+        assert_eq!(config.database_url, "postgres://localhost/rse");
+        assert_eq!(config.shutdown_timeout, Duration::from_secs(5));
+        assert_eq!(config.discord.guild_ids.len(), 2);
+        assert_eq!(config.trading.fee_bps, 25);
+        assert!(config.features.discord && config.features.api && !config.features.http);
+        // Configured cooldowns merge over the defaults, with 0 removing one
+        assert_eq!(
+            config.discord.cooldowns.get("find"),
+            Some(&Duration::from_secs(3))
+        );
+        assert_eq!(config.discord.cooldowns.get("order place"), None);
+        assert_eq!(
+            config.discord.cooldowns.get("buy"),
+            Some(&Duration::from_secs(5))
+        );
+        // Anything not in the file falls back to its default
+        assert_eq!(config.api.bind.to_string(), DEFAULT_API_BIND);
+        assert_eq!(config.order_sweep_interval, Duration::from_mins(1));
+    }
+
+    #[test]
+    fn environment_overrides_win_over_the_file() {
+        let path = file(
+            "overridden",
+            &format!(
+                "{MINIMAL}\n[trading]\nfee_bps = 25\ntreasury_account = \"00000000-0000-0000-0000-000000000001\"\n"
+            ),
+        );
+
+        let config = load(
+            Some(path),
+            &[
+                ("DATABASE_URL", "postgres://elsewhere/rse"),
+                ("RSE_TRADING_FEE_BPS", "50"),
+                ("RSE_DISCORD_ADMIN_IDS", "3, 4,"),
+                ("RSE_FEATURE_DISCORD", "false"),
+            ],
+        )
+        .expect("Valid");
+
+        assert_eq!(config.database_url, "postgres://elsewhere/rse");
+        assert_eq!(config.trading.fee_bps, 50);
+        assert_eq!(config.discord.admin_ids.len(), 2);
+        assert!(!config.features.discord);
+    }
+
+    #[test]
+    fn every_invalid_field_is_reported_at_once() {
+        let path = file(
+            "invalid",
+            r"
+            [discord]
+            daily_summary_hour = 24
+
+            [trading]
+            fee_bps = 20000
+
+            [pool]
+            max_connections = 2
+            min_connections = 3
+            ",
+        );
+
+        let fields = problem_fields(
+            load(
+                Some(path),
+                &[
+                    ("RSE_HTTP_BIND", "nowhere"),
+                    ("RSE_RETRY_MAX_ATTEMPTS", "lots"),
+                ],
+            )
+            .expect_err("Invalid"),
+        );
+
+        for field in [
+            "database_url",
+            "discord.token",
+            "discord.daily_summary_hour",
+            "trading.fee_bps",
+            "trading.treasury_account",
+            "pool.min_connections",
+            "http.bind",
+            "retry.max_attempts",
+        ] {
+            assert!(fields.contains(&field), "{field} missing from {fields:?}");
+        }
+    }
+
+    #[test]
+    fn unreadable_and_unknown_files_are_rejected() {
+        let missing = std::env::temp_dir().join("rse-config-does-not-exist.toml");
+        assert!(matches!(load(Some(missing), &[]), Err(Error::Read { .. })));
+
+        let path = file(
+            "unknown",
+            &format!("{MINIMAL}\n[discrod]\ntoken = \"typo\"\n"),
+        );
+        assert!(matches!(load(Some(path), &[]), Err(Error::Parse { .. })));
+    }
+
+    #[test]
+    fn deposits_need_a_wallet_only_when_taken() {
+        let config = load(
+            None,
+            &[
+                ("DATABASE_URL", "postgres://localhost/rse"),
+                ("DISCORD_TOKEN", "secret"),
+            ],
+        )
+        .expect("Valid");
+        assert!(!config.features.kromer);
+
+        let fields = problem_fields(
+            load(
+                None,
+                &[
+                    ("DATABASE_URL", "postgres://localhost/rse"),
+                    ("DISCORD_TOKEN", "secret"),
+                    ("RSE_FEATURE_KROMER", "true"),
+                    ("RSE_KROMER_NAME", "Not A Name"),
+                ],
+            )
+            .expect_err("Invalid"),
+        );
+        assert_eq!(fields, vec!["kromer.address", "kromer.name"]);
+    }
+
+    #[test]
+    fn the_wallet_key_is_never_printed() {
+        let config = load(
+            None,
+            &[
+                ("DATABASE_URL", "postgres://localhost/rse"),
+                ("DISCORD_TOKEN", "secret"),
+                ("RSE_KROMER_PRIVATE_KEY", "hunter2"),
+            ],
+        )
+        .expect("Valid");
+
+        assert_eq!(config.kromer.private_key.as_deref(), Some("hunter2"));
+        assert!(!format!("{:?}", config.kromer).contains("hunter2"));
+    }
+}
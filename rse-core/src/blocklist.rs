@@ -0,0 +1,167 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Words new stocks may not be listed under. Kept apart from [`Ticker`] parsing, which only checks
+//! the format, so existing stocks keep loading whatever is blocked later on.
+
+use std::sync::Arc;
+
+use crate::model::ticker::Ticker;
+
+/// Tickers the exchange keeps for itself, or that would be mistaken for something official
+const RESERVED: [&str; 8] = [
+    "ADMIN", "KROMR", "KRO", "KST", "RSE", "STAFF", "TEST", "NULL",
+];
+
+/// Characters commonly swapped in for letters, and the letter each stands in for
+const SUBSTITUTIONS: [(char, char); 8] = [
+    ('0', 'O'),
+    ('1', 'I'),
+    ('3', 'E'),
+    ('4', 'A'),
+    ('5', 'S'),
+    ('7', 'T'),
+    ('@', 'A'),
+    ('$', 'S'),
+];
+
+/// Why a ticker was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    /// The ticker is one of the words the exchange keeps for itself
+    Reserved,
+    /// The ticker contains a word blocked by the operator
+    Blocked,
+}
+
+impl Refusal {
+    /// Why the ticker can't be used, worded to follow "the ticker can't be used, as"
+    #[must_use]
+    pub const fn reason(self) -> &'static str {
+        match self {
+            Self::Reserved => "it is reserved by the exchange",
+            Self::Blocked => "it contains a blocked word",
+        }
+    }
+}
+
+/// The built-in reserved tickers, along with any words blocked by the operator. Reserved words
+/// only refuse the ticker spelling them out exactly, while blocked words are refused anywhere in a
+/// ticker, as a slur stays a slur with a letter tacked on. Both are compared after
+/// [`normalize`]-ing, so casing and simple leetspeak don't get around them.
+#[derive(Debug, Clone)]
+pub struct TickerBlocklist {
+    blocked: Arc<[String]>,
+}
+
+impl TickerBlocklist {
+    /// A blocklist refusing the reserved tickers and anything containing one of `words`. Words
+    /// that are empty once normalized are ignored
+    pub fn new(words: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let mut blocked: Vec<_> = words
+            .into_iter()
+            .map(|word| normalize(word.as_ref()))
+            .filter(|word| !word.is_empty())
+            .collect();
+        blocked.sort_unstable();
+        blocked.dedup();
+
+        Self {
+            blocked: blocked.into(),
+        }
+    }
+
+    /// Why `ticker` may not be listed, if it may not
+    #[must_use]
+    pub fn check(&self, ticker: &Ticker) -> Option<Refusal> {
+        let ticker = normalize(ticker.as_str());
+
+        if RESERVED.iter().any(|word| normalize(word) == ticker) {
+            return Some(Refusal::Reserved);
+        }
+
+        self.blocked
+            .iter()
+            .any(|word| ticker.contains(word.as_str()))
+            .then_some(Refusal::Blocked)
+    }
+}
+
+/// Only refuses the reserved tickers
+impl Default for TickerBlocklist {
+    fn default() -> Self {
+        Self::new(std::iter::empty::<&str>())
+    }
+}
+
+/// Uppercases `word` and undoes the [`SUBSTITUTIONS`], dropping anything else that isn't a letter,
+/// so `"k-r0mr"` and `"KROMR"` compare equal
+#[must_use]
+pub fn normalize(word: &str) -> String {
+    word.chars()
+        .map(|c| {
+            SUBSTITUTIONS
+                .iter()
+                .find(|&&(from, _)| from == c)
+                .map_or(c, |&(_, to)| to)
+        })
+        .filter(char::is_ascii_alphabetic)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticker(s: &str) -> Ticker {
+        Ticker::try_from(s).expect("Valid ticker")
+    }
+
+    #[test]
+    fn substitutions_become_letters() {
+        for (from, to) in SUBSTITUTIONS {
+            assert_eq!(normalize(&from.to_string()), to.to_string());
+        }
+
+        assert_eq!(normalize("k-r0mr"), "KROMR");
+        assert_eq!(normalize("4dm1n"), "ADMIN");
+        assert_eq!(normalize("t3$7"), "TEST");
+        assert_eq!(normalize(" _ "), "");
+    }
+
+    #[test]
+    fn reserved_tickers_only_match_exactly() {
+        let blocklist = TickerBlocklist::default();
+
+        assert_eq!(blocklist.check(&ticker("admin")), Some(Refusal::Reserved));
+        assert_eq!(blocklist.check(&ticker("KROMR")), Some(Refusal::Reserved));
+        assert_eq!(blocklist.check(&ticker("RSEX")), None);
+        assert_eq!(blocklist.check(&ticker("ABC")), None);
+    }
+
+    #[test]
+    fn blocked_words_match_anywhere() {
+        let blocklist = TickerBlocklist::new(["b4d", "", "BAD"]);
+
+        assert_eq!(blocklist.blocked.len(), 1);
+        assert_eq!(blocklist.check(&ticker("BAD")), Some(Refusal::Blocked));
+        assert_eq!(blocklist.check(&ticker("xbadx")), Some(Refusal::Blocked));
+        assert_eq!(blocklist.check(&ticker("BDA")), None);
+    }
+}
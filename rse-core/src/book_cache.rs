@@ -0,0 +1,181 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A short-lived cache of each stock's order book, so the same book being asked for over and over
+//! only has to be aggregated once in a while. Entries are dropped as soon as an [`Event`] shows
+//! their book changed, so they only go stale through changes made by another server.
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    event::Event,
+    model::{order::Book, ticker::Ticker},
+};
+
+/// How long a cached book is trusted for
+pub(crate) const BOOK_TTL: Duration = Duration::from_secs(2);
+
+/// Each stock's most recently read book, alongside how deep it was read
+#[derive(Debug)]
+pub(crate) struct BookCache {
+    ttl: Duration,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    books: HashMap<Ticker, (Instant, u32, Book)>,
+    /// Bumped on every invalidation, so reads that raced one don't cache what they read
+    generation: u64,
+}
+
+impl BookCache {
+    pub(crate) fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            inner: Mutex::default(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Inner> {
+        // Entries are only ever swapped whole, so a panic mid-update can't leave one half written
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Gets `depth` levels of a live book read at least that deep, or the current generation to
+    /// pass to [`insert`](Self::insert) after reading it
+    pub(crate) fn get(&self, ticker: Ticker, depth: u32) -> Result<Book, u64> {
+        let inner = self.lock();
+
+        match inner.books.get(&ticker) {
+            Some((at, read, book)) if at.elapsed() < self.ttl && *read >= depth => {
+                let depth = usize::try_from(depth).unwrap_or(usize::MAX);
+
+                Ok(Book {
+                    bids: book.bids.iter().take(depth).copied().collect(),
+                    asks: book.asks.iter().take(depth).copied().collect(),
+                    last_price: book.last_price,
+                })
+            }
+            _ => Err(inner.generation),
+        }
+    }
+
+    /// Caches `book`, read `depth` levels deep, unless something was invalidated since
+    /// `generation` was read
+    pub(crate) fn insert(&self, ticker: Ticker, depth: u32, book: Book, generation: u64) {
+        let mut inner = self.lock();
+
+        if inner.generation == generation {
+            inner.books.insert(ticker, (Instant::now(), depth, book));
+        }
+    }
+
+    /// Drops the cached book of whichever stock `event` changed the book of, if any
+    pub(crate) fn observe(&self, event: &Event) {
+        let ticker = match event {
+            Event::OrderPlaced { order, .. }
+            | Event::OrderCancelled { order, .. }
+            | Event::OrderExpired(order) => order.ticker,
+            _ => return,
+        };
+
+        let mut inner = self.lock();
+        inner.books.remove(&ticker);
+        inner.generation += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::model::{
+        Price, Shares,
+        order::{BookLevel, Order, OrderStatus, Side},
+    };
+
+    fn level(price: i64) -> BookLevel {
+        BookLevel {
+            price: Decimal::from(price),
+            shares: 1,
+            orders: 1,
+        }
+    }
+
+    fn book() -> Book {
+        Book {
+            bids: vec![level(3), level(2), level(1)],
+            asks: vec![level(4), level(5), level(6)],
+            last_price: None,
+        }
+    }
+
+    fn ticker() -> Ticker {
+        Ticker::try_from("ABC").expect("Valid ticker")
+    }
+
+    #[test]
+    fn shallower_reads_are_served_from_deeper_ones() {
+        let cache = BookCache::new(BOOK_TTL);
+        let generation = cache.get(ticker(), 3).expect_err("Empty");
+        cache.insert(ticker(), 3, book(), generation);
+
+        let shallow = cache.get(ticker(), 2).expect("Cached");
+        assert_eq!(shallow.bids, [level(3), level(2)]);
+        assert_eq!(shallow.asks, [level(4), level(5)]);
+        assert!(cache.get(ticker(), 4).is_err());
+    }
+
+    #[test]
+    fn reads_racing_an_invalidation_are_not_cached() {
+        let cache = BookCache::new(BOOK_TTL);
+        let generation = cache.get(ticker(), 3).expect_err("Empty");
+
+        cache.observe(&Event::OrderExpired(Order {
+            id: 1,
+            user: Uuid::nil(),
+            ticker: ticker(),
+            side: Side::Buy,
+            price: Price::new(Decimal::ONE).expect("Valid price"),
+            quantity: Shares::new(1).expect("Valid shares"),
+            remaining: Shares::ZERO,
+            status: OrderStatus::Expired,
+            created_at: Utc::now(),
+            expires_at: Some(Utc::now()),
+        }));
+        cache.insert(ticker(), 3, book(), generation);
+
+        assert!(cache.get(ticker(), 3).is_err());
+    }
+
+    #[test]
+    fn books_expire() {
+        let cache = BookCache::new(Duration::ZERO);
+        cache.insert(ticker(), 3, book(), 0);
+
+        assert!(cache.get(ticker(), 3).is_err());
+    }
+}
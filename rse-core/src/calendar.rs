@@ -0,0 +1,216 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! When the market is open for trading. Times are in UTC, with no daylight saving, so a session
+//! always opens and closes at the same time of day.
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Utc, Weekday};
+
+/// What happens to orders placed while the market is closed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AfterHours {
+    /// Orders are refused until the market opens
+    #[default]
+    Reject,
+    /// Orders are escrowed but kept off the book, then matched in the order they were placed once
+    /// the market opens
+    Queue,
+}
+
+/// The days and hours the market is open, less any holidays
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarketCalendar {
+    days: Vec<Weekday>,
+    open: NaiveTime,
+    close: NaiveTime,
+    holidays: BTreeSet<NaiveDate>,
+}
+
+impl MarketCalendar {
+    /// A market open from `open` until `close` on each of `days`. Returns [`None`] if it would
+    /// never open, as there are no days or it doesn't open before it closes.
+    #[must_use]
+    pub fn new(
+        days: impl IntoIterator<Item = Weekday>,
+        open: NaiveTime,
+        close: NaiveTime,
+    ) -> Option<Self> {
+        let mut days: Vec<_> = days.into_iter().collect();
+        days.sort_by_key(Weekday::num_days_from_monday);
+        days.dedup();
+
+        (!days.is_empty() && open < close).then_some(Self {
+            days,
+            open,
+            close,
+            holidays: BTreeSet::new(),
+        })
+    }
+
+    /// Keeps the market closed all day on each of `holidays`
+    #[must_use]
+    pub fn with_holidays(mut self, holidays: impl IntoIterator<Item = NaiveDate>) -> Self {
+        self.holidays.extend(holidays);
+        self
+    }
+
+    /// Whether the market is open at `at`. It opens on the dot, and is closed again at its
+    /// closing time.
+    #[must_use]
+    pub fn is_open(&self, at: DateTime<Utc>) -> bool {
+        let time = at.time();
+
+        self.is_trading_day(at.date_naive()) && self.open <= time && time < self.close
+    }
+
+    /// The next time the market opens strictly after `after`
+    #[must_use]
+    pub fn next_open(&self, after: DateTime<Utc>) -> DateTime<Utc> {
+        // Every holiday can push the next open back by at most a week
+        after
+            .date_naive()
+            .iter_days()
+            .take((self.holidays.len() + 1) * 7 + 1)
+            .filter(|date| self.is_trading_day(*date))
+            .map(|date| date.and_time(self.open).and_utc())
+            .find(|open| *open > after)
+            .expect("Opens at least once a week outside of holidays")
+    }
+
+    /// When the session open at `after` closes, or the next one does if the market is closed
+    #[must_use]
+    pub fn next_close(&self, after: DateTime<Utc>) -> DateTime<Utc> {
+        let date = if self.is_open(after) {
+            after.date_naive()
+        } else {
+            self.next_open(after).date_naive()
+        };
+
+        date.and_time(self.close).and_utc()
+    }
+
+    fn is_trading_day(&self, date: NaiveDate) -> bool {
+        self.days.contains(&date.weekday()) && !self.holidays.contains(&date)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).expect("Valid time")
+    }
+
+    /// A Monday
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 10, 13)
+            .expect("Valid date")
+            .checked_add_days(chrono::Days::new(u64::from(day)))
+            .expect("Valid date")
+    }
+
+    fn at(day: u32, h: u32, m: u32) -> DateTime<Utc> {
+        date(day).and_time(time(h, m)).and_utc()
+    }
+
+    fn weekdays() -> MarketCalendar {
+        MarketCalendar::new(
+            [
+                Weekday::Fri,
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+            ],
+            time(14, 0),
+            time(22, 0),
+        )
+        .expect("Valid calendar")
+    }
+
+    #[test]
+    fn never_opening_is_refused() {
+        assert_eq!(MarketCalendar::new([], time(14, 0), time(22, 0)), None);
+        assert_eq!(
+            MarketCalendar::new([Weekday::Mon], time(22, 0), time(14, 0)),
+            None
+        );
+        assert_eq!(
+            MarketCalendar::new([Weekday::Mon], time(14, 0), time(14, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn opens_on_the_dot_and_closes_on_the_dot() {
+        let calendar = weekdays();
+
+        assert!(!calendar.is_open(at(0, 14, 0) - TimeDelta::nanoseconds(1)));
+        assert!(calendar.is_open(at(0, 14, 0)));
+        assert!(calendar.is_open(at(0, 22, 0) - TimeDelta::nanoseconds(1)));
+        assert!(!calendar.is_open(at(0, 22, 0)));
+        assert!(!calendar.is_open(at(0, 4, 0)));
+
+        // Weekends
+        assert!(calendar.is_open(at(4, 15, 0)));
+        assert!(!calendar.is_open(at(5, 15, 0)));
+        assert!(!calendar.is_open(at(6, 15, 0)));
+    }
+
+    #[test]
+    fn next_open_and_close() {
+        let calendar = weekdays();
+
+        // Before the open, during the session and after the close
+        assert_eq!(calendar.next_open(at(0, 4, 0)), at(0, 14, 0));
+        assert_eq!(calendar.next_close(at(0, 4, 0)), at(0, 22, 0));
+        assert_eq!(calendar.next_open(at(0, 14, 0)), at(1, 14, 0));
+        assert_eq!(calendar.next_close(at(0, 14, 0)), at(0, 22, 0));
+        assert_eq!(calendar.next_open(at(0, 22, 0)), at(1, 14, 0));
+        assert_eq!(calendar.next_close(at(0, 22, 0)), at(1, 22, 0));
+
+        // Over the weekend
+        assert_eq!(calendar.next_open(at(4, 22, 0)), at(7, 14, 0));
+        assert_eq!(calendar.next_open(at(5, 12, 0)), at(7, 14, 0));
+        assert_eq!(calendar.next_close(at(6, 23, 59)), at(7, 22, 0));
+    }
+
+    #[test]
+    fn holidays_stay_closed() {
+        // A long weekend, closed on Friday and Monday
+        let calendar = weekdays().with_holidays([date(4), date(7)]);
+
+        assert!(calendar.is_open(at(3, 15, 0)));
+        assert!(!calendar.is_open(at(4, 15, 0)));
+        assert!(!calendar.is_open(at(7, 15, 0)));
+        assert_eq!(calendar.next_open(at(3, 22, 0)), at(8, 14, 0));
+        assert_eq!(calendar.next_close(at(4, 15, 0)), at(8, 22, 0));
+
+        // Every trading day for weeks on end
+        let calendar = MarketCalendar::new([Weekday::Sat], time(0, 0), time(1, 0))
+            .expect("Valid calendar")
+            .with_holidays((0..4).map(|week| date(5 + week * 7)));
+
+        assert_eq!(calendar.next_open(at(0, 0, 0)), at(33, 0, 0));
+    }
+}
@@ -0,0 +1,136 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Amounts of Kromer rendered for people to read, the same way wherever they are shown. This is
+//! for display only: anything read back by programs, such as exports or API responses, keeps the
+//! raw [`Decimal`] so nothing is lost to rounding or has to be parsed out of a formatted string.
+
+use std::sync::OnceLock;
+
+use rust_decimal::{Decimal, RoundingStrategy};
+
+/// The suffix shown after amounts of Kromer unless another is configured
+pub const DEFAULT_CURRENCY: &str = "KRO";
+
+/// The minus sign negative amounts start with, which unlike a hyphen is as wide as a plus sign
+const MINUS: char = '\u{2212}';
+
+static CURRENCY: OnceLock<String> = OnceLock::new();
+
+/// Sets the suffix shown after amounts of Kromer, with an empty one showing none. Only the first
+/// call has any effect, so it is set once at startup.
+pub fn set_currency(suffix: String) {
+    let _ = CURRENCY.set(suffix);
+}
+
+/// Renders an amount with exactly two decimal places and commas between thousands, such as
+/// `1,234.50`, without a currency. For columns of a table whose heading already says what they
+/// are in. Rounds half to even, as fees are, and starts negative amounts with a minus sign.
+#[must_use]
+pub fn format_amount(amount: Decimal) -> String {
+    let rounded = amount.round_dp_with_strategy(2, RoundingStrategy::MidpointNearestEven);
+    let digits = format!("{:.2}", rounded.abs());
+    let (whole, cents) = digits.split_once('.').expect("Always has decimal places");
+
+    let mut buff = String::with_capacity(digits.len() + whole.len() / 3 + 1);
+
+    if rounded.is_sign_negative() && !rounded.is_zero() {
+        buff.push(MINUS);
+    }
+
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            buff.push(',');
+        }
+        buff.push(digit);
+    }
+
+    buff.push('.');
+    buff.push_str(cents);
+
+    buff
+}
+
+/// Renders an amount of Kromer like [`format_amount`], followed by the configured currency, such
+/// as `1,234.50 KRO`
+#[must_use]
+pub fn format_kromer(amount: Decimal) -> String {
+    with_currency(format_amount(amount))
+}
+
+/// Renders a change to an amount of Kromer like [`format_kromer`], but always signed, such as
+/// `+12.50 KRO` or `−12.50 KRO`. Changes that round to nothing are left unsigned.
+#[must_use]
+pub fn format_signed(delta: Decimal) -> String {
+    let amount = format_amount(delta);
+
+    if amount.starts_with(MINUS) || amount.chars().all(|c| matches!(c, '0' | '.' | ',')) {
+        with_currency(amount)
+    } else {
+        with_currency(format!("+{amount}"))
+    }
+}
+
+fn with_currency(mut amount: String) -> String {
+    let currency = CURRENCY.get().map_or(DEFAULT_CURRENCY, String::as_str);
+
+    if !currency.is_empty() {
+        amount.push(' ');
+        amount.push_str(currency);
+    }
+
+    amount
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_round_half_to_even() {
+        assert_eq!(format_amount(Decimal::new(5, 3)), "0.00");
+        assert_eq!(format_amount(Decimal::new(15, 3)), "0.02");
+        assert_eq!(format_amount(Decimal::new(1_005, 3)), "1.00");
+        assert_eq!(format_amount(Decimal::new(-25, 3)), "\u{2212}0.02");
+        assert_eq!(format_amount(Decimal::new(125_000_000, 7)), "12.50");
+    }
+
+    #[test]
+    fn amounts_separate_thousands() {
+        assert_eq!(format_amount(Decimal::ZERO), "0.00");
+        assert_eq!(format_amount(Decimal::new(999_999, 2)), "9,999.99");
+        assert_eq!(
+            format_amount(Decimal::new(-1_234_567, 1)),
+            "\u{2212}123,456.70"
+        );
+        assert_eq!(
+            format_amount(Decimal::MAX),
+            "79,228,162,514,264,337,593,543,950,335.00"
+        );
+    }
+
+    #[test]
+    fn kromer_is_suffixed_and_deltas_signed() {
+        assert_eq!(format_kromer(Decimal::new(1, 1)), "0.10 KRO");
+        assert_eq!(format_kromer(Decimal::new(-1, 0)), "\u{2212}1.00 KRO");
+        assert_eq!(format_signed(Decimal::new(1_250, 2)), "+12.50 KRO");
+        assert_eq!(format_signed(Decimal::new(-1_250, 2)), "\u{2212}12.50 KRO");
+        assert_eq!(format_signed(Decimal::ZERO), "0.00 KRO");
+        assert_eq!(format_signed(Decimal::new(-4, 3)), "0.00 KRO");
+    }
+}
@@ -18,10 +18,14 @@
 
 //! Error types for the core `RSE` stock service
 
-use std::num::NonZeroU64;
-
 use snafu::Snafu;
 
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::model::{MergeConflict, link::IdentityKind, ticker::Ticker};
+
 #[allow(missing_docs)]
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -31,19 +35,132 @@ pub type Result<T> = std::result::Result<T, Error>;
 #[snafu(visibility(pub(crate)))]
 #[allow(missing_docs, variant_size_differences)]
 pub enum Error {
-    /// When a discord snowflake does not fit inside an i64
-    #[snafu(display(r#""{flake}" is not a valid Discord Snowflake"#))]
-    InvalidSnowflake { flake: NonZeroU64 },
     /// An issue with the underlying Database that we either do not know or can't handle
     #[snafu(display("Encountered an internal error"))]
     DatabaseError { source: crate::repo::Error },
     /// When trying to register and there is already an account linked to the provided ID
     #[snafu(display("The provided account already exists"))]
     AccountExists,
-    /// Could not find an account linked to a given ID. Meant for use from external services such
-    /// as Discord or `Chatbox`.
-    #[snafu(display("There is no account linked to passed ID"))]
-    UserNotFound,
+    /// An admin tried to register a Minecraft player who already has an account
+    #[snafu(display("That Minecraft account is already registered to {id}"))]
+    PlayerRegistered { id: Uuid },
+    /// Could not find an account linked to a given identity, of the kind given. Meant for use from
+    /// external services such as Discord or `Chatbox`.
+    #[snafu(display("There is no account linked to {kind}"))]
+    UserNotFound { kind: IdentityKind },
+    /// The user does not have enough Kromer to cover an order
+    #[snafu(display("You do not have enough Kromer to cover this"))]
+    InsufficientFunds,
+    /// The user does not have enough shares to cover an order
+    #[snafu(display("You do not have enough shares to cover this"))]
+    InsufficientShares,
+    /// The requested stock does not exist
+    #[snafu(display(r#"The stock "{ticker}" does not exist"#))]
+    StockNotFound { ticker: Ticker },
+    /// Trading in the stock is halted or it was delisted
+    #[snafu(display("Trading in ${ticker} is currently halted."))]
+    StockHalted { ticker: Ticker },
+    /// There is no open order with the given ID belonging to the user
+    #[snafu(display("You have no open order with ID {id}"))]
+    OrderNotFound { id: i32 },
+    /// An order was rejected before reaching the book
+    #[snafu(display("Invalid order: {reason}"))]
+    InvalidOrder { reason: &'static str },
+    /// An order's price strays too far from the stock's reference price
+    #[snafu(display("That price is too far from the market, the limit is {limit}"))]
+    PriceOutOfBand { limit: Decimal },
+    /// An order was placed outside of trading hours, and orders aren't queued until the open
+    #[snafu(display("The market is closed until {opens_at}"))]
+    MarketClosed { opens_at: DateTime<Utc> },
+    /// A dividend was rejected before being paid
+    #[snafu(display("Invalid dividend: {reason}"))]
+    InvalidDividend { reason: &'static str },
+    /// The user does not own the stock, which owner operations require
+    #[snafu(display(r#"You do not own "{ticker}""#))]
+    NotStockOwner { ticker: Ticker },
+    /// A stock with the given ticker is already listed
+    #[snafu(display(r#"The stock "{ticker}" already exists"#))]
+    StockExists { ticker: Ticker },
+    /// Issuing the requested shares would exceed the daily issuance cap
+    #[snafu(display(
+        "Issuing that many shares would exceed the daily cap, at most {available} more can be issued today"
+    ))]
+    IssuanceCapExceeded { available: u64 },
+    /// The ticker of a new stock is reserved or blocked
+    #[snafu(display(r#"The ticker "{ticker}" can't be used, as {reason}"#))]
+    TickerReserved {
+        ticker: Ticker,
+        reason: &'static str,
+    },
+    /// A stock was rejected before being listed
+    #[snafu(display("Invalid stock: {what} {source}"))]
+    InvalidStock {
+        what: &'static str,
+        source: crate::model::ValueError,
+    },
+    /// A ticker could not be parsed
+    #[snafu(display("Invalid ticker: {source}"))]
+    InvalidTicker {
+        source: crate::model::ticker::ParseError,
+    },
+    /// The details of a stock were rejected before being saved
+    #[snafu(display("Invalid stock details: {reason}"))]
+    InvalidMetadata { reason: &'static str },
+    /// A grant of Kromer was rejected before being credited
+    #[snafu(display("Invalid grant: {reason}"))]
+    InvalidGrant { reason: &'static str },
+    /// A balance adjustment was rejected before being made
+    #[snafu(display("Invalid adjustment: {reason}"))]
+    InvalidAdjustment { reason: &'static str },
+    /// A withdrawal was rejected before being requested
+    #[snafu(display("Invalid withdrawal: {reason}"))]
+    InvalidWithdrawal { reason: &'static str },
+    /// A file of prices to import has more rows than may be imported at once
+    #[snafu(display("That file has more than {max} rows, split it up and import each part"))]
+    ImportTooLarge { max: usize },
+    /// An account can't be closed while it still has open orders, shares, or a balance with
+    /// nowhere to send it
+    #[snafu(display(
+        "Your account still has {open_orders} open orders, shares in {holdings} stocks and {balance} Kromer with no address to send it to"
+    ))]
+    AccountNotEmpty {
+        open_orders: u64,
+        holdings: u64,
+        balance: Decimal,
+    },
+    /// Two accounts can't be merged as they are
+    #[snafu(display("Those accounts can't be merged, as {reason}"))]
+    MergeConflict { reason: MergeConflict },
+    /// There is no pending withdrawal request with the given ID
+    #[snafu(display("There is no pending withdrawal with ID {id}"))]
+    WithdrawalNotFound { id: i64 },
+    /// There is no balance adjustment with the given ID that could be reversed
+    #[snafu(display("There is no adjustment with ID {id}, or it is a reversal itself"))]
+    AdjustmentNotFound { id: i64 },
+    /// The balance adjustment was already reversed, which may only happen once
+    #[snafu(display("Adjustment {id} was already reversed"))]
+    AlreadyReversed { id: i64 },
+    /// Nobody other than the payer holds shares that would receive anything from a dividend
+    #[snafu(display(r#"Nobody else holds enough of "{ticker}" to be paid"#))]
+    NoShareholders { ticker: Ticker },
+    /// A link code doesn't exist, expired or was already used
+    #[snafu(display("That code is invalid, expired or was already used, ask for a new one"))]
+    InvalidLinkCode,
+    /// The idempotency key sent with a request was already used for a different one
+    #[snafu(display("That idempotency key was already used for a different request"))]
+    IdempotencyKeyReused,
+    /// Too many concurrent trades touched the same rows for this one to go through
+    #[snafu(display("The exchange is busy right now, please try again"))]
+    Busy,
+    /// The account did this too often recently, and has to wait before doing it again
+    #[snafu(display("You're doing that too often, try again in {}s", retry_after.as_secs().max(1)))]
+    RateLimited { retry_after: std::time::Duration },
+    /// The account is too new to do this yet
+    #[snafu(display("Your account is too new to do that until {eligible_at}"))]
+    AccountTooNew { eligible_at: DateTime<Utc> },
+    /// The account's privacy hides what was asked for from the viewer
+    #[snafu(display("This user's portfolio is private"))]
+    PrivateAccount,
     /// Thrown only by the [`list_stocks`](super::Service::list_stocks) method. Occurs when there
     /// are no stocks to fetch with a given page.
     #[snafu(display("Currently, no stocks exist"))]
@@ -55,7 +172,221 @@ impl From<crate::repo::Error> for Error {
         use crate::repo::Error as RepError;
         match value {
             RepError::AlreadyLinked => Self::AccountExists,
-            _ => Self::DatabaseError { source: value },
+            RepError::AccountNotFound { .. } => Self::UserNotFound {
+                kind: IdentityKind::Internal,
+            },
+            RepError::InsufficientFunds => Self::InsufficientFunds,
+            RepError::InsufficientShares => Self::InsufficientShares,
+            RepError::StockNotFound { ticker } => Self::StockNotFound { ticker },
+            RepError::StockHalted { ticker } => Self::StockHalted { ticker },
+            RepError::OrderNotFound { id } => Self::OrderNotFound { id },
+            RepError::NotStockOwner { ticker } => Self::NotStockOwner { ticker },
+            RepError::StockExists { ticker } => Self::StockExists { ticker },
+            RepError::IssuanceCapExceeded { available } => Self::IssuanceCapExceeded { available },
+            RepError::NoShareholders { ticker } => Self::NoShareholders { ticker },
+            RepError::WithdrawalNotFound { id } => Self::WithdrawalNotFound { id },
+            RepError::AdjustmentNotFound { id } => Self::AdjustmentNotFound { id },
+            RepError::AlreadyReversed { id } => Self::AlreadyReversed { id },
+            RepError::IdempotencyKeyReused => Self::IdempotencyKeyReused,
+            RepError::InvalidLinkCode => Self::InvalidLinkCode,
+            RepError::Conflict => Self::Busy,
+            RepError::MergeConflict { reason } => Self::MergeConflict { reason },
+            RepError::AccountNotEmpty {
+                open_orders,
+                holdings,
+                balance,
+            } => Self::AccountNotEmpty {
+                open_orders,
+                holdings,
+                balance,
+            },
+            RepError::Unavailable | RepError::Unspecified => Self::DatabaseError { source: value },
+        }
+    }
+}
+
+impl Error {
+    /// A short, stable name for what went wrong, such as `insufficient_funds`, for clients to
+    /// branch on rather than matching the message. Never changes once given out, even if the
+    /// message does.
+    #[must_use]
+    pub const fn code(&self) -> &'static str {
+        // No wildcard, so new variants can't be added without a code
+        match self {
+            Self::DatabaseError { .. } => "internal_error",
+            Self::AccountExists => "account_exists",
+            Self::PlayerRegistered { .. } => "player_registered",
+            Self::UserNotFound { .. } => "user_not_found",
+            Self::InsufficientFunds => "insufficient_funds",
+            Self::InsufficientShares => "insufficient_shares",
+            Self::StockNotFound { .. } => "stock_not_found",
+            Self::StockHalted { .. } => "stock_halted",
+            Self::OrderNotFound { .. } => "order_not_found",
+            Self::InvalidOrder { .. } => "invalid_order",
+            Self::PriceOutOfBand { .. } => "price_out_of_band",
+            Self::MarketClosed { .. } => "market_closed",
+            Self::InvalidDividend { .. } => "invalid_dividend",
+            Self::NotStockOwner { .. } => "not_stock_owner",
+            Self::StockExists { .. } => "stock_exists",
+            Self::IssuanceCapExceeded { .. } => "issuance_cap_exceeded",
+            Self::TickerReserved { .. } => "ticker_reserved",
+            Self::InvalidStock { .. } => "invalid_stock",
+            Self::InvalidTicker { .. } => "invalid_ticker",
+            Self::InvalidMetadata { .. } => "invalid_metadata",
+            Self::InvalidGrant { .. } => "invalid_grant",
+            Self::InvalidAdjustment { .. } => "invalid_adjustment",
+            Self::InvalidWithdrawal { .. } => "invalid_withdrawal",
+            Self::ImportTooLarge { .. } => "import_too_large",
+            Self::AccountNotEmpty { .. } => "account_not_empty",
+            Self::MergeConflict { .. } => "merge_conflict",
+            Self::WithdrawalNotFound { .. } => "withdrawal_not_found",
+            Self::AdjustmentNotFound { .. } => "adjustment_not_found",
+            Self::AlreadyReversed { .. } => "already_reversed",
+            Self::NoShareholders { .. } => "no_shareholders",
+            Self::InvalidLinkCode => "invalid_link_code",
+            Self::IdempotencyKeyReused => "idempotency_key_reused",
+            Self::Busy => "busy",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::AccountTooNew { .. } => "account_too_new",
+            Self::PrivateAccount => "private_account",
+            Self::NoStocksExist => "no_stocks_exist",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::model::{MergeConflict, ValueError, ticker::ParseError};
+
+    use super::*;
+
+    /// One of every variant, which [`listed`] has to be kept in step with
+    fn every_variant() -> Vec<Error> {
+        let ticker = Ticker::try_from("ABC").expect("Valid ticker");
+        let variants = vec![
+            Error::DatabaseError {
+                source: crate::repo::Error::Unspecified,
+            },
+            Error::AccountExists,
+            Error::PlayerRegistered { id: Uuid::nil() },
+            Error::UserNotFound {
+                kind: IdentityKind::Discord,
+            },
+            Error::InsufficientFunds,
+            Error::InsufficientShares,
+            Error::StockNotFound { ticker },
+            Error::StockHalted { ticker },
+            Error::OrderNotFound { id: 1 },
+            Error::InvalidOrder { reason: "" },
+            Error::PriceOutOfBand {
+                limit: Decimal::ONE,
+            },
+            Error::MarketClosed {
+                opens_at: DateTime::UNIX_EPOCH,
+            },
+            Error::InvalidDividend { reason: "" },
+            Error::NotStockOwner { ticker },
+            Error::StockExists { ticker },
+            Error::IssuanceCapExceeded { available: 1 },
+            Error::TickerReserved { ticker, reason: "" },
+            Error::InvalidStock {
+                what: "",
+                source: ValueError::NotPositive,
+            },
+            Error::InvalidTicker {
+                source: ParseError::InvalidLen,
+            },
+            Error::InvalidMetadata { reason: "" },
+            Error::InvalidGrant { reason: "" },
+            Error::InvalidAdjustment { reason: "" },
+            Error::InvalidWithdrawal { reason: "" },
+            Error::ImportTooLarge { max: 1 },
+            Error::AccountNotEmpty {
+                open_orders: 1,
+                holdings: 1,
+                balance: Decimal::ONE,
+            },
+            Error::MergeConflict {
+                reason: MergeConflict::SameAccount,
+            },
+            Error::WithdrawalNotFound { id: 1 },
+            Error::AdjustmentNotFound { id: 1 },
+            Error::AlreadyReversed { id: 1 },
+            Error::NoShareholders { ticker },
+            Error::InvalidLinkCode,
+            Error::IdempotencyKeyReused,
+            Error::Busy,
+            Error::RateLimited {
+                retry_after: std::time::Duration::ZERO,
+            },
+            Error::AccountTooNew {
+                eligible_at: DateTime::UNIX_EPOCH,
+            },
+            Error::PrivateAccount,
+            Error::NoStocksExist,
+        ];
+
+        variants
+    }
+
+    /// Matches without a wildcard, so a new variant fails to compile until it is matched here, as a
+    /// reminder to add it to [`every_variant`] too
+    const fn listed(variant: &Error) {
+        match variant {
+            Error::DatabaseError { .. }
+            | Error::AccountExists
+            | Error::PlayerRegistered { .. }
+            | Error::UserNotFound { .. }
+            | Error::InsufficientFunds
+            | Error::InsufficientShares
+            | Error::StockNotFound { .. }
+            | Error::StockHalted { .. }
+            | Error::OrderNotFound { .. }
+            | Error::InvalidOrder { .. }
+            | Error::PriceOutOfBand { .. }
+            | Error::MarketClosed { .. }
+            | Error::InvalidDividend { .. }
+            | Error::NotStockOwner { .. }
+            | Error::StockExists { .. }
+            | Error::IssuanceCapExceeded { .. }
+            | Error::TickerReserved { .. }
+            | Error::InvalidStock { .. }
+            | Error::InvalidTicker { .. }
+            | Error::InvalidMetadata { .. }
+            | Error::InvalidGrant { .. }
+            | Error::InvalidAdjustment { .. }
+            | Error::InvalidWithdrawal { .. }
+            | Error::ImportTooLarge { .. }
+            | Error::AccountNotEmpty { .. }
+            | Error::MergeConflict { .. }
+            | Error::WithdrawalNotFound { .. }
+            | Error::AdjustmentNotFound { .. }
+            | Error::AlreadyReversed { .. }
+            | Error::NoShareholders { .. }
+            | Error::InvalidLinkCode
+            | Error::IdempotencyKeyReused
+            | Error::Busy
+            | Error::RateLimited { .. }
+            | Error::AccountTooNew { .. }
+            | Error::PrivateAccount
+            | Error::NoStocksExist => {}
+        }
+    }
+
+    #[test]
+    fn every_variant_has_its_own_code() {
+        let variants = every_variant();
+        variants.iter().for_each(listed);
+        let codes: HashSet<_> = variants.iter().map(Error::code).collect();
+
+        assert_eq!(codes.len(), variants.len(), "{codes:?}");
+        for code in codes {
+            assert!(
+                code.chars().all(|c| c.is_ascii_lowercase() || c == '_'),
+                "{code} isn't snake case"
+            );
         }
     }
 }
@@ -0,0 +1,176 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Events the [`Service`](crate::Service) publishes as things happen on the exchange, so that
+//! front ends, metrics and the like can react without being threaded through it.
+//!
+//! Every method that changes the market publishes one event once its change has been committed,
+//! and none if it fails. Events carry everything needed to render them, so subscribers don't
+//! have to query anything back. Delivery is best-effort: events are only kept for subscribers
+//! that exist when they are published, and a subscriber that falls too far behind skips the
+//! oldest ones, seeing [`Lagged`](tokio::sync::broadcast::error::RecvError::Lagged) instead.
+
+use std::num::NonZeroU64;
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::model::{
+    Price, Shares, StockInfo, StockMetadata, StockStatus,
+    adjustment::Adjustment,
+    dividend::Dividend,
+    order::{Fill, Order},
+    reconcile::ReconciliationReport,
+    ticker::Ticker,
+    withdrawal::Withdrawal,
+};
+
+/// Something that happened on the exchange which users may want to hear about
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum Event {
+    /// A new account was registered
+    UserRegistered {
+        /// The new account
+        id: Uuid,
+        /// The Discord user it was linked to, if any
+        disc_id: Option<NonZeroU64>,
+        /// The Minecraft account it was linked to, if any
+        mc_id: Option<Uuid>,
+        /// When it was registered
+        time: DateTime<Utc>,
+    },
+    /// An account was closed and unlinked from whoever it belonged to
+    AccountClosed {
+        /// The account closed
+        id: Uuid,
+        /// When it was closed
+        time: DateTime<Utc>,
+    },
+    /// A stock was listed, with its owner holding every share
+    StockListed(StockInfo),
+    /// A stock that had never traded was given a starting price
+    ListingPriceSet {
+        /// The stock priced
+        ticker: Ticker,
+        /// Its starting price
+        price: Price,
+        /// When the price was set
+        time: DateTime<Utc>,
+    },
+    /// An order was placed and matched against the book as of its creation time. `fills` are the
+    /// trades it executed immediately, and may be empty if it is resting on the book. Orders queued
+    /// while the market is closed are published twice: once queued with no fills, and again when
+    /// they are matched at the open. Immediate-or-cancel orders are published already cancelled if
+    /// the book couldn't fill them
+    OrderPlaced {
+        /// The order as it stands after matching
+        order: Order,
+        /// Each trade executed, at the resting order's price
+        fills: Vec<Fill>,
+    },
+    /// A user cancelled one of their orders, with its escrow released
+    OrderCancelled {
+        /// The order as it stood when cancelled
+        order: Order,
+        /// When it was cancelled
+        time: DateTime<Utc>,
+    },
+    /// An order reached its expiry and was taken off the book, with its escrow released
+    OrderExpired(Order),
+    /// Kromer was credited to an account from outside the exchange
+    Granted {
+        /// The account credited
+        user: Uuid,
+        /// The amount credited
+        amount: Decimal,
+        /// Their balance afterwards
+        balance: Decimal,
+        /// When it was credited
+        time: DateTime<Utc>,
+    },
+    /// An admin adjusted a balance by hand, or reversed an earlier adjustment
+    BalanceAdjusted(Adjustment),
+    /// The owner of a stock handed it to someone else
+    OwnershipTransferred {
+        /// The stock handed over
+        ticker: Ticker,
+        /// The previous owner
+        from: Uuid,
+        /// The new owner
+        to: Uuid,
+        /// When it was handed over
+        time: DateTime<Utc>,
+    },
+    /// The owner of a stock changed its name, description or icon
+    StockMetadataUpdated {
+        /// The stock described
+        ticker: Ticker,
+        /// Its details afterwards
+        metadata: StockMetadata,
+        /// When they were changed
+        time: DateTime<Utc>,
+    },
+    /// The controller of a stock paid a dividend to its holders
+    DividendPaid {
+        /// What was paid, and to how many
+        dividend: Dividend,
+        /// When it was paid
+        time: DateTime<Utc>,
+    },
+    /// The owner of a stock issued new shares
+    SharesIssued {
+        /// The stock issued
+        ticker: Ticker,
+        /// The number of new shares
+        quantity: Shares,
+        /// The total number of shares issued afterwards
+        outstanding: Shares,
+        /// When they were issued
+        time: DateTime<Utc>,
+    },
+    /// The owner of a stock bought back and retired some of its shares
+    SharesBoughtBack {
+        /// The stock bought back
+        ticker: Ticker,
+        /// The number of shares retired
+        quantity: Shares,
+        /// The total number of shares issued afterwards
+        outstanding: Shares,
+        /// When they were retired
+        time: DateTime<Utc>,
+    },
+    /// An admin halted, resumed or delisted a stock
+    StockStatusChanged {
+        /// The stock affected
+        ticker: Ticker,
+        /// Whether it can be traded now
+        status: StockStatus,
+        /// When the status changed
+        time: DateTime<Utc>,
+    },
+    /// A user asked for Kromer to be sent out, which is held until an admin resolves it
+    WithdrawalRequested(Withdrawal),
+    /// An admin approved a withdrawal request, and the Kromer is being sent out
+    WithdrawalApproved(Withdrawal),
+    /// An admin denied a withdrawal request, and its amount was given back to the user
+    WithdrawalDenied(Withdrawal),
+    /// A reconciliation found something in the books that doesn't add up, for admins to look into
+    ReconciliationFailed(ReconciliationReport),
+}
@@ -0,0 +1,328 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Imports of price history kept outside the exchange, such as the records of a market moving onto
+//! it. Only prices are imported, balances and holdings are never touched
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use rust_decimal::Decimal;
+use snafu::ensure;
+
+use crate::{
+    Service,
+    error::{ImportTooLargeSnafu, Result},
+    model::{ImportedPrice, Price, ticker::Ticker},
+    repo::{Error as RepoError, StockRepository},
+};
+
+/// Formats timestamps without an offset may be written in, read as UTC
+const NAIVE_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"];
+
+/// The prices read from a file, and the rows that couldn't be read
+#[derive(Debug, Clone, Default)]
+pub struct PriceFile {
+    /// Every price read, alongside the line it was read from
+    pub prices: Vec<(u64, ImportedPrice)>,
+    /// Rows that were left out
+    pub invalid: Vec<InvalidRow>,
+}
+
+/// A row left out of an import
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidRow {
+    /// The line of the file the row is on
+    pub line: u64,
+    /// Why it was left out
+    pub reason: String,
+}
+
+/// What importing a file of prices did with each of its rows
+#[derive(Debug, Clone, Default)]
+pub struct ImportReport {
+    /// Prices that were recorded
+    pub inserted: u64,
+    /// Prices that had already been imported, for the same stock at the same time
+    pub duplicates: u64,
+    /// Rows that were left out, in the order they appear in the file
+    pub invalid: Vec<InvalidRow>,
+}
+
+impl std::fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} inserted, {} duplicates, {} invalid",
+            self.inserted,
+            self.duplicates,
+            self.invalid.len()
+        )
+    }
+}
+
+impl PriceFile {
+    /// Reads a CSV file of `ticker,timestamp,price,volume` rows, which may start with a header.
+    /// Timestamps are RFC 3339, or `YYYY-MM-DD HH:MM:SS` and `YYYY-MM-DD` in UTC, and volumes are
+    /// whole numbers of shares. Each stock's rows must be in chronological order and before `now`,
+    /// anything else is left out as invalid.
+    ///
+    /// # Errors
+    /// * [`ImportTooLarge`](crate::error::Error::ImportTooLarge) - The file has more than
+    ///   `max_rows` rows
+    pub fn parse(data: &[u8], max_rows: usize, now: DateTime<Utc>) -> Result<Self> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .trim(csv::Trim::All)
+            .comment(Some(b'#'))
+            .from_reader(data);
+
+        let mut file = Self::default();
+        let mut latest = HashMap::new();
+        let mut rows = 0;
+
+        for (i, record) in reader.records().enumerate() {
+            let record = match record {
+                Ok(record) => record,
+                Err(err) => {
+                    let line = err.position().map_or(0, csv::Position::line);
+                    ensure!(rows < max_rows, ImportTooLargeSnafu { max: max_rows });
+                    rows += 1;
+                    file.invalid.push(InvalidRow {
+                        line,
+                        reason: "not valid UTF-8 CSV".to_owned(),
+                    });
+                    continue;
+                }
+            };
+
+            if i == 0
+                && record
+                    .get(0)
+                    .is_some_and(|v| v.eq_ignore_ascii_case("ticker"))
+            {
+                continue;
+            }
+
+            ensure!(rows < max_rows, ImportTooLargeSnafu { max: max_rows });
+            rows += 1;
+
+            let line = record.position().map_or(0, csv::Position::line);
+            let price = parse_row(&record, now).and_then(|price| {
+                let last = latest.entry(price.ticker).or_insert(price.time);
+
+                if price.time < *last {
+                    return Err(format!(
+                        "earlier than the row before it for {}",
+                        price.ticker
+                    ));
+                }
+
+                *last = price.time;
+                Ok(price)
+            });
+
+            match price {
+                Ok(price) => file.prices.push((line, price)),
+                Err(reason) => file.invalid.push(InvalidRow { line, reason }),
+            }
+        }
+
+        Ok(file)
+    }
+}
+
+/// Reads a single row, returning why it is invalid if it is
+fn parse_row(
+    record: &csv::StringRecord,
+    now: DateTime<Utc>,
+) -> std::result::Result<ImportedPrice, String> {
+    let [Some(ticker), Some(time), Some(price), Some(volume), None] =
+        [0, 1, 2, 3, 4].map(|i| record.get(i))
+    else {
+        return Err(format!("expected 4 fields, found {}", record.len()));
+    };
+
+    let ticker = Ticker::try_from(ticker).map_err(|err| format!("invalid ticker: {err}"))?;
+    let time = parse_time(time).ok_or_else(|| "invalid timestamp".to_owned())?;
+
+    if time > now {
+        return Err("timestamp is in the future".to_owned());
+    }
+
+    let price = price
+        .parse::<Decimal>()
+        .map_err(|_| "invalid price".to_owned())
+        .and_then(|price| Price::new(price).map_err(|err| format!("price {err}")))?;
+    let volume = volume
+        .parse::<u64>()
+        .ok()
+        .filter(|v| i64::try_from(*v).is_ok())
+        .ok_or_else(|| "invalid volume, expected a whole number of shares".to_owned())?;
+
+    Ok(ImportedPrice {
+        ticker,
+        time,
+        price,
+        volume,
+    })
+}
+
+fn parse_time(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Some(time.to_utc());
+    }
+
+    if let Some(time) = NAIVE_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    {
+        return Some(time.and_utc());
+    }
+
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .map(|date| date.and_time(NaiveTime::MIN).and_utc())
+}
+
+impl<R: StockRepository> Service<R> {
+    /// Imports the prices read from a file, leaving out those of stocks that aren't listed or
+    /// have no owner to record them against. Prices already imported are skipped, so a file can
+    /// be imported any number of times.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](crate::error::Error::DatabaseError) - An issue with the underlying data
+    ///   store
+    #[tracing::instrument(skip_all, fields(prices = file.prices.len(), invalid = file.invalid.len()))]
+    pub async fn import_prices(&self, file: PriceFile) -> Result<ImportReport> {
+        let PriceFile {
+            prices,
+            mut invalid,
+        } = file;
+        let mut rejected = HashMap::new();
+
+        for (_, price) in &prices {
+            if rejected.contains_key(&price.ticker) {
+                continue;
+            }
+
+            let reason = match self.repo.stock_info(&price.ticker).await {
+                Ok(info) if info.owner.is_some() => None,
+                Ok(_) => Some("the stock has no owner to record prices against"),
+                Err(RepoError::StockNotFound { .. }) => Some("the stock is not listed"),
+                Err(err) => return Err(err.into()),
+            };
+            rejected.insert(price.ticker, reason);
+        }
+
+        let mut valid = Vec::with_capacity(prices.len());
+
+        for (line, price) in prices {
+            match rejected.get(&price.ticker).copied().flatten() {
+                Some(reason) => invalid.push(InvalidRow {
+                    line,
+                    reason: format!("{}: {reason}", price.ticker),
+                }),
+                None => valid.push(price),
+            }
+        }
+
+        invalid.sort_by_key(|row| row.line);
+
+        let inserted = if valid.is_empty() {
+            0
+        } else {
+            self.repo.import_prices(&valid).await?
+        };
+
+        Ok(ImportReport {
+            inserted,
+            duplicates: valid.len() as u64 - inserted,
+            invalid,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2025-10-15T00:00:00Z")
+            .expect("Valid time")
+            .to_utc()
+    }
+
+    #[test]
+    fn rows_are_read_after_a_header() {
+        let data = b"ticker,timestamp,price,volume\n\
+            abc,2025-01-01T12:00:00Z,1.50,10\n\
+            ABC,2025-01-02 12:00:00,2,0\n\
+            XYZ,2025-01-01,3.25,5\n";
+
+        let file = PriceFile::parse(data, 10, now()).expect("Within the cap");
+
+        assert!(file.invalid.is_empty());
+        let lines: Vec<u64> = file.prices.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, [2, 3, 4]);
+
+        let (_, first) = file.prices[0];
+        assert_eq!(first.ticker.as_str(), "ABC");
+        assert_eq!(first.price.get(), Decimal::new(150, 2));
+        assert_eq!(first.volume, 10);
+        assert_eq!(file.prices[2].1.time, now() - chrono::TimeDelta::days(287));
+    }
+
+    #[test]
+    fn bad_rows_are_left_out_with_a_reason() {
+        let data = b"ABC,2025-01-02T00:00:00Z,1,1\n\
+            ABC,2025-01-01T00:00:00Z,1,1\n\
+            ABC,yesterday,1,1\n\
+            ABC,2030-01-01T00:00:00Z,1,1\n\
+            ABC,2025-01-03T00:00:00Z,0,1\n\
+            ABC,2025-01-03T00:00:00Z,1.001,1\n\
+            ABC,2025-01-03T00:00:00Z,1,-1\n\
+            A,2025-01-03T00:00:00Z,1,1\n\
+            ABC,2025-01-03T00:00:00Z,1\n";
+
+        let file = PriceFile::parse(data, 10, now()).expect("Within the cap");
+
+        assert_eq!(file.prices.len(), 1);
+        let lines: Vec<u64> = file.invalid.iter().map(|row| row.line).collect();
+        assert_eq!(lines, [2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(
+            file.invalid[0].reason,
+            "earlier than the row before it for ABC"
+        );
+        assert_eq!(file.invalid[3].reason, "price must be positive");
+    }
+
+    #[test]
+    fn files_over_the_cap_are_refused() {
+        let data = b"ticker,timestamp,price,volume\n\
+            ABC,2025-01-01T00:00:00Z,1,1\n\
+            ABC,2025-01-02T00:00:00Z,1,1\n";
+
+        assert!(PriceFile::parse(data, 2, now()).is_ok());
+        assert_eq!(
+            PriceFile::parse(data, 1, now()).map(|file| file.prices.len()),
+            Err(crate::error::Error::ImportTooLarge { max: 1 })
+        );
+    }
+}
@@ -19,62 +19,472 @@
 //! The core of our system. Includes a generic stock service type which abstracts over our
 //! underlying data stores and notifiers.
 
-use std::num::NonZeroI64;
+use std::{num::NonZeroU64, sync::Arc, time::Duration};
 
 use crate::{
-    error::{DatabaseSnafu, NoStocksExistSnafu, UserNotFoundSnafu},
-    model::{Pager, UserInfo, ticker::Ticker},
+    blocklist::TickerBlocklist,
+    book_cache::{BOOK_TTL, BookCache},
+    calendar::{AfterHours, MarketCalendar},
+    clock::{Clock, SystemClock},
+    error::{
+        AccountTooNewSnafu, DatabaseSnafu, InvalidAdjustmentSnafu, InvalidDividendSnafu,
+        InvalidGrantSnafu, InvalidMetadataSnafu, InvalidOrderSnafu, InvalidWithdrawalSnafu,
+        MarketClosedSnafu, NoShareholdersSnafu, NoStocksExistSnafu, NotStockOwnerSnafu,
+        PlayerRegisteredSnafu, PriceOutOfBandSnafu, PrivateAccountSnafu, TickerReservedSnafu,
+        UserNotFoundSnafu,
+    },
+    event::Event,
+    limiter::{AccountMaturity, Operation, RateLimiter, RateLimits},
+    matching::{PriceBand, sweep_price},
+    model::{
+        AccountMerge, AccountSummary, Candle, CandleInterval, ClosedAccount, ExchangeCounts,
+        HoldingOrdering, HoldingPl, LatestPrice, LedgerKind, Movers, Page, Pager, PoolUsage,
+        PortfolioSnapshot, Price, PriceChange, Privacy, Registered, Shares, Statement,
+        StatementSnapshot, StockInfo, StockMatch, StockMetadata, StockOrdering, StockStatus,
+        UserFilter, UserInfo,
+        adjustment::{Adjustment, AdjustmentReason},
+        api_key::ApiKey,
+        audit::{Actor, AuditEntry, AuditFilter, NewAuditEntry},
+        deposit::{self, Deposit},
+        dividend::{Dividend, DividendPlan, Shareholders},
+        fee::FeeSchedule,
+        guild::GuildSettings,
+        idempotency::{IdempotencyKey, Idempotent},
+        link::{Identity, IdentityKind, LinkCode},
+        order::{Book, Fill, NewOrder, Order, Side, TickerTrade, UserTrade},
+        outbox::Notice,
+        reconcile::{AccountTotals, ReconciliationReport, StockTotals},
+        summary::DailySummary,
+        ticker::{self, Ticker},
+        usage::{CommandStats, CommandUse},
+        withdrawal::{Address, Withdrawal},
+    },
+    outbox::{DispatchPolicy, Notifier},
     repo::StockRepository,
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, DurationRound, NaiveDate, NaiveTime, TimeDelta, Utc};
 use error::Result;
 use rust_decimal::Decimal;
-use snafu::{OptionExt, ResultExt};
+use snafu::{OptionExt, ResultExt, ensure};
+use tokio::sync::broadcast;
+use tracing::{Span, instrument};
 use uuid::Uuid;
 
 #[allow(unused_imports)] // Used for docs
 use error::Error;
 
+pub mod blocklist;
+mod book_cache;
+pub mod calendar;
+pub mod clock;
+pub mod display;
 pub mod error;
+pub mod event;
+pub mod import;
+pub mod limiter;
+pub mod matching;
 pub mod model;
+pub mod outbox;
 pub mod repo;
+pub mod seed;
+pub mod snapshot;
+pub mod task;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+
+/// How many events a slow subscriber can fall behind by before it starts missing them
+const EVENT_CAPACITY: usize = 256;
+
+/// The most expired orders released in a single transaction
+const EXPIRY_CHUNK: u32 = 500;
+
+/// The most queued orders matched in a single transaction when the market opens. Smaller than
+/// [`EXPIRY_CHUNK`], as each one may lock a stock and settle fills
+const RELEASE_CHUNK: u32 = 50;
+
+/// How many stocks are read per query when listing every one of them
+const LIST_CHUNK: i64 = 500;
+
+/// How many price levels of the book a market order looks through to find its price
+const MARKET_DEPTH: u32 = 100;
+
+/// The most candles [`Service::candles`] returns, so a wide range of short intervals stays cheap
+const MAX_CANDLES: i32 = 1000;
+
+/// The most tickers [`Service::search_tickers`] suggests, which is as many as Discord shows
+const TICKER_SUGGESTIONS: u32 = 25;
+
+/// How many accounts or stocks are totalled up per query when reconciling
+const RECONCILE_CHUNK: u32 = 500;
+
+/// How many accounts are snapshotted per query when recording what portfolios are worth
+const SNAPSHOT_CHUNK: u32 = 500;
+
+/// How many recent transactions an [`AccountSummary`] shows
+const SUMMARY_TRANSACTIONS: u32 = 3;
+
+/// The default for how many shares an owner may issue per day, as a percentage of those
+/// outstanding
+const DEFAULT_ISSUANCE_CAP_PCT: u16 = 10;
+
+/// How long a resolved Minecraft username is trusted for, as usernames change hands
+const MC_USERNAME_TTL: TimeDelta = TimeDelta::days(1);
+
+/// How long an idempotency key is remembered for, and so how long a request can be retried
+const IDEMPOTENCY_WINDOW: TimeDelta = TimeDelta::days(1);
+
+/// The fewest characters a search for stocks may be, as shorter ones match nearly everything
+pub const MIN_SEARCH_LEN: usize = 2;
+
+/// How long a code for linking another identity to an account can be used for
+pub const LINK_CODE_TTL: TimeDelta = TimeDelta::minutes(10);
 
 /// A cheaply cloneable service managing our core business logic
 #[derive(Debug, Clone)]
 pub struct Service<R: StockRepository> {
     repo: R,
+    events: broadcast::Sender<Event>,
+    fees: Option<FeeSchedule>,
+    issuance_cap_pct: u16,
+    price_band: Option<PriceBand>,
+    /// Discord users exempt from the price band
+    admins: Arc<[NonZeroU64]>,
+    blocklist: TickerBlocklist,
+    calendar: Option<Arc<MarketCalendar>>,
+    after_hours: AfterHours,
+    clock: Arc<dyn Clock>,
+    books: Arc<BookCache>,
+    limiter: Option<Arc<RateLimiter>>,
+    maturity: Option<AccountMaturity>,
+    /// The Kromer name deposits are sent to, without the `.kro`
+    deposit_name: Option<Arc<str>>,
 }
 
 impl<R: StockRepository> Service<R> {
-    /// Create a new instance of [`Service`]
-    pub const fn new(repo: R) -> Self {
-        Self { repo }
+    /// Create a new instance of [`Service`] backed by `repo`, the only component it needs. Every
+    /// other component is optional and set with the `with_` methods below, defaulting to no fees,
+    /// no price band, no admins, a 10% issuance cap, only the built-in reserved tickers, a market
+    /// that never closes, no rate limits, no holding back of new accounts and the system clock.
+    pub fn new(repo: R) -> Self {
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+
+        Self {
+            repo,
+            events,
+            fees: None,
+            issuance_cap_pct: DEFAULT_ISSUANCE_CAP_PCT,
+            price_band: None,
+            admins: Arc::new([]),
+            blocklist: TickerBlocklist::default(),
+            calendar: None,
+            after_hours: AfterHours::Reject,
+            clock: Arc::new(SystemClock),
+            books: Arc::new(BookCache::new(BOOK_TTL)),
+            limiter: None,
+            maturity: None,
+            deposit_name: None,
+        }
+    }
+
+    /// Charges fees on every trade according to `fees`. Trades are free otherwise. A schedule of
+    /// zero basis points is the same as having none.
+    #[must_use]
+    pub const fn with_fees(mut self, fees: FeeSchedule) -> Self {
+        self.fees = if fees.bps == 0 { None } else { Some(fees) };
+        self
+    }
+
+    /// Caps the shares an owner may issue over any day at `pct` percent of those outstanding.
+    /// Defaults to 10%, a cap of 0 stops issuance entirely.
+    #[must_use]
+    pub const fn with_issuance_cap(mut self, pct: u16) -> Self {
+        self.issuance_cap_pct = pct;
+        self
+    }
+
+    /// Rejects orders priced outside `band` around their stock's reference price. Stocks that have
+    /// never traded are exempt. Orders may be priced anywhere otherwise.
+    #[must_use]
+    pub const fn with_price_band(mut self, band: PriceBand) -> Self {
+        self.price_band = Some(band);
+        self
+    }
+
+    /// Exempts accounts linked to these Discord users from the price band and account maturity
+    #[must_use]
+    pub fn with_admins(mut self, admins: impl IntoIterator<Item = NonZeroU64>) -> Self {
+        self.admins = admins.into_iter().collect();
+        self
+    }
+
+    /// Refuses to list new stocks under `blocklist`. Only the built-in reserved tickers are refused
+    /// otherwise.
+    #[must_use]
+    pub fn with_ticker_blocklist(mut self, blocklist: TickerBlocklist) -> Self {
+        self.blocklist = blocklist;
+        self
+    }
+
+    /// Only matches orders while `calendar` says the market is open, with `after_hours` deciding
+    /// what happens to those placed while it is closed. The market never closes otherwise.
+    #[must_use]
+    pub fn with_calendar(mut self, calendar: MarketCalendar, after_hours: AfterHours) -> Self {
+        self.calendar = Some(Arc::new(calendar));
+        self.after_hours = after_hours;
+        self
+    }
+
+    /// Limits how often each account may trade, look up its own orders and history, or register,
+    /// according to `limits`. Nothing is limited otherwise.
+    #[must_use]
+    pub fn with_rate_limits(mut self, limits: RateLimits) -> Self {
+        self.limiter = Some(Arc::new(RateLimiter::new(limits)));
+        self
+    }
+
+    /// Holds back accounts younger than `maturity` from placing large orders and listing stocks.
+    /// Accounts may do either from the start otherwise.
+    #[must_use]
+    pub const fn with_account_maturity(mut self, maturity: AccountMaturity) -> Self {
+        self.maturity = Some(maturity);
+        self
     }
 
-    /// Gets the UUID of an account from its linked Discord snowflake.
+    /// Takes deposits sent to metanames of the Kromer name `name`, given without the `.kro`, one
+    /// for each account. Deposits aren't taken otherwise.
+    #[must_use]
+    pub fn with_deposit_name(mut self, name: &str) -> Self {
+        self.deposit_name = Some(name.into());
+        self
+    }
+
+    /// Reads the time from `clock`, such as for checking order expiries and timestamping events.
+    /// The system clock is used otherwise.
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    /// The current time, as told by the service's clock
+    #[must_use]
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Fails if `actor` has done `operation` too often recently, and counts this time otherwise
+    fn throttle(&self, actor: Actor, operation: Operation) -> Result<()> {
+        let Some(limiter) = &self.limiter else {
+            return Ok(());
+        };
+
+        limiter
+            .acquire(actor, operation, self.now())
+            .map_err(|retry_after| {
+                tracing::debug!(%actor, ?operation, ?retry_after, "Rate limited");
+                Error::RateLimited { retry_after }
+            })
+    }
+
+    /// Forgets the rate limits of accounts that haven't been limited for a while, returning how
+    /// many were forgotten
+    #[must_use]
+    pub fn prune_rate_limits(&self) -> usize {
+        self.limiter
+            .as_ref()
+            .map_or(0, |limiter| limiter.prune(self.now()))
+    }
+
+    /// When the account `info` describes stops being held back from placing large orders and
+    /// listing stocks. `None` if it isn't held back, as it is old enough or an admin's, or new
+    /// accounts aren't held back at all
+    #[must_use]
+    pub fn matures_at(&self, info: &UserInfo) -> Option<DateTime<Utc>> {
+        let eligible_at = self.maturity?.eligible_at(info.created_at);
+
+        (eligible_at > self.now() && !self.is_admin(info)).then_some(eligible_at)
+    }
+
+    /// Fails if `user` is still held back from doing something worth `notional`, or from doing
+    /// something new accounts may not do at all when it is `None`
+    async fn ensure_mature(&self, user: &Uuid, notional: Option<Decimal>) -> Result<()> {
+        let Some(maturity) = &self.maturity else {
+            return Ok(());
+        };
+
+        if notional.is_some_and(|notional| notional <= maturity.order_cap) {
+            return Ok(());
+        }
+
+        // Missing accounts are left to be reported by whatever they were trying to do
+        let Some(info) = self.repo.user_info(user).await? else {
+            return Ok(());
+        };
+
+        match self.matures_at(&info) {
+            Some(eligible_at) => AccountTooNewSnafu { eligible_at }.fail(),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether the account `info` describes is linked to an admin's Discord account
+    fn is_admin(&self, info: &UserInfo) -> bool {
+        info.disc_id.is_some_and(|id| self.admins.contains(&id))
+    }
+
+    /// When the market is open, if it ever closes
+    #[must_use]
+    pub fn calendar(&self) -> Option<&MarketCalendar> {
+        self.calendar.as_deref()
+    }
+
+    /// When the market next opens, if it is closed right now. [`None`] while it is open.
+    #[must_use]
+    pub fn market_opens_at(&self) -> Option<DateTime<Utc>> {
+        let now = self.now();
+
+        self.calendar
+            .as_deref()
+            .filter(|calendar| !calendar.is_open(now))
+            .map(|calendar| calendar.next_open(now))
+    }
+
+    /// Creates the treasury account that fees are credited to if it doesn't exist yet. Does
+    /// nothing when no fees are charged.
     ///
     /// # Errors
-    /// * [`AccountNotFound`](Error::AccountNotFound) - There is no account linked with the
-    ///   provided Snowflake
     /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
-    pub async fn disc_to_id(&self, id: NonZeroI64) -> Result<Uuid> {
-        self.repo
-            .discord_to_id(id.get())
-            .await?
-            .context(UserNotFoundSnafu)
+    #[instrument(skip(self), level = "debug")]
+    pub async fn ensure_treasury(&self) -> Result<()> {
+        let Some(fees) = self.fees else {
+            return Ok(());
+        };
+
+        if self.repo.ensure_system_account(&fees.treasury).await? {
+            tracing::info!(treasury = %fees.treasury, "Created treasury account");
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the data store can be reached, giving up after `timeout`. Returns how long the
+    /// round trip took.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - The data store couldn't be reached in time
+    #[instrument(skip(self), level = "debug")]
+    pub async fn ping(&self, timeout: Duration) -> Result<Duration> {
+        let start = std::time::Instant::now();
+
+        tokio::time::timeout(timeout, self.repo.ping())
+            .await
+            .map_err(|_| Error::DatabaseError {
+                source: repo::Error::Unavailable,
+            })??;
+
+        Ok(start.elapsed())
+    }
+
+    /// Reports how busy the data store's pool of connections is, or `None` if it doesn't pool them
+    #[must_use]
+    pub fn pool_usage(&self) -> Option<PoolUsage> {
+        self.repo.pool_usage()
+    }
+
+    /// Gets the version of the most recent migration applied to the data store, or `None` if it
+    /// has none
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn schema_version(&self) -> Result<Option<i64>> {
+        Ok(self.repo.schema_version().await?)
+    }
+
+    /// Counts the accounts and stocks on the exchange
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn exchange_counts(&self) -> Result<ExchangeCounts> {
+        Ok(self.repo.exchange_counts().await?)
+    }
+
+    /// Subscribes to the [`Event`]s published by this service from now on. Delivery is
+    /// best-effort, as described in [`event`]
+    #[must_use]
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Gets the UUID of the account `identity` belongs to, checking that it exists when given one
+    /// directly. Takes a single lookup whatever the kind of identity.
+    ///
+    /// # Errors
+    /// * [`UserNotFound`](Error::UserNotFound) - No account is linked to the identity, or has the
+    ///   given ID
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn resolve(&self, identity: &Identity) -> Result<Uuid> {
+        let id = match *identity {
+            Identity::Discord(disc_id) => self.repo.discord_to_id(disc_id).await?,
+            Identity::Minecraft(mc_id) => self.repo.mc_to_id(&mc_id).await?,
+            Identity::Internal(id) => self.repo.user_exists(&id).await?.then_some(id),
+        };
+
+        id.context(UserNotFoundSnafu {
+            kind: identity.kind(),
+        })
+    }
+
+    /// Gets information about the account `identity` belongs to as seen by `viewer`, like
+    /// [`get_account_info`](Self::get_account_info)
+    ///
+    /// # Errors
+    /// * [`UserNotFound`](Error::UserNotFound) - No account is linked to the identity, or has the
+    ///   given ID
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, viewer), level = "debug")]
+    pub async fn account_info_by(
+        &self,
+        identity: &Identity,
+        viewer: Option<&Uuid>,
+    ) -> Result<UserInfo> {
+        let id = match *identity {
+            Identity::Internal(id) => id,
+            Identity::Discord(_) | Identity::Minecraft(_) => self.resolve(identity).await?,
+        };
+
+        self.get_account_info(&id, viewer).await
     }
 
-    /// Gets the UUID of an account from its linked Minecraft UUID
+    /// Gets the Minecraft UUID a username was resolved to, if it was resolved recently enough to
+    /// still be trusted. Usernames are case insensitive.
     ///
     /// # Errors
-    /// * [`AccountNotFound`](Error::AccountNotFound) - There is no account linked with the
-    ///   provided Minecraft account
     /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
-    pub async fn mc_to_id(&self, id: &Uuid) -> Result<Uuid> {
-        self.repo.mc_to_id(id).await?.context(UserNotFoundSnafu)
+    #[instrument(skip(self), level = "debug")]
+    pub async fn mc_username_to_uuid(&self, name: &str) -> Result<Option<Uuid>> {
+        Ok(self
+            .repo
+            .mc_username_to_uuid(name, self.now() - MC_USERNAME_TTL)
+            .await?)
+    }
+
+    /// Remembers that a Minecraft username was just resolved to `id`, for
+    /// [`mc_username_to_uuid`](Self::mc_username_to_uuid)
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn cache_mc_username(&self, name: &str, id: &Uuid) -> Result<()> {
+        Ok(self.repo.cache_mc_username(name, id).await?)
     }
 
-    /// Registers an account, linking it to a given user.
+    /// Registers an account, linking it to a given user, and publishes an
+    /// [`Event::UserRegistered`]. The registration is recorded in the audit log, with the linked ID
+    /// as the actor. If the ID is already linked, the existing account is returned as
+    /// [`Registered::Existing`] instead, which is how racing registrations resolve.
     ///
     /// # Arguments
     /// These arguments should have one [Some] and one [None]. Anything else will panic in debug
@@ -83,59 +493,2029 @@ impl<R: StockRepository> Service<R> {
     /// * `mc_id` - The Minecraft UUID to link to
     ///
     /// # Errors
-    /// * [`AccountExists`](Error::AccountExists) - The provided ID is already linked to an account
+    /// * [`RateLimited`](Error::RateLimited) - The user has registered too many accounts recently
+    /// * [`AccountExists`](Error::AccountExists) - Both IDs were provided and are linked to
+    ///   different accounts
     /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
     pub async fn register_account(
         &self,
-        disc_id: Option<NonZeroI64>,
+        disc_id: Option<NonZeroU64>,
         mc_id: Option<&Uuid>,
-    ) -> Result<Uuid> {
+    ) -> Result<Registered> {
         debug_assert_ne!(
             disc_id.is_some(),
             mc_id.is_some(),
             "Only one of these values should ever be Some"
         );
 
+        let actor = match (disc_id, mc_id) {
+            (Some(disc_id), _) => Actor::Discord(disc_id),
+            (None, Some(mc_id)) => Actor::Minecraft(*mc_id),
+            (None, None) => Actor::System,
+        };
+        self.throttle(actor, Operation::Registration)?;
+
+        self.register(disc_id, mc_id, &actor).await
+    }
+
+    /// Registers an account for the Minecraft player `mc_id` on their behalf, such as an admin
+    /// setting one up for a player not on Discord, and publishes an [`Event::UserRegistered`]. The
+    /// registration is recorded in the audit log with `actor` as the actor. Returns the new account.
+    ///
+    /// # Errors
+    /// * [`PlayerRegistered`](Error::PlayerRegistered) - The player already has an account
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn register_player(&self, mc_id: &Uuid, actor: &Actor) -> Result<Uuid> {
+        match self.register(None, Some(mc_id), actor).await? {
+            Registered::New(id) => Ok(id),
+            Registered::Existing(id) => PlayerRegisteredSnafu { id }.fail(),
+        }
+    }
+
+    /// Registers an account as [`register_account`](Self::register_account) does, recording
+    /// `actor` as who registered it
+    async fn register(
+        &self,
+        disc_id: Option<NonZeroU64>,
+        mc_id: Option<&Uuid>,
+        actor: &Actor,
+    ) -> Result<Registered> {
+        let registered = self.repo.register_user(disc_id, mc_id, actor).await?;
+
+        if let Registered::New(id) = registered {
+            self.publish(Event::UserRegistered {
+                id,
+                disc_id,
+                mc_id: mc_id.copied(),
+                time: self.now(),
+            });
+        }
+
+        Ok(registered)
+    }
+
+    /// Gives an account a one-time code, valid for [`LINK_CODE_TTL`], which proves whoever uses it
+    /// controls the account. Using it from Minecraft with
+    /// [`consume_link_code`](Self::consume_link_code), or from Discord with
+    /// [`consume_discord_link_code`](Self::consume_discord_link_code), links that identity to the
+    /// account. Any code the account was given before and hasn't used stops working. Returns the
+    /// code and when it expires.
+    ///
+    /// # Errors
+    /// * [`UserNotFound`](Error::UserNotFound) - The account does not exist or was closed
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn create_link_code(&self, id: &Uuid) -> Result<(LinkCode, DateTime<Utc>)> {
+        let expires_at = self.now() + LINK_CODE_TTL;
+        let code = self.repo.create_link_code(id, expires_at).await?;
+
+        Ok((code, expires_at))
+    }
+
+    /// Uses up a link code on behalf of the Minecraft player `mc_id`, linking them to the account
+    /// it was given to, which is returned. Each code can only be used once, even when used from two
+    /// places at once.
+    ///
+    /// # Errors
+    /// * [`InvalidLinkCode`](Error::InvalidLinkCode) - The code doesn't exist, expired or was
+    ///   already used
+    /// * [`AccountExists`](Error::AccountExists) - The player is linked to an account already, or
+    ///   the account already has a player
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, code), level = "debug")]
+    pub async fn consume_link_code(&self, code: &LinkCode, mc_id: &Uuid) -> Result<Uuid> {
+        Ok(self
+            .repo
+            .consume_link_code(code, Identity::Minecraft(*mc_id), self.now())
+            .await?)
+    }
+
+    /// Uses up a link code on behalf of the Discord user `disc_id`, linking them to the account it
+    /// was given to, for accounts registered from Minecraft first. See
+    /// [`consume_link_code`](Self::consume_link_code).
+    ///
+    /// # Errors
+    /// * [`InvalidLinkCode`](Error::InvalidLinkCode) - The code doesn't exist, expired or was
+    ///   already used
+    /// * [`AccountExists`](Error::AccountExists) - The user is linked to an account already, or
+    ///   the account already has a Discord user
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, code), level = "debug")]
+    pub async fn consume_discord_link_code(
+        &self,
+        code: &LinkCode,
+        disc_id: NonZeroU64,
+    ) -> Result<Uuid> {
         Ok(self
             .repo
-            .register_user(disc_id.map(NonZeroI64::get), mc_id)
+            .consume_link_code(code, Identity::Discord(disc_id), self.now())
             .await?)
     }
 
-    /// Gets information about a given account
+    /// Forgets link codes that expired before `now`, returning how many there were
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn purge_link_codes(&self, now: DateTime<Utc>) -> Result<u64> {
+        Ok(self.repo.purge_link_codes(now).await?)
+    }
+
+    /// Gives an account a new key to place orders through the REST API with. Any key it was given
+    /// before stops working. Only a hash of the key is kept, so this is the only time it can be
+    /// shown.
+    ///
+    /// # Errors
+    /// * [`UserNotFound`](Error::UserNotFound) - The account does not exist or was closed
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn create_api_key(&self, id: &Uuid) -> Result<ApiKey> {
+        Ok(self.repo.create_api_key(id).await?)
+    }
+
+    /// Finds the account an API key was given to. Returns [`None`] if it was never given out, was
+    /// replaced, or its account was closed.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip_all, level = "debug")]
+    pub async fn api_key_owner(&self, key: &ApiKey) -> Result<Option<Uuid>> {
+        Ok(self.repo.api_key_owner(key).await?)
+    }
+
+    /// Gets information about a given account as seen by `viewer`, the account asking or `None`
+    /// if they don't have one. The balance is left out unless the viewer owns the account or its
+    /// privacy allows it. Admins should view accounts as their owner.
+    ///
+    /// # Errors
+    /// * [`UserNotFound`](Error::UserNotFound) - There is no account linked to ID
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id, viewer), fields(user = %id), level = "debug")]
+    pub async fn get_account_info(&self, id: &Uuid, viewer: Option<&Uuid>) -> Result<UserInfo> {
+        let mut info = self.repo.user_info(id).await?.context(UserNotFoundSnafu {
+            kind: IdentityKind::Internal,
+        })?;
+
+        info.hide_from(viewer);
+
+        Ok(info)
+    }
+
+    /// Gets what others may see of an account
+    ///
+    /// # Errors
+    /// * [`UserNotFound`](Error::UserNotFound) - There is no account linked to ID
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn get_privacy(&self, id: &Uuid) -> Result<Privacy> {
+        Ok(self
+            .repo
+            .user_info(id)
+            .await?
+            .context(UserNotFoundSnafu {
+                kind: IdentityKind::Internal,
+            })?
+            .privacy)
+    }
+
+    /// Sets what others may see of an account
+    ///
+    /// # Errors
+    /// * [`UserNotFound`](Error::UserNotFound) - There is no account linked to ID
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn set_privacy(&self, id: &Uuid, privacy: Privacy) -> Result<()> {
+        Ok(self.repo.set_privacy(id, privacy).await?)
+    }
+
+    /// Fails unless `viewer` owns the account or its privacy lets others see its holdings
+    async fn ensure_holdings_visible(&self, id: &Uuid, viewer: Option<&Uuid>) -> Result<()> {
+        if viewer == Some(id) {
+            return Ok(());
+        }
+
+        ensure!(
+            self.get_privacy(id).await?.shows_holdings(),
+            PrivateAccountSnafu
+        );
+
+        Ok(())
+    }
+
+    /// Sets whether a user lets others show their portfolio in public replies
+    ///
+    /// # Errors
+    /// * [`UserNotFound`](Error::UserNotFound) - There is no account linked to ID
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn set_public_portfolio(&self, id: &Uuid, public: bool) -> Result<()> {
+        Ok(self.repo.set_public_portfolio(id, public).await?)
+    }
+
+    /// Sets whether a user is sent a receipt when their orders fill
     ///
     /// # Errors
     /// * [`UserNotFound`](Error::UserNotFound) - There is no account linked to ID
     /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
-    pub async fn get_account_info(&self, id: &Uuid) -> Result<UserInfo> {
-        self.repo.user_info(id).await?.context(UserNotFoundSnafu)
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn set_receipts(&self, id: &Uuid, enabled: bool) -> Result<()> {
+        Ok(self.repo.set_receipts(id, enabled).await?)
+    }
+
+    /// Resolves accounts back to the Discord snowflake and Minecraft UUID linked to them, in a
+    /// single query. Accounts that don't exist are left out.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, ids), fields(count = ids.len()), level = "debug")]
+    #[allow(clippy::type_complexity)]
+    pub async fn resolve_identities(
+        &self,
+        ids: &[Uuid],
+    ) -> Result<Vec<(Uuid, Option<NonZeroU64>, Option<Uuid>)>> {
+        Ok(self.repo.identities(ids).await?)
     }
 
-    /// Lists all of a user's holdings in a paginated way. Also returns the total number of entries
+    /// Lists all of a user's holdings in a paginated way and sorted by `order`, alongside the most
+    /// recent price of each stock if it has been traded. Also returns the total number of entries.
+    /// `viewer` is the account asking, as in [`get_account_info`](Self::get_account_info)
     ///
     /// # Errors
     /// * [`UserNotFound`](Error::UserNotFound) - There is no account linked to ID
+    /// * [`PrivateAccount`](Error::PrivateAccount) - The account's privacy hides its holdings
     /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
-    pub async fn get_holdings(&self, id: &Uuid, page: &Pager) -> Result<(Vec<(Ticker, u32)>, i64)> {
+    #[instrument(skip(self, id, viewer), fields(user = %id), level = "debug")]
+    #[allow(clippy::type_complexity)]
+    pub async fn get_holdings(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        order: HoldingOrdering,
+        viewer: Option<&Uuid>,
+    ) -> Result<Page<(Ticker, Shares, Option<Price>)>> {
+        self.ensure_holdings_visible(id, viewer).await?;
+
         self.repo
-            .get_holdings(id, page)
+            .get_holdings(id, page, order)
             .await?
-            .context(UserNotFoundSnafu)
+            .context(UserNotFoundSnafu {
+                kind: IdentityKind::Internal,
+            })
     }
 
-    /// Lists all stocks on the market, returning their ticker, most recent sell price, and number
-    /// of shares. Also returns the total number of stocks
+    /// Lists a user's holdings with their average cost and current price, from which unrealized
+    /// profit or loss can be derived, sorted by `order`. Also returns the total number of entries.
+    /// `viewer` is the account asking, as in [`get_account_info`](Self::get_account_info)
     ///
     /// # Errors
+    /// * [`UserNotFound`](Error::UserNotFound) - There is no account linked to ID
+    /// * [`PrivateAccount`](Error::PrivateAccount) - The account's privacy hides its holdings
     /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
-    pub async fn list_stocks(
+    #[instrument(skip(self, id, viewer), fields(user = %id), level = "debug")]
+    pub async fn get_holdings_pl(
         &self,
+        id: &Uuid,
         page: &Pager,
-    ) -> Result<(Vec<(Ticker, u32, Decimal, DateTime<Utc>)>, i64)> {
+        order: HoldingOrdering,
+        viewer: Option<&Uuid>,
+    ) -> Result<Page<HoldingPl>> {
+        self.ensure_holdings_visible(id, viewer).await?;
+
         self.repo
-            .list_stocks(page)
-            .await
-            .context(DatabaseSnafu)?
-            .context(NoStocksExistSnafu)
+            .get_holdings_pl(id, page, order)
+            .await?
+            .context(UserNotFoundSnafu {
+                kind: IdentityKind::Internal,
+            })
+    }
+
+    /// Gets the total value of a user's holdings at the most recent price of each stock. `viewer`
+    /// is the account asking, as in [`get_account_info`](Self::get_account_info)
+    ///
+    /// # Errors
+    /// * [`UserNotFound`](Error::UserNotFound) - There is no account linked to ID
+    /// * [`PrivateAccount`](Error::PrivateAccount) - The account's privacy hides its holdings
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id, viewer), fields(user = %id), level = "debug")]
+    pub async fn get_holdings_value(&self, id: &Uuid, viewer: Option<&Uuid>) -> Result<Decimal> {
+        self.ensure_holdings_visible(id, viewer).await?;
+
+        Ok(self.repo.holdings_value(id).await?)
+    }
+
+    /// Gets the value of many users' holdings at once, in the order `ids` are given, as
+    /// [`get_holdings_value`](Self::get_holdings_value) would for each. Privacy is not checked, so
+    /// this is only for callers that may see every account's holdings
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, ids), fields(users = ids.len()), level = "debug")]
+    pub async fn portfolio_values(&self, ids: &[Uuid]) -> Result<Vec<(Uuid, Decimal)>> {
+        Ok(self.repo.portfolio_values(ids).await?)
+    }
+
+    /// Gets a quick overview of where a user stands: the value of their holdings, how many open
+    /// orders they have, and their last few transactions. Meant for the user themselves, so
+    /// privacy is not checked. Unknown users get an empty summary.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn account_summary(&self, id: &Uuid) -> Result<AccountSummary> {
+        Ok(self.repo.account_summary(id, SUMMARY_TRANSACTIONS).await?)
+    }
+
+    /// Gets a page of a user's statement, newest first, with the running balance each entry left
+    /// behind. Trades are listed alongside ledger entries, and fees on purchases as entries of
+    /// their own. `filter` narrows down which entries are listed, not what the balance counts.
+    /// Meant for the user themselves, so privacy is not checked.
+    ///
+    /// Pass `None` as the `snapshot` for the first page, then the snapshot it returned for every
+    /// later one. Later pages then leave out whatever happened since the first, so the balance on
+    /// the last entry of a page always follows from the first entry of the next.
+    ///
+    /// # Errors
+    /// * [`RateLimited`](Error::RateLimited) - The user has looked up too much recently
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn get_ledger(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        filter: Option<LedgerKind>,
+        snapshot: Option<StatementSnapshot>,
+    ) -> Result<Statement> {
+        self.throttle(Actor::Account(*id), Operation::Lookup)?;
+        Ok(self.repo.statement(id, page, filter, snapshot).await?)
+    }
+
+    /// Lists up to `limit` of the trades a user took part in, oldest first, starting after the
+    /// trade with ID `after`. Pass the ID of the last trade returned to get the next chunk
+    ///
+    /// # Errors
+    /// * [`RateLimited`](Error::RateLimited) - The user has looked up too much recently
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn trade_history(
+        &self,
+        id: &Uuid,
+        after: Option<i32>,
+        limit: i64,
+    ) -> Result<Vec<UserTrade>> {
+        self.throttle(Actor::Account(*id), Operation::Lookup)?;
+        Ok(self.repo.trade_history(id, after, limit).await?)
+    }
+
+    /// Lists the trades of a stock, newest first, with the accounts on either side of each
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn ticker_trades(&self, ticker: &Ticker, page: &Pager) -> Result<Page<TickerTrade>> {
+        Ok(self.repo.ticker_trades(ticker, page).await?)
+    }
+
+    /// Suggests stocks whose ticker starts with `prefix`, alphabetically and with their name if it
+    /// was set. The prefix is matched in any case and with or without a leading `$`, and one that
+    /// no ticker could start with suggests nothing.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn search_tickers(&self, prefix: &str) -> Result<Vec<(Ticker, Option<String>)>> {
+        let trimmed = prefix.trim();
+        let Ok(prefix) = ticker::parse_prefix(trimmed.strip_prefix('$').unwrap_or(trimmed)) else {
+            return Ok(Vec::new());
+        };
+
+        Ok(self
+            .repo
+            .search_tickers(&prefix, TICKER_SUGGESTIONS)
+            .await?)
     }
+
+    /// Searches for stocks by part of their ticker, name or description, or by words close to those
+    /// in their name or description, best matches first. Surrounding whitespace is ignored, and
+    /// nothing is searched for if that leaves less than [`MIN_SEARCH_LEN`] characters.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn search_stocks(&self, query: &str, page: &Pager) -> Result<Page<StockMatch>> {
+        let query = query.trim();
+
+        if query.chars().count() < MIN_SEARCH_LEN {
+            return Ok(Page::new(Vec::new(), 0, page));
+        }
+
+        Ok(self.repo.search_stocks(query, page).await?)
+    }
+
+    /// Lists the stocks on the market whose ticker starts with `prefix`, sorted by `order`,
+    /// returning their ticker, number of shares, the price and time of their last match if they
+    /// have been traded, and name if one was set. Also returns the total number of matching stocks
+    ///
+    /// # Errors
+    /// * [`NoStocksExist`](Error::NoStocksExist) - No stocks match, or the page is past the end
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    #[allow(clippy::type_complexity)]
+    pub async fn list_stocks(
+        &self,
+        page: &Pager,
+        order: StockOrdering,
+        prefix: &str,
+    ) -> Result<
+        Page<(
+            Ticker,
+            Shares,
+            Option<Price>,
+            Option<DateTime<Utc>>,
+            Option<String>,
+        )>,
+    > {
+        self.repo
+            .list_stocks(page, order, prefix)
+            .await
+            .context(DatabaseSnafu)?
+            .context(NoStocksExistSnafu)
+    }
+
+    /// Lists every stock on the market like [`list_stocks`](Self::list_stocks), for callers that
+    /// need all of them rather than a page. Read a chunk at a time, so a stock listed partway
+    /// through may be left out. An empty market lists no stocks rather than failing.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    #[allow(clippy::type_complexity)]
+    pub async fn all_stocks(
+        &self,
+        order: StockOrdering,
+    ) -> Result<
+        Vec<(
+            Ticker,
+            Shares,
+            Option<Price>,
+            Option<DateTime<Utc>>,
+            Option<String>,
+        )>,
+    > {
+        let mut page = Pager::new(0, LIST_CHUNK);
+        let mut stocks = Vec::new();
+
+        while let Some(chunk) = self.repo.list_stocks(&page, order, "").await? {
+            let done = chunk.items.len() < usize::try_from(LIST_CHUNK).unwrap_or(usize::MAX);
+            stocks.extend(chunk.items);
+
+            if done {
+                break;
+            }
+            page.add_offset(LIST_CHUNK);
+        }
+
+        Ok(stocks)
+    }
+
+    /// Gets up to `count` of the stocks whose price rose the most since their latest close, and
+    /// up to `count` whose price fell the most. Stocks that haven't closed yet are compared to
+    /// their price a day ago, or to their oldest price if first traded since.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn top_movers(&self, count: u8) -> Result<Movers> {
+        let now = self.now();
+
+        Ok(self.repo.top_movers(now.date_naive(), now, count).await?)
+    }
+
+    /// Summarizes trading over the UTC day `date`: how much was traded, the biggest trade, and
+    /// the top 3 gainers and losers
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn daily_summary(&self, date: NaiveDate) -> Result<DailySummary> {
+        Ok(self.repo.daily_summary(date).await?)
+    }
+
+    /// Whether the summary of `date` has already been posted
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn summary_sent(&self, date: NaiveDate) -> Result<bool> {
+        Ok(self.repo.summary_sent(date).await?)
+    }
+
+    /// Records that the summary of `date` has been posted, so it isn't posted again
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn record_summary_sent(&self, date: NaiveDate) -> Result<()> {
+        Ok(self.repo.record_summary_sent(date).await?)
+    }
+
+    /// Records the close of every traded stock for the UTC day `date`, which price changes are
+    /// measured from afterwards. Closing a day twice does nothing. Returns how many stocks closed.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn record_daily_closes(&self, date: NaiveDate) -> Result<u64> {
+        Ok(self.repo.record_daily_closes(date).await?)
+    }
+
+    /// Snapshots what every open account is worth right now as of the UTC day `date`, a chunk of
+    /// accounts at a time. Accounts already snapshotted for `date` are skipped, so it can be run
+    /// again after a restart. Returns how many snapshots were recorded.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store.
+    ///   Chunks recorded before the failure are kept
+    #[instrument(skip(self), level = "debug")]
+    pub async fn record_portfolio_snapshots(&self, date: NaiveDate) -> Result<u64> {
+        let mut after = None;
+        let mut recorded = 0;
+
+        loop {
+            let (last, count) = self
+                .repo
+                .record_portfolio_snapshots(date, after.as_ref(), SNAPSHOT_CHUNK)
+                .await?;
+            recorded += count;
+
+            match last {
+                Some(last) => after = Some(last),
+                None => return Ok(recorded),
+            }
+        }
+    }
+
+    /// Gets what a user was worth at the end of each day since `since`, oldest first. Starts
+    /// from when they registered if that was later, and skips days no snapshot was taken on.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn portfolio_history(
+        &self,
+        id: &Uuid,
+        since: NaiveDate,
+    ) -> Result<Vec<PortfolioSnapshot>> {
+        Ok(self.repo.portfolio_history(id, since).await?)
+    }
+
+    /// Gets how the price of `ticker` moved since its latest close, or over the last day if it
+    /// hasn't closed yet. Returns [`None`] if it never traded.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn price_change(&self, ticker: &Ticker) -> Result<Option<PriceChange>> {
+        Ok(self.repo.price_change(ticker, self.now()).await?)
+    }
+
+    /// Gets the price history of `ticker` over the last `range` as candles of `interval`, oldest
+    /// first. Intervals without trades have no candle. The range is cut down to the most recent
+    /// thousand intervals, and to nothing if it's negative, then stretched back to the start of
+    /// the interval it begins in so the oldest candle covers its whole interval.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn candles(
+        &self,
+        ticker: &Ticker,
+        interval: CandleInterval,
+        range: TimeDelta,
+    ) -> Result<Vec<Candle>> {
+        let range = range.clamp(TimeDelta::zero(), interval.duration() * MAX_CANDLES);
+        let now = self.now();
+        // Candles are bucketed from the epoch, so the range starts on a bucket boundary too
+        let from = now - range;
+        let from = from.duration_trunc(interval.duration()).unwrap_or(from);
+
+        Ok(self.repo.candles(ticker, interval, from, now).await?)
+    }
+
+    /// Records an action in the audit log. Actions that change state through the [`Service`] are
+    /// already recorded, so this is meant for things like admin commands that only read data.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn record_audit(&self, entry: &NewAuditEntry) -> Result<()> {
+        Ok(self.repo.record_audit(entry).await?)
+    }
+
+    /// Lists accounts matching `filter`, in the order it asks for, along with the total number of
+    /// matching accounts. Balances are left out unless `show_balances` is set. Meant for admins,
+    /// so callers must check who is asking.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn list_users(
+        &self,
+        page: &Pager,
+        filter: UserFilter,
+        show_balances: bool,
+    ) -> Result<Page<UserInfo>> {
+        let mut users = self.repo.list_users(page, filter).await?;
+
+        if !show_balances {
+            for user in &mut users.items {
+                user.hide_balances();
+            }
+        }
+
+        Ok(users)
+    }
+
+    /// Lists audit log entries, newest first, optionally restricted by `filter`. Also returns the
+    /// total number of matching entries
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn get_audit_log(
+        &self,
+        page: &Pager,
+        filter: Option<AuditFilter>,
+    ) -> Result<Page<AuditEntry>> {
+        Ok(self
+            .repo
+            .audit_log(page, &filter.unwrap_or_default())
+            .await?)
+    }
+
+    /// Places a limit order and matches it against the book, then publishes an
+    /// [`Event::OrderPlaced`]. Whatever isn't filled immediately rests on the book until it is
+    /// filled, cancelled, or reaches `expires_at`. The Kromer or shares needed to cover the order
+    /// are held in escrow until then. Buyers also pay the fee on every fill, if any.
+    ///
+    /// While the market is closed, orders are either rejected or, if the service queues them,
+    /// returned as [`Queued`](model::order::OrderStatus::Queued) with no fills, to be matched by
+    /// [`release_queued_orders`](Self::release_queued_orders) once it opens.
+    ///
+    /// # Errors
+    /// * [`RateLimited`](Error::RateLimited) - The user has traded too often recently
+    /// * [`MarketClosed`](Error::MarketClosed) - The market is closed, and orders aren't queued
+    /// * [`InvalidOrder`](Error::InvalidOrder) - The expiry is not in the future
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`StockHalted`](Error::StockHalted) - Trading in the stock is halted or it was delisted
+    /// * [`PriceOutOfBand`](Error::PriceOutOfBand) - The price is outside the price band, and the
+    ///   user is not an admin
+    /// * [`AccountTooNew`](Error::AccountTooNew) - The order is worth more than the user's account
+    ///   is old enough to trade, and the user is not an admin
+    /// * [`UserNotFound`](Error::UserNotFound) - There is no account linked to ID
+    /// * [`InsufficientFunds`](Error::InsufficientFunds) - The user's available balance can't cover
+    ///   a buy order and its fee
+    /// * [`InsufficientShares`](Error::InsufficientShares) - The user can't cover a sell order
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, user), fields(user = %user, ticker = %ticker), level = "debug")]
+    pub async fn place_order(
+        &self,
+        user: &Uuid,
+        ticker: &Ticker,
+        side: Side,
+        price: Price,
+        quantity: Shares,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<(Order, Vec<Fill>)> {
+        let order = NewOrder {
+            user: *user,
+            ticker: *ticker,
+            side,
+            price,
+            quantity,
+            expires_at,
+            immediate_or_cancel: false,
+        };
+
+        self.enter_order(&order).await
+    }
+
+    /// Checks, places and publishes `order` on behalf of [`place_order`](Self::place_order) and
+    /// [`place_market_order`](Self::place_market_order)
+    async fn enter_order(&self, order: &NewOrder) -> Result<(Order, Vec<Fill>)> {
+        self.throttle(Actor::Account(order.user), Operation::Trade)?;
+        let queue = self.check_order(order).await?;
+
+        let (order, fills) = if queue {
+            let order = self
+                .repo
+                .queue_order(order, self.fees.as_ref(), self.now())
+                .await?;
+            (order, Vec::new())
+        } else {
+            self.repo
+                .place_order(order, self.fees.as_ref(), self.now())
+                .await?
+        };
+        self.publish(Event::OrderPlaced {
+            order,
+            fills: fills.clone(),
+        });
+
+        Ok((order, fills))
+    }
+
+    /// Places an order like [`place_order`](Self::place_order), unless the user already sent
+    /// `key` within the last day. Retrying the same order with the same key replays what placing
+    /// it returned the first time rather than placing it again, even while the first attempt is
+    /// still in progress. Only orders actually placed publish an [`Event::OrderPlaced`].
+    ///
+    /// # Errors
+    /// * [`RateLimited`](Error::RateLimited) - The user has traded too often recently
+    /// * [`IdempotencyKeyReused`](Error::IdempotencyKeyReused) - The user sent the key with a
+    ///   different order within the last day
+    /// * Any error [`place_order`](Self::place_order) returns
+    #[instrument(skip(self, order), fields(user = %order.user, ticker = %order.ticker), level = "debug")]
+    pub async fn place_order_once(
+        &self,
+        key: &IdempotencyKey,
+        order: &NewOrder,
+    ) -> Result<Idempotent<(Order, Vec<Fill>)>> {
+        self.throttle(Actor::Account(order.user), Operation::Trade)?;
+        let queue = self.check_order(order).await?;
+
+        let now = self.now();
+        let placed = self
+            .repo
+            .place_order_once(
+                key,
+                order,
+                self.fees.as_ref(),
+                now - IDEMPOTENCY_WINDOW,
+                queue,
+                now,
+            )
+            .await?;
+
+        if let Idempotent::Executed((order, fills)) = &placed {
+            self.publish(Event::OrderPlaced {
+                order: *order,
+                fills: fills.clone(),
+            });
+        }
+
+        Ok(placed)
+    }
+
+    /// Places a market order, which trades `quantity` shares right away at the best prices on the
+    /// book and never rests on it. It is placed as an immediate-or-cancel limit order at the worst
+    /// price needed to fill it, so it is checked, escrowed and published like one, and whatever the
+    /// book couldn't fill is cancelled along with it. Market orders are never queued while the
+    /// market is closed.
+    ///
+    /// # Errors
+    /// * [`MarketClosed`](Error::MarketClosed) - The market is closed
+    /// * [`InvalidOrder`](Error::InvalidOrder) - There are no orders on the other side of the book
+    ///   to trade with
+    /// * Any error [`place_order`](Self::place_order) returns
+    #[instrument(skip(self, user), fields(user = %user, ticker = %ticker), level = "debug")]
+    pub async fn place_market_order(
+        &self,
+        user: &Uuid,
+        ticker: &Ticker,
+        side: Side,
+        quantity: Shares,
+    ) -> Result<(Order, Vec<Fill>)> {
+        if let Some(calendar) = self.calendar.as_deref() {
+            let now = self.now();
+            ensure!(
+                calendar.is_open(now),
+                MarketClosedSnafu {
+                    opens_at: calendar.next_open(now)
+                }
+            );
+        }
+
+        // The user's own orders would be skipped by matching, so they mustn't set the price either
+        let levels = self
+            .repo
+            .levels_against(ticker, side, user, MARKET_DEPTH, self.now())
+            .await?;
+        let price = sweep_price(&levels, quantity)
+            .and_then(|price| Price::new(price).ok())
+            .context(InvalidOrderSnafu {
+                reason: "there are no orders to trade with",
+            })?;
+
+        self.enter_order(&NewOrder {
+            user: *user,
+            ticker: *ticker,
+            side,
+            price,
+            quantity,
+            expires_at: None,
+            immediate_or_cancel: true,
+        })
+        .await
+    }
+
+    /// Forgets idempotency keys sent more than a day before `now`, returning how many there were
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn purge_idempotency_keys(&self, now: DateTime<Utc>) -> Result<u64> {
+        Ok(self
+            .repo
+            .purge_idempotency_keys(now - IDEMPOTENCY_WINDOW)
+            .await?)
+    }
+
+    /// Rejects orders placed outside trading hours unless they can be queued, expiring in the past,
+    /// priced outside the band by anyone but an admin, or worth more than new accounts may trade.
+    /// Returns whether the order should be queued until the open
+    async fn check_order(&self, order: &NewOrder) -> Result<bool> {
+        let now = self.now();
+        let queue = match self.calendar.as_deref() {
+            Some(calendar) if !calendar.is_open(now) => {
+                ensure!(
+                    self.after_hours == AfterHours::Queue && !order.immediate_or_cancel,
+                    MarketClosedSnafu {
+                        opens_at: calendar.next_open(now)
+                    }
+                );
+                true
+            }
+            _ => false,
+        };
+
+        ensure!(
+            order.expires_at.is_none_or(|v| v > now),
+            InvalidOrderSnafu {
+                reason: "expiry must be in the future"
+            }
+        );
+
+        if let Some(band) = &self.price_band {
+            self.ensure_in_band(band, &order.user, &order.ticker, order.price)
+                .await?;
+        }
+
+        let notional = order.price.get() * Decimal::from(order.quantity.get());
+        self.ensure_mature(&order.user, Some(notional)).await?;
+
+        Ok(queue)
+    }
+
+    /// Checks `price` against the band around the stock's reference price, which admins may
+    /// ignore
+    async fn ensure_in_band(
+        &self,
+        band: &PriceBand,
+        user: &Uuid,
+        ticker: &Ticker,
+        price: Price,
+    ) -> Result<()> {
+        let now = self.now();
+        let since = TimeDelta::from_std(band.lookback)
+            .ok()
+            .and_then(|lookback| now.checked_sub_signed(lookback))
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let close = now.date_naive().and_time(NaiveTime::MIN).and_utc();
+
+        let Some(reference) = self.repo.reference_price(ticker, since, close).await? else {
+            return Ok(());
+        };
+
+        let Some(limit) = band.breached(price, reference) else {
+            return Ok(());
+        };
+
+        let admin = !self.admins.is_empty()
+            && self
+                .repo
+                .user_info(user)
+                .await?
+                .is_some_and(|info| self.is_admin(&info));
+        ensure!(admin, PriceOutOfBandSnafu { limit });
+
+        Ok(())
+    }
+
+    /// Cancels one of a user's open orders, releasing whatever remains of its escrow, and publishes
+    /// an [`Event::OrderCancelled`]
+    ///
+    /// # Errors
+    /// * [`RateLimited`](Error::RateLimited) - The user has traded too often recently
+    /// * [`OrderNotFound`](Error::OrderNotFound) - The user has no open order with this ID
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, user), fields(user = %user), level = "debug")]
+    pub async fn cancel_order(&self, id: i32, user: &Uuid) -> Result<Order> {
+        self.throttle(Actor::Account(*user), Operation::Trade)?;
+        let order = self.repo.cancel_order(id, user).await?;
+        self.publish(Event::OrderCancelled {
+            order,
+            time: self.now(),
+        });
+
+        Ok(order)
+    }
+
+    /// Takes every open order that expired at or before `now` off the book, releasing its escrow
+    /// and publishing an [`Event::OrderExpired`] for it. Work is done in chunks of bounded size, each
+    /// in its own transaction, so a large backlog never holds locks for long. Running it again for
+    /// the same `now` does nothing, as expired orders are no longer open. Returns the number of
+    /// orders expired.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store. Chunks
+    ///   committed before the failure stay expired
+    #[instrument(skip(self), level = "debug")]
+    pub async fn expire_orders(&self, now: DateTime<Utc>) -> Result<usize> {
+        let mut total = 0;
+
+        loop {
+            let expired = self.repo.expire_orders(now, EXPIRY_CHUNK).await?;
+            let count = expired.len();
+            total += count;
+
+            for order in expired {
+                // Nobody listening is fine, there is just nobody to notify
+                self.publish(Event::OrderExpired(order));
+            }
+
+            if count < EXPIRY_CHUNK as usize {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Matches the orders queued while the market was closed, oldest first, if it is open now.
+    /// Publishes an [`Event::OrderPlaced`] for each as it reaches the book. Work is done in chunks
+    /// of bounded size, each in its own transaction. Returns the number of orders released.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store. Chunks
+    ///   committed before the failure stay released
+    #[instrument(skip(self), level = "debug")]
+    pub async fn release_queued_orders(&self) -> Result<usize> {
+        if self.market_opens_at().is_some() {
+            return Ok(0);
+        }
+
+        let mut total = 0;
+
+        loop {
+            let released = self
+                .repo
+                .release_queued_orders(RELEASE_CHUNK, self.fees.as_ref(), self.now())
+                .await?;
+            let count = released.len();
+            total += count;
+
+            for (order, fills) in released {
+                self.publish(Event::OrderPlaced { order, fills });
+            }
+
+            if count < RELEASE_CHUNK as usize {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// Checks that the books add up: that every account's Kromer matches its ledger and trades,
+    /// that no stock has more shares held than were issued, and that what is in escrow matches the
+    /// open orders. Accounts and stocks are totalled up in chunks, each in its own query, so no
+    /// long-running transaction is held. Anything that doesn't add up is logged as an error and
+    /// published in an [`Event::ReconciliationFailed`], but never fixed.
+    ///
+    /// Each check only compares totals read in the same query, so trading while this runs can't
+    /// cause false alarms.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn reconcile(&self) -> Result<ReconciliationReport> {
+        let mut report = ReconciliationReport::new(self.now());
+        let mut after = None;
+
+        loop {
+            let chunk = self
+                .repo
+                .account_totals(after.as_ref(), RECONCILE_CHUNK)
+                .await?;
+            report.accounts += chunk.len() as u64;
+            report
+                .discrepancies
+                .extend(chunk.iter().flat_map(AccountTotals::discrepancies));
+
+            match chunk.last() {
+                Some(last) if chunk.len() == RECONCILE_CHUNK as usize => after = Some(last.user),
+                _ => break,
+            }
+        }
+
+        let mut after = None;
+
+        loop {
+            let chunk = self
+                .repo
+                .stock_totals(after.as_ref(), RECONCILE_CHUNK)
+                .await?;
+            report.stocks += chunk.len() as u64;
+            report
+                .discrepancies
+                .extend(chunk.iter().flat_map(StockTotals::discrepancies));
+
+            match chunk.last() {
+                Some(last) if chunk.len() == RECONCILE_CHUNK as usize => after = Some(last.ticker),
+                _ => break,
+            }
+        }
+
+        for discrepancy in &report.discrepancies {
+            tracing::error!(?discrepancy, "{discrepancy}");
+        }
+
+        if !report.is_clean() {
+            let _ = self
+                .events
+                .send(Event::ReconciliationFailed(report.clone()));
+        }
+
+        Ok(report)
+    }
+
+    /// Records a batch of finished bot command invocations
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, uses), fields(uses = uses.len()), level = "debug")]
+    pub async fn record_command_uses(&self, uses: &[CommandUse]) -> Result<()> {
+        if uses.is_empty() {
+            return Ok(());
+        }
+
+        Ok(self.repo.record_command_uses(uses).await?)
+    }
+
+    /// How every bot command was used over the last day and week, the most used first
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn command_stats(&self) -> Result<Vec<CommandStats>> {
+        Ok(self.repo.command_stats(self.now()).await?)
+    }
+
+    /// Delivers one batch of due outbox notices through `notifier`, marking each sent once it has
+    /// been delivered. Failed notices are tried again later following `policy`, and parked with an
+    /// error logged once they run out of attempts or can't be decoded at all. Returns the number
+    /// of notices delivered.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store. Notices
+    ///   claimed but not yet resolved are claimed again once their lease runs out
+    #[instrument(skip(self, notifier, policy), level = "debug")]
+    pub async fn dispatch_outbox<N: Notifier>(
+        &self,
+        notifier: &N,
+        policy: &DispatchPolicy,
+        now: DateTime<Utc>,
+    ) -> Result<usize> {
+        let entries = self
+            .repo
+            .claim_outbox(now, policy.lease_until(now), policy.batch)
+            .await?;
+        let mut delivered = 0;
+
+        for entry in entries {
+            let attempt = entry.attempts.saturating_add(1);
+
+            let (err, retry_at) = match serde_json::from_value::<Notice>(entry.payload) {
+                Ok(notice) => match notifier.deliver(&notice).await {
+                    Ok(()) => {
+                        self.repo.outbox_sent(entry.id).await?;
+                        delivered += 1;
+                        continue;
+                    }
+                    Err(err) => (err.to_string(), policy.retry_at(attempt, now)),
+                },
+                // Retrying won't make it decode
+                Err(err) => (format!("Malformed notice: {err}"), None),
+            };
+
+            if retry_at.is_some() {
+                tracing::warn!(id = entry.id, attempt, %err, "Couldn't deliver notice, retrying later");
+            } else {
+                tracing::error!(id = entry.id, attempt, %err, "Couldn't deliver notice, parking it");
+            }
+
+            self.repo.outbox_failed(entry.id, &err, retry_at).await?;
+        }
+
+        Ok(delivered)
+    }
+
+    /// Lists a user's open orders, newest first. Also returns the total number of open orders
+    ///
+    /// # Errors
+    /// * [`RateLimited`](Error::RateLimited) - The user has looked up too much recently
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, user), fields(user = %user), level = "debug")]
+    pub async fn open_orders(&self, user: &Uuid, page: &Pager) -> Result<Page<Order>> {
+        self.throttle(Actor::Account(*user), Operation::Lookup)?;
+        Ok(self.repo.open_orders(user, page).await?)
+    }
+
+    /// Gets information about a stock, including who owns it
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), fields(ticker = %ticker), level = "debug")]
+    pub async fn get_stock_info(&self, ticker: &Ticker) -> Result<StockInfo> {
+        Ok(self.repo.stock_info(ticker).await?)
+    }
+
+    /// Gets the number of shares issued for a stock and who holds them
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), fields(ticker = %ticker), level = "debug")]
+    pub async fn get_shareholders(&self, ticker: &Ticker) -> Result<Shareholders> {
+        Ok(self.repo.shareholders(ticker).await?)
+    }
+
+    /// Checks that `user` owns a stock. Meant to gate owner operations before doing any work, as
+    /// the operations themselves check again when they run.
+    ///
+    /// # Errors
+    /// * [`NotStockOwner`](Error::NotStockOwner) - The user doesn't own the stock
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, user), fields(user = %user, ticker = %ticker), level = "debug")]
+    pub async fn assert_stock_owner(&self, user: &Uuid, ticker: &Ticker) -> Result<()> {
+        let info = self.repo.stock_info(ticker).await?;
+
+        ensure!(
+            info.owner.as_ref() == Some(user),
+            NotStockOwnerSnafu { ticker: *ticker }
+        );
+
+        Ok(())
+    }
+
+    /// Checks that a new stock may be listed under `ticker`, which is refused when it is reserved
+    /// or blocked. Stocks already listed are never checked again.
+    ///
+    /// # Errors
+    /// * [`TickerReserved`](Error::TickerReserved) - The ticker is reserved or blocked
+    #[instrument(skip(self), level = "debug")]
+    pub fn validate_new_ticker(&self, ticker: &Ticker) -> Result<()> {
+        match self.blocklist.check(ticker) {
+            Some(refusal) => TickerReservedSnafu {
+                ticker: *ticker,
+                reason: refusal.reason(),
+            }
+            .fail(),
+            None => Ok(()),
+        }
+    }
+
+    /// Lists a new stock owned by `owner`, who starts out holding every share, and publishes an
+    /// [`Event::StockListed`]. The listing is recorded in the audit log under `actor`.
+    ///
+    /// # Errors
+    /// * [`TickerReserved`](Error::TickerReserved) - The ticker is reserved or blocked
+    /// * [`AccountTooNew`](Error::AccountTooNew) - The owner's account is too new to list stocks,
+    ///   and an admin isn't listing it for them
+    /// * [`StockExists`](Error::StockExists) - A stock with this ticker already exists
+    /// * [`UserNotFound`](Error::UserNotFound) - The owner does not have an account
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, owner), fields(user = %owner, ticker = %ticker), level = "debug")]
+    pub async fn create_stock(
+        &self,
+        ticker: &Ticker,
+        shares: Shares,
+        owner: &Uuid,
+        actor: &Actor,
+    ) -> Result<StockInfo> {
+        self.validate_new_ticker(ticker)?;
+
+        if !matches!(actor, Actor::Discord(id) if self.admins.contains(id)) {
+            self.ensure_mature(owner, None).await?;
+        }
+
+        let info = self.repo.create_stock(ticker, shares, owner, actor).await?;
+        self.publish(Event::StockListed(info.clone()));
+
+        Ok(info)
+    }
+
+    /// Sets the price of a stock that has never traded, returning whether it was set and publishing
+    /// an [`Event::ListingPriceSet`] if so. Once a stock has a price, whether from trading or an
+    /// earlier listing price, this does nothing.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), fields(ticker = %ticker), level = "debug")]
+    pub async fn set_listing_price(&self, ticker: &Ticker, price: Price) -> Result<bool> {
+        let set = self.repo.set_listing_price(ticker, price).await?;
+
+        if set {
+            self.publish(Event::ListingPriceSet {
+                ticker: *ticker,
+                price,
+                time: self.now(),
+            });
+        }
+
+        Ok(set)
+    }
+
+    /// Credits `amount` Kromer to a user from outside the exchange, returning their new balance and
+    /// publishing an [`Event::Granted`]. The grant is recorded in the audit log under `actor`.
+    ///
+    /// # Errors
+    /// * [`InvalidGrant`](Error::InvalidGrant) - The amount is out of range
+    /// * [`UserNotFound`](Error::UserNotFound) - The user does not have an account
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn grant(&self, id: &Uuid, amount: Decimal, actor: &Actor) -> Result<Decimal> {
+        validate_grant(amount)?;
+
+        let balance = self.repo.grant(id, amount, actor).await?;
+        self.publish(Event::Granted {
+            user: *id,
+            amount,
+            balance,
+            time: self.now(),
+        });
+
+        Ok(balance)
+    }
+
+    /// The address Kromer can be sent to to deposit it to a user's account, as in
+    /// `<metaname>@<name>.kro`, or [`None`] if deposits aren't taken. See
+    /// [`with_deposit_name`](Self::with_deposit_name).
+    #[must_use]
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub fn deposit_address(&self, id: &Uuid) -> Option<String> {
+        let name = self.deposit_name.as_deref()?;
+        Some(format!("{}@{name}.kro", deposit::metaname(id)))
+    }
+
+    /// Credits a deposit sent to `wallet` to the account it was for, returning their new balance
+    /// and publishing an [`Event::Granted`], or returning [`None`] if its transaction has already
+    /// been credited. `wallet`'s cursor moves up to the deposit's transaction along with it.
+    ///
+    /// # Errors
+    /// * [`InvalidGrant`](Error::InvalidGrant) - The amount is out of range
+    /// * [`UserNotFound`](Error::UserNotFound) - The user does not have an open account
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, deposit), fields(user = %deposit.user, transaction = deposit.transaction), level = "debug")]
+    pub async fn credit_deposit(
+        &self,
+        wallet: &Address,
+        deposit: &Deposit,
+    ) -> Result<Option<Decimal>> {
+        validate_grant(deposit.amount)?;
+
+        let Some(balance) = self.repo.credit_deposit(wallet, deposit).await? else {
+            return Ok(None);
+        };
+
+        self.publish(Event::Granted {
+            user: deposit.user,
+            amount: deposit.amount,
+            balance,
+            time: self.now(),
+        });
+
+        Ok(Some(balance))
+    }
+
+    /// Records a deposit sent to `wallet` that can't be credited, as its account is closed or
+    /// doesn't exist or its amount is out of range, in the audit log as an
+    /// [`UncreditedDeposit`](model::audit::Action::UncreditedDeposit) for an admin to send back.
+    /// `wallet`'s cursor moves up to the deposit's transaction along with it.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, deposit), fields(user = %deposit.user, transaction = deposit.transaction), level = "debug")]
+    pub async fn record_uncredited_deposit(
+        &self,
+        wallet: &Address,
+        deposit: &Deposit,
+    ) -> Result<()> {
+        Ok(self.repo.record_uncredited_deposit(wallet, deposit).await?)
+    }
+
+    /// Fetches the ID of the newest transaction of `wallet` already scanned for deposits, if it
+    /// has ever been scanned
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), fields(wallet = %wallet), level = "debug")]
+    pub async fn kromer_cursor(&self, wallet: &Address) -> Result<Option<i64>> {
+        Ok(self.repo.kromer_cursor(wallet).await?)
+    }
+
+    /// Moves `wallet`'s cursor up to `transaction` once everything up to it has been scanned for
+    /// deposits, so scanning picks up after it from then on. Never moves it back.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), fields(wallet = %wallet), level = "debug")]
+    pub async fn advance_kromer_cursor(&self, wallet: &Address, transaction: i64) -> Result<()> {
+        Ok(self.repo.advance_kromer_cursor(wallet, transaction).await?)
+    }
+
+    /// Adds `delta` Kromer to a user's balance by hand, or takes it away if negative, publishing an
+    /// [`Event::BalanceAdjusted`]. The adjustment is written to the ledger under `reason` and
+    /// recorded in the audit log under `actor`, and its ID can be passed to
+    /// [`reverse_adjustment`](Self::reverse_adjustment) to undo it. Taking more than the user has
+    /// needs `allow_negative`, which should be reserved for the most trusted admins.
+    ///
+    /// # Errors
+    /// * [`InvalidAdjustment`](Error::InvalidAdjustment) - The delta is zero or out of range
+    /// * [`UserNotFound`](Error::UserNotFound) - The user does not have an account
+    /// * [`InsufficientFunds`](Error::InsufficientFunds) - The balance would go below zero without
+    ///   `allow_negative`
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn admin_adjust_balance(
+        &self,
+        id: &Uuid,
+        delta: Decimal,
+        reason: AdjustmentReason,
+        allow_negative: bool,
+        actor: &Actor,
+    ) -> Result<Adjustment> {
+        validate_adjustment(delta)?;
+
+        let adjustment = self
+            .repo
+            .adjust_balance(id, delta, reason, allow_negative, actor)
+            .await?;
+        self.publish(Event::BalanceAdjusted(adjustment));
+
+        Ok(adjustment)
+    }
+
+    /// Undoes an adjustment by making an equal and opposite one linked to it, publishing an
+    /// [`Event::BalanceAdjusted`]. Each adjustment can only be reversed once, and reversing one
+    /// that credited Kromer the user has since spent needs `allow_negative`.
+    ///
+    /// # Errors
+    /// * [`AdjustmentNotFound`](Error::AdjustmentNotFound) - There is no adjustment with the ID,
+    ///   or it is a reversal itself
+    /// * [`AlreadyReversed`](Error::AlreadyReversed) - The adjustment was already reversed
+    /// * [`InsufficientFunds`](Error::InsufficientFunds) - The balance would go below zero without
+    ///   `allow_negative`
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn reverse_adjustment(
+        &self,
+        id: i64,
+        allow_negative: bool,
+        actor: &Actor,
+    ) -> Result<Adjustment> {
+        let adjustment = self
+            .repo
+            .reverse_adjustment(id, allow_negative, actor)
+            .await?;
+        self.publish(Event::BalanceAdjusted(adjustment));
+
+        Ok(adjustment)
+    }
+
+    /// Sets whether a stock can be traded, recording the change in the audit log under `actor` and
+    /// publishing an [`Event::StockStatusChanged`]. Resting orders stay on the book, but can't
+    /// match while trading is halted.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), fields(ticker = %ticker), level = "debug")]
+    pub async fn set_stock_status(
+        &self,
+        ticker: &Ticker,
+        status: StockStatus,
+        actor: &Actor,
+    ) -> Result<StockInfo> {
+        let info = self.repo.set_stock_status(ticker, status, actor).await?;
+        self.publish(Event::StockStatusChanged {
+            ticker: *ticker,
+            status,
+            time: self.now(),
+        });
+
+        Ok(info)
+    }
+
+    /// Hands a stock owned by `owner` to `new_owner`, recording the transfer in the audit log and
+    /// publishing an [`Event::OwnershipTransferred`]. The owner's shares stay where they are.
+    ///
+    /// # Errors
+    /// * [`NotStockOwner`](Error::NotStockOwner) - `owner` doesn't own the stock
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`UserNotFound`](Error::UserNotFound) - The new owner does not have an account
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, owner), fields(user = %owner, ticker = %ticker), level = "debug")]
+    pub async fn transfer_ownership(
+        &self,
+        ticker: &Ticker,
+        owner: &Uuid,
+        new_owner: &Uuid,
+    ) -> Result<StockInfo> {
+        let info = self
+            .repo
+            .transfer_ownership(ticker, owner, new_owner)
+            .await?;
+        self.publish(Event::OwnershipTransferred {
+            ticker: *ticker,
+            from: *owner,
+            to: *new_owner,
+            time: self.now(),
+        });
+
+        Ok(info)
+    }
+
+    /// Replaces the name, description and icon of a stock owned by `owner`, recording the change in
+    /// the audit log and publishing an [`Event::StockMetadataUpdated`]. Each field is trimmed, and
+    /// blank ones are cleared.
+    ///
+    /// # Errors
+    /// * [`InvalidMetadata`](Error::InvalidMetadata) - A field is too long, or the icon is not an
+    ///   `https` URL
+    /// * [`NotStockOwner`](Error::NotStockOwner) - `owner` doesn't own the stock
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, owner, metadata), fields(user = %owner, ticker = %ticker), level = "debug")]
+    pub async fn update_stock_metadata(
+        &self,
+        ticker: &Ticker,
+        owner: &Uuid,
+        metadata: StockMetadata,
+    ) -> Result<StockInfo> {
+        let metadata = validate_metadata(metadata)?;
+        let info = self
+            .repo
+            .update_stock_metadata(ticker, &metadata, owner)
+            .await?;
+        self.publish(Event::StockMetadataUpdated {
+            ticker: *ticker,
+            metadata,
+            time: self.now(),
+        });
+
+        Ok(info)
+    }
+
+    /// Issues `quantity` new shares of a stock to its owner, then publishes an
+    /// [`Event::SharesIssued`]. Shares issued over the last day may not exceed the issuance cap
+    /// set with [`with_issuance_cap`](Self::with_issuance_cap).
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`NotStockOwner`](Error::NotStockOwner) - `owner` doesn't own the stock
+    /// * [`IssuanceCapExceeded`](Error::IssuanceCapExceeded) - The issuance would go over the cap
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, owner), fields(user = %owner, ticker = %ticker), level = "debug")]
+    pub async fn issue_shares(
+        &self,
+        ticker: &Ticker,
+        quantity: Shares,
+        owner: &Uuid,
+    ) -> Result<StockInfo> {
+        let info = self
+            .repo
+            .issue_shares(ticker, quantity, owner, self.issuance_cap_pct)
+            .await?;
+
+        // Nobody listening is fine, there is just nobody to notify
+        self.publish(Event::SharesIssued {
+            ticker: *ticker,
+            quantity,
+            outstanding: info.shares,
+            time: self.now(),
+        });
+
+        Ok(info)
+    }
+
+    /// Retires `quantity` of the owner's shares of a stock, then publishes an
+    /// [`Event::SharesBoughtBack`]. Shares held in escrow by open orders can't be bought back.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`NotStockOwner`](Error::NotStockOwner) - `owner` doesn't own the stock
+    /// * [`InsufficientShares`](Error::InsufficientShares) - The owner doesn't hold enough shares
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, owner), fields(user = %owner, ticker = %ticker), level = "debug")]
+    pub async fn buyback(
+        &self,
+        ticker: &Ticker,
+        quantity: Shares,
+        owner: &Uuid,
+    ) -> Result<StockInfo> {
+        let info = self.repo.buyback(ticker, quantity, owner).await?;
+
+        // Nobody listening is fine, there is just nobody to notify
+        self.publish(Event::SharesBoughtBack {
+            ticker: *ticker,
+            quantity,
+            outstanding: info.shares,
+            time: self.now(),
+        });
+
+        Ok(info)
+    }
+
+    /// Works out what a dividend of `per_share` from `payer` would cost and who it would pay,
+    /// without paying it. Each holder's entitlement is rounded down to the cent, with the
+    /// remainder staying with the payer.
+    ///
+    /// # Errors
+    /// * [`InvalidDividend`](Error::InvalidDividend) - The amount per share is out of range
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`NotStockOwner`](Error::NotStockOwner) - The payer doesn't own the stock
+    /// * [`NoShareholders`](Error::NoShareholders) - Nobody else would receive anything
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, payer), fields(user = %payer, ticker = %ticker), level = "debug")]
+    pub async fn quote_dividend(
+        &self,
+        ticker: &Ticker,
+        per_share: Decimal,
+        payer: &Uuid,
+    ) -> Result<DividendPlan> {
+        validate_dividend(per_share)?;
+        self.assert_stock_owner(payer, ticker).await?;
+
+        let shareholders = self.repo.shareholders(ticker).await?;
+        let plan = shareholders.plan_dividend(payer, per_share);
+        ensure!(
+            !plan.payouts.is_empty(),
+            NoShareholdersSnafu { ticker: *ticker }
+        );
+
+        Ok(plan)
+    }
+
+    /// Pays a dividend of `per_share` from `payer` to every other holder of a stock, then
+    /// publishes an [`Event::DividendPaid`]. Only the owner of the stock may pay one. See
+    /// [`quote_dividend`](Self::quote_dividend) for how payouts are worked out.
+    ///
+    /// # Errors
+    /// * [`InvalidDividend`](Error::InvalidDividend) - The amount per share is out of range
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`NotStockOwner`](Error::NotStockOwner) - The payer doesn't own the stock
+    /// * [`NoShareholders`](Error::NoShareholders) - Nobody else would receive anything
+    /// * [`InsufficientFunds`](Error::InsufficientFunds) - The payer can't cover the dividend
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, payer), fields(user = %payer, ticker = %ticker), level = "debug")]
+    pub async fn pay_dividend(
+        &self,
+        ticker: &Ticker,
+        per_share: Decimal,
+        payer: &Uuid,
+    ) -> Result<Dividend> {
+        validate_dividend(per_share)?;
+
+        let dividend = self.repo.pay_dividend(ticker, per_share, payer).await?;
+
+        // Nobody listening is fine, there is just nobody to notify
+        self.publish(Event::DividendPaid {
+            dividend,
+            time: self.now(),
+        });
+
+        Ok(dividend)
+    }
+
+    /// Gets up to `depth` price levels of each side of a stock's order book, aggregated by price,
+    /// alongside the price of the most recent trade
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), fields(ticker = %ticker, cached), level = "debug")]
+    pub async fn order_book(&self, ticker: &Ticker, depth: u8) -> Result<Book> {
+        let depth = depth.into();
+        let generation = match self.books.get(*ticker, depth) {
+            Ok(book) => {
+                Span::current().record("cached", true);
+                return Ok(book);
+            }
+            Err(generation) => generation,
+        };
+
+        Span::current().record("cached", false);
+        let book = self.repo.book(ticker, depth).await?;
+        self.books.insert(*ticker, depth, book.clone(), generation);
+
+        Ok(book)
+    }
+
+    /// Gets the price of the most recent trade of each of `tickers`, or of every stock if
+    /// [`None`], ordered by ticker. Stocks that have never traded are left out
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn latest_prices(&self, tickers: Option<&[Ticker]>) -> Result<Vec<LatestPrice>> {
+        Ok(self.repo.latest_prices(tickers).await?)
+    }
+
+    /// Asks for `amount` of a user's Kromer to be sent to `address`, publishing an
+    /// [`Event::WithdrawalRequested`]. The amount is held out of their balance until an admin
+    /// approves or denies the request.
+    ///
+    /// # Errors
+    /// * [`InvalidWithdrawal`](Error::InvalidWithdrawal) - The amount is out of range
+    /// * [`InsufficientFunds`](Error::InsufficientFunds) - The user's available balance can't
+    ///   cover it
+    /// * [`UserNotFound`](Error::UserNotFound) - The user does not have an account
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn request_withdrawal(
+        &self,
+        id: &Uuid,
+        amount: Decimal,
+        address: &Address,
+        actor: &Actor,
+    ) -> Result<Withdrawal> {
+        validate_withdrawal(amount)?;
+
+        let withdrawal = self
+            .repo
+            .request_withdrawal(id, amount, address, actor)
+            .await?;
+        self.publish(Event::WithdrawalRequested(withdrawal));
+
+        Ok(withdrawal)
+    }
+
+    /// Asks for `amount` of a user's Kromer to be sent to `address` on their own behalf. Once an
+    /// admin approves it, it is paid out from the exchange's wallet. See
+    /// [`request_withdrawal`](Self::request_withdrawal).
+    ///
+    /// # Errors
+    /// See [`request_withdrawal`](Self::request_withdrawal)
+    #[instrument(skip(self, user), fields(user = %user), level = "debug")]
+    pub async fn withdraw(
+        &self,
+        user: &Uuid,
+        amount: Decimal,
+        address: &Address,
+    ) -> Result<Withdrawal> {
+        self.request_withdrawal(user, amount, address, &Actor::Account(*user))
+            .await
+    }
+
+    /// Closes a user's account at their request, unlinking it so the same Discord user or
+    /// Minecraft player can register afresh later. The account must have no open orders or shares
+    /// left. Any balance is held in a final withdrawal request to `payout`, and an
+    /// [`Event::AccountClosed`] is published along with an [`Event::WithdrawalRequested`] if so.
+    ///
+    /// # Errors
+    /// * [`AccountNotEmpty`](Error::AccountNotEmpty) - The account has open orders or shares left,
+    ///   or a balance but no `payout` address
+    /// * [`UserNotFound`](Error::UserNotFound) - The user does not have an account
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn close_account(
+        &self,
+        id: &Uuid,
+        payout: Option<&Address>,
+    ) -> Result<ClosedAccount> {
+        let closed = self
+            .repo
+            .close_account(id, payout, false, &Actor::Account(*id))
+            .await?;
+        self.publish_closure(id, &closed);
+
+        Ok(closed)
+    }
+
+    /// Closes an account like [`close_account`](Self::close_account) on behalf of an admin, with
+    /// the closure recorded in the audit log under `actor`. With `force` set, its open orders are
+    /// cancelled and its shares retired at the most recent price of each stock first, crediting
+    /// the proceeds, and an [`Event::OrderCancelled`] is published for every order cancelled.
+    ///
+    /// # Errors
+    /// * [`AccountNotEmpty`](Error::AccountNotEmpty) - The account has open orders or shares left,
+    ///   which only happens when forced if it holds every share of a stock, or a balance but no
+    ///   `payout` address
+    /// * [`UserNotFound`](Error::UserNotFound) - The user does not have an account
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self, id), fields(user = %id), level = "debug")]
+    pub async fn admin_close_account(
+        &self,
+        id: &Uuid,
+        payout: Option<&Address>,
+        force: bool,
+        actor: &Actor,
+    ) -> Result<ClosedAccount> {
+        let closed = self.repo.close_account(id, payout, force, actor).await?;
+        self.publish_closure(id, &closed);
+
+        Ok(closed)
+    }
+
+    /// Merges a duplicate account into another on behalf of an admin, moving its balance, shares,
+    /// history, stocks and links onto `survivor` before closing it. Shares of a stock both hold
+    /// are added together. The merge is recorded in the audit log under `actor`, and an
+    /// [`Event::AccountClosed`] is published for the casualty.
+    ///
+    /// With `dry_run` set nothing changes, but what would move is returned after making the same
+    /// checks.
+    ///
+    /// # Errors
+    /// * [`MergeConflict`](Error::MergeConflict) - Both accounts are linked to a Discord user or
+    ///   to a Minecraft player, or the casualty has open orders or a pending withdrawal
+    /// * [`UserNotFound`](Error::UserNotFound) - Either account does not exist or was closed
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn merge_accounts(
+        &self,
+        survivor: &Uuid,
+        casualty: &Uuid,
+        dry_run: bool,
+        actor: &Actor,
+    ) -> Result<AccountMerge> {
+        let merge = self
+            .repo
+            .merge_accounts(survivor, casualty, dry_run, actor)
+            .await?;
+
+        if !dry_run {
+            self.publish(Event::AccountClosed {
+                id: *casualty,
+                time: self.now(),
+            });
+        }
+
+        Ok(merge)
+    }
+
+    /// Publishes `event` to subscribers, first dropping any cached order book it changed
+    fn publish(&self, event: Event) {
+        self.books.observe(&event);
+        let _ = self.events.send(event);
+    }
+
+    fn publish_closure(&self, id: &Uuid, closed: &ClosedAccount) {
+        let time = self.now();
+
+        for order in &closed.cancelled {
+            self.publish(Event::OrderCancelled {
+                order: *order,
+                time,
+            });
+        }
+        if let Some(withdrawal) = closed.withdrawal {
+            self.publish(Event::WithdrawalRequested(withdrawal));
+        }
+        self.publish(Event::AccountClosed { id: *id, time });
+    }
+
+    /// Lists pending withdrawal requests, oldest first
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn pending_withdrawals(&self, page: &Pager) -> Result<Page<Withdrawal>> {
+        Ok(self.repo.pending_withdrawals(page).await?)
+    }
+
+    /// Approves a pending withdrawal request, publishing an [`Event::WithdrawalApproved`]. It is
+    /// then paid out by whatever [claims payouts](Self::claim_payout).
+    ///
+    /// # Errors
+    /// * [`WithdrawalNotFound`](Error::WithdrawalNotFound) - The request is not pending
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn approve_withdrawal(&self, id: i64, actor: &Actor) -> Result<Withdrawal> {
+        let withdrawal = self.repo.resolve_withdrawal(id, true, actor).await?;
+        self.publish(Event::WithdrawalApproved(withdrawal));
+
+        Ok(withdrawal)
+    }
+
+    /// Denies a pending withdrawal request, giving its amount back to the user and publishing an
+    /// [`Event::WithdrawalDenied`]
+    ///
+    /// # Errors
+    /// * [`WithdrawalNotFound`](Error::WithdrawalNotFound) - The request is not pending
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn deny_withdrawal(&self, id: i64, actor: &Actor) -> Result<Withdrawal> {
+        let withdrawal = self.repo.resolve_withdrawal(id, false, actor).await?;
+        self.publish(Event::WithdrawalDenied(withdrawal));
+
+        Ok(withdrawal)
+    }
+
+    /// Claims the oldest approved withdrawal not yet paid out, if there is one, to be sent from
+    /// the exchange's wallet. A claimed withdrawal is never handed out again unless
+    /// [released](Self::release_payout), so it can't be paid twice.
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn claim_payout(&self) -> Result<Option<Withdrawal>> {
+        Ok(self.repo.claim_payout().await?)
+    }
+
+    /// Records the Kromer transaction a claimed withdrawal was paid out in
+    ///
+    /// # Errors
+    /// * [`WithdrawalNotFound`](Error::WithdrawalNotFound) - The payout isn't claimed, or was
+    ///   already recorded
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn record_payout(&self, id: i64, transaction: i64) -> Result<()> {
+        Ok(self.repo.record_payout(id, transaction).await?)
+    }
+
+    /// Gives up a claimed payout that couldn't be sent, so it is claimed again later
+    ///
+    /// # Errors
+    /// * [`WithdrawalNotFound`](Error::WithdrawalNotFound) - The payout isn't claimed, or was
+    ///   already recorded
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn release_payout(&self, id: i64) -> Result<()> {
+        Ok(self.repo.release_payout(id).await?)
+    }
+
+    /// Gets the settings of a Discord server, or the defaults if it never changed any
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn guild_settings(&self, guild: NonZeroU64) -> Result<GuildSettings> {
+        Ok(self.repo.guild_settings(guild).await?.unwrap_or_default())
+    }
+
+    /// Gets the settings of every Discord server that changed any
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn all_guild_settings(&self) -> Result<Vec<(NonZeroU64, GuildSettings)>> {
+        Ok(self.repo.all_guild_settings().await?)
+    }
+
+    /// Replaces the settings of a Discord server. Meant for admins, so callers must check who is
+    /// asking
+    ///
+    /// # Errors
+    /// * [`DatabaseError`](Error::DatabaseError) - An issue with the underlying data store
+    #[instrument(skip(self), level = "debug")]
+    pub async fn set_guild_settings(
+        &self,
+        guild: NonZeroU64,
+        settings: &GuildSettings,
+    ) -> Result<()> {
+        Ok(self.repo.set_guild_settings(guild, settings).await?)
+    }
+}
+
+fn validate_dividend(per_share: Decimal) -> Result<()> {
+    ensure!(
+        per_share > Decimal::ZERO,
+        InvalidDividendSnafu {
+            reason: "amount per share must be positive"
+        }
+    );
+    ensure!(
+        per_share < Decimal::from(100_000_000_000_000_i64),
+        InvalidDividendSnafu {
+            reason: "amount per share is too large"
+        }
+    );
+
+    Ok(())
+}
+
+fn validate_metadata(metadata: StockMetadata) -> Result<StockMetadata> {
+    fn clean(field: Option<String>) -> Option<String> {
+        field.map(|s| s.trim().to_owned()).filter(|s| !s.is_empty())
+    }
+
+    let metadata = StockMetadata {
+        name: clean(metadata.name),
+        description: clean(metadata.description),
+        icon_url: clean(metadata.icon_url),
+    };
+
+    ensure!(
+        metadata
+            .name
+            .as_ref()
+            .is_none_or(|s| s.chars().count() <= StockMetadata::MAX_NAME),
+        InvalidMetadataSnafu {
+            reason: "the name can be at most 64 characters"
+        }
+    );
+    ensure!(
+        metadata
+            .description
+            .as_ref()
+            .is_none_or(|s| s.chars().count() <= StockMetadata::MAX_DESCRIPTION),
+        InvalidMetadataSnafu {
+            reason: "the description can be at most 500 characters"
+        }
+    );
+    ensure!(
+        metadata.icon_url.as_deref().is_none_or(|url| {
+            url.len() <= StockMetadata::MAX_ICON_URL
+                && url
+                    .strip_prefix("https://")
+                    .is_some_and(|rest| !rest.is_empty() && !rest.starts_with('/'))
+                && !url.chars().any(|c| c.is_whitespace() || c.is_control())
+        }),
+        InvalidMetadataSnafu {
+            reason: "the icon must be an https:// link"
+        }
+    );
+
+    Ok(metadata)
+}
+
+fn validate_grant(amount: Decimal) -> Result<()> {
+    ensure!(
+        amount > Decimal::ZERO,
+        InvalidGrantSnafu {
+            reason: "amount must be positive"
+        }
+    );
+    ensure!(
+        amount.normalize().scale() <= 2,
+        InvalidGrantSnafu {
+            reason: "amount can have at most 2 decimal places"
+        }
+    );
+    ensure!(
+        amount < Decimal::from(100_000_000_000_000_i64),
+        InvalidGrantSnafu {
+            reason: "amount is too large"
+        }
+    );
+
+    Ok(())
+}
+
+fn validate_adjustment(delta: Decimal) -> Result<()> {
+    ensure!(
+        !delta.is_zero(),
+        InvalidAdjustmentSnafu {
+            reason: "delta can't be zero"
+        }
+    );
+    ensure!(
+        delta.normalize().scale() <= 2,
+        InvalidAdjustmentSnafu {
+            reason: "delta can have at most 2 decimal places"
+        }
+    );
+    ensure!(
+        delta.abs() < Decimal::from(100_000_000_000_000_i64),
+        InvalidAdjustmentSnafu {
+            reason: "delta is too large"
+        }
+    );
+
+    Ok(())
+}
+
+fn validate_withdrawal(amount: Decimal) -> Result<()> {
+    ensure!(
+        amount > Decimal::ZERO,
+        InvalidWithdrawalSnafu {
+            reason: "amount must be positive"
+        }
+    );
+    ensure!(
+        amount.normalize().scale() <= 2,
+        InvalidWithdrawalSnafu {
+            reason: "amount can have at most 2 decimal places"
+        }
+    );
+    ensure!(
+        amount < Decimal::from(100_000_000_000_000_i64),
+        InvalidWithdrawalSnafu {
+            reason: "amount is too large"
+        }
+    );
+
+    Ok(())
 }
@@ -0,0 +1,264 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//! Per-account rate limits on what the service does, so a script submitting orders in a loop
+//! can't hog the matching engine or the ledger. Each account gets a token bucket per kind of
+//! [`Operation`], which refills steadily and lets short bursts through.
+//!
+//! New accounts are also held back until they reach an [`AccountMaturity`], so throwaway accounts
+//! can't be used to dodge those limits or push small stocks around.
+
+use std::{num::NonZeroU32, time::Duration};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use dashmap::DashMap;
+use rust_decimal::Decimal;
+
+use crate::model::audit::Actor;
+
+/// The kinds of operation limited separately from each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// Placing and cancelling orders
+    Trade,
+    /// Looking up an account's own orders, statement and history
+    Lookup,
+    /// Registering a new account
+    Registration,
+}
+
+/// How many times something may be done over a period. Up to `count` at once, after which one
+/// more is allowed every `per / count`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    /// How many times it may be done
+    pub count: NonZeroU32,
+    /// Over how long
+    pub per: Duration,
+}
+
+impl Rate {
+    /// Allows `count` a minute
+    #[must_use]
+    pub const fn per_minute(count: NonZeroU32) -> Self {
+        Self {
+            count,
+            per: Duration::from_mins(1),
+        }
+    }
+
+    /// Allows `count` an hour
+    #[must_use]
+    pub const fn per_hour(count: NonZeroU32) -> Self {
+        Self {
+            count,
+            per: Duration::from_hours(1),
+        }
+    }
+}
+
+/// How often each account may do each kind of [`Operation`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimits {
+    /// Orders placed or cancelled
+    pub trades: Rate,
+    /// Lookups of an account's own orders, statement and history
+    pub lookups: Rate,
+    /// Accounts registered
+    pub registrations: Rate,
+}
+
+impl RateLimits {
+    /// The defaults for Discord, where every request is typed out by a person
+    pub const DISCORD: Self = Self {
+        trades: Rate::per_minute(NonZeroU32::new(30).expect("Not zero")),
+        lookups: Rate::per_minute(NonZeroU32::new(60).expect("Not zero")),
+        registrations: Rate::per_hour(NonZeroU32::new(5).expect("Not zero")),
+    };
+
+    /// The defaults for API keys, which are used by scripts and so are held to less
+    pub const API: Self = Self {
+        trades: Rate::per_minute(NonZeroU32::new(10).expect("Not zero")),
+        lookups: Rate::per_minute(NonZeroU32::new(30).expect("Not zero")),
+        registrations: Rate::per_hour(NonZeroU32::new(2).expect("Not zero")),
+    };
+
+    const fn rate(&self, operation: Operation) -> Rate {
+        match operation {
+            Operation::Trade => self.trades,
+            Operation::Lookup => self.lookups,
+            Operation::Registration => self.registrations,
+        }
+    }
+}
+
+/// What accounts may not do until they are `age` old: place orders worth more than `order_cap`,
+/// or list stocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountMaturity {
+    /// How old an account has to be to no longer be held back
+    pub age: TimeDelta,
+    /// The most a single order from a newer account may be worth, in Kromer
+    pub order_cap: Decimal,
+}
+
+impl AccountMaturity {
+    /// When an account created at `created_at` stops being held back
+    #[must_use]
+    pub fn eligible_at(&self, created_at: DateTime<Utc>) -> DateTime<Utc> {
+        created_at
+            .checked_add_signed(self.age)
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+/// Every account's buckets. A bucket is kept as the time it will next be full, which is all a
+/// steadily refilling bucket needs, and dropped once that has passed.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limits: RateLimits,
+    buckets: DashMap<(Actor, Operation), DateTime<Utc>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limits: RateLimits) -> Self {
+        Self {
+            limits,
+            buckets: DashMap::new(),
+        }
+    }
+
+    /// Takes a token from `actor`'s bucket for `operation` at `now`, or returns how long until
+    /// one can be taken if it is empty
+    pub(crate) fn acquire(
+        &self,
+        actor: Actor,
+        operation: Operation,
+        now: DateTime<Utc>,
+    ) -> Result<(), Duration> {
+        let rate = self.limits.rate(operation);
+        let per = TimeDelta::from_std(rate.per).unwrap_or(TimeDelta::MAX);
+        let interval = per / i32::try_from(rate.count.get()).unwrap_or(i32::MAX);
+
+        let mut full_at = self.buckets.entry((actor, operation)).or_insert(now);
+        let from = (*full_at).max(now);
+        let full_after = from + interval;
+
+        // Full after more than `per` means taking this token would go past the last one
+        if full_after - now > per {
+            let wait = full_after - now - per;
+            return Err(wait.to_std().unwrap_or_default());
+        }
+
+        *full_at = full_after;
+        Ok(())
+    }
+
+    /// Forgets buckets that have refilled by `now`, returning how many there were. Accounts seen
+    /// again just start with a full bucket.
+    pub(crate) fn prune(&self, now: DateTime<Utc>) -> usize {
+        let before = self.buckets.len();
+        self.buckets.retain(|_, full_at| *full_at > now);
+        before - self.buckets.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn limiter(count: u32) -> RateLimiter {
+        let rate = Rate::per_minute(NonZeroU32::new(count).expect("Not zero"));
+
+        RateLimiter::new(RateLimits {
+            trades: rate,
+            lookups: rate,
+            registrations: rate,
+        })
+    }
+
+    #[test]
+    fn bursts_then_refills_steadily() {
+        let limiter = limiter(3);
+        let actor = Actor::Account(Uuid::nil());
+        let now = DateTime::UNIX_EPOCH;
+
+        for _ in 0..3 {
+            limiter
+                .acquire(actor, Operation::Trade, now)
+                .expect("Within the burst");
+        }
+        assert_eq!(
+            limiter.acquire(actor, Operation::Trade, now),
+            Err(Duration::from_secs(20))
+        );
+
+        let later = now + TimeDelta::seconds(15);
+        assert_eq!(
+            limiter.acquire(actor, Operation::Trade, later),
+            Err(Duration::from_secs(5))
+        );
+
+        let later = now + TimeDelta::seconds(20);
+        limiter
+            .acquire(actor, Operation::Trade, later)
+            .expect("One token refilled");
+        assert!(limiter.acquire(actor, Operation::Trade, later).is_err());
+    }
+
+    #[test]
+    fn buckets_are_separate() {
+        let limiter = limiter(1);
+        let now = DateTime::UNIX_EPOCH;
+        let first = Actor::Account(Uuid::nil());
+        let second = Actor::Account(Uuid::max());
+
+        limiter
+            .acquire(first, Operation::Trade, now)
+            .expect("First trade");
+        assert!(limiter.acquire(first, Operation::Trade, now).is_err());
+
+        limiter
+            .acquire(first, Operation::Lookup, now)
+            .expect("Lookups are limited apart from trades");
+        limiter
+            .acquire(second, Operation::Trade, now)
+            .expect("Other accounts have their own bucket");
+    }
+
+    #[test]
+    fn refilled_buckets_are_pruned() {
+        let limiter = limiter(2);
+        let now = DateTime::UNIX_EPOCH;
+        let first = Actor::Account(Uuid::nil());
+        let second = Actor::Account(Uuid::max());
+
+        limiter.acquire(first, Operation::Trade, now).expect("Free");
+        limiter
+            .acquire(second, Operation::Trade, now)
+            .expect("Free");
+        limiter
+            .acquire(second, Operation::Trade, now + TimeDelta::seconds(10))
+            .expect("Free");
+
+        assert_eq!(limiter.prune(now + TimeDelta::seconds(30)), 1);
+        assert_eq!(limiter.prune(now + TimeDelta::seconds(60)), 1);
+        assert_eq!(limiter.prune(now + TimeDelta::seconds(60)), 0);
+    }
+}
@@ -0,0 +1,409 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Matching of incoming orders against the resting orders of a book. Kept free of any storage
+//! concerns so a repository can run it inside whatever transaction it needs.
+
+use std::{num::NonZeroU16, time::Duration};
+
+use chrono::{DateTime, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+use uuid::Uuid;
+
+use crate::model::{
+    Price, Shares,
+    order::{BookLevel, Fill, Side},
+};
+
+/// An order looking to trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IncomingOrder {
+    /// The ID of the order
+    pub id: i32,
+    /// The user that placed the order
+    pub user: Uuid,
+    /// Whether the order buys or sells
+    pub side: Side,
+    /// The worst price per share the user will accept
+    pub price: Price,
+    /// The number of shares left to fill
+    pub remaining: Shares,
+}
+
+/// An order on the opposite side of the book, which an [`IncomingOrder`] may trade with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestingOrder {
+    /// The ID of the order. Lower IDs were placed first
+    pub id: i32,
+    /// The user that placed the order
+    pub user: Uuid,
+    /// The price the order rests at
+    pub price: Price,
+    /// The number of shares left to fill
+    pub remaining: Shares,
+    /// When the order stops being matched, if ever
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Crosses `incoming` against `resting` orders at price-time priority, returning the resulting
+/// fills in execution order. Trades execute at the resting order's price. Resting orders placed by
+/// the same user are skipped so nobody can trade with themselves, as are orders that expired at or
+/// before `now` but have not been swept off the book yet.
+///
+/// Fills are matched without fees, which are left to whoever settles them.
+///
+/// Whatever is left of `incoming` after the returned fills should rest on the book.
+#[must_use]
+pub fn match_order(
+    incoming: &IncomingOrder,
+    resting: &[RestingOrder],
+    now: DateTime<Utc>,
+) -> Vec<Fill> {
+    let mut book: Vec<_> = resting
+        .iter()
+        .filter(|order| {
+            order.remaining > Shares::ZERO
+                && order.user != incoming.user
+                && order.expires_at.is_none_or(|v| v > now)
+                && crosses(incoming, order.price)
+        })
+        .collect();
+
+    book.sort_by(|a, b| {
+        let by_price = match incoming.side {
+            Side::Buy => a.price.cmp(&b.price),
+            Side::Sell => b.price.cmp(&a.price),
+        };
+
+        by_price.then(a.id.cmp(&b.id))
+    });
+
+    let mut left = incoming.remaining;
+    let mut fills = Vec::new();
+
+    for order in book {
+        if left == Shares::ZERO {
+            break;
+        }
+
+        let shares = left.min(order.remaining);
+        left = left.checked_sub(shares).expect("Never more than left");
+
+        let (buy_order, buyer, sell_order, seller) = match incoming.side {
+            Side::Buy => (incoming.id, incoming.user, order.id, order.user),
+            Side::Sell => (order.id, order.user, incoming.id, incoming.user),
+        };
+
+        fills.push(Fill {
+            buy_order,
+            sell_order,
+            buyer,
+            seller,
+            price: order.price,
+            shares,
+            fee: Decimal::ZERO,
+        });
+    }
+
+    fills
+}
+
+/// How far an order's price may stray from a stock's reference price, which is its last trade
+/// within `lookback`, falling back to the previous day's close
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceBand {
+    /// The furthest a price may be from the reference, in percent of it
+    pub pct: NonZeroU16,
+    /// How far back the last trade may be to count as the reference
+    pub lookback: Duration,
+}
+
+impl PriceBand {
+    /// The limit `price` breaks, if it is outside the band around `reference`. Limits are rounded
+    /// inwards to whole ticks, so a price exactly `pct` percent away is still allowed.
+    #[must_use]
+    pub fn breached(&self, price: Price, reference: Price) -> Option<Decimal> {
+        let (price, reference) = (price.get(), reference.get());
+        let width = reference * Decimal::from(self.pct.get()) / Decimal::ONE_HUNDRED;
+        let upper = (reference + width).round_dp_with_strategy(2, RoundingStrategy::ToZero);
+        let lower = (reference - width).round_dp_with_strategy(2, RoundingStrategy::AwayFromZero);
+
+        if price > upper {
+            Some(upper)
+        } else if price < lower {
+            Some(lower)
+        } else {
+            None
+        }
+    }
+}
+
+/// The worst price a market order for `quantity` shares has to accept to fill against `levels`,
+/// one side of a book with the best price first. When the levels don't hold enough shares, it is
+/// the worst price among them, so the order fills as much as it can. Returns [`None`] if there are
+/// no levels to trade against.
+#[must_use]
+pub fn sweep_price(levels: &[BookLevel], quantity: Shares) -> Option<Decimal> {
+    let wanted = u64::from(quantity.get());
+    let mut seen = 0;
+
+    for level in levels {
+        seen += level.shares;
+
+        if seen >= wanted {
+            return Some(level.price);
+        }
+    }
+
+    levels.last().map(|level| level.price)
+}
+
+/// Whether an order at `price` is good enough for `incoming`
+fn crosses(incoming: &IncomingOrder, price: Price) -> bool {
+    match incoming.side {
+        Side::Buy => price <= incoming.price,
+        Side::Sell => price >= incoming.price,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TAKER: Uuid = Uuid::from_u128(1);
+    const MAKER_A: Uuid = Uuid::from_u128(2);
+    const MAKER_B: Uuid = Uuid::from_u128(3);
+
+    fn incoming(side: Side, price: i64, remaining: u32) -> IncomingOrder {
+        IncomingOrder {
+            id: 100,
+            user: TAKER,
+            side,
+            price: Price::new(Decimal::from(price)).expect("Valid price"),
+            remaining: Shares::try_from(remaining).expect("Valid shares"),
+        }
+    }
+
+    fn resting(id: i32, user: Uuid, price: i64, remaining: u32) -> RestingOrder {
+        RestingOrder {
+            id,
+            user,
+            price: Price::new(Decimal::from(price)).expect("Valid price"),
+            remaining: Shares::try_from(remaining).expect("Valid shares"),
+            expires_at: None,
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_760_000_000, 0).expect("In range")
+    }
+
+    #[test]
+    fn empty_book_has_no_fills() {
+        assert!(match_order(&incoming(Side::Buy, 10, 5), &[], now()).is_empty());
+    }
+
+    #[test]
+    fn buy_takes_cheapest_ask_first() {
+        let book = [resting(1, MAKER_A, 12, 5), resting(2, MAKER_B, 10, 5)];
+        let fills = match_order(&incoming(Side::Buy, 12, 5), &book, now());
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].sell_order, 2);
+        assert_eq!(fills[0].price.get(), Decimal::from(10));
+    }
+
+    #[test]
+    fn sell_takes_highest_bid_first() {
+        let book = [resting(1, MAKER_A, 8, 5), resting(2, MAKER_B, 9, 5)];
+        let fills = match_order(&incoming(Side::Sell, 8, 7), &book, now());
+
+        assert_eq!(fills.len(), 2);
+        assert_eq!((fills[0].buy_order, fills[0].shares.get()), (2, 5));
+        assert_eq!((fills[1].buy_order, fills[1].shares.get()), (1, 2));
+        assert!(fills.iter().all(|f| f.seller == TAKER));
+    }
+
+    #[test]
+    fn equal_prices_fill_in_time_order() {
+        let book = [resting(7, MAKER_A, 10, 5), resting(3, MAKER_B, 10, 5)];
+        let fills = match_order(&incoming(Side::Buy, 10, 5), &book, now());
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].sell_order, 3);
+        assert_eq!(fills[0].seller, MAKER_B);
+    }
+
+    #[test]
+    fn trades_at_resting_price() {
+        let book = [resting(1, MAKER_A, 9, 5)];
+        let fills = match_order(&incoming(Side::Buy, 15, 5), &book, now());
+
+        assert_eq!(fills[0].price.get(), Decimal::from(9));
+    }
+
+    #[test]
+    fn non_crossing_prices_do_not_trade() {
+        let book = [resting(1, MAKER_A, 11, 5)];
+
+        assert!(match_order(&incoming(Side::Buy, 10, 5), &book, now()).is_empty());
+        assert!(match_order(&incoming(Side::Sell, 12, 5), &book, now()).is_empty());
+    }
+
+    #[test]
+    fn partial_fill_of_incoming_leaves_remainder() {
+        let book = [resting(1, MAKER_A, 10, 3), resting(2, MAKER_B, 11, 2)];
+        let fills = match_order(&incoming(Side::Buy, 11, 10), &book, now());
+
+        let filled: u32 = fills.iter().map(|f| f.shares.get()).sum();
+        assert_eq!(filled, 5);
+        assert_eq!(fills.len(), 2);
+    }
+
+    #[test]
+    fn partial_fill_of_resting_order() {
+        let book = [resting(1, MAKER_A, 10, 8), resting(2, MAKER_B, 10, 8)];
+        let fills = match_order(&incoming(Side::Buy, 10, 3), &book, now());
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!((fills[0].sell_order, fills[0].shares.get()), (1, 3));
+    }
+
+    #[test]
+    fn skips_own_orders() {
+        let book = [resting(1, TAKER, 9, 5), resting(2, MAKER_A, 10, 5)];
+        let fills = match_order(&incoming(Side::Buy, 10, 5), &book, now());
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].seller, MAKER_A);
+    }
+
+    #[test]
+    fn skips_exhausted_orders() {
+        let book = [resting(1, MAKER_A, 9, 0), resting(2, MAKER_B, 10, 5)];
+        let fills = match_order(&incoming(Side::Buy, 10, 5), &book, now());
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].sell_order, 2);
+    }
+
+    #[test]
+    fn buyer_and_seller_follow_sides() {
+        let book = [resting(1, MAKER_A, 10, 5)];
+
+        let buy = match_order(&incoming(Side::Buy, 10, 5), &book, now());
+        assert_eq!((buy[0].buyer, buy[0].seller), (TAKER, MAKER_A));
+        assert_eq!((buy[0].buy_order, buy[0].sell_order), (100, 1));
+
+        let sell = match_order(&incoming(Side::Sell, 10, 5), &book, now());
+        assert_eq!((sell[0].buyer, sell[0].seller), (MAKER_A, TAKER));
+        assert_eq!((sell[0].buy_order, sell[0].sell_order), (1, 100));
+    }
+
+    #[test]
+    fn skips_expired_orders() {
+        let mut expired = resting(1, MAKER_A, 9, 5);
+        expired.expires_at = Some(now() - chrono::Duration::minutes(1));
+        let book = [expired, resting(2, MAKER_B, 10, 5)];
+        let fills = match_order(&incoming(Side::Buy, 10, 5), &book, now());
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].sell_order, 2);
+    }
+
+    #[test]
+    fn order_expiring_before_first_match_does_not_trade() {
+        // Rested with an hour to live, but nothing crossed it until after that hour was up
+        let mut order = resting(1, MAKER_A, 10, 5);
+        order.expires_at = Some(now() + chrono::Duration::hours(1));
+        let book = [order];
+
+        let later = now() + chrono::Duration::hours(1);
+        assert!(match_order(&incoming(Side::Buy, 10, 5), &book, later).is_empty());
+        assert_eq!(
+            match_order(&incoming(Side::Buy, 10, 5), &book, now()).len(),
+            1
+        );
+    }
+
+    fn band(pct: u16) -> PriceBand {
+        PriceBand {
+            pct: NonZeroU16::new(pct).expect("Non zero"),
+            lookback: Duration::from_hours(24),
+        }
+    }
+
+    fn dec(v: &str) -> Decimal {
+        v.parse().expect("Valid decimal")
+    }
+
+    fn price(v: &str) -> Price {
+        Price::new(dec(v)).expect("Valid price")
+    }
+
+    #[test]
+    fn band_edges_are_allowed() {
+        let band = band(10);
+
+        assert_eq!(band.breached(price("11.00"), price("10")), None);
+        assert_eq!(band.breached(price("9.00"), price("10")), None);
+        assert_eq!(
+            band.breached(price("11.01"), price("10")),
+            Some(dec("11.00"))
+        );
+        assert_eq!(band.breached(price("8.99"), price("10")), Some(dec("9.00")));
+    }
+
+    #[test]
+    fn band_limits_round_inwards() {
+        // 3.33 ± 10% is 2.997 to 3.663, so the last whole ticks inside are 3.00 and 3.66
+        let band = band(10);
+
+        assert_eq!(band.breached(price("3.66"), price("3.33")), None);
+        assert_eq!(
+            band.breached(price("3.67"), price("3.33")),
+            Some(dec("3.66"))
+        );
+        assert_eq!(band.breached(price("3.00"), price("3.33")), None);
+        assert_eq!(
+            band.breached(price("2.99"), price("3.33")),
+            Some(dec("3.00"))
+        );
+    }
+
+    fn level(price: &str, shares: u64) -> BookLevel {
+        BookLevel {
+            price: dec(price),
+            shares,
+            orders: 1,
+        }
+    }
+
+    #[test]
+    fn market_orders_sweep_until_filled() {
+        let asks = [level("5", 3), level("6", 4), level("8", 10)];
+        let shares = |n| Shares::new(n).expect("Valid shares");
+
+        assert_eq!(sweep_price(&asks, shares(3)), Some(dec("5")));
+        assert_eq!(sweep_price(&asks, shares(4)), Some(dec("6")));
+        assert_eq!(sweep_price(&asks, shares(7)), Some(dec("6")));
+        // More than the book holds takes everything there is
+        assert_eq!(sweep_price(&asks, shares(100)), Some(dec("8")));
+        assert_eq!(sweep_price(&[], shares(1)), None);
+    }
+}
@@ -18,26 +18,893 @@
 
 //! Types that model our stock domain
 
-use chrono::{DateTime, Utc};
-use rust_decimal::Decimal;
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+use snafu::{OptionExt, Snafu, ensure};
 use std::num::NonZeroU64;
 use uuid::Uuid;
 
+use crate::model::ticker::Ticker;
+
+pub mod adjustment;
+pub mod api_key;
+pub mod audit;
+pub mod deposit;
+pub mod dividend;
+pub mod fee;
+pub mod guild;
+pub mod idempotency;
+pub mod link;
+pub mod order;
+pub mod outbox;
+pub mod reconcile;
+pub mod snapshot;
+pub mod summary;
 pub mod ticker;
+pub mod usage;
+pub mod view;
+pub mod withdrawal;
 
 /// Information about a given user
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, serde::Serialize)]
 pub struct UserInfo {
     /// The internal ID of the user
     pub id: Uuid,
-    /// The Kromer balance of this user
-    pub balance: Decimal,
+    /// The Kromer this user owns, including what is on hold. `None` when their privacy hides it
+    /// from whoever asked, as are the two parts below
+    pub balance: Option<Decimal>,
+    /// The part of the balance free to spend or withdraw
+    pub available_balance: Option<Decimal>,
+    /// The part of the balance on hold, escrowed by open buy orders or waiting to be withdrawn
+    pub held_balance: Option<Decimal>,
     /// When the user was created
     pub created_at: DateTime<Utc>,
     /// The linked Minecraft ID
     pub mc_id: Option<Uuid>,
     /// The linked Discord ID
     pub disc_id: Option<NonZeroU64>,
+    /// Whether the user lets others show their portfolio in public replies
+    pub public_portfolio: bool,
+    /// What others may see of the account
+    pub privacy: Privacy,
+    /// When the account was closed, after which it is no longer linked to anyone
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+impl UserInfo {
+    /// Leaves out the balance and both its parts
+    pub const fn hide_balances(&mut self) {
+        self.balance = None;
+        self.available_balance = None;
+        self.held_balance = None;
+    }
+
+    /// Leaves out whatever the account's privacy hides from `viewer`, the account asking or `None`
+    /// if they don't have one
+    pub fn hide_from(&mut self, viewer: Option<&Uuid>) {
+        if viewer != Some(&self.id) && !self.privacy.shows_balance() {
+            self.hide_balances();
+        }
+    }
+}
+
+/// What closing an account took care of
+#[derive(Debug, Clone)]
+pub struct ClosedAccount {
+    /// The Discord user the account was linked to
+    pub disc_id: Option<NonZeroU64>,
+    /// The Minecraft player the account was linked to
+    pub mc_id: Option<Uuid>,
+    /// Orders cancelled before closing, which only happens when forced
+    pub cancelled: Vec<order::Order>,
+    /// The Kromer credited for shares liquidated before closing, which only happens when forced
+    pub liquidated: Decimal,
+    /// The request holding whatever was left of the balance, if anything was
+    pub withdrawal: Option<withdrawal::Withdrawal>,
+}
+
+/// What merging one account into another moved, or would move when only previewed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountMerge {
+    /// The account everything was moved onto
+    pub survivor: Uuid,
+    /// The account everything was moved off of, which is closed afterwards
+    pub casualty: Uuid,
+    /// The Kromer moved
+    pub balance: Decimal,
+    /// The shares of each stock moved, added to any the survivor already held
+    pub holdings: Vec<(Ticker, Shares)>,
+    /// The number of ledger entries moved
+    pub ledger_entries: u64,
+    /// The number of trades moved
+    pub trades: u64,
+    /// The number of stocks whose ownership was moved
+    pub stocks: u64,
+    /// The Discord user moved onto the survivor, if the casualty was linked to one
+    pub disc_id: Option<NonZeroU64>,
+    /// The Minecraft player moved onto the survivor, if the casualty was linked to one
+    pub mc_id: Option<Uuid>,
+}
+
+/// Why two accounts can't be merged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergeConflict {
+    /// An account can't be merged into itself
+    SameAccount,
+    /// Both accounts are linked to a Discord user, and an account can only have one
+    BothDiscord,
+    /// Both accounts are linked to a Minecraft player, and an account can only have one
+    BothMinecraft,
+    /// The casualty has orders still on the book, which have to be cancelled first
+    OpenOrders,
+    /// The casualty has a withdrawal waiting on an admin, which has to be resolved first
+    PendingWithdrawal,
+}
+
+impl std::fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::SameAccount => "they are the same account",
+            Self::BothDiscord => "both are linked to a Discord user",
+            Self::BothMinecraft => "both are linked to a Minecraft player",
+            Self::OpenOrders => "the account being merged away has open orders",
+            Self::PendingWithdrawal => "the account being merged away has a pending withdrawal",
+        })
+    }
+}
+
+/// A quick overview of where a user stands, for showing alongside their [`UserInfo`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountSummary {
+    /// The total value of the user's holdings at the most recent price of each stock. Stocks that
+    /// have never traded are not counted
+    pub holdings_value: Decimal,
+    /// The number of stocks the user holds shares in
+    pub stocks_held: u32,
+    /// The number of the user's orders still on the book
+    pub open_orders: u64,
+    /// The user's most recent transactions, newest first
+    pub recent: Vec<Transaction>,
+}
+
+/// A change to a user's balance, from a trade or a ledger entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transaction {
+    /// When the transaction happened
+    pub time: DateTime<Utc>,
+    /// What caused it
+    pub kind: TransactionKind,
+    /// How much the user's balance changed by, including any fee paid
+    pub delta: Decimal,
+    /// The stock involved, if any
+    pub ticker: Option<Ticker>,
+}
+
+/// What caused a [`Transaction`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TransactionKind {
+    /// Bought shares
+    Buy,
+    /// Sold shares
+    Sell,
+    /// Paid or received a dividend
+    Dividend,
+    /// Received Kromer from an admin
+    Grant,
+    /// Sent Kromer to the exchange's wallet
+    Deposit,
+    /// Requested a withdrawal, holding the amount
+    Withdrawal,
+    /// Had a denied withdrawal returned
+    WithdrawalReleased,
+    /// Had shares bought out when the account was closed
+    Liquidation,
+    /// Paid the fee on a purchase. Only listed separately in statements, elsewhere fees are
+    /// included in the [`Buy`](Self::Buy)
+    Fee,
+    /// Had the balance adjusted by hand by an admin, or an adjustment reversed
+    Adjustment,
+}
+
+impl TransactionKind {
+    /// The stable name this kind is stored under
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Buy => "buy",
+            Self::Sell => "sell",
+            Self::Dividend => "dividend",
+            Self::Grant => "grant",
+            Self::Deposit => "deposit",
+            Self::Withdrawal => "withdrawal",
+            Self::WithdrawalReleased => "withdrawal_released",
+            Self::Liquidation => "liquidation",
+            Self::Fee => "fee",
+            Self::Adjustment => "adjustment",
+        }
+    }
+}
+
+impl std::fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for TransactionKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "buy" => Ok(Self::Buy),
+            "sell" => Ok(Self::Sell),
+            "dividend" => Ok(Self::Dividend),
+            "grant" => Ok(Self::Grant),
+            "deposit" => Ok(Self::Deposit),
+            "withdrawal" => Ok(Self::Withdrawal),
+            "withdrawal_released" => Ok(Self::WithdrawalReleased),
+            "liquidation" => Ok(Self::Liquidation),
+            "fee" => Ok(Self::Fee),
+            "adjustment" => Ok(Self::Adjustment),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The kinds of transactions a statement can be narrowed down to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LedgerKind {
+    /// Buying and selling shares, including shares bought out on closure
+    Trades,
+    /// Kromer deposited or granted by admins, and balances they adjusted by hand
+    Deposits,
+    /// Dividends paid and received
+    Dividends,
+    /// Fees paid on purchases
+    Fees,
+    /// Withdrawals requested, and those returned when denied
+    Withdrawals,
+}
+
+impl LedgerKind {
+    /// Every kind of transaction this covers
+    #[must_use]
+    pub const fn kinds(self) -> &'static [TransactionKind] {
+        match self {
+            Self::Trades => &[
+                TransactionKind::Buy,
+                TransactionKind::Sell,
+                TransactionKind::Liquidation,
+            ],
+            Self::Deposits => &[
+                TransactionKind::Deposit,
+                TransactionKind::Grant,
+                TransactionKind::Adjustment,
+            ],
+            Self::Dividends => &[TransactionKind::Dividend],
+            Self::Fees => &[TransactionKind::Fee],
+            Self::Withdrawals => &[
+                TransactionKind::Withdrawal,
+                TransactionKind::WithdrawalReleased,
+            ],
+        }
+    }
+}
+
+/// A line of a user's statement
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatementEntry {
+    /// The change to the user's Kromer
+    pub transaction: Transaction,
+    /// The user's Kromer once this and every earlier transaction went through, counting what is
+    /// held in escrow or for withdrawals. Always counts every kind of transaction, even when the
+    /// statement is narrowed down to some.
+    pub balance: Decimal,
+}
+
+/// The newest ledger entry and trade a statement covers. Paging through a statement with the
+/// snapshot its first page was taken at leaves out anything that happened since, so every page
+/// agrees on the running balance even while new transactions come in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StatementSnapshot {
+    /// The ID of the newest ledger entry covered
+    pub ledger: i64,
+    /// The ID of the newest stock event covered
+    pub event: i32,
+}
+
+/// A page of a user's statement, newest first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    /// The entries on this page
+    pub page: Page<StatementEntry>,
+    /// What the statement covers, to be passed back in for every later page
+    pub snapshot: StatementSnapshot,
+}
+
+/// The outcome of registering an ID, which may already have been linked to an account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Registered {
+    /// A new account was created for the ID
+    New(Uuid),
+    /// The ID was already linked to this account, so nothing was created
+    Existing(Uuid),
+}
+
+impl Registered {
+    /// The account linked to the ID, whether or not it was just created
+    #[must_use]
+    pub const fn id(self) -> Uuid {
+        match self {
+            Self::New(id) | Self::Existing(id) => id,
+        }
+    }
+}
+
+/// What accounts other than the owner may see of an account. The owner always sees everything
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Privacy {
+    /// Anyone may see the balance and holdings
+    Public,
+    /// Anyone may see the holdings, but not the balance
+    #[default]
+    HoldingsOnly,
+    /// Nobody may see the balance or holdings
+    Private,
+}
+
+impl Privacy {
+    /// The stable name this setting is stored under
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Public => "public",
+            Self::HoldingsOnly => "holdings_only",
+            Self::Private => "private",
+        }
+    }
+
+    /// Whether others may see the balance
+    #[must_use]
+    pub const fn shows_balance(self) -> bool {
+        matches!(self, Self::Public)
+    }
+
+    /// Whether others may see the holdings
+    #[must_use]
+    pub const fn shows_holdings(self) -> bool {
+        !matches!(self, Self::Private)
+    }
+}
+
+impl std::fmt::Display for Privacy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Privacy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "public" => Ok(Self::Public),
+            "holdings_only" => Ok(Self::HoldingsOnly),
+            "private" => Ok(Self::Private),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A number of shares. Never more than [`Shares::MAX`], the most the database can store
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
+#[serde(transparent)]
+pub struct Shares(u32);
+
+impl Shares {
+    /// No shares at all, as left on a filled order
+    pub const ZERO: Self = Self(0);
+
+    /// The most shares that can be stored
+    pub const MAX: Self = Self(i32::MAX.unsigned_abs());
+
+    /// A positive number of shares, as orders and issuances need
+    ///
+    /// # Errors
+    /// * [`NotPositive`](ValueError::NotPositive) - `shares` is 0
+    /// * [`TooLarge`](ValueError::TooLarge) - `shares` is more than [`Shares::MAX`]
+    pub fn new(shares: u32) -> Result<Self, ValueError> {
+        ensure!(shares > 0, NotPositiveSnafu);
+        Self::try_from(shares)
+    }
+
+    /// The number of shares
+    #[must_use]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+
+    /// Adds `other`, failing rather than going past [`Shares::MAX`]
+    ///
+    /// # Errors
+    /// * [`TooLarge`](ValueError::TooLarge) - The sum is more than [`Shares::MAX`]
+    pub fn checked_add(self, other: Self) -> Result<Self, ValueError> {
+        Self::try_from(self.0.checked_add(other.0).context(TooLargeSnafu)?)
+    }
+
+    /// Takes away `other`, failing rather than going below zero
+    ///
+    /// # Errors
+    /// * [`NotPositive`](ValueError::NotPositive) - `other` is more than `self`
+    pub fn checked_sub(self, other: Self) -> Result<Self, ValueError> {
+        self.0
+            .checked_sub(other.0)
+            .map(Self)
+            .context(NotPositiveSnafu)
+    }
+
+    /// Multiplies by `factor`, failing rather than going past [`Shares::MAX`]
+    ///
+    /// # Errors
+    /// * [`TooLarge`](ValueError::TooLarge) - The product is more than [`Shares::MAX`]
+    pub fn checked_mul(self, factor: u32) -> Result<Self, ValueError> {
+        Self::try_from(self.0.checked_mul(factor).context(TooLargeSnafu)?)
+    }
+}
+
+impl TryFrom<u32> for Shares {
+    type Error = ValueError;
+
+    /// Any number of shares up to [`Shares::MAX`], including none
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        ensure!(value <= Self::MAX.0, TooLargeSnafu);
+        Ok(Self(value))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Shares {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::try_from(u32::deserialize(deserializer)?).map_err(serde::de::Error::custom)
+    }
+}
+
+impl TryFrom<i32> for Shares {
+    type Error = ValueError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        u32::try_from(value)
+            .map(Self)
+            .ok()
+            .context(NotPositiveSnafu)
+    }
+}
+
+impl From<Shares> for u32 {
+    fn from(value: Shares) -> Self {
+        value.0
+    }
+}
+
+impl From<Shares> for i32 {
+    fn from(value: Shares) -> Self {
+        value.0.try_into().expect("Shares never exceed i32::MAX")
+    }
+}
+
+impl From<Shares> for Decimal {
+    fn from(value: Shares) -> Self {
+        Self::from(value.0)
+    }
+}
+
+impl std::fmt::Display for Shares {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// A price per share in Kromer. Always positive, with at most 2 decimal places, and below
+/// [`Price::MAX`], so it fits the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize)]
+#[serde(transparent)]
+pub struct Price(Decimal);
+
+impl Price {
+    /// Every price is below this
+    pub const MAX: Decimal = Decimal::from_parts(276_447_232, 23_283, 0, false, 0);
+
+    /// Checks that `price` is a valid price
+    ///
+    /// # Errors
+    /// * [`NotPositive`](ValueError::NotPositive) - `price` is 0 or less
+    /// * [`TooPrecise`](ValueError::TooPrecise) - `price` has more than 2 decimal places
+    /// * [`TooLarge`](ValueError::TooLarge) - `price` is at least [`Price::MAX`]
+    pub fn new(price: Decimal) -> Result<Self, ValueError> {
+        ensure!(price > Decimal::ZERO, NotPositiveSnafu);
+        ensure!(price.normalize().scale() <= 2, TooPreciseSnafu);
+        ensure!(price < Self::MAX, TooLargeSnafu);
+
+        Ok(Self(price))
+    }
+
+    /// The price as a plain number
+    #[must_use]
+    pub const fn get(self) -> Decimal {
+        self.0
+    }
+
+    /// Adds `other`, failing if the sum is too large to be a price
+    ///
+    /// # Errors
+    /// * [`TooLarge`](ValueError::TooLarge) - The sum is at least [`Price::MAX`]
+    pub fn checked_add(self, other: Self) -> Result<Self, ValueError> {
+        Self::new(self.0.checked_add(other.0).context(TooLargeSnafu)?)
+    }
+
+    /// Takes away `other`, failing if nothing positive is left
+    ///
+    /// # Errors
+    /// * [`NotPositive`](ValueError::NotPositive) - `other` is at least `self`
+    pub fn checked_sub(self, other: Self) -> Result<Self, ValueError> {
+        Self::new(self.0 - other.0)
+    }
+
+    /// The value of `shares` at this price, rounded half-even to 2 decimal places. Every notional
+    /// value on the exchange is worked out here. Can't overflow, as even [`Price::MAX`] times
+    /// [`Shares::MAX`] fits in a [`Decimal`].
+    #[must_use]
+    pub fn notional(self, shares: Shares) -> Decimal {
+        (self.0 * Decimal::from(shares))
+            .round_dp_with_strategy(2, RoundingStrategy::MidpointNearestEven)
+    }
+}
+
+impl TryFrom<Decimal> for Price {
+    type Error = ValueError;
+
+    fn try_from(value: Decimal) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Price {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::new(<Decimal as serde::Deserialize>::deserialize(deserializer)?)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl From<Price> for Decimal {
+    fn from(value: Price) -> Self {
+        value.0
+    }
+}
+
+impl std::fmt::Display for Price {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Why a number is not a valid [`Shares`] or [`Price`]
+#[derive(Debug, Snafu, Clone, Copy, PartialEq, Eq)]
+pub enum ValueError {
+    /// Zero or negative where something positive is needed
+    #[snafu(display("must be positive"))]
+    NotPositive,
+    /// A price with fractions of a Kromer cent
+    #[snafu(display("can have at most 2 decimal places"))]
+    TooPrecise,
+    /// Too large to be stored
+    #[snafu(display("is too large"))]
+    TooLarge,
+}
+
+impl sqlx::Type<sqlx::Postgres> for Shares {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <i32 as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Postgres> for Shares {
+    fn encode_by_ref(
+        &self,
+        buf: &mut sqlx::postgres::PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <i32 as sqlx::Encode<sqlx::Postgres>>::encode(i32::from(*self), buf)
+    }
+}
+
+impl sqlx::Decode<'_, sqlx::Postgres> for Shares {
+    fn decode(value: sqlx::postgres::PgValueRef<'_>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(Self::try_from(
+            <i32 as sqlx::Decode<sqlx::Postgres>>::decode(value)?,
+        )?)
+    }
+}
+
+impl sqlx::Type<sqlx::Postgres> for Price {
+    fn type_info() -> sqlx::postgres::PgTypeInfo {
+        <Decimal as sqlx::Type<sqlx::Postgres>>::type_info()
+    }
+}
+
+impl sqlx::Encode<'_, sqlx::Postgres> for Price {
+    fn encode_by_ref(
+        &self,
+        buf: &mut sqlx::postgres::PgArgumentBuffer,
+    ) -> Result<sqlx::encode::IsNull, sqlx::error::BoxDynError> {
+        <Decimal as sqlx::Encode<sqlx::Postgres>>::encode_by_ref(&self.0, buf)
+    }
+}
+
+impl sqlx::Decode<'_, sqlx::Postgres> for Price {
+    fn decode(value: sqlx::postgres::PgValueRef<'_>) -> Result<Self, sqlx::error::BoxDynError> {
+        Ok(Self::new(
+            <Decimal as sqlx::Decode<sqlx::Postgres>>::decode(value)?,
+        )?)
+    }
+}
+
+/// Information about a listed stock
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct StockInfo {
+    /// The ticker of the stock
+    pub ticker: Ticker,
+    /// The account that owns the stock, if anyone does
+    pub owner: Option<Uuid>,
+    /// The total number of shares issued
+    pub shares: Shares,
+    /// When the stock was listed
+    pub created_at: DateTime<Utc>,
+    /// Whether the stock can currently be traded
+    pub status: StockStatus,
+    /// Details set by the owner to describe the stock
+    pub metadata: StockMetadata,
+}
+
+/// Details the owner of a stock may set to describe it. Each is optional, and the ticker stands in
+/// for anything unset
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct StockMetadata {
+    /// The display name of the company behind the stock
+    pub name: Option<String>,
+    /// What the company does, at most [`StockMetadata::MAX_DESCRIPTION`] characters
+    pub description: Option<String>,
+    /// An `https` URL to an image representing the stock
+    pub icon_url: Option<String>,
+}
+
+impl StockMetadata {
+    /// The longest a name may be, in characters
+    pub const MAX_NAME: usize = 64;
+    /// The longest a description may be, in characters
+    pub const MAX_DESCRIPTION: usize = 500;
+    /// The longest an icon URL may be, in bytes
+    pub const MAX_ICON_URL: usize = 2048;
+}
+
+/// Whether a stock can be traded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StockStatus {
+    /// Orders are placed and matched as normal
+    #[default]
+    Active,
+    /// Trading was stopped by an admin until further notice. Resting orders stay on the book
+    Halted,
+    /// The stock was taken off the exchange. Resting orders stay on the book
+    Delisted,
+}
+
+impl StockStatus {
+    /// The stable name this status is stored under
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Halted => "halted",
+            Self::Delisted => "delisted",
+        }
+    }
+
+    /// Whether orders on the stock may be placed and matched
+    #[must_use]
+    pub const fn is_tradable(self) -> bool {
+        matches!(self, Self::Active)
+    }
+}
+
+impl std::fmt::Display for StockStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for StockStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(Self::Active),
+            "halted" => Ok(Self::Halted),
+            "delisted" => Ok(Self::Delisted),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A stock's price movement over a window of time
+#[derive(Debug, Clone, Copy)]
+pub struct Mover {
+    /// The stock that moved
+    pub ticker: Ticker,
+    /// The price of its most recent trade
+    pub last_price: Decimal,
+    /// How much the price changed over the window, in percent
+    pub pct_change: Decimal,
+}
+
+/// How a stock's price moved since its latest close
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriceChange {
+    /// The price of its most recent trade
+    pub last: Decimal,
+    /// The price the change is measured from
+    pub reference: Decimal,
+    /// Whether `reference` is the latest close, rather than the price a day ago for stocks that
+    /// haven't closed yet
+    pub since_close: bool,
+}
+
+impl PriceChange {
+    /// How much the price changed, in percent
+    #[must_use]
+    pub fn pct(&self) -> Decimal {
+        (self.last - self.reference) / self.reference * Decimal::ONE_HUNDRED
+    }
+}
+
+/// How long each [`Candle`] of a stock's price history spans
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CandleInterval {
+    /// Five minutes
+    FiveMinutes,
+    /// An hour
+    Hour,
+    /// A UTC day
+    Day,
+}
+
+impl CandleInterval {
+    /// How long a candle spans
+    #[must_use]
+    pub const fn duration(self) -> TimeDelta {
+        match self {
+            Self::FiveMinutes => TimeDelta::minutes(5),
+            Self::Hour => TimeDelta::hours(1),
+            Self::Day => TimeDelta::days(1),
+        }
+    }
+}
+
+/// The prices a stock traded at over one [`CandleInterval`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candle {
+    /// When the interval started
+    pub start: DateTime<Utc>,
+    /// The price of its first trade
+    pub open: Price,
+    /// The highest price it traded at
+    pub high: Price,
+    /// The lowest price it traded at
+    pub low: Price,
+    /// The price of its last trade
+    pub close: Price,
+    /// The number of shares traded, including imported volume
+    pub volume: u64,
+}
+
+/// What an account was worth at the end of a day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortfolioSnapshot {
+    /// The UTC day it was taken for
+    pub date: NaiveDate,
+    /// Its Kromer, including any held for open buy orders
+    pub balance: Decimal,
+    /// What its holdings were worth at their latest prices
+    pub holdings_value: Decimal,
+}
+
+impl PortfolioSnapshot {
+    /// Everything the account was worth
+    #[must_use]
+    pub fn net_worth(&self) -> Decimal {
+        self.balance + self.holdings_value
+    }
+}
+
+/// A price a stock traded at on another market, before it was listed on the exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImportedPrice {
+    /// The stock that traded
+    pub ticker: Ticker,
+    /// When it traded
+    pub time: DateTime<Utc>,
+    /// The price it traded at
+    pub price: Price,
+    /// The shares the other market reported as traded at this price
+    pub volume: u64,
+}
+
+/// The stocks whose prices moved the most over a window of time
+#[derive(Debug, Clone, Default)]
+pub struct Movers {
+    /// Stocks whose price rose, biggest rise first
+    pub gainers: Vec<Mover>,
+    /// Stocks whose price fell, biggest fall first
+    pub losers: Vec<Mover>,
+}
+
+/// The price of a stock's most recent trade
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct LatestPrice {
+    /// The stock traded
+    pub ticker: Ticker,
+    /// The price it last traded at
+    pub price: Price,
+    /// When it last traded
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A holding alongside what is needed to work out its profit or loss
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct HoldingPl {
+    /// The stock held
+    pub ticker: Ticker,
+    /// The number of shares held
+    pub shares: Shares,
+    /// The average price paid per share, if known
+    pub avg_cost: Option<Decimal>,
+    /// The most recent price of the stock, if it has been traded
+    pub price: Option<Price>,
+}
+
+impl HoldingPl {
+    /// The profit or loss that would be realized by selling every share at the current price.
+    /// [`None`] when either the cost basis or the current price is unknown.
+    #[must_use]
+    pub fn unrealized_pl(&self) -> Option<Decimal> {
+        Some((self.price?.get() - self.avg_cost?) * Decimal::from(self.shares))
+    }
+}
+
+/// Works out the new average cost of a holding after buying more shares, weighting the existing
+/// basis by the shares already held. Stays unknown if the existing basis of a non-empty holding is
+/// unknown. Values are left unrounded.
+#[must_use]
+pub fn weighted_avg_cost(
+    held: Shares,
+    avg_cost: Option<Decimal>,
+    bought: Shares,
+    price: Decimal,
+) -> Option<Decimal> {
+    if held == Shares::ZERO {
+        return Some(price);
+    }
+
+    let held = Decimal::from(held);
+    let bought = Decimal::from(bought);
+
+    Some((avg_cost? * held + price * bought) / (held + bought))
+}
+
+/// The profit or loss realized by selling `sold` shares at `price`. Selling leaves the average
+/// cost unchanged. [`None`] when the basis is unknown.
+#[must_use]
+pub fn realized_pl(avg_cost: Option<Decimal>, sold: Shares, price: Price) -> Option<Decimal> {
+    Some((price.get() - avg_cost?) * Decimal::from(sold))
 }
 
 /// A paginated request helper
@@ -88,3 +955,349 @@ impl Pager {
         self.limit = v;
     }
 }
+
+/// One page of a paginated listing, alongside the number of entries across every page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<T> {
+    /// The entries on this page
+    pub items: Vec<T>,
+    /// The number of entries across every page
+    pub total: u64,
+    /// The number of entries before this page
+    pub offset: u64,
+}
+
+impl<T> Page<T> {
+    /// Creates the page `page` asked for
+    #[must_use]
+    pub fn new(items: Vec<T>, total: u64, page: &Pager) -> Self {
+        Self {
+            items,
+            total,
+            offset: page.offset().try_into().unwrap_or_default(),
+        }
+    }
+
+    /// The number of pages of `size` entries it takes to list every entry. There are none when
+    /// there are no entries
+    #[must_use]
+    pub const fn total_pages(&self, size: u64) -> u64 {
+        self.total.div_ceil(size)
+    }
+
+    /// Whether there are no entries past this page
+    #[must_use]
+    pub fn is_last(&self) -> bool {
+        self.offset + self.items.len() as u64 >= self.total
+    }
+}
+
+/// The order holdings are listed in. Everything but [`Ticker`](Self::Ticker) puts the largest
+/// first, with ties broken by ticker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum HoldingOrdering {
+    /// Alphabetically by ticker
+    #[default]
+    Ticker,
+    /// By number of shares held
+    Shares,
+    /// By value at the most recent price. Holdings in stocks that have never traded come last
+    Value,
+}
+
+impl HoldingOrdering {
+    /// The name the repository sorts by
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ticker => "ticker",
+            Self::Shares => "shares",
+            Self::Value => "value",
+        }
+    }
+}
+
+/// Which part of a stock a search matched best
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchField {
+    /// Its ticker
+    Ticker,
+    /// The name its owner gave it
+    Name,
+    /// The description its owner gave it
+    Description,
+}
+
+impl SearchField {
+    /// The stable name the repository reports this field under
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ticker => "ticker",
+            Self::Name => "name",
+            Self::Description => "description",
+        }
+    }
+}
+
+impl std::str::FromStr for SearchField {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ticker" => Ok(Self::Ticker),
+            "name" => Ok(Self::Name),
+            "description" => Ok(Self::Description),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A stock found by searching, with what it was found by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StockMatch {
+    /// The stock's ticker
+    pub ticker: Ticker,
+    /// Its name, if one was set
+    pub name: Option<String>,
+    /// Its description, if one was set
+    pub description: Option<String>,
+    /// The price of its most recent trade, if it ever traded
+    pub price: Option<Price>,
+    /// The part of it that matched best
+    pub matched: SearchField,
+}
+
+/// The order stocks are listed in. Everything but [`Ticker`](Self::Ticker) puts the largest first,
+/// with ties broken by ticker.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StockOrdering {
+    /// Alphabetically by ticker
+    #[default]
+    Ticker,
+    /// By most recent price. Stocks that have never traded come last
+    Price,
+    /// By shares traded over the last day
+    Volume,
+    /// By percent change in price since the latest close, or over the last day for stocks that
+    /// haven't closed yet. Stocks that have never traded come last
+    Change,
+}
+
+impl StockOrdering {
+    /// The name the repository sorts by
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Ticker => "ticker",
+            Self::Price => "price",
+            Self::Volume => "volume",
+            Self::Change => "change",
+        }
+    }
+}
+
+/// The order accounts are listed in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UserOrdering {
+    /// Most recently registered first
+    #[default]
+    Newest,
+    /// Largest balance first, with ties broken by registration
+    Balance,
+}
+
+impl UserOrdering {
+    /// The name the repository sorts by
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Newest => "newest",
+            Self::Balance => "balance",
+        }
+    }
+}
+
+/// Which of the identities an account may be linked to it must have
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UserLinks {
+    /// Accounts however they are linked
+    #[default]
+    Any,
+    /// Accounts linked to a Discord user
+    Discord,
+    /// Accounts linked to a Minecraft player
+    Minecraft,
+    /// Accounts linked to only one of Discord and Minecraft
+    OneSided,
+}
+
+impl UserLinks {
+    /// The name the repository filters by
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Any => "any",
+            Self::Discord => "discord",
+            Self::Minecraft => "minecraft",
+            Self::OneSided => "one_sided",
+        }
+    }
+}
+
+/// Restricts and sorts the accounts returned when browsing them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UserFilter {
+    /// Which identities accounts must be linked to
+    pub links: UserLinks,
+    /// The order to list them in
+    pub order: UserOrdering,
+}
+
+/// How big the exchange is
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExchangeCounts {
+    /// Open accounts linked to a Discord user or Minecraft player, leaving out the exchange's own
+    pub accounts: u64,
+    /// Stocks that haven't been delisted
+    pub stocks: u64,
+}
+
+/// How busy the repository's pool of connections is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct PoolUsage {
+    /// The connections currently open, whether in use or idle
+    pub size: u32,
+    /// The open connections not in use
+    pub idle: u32,
+    /// The most connections the pool will open
+    pub max: u32,
+}
+
+impl PoolUsage {
+    /// The connections currently in use
+    #[must_use]
+    pub const fn in_use(self) -> u32 {
+        self.size.saturating_sub(self.idle)
+    }
+
+    /// Whether every connection the pool may open is in use, so that further queries have to wait
+    /// for one to be returned
+    #[must_use]
+    pub const fn is_saturated(self) -> bool {
+        self.in_use() >= self.max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(items: usize, total: u64, offset: i64) -> Page<()> {
+        Page::new(vec![(); items], total, &Pager::new(offset, 16))
+    }
+
+    #[test]
+    fn total_pages_round_up() {
+        assert_eq!(page(0, 0, 0).total_pages(16), 0);
+        assert_eq!(page(1, 1, 0).total_pages(16), 1);
+        assert_eq!(page(16, 16, 0).total_pages(16), 1);
+        assert_eq!(page(16, 17, 0).total_pages(16), 2);
+        assert_eq!(page(16, 33, 0).total_pages(16), 3);
+    }
+
+    #[test]
+    fn last_page_reaches_the_total() {
+        assert!(page(0, 0, 0).is_last());
+        assert!(!page(16, 17, 0).is_last());
+        assert!(page(1, 17, 16).is_last());
+        assert!(page(0, 17, 32).is_last());
+    }
+
+    fn price(s: &str) -> Price {
+        Price::new(s.parse().expect("Valid decimal")).expect("Valid price")
+    }
+
+    #[test]
+    fn shares_stay_in_range() {
+        assert_eq!(Shares::new(0), Err(ValueError::NotPositive));
+        assert_eq!(Shares::new(1).map(Shares::get), Ok(1));
+        assert_eq!(Shares::new(Shares::MAX.get()), Ok(Shares::MAX));
+        assert_eq!(
+            Shares::new(Shares::MAX.get() + 1),
+            Err(ValueError::TooLarge)
+        );
+        assert_eq!(Shares::try_from(0_u32), Ok(Shares::ZERO));
+        assert_eq!(Shares::try_from(-1_i32), Err(ValueError::NotPositive));
+    }
+
+    #[test]
+    fn share_arithmetic_is_checked() {
+        let one = Shares::new(1).expect("Valid shares");
+
+        assert_eq!(Shares::MAX.checked_add(one), Err(ValueError::TooLarge));
+        assert_eq!(Shares::ZERO.checked_sub(one), Err(ValueError::NotPositive));
+        assert_eq!(one.checked_sub(one), Ok(Shares::ZERO));
+        assert_eq!(Shares::MAX.checked_mul(2), Err(ValueError::TooLarge));
+        assert_eq!(Shares::MAX.checked_mul(u32::MAX), Err(ValueError::TooLarge));
+        assert_eq!(one.checked_mul(3).map(Shares::get), Ok(3));
+    }
+
+    #[test]
+    fn prices_stay_in_range() {
+        assert_eq!(Price::new(Decimal::ZERO), Err(ValueError::NotPositive));
+        assert_eq!(
+            Price::new(Decimal::NEGATIVE_ONE),
+            Err(ValueError::NotPositive)
+        );
+        assert_eq!(
+            Price::new("0.001".parse().expect("Valid decimal")),
+            Err(ValueError::TooPrecise)
+        );
+        assert_eq!(Price::new(Price::MAX), Err(ValueError::TooLarge));
+        assert_eq!(Price::MAX, Decimal::from(100_000_000_000_000_i64));
+        // Trailing zeroes don't count as precision
+        assert_eq!(price("1.500").get(), Decimal::new(15, 1));
+        assert_eq!(
+            price("0.01").checked_sub(price("0.01")),
+            Err(ValueError::NotPositive)
+        );
+        assert_eq!(price("1.25").checked_add(price("0.75")), Ok(price("2")));
+    }
+
+    #[test]
+    fn pools_saturate_once_every_connection_is_in_use() {
+        let pool = PoolUsage {
+            size: 10,
+            idle: 1,
+            max: 10,
+        };
+
+        assert_eq!(pool.in_use(), 9);
+        assert!(!pool.is_saturated());
+        assert!(PoolUsage { idle: 0, ..pool }.is_saturated());
+        // A pool still growing isn't saturated, even with nothing idle
+        assert!(
+            !PoolUsage {
+                size: 4,
+                idle: 0,
+                ..pool
+            }
+            .is_saturated()
+        );
+    }
+
+    #[test]
+    fn notionals_are_exact() {
+        let three = Shares::new(3).expect("Valid shares");
+
+        assert_eq!(price("0.01").notional(three), Decimal::new(3, 2));
+        assert_eq!(price("1.5").notional(Shares::ZERO), Decimal::ZERO);
+        assert_eq!(
+            Price::new(Price::MAX - Decimal::new(1, 2))
+                .expect("Valid price")
+                .notional(Shares::MAX),
+            (Price::MAX - Decimal::new(1, 2)) * Decimal::from(Shares::MAX)
+        );
+    }
+}
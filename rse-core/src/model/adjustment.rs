@@ -0,0 +1,92 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Corrections admins make to balances by hand, and their reversals
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::model::audit::Actor;
+
+/// Why an admin adjusted a balance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AdjustmentReason {
+    /// Paying back Kromer lost to a bug or a mistake
+    Refund,
+    /// Fixing a balance that is wrong for any other reason
+    Correction,
+    /// Paying out a prize, such as for an event
+    Prize,
+    /// Taking Kromer away as a punishment
+    Penalty,
+}
+
+impl AdjustmentReason {
+    /// The stable name this reason is stored under
+    #[must_use]
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Refund => "refund",
+            Self::Correction => "correction",
+            Self::Prize => "prize",
+            Self::Penalty => "penalty",
+        }
+    }
+}
+
+impl std::fmt::Display for AdjustmentReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for AdjustmentReason {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "refund" => Ok(Self::Refund),
+            "correction" => Ok(Self::Correction),
+            "prize" => Ok(Self::Prize),
+            "penalty" => Ok(Self::Penalty),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A change an admin made to a user's balance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Adjustment {
+    /// The ID of the adjustment, which it can be reversed by
+    pub id: i64,
+    /// The account adjusted
+    pub user: Uuid,
+    /// The Kromer added to the balance, negative if taken away
+    pub delta: Decimal,
+    /// Why the balance was adjusted. Reversals keep the reason of what they reverse
+    pub reason: AdjustmentReason,
+    /// The admin that made the adjustment
+    pub actor: Actor,
+    /// The adjustment this reverses, if it is a reversal
+    pub reverses: Option<i64>,
+    /// The user's balance afterwards
+    pub balance: Decimal,
+    /// When the adjustment was made
+    pub time: DateTime<Utc>,
+}
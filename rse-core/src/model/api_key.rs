@@ -0,0 +1,127 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Keys accounts place orders through the REST API with
+
+use std::{fmt::Write, str::FromStr};
+
+use sha2::{Digest, Sha256};
+use snafu::{Snafu, ensure};
+
+/// A secret an account authenticates to the REST API with. Only its [`hash`](Self::hash) is
+/// stored, so the key itself is only ever shown once, when it is created, and is left out of
+/// [`Debug`] output.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ApiKey(String);
+
+impl ApiKey {
+    /// What every key starts with, so a leaked key is easy to recognise
+    const PREFIX: &str = "rse_";
+
+    /// How many random bytes a key holds, written out in hex after the prefix
+    const BYTES: usize = 32;
+
+    /// Creates a new key from the operating system's secure random number generator
+    ///
+    /// # Panics
+    /// If the operating system can't provide random bytes
+    #[must_use]
+    pub fn generate() -> Self {
+        let mut bytes = [0; Self::BYTES];
+        getrandom::fill(&mut bytes).expect("The OS random number generator is available");
+
+        let mut key = String::with_capacity(Self::PREFIX.len() + Self::BYTES * 2);
+        key.push_str(Self::PREFIX);
+        for byte in bytes {
+            write!(key, "{byte:02x}").expect("Writing to a string never fails");
+        }
+
+        Self(key)
+    }
+
+    /// Gets the key as a string slice
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The fingerprint the key is stored and looked up by
+    #[must_use]
+    pub fn hash(&self) -> [u8; 32] {
+        Sha256::digest(self.0.as_bytes()).into()
+    }
+}
+
+impl std::fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ApiKey(<redacted>)")
+    }
+}
+
+impl FromStr for ApiKey {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.strip_prefix(Self::PREFIX).ok_or(ParseError)?;
+        ensure!(
+            hex.len() == Self::BYTES * 2
+                && hex
+                    .bytes()
+                    .all(|b| b.is_ascii_digit() || matches!(b, b'a'..=b'f')),
+            ParseSnafu
+        );
+
+        Ok(Self(s.to_owned()))
+    }
+}
+
+/// Failed to parse an [`ApiKey`]
+#[derive(Debug, Snafu, Clone, Copy, PartialEq, Eq)]
+#[snafu(display("Not a valid API key"))]
+pub struct ParseError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_keys_parse_back() {
+        let key = ApiKey::generate();
+
+        assert_eq!(key.as_str().parse(), Ok(key.clone()));
+        assert_ne!(key, ApiKey::generate());
+        assert_eq!(format!("{key:?}"), "ApiKey(<redacted>)");
+    }
+
+    #[test]
+    fn parsing_rejects_anything_but_a_whole_key() {
+        let key = ApiKey::generate();
+        let hex = &key.as_str()[ApiKey::PREFIX.len()..];
+
+        assert_eq!(hex.parse::<ApiKey>(), Err(ParseError));
+        assert_eq!(key.as_str()[1..].parse::<ApiKey>(), Err(ParseError));
+        assert_eq!(
+            format!("{}0", key.as_str()).parse::<ApiKey>(),
+            Err(ParseError)
+        );
+        assert_eq!(
+            key.as_str().to_uppercase().parse::<ApiKey>(),
+            Err(ParseError)
+        );
+    }
+}
@@ -0,0 +1,243 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Types describing entries in the audit log
+
+use std::{num::NonZeroU64, str::FromStr};
+
+use chrono::{DateTime, Utc};
+use snafu::Snafu;
+use uuid::Uuid;
+
+/// Who performed an audited action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Actor {
+    /// The exchange itself, such as background jobs
+    System,
+    /// A registered account
+    Account(Uuid),
+    /// A Discord user, identified by their snowflake. Used for admins and for users that may not
+    /// have an account yet
+    Discord(NonZeroU64),
+    /// A Minecraft player, identified by their UUID
+    Minecraft(Uuid),
+}
+
+impl std::fmt::Display for Actor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::System => f.write_str("system"),
+            Self::Account(id) => write!(f, "account:{id}"),
+            Self::Discord(id) => write!(f, "discord:{id}"),
+            Self::Minecraft(id) => write!(f, "minecraft:{id}"),
+        }
+    }
+}
+
+impl FromStr for Actor {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "system" {
+            return Ok(Self::System);
+        }
+
+        let (kind, id) = s.split_once(':').ok_or(ParseError)?;
+
+        match kind {
+            "account" => id.parse().map(Self::Account).map_err(|_| ParseError),
+            "discord" => id.parse().map(Self::Discord).map_err(|_| ParseError),
+            "minecraft" => id.parse().map(Self::Minecraft).map_err(|_| ParseError),
+            _ => Err(ParseError),
+        }
+    }
+}
+
+/// The kind of action an audit entry describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    /// An account was registered
+    Register,
+    /// An admin ran a privileged command
+    AdminCommand,
+    /// A stock was listed on the exchange
+    CreateStock,
+    /// A stock was handed to a new owner
+    TransferOwnership,
+    /// The owner of a stock issued new shares to themselves
+    IssueShares,
+    /// The owner of a stock retired some of their shares
+    Buyback,
+    /// Kromer was credited to an account from outside the exchange
+    Grant,
+    /// A user asked for Kromer to be withdrawn from the exchange
+    RequestWithdrawal,
+    /// An admin approved or denied a withdrawal
+    ResolveWithdrawal,
+    /// An approved withdrawal was sent out of the exchange's wallet
+    PayWithdrawal,
+    /// An admin halted, resumed or delisted a stock
+    SetStockStatus,
+    /// The owner of a stock changed its name, description or icon
+    UpdateStockMetadata,
+    /// An admin looked at the balances of accounts they were browsing
+    ViewBalances,
+    /// An account was closed, by its owner or forcibly by an admin
+    CloseAccount,
+    /// An admin granted a permission to a role in their server
+    GrantPermission,
+    /// An admin revoked a permission from a role in their server
+    RevokePermission,
+    /// An admin adjusted a balance by hand
+    AdjustBalance,
+    /// An admin reversed an earlier balance adjustment
+    ReverseAdjustment,
+    /// A Discord user or Minecraft player was linked to an existing account with a one-time code
+    LinkIdentity,
+    /// A Discord user or Minecraft player was detached from an account, as it was closed or merged
+    /// into another
+    UnlinkIdentity,
+    /// The owner of a stock paid a dividend to its holders
+    PayDividend,
+    /// An admin merged a duplicate account into another
+    MergeAccounts,
+    /// Kromer sent to the exchange's wallet was credited to the account it was sent to
+    Deposit,
+    /// Kromer sent to the exchange's wallet couldn't be credited, as the account it was sent to is
+    /// closed or doesn't exist, and is left for an admin to send back
+    UncreditedDeposit,
+}
+
+impl Action {
+    /// The stable name this action is stored under
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Register => "register",
+            Self::AdminCommand => "admin_command",
+            Self::CreateStock => "create_stock",
+            Self::TransferOwnership => "transfer_ownership",
+            Self::IssueShares => "issue_shares",
+            Self::Buyback => "buyback",
+            Self::Grant => "grant",
+            Self::RequestWithdrawal => "request_withdrawal",
+            Self::ResolveWithdrawal => "resolve_withdrawal",
+            Self::PayWithdrawal => "pay_withdrawal",
+            Self::SetStockStatus => "set_stock_status",
+            Self::UpdateStockMetadata => "update_stock_metadata",
+            Self::ViewBalances => "view_balances",
+            Self::CloseAccount => "close_account",
+            Self::GrantPermission => "grant_permission",
+            Self::RevokePermission => "revoke_permission",
+            Self::AdjustBalance => "adjust_balance",
+            Self::ReverseAdjustment => "reverse_adjustment",
+            Self::LinkIdentity => "link_identity",
+            Self::UnlinkIdentity => "unlink_identity",
+            Self::PayDividend => "pay_dividend",
+            Self::MergeAccounts => "merge_accounts",
+            Self::Deposit => "deposit",
+            Self::UncreditedDeposit => "uncredited_deposit",
+        }
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Action {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "register" => Ok(Self::Register),
+            "admin_command" => Ok(Self::AdminCommand),
+            "create_stock" => Ok(Self::CreateStock),
+            "transfer_ownership" => Ok(Self::TransferOwnership),
+            "issue_shares" => Ok(Self::IssueShares),
+            "buyback" => Ok(Self::Buyback),
+            "grant" => Ok(Self::Grant),
+            "request_withdrawal" => Ok(Self::RequestWithdrawal),
+            "resolve_withdrawal" => Ok(Self::ResolveWithdrawal),
+            "pay_withdrawal" => Ok(Self::PayWithdrawal),
+            "set_stock_status" => Ok(Self::SetStockStatus),
+            "update_stock_metadata" => Ok(Self::UpdateStockMetadata),
+            "view_balances" => Ok(Self::ViewBalances),
+            "close_account" => Ok(Self::CloseAccount),
+            "grant_permission" => Ok(Self::GrantPermission),
+            "revoke_permission" => Ok(Self::RevokePermission),
+            "adjust_balance" => Ok(Self::AdjustBalance),
+            "reverse_adjustment" => Ok(Self::ReverseAdjustment),
+            "link_identity" => Ok(Self::LinkIdentity),
+            "unlink_identity" => Ok(Self::UnlinkIdentity),
+            "pay_dividend" => Ok(Self::PayDividend),
+            "merge_accounts" => Ok(Self::MergeAccounts),
+            "deposit" => Ok(Self::Deposit),
+            "uncredited_deposit" => Ok(Self::UncreditedDeposit),
+            _ => Err(ParseError),
+        }
+    }
+}
+
+/// Failed to parse an [`Actor`] or [`Action`] from its stored form
+#[derive(Debug, Snafu, Clone, Copy, PartialEq, Eq)]
+#[snafu(display("Not a valid audit value"))]
+pub struct ParseError;
+
+/// A new entry to be written to the audit log
+#[derive(Debug, Clone)]
+pub struct NewAuditEntry {
+    /// Who performed the action
+    pub actor: Actor,
+    /// What was done
+    pub action: Action,
+    /// What it was done to, such as an account UUID or ticker
+    pub target: Option<String>,
+    /// Free-form details of the action
+    pub details: serde_json::Value,
+}
+
+/// An entry read back from the audit log
+#[derive(Debug, Clone)]
+pub struct AuditEntry {
+    /// The ID of the entry, increasing with time
+    pub id: i64,
+    /// When the action was performed
+    pub time: DateTime<Utc>,
+    /// Who performed the action
+    pub actor: Actor,
+    /// What was done
+    pub action: Action,
+    /// What it was done to, such as an account UUID or ticker
+    pub target: Option<String>,
+    /// Free-form details of the action
+    pub details: serde_json::Value,
+}
+
+/// Restricts which entries are returned when reading the audit log. Unset fields match anything.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    /// Only entries performed by this actor
+    pub actor: Option<Actor>,
+    /// Only entries of this kind
+    pub action: Option<Action>,
+    /// Only entries concerning this target
+    pub target: Option<String>,
+}
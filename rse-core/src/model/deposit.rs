@@ -0,0 +1,73 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Kromer sent to the exchange from outside, and how it finds its way to an account
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::model::withdrawal::Address;
+
+/// A transfer of Kromer to the exchange's wallet, to be credited to an account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deposit {
+    /// The ID of the Kromer transaction, which is only ever credited once
+    pub transaction: i64,
+    /// The account it was sent to
+    pub user: Uuid,
+    /// How much was sent
+    pub amount: Decimal,
+    /// The address it was sent from
+    pub from: Address,
+    /// When it was sent
+    pub time: DateTime<Utc>,
+}
+
+/// The metaname deposits for `user` are sent with, as in `<metaname>@<name>.kro`. Each account has
+/// its own, so a transfer says who it is for without the sender having to write anything
+#[must_use]
+pub fn metaname(user: &Uuid) -> String {
+    user.simple().to_string()
+}
+
+/// Reads the account a deposit is for back out of the metaname it was sent with
+#[must_use]
+pub fn parse_metaname(metaname: &str) -> Option<Uuid> {
+    // Other forms of UUIDs would parse too, but are never handed out
+    if metaname.len() != 32 {
+        return None;
+    }
+
+    Uuid::try_parse(metaname).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metanames_round_trip() {
+        let user = Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+
+        assert_eq!(metaname(&user), "0123456789abcdef0123456789abcdef");
+        assert_eq!(parse_metaname(&metaname(&user)), Some(user));
+        assert_eq!(parse_metaname(&user.hyphenated().to_string()), None);
+        assert_eq!(parse_metaname("shop"), None);
+    }
+}
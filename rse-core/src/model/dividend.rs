@@ -0,0 +1,94 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Dividends paid by the owner of a stock to everyone else holding it
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use uuid::Uuid;
+
+use crate::model::ticker::Ticker;
+
+/// Who holds a stock, and how much of it was issued
+#[derive(Debug, Clone, Default)]
+pub struct Shareholders {
+    /// The total number of shares issued
+    pub issued: u32,
+    /// Each holder alongside the shares they hold, including any escrowed by open orders
+    pub holders: Vec<(Uuid, u32)>,
+}
+
+impl Shareholders {
+    /// The number of shares `user` holds
+    #[must_use]
+    pub fn held_by(&self, user: &Uuid) -> u32 {
+        self.holders
+            .iter()
+            .find(|(id, _)| id == user)
+            .map_or(0, |(_, shares)| *shares)
+    }
+
+    /// Works out what each holder other than `payer` is owed by a dividend of `per_share`.
+    /// Entitlements are rounded down to the cent, with the remainder staying with the payer.
+    /// Holders owed less than a cent are left out.
+    #[must_use]
+    pub fn plan_dividend(&self, payer: &Uuid, per_share: Decimal) -> DividendPlan {
+        let mut plan = DividendPlan::default();
+
+        for (holder, shares) in self.holders.iter().filter(|(id, _)| id != payer) {
+            plan.shares += u64::from(*shares);
+
+            let payout = (per_share * Decimal::from(*shares))
+                .round_dp_with_strategy(2, RoundingStrategy::ToZero);
+
+            if payout > Decimal::ZERO {
+                plan.total += payout;
+                plan.payouts.push((*holder, payout));
+            }
+        }
+
+        plan
+    }
+}
+
+/// What a dividend would pay out, before it is paid
+#[derive(Debug, Clone, Default)]
+pub struct DividendPlan {
+    /// Each holder paid alongside what they receive
+    pub payouts: Vec<(Uuid, Decimal)>,
+    /// The number of shares the dividend is paid on, excluding the payer's own
+    pub shares: u64,
+    /// The total the payer is debited
+    pub total: Decimal,
+}
+
+/// A dividend that has been paid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Dividend {
+    /// The stock the dividend was paid on
+    pub ticker: Ticker,
+    /// The user that paid it
+    pub payer: Uuid,
+    /// The amount declared per share
+    pub per_share: Decimal,
+    /// The number of shares it was paid on
+    pub shares: u64,
+    /// The number of holders paid
+    pub holders: u32,
+    /// The total debited from the payer
+    pub total: Decimal,
+}
@@ -0,0 +1,94 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The fees the exchange charges on trades
+
+use rust_decimal::{Decimal, RoundingStrategy};
+use uuid::Uuid;
+
+/// The largest fee that can be charged, in basis points. Anything above would cost more than the
+/// trade itself
+pub const MAX_FEE_BPS: u16 = 10_000;
+
+/// A flat fee on the notional value of every fill, paid by the buyer into a treasury account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeeSchedule {
+    /// The fee in basis points, hundredths of a percent, of the notional value
+    pub bps: u16,
+    /// The account fees are credited to
+    pub treasury: Uuid,
+}
+
+impl FeeSchedule {
+    /// The fee owed on a trade worth `notional`, rounded half-even to 2 decimal places
+    #[must_use]
+    pub fn fee(&self, notional: Decimal) -> Decimal {
+        (notional * Decimal::from(self.bps) / Decimal::from(MAX_FEE_BPS))
+            .round_dp_with_strategy(2, RoundingStrategy::MidpointNearestEven)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(bps: u16) -> FeeSchedule {
+        FeeSchedule {
+            bps,
+            treasury: Uuid::nil(),
+        }
+    }
+
+    fn dec(v: &str) -> Decimal {
+        v.parse().expect("Valid decimal")
+    }
+
+    #[test]
+    fn zero_bps_is_free() {
+        assert_eq!(schedule(0).fee(dec("33.335")), Decimal::ZERO);
+        assert_eq!(schedule(0).fee(dec("1000000")), Decimal::ZERO);
+    }
+
+    #[test]
+    fn awkward_notionals() {
+        // 33.335 * 1% = 0.33335
+        assert_eq!(schedule(100).fee(dec("33.335")), dec("0.33"));
+        // 33.335 * 1.5% = 0.500025
+        assert_eq!(schedule(150).fee(dec("33.335")), dec("0.50"));
+        // 33.335 * 50% = 16.6675
+        assert_eq!(schedule(5_000).fee(dec("33.335")), dec("16.67"));
+        // 33.335 * 100% = 33.335, where the midpoint goes to the even 33.34
+        assert_eq!(schedule(10_000).fee(dec("33.335")), dec("33.34"));
+    }
+
+    #[test]
+    fn midpoints_round_to_even() {
+        // 0.005 and 0.015 are both exact midpoints
+        assert_eq!(schedule(100).fee(dec("0.50")), dec("0.00"));
+        assert_eq!(schedule(100).fee(dec("1.50")), dec("0.02"));
+        assert_eq!(schedule(100).fee(dec("2.50")), dec("0.02"));
+        // 1234 * 0.25% = 3.085
+        assert_eq!(schedule(25).fee(dec("1234")), dec("3.08"));
+    }
+
+    #[test]
+    fn exact_fees_are_unchanged() {
+        assert_eq!(schedule(35).fee(dec("100")), dec("0.35"));
+        assert_eq!(schedule(1).fee(dec("10000")), dec("1.00"));
+    }
+}
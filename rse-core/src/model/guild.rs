@@ -0,0 +1,126 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Settings each Discord server the bot is in can change for itself
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    num::NonZeroU64,
+    str::FromStr,
+};
+
+use snafu::Snafu;
+
+/// Something privileged a member can be allowed to do. Configured admins may do everything, and
+/// servers can grant each permission to roles of their own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Permission {
+    /// Look at the audit log, usage, accounts and the data of other users
+    ViewAdmin,
+    /// Retire shares held by others, such as when forcibly closing an account
+    ManageStocks,
+    /// Close accounts and review withdrawals
+    ManageBalances,
+    /// Halt and resume trading in a stock
+    Halt,
+}
+
+impl Permission {
+    /// Every permission, in the order they are listed in
+    pub const ALL: [Self; 4] = [
+        Self::ViewAdmin,
+        Self::ManageStocks,
+        Self::ManageBalances,
+        Self::Halt,
+    ];
+
+    /// The stable name this permission is stored under
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::ViewAdmin => "view_admin",
+            Self::ManageStocks => "manage_stocks",
+            Self::ManageBalances => "manage_balances",
+            Self::Halt => "halt",
+        }
+    }
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Permission {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|permission| permission.as_str() == s)
+            .ok_or(ParseError)
+    }
+}
+
+/// Failed to parse a [`Permission`] from its stored form
+#[derive(Debug, Snafu, Clone, Copy, PartialEq, Eq)]
+#[snafu(display("Not a valid permission"))]
+pub struct ParseError;
+
+/// How the bot behaves in one Discord server. Servers that never changed anything get the
+/// [`Default`], which enables trading and leaves everything else unset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuildSettings {
+    /// The channel market-wide announcements are posted to in this server, if any
+    pub market_feed_channel: Option<NonZeroU64>,
+    /// Whether orders may be placed from this server
+    pub trading_enabled: bool,
+    /// The locale replies fall back to when the user's own isn't translated
+    pub locale: Option<String>,
+    /// Members with this role have every [`Permission`] in this server
+    pub admin_role: Option<NonZeroU64>,
+    /// The permissions members of each role have in this server, on top of the admin role's
+    pub role_permissions: BTreeMap<NonZeroU64, BTreeSet<Permission>>,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        Self {
+            market_feed_channel: None,
+            trading_enabled: true,
+            locale: None,
+            admin_role: None,
+            role_permissions: BTreeMap::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn permissions_round_trip_through_their_names() {
+        for permission in Permission::ALL {
+            assert_eq!(permission.as_str().parse(), Ok(permission));
+        }
+
+        assert_eq!("admin".parse::<Permission>(), Err(ParseError));
+    }
+}
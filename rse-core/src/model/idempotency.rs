@@ -0,0 +1,154 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Keys clients send along with requests that change something, so a request retried after a
+//! network failure is only carried out once
+
+use sha2::{Digest, Sha256};
+use snafu::ensure;
+
+use crate::model::order::NewOrder;
+
+/// A key chosen by a client for one request and sent again with every retry of it. Between 1 and
+/// [`IdempotencyKey::MAX_LEN`] printable ASCII characters. Keys are scoped to the account making
+/// the request, so clients only need to avoid reusing their own.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IdempotencyKey(String);
+
+impl IdempotencyKey {
+    /// The longest a key may be
+    pub const MAX_LEN: usize = 255;
+
+    /// Creates a new [`IdempotencyKey`]
+    ///
+    /// # Errors
+    /// See [`ParseError`] for more information
+    pub fn new(key: &str) -> Result<Self, ParseError> {
+        ensure!(
+            !key.is_empty() && key.len() <= Self::MAX_LEN,
+            InvalidLenSnafu
+        );
+        ensure!(key.bytes().all(|b| b.is_ascii_graphic()), InvalidCharsSnafu);
+
+        Ok(Self(key.to_owned()))
+    }
+
+    /// Gets the key as a string slice
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for IdempotencyKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<&str> for IdempotencyKey {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+/// Errors when parsing a value into an [`IdempotencyKey`]
+#[derive(Debug, snafu::Snafu, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// Keys are made of printable ASCII characters, without spaces
+    #[snafu(display("Must only contain printable ASCII characters"))]
+    InvalidChars,
+    /// Keys are between 1 and 255 characters long
+    #[snafu(display("Length must be between 1 and 255 characters"))]
+    InvalidLen,
+}
+
+/// A fingerprint of a request, stored with its key so the key being sent again with a different
+/// request is noticed
+pub type RequestHash = [u8; 32];
+
+/// Fingerprints an order request
+#[must_use]
+pub fn order_hash(order: &NewOrder) -> RequestHash {
+    let request = serde_json::to_vec(order).expect("Orders always serialize");
+    Sha256::digest(request).into()
+}
+
+/// What came of a request made with an idempotency key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Idempotent<T> {
+    /// The request was carried out now
+    Executed(T),
+    /// The key was already used for the same request, which is not carried out again. Holds what
+    /// it returned the first time.
+    Replayed(T),
+}
+
+impl<T> Idempotent<T> {
+    /// What the request returned, whether now or the first time
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::Executed(v) | Self::Replayed(v) => v,
+        }
+    }
+
+    /// Whether the request had already been carried out
+    pub const fn is_replay(&self) -> bool {
+        matches!(self, Self::Replayed(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::model::{Price, Shares, order::Side, ticker::Ticker};
+
+    fn order(quantity: u32) -> NewOrder {
+        NewOrder {
+            user: Uuid::nil(),
+            ticker: Ticker::try_from("ABC").expect("Valid ticker"),
+            side: Side::Buy,
+            price: Price::new(10.into()).expect("Valid price"),
+            quantity: Shares::new(quantity).expect("Valid shares"),
+            expires_at: None,
+            immediate_or_cancel: false,
+        }
+    }
+
+    #[test]
+    fn keys_are_printable_ascii() {
+        assert!(IdempotencyKey::new("3f9c-retry_1").is_ok());
+        assert_eq!(IdempotencyKey::new(""), Err(ParseError::InvalidLen));
+        assert_eq!(
+            IdempotencyKey::new(&"a".repeat(256)),
+            Err(ParseError::InvalidLen)
+        );
+        assert_eq!(IdempotencyKey::new("a key"), Err(ParseError::InvalidChars));
+        assert_eq!(IdempotencyKey::new("clé"), Err(ParseError::InvalidChars));
+    }
+
+    #[test]
+    fn only_identical_orders_hash_the_same() {
+        assert_eq!(order_hash(&order(5)), order_hash(&order(5)));
+        assert_ne!(order_hash(&order(5)), order_hash(&order(6)));
+    }
+}
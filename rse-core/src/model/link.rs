@@ -0,0 +1,166 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! One-time codes proving that whoever holds an account also controls a Minecraft player or
+//! Discord user, before the two are linked
+
+use std::{num::NonZeroU64, str::FromStr};
+
+use snafu::{Snafu, ensure};
+use uuid::Uuid;
+
+/// A short code an account is given to link another identity to it. Made of characters that
+/// can't be mistaken for one another, so `0`, `1`, `I` and `O` never appear, and parsed
+/// regardless of case. Left out of [`Debug`] output, as anyone reading it could use a live code.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct LinkCode([u8; LinkCode::LEN]);
+
+impl LinkCode {
+    /// How many characters a code has
+    pub const LEN: usize = 6;
+
+    /// Every character a code is made of. 32 of them, so each random byte maps onto one evenly
+    const ALPHABET: &[u8; 32] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+    /// Creates a new code from the operating system's secure random number generator
+    ///
+    /// # Panics
+    /// If the operating system can't provide random bytes
+    #[must_use]
+    pub fn generate() -> Self {
+        let mut bytes = [0; Self::LEN];
+        getrandom::fill(&mut bytes).expect("The OS random number generator is available");
+
+        Self(bytes.map(|byte| Self::ALPHABET[usize::from(byte) % Self::ALPHABET.len()]))
+    }
+
+    /// Gets the code as a string slice
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0).expect("Codes are ASCII")
+    }
+}
+
+impl std::fmt::Display for LinkCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::fmt::Debug for LinkCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("LinkCode(<redacted>)")
+    }
+}
+
+impl FromStr for LinkCode {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_uppercase();
+        let bytes: [u8; Self::LEN] = s.as_bytes().try_into().map_err(|_| ParseError)?;
+        ensure!(
+            bytes.iter().all(|byte| Self::ALPHABET.contains(byte)),
+            ParseSnafu
+        );
+
+        Ok(Self(bytes))
+    }
+}
+
+/// Failed to parse a [`LinkCode`]
+#[derive(Debug, Snafu, Clone, Copy, PartialEq, Eq)]
+#[snafu(display("Not a valid link code"))]
+pub struct ParseError;
+
+/// Someone an account can be looked up by. Discord users and Minecraft players can also be
+/// linked to an account with a [`LinkCode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Identity {
+    /// A Discord user, identified by their snowflake
+    Discord(NonZeroU64),
+    /// A Minecraft player, identified by their UUID
+    Minecraft(Uuid),
+    /// An account itself, identified by its internal ID
+    Internal(Uuid),
+}
+
+impl Identity {
+    /// What kind of identity this is
+    #[must_use]
+    pub const fn kind(&self) -> IdentityKind {
+        match self {
+            Self::Discord(_) => IdentityKind::Discord,
+            Self::Minecraft(_) => IdentityKind::Minecraft,
+            Self::Internal(_) => IdentityKind::Internal,
+        }
+    }
+}
+
+/// The kind of an [`Identity`], for saying which one an account couldn't be found by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IdentityKind {
+    /// A Discord user
+    Discord,
+    /// A Minecraft player
+    Minecraft,
+    /// An internal account ID
+    Internal,
+}
+
+impl std::fmt::Display for IdentityKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Discord => "that Discord user",
+            Self::Minecraft => "that Minecraft player",
+            Self::Internal => "that ID",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_codes_parse_back() {
+        for _ in 0..100 {
+            let code = LinkCode::generate();
+
+            assert_eq!(code.as_str().parse(), Ok(code));
+            assert!(!code.as_str().contains(['0', '1', 'I', 'O']));
+        }
+    }
+
+    #[test]
+    fn parsing_ignores_case_and_rejects_ambiguous_characters() {
+        let code: LinkCode = " ab3xyz ".parse().expect("Valid code");
+
+        assert_eq!(code.as_str(), "AB3XYZ");
+        assert_eq!("AB0XYZ".parse::<LinkCode>(), Err(ParseError));
+        assert_eq!("ABCDE".parse::<LinkCode>(), Err(ParseError));
+        assert_eq!("ABCDEFG".parse::<LinkCode>(), Err(ParseError));
+    }
+
+    #[test]
+    fn debug_output_hides_the_code() {
+        let code: LinkCode = "AB3XYZ".parse().expect("Valid code");
+
+        assert_eq!(format!("{code:?}"), "LinkCode(<redacted>)");
+    }
+}
@@ -0,0 +1,322 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Types describing orders on the exchange
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::model::{Price, Shares, ticker::Ticker};
+
+/// Which side of the book an order is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Side {
+    /// Wants to buy shares
+    Buy,
+    /// Wants to sell shares
+    Sell,
+}
+
+impl Side {
+    /// The side an order would have to be on to trade with this one
+    #[must_use]
+    pub const fn opposite(&self) -> Self {
+        match self {
+            Self::Buy => Self::Sell,
+            Self::Sell => Self::Buy,
+        }
+    }
+}
+
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Buy => f.write_str("buy"),
+            Self::Sell => f.write_str("sell"),
+        }
+    }
+}
+
+/// The lifecycle state of an order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderStatus {
+    /// Placed while the market was closed, escrowed but waiting for it to open before being matched
+    Queued,
+    /// Resting on the book, waiting to be filled
+    Open,
+    /// Completely filled
+    Filled,
+    /// Cancelled by its owner, with any remainder released from escrow
+    Cancelled,
+    /// Reached its expiry before being filled, with any remainder released from escrow
+    Expired,
+}
+
+impl OrderStatus {
+    /// The stable name this status is stored under
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Open => "open",
+            Self::Filled => "filled",
+            Self::Cancelled => "cancelled",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+impl std::fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for OrderStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "queued" => Ok(Self::Queued),
+            "open" => Ok(Self::Open),
+            "filled" => Ok(Self::Filled),
+            "cancelled" => Ok(Self::Cancelled),
+            "expired" => Ok(Self::Expired),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A limit order that has not been placed yet
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct NewOrder {
+    /// The user placing the order
+    pub user: Uuid,
+    /// The stock to trade
+    pub ticker: Ticker,
+    /// Whether the order buys or sells
+    pub side: Side,
+    /// The worst price per share the user will accept
+    pub price: Price,
+    /// The number of shares to trade
+    pub quantity: Shares,
+    /// When the order stops being matched, if ever
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Whether whatever isn't filled immediately is cancelled instead of resting on the book
+    pub immediate_or_cancel: bool,
+}
+
+/// A limit order placed by a user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Order {
+    /// The ID of the order. Also gives time priority, as IDs only ever increase
+    pub id: i32,
+    /// The user that placed the order
+    pub user: Uuid,
+    /// The stock being traded
+    pub ticker: Ticker,
+    /// Whether the order buys or sells
+    pub side: Side,
+    /// The worst price per share the user will accept
+    pub price: Price,
+    /// The number of shares originally ordered
+    pub quantity: Shares,
+    /// The number of shares still waiting to be filled
+    pub remaining: Shares,
+    /// The state of the order
+    pub status: OrderStatus,
+    /// When the order was placed
+    pub created_at: DateTime<Utc>,
+    /// When the order stops being matched and is taken off the book. [`None`] means it stays until
+    /// filled or cancelled
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// A trade between two orders
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Fill {
+    /// The buying order
+    pub buy_order: i32,
+    /// The selling order
+    pub sell_order: i32,
+    /// The user that bought shares
+    pub buyer: Uuid,
+    /// The user that sold shares
+    pub seller: Uuid,
+    /// The price per share the trade executed at
+    pub price: Price,
+    /// The number of shares traded
+    pub shares: Shares,
+    /// The fee the buyer paid on top of the notional value
+    pub fee: Decimal,
+}
+
+impl Fill {
+    /// The value of the shares traded, excluding fees
+    #[must_use]
+    pub fn notional(&self) -> Decimal {
+        self.price.notional(self.shares)
+    }
+}
+
+/// What a user is told when one of their orders fills. Fills of the same order made in quick
+/// succession are collected into a single receipt.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Receipt {
+    /// The user the order belongs to
+    pub user: Uuid,
+    /// The order that filled
+    pub order: i32,
+    /// The stock traded
+    pub ticker: Ticker,
+    /// Whether the order bought or sold
+    pub side: Side,
+    /// Each fill, oldest first
+    pub fills: Vec<ReceiptFill>,
+    /// The shares of the order still waiting to be filled after the latest fill
+    pub remaining: Shares,
+    /// The shares of the stock the user held after the latest fill, including any in escrow
+    pub position: Shares,
+}
+
+/// A single fill on a [`Receipt`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct ReceiptFill {
+    /// The price per share the fill executed at
+    pub price: Price,
+    /// The number of shares traded
+    pub shares: Shares,
+    /// The fee paid on top of the notional value, which only buyers pay
+    pub fee: Decimal,
+}
+
+impl Receipt {
+    /// Adds the fills of `later`, a receipt for the same order, taking on what it says remains
+    pub fn merge(&mut self, later: Self) {
+        self.fills.extend(later.fills);
+        self.remaining = later.remaining;
+        self.position = later.position;
+    }
+
+    /// The number of shares traded across every fill
+    #[must_use]
+    pub fn shares(&self) -> u64 {
+        self.fills
+            .iter()
+            .map(|fill| u64::from(fill.shares.get()))
+            .sum()
+    }
+
+    /// The fees paid across every fill
+    #[must_use]
+    pub fn fees(&self) -> Decimal {
+        self.fills.iter().map(|fill| fill.fee).sum()
+    }
+
+    /// The volume weighted average price across every fill
+    #[must_use]
+    pub fn average_price(&self) -> Decimal {
+        let notional: Decimal = self
+            .fills
+            .iter()
+            .map(|fill| fill.price.notional(fill.shares))
+            .sum();
+
+        match self.shares() {
+            0 => Decimal::ZERO,
+            shares => notional / Decimal::from(shares),
+        }
+    }
+}
+
+/// A trade as seen by one of the users in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserTrade {
+    /// Increases with every trade, so trades can be listed after a given one
+    pub id: i32,
+    /// When the trade executed
+    pub time: DateTime<Utc>,
+    /// The stock traded
+    pub ticker: Ticker,
+    /// Whether the user bought or sold
+    pub side: Side,
+    /// The price per share the trade executed at
+    pub price: Price,
+    /// The number of shares traded
+    pub shares: Shares,
+    /// The fee the user paid, which is only ever charged to the buyer
+    pub fee: Decimal,
+    /// The profit or loss realized by selling, if known
+    pub realized_pl: Option<Decimal>,
+}
+
+/// A trade of a stock, between the accounts on either side of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickerTrade {
+    /// Increases with every trade
+    pub id: i32,
+    /// When the trade executed
+    pub time: DateTime<Utc>,
+    /// The price per share the trade executed at
+    pub price: Price,
+    /// The number of shares traded
+    pub shares: Shares,
+    /// The account that bought
+    pub buyer: Uuid,
+    /// The account that sold
+    pub seller: Uuid,
+}
+
+/// All open orders at a single price, aggregated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BookLevel {
+    /// The price of this level
+    pub price: Decimal,
+    /// The total number of shares waiting at this price
+    pub shares: u64,
+    /// The number of orders at this price
+    pub orders: u32,
+}
+
+/// A snapshot of the order book of a stock, best prices first
+#[derive(Debug, Clone, Default)]
+pub struct Book {
+    /// Buy orders, highest price first
+    pub bids: Vec<BookLevel>,
+    /// Sell orders, lowest price first
+    pub asks: Vec<BookLevel>,
+    /// The price of the most recent trade, if there has been one
+    pub last_price: Option<Decimal>,
+}
+
+impl Book {
+    /// The difference between the best ask and the best bid, if both sides have orders
+    #[must_use]
+    pub fn spread(&self) -> Option<Decimal> {
+        Some(self.asks.first()?.price - self.bids.first()?.price)
+    }
+
+    /// The price halfway between the best bid and the best ask, if both sides have orders
+    #[must_use]
+    pub fn mid_price(&self) -> Option<Decimal> {
+        Some((self.asks.first()?.price + self.bids.first()?.price) / Decimal::TWO)
+    }
+}
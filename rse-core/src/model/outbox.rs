@@ -0,0 +1,80 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Notifications written alongside the change they describe, and delivered once it has committed
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::dividend::Dividend;
+use crate::model::order::Receipt;
+use crate::model::withdrawal::Withdrawal;
+
+/// A notification kept in the outbox until it is delivered
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "data", rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum Notice {
+    /// A dividend was paid out to a stock's holders
+    DividendPaid(Dividend),
+    /// An admin approved or denied a withdrawal request
+    WithdrawalResolved(Withdrawal),
+    /// One of a user's orders filled, once or several times in quick succession
+    TradeReceipt(Receipt),
+}
+
+/// A row of the outbox claimed for delivery
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxEntry {
+    /// The ID of the row
+    pub id: i64,
+    /// The notice as it was written, which may not decode if it came from another version
+    pub payload: serde_json::Value,
+    /// How many deliveries have failed so far
+    pub attempts: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::model::withdrawal::{Address, WithdrawalStatus};
+
+    #[test]
+    fn notices_round_trip() {
+        let notice = Notice::WithdrawalResolved(Withdrawal {
+            id: 7,
+            user: Uuid::nil(),
+            amount: Decimal::new(1250, 2),
+            address: Address::try_from("k0abc12xyz").expect("Valid address"),
+            status: WithdrawalStatus::Processed,
+            requested_at: Utc::now(),
+            resolved_at: Some(Utc::now()),
+        });
+
+        let json = serde_json::to_value(&notice).expect("Notices serialize");
+        assert_eq!(json["kind"], "withdrawal_resolved");
+        assert_eq!(json["data"]["address"], "k0abc12xyz");
+        assert_eq!(json["data"]["status"], "processed");
+
+        let back: Notice = serde_json::from_value(json).expect("Notices deserialize");
+        assert_eq!(back, notice);
+    }
+}
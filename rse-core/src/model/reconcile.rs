@@ -0,0 +1,279 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Checks that the books still add up, so bugs corrupting them are noticed before users do
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use super::ticker::Ticker;
+
+/// What an account holds next to what its history says it should
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AccountTotals {
+    /// The account
+    pub user: Uuid,
+    /// Whether it is a system account, such as the treasury
+    pub system: bool,
+    /// Its spendable Kromer
+    pub balance: Decimal,
+    /// Its Kromer held in escrow for open buy orders
+    pub escrow: Decimal,
+    /// The sum of its ledger entries
+    pub ledger: Decimal,
+    /// What it made selling shares, less what it paid buying them, fees included
+    pub traded: Decimal,
+    /// What its open buy orders have left to pay, fees included
+    pub open_buys: Decimal,
+}
+
+impl AccountTotals {
+    /// Whatever about the account doesn't add up. System accounts are only checked for escrow, as
+    /// fees are credited to the treasury without a ledger entry.
+    pub fn discrepancies(&self) -> impl Iterator<Item = Discrepancy> {
+        let held = self.balance + self.escrow;
+        let expected = self.ledger + self.traded;
+
+        let balance = (!self.system && held != expected).then_some(Discrepancy::Balance {
+            user: self.user,
+            held,
+            expected,
+        });
+        let escrow = (self.escrow != self.open_buys).then_some(Discrepancy::Escrow {
+            user: self.user,
+            escrow: self.escrow,
+            open_orders: self.open_buys,
+        });
+
+        balance.into_iter().chain(escrow)
+    }
+}
+
+/// The shares of a stock held across every account, next to how many exist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StockTotals {
+    /// The stock
+    pub ticker: Ticker,
+    /// The number of shares issued
+    pub issued: i64,
+    /// The shares held across every account, escrowed ones included
+    pub held: i64,
+    /// The shares held in escrow for open sell orders
+    pub escrow: i64,
+    /// What the open sell orders have left to sell
+    pub open_sells: i64,
+}
+
+impl StockTotals {
+    /// Whatever about the stock doesn't add up
+    pub fn discrepancies(&self) -> impl Iterator<Item = Discrepancy> {
+        let over_held = (self.held > self.issued).then_some(Discrepancy::OverHeld {
+            ticker: self.ticker,
+            held: self.held,
+            issued: self.issued,
+        });
+        let escrow = (self.escrow != self.open_sells).then_some(Discrepancy::ShareEscrow {
+            ticker: self.ticker,
+            escrow: self.escrow,
+            open_orders: self.open_sells,
+        });
+
+        over_held.into_iter().chain(escrow)
+    }
+}
+
+/// Something in the books that doesn't add up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// An account's Kromer, escrow included, differs from what its ledger and trades add up to
+    Balance {
+        /// The account
+        user: Uuid,
+        /// Its balance and escrow
+        held: Decimal,
+        /// What its ledger and trades add up to
+        expected: Decimal,
+    },
+    /// An account's escrowed Kromer differs from what its open buy orders have left to pay
+    Escrow {
+        /// The account
+        user: Uuid,
+        /// Its escrow
+        escrow: Decimal,
+        /// What its open buy orders have left to pay
+        open_orders: Decimal,
+    },
+    /// More shares of a stock are held than were issued
+    OverHeld {
+        /// The stock
+        ticker: Ticker,
+        /// The shares held
+        held: i64,
+        /// The shares issued
+        issued: i64,
+    },
+    /// The shares of a stock in escrow differ from what its open sell orders have left to sell
+    ShareEscrow {
+        /// The stock
+        ticker: Ticker,
+        /// The shares in escrow
+        escrow: i64,
+        /// What its open sell orders have left to sell
+        open_orders: i64,
+    },
+}
+
+impl std::fmt::Display for Discrepancy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Balance {
+                user,
+                held,
+                expected,
+            } => write!(
+                f,
+                "Account {user} holds {held} Kromer, but its ledger and trades add up to {expected}"
+            ),
+            Self::Escrow {
+                user,
+                escrow,
+                open_orders,
+            } => write!(
+                f,
+                "Account {user} has {escrow} Kromer in escrow, but its open orders need {open_orders}"
+            ),
+            Self::OverHeld {
+                ticker,
+                held,
+                issued,
+            } => write!(
+                f,
+                "{held} shares of ${ticker} are held, but only {issued} were issued"
+            ),
+            Self::ShareEscrow {
+                ticker,
+                escrow,
+                open_orders,
+            } => write!(
+                f,
+                "{escrow} shares of ${ticker} are in escrow, but its open orders need {open_orders}"
+            ),
+        }
+    }
+}
+
+/// What a reconciliation of the books found. Discrepancies are only reported, never fixed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconciliationReport {
+    /// When the reconciliation started
+    pub started_at: DateTime<Utc>,
+    /// The number of accounts checked
+    pub accounts: u64,
+    /// The number of stocks checked
+    pub stocks: u64,
+    /// Everything that didn't add up, accounts first
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl ReconciliationReport {
+    /// An empty report of a reconciliation started at `started_at`
+    #[must_use]
+    pub const fn new(started_at: DateTime<Utc>) -> Self {
+        Self {
+            started_at,
+            accounts: 0,
+            stocks: 0,
+            discrepancies: Vec::new(),
+        }
+    }
+
+    /// Whether everything added up
+    #[must_use]
+    pub const fn is_clean(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(balance: i64, escrow: i64) -> AccountTotals {
+        AccountTotals {
+            user: Uuid::nil(),
+            system: false,
+            balance: Decimal::from(balance),
+            escrow: Decimal::from(escrow),
+            ledger: Decimal::from(100),
+            traded: Decimal::from(-30),
+            open_buys: Decimal::from(20),
+        }
+    }
+
+    fn stock(held: i64, escrow: i64) -> StockTotals {
+        StockTotals {
+            ticker: Ticker::try_from("ABC").expect("Valid ticker"),
+            issued: 100,
+            held,
+            escrow,
+            open_sells: 10,
+        }
+    }
+
+    #[test]
+    fn balanced_books_have_no_discrepancies() {
+        assert_eq!(account(50, 20).discrepancies().count(), 0);
+        assert_eq!(stock(100, 10).discrepancies().count(), 0);
+        assert_eq!(stock(90, 10).discrepancies().count(), 0);
+    }
+
+    #[test]
+    fn each_broken_invariant_is_reported() {
+        // Moving Kromer between the balance and escrow keeps the total right, but not the escrow
+        assert_eq!(
+            account(51, 19).discrepancies().collect::<Vec<_>>(),
+            [Discrepancy::Escrow {
+                user: Uuid::nil(),
+                escrow: Decimal::from(19),
+                open_orders: Decimal::from(20),
+            }]
+        );
+        assert!(matches!(
+            account(60, 20).discrepancies().collect::<Vec<_>>()[..],
+            [Discrepancy::Balance { .. }]
+        ));
+        assert!(matches!(
+            stock(101, 9).discrepancies().collect::<Vec<_>>()[..],
+            [
+                Discrepancy::OverHeld { held: 101, .. },
+                Discrepancy::ShareEscrow { escrow: 9, .. }
+            ]
+        ));
+    }
+
+    #[test]
+    fn system_accounts_skip_the_ledger() {
+        let treasury = AccountTotals {
+            system: true,
+            ..account(500, 20)
+        };
+
+        assert_eq!(treasury.discrepancies().count(), 0);
+    }
+}
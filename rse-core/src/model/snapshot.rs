@@ -0,0 +1,167 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The records a snapshot of the whole exchange is made of
+
+use std::num::NonZeroU64;
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::model::{Price, Shares, StockStatus, order::Order, ticker::Ticker};
+
+/// The parts of a snapshot, in the order they are taken and restored, each only referring to
+/// those before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotSection {
+    /// Every account, system ones included
+    Users,
+    /// Every stock, delisted ones included
+    Stocks,
+    /// Every non-empty holding
+    Holdings,
+    /// Orders that are open or queued
+    Orders,
+    /// The latest price of every stock that has one
+    Prices,
+}
+
+impl SnapshotSection {
+    /// Every section, in order
+    pub const ALL: [Self; 5] = [
+        Self::Users,
+        Self::Stocks,
+        Self::Holdings,
+        Self::Orders,
+        Self::Prices,
+    ];
+}
+
+/// A single row of a snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SnapshotRecord {
+    /// An account
+    User(SnapshotUser),
+    /// A stock
+    Stock(SnapshotStock),
+    /// Shares of a stock held by an account
+    Holding(SnapshotHolding),
+    /// An order still waiting to be filled
+    Order(SnapshotOrder),
+    /// The latest price of a stock
+    Price(SnapshotPrice),
+}
+
+impl SnapshotRecord {
+    /// The section the record belongs to
+    #[must_use]
+    pub const fn section(&self) -> SnapshotSection {
+        match self {
+            Self::User(_) => SnapshotSection::Users,
+            Self::Stock(_) => SnapshotSection::Stocks,
+            Self::Holding(_) => SnapshotSection::Holdings,
+            Self::Order(_) => SnapshotSection::Orders,
+            Self::Price(_) => SnapshotSection::Prices,
+        }
+    }
+}
+
+/// An account and its balance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotUser {
+    /// The internal ID of the account
+    pub id: Uuid,
+    /// The linked Discord ID
+    pub disc_id: Option<NonZeroU64>,
+    /// The linked Minecraft ID
+    pub mc_id: Option<Uuid>,
+    /// The Kromer the account owns, including what is on hold
+    pub balance: Decimal,
+    /// The part of the balance escrowed by open buy orders
+    pub escrow: Decimal,
+    /// Whether the account is frozen
+    pub frozen: bool,
+    /// Whether the account belongs to the exchange itself, such as the treasury
+    pub system: bool,
+    /// When the account was created
+    pub created_at: DateTime<Utc>,
+    /// When the account was closed
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+/// A stock and who owns it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotStock {
+    /// The stock's ticker
+    pub ticker: Ticker,
+    /// The number of shares outstanding
+    pub shares: Shares,
+    /// The account that owns the stock, if it was listed after owners were tracked
+    pub owner: Option<Uuid>,
+    /// Whether the stock can be traded
+    pub status: StockStatus,
+    /// The name its owner gave it
+    pub name: Option<String>,
+    /// The description its owner gave it
+    pub description: Option<String>,
+    /// The icon its owner gave it
+    pub icon_url: Option<String>,
+    /// When the stock was listed
+    pub created_at: DateTime<Utc>,
+}
+
+/// Shares of a stock held by an account
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotHolding {
+    /// The account holding the shares
+    pub user: Uuid,
+    /// The stock held
+    pub ticker: Ticker,
+    /// The number of shares held, including those escrowed
+    pub shares: Shares,
+    /// The part of the shares escrowed by open sell orders
+    pub escrow: Shares,
+    /// The average price paid per share, if known
+    pub avg_cost: Option<Decimal>,
+}
+
+/// An order still waiting to be filled, and the fee escrowed with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotOrder {
+    /// The order itself
+    #[serde(flatten)]
+    pub order: Order,
+    /// The fee held back for whatever remains of a buy order
+    pub fee_escrow: Decimal,
+}
+
+/// The latest price of a stock
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotPrice {
+    /// The stock priced
+    pub ticker: Ticker,
+    /// Its latest price
+    pub price: Price,
+    /// When it was traded at that price
+    pub updated_at: DateTime<Utc>,
+    /// The account the price is recorded against, the buyer of the trade that set it
+    pub by: Uuid,
+}
@@ -0,0 +1,68 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Summaries of trading on the exchange over a period of time
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+use crate::model::{Movers, ticker::Ticker};
+
+/// A single trade between two accounts
+#[derive(Debug, Clone, Copy)]
+pub struct Trade {
+    /// The stock traded
+    pub ticker: Ticker,
+    /// The price each share traded at
+    pub price: Decimal,
+    /// The number of shares traded
+    pub shares: u32,
+    /// When the trade happened
+    pub time: DateTime<Utc>,
+}
+
+impl Trade {
+    /// The total value of the trade
+    #[must_use]
+    pub fn value(&self) -> Decimal {
+        self.price * Decimal::from(self.shares)
+    }
+}
+
+/// Trading over a single UTC day
+#[derive(Debug, Clone)]
+pub struct DailySummary {
+    /// The day summarized
+    pub date: NaiveDate,
+    /// The number of trades made
+    pub trades: u64,
+    /// The total value of every trade made
+    pub volume: Decimal,
+    /// The trade with the highest value, if any were made
+    pub biggest_trade: Option<Trade>,
+    /// The stocks whose price moved the most over the day
+    pub movers: Movers,
+}
+
+impl DailySummary {
+    /// Whether nothing was traded all day
+    #[must_use]
+    pub const fn is_quiet(&self) -> bool {
+        self.trades == 0
+    }
+}
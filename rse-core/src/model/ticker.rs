@@ -53,6 +53,21 @@ impl Ticker {
     }
 }
 
+/// Checks that `v` could be the start of a ticker, returning it in uppercase. An empty prefix is
+/// the start of every ticker.
+///
+/// # Errors
+/// See [`ParseError`] for more information
+pub fn parse_prefix(v: &str) -> Result<String, ParseError> {
+    ensure!(v.len() <= 5, InvalidLenSnafu);
+    ensure!(
+        v.bytes().all(|b| b.is_ascii_alphabetic()),
+        InvalidCharsSnafu
+    );
+
+    Ok(v.to_ascii_uppercase())
+}
+
 impl std::fmt::Display for Ticker {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.write_str(self.as_str())
@@ -71,6 +86,19 @@ impl std::hash::Hash for Ticker {
     }
 }
 
+impl serde::Serialize for Ticker {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Ticker {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::try_from(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
 impl TryFrom<&str> for Ticker {
     type Error = ParseError;
 
@@ -88,7 +116,7 @@ impl TryFrom<&[u8]> for Ticker {
 }
 
 /// Errors when parsing a value into a [`Ticker`]
-#[derive(Debug, snafu::Snafu, Clone, Copy)]
+#[derive(Debug, snafu::Snafu, Clone, Copy, PartialEq, Eq)]
 pub enum ParseError {
     /// All characters must be ASCII letters
     #[snafu(display("All characters must be ASCII letters"))]
@@ -0,0 +1,83 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! How often each bot command is used, and how often it fails
+
+use std::{num::NonZeroU64, time::Duration};
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+
+/// One finished invocation of a bot command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandUse {
+    /// The command's qualified name, such as `admin reconcile`
+    pub command: String,
+    /// The Discord server it was used in, or [`None`] in DMs
+    pub guild: Option<NonZeroU64>,
+    /// Whether it succeeded
+    pub success: bool,
+    /// How long it took
+    pub duration: Duration,
+    /// When it finished
+    pub time: DateTime<Utc>,
+}
+
+/// How many times a command was used over some period, and how many of those failed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsageTotals {
+    /// Every invocation
+    pub uses: u64,
+    /// The invocations that failed
+    pub failures: u64,
+}
+
+impl UsageTotals {
+    /// The percentage of invocations that failed, or [`None`] if there were none
+    #[must_use]
+    pub fn error_rate(&self) -> Option<Decimal> {
+        (self.uses > 0)
+            .then(|| Decimal::from(self.failures) * Decimal::ONE_HUNDRED / Decimal::from(self.uses))
+    }
+}
+
+/// How a command was used over the last day and week
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandStats {
+    /// The command's qualified name
+    pub command: String,
+    /// Its uses over the last 24 hours
+    pub day: UsageTotals,
+    /// Its uses over the last 7 days
+    pub week: UsageTotals,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_rate_is_a_percentage() {
+        let totals = UsageTotals {
+            uses: 8,
+            failures: 2,
+        };
+        assert_eq!(totals.error_rate(), Some(Decimal::from(25)));
+        assert_eq!(UsageTotals::default().error_rate(), None);
+    }
+}
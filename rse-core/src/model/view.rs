@@ -0,0 +1,251 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! What other services and users are shown of our models. Models can change shape with the
+//! database, while these are what API consumers rely on, so fields here are only ever added
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use uuid::Uuid;
+
+use super::{HoldingPl, Price, Privacy, Shares, StockInfo, StockStatus, UserInfo, ticker::Ticker};
+
+/// An account as shown to others. Which Discord user and Minecraft player it is linked to is only
+/// shown to its owner, and the balance only when its privacy allows
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PublicUserInfo {
+    /// The internal ID of the account
+    pub id: Uuid,
+    /// The Kromer balance, `None` when hidden from the viewer
+    pub balance: Option<Decimal>,
+    /// When the account was created
+    pub created_at: DateTime<Utc>,
+    /// When the account was closed, if it was
+    pub closed_at: Option<DateTime<Utc>>,
+    /// What others may see of the account
+    pub privacy: Privacy,
+    /// Whether the owner lets others show their portfolio in public replies
+    pub public_portfolio: bool,
+    /// The linked Discord ID, as a string since snowflakes don't fit exactly in a JSON number.
+    /// Only shown to the owner
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disc_id: Option<String>,
+    /// The linked Minecraft ID. Only shown to the owner
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mc_id: Option<Uuid>,
+}
+
+impl PublicUserInfo {
+    /// Shows `info` to `viewer`, leaving out the linked IDs unless they own the account
+    #[must_use]
+    pub fn new(info: UserInfo, viewer: Option<&Uuid>) -> Self {
+        let owner = viewer == Some(&info.id);
+
+        Self {
+            id: info.id,
+            balance: info.balance,
+            created_at: info.created_at,
+            closed_at: info.closed_at,
+            privacy: info.privacy,
+            public_portfolio: info.public_portfolio,
+            disc_id: info.disc_id.filter(|_| owner).map(|id| id.to_string()),
+            mc_id: info.mc_id.filter(|_| owner),
+        }
+    }
+}
+
+/// Shows `info` to someone other than its owner
+impl From<UserInfo> for PublicUserInfo {
+    fn from(value: UserInfo) -> Self {
+        Self::new(value, None)
+    }
+}
+
+/// A holding, along with its value and profit or loss at the latest price
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct HoldingView {
+    /// The stock held
+    pub ticker: Ticker,
+    /// The number of shares held
+    pub shares: Shares,
+    /// The average price paid per share, if known
+    pub avg_cost: Option<Decimal>,
+    /// The latest price of the stock, if it has been traded
+    pub price: Option<Price>,
+    /// What the shares are worth at the latest price
+    pub value: Option<Decimal>,
+    /// The profit or loss of selling every share at the latest price
+    pub unrealized_pl: Option<Decimal>,
+}
+
+impl From<&HoldingPl> for HoldingView {
+    fn from(value: &HoldingPl) -> Self {
+        Self {
+            ticker: value.ticker,
+            shares: value.shares,
+            avg_cost: value.avg_cost,
+            price: value.price,
+            value: value.price.map(|price| price.notional(value.shares)),
+            unrealized_pl: value.unrealized_pl(),
+        }
+    }
+}
+
+/// A listed stock, with its metadata flattened in
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StockView {
+    /// The ticker of the stock
+    pub ticker: Ticker,
+    /// The display name of the company behind it, if set
+    pub name: Option<String>,
+    /// What the company does, if set
+    pub description: Option<String>,
+    /// An image representing the stock, if set
+    pub icon_url: Option<String>,
+    /// The account that owns the stock, if anyone does
+    pub owner: Option<Uuid>,
+    /// The total number of shares issued
+    pub shares: Shares,
+    /// Whether the stock can currently be traded
+    pub status: StockStatus,
+    /// When the stock was listed
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<StockInfo> for StockView {
+    fn from(value: StockInfo) -> Self {
+        Self {
+            ticker: value.ticker,
+            name: value.metadata.name,
+            description: value.metadata.description,
+            icon_url: value.metadata.icon_url,
+            owner: value.owner,
+            shares: value.shares,
+            status: value.status,
+            created_at: value.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::model::StockMetadata;
+
+    fn time() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_760_486_400, 0).expect("Valid timestamp")
+    }
+
+    fn user() -> UserInfo {
+        UserInfo {
+            id: Uuid::from_u128(1),
+            balance: Some(Decimal::new(1050, 2)),
+            available_balance: Some(Decimal::new(800, 2)),
+            held_balance: Some(Decimal::new(250, 2)),
+            created_at: time(),
+            mc_id: Some(Uuid::from_u128(2)),
+            disc_id: NonZeroU64::new(u64::MAX),
+            public_portfolio: true,
+            privacy: Privacy::HoldingsOnly,
+            closed_at: None,
+        }
+    }
+
+    #[test]
+    fn others_see_no_linked_ids() {
+        let json = serde_json::to_value(PublicUserInfo::from(user())).expect("Serializes");
+
+        assert_eq!(
+            json,
+            json!({
+                "id": "00000000-0000-0000-0000-000000000001",
+                "balance": "10.50",
+                "created_at": "2025-10-15T00:00:00Z",
+                "closed_at": null,
+                "privacy": "holdings_only",
+                "public_portfolio": true,
+            })
+        );
+    }
+
+    #[test]
+    fn owners_see_their_linked_ids() {
+        let user = user();
+        let json =
+            serde_json::to_value(PublicUserInfo::new(user, Some(&user.id))).expect("Serializes");
+
+        assert_eq!(json["disc_id"], "18446744073709551615");
+        assert_eq!(json["mc_id"], "00000000-0000-0000-0000-000000000002");
+    }
+
+    #[test]
+    fn holding_view_shape() {
+        let holding = HoldingPl {
+            ticker: Ticker::try_from("ABC").expect("Valid ticker"),
+            shares: Shares::new(3).expect("Valid shares"),
+            avg_cost: Some(Decimal::from(2)),
+            price: Some(Price::new(Decimal::new(550, 2)).expect("Valid price")),
+        };
+
+        assert_eq!(
+            serde_json::to_value(HoldingView::from(&holding)).expect("Serializes"),
+            json!({
+                "ticker": "ABC",
+                "shares": 3,
+                "avg_cost": "2",
+                "price": "5.50",
+                "value": "16.50",
+                "unrealized_pl": "10.50",
+            })
+        );
+    }
+
+    #[test]
+    fn stock_view_shape() {
+        let stock = StockInfo {
+            ticker: Ticker::try_from("ABC").expect("Valid ticker"),
+            owner: None,
+            shares: Shares::new(1000).expect("Valid shares"),
+            created_at: time(),
+            status: StockStatus::Halted,
+            metadata: StockMetadata {
+                name: Some("Abc Corp".to_owned()),
+                ..StockMetadata::default()
+            },
+        };
+
+        assert_eq!(
+            serde_json::to_value(StockView::from(stock)).expect("Serializes"),
+            json!({
+                "ticker": "ABC",
+                "name": "Abc Corp",
+                "description": null,
+                "icon_url": null,
+                "owner": null,
+                "shares": 1000,
+                "status": "halted",
+                "created_at": "2025-10-15T00:00:00Z",
+            })
+        );
+    }
+}
@@ -0,0 +1,180 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Requests to send Kromer out of the exchange, which admins review by hand
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use snafu::{OptionExt, ensure};
+use uuid::Uuid;
+
+/// A Kromer address, `k` followed by 9 lowercase ASCII letters or digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Address([u8; 10]);
+
+impl Address {
+    /// Creates a new [`Address`]
+    ///
+    /// # Errors
+    /// See [`ParseError`] for more information
+    pub fn new(v: &[u8]) -> Result<Self, ParseError> {
+        let v: [u8; 10] = v.try_into().ok().context(InvalidLenSnafu)?;
+
+        ensure!(
+            v[0] == b'k'
+                && v[1..]
+                    .iter()
+                    .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit()),
+            InvalidCharsSnafu
+        );
+
+        Ok(Self(v))
+    }
+
+    /// Gets the inner value as a string slice
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.0)
+            .expect("Addresses being valid ASCII, and therefore UTF-8, is one of our invariants")
+    }
+}
+
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl serde::Serialize for Address {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Address {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Self::try_from(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+impl TryFrom<&str> for Address {
+    type Error = ParseError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        Self::new(value.as_bytes())
+    }
+}
+
+/// Errors when parsing a value into an [`Address`]
+#[derive(Debug, snafu::Snafu, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// Addresses start with `k`, followed by lowercase ASCII letters or digits
+    #[snafu(display("Must be a `k` followed by lowercase letters or digits"))]
+    InvalidChars,
+    /// Addresses are exactly 10 characters
+    #[snafu(display("Length must be exactly 10 characters"))]
+    InvalidLen,
+}
+
+/// Where a withdrawal request is in its review
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WithdrawalStatus {
+    /// Waiting on an admin, with the amount held from the user's balance
+    Pending,
+    /// Approved by an admin, and handed on to be sent out
+    Processed,
+    /// Denied by an admin, with the amount released back to the user's balance
+    Denied,
+}
+
+impl WithdrawalStatus {
+    /// The stable name this status is stored under
+    #[must_use]
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Processed => "processed",
+            Self::Denied => "denied",
+        }
+    }
+}
+
+impl std::fmt::Display for WithdrawalStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for WithdrawalStatus {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(Self::Pending),
+            "processed" => Ok(Self::Processed),
+            "denied" => Ok(Self::Denied),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A request to send Kromer from a user's balance to an address outside the exchange
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Withdrawal {
+    /// The ID of the request
+    pub id: i64,
+    /// Who asked for the withdrawal
+    pub user: Uuid,
+    /// The Kromer to send
+    pub amount: Decimal,
+    /// Where to send it
+    pub address: Address,
+    /// Where the request is in its review
+    pub status: WithdrawalStatus,
+    /// When the request was made
+    pub requested_at: DateTime<Utc>,
+    /// When an admin approved or denied the request
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_parse() {
+        let address = Address::try_from("k0abc12xyz").expect("Valid address");
+        assert_eq!(address.as_str(), "k0abc12xyz");
+
+        assert_eq!(Address::try_from("k0abc"), Err(ParseError::InvalidLen));
+        assert_eq!(
+            Address::try_from("k0abc12xyzz"),
+            Err(ParseError::InvalidLen)
+        );
+        assert_eq!(
+            Address::try_from("x0abc12xyz"),
+            Err(ParseError::InvalidChars)
+        );
+        assert_eq!(
+            Address::try_from("k0ABC12xyz"),
+            Err(ParseError::InvalidChars)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Delivery of the notices kept in the outbox. Notices are written in the same transaction as the
+//! change they describe, then handed to a [`Notifier`] by [`Service::dispatch_outbox`] until it
+//! succeeds. Delivery is at least once: a dispatcher that dies after delivering a notice but
+//! before marking it sent will deliver it again once its lease runs out, so notifiers should
+//! treat a repeated notice as harmless.
+//!
+//! [`Service::dispatch_outbox`]: crate::Service::dispatch_outbox
+
+use std::{num::NonZeroU32, time::Duration};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::model::outbox::Notice;
+
+/// The longest a failed notice waits before it is tried again, however many attempts came before
+const MAX_DELAY: Duration = Duration::from_hours(1);
+
+/// Something that delivers notices taken from the outbox, such as a Discord feed
+pub trait Notifier: Send + Sync {
+    /// Why a delivery failed, kept alongside the notice in the outbox
+    type Error: std::fmt::Display;
+
+    /// Delivers `notice`. May be called more than once for the same notice.
+    fn deliver(&self, notice: &Notice) -> impl Future<Output = Result<(), Self::Error>> + Send;
+}
+
+/// How often and how patiently the outbox is dispatched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DispatchPolicy {
+    /// How long to wait between checks for due notices
+    pub poll_interval: Duration,
+    /// The most notices claimed at once
+    pub batch: u32,
+    /// How many deliveries of a notice are tried before it is parked
+    pub max_attempts: NonZeroU32,
+    /// The backoff after the first failure, doubling with every failure after it
+    pub base_delay: Duration,
+    /// How long a claimed notice is held before another dispatcher may claim it. Should comfortably
+    /// exceed the time to deliver a whole batch.
+    pub lease: Duration,
+}
+
+impl Default for DispatchPolicy {
+    fn default() -> Self {
+        Self {
+            poll_interval: Duration::from_secs(5),
+            batch: 50,
+            max_attempts: NonZeroU32::new(8).expect("Non-zero"),
+            base_delay: Duration::from_secs(10),
+            lease: Duration::from_mins(5),
+        }
+    }
+}
+
+impl DispatchPolicy {
+    /// When to try a notice again after its `attempt`th delivery failed at `now`. Doubles from the
+    /// base delay with every attempt up to an hour. Returns [`None`] once the notice has used all
+    /// its attempts and should be parked.
+    #[must_use]
+    pub fn retry_at(&self, attempt: u32, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        if attempt >= self.max_attempts.get() {
+            return None;
+        }
+
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(MAX_DELAY);
+
+        Some(now + TimeDelta::from_std(delay).expect("Capped at an hour"))
+    }
+
+    /// When a notice claimed at `now` may be claimed again
+    #[must_use]
+    pub fn lease_until(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        TimeDelta::from_std(self.lease)
+            .ok()
+            .and_then(|lease| now.checked_add_signed(lease))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_then_parks() {
+        let policy = DispatchPolicy {
+            max_attempts: NonZeroU32::new(4).expect("Non-zero"),
+            ..DispatchPolicy::default()
+        };
+        let now = DateTime::UNIX_EPOCH;
+
+        assert_eq!(policy.retry_at(1, now), Some(now + TimeDelta::seconds(10)));
+        assert_eq!(policy.retry_at(2, now), Some(now + TimeDelta::seconds(20)));
+        assert_eq!(policy.retry_at(3, now), Some(now + TimeDelta::seconds(40)));
+        assert_eq!(policy.retry_at(4, now), None);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let policy = DispatchPolicy {
+            max_attempts: NonZeroU32::MAX,
+            ..DispatchPolicy::default()
+        };
+        let now = DateTime::UNIX_EPOCH;
+
+        assert_eq!(policy.retry_at(40, now), Some(now + TimeDelta::hours(1)));
+    }
+}
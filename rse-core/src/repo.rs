@@ -18,14 +18,42 @@
 
 //! Abstract implementation details for the backing stock repository
 
-use crate::model::{Pager, UserInfo, ticker::Ticker};
-use chrono::{DateTime, Utc};
+use crate::model::{
+    AccountMerge, AccountSummary, Candle, CandleInterval, ClosedAccount, ExchangeCounts,
+    HoldingOrdering, HoldingPl, ImportedPrice, LatestPrice, LedgerKind, MergeConflict, Movers,
+    Page, Pager, PoolUsage, PortfolioSnapshot, Price, PriceChange, Privacy, Registered, Shares,
+    Statement, StatementSnapshot, StockInfo, StockMatch, StockMetadata, StockOrdering, StockStatus,
+    UserFilter, UserInfo,
+    adjustment::{Adjustment, AdjustmentReason},
+    api_key::ApiKey,
+    audit::{Actor, AuditEntry, AuditFilter, NewAuditEntry},
+    deposit::Deposit,
+    dividend::{Dividend, Shareholders},
+    fee::FeeSchedule,
+    guild::GuildSettings,
+    idempotency::{IdempotencyKey, Idempotent},
+    link::{Identity, LinkCode},
+    order::{Book, BookLevel, Fill, NewOrder, Order, Side, TickerTrade, UserTrade},
+    outbox::OutboxEntry,
+    reconcile::{AccountTotals, StockTotals},
+    snapshot::{SnapshotRecord, SnapshotSection},
+    summary::DailySummary,
+    ticker::Ticker,
+    usage::{CommandStats, CommandUse},
+    withdrawal::{Address, Withdrawal},
+};
+use chrono::{DateTime, NaiveDate, Utc};
 use rust_decimal::Decimal;
 use snafu::Snafu;
+use std::num::NonZeroU64;
 use uuid::Uuid;
 
+pub use cache::CachedRepo;
 pub use pg::PgPort;
+pub use retry::{RetryPolicy, RetryingRepo};
+mod cache;
 mod pg;
+mod retry;
 
 #[allow(missing_docs)]
 pub type Result<T> = std::result::Result<T, Error>;
@@ -43,11 +71,81 @@ pub enum Error {
     /// should be able to determine this.
     #[snafu(display("An account is already linked to this"))]
     AlreadyLinked,
+    /// The user does not have enough Kromer to cover an order
+    #[snafu(display("Insufficient funds"))]
+    InsufficientFunds,
+    /// The user does not have enough shares to cover an order
+    #[snafu(display("Insufficient shares"))]
+    InsufficientShares,
+    /// Could not find the given stock
+    #[snafu(display(r#"Could not find stock "{ticker}""#))]
+    StockNotFound { ticker: Ticker },
+    /// Trading in the given stock is halted or it was delisted
+    #[snafu(display(r#"Trading in "{ticker}" is halted"#))]
+    StockHalted { ticker: Ticker },
+    /// Could not find an open order with the given ID belonging to the user
+    #[snafu(display(r#"Could not find open order "{id}""#))]
+    OrderNotFound { id: i32 },
+    /// The user does not own the stock
+    #[snafu(display(r#"User does not own stock "{ticker}""#))]
+    NotStockOwner { ticker: Ticker },
+    /// A stock with the given ticker already exists
+    #[snafu(display(r#"Stock "{ticker}" already exists"#))]
+    StockExists { ticker: Ticker },
+    /// Issuing shares would exceed the daily issuance cap
+    #[snafu(display("Issuance cap exceeded, {available} shares available"))]
+    IssuanceCapExceeded { available: u64 },
+    /// Nobody other than the payer would receive anything from a dividend
+    #[snafu(display(r#"No shareholders to pay for stock "{ticker}""#))]
+    NoShareholders { ticker: Ticker },
+    /// The account still has open orders, shares, or a balance with nowhere to send it
+    #[snafu(display(
+        "Account still has {open_orders} open orders, {holdings} holdings and {balance} Kromer"
+    ))]
+    AccountNotEmpty {
+        open_orders: u64,
+        holdings: u64,
+        balance: Decimal,
+    },
+    /// The accounts can't be merged as they are
+    #[snafu(display("Can't merge the accounts, as {reason}"))]
+    MergeConflict { reason: MergeConflict },
+    /// Could not find a pending withdrawal request with the given ID
+    #[snafu(display(r#"Could not find pending withdrawal "{id}""#))]
+    WithdrawalNotFound { id: i64 },
+    /// Could not find a balance adjustment with the given ID that isn't itself a reversal
+    #[snafu(display(r#"Could not find adjustment "{id}""#))]
+    AdjustmentNotFound { id: i64 },
+    /// The balance adjustment was already reversed
+    #[snafu(display(r#"Adjustment "{id}" was already reversed"#))]
+    AlreadyReversed { id: i64 },
+    /// The link code doesn't exist, expired or was already used
+    #[snafu(display("Invalid link code"))]
+    InvalidLinkCode,
+    /// The idempotency key was already used for a different request
+    #[snafu(display("Idempotency key was used for a different request"))]
+    IdempotencyKeyReused,
+    /// The backing store couldn't be reached or dropped the request, such as when a connection
+    /// is reset or no pooled connection frees up in time. Trying again may succeed.
+    #[snafu(display("The DB is temporarily unavailable"))]
+    Unavailable,
+    /// The write kept deadlocking or failing to serialize against concurrent ones, and was rolled
+    /// back each time. Trying again later may succeed.
+    #[snafu(display("The write conflicted with concurrent ones"))]
+    Conflict,
     /// An underlying error that either do not know, or cannot handle
     #[snafu(display("An unspecified DB error occurred"))]
     Unspecified,
 }
 
+impl Error {
+    /// Whether the error is likely to go away if the operation is tried again
+    #[must_use]
+    pub const fn is_transient(&self) -> bool {
+        matches!(self, Self::Unavailable)
+    }
+}
+
 /// A port handling all the logic for storing and querying our backing data store.
 pub trait StockRepository: 'static + Clone + Send + Sync {
     /// Checks if a user exists
@@ -66,7 +164,7 @@ pub trait StockRepository: 'static + Clone + Send + Sync {
     ///
     /// # Errors
     /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
-    fn discord_to_id(&self, id: i64) -> impl Future<Output = Result<Option<Uuid>>> + Send;
+    fn discord_to_id(&self, id: NonZeroU64) -> impl Future<Output = Result<Option<Uuid>>> + Send;
 
     /// Takes a Minecraft UUID and returns the UUID of the account its linked to if it exists.
     ///
@@ -74,31 +172,243 @@ pub trait StockRepository: 'static + Clone + Send + Sync {
     /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
     fn mc_to_id(&self, id: &Uuid) -> impl Future<Output = Result<Option<Uuid>>> + Send;
 
+    /// Takes a Minecraft username and returns the UUID it was last resolved to, as long as that
+    /// was no earlier than `since`. Usernames are case insensitive.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn mc_username_to_uuid(
+        &self,
+        name: &str,
+        since: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Option<Uuid>>> + Send;
+
+    /// Remembers that the Minecraft username `name` resolved to `id` just now, replacing whatever
+    /// it resolved to before
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn cache_mc_username(&self, name: &str, id: &Uuid) -> impl Future<Output = Result<()>> + Send;
+
     /// Retrieves user info given an ID, returning it if it exists
     ///
     /// # Errors
     /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
     fn user_info(&self, id: &Uuid) -> impl Future<Output = Result<Option<UserInfo>>> + Send;
 
-    /// Registers a user, returning the UUID of the new user.
+    /// Sets what others may see of a user's account
+    ///
+    /// # Errors
+    /// * [`AccountNotFound`](Error::AccountNotFound) - The user does not exist
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn set_privacy(&self, id: &Uuid, privacy: Privacy) -> impl Future<Output = Result<()>> + Send;
+
+    /// Sets whether a user lets others show their portfolio in public replies
+    ///
+    /// # Errors
+    /// * [`AccountNotFound`](Error::AccountNotFound) - The user does not exist
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn set_public_portfolio(
+        &self,
+        id: &Uuid,
+        public: bool,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Sets whether a user is sent a receipt when their orders fill
+    ///
+    /// # Errors
+    /// * [`AccountNotFound`](Error::AccountNotFound) - The user does not exist
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn set_receipts(&self, id: &Uuid, enabled: bool) -> impl Future<Output = Result<()>> + Send;
+
+    /// Looks up the Discord snowflake and Minecraft UUID linked to each of `ids` at once.
+    /// Accounts that don't exist are left out rather than failing the whole batch.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    #[allow(clippy::type_complexity)]
+    fn identities(
+        &self,
+        ids: &[Uuid],
+    ) -> impl Future<Output = Result<Vec<(Uuid, Option<NonZeroU64>, Option<Uuid>)>>> + Send;
+
+    /// Registers a user, returning the UUID of the new user. Registering an ID already linked to
+    /// an account creates nothing and returns that account instead, so racing registrations of
+    /// the same ID agree on a single account.
     ///
     /// # Arguments
     /// At least one of `disc_id` and `mc_id` should be [Some], but never both for external
     /// endpoints.
     /// * `disc_id` - The Discord snowflake of the new user
     /// * `mc_id`- The Minecraft UUID of the new user
+    /// * `actor` - Who is registering the user, recorded in the audit log in the same transaction
     ///
     /// # Errors
-    /// * [`AlreadyLinked`](Error::AlreadyLinked) - The passed in ID is already linked to an
-    ///   account
+    /// * [`AlreadyLinked`](Error::AlreadyLinked) - Both IDs were passed in and are linked to
+    ///   different accounts
     /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
     fn register_user(
         &self,
-        disc_id: Option<i64>,
+        disc_id: Option<NonZeroU64>,
         mc_id: Option<&Uuid>,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<Registered>> + Send;
+
+    /// Gives an account a new one-time code for linking another identity to it, valid until
+    /// `expires_at`. Codes the account was given before and hasn't used are replaced.
+    ///
+    /// # Errors
+    /// * [`AccountNotFound`](Error::AccountNotFound) - The account does not exist or was closed
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn create_link_code(
+        &self,
+        id: &Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> impl Future<Output = Result<LinkCode>> + Send;
+
+    /// Uses up a link code, linking `identity` to the account it was given to and returning that
+    /// account. Checking the code, marking it used and linking happen in one transaction, so a
+    /// code used twice at once only links once. The link is recorded in the audit log with the
+    /// identity as the actor.
+    ///
+    /// # Errors
+    /// * [`InvalidLinkCode`](Error::InvalidLinkCode) - The code doesn't exist, expired by `now`
+    ///   or was already used
+    /// * [`AlreadyLinked`](Error::AlreadyLinked) - The identity is linked to an account already,
+    ///   the account already has an identity of the same kind, or the identity is an account
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn consume_link_code(
+        &self,
+        code: &LinkCode,
+        identity: Identity,
+        now: DateTime<Utc>,
     ) -> impl Future<Output = Result<Uuid>> + Send;
 
-    /// Lists a user's holdings in a paginated way, as well as the total number of entries.
+    /// Forgets every link code that expired before `before`, used or not, returning how many
+    /// there were
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn purge_link_codes(&self, before: DateTime<Utc>) -> impl Future<Output = Result<u64>> + Send;
+
+    /// Gives an account a new key for the REST API, storing only its hash. Any key the account was
+    /// given before stops working.
+    ///
+    /// # Errors
+    /// * [`AccountNotFound`](Error::AccountNotFound) - The account does not exist or was closed
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn create_api_key(&self, id: &Uuid) -> impl Future<Output = Result<ApiKey>> + Send;
+
+    /// Finds the account `key` was given to. Returns [`None`] if it was never given out, was
+    /// replaced, or its account was closed.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn api_key_owner(&self, key: &ApiKey) -> impl Future<Output = Result<Option<Uuid>>> + Send;
+
+    /// Closes an account, unlinking it from its Discord user and Minecraft player so they can
+    /// register again. The account itself is kept so its history still points somewhere. Whatever
+    /// is left of the balance is held in a withdrawal request to `payout`, and the closure is
+    /// recorded in the audit log under `actor`, all in one transaction.
+    ///
+    /// Unless `force` is set, the account must have no open orders or shares. When set, its orders
+    /// are cancelled and its shares retired at the most recent price of each stock first. Shares
+    /// of a stock the account holds every share of can't be retired and are left alone.
+    ///
+    /// # Errors
+    /// * [`AccountNotFound`](Error::AccountNotFound) - The account does not exist, is owned by the
+    ///   exchange, or was already closed
+    /// * [`AccountNotEmpty`](Error::AccountNotEmpty) - The account has open orders or shares left,
+    ///   or a balance but no `payout` address
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn close_account(
+        &self,
+        id: &Uuid,
+        payout: Option<&Address>,
+        force: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<ClosedAccount>> + Send;
+
+    /// Merges the account `casualty` into `survivor`, moving its balance, shares, ledger entries,
+    /// trades, stocks and links onto `survivor` before closing it, all in one transaction. Shares
+    /// of a stock both hold are added together, averaging their cost basis. Moved ledger entries
+    /// keep a note of the account they came from. The merge is recorded in the audit log under
+    /// `actor`.
+    ///
+    /// With `dry_run` set, the transaction is rolled back instead, so nothing changes but the same
+    /// checks are made and what would have moved is returned.
+    ///
+    /// # Errors
+    /// * [`AccountNotFound`](Error::AccountNotFound) - Either account does not exist, is owned by
+    ///   the exchange, or was closed
+    /// * [`MergeConflict`](Error::MergeConflict) - The accounts can't be merged as they are
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn merge_accounts(
+        &self,
+        survivor: &Uuid,
+        casualty: &Uuid,
+        dry_run: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<AccountMerge>> + Send;
+
+    /// Creates an account owned by the exchange itself with the given ID, unless it already
+    /// exists. Creation is recorded in the audit log as a registration by the system. Returns
+    /// whether the account was created.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn ensure_system_account(&self, id: &Uuid) -> impl Future<Output = Result<bool>> + Send;
+
+    /// Checks that the repository can be reached by making the cheapest possible round trip to it.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - The underlying repository couldn't be reached
+    fn ping(&self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Reports how busy the pool of connections to the repository is, or `None` if it doesn't
+    /// pool them
+    fn pool_usage(&self) -> Option<PoolUsage>;
+
+    /// Gets the version of the most recent migration applied to the repository, or `None` if it
+    /// has none
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn schema_version(&self) -> impl Future<Output = Result<Option<i64>>> + Send;
+
+    /// Reads up to `limit` records of a section of a snapshot, continuing after `after`, the last
+    /// record of the previous chunk. Each chunk is read in its own repeatable read, read only
+    /// transaction, so no locks are held between chunks.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn snapshot_chunk(
+        &self,
+        section: SnapshotSection,
+        after: Option<&SnapshotRecord>,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<SnapshotRecord>>> + Send;
+
+    /// Writes records of a snapshot back as they are, in a single transaction. Records must come
+    /// in the order they were taken, and the repository must not already hold any of them.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository, such as a
+    ///   record that already exists
+    fn restore_snapshot(
+        &self,
+        records: &[SnapshotRecord],
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Counts the accounts and stocks on the exchange
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn exchange_counts(&self) -> impl Future<Output = Result<ExchangeCounts>> + Send;
+
+    /// Lists a user's holdings sorted by `order` in a paginated way, as well as the total number of
+    /// entries. Each holding includes the most recent price of its stock, if it has ever been
+    /// traded.
     ///
     /// # Errors
     /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
@@ -107,9 +417,184 @@ pub trait StockRepository: 'static + Clone + Send + Sync {
         &self,
         id: &Uuid,
         page: &Pager,
-    ) -> impl Future<Output = Result<Option<(Vec<(Ticker, u32)>, i64)>>> + Send;
+        order: HoldingOrdering,
+    ) -> impl Future<Output = Result<Option<Page<(Ticker, Shares, Option<Price>)>>>> + Send;
+
+    /// Lists a user's holdings with their cost basis and current price, sorted by `order` in a
+    /// paginated way, as well as the total number of entries.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn get_holdings_pl(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        order: HoldingOrdering,
+    ) -> impl Future<Output = Result<Option<Page<HoldingPl>>>> + Send;
+
+    /// Sums the value of all of a user's holdings at their most recent prices. Holdings in stocks
+    /// that have never been traded are not counted.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn holdings_value(&self, id: &Uuid) -> impl Future<Output = Result<Decimal>> + Send;
+
+    /// Sums the value of each user's holdings like [`holdings_value`](Self::holdings_value), but
+    /// for many users at once. Returns a value for every ID in the order given, zero for those with
+    /// no holdings or no account.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn portfolio_values(
+        &self,
+        ids: &[Uuid],
+    ) -> impl Future<Output = Result<Vec<(Uuid, Decimal)>>> + Send;
+
+    /// Gets a quick overview of a user's holdings, open orders and last `recent` transactions.
+    /// Unknown users get an empty summary.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn account_summary(
+        &self,
+        id: &Uuid,
+        recent: u32,
+    ) -> impl Future<Output = Result<AccountSummary>> + Send;
+
+    /// Gets a page of a user's statement, newest first: their ledger entries and trades, with the
+    /// fee on each purchase as its own entry, each alongside the running balance it left behind.
+    /// Only entries of `filter`'s kinds are listed, but the running balance always counts every
+    /// entry. Without a `snapshot`, the statement covers everything up to now, and the snapshot
+    /// returned should be passed back in for later pages so they cover the same entries.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn statement(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        filter: Option<LedgerKind>,
+        snapshot: Option<StatementSnapshot>,
+    ) -> impl Future<Output = Result<Statement>> + Send;
+
+    /// Lists up to `limit` of the trades a user took part in, oldest first, starting after the
+    /// trade with ID `after`. Trades a user made with themselves are listed as buys, and listing
+    /// prices, which aren't trades, are left out.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn trade_history(
+        &self,
+        id: &Uuid,
+        after: Option<i32>,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<UserTrade>>> + Send;
+
+    /// Lists the trades of a stock, newest first, alongside the total number of them. Listing
+    /// prices, which aren't trades, are left out.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn ticker_trades(
+        &self,
+        ticker: &Ticker,
+        page: &Pager,
+    ) -> impl Future<Output = Result<Page<TickerTrade>>> + Send;
+
+    /// Gets up to `count` stocks whose price rose the most on the UTC day `date` up to `until`, and
+    /// up to `count` whose price fell the most. Each stock's last price before `until` is compared
+    /// to its latest close before `date`. Stocks that haven't closed yet are compared to their
+    /// price a day before `until` instead, or to their oldest price if first traded after then.
+    /// Stocks that have never traded or haven't moved are left out.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn top_movers(
+        &self,
+        date: NaiveDate,
+        until: DateTime<Utc>,
+        count: u8,
+    ) -> impl Future<Output = Result<Movers>> + Send;
+
+    /// Summarizes trading over the UTC day `date`, including its top 3 movers
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn daily_summary(&self, date: NaiveDate) -> impl Future<Output = Result<DailySummary>> + Send;
 
-    /// Lists all stocks
+    /// Whether the summary of `date` has already been posted
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn summary_sent(&self, date: NaiveDate) -> impl Future<Output = Result<bool>> + Send;
+
+    /// Records that the summary of `date` has been posted. Recording a day twice does nothing.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn record_summary_sent(&self, date: NaiveDate) -> impl Future<Output = Result<()>> + Send;
+
+    /// Records the close of every stock traded by the end of the UTC day `date`: its last price
+    /// by then and the shares traded that day. Stocks already closed for `date` are left as they
+    /// are. Returns how many closes were recorded.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn record_daily_closes(&self, date: NaiveDate) -> impl Future<Output = Result<u64>> + Send;
+
+    /// Snapshots the balance and holdings value, as they stand, of up to `limit` open accounts
+    /// with IDs after `after`, in order of ID, as of the UTC day `date`. Accounts already
+    /// snapshotted for `date` are left as they are. Returns the last account in the page, or
+    /// [`None`] past the last one, and how many snapshots were recorded.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn record_portfolio_snapshots(
+        &self,
+        date: NaiveDate,
+        after: Option<&Uuid>,
+        limit: u32,
+    ) -> impl Future<Output = Result<(Option<Uuid>, u64)>> + Send;
+
+    /// Gets the snapshots of a user's portfolio taken on or after `since`, oldest first
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn portfolio_history(
+        &self,
+        id: &Uuid,
+        since: NaiveDate,
+    ) -> impl Future<Output = Result<Vec<PortfolioSnapshot>>> + Send;
+
+    /// Gets how the price of `ticker` moved since its latest close before the UTC day of `now`,
+    /// or over the day before `now` if it hasn't closed yet. Returns [`None`] if it never traded.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn price_change(
+        &self,
+        ticker: &Ticker,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Option<PriceChange>>> + Send;
+
+    /// Aggregates the trades of `ticker` from `from` up to `to` into candles of `interval`, oldest
+    /// first. Intervals start on multiples of their length since the Unix epoch, so days start at
+    /// midnight UTC. Intervals without trades have no candle, and listing prices, which aren't
+    /// trades, are left out while imported prices are counted.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn candles(
+        &self,
+        ticker: &Ticker,
+        interval: CandleInterval,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<Candle>>> + Send;
+
+    /// Lists the stocks whose ticker starts with `prefix`, sorted by `order`, with the price and
+    /// time of their most recent trade if they have been traded and their name if it was set.
+    /// Returns [`None`] if the page is empty.
     ///
     /// # Errors
     /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
@@ -117,5 +602,647 @@ pub trait StockRepository: 'static + Clone + Send + Sync {
     fn list_stocks(
         &self,
         page: &Pager,
-    ) -> impl Future<Output = Result<Option<(Vec<(Ticker, u32, Decimal, DateTime<Utc>)>, i64)>>> + Send;
+        order: StockOrdering,
+        prefix: &str,
+    ) -> impl Future<
+        Output = Result<
+            Option<
+                Page<(
+                    Ticker,
+                    Shares,
+                    Option<Price>,
+                    Option<DateTime<Utc>>,
+                    Option<String>,
+                )>,
+            >,
+        >,
+    > + Send;
+
+    /// Lists up to `limit` stocks whose ticker starts with `prefix`, in alphabetical order, with
+    /// their name if it was set. Meant for suggesting tickers as they are typed.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn search_tickers(
+        &self,
+        prefix: &str,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<(Ticker, Option<String>)>>> + Send;
+
+    /// Searches for stocks whose ticker, name or description contains `query`, or whose name or
+    /// description has words close to it, ignoring case. The best matches come first, with what
+    /// each matched best by. Characters with special meaning in `LIKE` patterns are matched as is.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn search_stocks(
+        &self,
+        query: &str,
+        page: &Pager,
+    ) -> impl Future<Output = Result<Page<StockMatch>>> + Send;
+
+    /// Lists accounts matching `filter`, in the order it asks for. Balances are always included,
+    /// leaving it to the caller to hide them.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn list_users(
+        &self,
+        page: &Pager,
+        filter: UserFilter,
+    ) -> impl Future<Output = Result<Page<UserInfo>>> + Send;
+
+    /// Appends an entry to the audit log. Only for actions that do not change any other state, as
+    /// mutating methods record their own entries within the same transaction.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn record_audit(&self, entry: &NewAuditEntry) -> impl Future<Output = Result<()>> + Send;
+
+    /// Lists audit log entries matching `filter`, newest first, as well as the total number of
+    /// matching entries.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn audit_log(
+        &self,
+        page: &Pager,
+        filter: &AuditFilter,
+    ) -> impl Future<Output = Result<Page<AuditEntry>>> + Send;
+
+    /// Places a limit order, escrowing the Kromer or shares needed to cover it, then matches it
+    /// against the book using [`match_order`](crate::matching::match_order), skipping resting
+    /// orders expired by `now`. Every fill moves shares and balances and is recorded as a stock
+    /// event, all in one transaction. Returns the order as it stands after matching, alongside its
+    /// fills.
+    ///
+    /// With a fee schedule, buy orders also escrow the fee on their full value, and each fill
+    /// charges the buyer its fee, credited to the treasury. Without one, no fees are charged at all.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`StockHalted`](Error::StockHalted) - Trading in the stock is halted or it was delisted
+    /// * [`AccountNotFound`](Error::AccountNotFound) - The user does not exist
+    /// * [`InsufficientFunds`](Error::InsufficientFunds) - The user can't cover a buy order
+    /// * [`InsufficientShares`](Error::InsufficientShares) - The user can't cover a sell order
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn place_order(
+        &self,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<(Order, Vec<Fill>)>> + Send;
+
+    /// Places a limit order while the market is closed, escrowing what it needs like
+    /// [`place_order`](Self::place_order) but keeping it off the book, as
+    /// [`Queued`](crate::model::order::OrderStatus::Queued), until
+    /// [`release_queued_orders`](Self::release_queued_orders) matches it. Queued orders can be
+    /// cancelled and expire like open ones.
+    ///
+    /// # Errors
+    /// * Any error [`place_order`](Self::place_order) returns
+    fn queue_order(
+        &self,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Order>> + Send;
+
+    /// Puts up to `limit` queued orders on the book, oldest first, matching each like a new order
+    /// in a single transaction, against resting orders that haven't expired by `now`. Orders for
+    /// stocks that are halted are put on the book without matching. Returns each order as it
+    /// stands after matching, alongside its fills, which is fewer than `limit` once there are none
+    /// left.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn release_queued_orders(
+        &self,
+        limit: u32,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<(Order, Vec<Fill>)>>> + Send;
+
+    /// Places an order like [`place_order`](Self::place_order), or queues it like
+    /// [`queue_order`](Self::queue_order) when `queue` is set, unless its user already used
+    /// `key` since `since`. The key is recorded along with a hash of the order and what placing it
+    /// returned, in the same transaction as the order. A repeated request is replayed from that
+    /// record instead of placing the order again, and concurrent requests with the same key wait
+    /// on each other, so only one of them places it. Keys recorded before `since` count as unused,
+    /// and failed requests don't record their key at all.
+    ///
+    /// # Errors
+    /// * [`IdempotencyKeyReused`](Error::IdempotencyKeyReused) - The user used the key for a
+    ///   different order since `since`
+    /// * Any error [`place_order`](Self::place_order) returns
+    fn place_order_once(
+        &self,
+        key: &IdempotencyKey,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        since: DateTime<Utc>,
+        queue: bool,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Idempotent<(Order, Vec<Fill>)>>> + Send;
+
+    /// Forgets every idempotency key recorded before `before`, returning how many there were
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn purge_idempotency_keys(
+        &self,
+        before: DateTime<Utc>,
+    ) -> impl Future<Output = Result<u64>> + Send;
+
+    /// Cancels an open or queued order belonging to `user`, releasing whatever remains of its
+    /// escrow.
+    ///
+    /// # Errors
+    /// * [`OrderNotFound`](Error::OrderNotFound) - There is no open order with this ID belonging
+    ///   to the user
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn cancel_order(&self, id: i32, user: &Uuid) -> impl Future<Output = Result<Order>> + Send;
+
+    /// Expires up to `limit` open or queued orders whose expiry is at or before `now` in a single
+    /// transaction, releasing their escrow. Returns the orders expired, which is fewer than `limit`
+    /// once there are none left.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn expire_orders(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<Order>>> + Send;
+
+    /// Gets information about a stock
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn stock_info(&self, ticker: &Ticker) -> impl Future<Output = Result<StockInfo>> + Send;
+
+    /// Lists a new stock owned by `owner`, who is credited every one of its `shares`. The listing
+    /// is recorded in the audit log under `actor` in the same transaction.
+    ///
+    /// # Errors
+    /// * [`StockExists`](Error::StockExists) - A stock with this ticker already exists
+    /// * [`AccountNotFound`](Error::AccountNotFound) - The owner does not exist
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn create_stock(
+        &self,
+        ticker: &Ticker,
+        shares: Shares,
+        owner: &Uuid,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<StockInfo>> + Send;
+
+    /// Sets the price of a stock that has never traded, by recording a trade of zero shares between
+    /// its owner and themselves. Returns whether the price was set, which it isn't once the stock
+    /// has a price.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn set_listing_price(
+        &self,
+        ticker: &Ticker,
+        price: Price,
+    ) -> impl Future<Output = Result<bool>> + Send;
+
+    /// Records prices stocks traded at on another market as trades of zero shares between each
+    /// stock's owner and themselves, in batches, then recloses every finished day they fall on.
+    /// Prices already imported for a stock at the same time are skipped, and the number recorded
+    /// is returned. Nothing but prices is changed.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository, such as
+    ///   a stock that doesn't exist or has no owner
+    fn import_prices(&self, prices: &[ImportedPrice]) -> impl Future<Output = Result<u64>> + Send;
+
+    /// Credits `amount` to a user's balance from outside the exchange, returning their new
+    /// balance. The grant is written to the ledger and recorded in the audit log under `actor` in
+    /// the same transaction.
+    ///
+    /// # Errors
+    /// * [`AccountNotFound`](Error::AccountNotFound) - The user does not exist
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn grant(
+        &self,
+        id: &Uuid,
+        amount: Decimal,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<Decimal>> + Send;
+
+    /// Credits a deposit sent to `wallet` to the account it was for, returning their new
+    /// balance, or [`None`] if its transaction has already been credited. The deposit is written
+    /// to the ledger, `wallet`'s cursor moved up to its transaction and an audit entry recorded
+    /// under [`Actor::System`] in the same transaction.
+    ///
+    /// # Errors
+    /// * [`AccountNotFound`](Error::AccountNotFound) - The user does not exist or their account
+    ///   has been closed
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn credit_deposit(
+        &self,
+        wallet: &Address,
+        deposit: &Deposit,
+    ) -> impl Future<Output = Result<Option<Decimal>>> + Send;
+
+    /// Records a deposit sent to `wallet` that can't be credited in the audit log under
+    /// [`Actor::System`], so an admin can find it and send it back, moving `wallet`'s cursor up to
+    /// its transaction in the same transaction.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn record_uncredited_deposit(
+        &self,
+        wallet: &Address,
+        deposit: &Deposit,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Fetches the ID of the newest transaction of `wallet` already scanned for deposits, if it
+    /// has ever been scanned
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn kromer_cursor(&self, wallet: &Address) -> impl Future<Output = Result<Option<i64>>> + Send;
+
+    /// Moves `wallet`'s cursor up to `transaction`, once everything up to it has been scanned for
+    /// deposits. Never moves it back.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn advance_kromer_cursor(
+        &self,
+        wallet: &Address,
+        transaction: i64,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Adds `delta` to a user's balance by hand, taking Kromer away if negative. The adjustment is
+    /// written to the ledger, given an ID it can later be reversed by and recorded in the audit
+    /// log under `actor` in the same transaction. Only balances that would go below zero need
+    /// `allow_negative`.
+    ///
+    /// # Errors
+    /// * [`AccountNotFound`](Error::AccountNotFound) - The user does not exist
+    /// * [`InsufficientFunds`](Error::InsufficientFunds) - The balance would go below zero without
+    ///   `allow_negative`
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn adjust_balance(
+        &self,
+        id: &Uuid,
+        delta: Decimal,
+        reason: AdjustmentReason,
+        allow_negative: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<Adjustment>> + Send;
+
+    /// Undoes an adjustment by making an equal and opposite one linked to it, with the same
+    /// reason. Each adjustment can only be reversed once, and reversals can't be reversed
+    /// themselves.
+    ///
+    /// # Errors
+    /// * [`AdjustmentNotFound`](Error::AdjustmentNotFound) - There is no adjustment with the ID,
+    ///   or it is a reversal
+    /// * [`AlreadyReversed`](Error::AlreadyReversed) - The adjustment was already reversed
+    /// * [`InsufficientFunds`](Error::InsufficientFunds) - The balance would go below zero without
+    ///   `allow_negative`
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn reverse_adjustment(
+        &self,
+        id: i64,
+        allow_negative: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<Adjustment>> + Send;
+
+    /// Sets whether a stock can be traded, recording the change in the audit log under `actor` in
+    /// the same transaction. Resting orders are left on the book either way.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn set_stock_status(
+        &self,
+        ticker: &Ticker,
+        status: StockStatus,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<StockInfo>> + Send;
+
+    /// Hands a stock owned by `from` to `to`, recording the transfer in the audit log in the same
+    /// transaction. Shares are not moved.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`NotStockOwner`](Error::NotStockOwner) - `from` doesn't own the stock
+    /// * [`AccountNotFound`](Error::AccountNotFound) - `to` does not exist
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn transfer_ownership(
+        &self,
+        ticker: &Ticker,
+        from: &Uuid,
+        to: &Uuid,
+    ) -> impl Future<Output = Result<StockInfo>> + Send;
+
+    /// Replaces the name, description and icon of a stock owned by `owner`, recording the change in
+    /// the audit log in the same transaction.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`NotStockOwner`](Error::NotStockOwner) - `owner` doesn't own the stock
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn update_stock_metadata(
+        &self,
+        ticker: &Ticker,
+        metadata: &StockMetadata,
+        owner: &Uuid,
+    ) -> impl Future<Output = Result<StockInfo>> + Send;
+
+    /// Issues `quantity` new shares of a stock to its owner, recording the issuance in the audit log
+    /// in the same transaction. Shares issued over the last day, read back from the audit log,
+    /// may not exceed `daily_cap_pct` percent of the shares outstanding.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`NotStockOwner`](Error::NotStockOwner) - `owner` doesn't own the stock
+    /// * [`IssuanceCapExceeded`](Error::IssuanceCapExceeded) - The issuance would go over the cap
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn issue_shares(
+        &self,
+        ticker: &Ticker,
+        quantity: Shares,
+        owner: &Uuid,
+        daily_cap_pct: u16,
+    ) -> impl Future<Output = Result<StockInfo>> + Send;
+
+    /// Retires `quantity` shares of a stock from its owner's holdings, recording the buyback in
+    /// the audit log in the same transaction. Shares escrowed by open orders can't be bought back.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`NotStockOwner`](Error::NotStockOwner) - `owner` doesn't own the stock
+    /// * [`InsufficientShares`](Error::InsufficientShares) - The owner doesn't hold enough shares,
+    ///   or the buyback would leave no shares issued
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn buyback(
+        &self,
+        ticker: &Ticker,
+        quantity: Shares,
+        owner: &Uuid,
+    ) -> impl Future<Output = Result<StockInfo>> + Send;
+
+    /// Gets the number of shares issued for a stock and who holds them, counting escrowed shares
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn shareholders(&self, ticker: &Ticker) -> impl Future<Output = Result<Shareholders>> + Send;
+
+    /// Pays a dividend of `per_share` from `payer` to every other holder of a stock, following
+    /// [`Shareholders::plan_dividend`]. The payer is debited, holders are credited, and every
+    /// movement is written to the ledger tagged with the ticker, all in one transaction. A
+    /// [`DividendPaid`](crate::model::outbox::Notice::DividendPaid) notice is queued in the
+    /// outbox as part of it.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`NotStockOwner`](Error::NotStockOwner) - The payer doesn't own the stock
+    /// * [`NoShareholders`](Error::NoShareholders) - Nobody else would receive anything
+    /// * [`InsufficientFunds`](Error::InsufficientFunds) - The payer can't cover the dividend
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn pay_dividend(
+        &self,
+        ticker: &Ticker,
+        per_share: Decimal,
+        payer: &Uuid,
+    ) -> impl Future<Output = Result<Dividend>> + Send;
+
+    /// Lists a user's open orders, newest first, as well as the total number of open orders.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn open_orders(
+        &self,
+        user: &Uuid,
+        page: &Pager,
+    ) -> impl Future<Output = Result<Page<Order>>> + Send;
+
+    /// Gets up to `depth` price levels of each side of a stock's order book, aggregated by price,
+    /// alongside the price of the most recent trade.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn book(&self, ticker: &Ticker, depth: u32) -> impl Future<Output = Result<Book>> + Send;
+
+    /// Gets up to `depth` price levels of the orders an incoming `side` order by `user` would trade
+    /// with, aggregated by price and best first. As in matching, `user`'s own orders and those
+    /// expired as of `now` are left out.
+    ///
+    /// # Errors
+    /// * [`StockNotFound`](Error::StockNotFound) - The stock does not exist
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn levels_against(
+        &self,
+        ticker: &Ticker,
+        side: Side,
+        user: &Uuid,
+        depth: u32,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<BookLevel>>> + Send;
+
+    /// Gets the price of a stock's last trade at or after `since`, falling back to its last trade
+    /// before `close`. Returns [`None`] if it has traded in neither window.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn reference_price(
+        &self,
+        ticker: &Ticker,
+        since: DateTime<Utc>,
+        close: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Option<Price>>> + Send;
+
+    /// Gets the price of the most recent trade of each of `tickers`, or of every stock if
+    /// [`None`], ordered by ticker. Stocks that have never traded are left out.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn latest_prices(
+        &self,
+        tickers: Option<&[Ticker]>,
+    ) -> impl Future<Output = Result<Vec<LatestPrice>>> + Send;
+
+    /// Asks for `amount` of a user's Kromer to be sent to `address`, taking it from their balance
+    /// so it can't be spent while the request is pending. The request, the ledger entry and the
+    /// audit entry under `actor` are written in the same transaction.
+    ///
+    /// # Errors
+    /// * [`AccountNotFound`](Error::AccountNotFound) - The user does not exist
+    /// * [`InsufficientFunds`](Error::InsufficientFunds) - The user's balance can't cover it
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn request_withdrawal(
+        &self,
+        user: &Uuid,
+        amount: Decimal,
+        address: &Address,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<Withdrawal>> + Send;
+
+    /// Lists pending withdrawal requests, oldest first, as well as the total number pending.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn pending_withdrawals(
+        &self,
+        page: &Pager,
+    ) -> impl Future<Output = Result<Page<Withdrawal>>> + Send;
+
+    /// Approves or denies a pending withdrawal request, giving the amount back to the user if it
+    /// is denied. Recorded in the audit log under `actor`, and a
+    /// [`WithdrawalResolved`](crate::model::outbox::Notice::WithdrawalResolved) notice queued in
+    /// the outbox, in the same transaction.
+    ///
+    /// # Errors
+    /// * [`WithdrawalNotFound`](Error::WithdrawalNotFound) - There is no pending request with
+    ///   this ID
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn resolve_withdrawal(
+        &self,
+        id: i64,
+        approve: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<Withdrawal>> + Send;
+
+    /// Claims the oldest approved withdrawal not yet paid out, if there is one. A claimed
+    /// withdrawal is never claimed again unless [released](Self::release_payout), so it can't be
+    /// sent twice.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn claim_payout(&self) -> impl Future<Output = Result<Option<Withdrawal>>> + Send;
+
+    /// Records the Kromer transaction a claimed withdrawal was paid out in, along with an audit
+    /// entry under [`Actor::System`] in the same transaction.
+    ///
+    /// # Errors
+    /// * [`WithdrawalNotFound`](Error::WithdrawalNotFound) - There is no claimed payout with this
+    ///   ID waiting on its transaction
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn record_payout(&self, id: i64, transaction: i64) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gives up a claimed payout the node refused to send, so it is claimed again later
+    ///
+    /// # Errors
+    /// * [`WithdrawalNotFound`](Error::WithdrawalNotFound) - There is no claimed payout with this
+    ///   ID waiting on its transaction
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn release_payout(&self, id: i64) -> impl Future<Output = Result<()>> + Send;
+
+    /// Queues `payload` in the outbox, to be delivered as soon as it is due. Only for notices
+    /// with no other state to change, as mutating methods queue their own within the same
+    /// transaction.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn enqueue_outbox(
+        &self,
+        payload: &serde_json::Value,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Claims up to `limit` outbox entries due by `now`, oldest first, leasing them until
+    /// `until`. Entries that are neither sent nor failed by then are handed out again, so a
+    /// dispatcher that dies mid-delivery does not lose them.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn claim_outbox(
+        &self,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<OutboxEntry>>> + Send;
+
+    /// Marks an outbox entry as delivered, so it is never claimed again
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn outbox_sent(&self, id: i64) -> impl Future<Output = Result<()>> + Send;
+
+    /// Records a failed delivery of an outbox entry, retrying it at `retry_at`. Passing `None`
+    /// parks the entry, leaving it in the table for an admin but never claiming it again.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn outbox_failed(
+        &self,
+        id: i64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets the settings of a Discord server, or [`None`] if it never changed any
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn guild_settings(
+        &self,
+        guild: NonZeroU64,
+    ) -> impl Future<Output = Result<Option<GuildSettings>>> + Send;
+
+    /// Gets the settings of every Discord server that changed any
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn all_guild_settings(
+        &self,
+    ) -> impl Future<Output = Result<Vec<(NonZeroU64, GuildSettings)>>> + Send;
+
+    /// Replaces the settings of a Discord server
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn set_guild_settings(
+        &self,
+        guild: NonZeroU64,
+        settings: &GuildSettings,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Up to `limit` accounts with IDs after `after`, in order of ID, each totalled up for
+    /// reconciliation. Every page is read in a single statement, so its totals agree with each
+    /// other, but pages may be read at different points in time.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn account_totals(
+        &self,
+        after: Option<&Uuid>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<AccountTotals>>> + Send;
+
+    /// Up to `limit` stocks with tickers after `after`, in order of ticker, each totalled up for
+    /// reconciliation. Pages are read like [`account_totals`](Self::account_totals)'s.
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn stock_totals(
+        &self,
+        after: Option<&Ticker>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<StockTotals>>> + Send;
+
+    /// Records finished command invocations in bulk
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn record_command_uses(&self, uses: &[CommandUse]) -> impl Future<Output = Result<()>> + Send;
+
+    /// How every command used in the 7 days before `now` was used, the most used first
+    ///
+    /// # Errors
+    /// * [`Unspecified`](Error::Unspecified) - An issue with the underlying repository
+    fn command_stats(
+        &self,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<CommandStats>>> + Send;
 }
@@ -0,0 +1,1161 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A [`StockRepository`] decorator caching lookups that rarely change
+
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    num::NonZeroU64,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::FutureExt;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::model::{
+    AccountMerge, AccountSummary, Candle, CandleInterval, ClosedAccount, ExchangeCounts,
+    HoldingOrdering, HoldingPl, ImportedPrice, LatestPrice, LedgerKind, Movers, Page, Pager,
+    PoolUsage, PortfolioSnapshot, Price, PriceChange, Privacy, Registered, Shares, Statement,
+    StatementSnapshot, StockInfo, StockMatch, StockMetadata, StockOrdering, StockStatus,
+    UserFilter, UserInfo,
+    adjustment::{Adjustment, AdjustmentReason},
+    api_key::ApiKey,
+    audit::{Actor, AuditEntry, AuditFilter, NewAuditEntry},
+    deposit::Deposit,
+    dividend::{Dividend, Shareholders},
+    fee::FeeSchedule,
+    guild::GuildSettings,
+    idempotency::{IdempotencyKey, Idempotent},
+    link::{Identity, LinkCode},
+    order::{Book, BookLevel, Fill, NewOrder, Order, Side, TickerTrade, UserTrade},
+    outbox::OutboxEntry,
+    reconcile::{AccountTotals, StockTotals},
+    snapshot::{SnapshotRecord, SnapshotSection},
+    summary::DailySummary,
+    ticker::Ticker,
+    usage::{CommandStats, CommandUse},
+    withdrawal::{Address, Withdrawal},
+};
+use crate::repo::StockRepository;
+
+/// How long a cached lookup is trusted for by default
+const DEFAULT_TTL: Duration = Duration::from_mins(5);
+
+/// The longest the exchange's counts are trusted for, as they change with every registration and
+/// listing, which are not invalidated
+const COUNTS_TTL: Duration = Duration::from_mins(1);
+
+/// How many lookups of each kind are cached at most by default
+const DEFAULT_CAPACITY: usize = 10_000;
+
+/// A map whose entries expire a fixed time after being inserted, holding a bounded number of them
+#[derive(Debug)]
+struct TtlCache<K, V> {
+    ttl: Duration,
+    capacity: usize,
+    inner: Mutex<TtlInner<K, V>>,
+}
+
+#[derive(Debug)]
+struct TtlInner<K, V> {
+    entries: HashMap<K, (Instant, V)>,
+    /// Bumped on every invalidation, so lookups that raced one don't cache what they read
+    generation: u64,
+}
+
+impl<K: Copy + Eq + Hash, V: Clone> TtlCache<K, V> {
+    fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity,
+            inner: Mutex::new(TtlInner {
+                entries: HashMap::new(),
+                generation: 0,
+            }),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TtlInner<K, V>> {
+        // Entries are only ever swapped whole, so a panic mid-update can't leave one half written
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Gets a live entry, or the current generation to pass to [`insert`](Self::insert) after
+    /// looking the value up
+    fn get(&self, key: &K) -> Result<V, u64> {
+        let mut inner = self.lock();
+
+        match inner.entries.get(key) {
+            Some((at, value)) if at.elapsed() < self.ttl => Ok(value.clone()),
+            Some(_) => {
+                inner.entries.remove(key);
+                Err(inner.generation)
+            }
+            None => Err(inner.generation),
+        }
+    }
+
+    /// Caches `value`, unless something was invalidated since `generation` was read
+    fn insert(&self, key: K, value: V, generation: u64) {
+        let mut inner = self.lock();
+
+        if inner.generation != generation || self.capacity == 0 {
+            return;
+        }
+
+        if inner.entries.len() >= self.capacity && !inner.entries.contains_key(&key) {
+            let ttl = self.ttl;
+            inner.entries.retain(|_, (at, _)| at.elapsed() < ttl);
+
+            if inner.entries.len() >= self.capacity
+                && let Some(oldest) = inner
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, (at, _))| *at)
+                    .map(|(key, _)| *key)
+            {
+                inner.entries.remove(&oldest);
+            }
+        }
+
+        inner.entries.insert(key, (Instant::now(), value));
+    }
+
+    fn invalidate(&self, key: &K) {
+        let mut inner = self.lock();
+        inner.entries.remove(key);
+        inner.generation += 1;
+    }
+}
+
+/// Wraps a [`StockRepository`], caching account lookups by Discord snowflake or Minecraft UUID,
+/// whether stocks exist, each Discord server's settings, and for at most a minute the exchange's
+/// counts. Writes through this repository that change the other lookups invalidate them, so
+/// cached values only go stale through writes made elsewhere, and then only for the cache's time
+/// to live. Clones share the same cache.
+#[derive(Debug, Clone)]
+pub struct CachedRepo<R> {
+    inner: R,
+    discord: Arc<TtlCache<NonZeroU64, Option<Uuid>>>,
+    mc: Arc<TtlCache<Uuid, Option<Uuid>>>,
+    stocks: Arc<TtlCache<Ticker, bool>>,
+    guilds: Arc<TtlCache<NonZeroU64, Option<GuildSettings>>>,
+    counts: Arc<TtlCache<(), ExchangeCounts>>,
+}
+
+impl<R: StockRepository> CachedRepo<R> {
+    /// Wraps `inner`, trusting each cached lookup for 5 minutes and caching up to 10,000 of each
+    /// kind
+    #[must_use]
+    pub fn new(inner: R) -> Self {
+        Self::with_limits(inner, DEFAULT_TTL, DEFAULT_CAPACITY)
+    }
+
+    /// Wraps `inner`, trusting each cached lookup for `ttl` and caching up to `capacity` of each
+    /// kind. The oldest lookups are evicted first once full.
+    #[must_use]
+    pub fn with_limits(inner: R, ttl: Duration, capacity: usize) -> Self {
+        Self {
+            inner,
+            discord: Arc::new(TtlCache::new(ttl, capacity)),
+            mc: Arc::new(TtlCache::new(ttl, capacity)),
+            stocks: Arc::new(TtlCache::new(ttl, capacity)),
+            guilds: Arc::new(TtlCache::new(ttl, capacity)),
+            counts: Arc::new(TtlCache::new(ttl.min(COUNTS_TTL), capacity)),
+        }
+    }
+}
+
+/// Returns the cached value for `key`, or caches what `fetch` finds otherwise
+async fn cached<K: Copy + Eq + Hash, V: Clone>(
+    cache: &TtlCache<K, V>,
+    key: K,
+    fetch: impl Future<Output = super::Result<V>>,
+) -> super::Result<V> {
+    match cache.get(&key) {
+        Ok(value) => Ok(value),
+        Err(generation) => {
+            let value = fetch.await?;
+            cache.insert(key, value.clone(), generation);
+            Ok(value)
+        }
+    }
+}
+
+impl<R: StockRepository> StockRepository for CachedRepo<R> {
+    fn stock_exists(&self, stock: &Ticker) -> impl Future<Output = super::Result<bool>> + Send {
+        cached(&self.stocks, *stock, self.inner.stock_exists(stock))
+    }
+
+    fn discord_to_id(
+        &self,
+        id: NonZeroU64,
+    ) -> impl Future<Output = super::Result<Option<Uuid>>> + Send {
+        cached(&self.discord, id, self.inner.discord_to_id(id))
+    }
+
+    fn mc_to_id(&self, id: &Uuid) -> impl Future<Output = super::Result<Option<Uuid>>> + Send {
+        cached(&self.mc, *id, self.inner.mc_to_id(id))
+    }
+
+    fn mc_username_to_uuid(
+        &self,
+        name: &str,
+        since: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Option<Uuid>>> + Send {
+        self.inner.mc_username_to_uuid(name, since)
+    }
+
+    fn cache_mc_username(
+        &self,
+        name: &str,
+        id: &Uuid,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.cache_mc_username(name, id)
+    }
+
+    fn identities(
+        &self,
+        ids: &[Uuid],
+    ) -> impl Future<Output = super::Result<Vec<(Uuid, Option<NonZeroU64>, Option<Uuid>)>>> + Send
+    {
+        self.inner.identities(ids)
+    }
+
+    fn register_user(
+        &self,
+        disc_id: Option<NonZeroU64>,
+        mc_id: Option<&Uuid>,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Registered>> + Send {
+        // Invalidated even on failure, being told the IDs are already linked means they are stale
+        self.inner
+            .register_user(disc_id, mc_id, actor)
+            .inspect(move |_| {
+                if let Some(disc_id) = disc_id {
+                    self.discord.invalidate(&disc_id);
+                }
+
+                if let Some(mc_id) = mc_id {
+                    self.mc.invalidate(mc_id);
+                }
+            })
+    }
+
+    fn create_link_code(
+        &self,
+        id: &Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<LinkCode>> + Send {
+        self.inner.create_link_code(id, expires_at)
+    }
+
+    fn consume_link_code(
+        &self,
+        code: &LinkCode,
+        identity: Identity,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Uuid>> + Send {
+        self.inner
+            .consume_link_code(code, identity, now)
+            .inspect(move |_| match identity {
+                Identity::Discord(disc_id) => self.discord.invalidate(&disc_id),
+                Identity::Minecraft(mc_id) => self.mc.invalidate(&mc_id),
+                Identity::Internal(_) => {}
+            })
+    }
+
+    fn purge_link_codes(
+        &self,
+        before: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<u64>> + Send {
+        self.inner.purge_link_codes(before)
+    }
+
+    fn create_api_key(&self, id: &Uuid) -> impl Future<Output = super::Result<ApiKey>> + Send {
+        self.inner.create_api_key(id)
+    }
+
+    fn api_key_owner(
+        &self,
+        key: &ApiKey,
+    ) -> impl Future<Output = super::Result<Option<Uuid>>> + Send {
+        self.inner.api_key_owner(key)
+    }
+
+    fn close_account(
+        &self,
+        id: &Uuid,
+        payout: Option<&Address>,
+        force: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<ClosedAccount>> + Send {
+        self.inner
+            .close_account(id, payout, force, actor)
+            .inspect(move |res| {
+                if let Ok(closed) = res {
+                    if let Some(disc_id) = closed.disc_id {
+                        self.discord.invalidate(&disc_id);
+                    }
+
+                    if let Some(mc_id) = closed.mc_id {
+                        self.mc.invalidate(&mc_id);
+                    }
+                }
+            })
+    }
+
+    fn merge_accounts(
+        &self,
+        survivor: &Uuid,
+        casualty: &Uuid,
+        dry_run: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<AccountMerge>> + Send {
+        self.inner
+            .merge_accounts(survivor, casualty, dry_run, actor)
+            .inspect(move |res| {
+                if let Ok(merged) = res
+                    && !dry_run
+                {
+                    if let Some(disc_id) = merged.disc_id {
+                        self.discord.invalidate(&disc_id);
+                    }
+
+                    if let Some(mc_id) = merged.mc_id {
+                        self.mc.invalidate(&mc_id);
+                    }
+                }
+            })
+    }
+
+    fn create_stock(
+        &self,
+        ticker: &Ticker,
+        shares: Shares,
+        owner: &Uuid,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner
+            .create_stock(ticker, shares, owner, actor)
+            .inspect(move |_| self.stocks.invalidate(ticker))
+    }
+
+    fn user_exists(&self, id: &Uuid) -> impl Future<Output = super::Result<bool>> + Send {
+        self.inner.user_exists(id)
+    }
+
+    fn user_info(&self, id: &Uuid) -> impl Future<Output = super::Result<Option<UserInfo>>> + Send {
+        self.inner.user_info(id)
+    }
+
+    fn set_privacy(
+        &self,
+        id: &Uuid,
+        privacy: Privacy,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.set_privacy(id, privacy)
+    }
+
+    fn set_public_portfolio(
+        &self,
+        id: &Uuid,
+        public: bool,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.set_public_portfolio(id, public)
+    }
+
+    fn set_receipts(
+        &self,
+        id: &Uuid,
+        enabled: bool,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.set_receipts(id, enabled)
+    }
+
+    fn ensure_system_account(&self, id: &Uuid) -> impl Future<Output = super::Result<bool>> + Send {
+        self.inner.ensure_system_account(id)
+    }
+
+    fn ping(&self) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.ping()
+    }
+
+    fn pool_usage(&self) -> Option<PoolUsage> {
+        self.inner.pool_usage()
+    }
+
+    fn schema_version(&self) -> impl Future<Output = super::Result<Option<i64>>> + Send {
+        self.inner.schema_version()
+    }
+
+    fn snapshot_chunk(
+        &self,
+        section: SnapshotSection,
+        after: Option<&SnapshotRecord>,
+        limit: i64,
+    ) -> impl Future<Output = super::Result<Vec<SnapshotRecord>>> + Send {
+        self.inner.snapshot_chunk(section, after, limit)
+    }
+
+    fn restore_snapshot(
+        &self,
+        records: &[SnapshotRecord],
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.restore_snapshot(records)
+    }
+
+    fn exchange_counts(&self) -> impl Future<Output = super::Result<ExchangeCounts>> + Send {
+        cached(&self.counts, (), self.inner.exchange_counts())
+    }
+
+    fn get_holdings(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        order: HoldingOrdering,
+    ) -> impl Future<Output = super::Result<Option<Page<(Ticker, Shares, Option<Price>)>>>> + Send
+    {
+        self.inner.get_holdings(id, page, order)
+    }
+
+    fn get_holdings_pl(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        order: HoldingOrdering,
+    ) -> impl Future<Output = super::Result<Option<Page<HoldingPl>>>> + Send {
+        self.inner.get_holdings_pl(id, page, order)
+    }
+
+    fn holdings_value(&self, id: &Uuid) -> impl Future<Output = super::Result<Decimal>> + Send {
+        self.inner.holdings_value(id)
+    }
+
+    fn portfolio_values(
+        &self,
+        ids: &[Uuid],
+    ) -> impl Future<Output = super::Result<Vec<(Uuid, Decimal)>>> + Send {
+        self.inner.portfolio_values(ids)
+    }
+
+    fn account_summary(
+        &self,
+        id: &Uuid,
+        recent: u32,
+    ) -> impl Future<Output = super::Result<AccountSummary>> + Send {
+        self.inner.account_summary(id, recent)
+    }
+
+    fn statement(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        filter: Option<LedgerKind>,
+        snapshot: Option<StatementSnapshot>,
+    ) -> impl Future<Output = super::Result<Statement>> + Send {
+        self.inner.statement(id, page, filter, snapshot)
+    }
+
+    fn trade_history(
+        &self,
+        id: &Uuid,
+        after: Option<i32>,
+        limit: i64,
+    ) -> impl Future<Output = super::Result<Vec<UserTrade>>> + Send {
+        self.inner.trade_history(id, after, limit)
+    }
+
+    fn ticker_trades(
+        &self,
+        ticker: &Ticker,
+        page: &Pager,
+    ) -> impl Future<Output = super::Result<Page<TickerTrade>>> + Send {
+        self.inner.ticker_trades(ticker, page)
+    }
+
+    fn top_movers(
+        &self,
+        date: NaiveDate,
+        until: DateTime<Utc>,
+        count: u8,
+    ) -> impl Future<Output = super::Result<Movers>> + Send {
+        self.inner.top_movers(date, until, count)
+    }
+
+    fn daily_summary(
+        &self,
+        date: NaiveDate,
+    ) -> impl Future<Output = super::Result<DailySummary>> + Send {
+        self.inner.daily_summary(date)
+    }
+
+    fn summary_sent(&self, date: NaiveDate) -> impl Future<Output = super::Result<bool>> + Send {
+        self.inner.summary_sent(date)
+    }
+
+    fn record_summary_sent(
+        &self,
+        date: NaiveDate,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.record_summary_sent(date)
+    }
+
+    fn record_daily_closes(
+        &self,
+        date: NaiveDate,
+    ) -> impl Future<Output = super::Result<u64>> + Send {
+        self.inner.record_daily_closes(date)
+    }
+
+    fn record_portfolio_snapshots(
+        &self,
+        date: NaiveDate,
+        after: Option<&Uuid>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<(Option<Uuid>, u64)>> + Send {
+        self.inner.record_portfolio_snapshots(date, after, limit)
+    }
+
+    fn portfolio_history(
+        &self,
+        id: &Uuid,
+        since: NaiveDate,
+    ) -> impl Future<Output = super::Result<Vec<PortfolioSnapshot>>> + Send {
+        self.inner.portfolio_history(id, since)
+    }
+
+    fn price_change(
+        &self,
+        ticker: &Ticker,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Option<PriceChange>>> + Send {
+        self.inner.price_change(ticker, now)
+    }
+
+    fn candles(
+        &self,
+        ticker: &Ticker,
+        interval: CandleInterval,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Vec<Candle>>> + Send {
+        self.inner.candles(ticker, interval, from, to)
+    }
+
+    fn list_stocks(
+        &self,
+        page: &Pager,
+        order: StockOrdering,
+        prefix: &str,
+    ) -> impl Future<
+        Output = super::Result<
+            Option<
+                Page<(
+                    Ticker,
+                    Shares,
+                    Option<Price>,
+                    Option<DateTime<Utc>>,
+                    Option<String>,
+                )>,
+            >,
+        >,
+    > + Send {
+        self.inner.list_stocks(page, order, prefix)
+    }
+
+    fn search_tickers(
+        &self,
+        prefix: &str,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<(Ticker, Option<String>)>>> + Send {
+        self.inner.search_tickers(prefix, limit)
+    }
+
+    fn search_stocks(
+        &self,
+        query: &str,
+        page: &Pager,
+    ) -> impl Future<Output = super::Result<Page<StockMatch>>> + Send {
+        self.inner.search_stocks(query, page)
+    }
+
+    fn list_users(
+        &self,
+        page: &Pager,
+        filter: UserFilter,
+    ) -> impl Future<Output = super::Result<Page<UserInfo>>> + Send {
+        self.inner.list_users(page, filter)
+    }
+
+    fn record_audit(
+        &self,
+        entry: &NewAuditEntry,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.record_audit(entry)
+    }
+
+    fn audit_log(
+        &self,
+        page: &Pager,
+        filter: &AuditFilter,
+    ) -> impl Future<Output = super::Result<Page<AuditEntry>>> + Send {
+        self.inner.audit_log(page, filter)
+    }
+
+    fn place_order(
+        &self,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<(Order, Vec<Fill>)>> + Send {
+        self.inner.place_order(order, fees, now)
+    }
+
+    fn queue_order(
+        &self,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Order>> + Send {
+        self.inner.queue_order(order, fees, now)
+    }
+
+    fn release_queued_orders(
+        &self,
+        limit: u32,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Vec<(Order, Vec<Fill>)>>> + Send {
+        self.inner.release_queued_orders(limit, fees, now)
+    }
+
+    fn place_order_once(
+        &self,
+        key: &IdempotencyKey,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        since: DateTime<Utc>,
+        queue: bool,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Idempotent<(Order, Vec<Fill>)>>> + Send {
+        self.inner
+            .place_order_once(key, order, fees, since, queue, now)
+    }
+
+    fn purge_idempotency_keys(
+        &self,
+        before: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<u64>> + Send {
+        self.inner.purge_idempotency_keys(before)
+    }
+
+    fn cancel_order(
+        &self,
+        id: i32,
+        user: &Uuid,
+    ) -> impl Future<Output = super::Result<Order>> + Send {
+        self.inner.cancel_order(id, user)
+    }
+
+    fn expire_orders(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<Order>>> + Send {
+        self.inner.expire_orders(now, limit)
+    }
+
+    fn stock_info(&self, ticker: &Ticker) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner.stock_info(ticker)
+    }
+
+    fn set_listing_price(
+        &self,
+        ticker: &Ticker,
+        price: Price,
+    ) -> impl Future<Output = super::Result<bool>> + Send {
+        self.inner.set_listing_price(ticker, price)
+    }
+
+    fn import_prices(
+        &self,
+        prices: &[ImportedPrice],
+    ) -> impl Future<Output = super::Result<u64>> + Send {
+        self.inner.import_prices(prices)
+    }
+
+    fn grant(
+        &self,
+        id: &Uuid,
+        amount: Decimal,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Decimal>> + Send {
+        self.inner.grant(id, amount, actor)
+    }
+
+    fn credit_deposit(
+        &self,
+        wallet: &Address,
+        deposit: &Deposit,
+    ) -> impl Future<Output = super::Result<Option<Decimal>>> + Send {
+        self.inner.credit_deposit(wallet, deposit)
+    }
+
+    fn record_uncredited_deposit(
+        &self,
+        wallet: &Address,
+        deposit: &Deposit,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.record_uncredited_deposit(wallet, deposit)
+    }
+
+    fn kromer_cursor(
+        &self,
+        wallet: &Address,
+    ) -> impl Future<Output = super::Result<Option<i64>>> + Send {
+        self.inner.kromer_cursor(wallet)
+    }
+
+    fn advance_kromer_cursor(
+        &self,
+        wallet: &Address,
+        transaction: i64,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.advance_kromer_cursor(wallet, transaction)
+    }
+
+    fn adjust_balance(
+        &self,
+        id: &Uuid,
+        delta: Decimal,
+        reason: AdjustmentReason,
+        allow_negative: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Adjustment>> + Send {
+        self.inner
+            .adjust_balance(id, delta, reason, allow_negative, actor)
+    }
+
+    fn reverse_adjustment(
+        &self,
+        id: i64,
+        allow_negative: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Adjustment>> + Send {
+        self.inner.reverse_adjustment(id, allow_negative, actor)
+    }
+
+    fn set_stock_status(
+        &self,
+        ticker: &Ticker,
+        status: StockStatus,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner.set_stock_status(ticker, status, actor)
+    }
+
+    fn transfer_ownership(
+        &self,
+        ticker: &Ticker,
+        from: &Uuid,
+        to: &Uuid,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner.transfer_ownership(ticker, from, to)
+    }
+
+    fn update_stock_metadata(
+        &self,
+        ticker: &Ticker,
+        metadata: &StockMetadata,
+        owner: &Uuid,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner.update_stock_metadata(ticker, metadata, owner)
+    }
+
+    fn issue_shares(
+        &self,
+        ticker: &Ticker,
+        quantity: Shares,
+        owner: &Uuid,
+        daily_cap_pct: u16,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner
+            .issue_shares(ticker, quantity, owner, daily_cap_pct)
+    }
+
+    fn buyback(
+        &self,
+        ticker: &Ticker,
+        quantity: Shares,
+        owner: &Uuid,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner.buyback(ticker, quantity, owner)
+    }
+
+    fn shareholders(
+        &self,
+        ticker: &Ticker,
+    ) -> impl Future<Output = super::Result<Shareholders>> + Send {
+        self.inner.shareholders(ticker)
+    }
+
+    fn pay_dividend(
+        &self,
+        ticker: &Ticker,
+        per_share: Decimal,
+        payer: &Uuid,
+    ) -> impl Future<Output = super::Result<Dividend>> + Send {
+        self.inner.pay_dividend(ticker, per_share, payer)
+    }
+
+    fn open_orders(
+        &self,
+        user: &Uuid,
+        page: &Pager,
+    ) -> impl Future<Output = super::Result<Page<Order>>> + Send {
+        self.inner.open_orders(user, page)
+    }
+
+    fn book(
+        &self,
+        ticker: &Ticker,
+        depth: u32,
+    ) -> impl Future<Output = super::Result<Book>> + Send {
+        self.inner.book(ticker, depth)
+    }
+
+    fn levels_against(
+        &self,
+        ticker: &Ticker,
+        side: Side,
+        user: &Uuid,
+        depth: u32,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Vec<BookLevel>>> + Send {
+        self.inner.levels_against(ticker, side, user, depth, now)
+    }
+
+    fn reference_price(
+        &self,
+        ticker: &Ticker,
+        since: DateTime<Utc>,
+        close: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Option<Price>>> + Send {
+        self.inner.reference_price(ticker, since, close)
+    }
+
+    fn latest_prices(
+        &self,
+        tickers: Option<&[Ticker]>,
+    ) -> impl Future<Output = super::Result<Vec<LatestPrice>>> + Send {
+        self.inner.latest_prices(tickers)
+    }
+
+    fn request_withdrawal(
+        &self,
+        user: &Uuid,
+        amount: Decimal,
+        address: &Address,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Withdrawal>> + Send {
+        self.inner.request_withdrawal(user, amount, address, actor)
+    }
+
+    fn pending_withdrawals(
+        &self,
+        page: &Pager,
+    ) -> impl Future<Output = super::Result<Page<Withdrawal>>> + Send {
+        self.inner.pending_withdrawals(page)
+    }
+
+    fn resolve_withdrawal(
+        &self,
+        id: i64,
+        approve: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Withdrawal>> + Send {
+        self.inner.resolve_withdrawal(id, approve, actor)
+    }
+
+    fn claim_payout(&self) -> impl Future<Output = super::Result<Option<Withdrawal>>> + Send {
+        self.inner.claim_payout()
+    }
+
+    fn record_payout(
+        &self,
+        id: i64,
+        transaction: i64,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.record_payout(id, transaction)
+    }
+
+    fn release_payout(&self, id: i64) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.release_payout(id)
+    }
+
+    fn enqueue_outbox(
+        &self,
+        payload: &serde_json::Value,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.enqueue_outbox(payload)
+    }
+
+    fn claim_outbox(
+        &self,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<OutboxEntry>>> + Send {
+        self.inner.claim_outbox(now, until, limit)
+    }
+
+    fn outbox_sent(&self, id: i64) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.outbox_sent(id)
+    }
+
+    fn outbox_failed(
+        &self,
+        id: i64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.outbox_failed(id, error, retry_at)
+    }
+
+    fn guild_settings(
+        &self,
+        guild: NonZeroU64,
+    ) -> impl Future<Output = super::Result<Option<GuildSettings>>> + Send {
+        cached(&self.guilds, guild, self.inner.guild_settings(guild))
+    }
+
+    fn all_guild_settings(
+        &self,
+    ) -> impl Future<Output = super::Result<Vec<(NonZeroU64, GuildSettings)>>> + Send {
+        self.inner.all_guild_settings()
+    }
+
+    fn set_guild_settings(
+        &self,
+        guild: NonZeroU64,
+        settings: &GuildSettings,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner
+            .set_guild_settings(guild, settings)
+            .inspect(move |_| self.guilds.invalidate(&guild))
+    }
+
+    fn account_totals(
+        &self,
+        after: Option<&Uuid>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<AccountTotals>>> + Send {
+        self.inner.account_totals(after, limit)
+    }
+
+    fn stock_totals(
+        &self,
+        after: Option<&Ticker>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<StockTotals>>> + Send {
+        self.inner.stock_totals(after, limit)
+    }
+
+    fn record_command_uses(
+        &self,
+        uses: &[CommandUse],
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.record_command_uses(uses)
+    }
+
+    fn command_stats(
+        &self,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Vec<CommandStats>>> + Send {
+        self.inner.command_stats(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{ChaosRepo, Stub};
+
+    fn ticker() -> Ticker {
+        Ticker::try_from("ABC").expect("Valid ticker")
+    }
+
+    #[tokio::test]
+    async fn repeated_lookups_hit_the_cache() {
+        let chaos = ChaosRepo::new(Stub::default());
+        let repo = CachedRepo::new(chaos.clone());
+
+        for _ in 0..3 {
+            assert_eq!(
+                repo.discord_to_id(NonZeroU64::MIN).await.expect("Lookup"),
+                None
+            );
+            assert!(!repo.stock_exists(&ticker()).await.expect("Lookup"));
+        }
+
+        assert_eq!(chaos.total_calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn counts_are_cached() {
+        let chaos = ChaosRepo::new(Stub::default());
+        let repo = CachedRepo::new(chaos.clone());
+
+        for _ in 0..3 {
+            assert_eq!(repo.exchange_counts().await, Ok(ExchangeCounts::default()));
+        }
+
+        assert_eq!(chaos.total_calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn registering_evicts_cached_miss() {
+        let chaos = ChaosRepo::new(Stub::default());
+        let repo = CachedRepo::new(chaos.clone());
+
+        assert_eq!(
+            repo.discord_to_id(NonZeroU64::MIN).await.expect("Lookup"),
+            None
+        );
+
+        let id = repo
+            .register_user(Some(NonZeroU64::MIN), None, &Actor::System)
+            .await
+            .expect("Registered")
+            .id();
+
+        assert_eq!(
+            repo.discord_to_id(NonZeroU64::MIN).await.expect("Lookup"),
+            Some(id)
+        );
+        assert_eq!(chaos.total_calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn creating_stock_evicts_cached_miss() {
+        let chaos = ChaosRepo::new(Stub::default());
+        let repo = CachedRepo::new(chaos.clone());
+
+        assert!(!repo.stock_exists(&ticker()).await.expect("Lookup"));
+
+        let shares = Shares::new(100).expect("Valid shares");
+        repo.create_stock(&ticker(), shares, &Uuid::nil(), &Actor::System)
+            .await
+            .expect("Created");
+
+        assert!(repo.stock_exists(&ticker()).await.expect("Lookup"));
+    }
+
+    #[tokio::test]
+    async fn editing_guild_settings_evicts_them() {
+        let chaos = ChaosRepo::new(Stub::default());
+        let repo = CachedRepo::new(chaos.clone());
+
+        for _ in 0..2 {
+            assert_eq!(
+                repo.guild_settings(NonZeroU64::MIN).await.expect("Lookup"),
+                None
+            );
+        }
+        assert_eq!(chaos.total_calls(), 1);
+
+        let settings = GuildSettings {
+            trading_enabled: false,
+            ..GuildSettings::default()
+        };
+        repo.set_guild_settings(NonZeroU64::MIN, &settings)
+            .await
+            .expect("Saved");
+
+        assert_eq!(
+            repo.guild_settings(NonZeroU64::MIN).await.expect("Lookup"),
+            Some(settings)
+        );
+    }
+
+    #[tokio::test]
+    async fn clones_share_invalidations() {
+        let chaos = ChaosRepo::new(Stub::default());
+        let repo = CachedRepo::new(chaos.clone());
+        let other = repo.clone();
+
+        assert_eq!(
+            repo.discord_to_id(NonZeroU64::MIN).await.expect("Lookup"),
+            None
+        );
+
+        other
+            .register_user(Some(NonZeroU64::MIN), None, &Actor::System)
+            .await
+            .expect("Registered");
+
+        assert!(
+            repo.discord_to_id(NonZeroU64::MIN)
+                .await
+                .expect("Lookup")
+                .is_some()
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_looked_up_again() {
+        let chaos = ChaosRepo::new(Stub::default());
+        let repo = CachedRepo::with_limits(chaos.clone(), Duration::ZERO, 16);
+
+        repo.mc_to_id(&Uuid::nil()).await.expect("Lookup");
+        repo.mc_to_id(&Uuid::nil()).await.expect("Lookup");
+
+        assert_eq!(chaos.total_calls(), 2);
+    }
+
+    #[tokio::test]
+    async fn oldest_entry_is_evicted_when_full() {
+        let chaos = ChaosRepo::new(Stub::default());
+        let repo = CachedRepo::with_limits(chaos.clone(), DEFAULT_TTL, 2);
+
+        for id in [1, 2, 3, 3, 2] {
+            let id = NonZeroU64::new(id).expect("Non-zero");
+            repo.discord_to_id(id).await.expect("Lookup");
+        }
+        assert_eq!(chaos.total_calls(), 3);
+
+        repo.discord_to_id(NonZeroU64::MIN).await.expect("Lookup");
+        assert_eq!(chaos.total_calls(), 4);
+    }
+
+    #[test]
+    fn lookup_racing_invalidation_is_not_cached() {
+        let cache = TtlCache::<u64, Option<Uuid>>::new(DEFAULT_TTL, 16);
+
+        let generation = cache.get(&1).expect_err("Empty cache");
+        cache.invalidate(&1);
+        cache.insert(1, None, generation);
+
+        assert!(cache.get(&1).is_err());
+    }
+}
@@ -16,251 +16,5727 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use std::num::NonZeroU64;
+use std::{
+    collections::BTreeMap,
+    num::NonZeroU64,
+    time::{Duration, Instant},
+};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc};
 use futures_util::{FutureExt, TryFutureExt};
 use rust_decimal::Decimal;
+use snafu::{OptionExt, ensure};
+use tracing::{Instrument, Span};
 use uuid::Uuid;
 
+use crate::matching::{IncomingOrder, RestingOrder, match_order};
+use crate::model::adjustment::{Adjustment, AdjustmentReason};
+use crate::model::api_key::ApiKey;
+use crate::model::audit::{Action, Actor, AuditEntry, AuditFilter, NewAuditEntry};
+use crate::model::deposit::Deposit;
+use crate::model::dividend::{Dividend, Shareholders};
+use crate::model::fee::FeeSchedule;
+use crate::model::guild::GuildSettings;
+use crate::model::idempotency::{IdempotencyKey, Idempotent, order_hash};
+use crate::model::link::{Identity, LinkCode};
+use crate::model::order::{
+    Book, BookLevel, Fill, NewOrder, Order, OrderStatus, Receipt, ReceiptFill, Side, TickerTrade,
+    UserTrade,
+};
+use crate::model::outbox::{Notice, OutboxEntry};
+use crate::model::reconcile::{AccountTotals, StockTotals};
+use crate::model::snapshot::{
+    SnapshotHolding, SnapshotOrder, SnapshotPrice, SnapshotRecord, SnapshotSection, SnapshotStock,
+    SnapshotUser,
+};
+use crate::model::summary::{DailySummary, Trade};
 use crate::model::ticker::Ticker;
-use crate::model::{Pager, UserInfo};
-use crate::repo::Error;
+use crate::model::usage::{CommandStats, CommandUse, UsageTotals};
+use crate::model::withdrawal::{Address, Withdrawal};
+use crate::model::{
+    AccountMerge, AccountSummary, Candle, CandleInterval, ClosedAccount, ExchangeCounts,
+    HoldingOrdering, HoldingPl, ImportedPrice, LatestPrice, LedgerKind, MergeConflict, Mover,
+    Movers, Page, Pager, PoolUsage, PortfolioSnapshot, Price, PriceChange, Privacy, Registered,
+    Shares, Statement, StatementEntry, StatementSnapshot, StockInfo, StockMatch, StockMetadata,
+    StockOrdering, StockStatus, Transaction, UserFilter, UserInfo, realized_pl, weighted_avg_cost,
+};
+use crate::repo::{
+    AccountNotEmptySnafu, AccountNotFoundSnafu, AdjustmentNotFoundSnafu, AlreadyLinkedSnafu, Error,
+    IdempotencyKeyReusedSnafu, InsufficientSharesSnafu, IssuanceCapExceededSnafu,
+    MergeConflictSnafu, NoShareholdersSnafu, NotStockOwnerSnafu, StockHaltedSnafu,
+    StockNotFoundSnafu,
+};
 
 /// A port for a `Postgres` back end
 #[derive(Debug, Clone)]
 pub struct PgPort {
     pool: sqlx::PgPool,
+    slow_query: Duration,
 }
 
 impl PgPort {
     /// Creates a new instance of [`PgPort`]
     #[must_use]
     pub const fn new(pool: sqlx::PgPool) -> Self {
-        Self { pool }
+        Self {
+            pool,
+            slow_query: DEFAULT_SLOW_QUERY,
+        }
+    }
+
+    /// Logs a warning for every repository call taking longer than `threshold`. Defaults to 250
+    /// milliseconds.
+    #[must_use]
+    pub const fn with_slow_query(mut self, threshold: Duration) -> Self {
+        self.slow_query = threshold;
+        self
+    }
+}
+
+/// How long a repository call may take before it is logged as slow, unless configured otherwise
+const DEFAULT_SLOW_QUERY: Duration = Duration::from_millis(250);
+
+/// Span wrapping a single repository call, named after the query it runs
+fn query_span(name: &'static str) -> Span {
+    tracing::debug_span!("query", name)
+}
+
+/// Instruments repository calls
+trait Query: Future + Sized {
+    /// Runs the call in a span named after its query, warning if it takes longer than `slow`. The
+    /// warning is emitted within the span, so also carries whatever the call was made for, such as
+    /// a command invocation.
+    fn query(self, name: &'static str, slow: Duration) -> impl Future<Output = Self::Output> {
+        async move {
+            let start = Instant::now();
+            let res = self.await;
+            let elapsed = start.elapsed();
+
+            if elapsed > slow {
+                tracing::warn!(?elapsed, "Slow query");
+            }
+
+            res
+        }
+        .instrument(query_span(name))
+    }
+}
+
+impl<F: Future> Query for F {}
+
+/// Logs an unexpected database error before hiding it behind [`Error::Unspecified`], or
+/// [`Error::Unavailable`] if it looks like the database was only briefly unreachable
+#[allow(clippy::needless_pass_by_value)] // Taken by value so it can be passed to `map_err`
+fn unspecified(err: sqlx::Error) -> Error {
+    if is_conflict(&err) {
+        tracing::warn!(%err, "transaction conflicted with a concurrent one");
+        return Error::Conflict;
+    }
+
+    tracing::error!(%err, "unexpected database error");
+
+    if is_transient(&err) {
+        Error::Unavailable
+    } else {
+        Error::Unspecified
+    }
+}
+
+/// Whether `err` came from losing the connection to the database rather than from the query
+fn is_transient(err: &sqlx::Error) -> bool {
+    match err {
+        sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut | sqlx::Error::WorkerCrashed => true,
+        sqlx::Error::Database(err) => err.code().is_some_and(|code| {
+            // Connection exceptions, the server shutting down, and running out of connections
+            code.starts_with("08") || matches!(&*code, "57P01" | "57P02" | "57P03" | "53300")
+        }),
+        _ => false,
+    }
+}
+
+/// Whether `err` is a deadlock or serialization failure, after which Postgres has rolled back the
+/// transaction
+fn is_conflict(err: &sqlx::Error) -> bool {
+    matches!(err, sqlx::Error::Database(err)
+        if err.code().is_some_and(|code| matches!(&*code, "40001" | "40P01")))
+}
+
+/// How many times a trade is attempted before a conflict with concurrent ones is surfaced
+const CONFLICT_ATTEMPTS: u32 = 3;
+
+/// Runs `write` again while it fails with [`Error::Conflict`], up to [`CONFLICT_ATTEMPTS`] times in
+/// total. `write` must do all of its work in one transaction, so a failed attempt left nothing
+/// behind.
+async fn retry_conflicts<T, F>(mut write: impl FnMut() -> F) -> super::Result<T>
+where
+    F: Future<Output = super::Result<T>>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match write().await {
+            Err(Error::Conflict) if attempt < CONFLICT_ATTEMPTS => {
+                attempt += 1;
+                // Jittered so the transactions that collided don't just collide again
+                tokio::time::sleep(Duration::from_millis(fastrand::u64(5..=50))).await;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Appends an entry to the audit log using `conn`, so it can share a transaction with the action it
+/// describes
+async fn insert_audit(conn: &mut sqlx::PgConnection, entry: &NewAuditEntry) -> super::Result<()> {
+    sqlx::query!(
+        "INSERT INTO audit_log (actor, action, target, details) VALUES ($1, $2, $3, $4)",
+        entry.actor.to_string(),
+        entry.action.as_str(),
+        entry.target,
+        entry.details
+    )
+    .execute(conn)
+    .await
+    .map_err(unspecified)?;
+
+    Ok(())
+}
+
+/// Records each identity detached from the account `id` in the audit log using `conn`, one entry
+/// per identity, noting the `action` that detached it
+async fn audit_unlinks(
+    conn: &mut sqlx::PgConnection,
+    id: &Uuid,
+    disc_id: Option<NonZeroU64>,
+    mc_id: Option<Uuid>,
+    action: Action,
+    actor: &Actor,
+) -> super::Result<()> {
+    let identities = [
+        disc_id.map(|disc_id| serde_json::json!({ "disc_id": disc_id })),
+        mc_id.map(|mc_id| serde_json::json!({ "mc_id": mc_id })),
+    ];
+
+    for mut details in identities.into_iter().flatten() {
+        details["by"] = action.as_str().into();
+
+        let entry = NewAuditEntry {
+            actor: *actor,
+            action: Action::UnlinkIdentity,
+            target: Some(id.to_string()),
+            details,
+        };
+        insert_audit(&mut *conn, &entry).await?;
+    }
+
+    Ok(())
+}
+
+/// Queues `notice` in the outbox using `conn`, so it is only delivered if the change it describes
+/// commits
+async fn insert_outbox(conn: &mut sqlx::PgConnection, notice: &Notice) -> super::Result<()> {
+    let payload = serde_json::to_value(notice).map_err(|err| {
+        tracing::error!(%err, "could not serialize notice");
+        Error::Unspecified
+    })?;
+
+    insert_outbox_payload(conn, &payload).await
+}
+
+async fn insert_outbox_payload(
+    conn: &mut sqlx::PgConnection,
+    payload: &serde_json::Value,
+) -> super::Result<()> {
+    sqlx::query!("INSERT INTO outbox (payload) VALUES ($1)", payload)
+        .execute(conn)
+        .await
+        .map_err(unspecified)?;
+
+    Ok(())
+}
+
+/// Moves `wallet`'s deposit cursor up to `transaction`, leaving it be if it's already past it
+async fn upsert_kromer_cursor(
+    conn: &mut sqlx::PgConnection,
+    wallet: &Address,
+    transaction: i64,
+) -> super::Result<()> {
+    sqlx::query!(
+        "INSERT INTO kromer_cursor (address, transaction_id) VALUES ($1, $2)
+        ON CONFLICT (address) DO UPDATE
+        SET transaction_id = GREATEST(kromer_cursor.transaction_id, EXCLUDED.transaction_id)",
+        wallet.as_str(),
+        transaction
+    )
+    .execute(conn)
+    .await
+    .map_err(unspecified)?;
+
+    Ok(())
+}
+
+fn is_check_violation(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .is_some_and(sqlx::error::DatabaseError::is_check_violation)
+}
+
+fn is_foreign_key_violation(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .is_some_and(sqlx::error::DatabaseError::is_foreign_key_violation)
+}
+
+/// A stock as stored in the `stocks` table
+struct StockRow {
+    pub ticker: String,
+    pub owner_id: Option<Uuid>,
+    pub shares: i32,
+    pub created_at: DateTime<Utc>,
+    pub status: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub icon_url: Option<String>,
+}
+
+impl StockRow {
+    fn into_info(self) -> Option<StockInfo> {
+        Some(StockInfo {
+            ticker: Ticker::try_from(self.ticker.as_str()).ok()?,
+            owner: self.owner_id,
+            shares: Shares::try_from(self.shares).ok()?,
+            created_at: self.created_at,
+            status: self.status.parse().ok()?,
+            metadata: StockMetadata {
+                name: self.name,
+                description: self.description,
+                icon_url: self.icon_url,
+            },
+        })
+    }
+}
+
+/// A withdrawal request as stored in the `withdrawal_requests` table
+struct WithdrawalRow {
+    pub withdrawal_id: i64,
+    pub user_id: Uuid,
+    pub amount: Decimal,
+    pub address: String,
+    pub status: String,
+    pub requested_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+impl WithdrawalRow {
+    fn into_withdrawal(self) -> Option<Withdrawal> {
+        Some(Withdrawal {
+            id: self.withdrawal_id,
+            user: self.user_id,
+            amount: self.amount,
+            address: Address::try_from(self.address.as_str()).ok()?,
+            status: self.status.parse().ok()?,
+            requested_at: self.requested_at,
+            resolved_at: self.resolved_at,
+        })
+    }
+}
+
+/// A price level of an order book, aggregated from the `orders` table
+struct LevelRow {
+    pub price: Decimal,
+    pub shares: i64,
+    pub orders: i64,
+}
+
+impl From<LevelRow> for BookLevel {
+    fn from(v: LevelRow) -> Self {
+        Self {
+            price: v.price,
+            shares: v.shares.try_into().expect("Enforced by DB"),
+            orders: v.orders.try_into().unwrap_or(u32::MAX),
+        }
+    }
+}
+
+/// An order as stored in the `orders` table
+struct OrderRow {
+    pub order_id: i32,
+    pub user_id: Uuid,
+    pub ticker: String,
+    pub price: Decimal,
+    pub shares: i32,
+    pub remaining: i32,
+    pub is_buy: bool,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl OrderRow {
+    fn into_order(self) -> Option<Order> {
+        Some(Order {
+            id: self.order_id,
+            user: self.user_id,
+            ticker: Ticker::try_from(self.ticker.as_str()).ok()?,
+            side: if self.is_buy { Side::Buy } else { Side::Sell },
+            price: Price::try_from(self.price).ok()?,
+            quantity: Shares::try_from(self.shares).ok()?,
+            remaining: Shares::try_from(self.remaining).ok()?,
+            status: self.status.parse().ok()?,
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+        })
+    }
+}
+
+/// Takes the total number of rows from the first row of a page fetched with `COUNT(*) OVER ()`,
+/// so the page and its total come from the same snapshot. A page past the end has no rows to
+/// carry the total, so only then is `count` run for it.
+async fn page_total(
+    first: Option<i64>,
+    page: &Pager,
+    count: impl Future<Output = Result<Option<i64>, sqlx::Error>>,
+) -> super::Result<u64> {
+    let total = match first {
+        Some(total) => total,
+        None if page.offset() == 0 => 0,
+        None => count.await.map_err(unspecified)?.unwrap_or_default(),
+    };
+
+    Ok(total.cast_unsigned())
+}
+
+/// Sums the value of each user's holdings at their stocks' most recent prices in one statement,
+/// in the order `ids` are given. Stocks that have never traded are worth nothing.
+async fn portfolio_values(
+    pool: &sqlx::PgPool,
+    ids: &[Uuid],
+) -> Result<Vec<(Uuid, Decimal)>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"SELECT ids.id as "id!", COALESCE(SUM(holdings.shares * latest.price), 0) as "value!"
+        FROM UNNEST($1::uuid[]) WITH ORDINALITY ids (id, ord)
+        LEFT JOIN holdings ON holdings.user_id = ids.id
+        LEFT JOIN latest_prices latest ON latest.ticker = holdings.ticker
+        GROUP BY ids.id, ids.ord
+        ORDER BY ids.ord"#,
+        ids
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.into_iter().map(|row| (row.id, row.value)).collect())
+}
+
+/// A `LIKE` pattern matching anything containing `text`, with the wildcards and escapes in it
+/// matched as is
+fn contains_pattern(text: &str) -> String {
+    let mut pattern = String::with_capacity(text.len() + 2);
+    pattern.push('%');
+
+    for c in text.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+
+    pattern.push('%');
+    pattern
+}
+
+/// Stores a Discord snowflake in a `BIGINT` column, keeping its bits as they are. Snowflakes above
+/// [`i64::MAX`] come out negative, which [`snowflake_from_db`] undoes.
+const fn snowflake_to_db(id: NonZeroU64) -> i64 {
+    id.get().cast_signed()
+}
+
+/// Reads back a Discord snowflake stored by [`snowflake_to_db`]
+fn snowflake_from_db(id: i64) -> NonZeroU64 {
+    NonZeroU64::new(id.cast_unsigned()).expect("Enforced by DB")
+}
+
+/// A row of `guild_settings`
+struct GuildSettingsRow {
+    guild_id: i64,
+    market_feed_channel: Option<i64>,
+    trading_enabled: bool,
+    locale: Option<String>,
+    admin_role: Option<i64>,
+    /// Each granted permission's role, lined up with `permissions`
+    role_ids: Vec<i64>,
+    permissions: Vec<String>,
+}
+
+impl From<GuildSettingsRow> for (NonZeroU64, GuildSettings) {
+    fn from(row: GuildSettingsRow) -> Self {
+        let mut settings = GuildSettings {
+            market_feed_channel: row.market_feed_channel.map(snowflake_from_db),
+            trading_enabled: row.trading_enabled,
+            locale: row.locale,
+            admin_role: row.admin_role.map(snowflake_from_db),
+            role_permissions: BTreeMap::new(),
+        };
+
+        for (role, permission) in row.role_ids.into_iter().zip(row.permissions) {
+            settings
+                .role_permissions
+                .entry(snowflake_from_db(role))
+                .or_default()
+                .insert(permission.parse().expect("Enforced by DB"));
+        }
+
+        (snowflake_from_db(row.guild_id), settings)
+    }
+}
+
+/// Locks a stock's row for the rest of the transaction, failing unless `owner` owns it. Returns
+/// the number of shares issued.
+async fn lock_owned_stock(
+    conn: &mut sqlx::PgConnection,
+    ticker: &Ticker,
+    owner: &Uuid,
+) -> super::Result<i32> {
+    let stock = sqlx::query!(
+        "SELECT owner_id, shares FROM stocks WHERE ticker = $1 FOR UPDATE",
+        ticker.as_str()
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(unspecified)?
+    .context(StockNotFoundSnafu { ticker: *ticker })?;
+
+    ensure!(
+        stock.owner_id.as_ref() == Some(owner),
+        NotStockOwnerSnafu { ticker: *ticker }
+    );
+
+    Ok(stock.shares)
+}
+
+/// Loads who holds a stock. Holdings only stay consistent with the result if the caller locked the
+/// stock's row beforehand
+async fn load_shareholders(
+    conn: &mut sqlx::PgConnection,
+    ticker: &Ticker,
+) -> super::Result<Shareholders> {
+    let issued = sqlx::query_scalar!(
+        "SELECT shares FROM stocks WHERE ticker = $1",
+        ticker.as_str()
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(unspecified)?
+    .context(StockNotFoundSnafu { ticker: *ticker })?;
+
+    let holders = sqlx::query!(
+        r#"SELECT user_id, shares + escrow as "held!" FROM holdings
+        WHERE ticker = $1 AND shares + escrow > 0 ORDER BY user_id"#,
+        ticker.as_str()
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(unspecified)?
+    .into_iter()
+    .map(|v| (v.user_id, v.held.try_into().expect("Enforced by DB")))
+    .collect();
+
+    Ok(Shareholders {
+        issued: issued.try_into().expect("Enforced by DB"),
+        holders,
+    })
+}
+
+/// Locks a stock for the rest of the transaction, failing if it can't be traded. This serializes
+/// matching on its book, and keeps it from being halted halfway through
+async fn lock_tradable(conn: &mut sqlx::PgConnection, ticker: &Ticker) -> super::Result<()> {
+    let status = sqlx::query_scalar!(
+        "SELECT status FROM stocks WHERE ticker = $1 FOR UPDATE",
+        ticker.as_str()
+    )
+    .fetch_optional(conn)
+    .await
+    .map_err(unspecified)?
+    .context(StockNotFoundSnafu { ticker: *ticker })?;
+
+    ensure!(
+        status.parse().is_ok_and(StockStatus::is_tradable),
+        StockHaltedSnafu { ticker: *ticker }
+    );
+
+    Ok(())
+}
+
+/// Reserves what the user needs to cover an order, so it can't be spent elsewhere while the order
+/// rests on the book. Buy orders also reserve `fee`
+async fn escrow(
+    conn: &mut sqlx::PgConnection,
+    order: &NewOrder,
+    fee: Decimal,
+) -> super::Result<()> {
+    let NewOrder {
+        user,
+        ticker,
+        side,
+        price,
+        quantity,
+        ..
+    } = order;
+
+    match side {
+        Side::Buy => {
+            let res = sqlx::query!(
+                "UPDATE users SET balance = balance - $2, escrow = escrow + $2 WHERE user_id = $1",
+                user,
+                price.notional(*quantity) + fee
+            )
+            .execute(conn)
+            .await
+            .map_err(|err| {
+                if is_check_violation(&err) {
+                    Error::InsufficientFunds
+                } else {
+                    unspecified(err)
+                }
+            })?;
+
+            ensure!(res.rows_affected() == 1, AccountNotFoundSnafu { id: *user });
+        }
+        Side::Sell => {
+            let res = sqlx::query!(
+                "UPDATE holdings SET shares = shares - $3, escrow = escrow + $3
+                WHERE user_id = $1 AND ticker = $2",
+                user,
+                ticker.as_str(),
+                i32::from(*quantity)
+            )
+            .execute(conn)
+            .await
+            .map_err(|err| {
+                if is_check_violation(&err) {
+                    Error::InsufficientShares
+                } else {
+                    unspecified(err)
+                }
+            })?;
+
+            ensure!(res.rows_affected() == 1, InsufficientSharesSnafu);
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds `delta` to `user`'s balance as an adjustment by hand, recording it in the ledger and the
+/// audit log. Reversals pass the adjustment they undo as `reverses`, which fails with
+/// [`AlreadyReversed`](Error::AlreadyReversed) if it was already undone
+async fn apply_adjustment(
+    conn: &mut sqlx::PgConnection,
+    user: &Uuid,
+    delta: Decimal,
+    reason: AdjustmentReason,
+    reverses: Option<i64>,
+    allow_negative: bool,
+    actor: &Actor,
+) -> super::Result<Adjustment> {
+    if allow_negative {
+        // Scoped to the transaction, so nothing else it shares a connection with can overdraw
+        sqlx::query_scalar!("SELECT set_config('rse.allow_overdraw', 'on', TRUE)")
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(unspecified)?;
+    }
+
+    let balance = sqlx::query_scalar!(
+        "UPDATE users SET balance = balance + $2 WHERE user_id = $1 RETURNING balance",
+        user,
+        delta
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|err| {
+        if is_check_violation(&err) {
+            Error::InsufficientFunds
+        } else {
+            unspecified(err)
+        }
+    })?
+    .context(AccountNotFoundSnafu { id: *user })?;
+
+    let row = sqlx::query!(
+        "WITH entry AS (
+            INSERT INTO ledger (user_id, kind, delta) VALUES ($1, 'adjustment', $2)
+            RETURNING ledger_id, time
+        )
+        INSERT INTO balance_adjustments (time, user_id, delta, reason, actor, ledger_id, reverses)
+        SELECT time, $1, $2, $3, $4, ledger_id, $5 FROM entry
+        RETURNING adjustment_id, time",
+        user,
+        delta,
+        reason.as_str(),
+        actor.to_string(),
+        reverses
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|err| match reverses {
+        Some(id)
+            if err
+                .as_database_error()
+                .is_some_and(sqlx::error::DatabaseError::is_unique_violation) =>
+        {
+            Error::AlreadyReversed { id }
+        }
+        _ => unspecified(err),
+    })?;
+
+    let entry = NewAuditEntry {
+        actor: *actor,
+        action: if reverses.is_some() {
+            Action::ReverseAdjustment
+        } else {
+            Action::AdjustBalance
+        },
+        target: Some(user.to_string()),
+        details: serde_json::json!({
+            "adjustment": row.adjustment_id,
+            "delta": delta,
+            "reason": reason.as_str(),
+            "reverses": reverses,
+            "allow_negative": allow_negative,
+        }),
+    };
+    insert_audit(conn, &entry).await?;
+
+    Ok(Adjustment {
+        id: row.adjustment_id,
+        user: *user,
+        delta,
+        reason,
+        actor: *actor,
+        reverses,
+        balance,
+        time: row.time,
+    })
+}
+
+/// Takes `amount` out of `user`'s balance and holds it in a new withdrawal request to `address`,
+/// recording the request in the ledger and the audit log
+async fn hold_withdrawal(
+    conn: &mut sqlx::PgConnection,
+    user: &Uuid,
+    amount: Decimal,
+    address: &Address,
+    actor: &Actor,
+) -> super::Result<Withdrawal> {
+    let res = sqlx::query!(
+        "UPDATE users SET balance = balance - $2 WHERE user_id = $1",
+        user,
+        amount
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(|err| {
+        if is_check_violation(&err) {
+            Error::InsufficientFunds
+        } else {
+            unspecified(err)
+        }
+    })?;
+    ensure!(res.rows_affected() == 1, AccountNotFoundSnafu { id: *user });
+
+    sqlx::query!(
+        "INSERT INTO ledger (user_id, kind, delta) VALUES ($1, 'withdrawal', $2)",
+        user,
+        -amount
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    let withdrawal = sqlx::query_as!(
+        WithdrawalRow,
+        r#"INSERT INTO withdrawal_requests (user_id, amount, address) VALUES ($1, $2, $3)
+        RETURNING withdrawal_id, user_id, amount, address as "address!", status,
+            requested_at, resolved_at"#,
+        user,
+        amount,
+        address.as_str()
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(unspecified)?
+    .into_withdrawal()
+    .ok_or(Error::Unspecified)?;
+
+    let entry = NewAuditEntry {
+        actor: *actor,
+        action: Action::RequestWithdrawal,
+        target: Some(withdrawal.id.to_string()),
+        details: serde_json::json!({
+            "user": user,
+            "amount": amount,
+            "address": address.as_str(),
+        }),
+    };
+    insert_audit(conn, &entry).await?;
+
+    Ok(withdrawal)
+}
+
+/// Cancels every open order `user` has, releasing their escrow
+async fn cancel_open_orders(
+    conn: &mut sqlx::PgConnection,
+    user: &Uuid,
+) -> super::Result<Vec<Order>> {
+    let orders: Vec<_> = sqlx::query_as!(
+        OrderRow,
+        r#"UPDATE orders SET status = 'cancelled'
+        WHERE user_id = $1 AND status IN ('queued', 'open')
+        RETURNING order_id, user_id, ticker, price, shares, remaining, type as is_buy, status,
+            created_at, expires_at"#,
+        user
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(unspecified)?
+    .into_iter()
+    .filter_map(OrderRow::into_order)
+    .collect();
+
+    for order in &orders {
+        release_escrow(&mut *conn, order).await?;
+    }
+
+    Ok(orders)
+}
+
+/// Adds every share `casualty` holds to what `survivor` holds of the same stock, averaging their
+/// cost basis, and returns the shares moved of each stock. The casualty must have nothing in
+/// escrow.
+async fn move_holdings(
+    conn: &mut sqlx::PgConnection,
+    survivor: &Uuid,
+    casualty: &Uuid,
+) -> super::Result<Vec<(Ticker, Shares)>> {
+    let holdings = sqlx::query!(
+        r#"DELETE FROM holdings WHERE user_id = $1
+        RETURNING ticker, shares as "shares: Shares", avg_cost"#,
+        casualty
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    let mut moved = Vec::with_capacity(holdings.len());
+
+    for holding in holdings {
+        if holding.shares == Shares::ZERO {
+            continue;
+        }
+
+        let held = sqlx::query!(
+            r#"SELECT shares + escrow as "held!: Shares", avg_cost FROM holdings
+            WHERE user_id = $1 AND ticker = $2
+            FOR UPDATE"#,
+            survivor,
+            holding.ticker
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(unspecified)?;
+
+        let (held, held_cost) = held.map_or((Shares::ZERO, None), |row| (row.held, row.avg_cost));
+        let avg_cost = holding
+            .avg_cost
+            .and_then(|cost| weighted_avg_cost(held, held_cost, holding.shares, cost));
+
+        sqlx::query!(
+            "INSERT INTO holdings (ticker, user_id, shares, avg_cost) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, ticker) DO UPDATE
+                SET shares = holdings.shares + EXCLUDED.shares, avg_cost = EXCLUDED.avg_cost",
+            holding.ticker,
+            survivor,
+            i32::from(holding.shares),
+            avg_cost
+        )
+        .execute(&mut *conn)
+        .await
+        .map_err(unspecified)?;
+
+        let ticker = Ticker::try_from(holding.ticker.as_str()).map_err(|_| Error::Unspecified)?;
+        moved.push((ticker, holding.shares));
+    }
+
+    Ok(moved)
+}
+
+/// Moves the balance and links of the casualty of `merge` onto its survivor, then closes it
+async fn hand_over_account(
+    conn: &mut sqlx::PgConnection,
+    merge: &AccountMerge,
+) -> super::Result<()> {
+    // The casualty gives up its links before the survivor takes them, as each is unique
+    sqlx::query!(
+        "UPDATE users SET disc_id = NULL, mc_id = NULL, balance = 0,
+            closed_at = timezone('utc', now())
+        WHERE user_id = $1",
+        merge.casualty
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    sqlx::query!(
+        "UPDATE users SET balance = balance + $2, disc_id = COALESCE(disc_id, $3),
+            mc_id = COALESCE(mc_id, $4)
+        WHERE user_id = $1",
+        merge.survivor,
+        merge.balance,
+        merge.disc_id.map(snowflake_to_db),
+        merge.mc_id
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    Ok(())
+}
+
+/// Records `merge` in the audit log using `conn`, along with each identity its casualty gave up
+async fn audit_merge(
+    conn: &mut sqlx::PgConnection,
+    merge: &AccountMerge,
+    actor: &Actor,
+) -> super::Result<()> {
+    let entry = NewAuditEntry {
+        actor: *actor,
+        action: Action::MergeAccounts,
+        target: Some(merge.survivor.to_string()),
+        details: serde_json::json!({
+            "casualty": merge.casualty,
+            "balance": merge.balance,
+            "holdings": merge.holdings.len(),
+            "ledger_entries": merge.ledger_entries,
+            "trades": merge.trades,
+            "stocks": merge.stocks,
+            "disc_id": merge.disc_id,
+            "mc_id": merge.mc_id,
+        }),
+    };
+    insert_audit(&mut *conn, &entry).await?;
+
+    audit_unlinks(
+        conn,
+        &merge.casualty,
+        merge.disc_id,
+        merge.mc_id,
+        Action::MergeAccounts,
+        actor,
+    )
+    .await
+}
+
+/// Moves the ledger entries, balance adjustments, deposits, orders, trades and stocks of the
+/// casualty of `merge` onto its survivor, counting what moved. Ledger entries note the account
+/// they came from.
+async fn move_history(
+    conn: &mut sqlx::PgConnection,
+    merge: &mut AccountMerge,
+) -> super::Result<()> {
+    let (survivor, casualty) = (&merge.survivor, &merge.casualty);
+
+    merge.ledger_entries = sqlx::query!(
+        "UPDATE ledger SET user_id = $1, merged_from = COALESCE(merged_from, $2)
+        WHERE user_id = $2",
+        survivor,
+        casualty
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?
+    .rows_affected();
+
+    sqlx::query!(
+        "UPDATE balance_adjustments SET user_id = $1 WHERE user_id = $2",
+        survivor,
+        casualty
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    sqlx::query!(
+        "UPDATE kromer_deposits SET user_id = $1 WHERE user_id = $2",
+        survivor,
+        casualty
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    sqlx::query!(
+        "UPDATE orders SET user_id = $1 WHERE user_id = $2",
+        survivor,
+        casualty
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    merge.trades = sqlx::query!(
+        "UPDATE stock_events SET
+            buyer_id = CASE WHEN buyer_id = $2 THEN $1 ELSE buyer_id END,
+            seller_id = CASE WHEN seller_id = $2 THEN $1 ELSE seller_id END
+        WHERE buyer_id = $2 OR seller_id = $2",
+        survivor,
+        casualty
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?
+    .rows_affected();
+
+    merge.stocks = sqlx::query!(
+        "UPDATE stocks SET owner_id = $1 WHERE owner_id = $2",
+        survivor,
+        casualty
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?
+    .rows_affected();
+
+    // Codes it handed out would otherwise link identities to a closed account
+    sqlx::query!("DELETE FROM link_codes WHERE user_id = $1", casualty)
+        .execute(&mut *conn)
+        .await
+        .map_err(unspecified)?;
+
+    Ok(())
+}
+
+/// Retires every share `user` holds, crediting them at the most recent price of each stock, or
+/// nothing if it never traded. Shares of a stock they hold every share of are left alone, as a
+/// stock always has at least one share issued. Returns the Kromer credited.
+async fn liquidate_holdings(conn: &mut sqlx::PgConnection, user: &Uuid) -> super::Result<Decimal> {
+    let holdings = sqlx::query!(
+        r#"SELECT holdings.ticker, holdings.shares as "shares!: Shares",
+            latest.price as "price?: Price"
+        FROM holdings LEFT JOIN latest_prices latest ON latest.ticker = holdings.ticker
+        WHERE user_id = $1 AND shares > 0
+        ORDER BY holdings.ticker
+        FOR UPDATE OF holdings"#,
+        user
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    let mut credited = Decimal::ZERO;
+
+    for holding in holdings {
+        let res = sqlx::query!(
+            "UPDATE stocks SET shares = shares - $2 WHERE ticker = $1 AND shares > $2",
+            holding.ticker,
+            i32::from(holding.shares)
+        )
+        .execute(&mut *conn)
+        .await
+        .map_err(unspecified)?;
+
+        if res.rows_affected() == 0 {
+            continue;
+        }
+
+        sqlx::query!(
+            "UPDATE holdings SET shares = 0 WHERE user_id = $1 AND ticker = $2",
+            user,
+            holding.ticker
+        )
+        .execute(&mut *conn)
+        .await
+        .map_err(unspecified)?;
+
+        let proceeds = holding
+            .price
+            .map_or(Decimal::ZERO, |price| price.notional(holding.shares));
+
+        if proceeds > Decimal::ZERO {
+            sqlx::query!(
+                "UPDATE users SET balance = balance + $2 WHERE user_id = $1",
+                user,
+                proceeds
+            )
+            .execute(&mut *conn)
+            .await
+            .map_err(unspecified)?;
+
+            sqlx::query!(
+                "INSERT INTO ledger (user_id, kind, delta, ticker)
+                VALUES ($1, 'liquidation', $2, $3)",
+                user,
+                proceeds,
+                holding.ticker
+            )
+            .execute(&mut *conn)
+            .await
+            .map_err(unspecified)?;
+
+            credited += proceeds;
+        }
+    }
+
+    Ok(credited)
+}
+
+/// Returns whatever remains of a closed order's escrow to its owner
+async fn release_escrow(conn: &mut sqlx::PgConnection, order: &Order) -> super::Result<()> {
+    match order.side {
+        Side::Buy => {
+            // The caller already holds the lock on the order
+            let fee_escrow = sqlx::query_scalar!(
+                "SELECT fee_escrow FROM orders WHERE order_id = $1",
+                order.id
+            )
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(unspecified)?;
+
+            sqlx::query!(
+                "UPDATE orders SET fee_escrow = 0 WHERE order_id = $1",
+                order.id
+            )
+            .execute(&mut *conn)
+            .await
+            .map_err(unspecified)?;
+
+            sqlx::query!(
+                "UPDATE users SET escrow = escrow - $2, balance = balance + $2 WHERE user_id = $1",
+                order.user,
+                order.price.notional(order.remaining) + fee_escrow
+            )
+            .execute(conn)
+            .await
+            .map_err(unspecified)?
+        }
+        Side::Sell => sqlx::query!(
+            "UPDATE holdings SET escrow = escrow - $3, shares = shares + $3
+            WHERE user_id = $1 AND ticker = $2",
+            order.user,
+            order.ticker.as_str(),
+            i32::from(order.remaining)
+        )
+        .execute(conn)
+        .await
+        .map_err(unspecified)?,
+    };
+
+    Ok(())
+}
+
+/// Releases the escrow backing the buy side of a fill, refunding anything the buyer saved, and
+/// pays the fee into `treasury`
+async fn settle_buyer(
+    conn: &mut sqlx::PgConnection,
+    fill: &Fill,
+    treasury: Option<&Uuid>,
+) -> super::Result<()> {
+    let buy = sqlx::query!(
+        "SELECT price, remaining, fee_escrow FROM orders WHERE order_id = $1",
+        fill.buy_order
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    // Whatever fee escrow is left over once the order is filled goes back to the buyer
+    let fee_released = if buy.remaining == 0 {
+        buy.fee_escrow
+    } else {
+        fill.fee.min(buy.fee_escrow)
+    };
+
+    if !fee_released.is_zero() {
+        sqlx::query!(
+            "UPDATE orders SET fee_escrow = fee_escrow - $2 WHERE order_id = $1",
+            fill.buy_order,
+            fee_released
+        )
+        .execute(&mut *conn)
+        .await
+        .map_err(unspecified)?;
+    }
+
+    // The buyer escrowed their limit price, so refund anything they saved. Fees on partial fills
+    // can round to a cent or so more than was escrowed, which comes out of their balance instead
+    let value = fill.notional();
+    let released = Decimal::from(fill.shares) * buy.price + fee_released;
+    sqlx::query!(
+        "UPDATE users SET escrow = escrow - $2, balance = balance + $3 WHERE user_id = $1",
+        fill.buyer,
+        released,
+        released - value - fill.fee
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(|err| {
+        if is_check_violation(&err) {
+            Error::InsufficientFunds
+        } else {
+            unspecified(err)
+        }
+    })?;
+
+    if let Some(treasury) = treasury
+        && !fill.fee.is_zero()
+    {
+        sqlx::query!(
+            "UPDATE users SET balance = balance + $2 WHERE user_id = $1",
+            treasury,
+            fill.fee
+        )
+        .execute(&mut *conn)
+        .await
+        .map_err(unspecified)?;
+    }
+
+    Ok(())
+}
+
+/// Escrows what a new order needs, adds it to the book and matches it against the resting orders on
+/// the other side, settling every fill. Queued orders are only escrowed and stored, to be matched
+/// once the market opens. Whatever an immediate-or-cancel order couldn't fill is cancelled and its
+/// escrow released straight away. Resting orders expired by `now` are left alone. Returns the order
+/// as it stands afterwards
+async fn enter_order(
+    conn: &mut sqlx::PgConnection,
+    order: &NewOrder,
+    fees: Option<&FeeSchedule>,
+    queue: bool,
+    now: DateTime<Utc>,
+) -> super::Result<(Order, Vec<Fill>)> {
+    let NewOrder {
+        user,
+        ticker,
+        side,
+        price,
+        quantity,
+        expires_at,
+        immediate_or_cancel,
+    } = *order;
+
+    let fee_escrow = match side {
+        Side::Buy => fees.map_or(Decimal::ZERO, |f| f.fee(price.notional(quantity))),
+        Side::Sell => Decimal::ZERO,
+    };
+    let status = if queue {
+        OrderStatus::Queued
+    } else {
+        OrderStatus::Open
+    };
+
+    lock_tradable(&mut *conn, &ticker).await?;
+
+    escrow(&mut *conn, order, fee_escrow).await?;
+
+    let id = sqlx::query_scalar!(
+        "INSERT INTO orders
+            (user_id, ticker, price, shares, remaining, type, expires_at, fee_escrow, status)
+        VALUES ($1, $2, $3, $4, $4, $5, $6, $7, $8) RETURNING order_id",
+        user,
+        ticker.as_str(),
+        price.get(),
+        i32::from(quantity),
+        side == Side::Buy,
+        expires_at,
+        fee_escrow,
+        status.as_str()
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    let fills = if queue {
+        Vec::new()
+    } else {
+        let incoming = IncomingOrder {
+            id,
+            user,
+            side,
+            price,
+            remaining: quantity,
+        };
+
+        match_on_book(&mut *conn, &ticker, &incoming, fees, now).await?
+    };
+
+    if immediate_or_cancel && !queue {
+        let unfilled = sqlx::query_as!(
+            OrderRow,
+            r#"UPDATE orders SET status = 'cancelled' WHERE order_id = $1 AND status = 'open'
+            RETURNING order_id, user_id, ticker, price, shares, remaining, type as is_buy,
+                status, created_at, expires_at"#,
+            id
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(unspecified)?
+        .map(|row| row.into_order().ok_or(Error::Unspecified))
+        .transpose()?;
+
+        if let Some(order) = unfilled {
+            release_escrow(&mut *conn, &order).await?;
+            return Ok((order, fills));
+        }
+    }
+
+    Ok((load_order(&mut *conn, id).await?, fills))
+}
+
+/// Matches an order already on the book against the resting orders on the other side that haven't
+/// expired by `now`, settling every fill. The stock must already be locked
+async fn match_on_book(
+    conn: &mut sqlx::PgConnection,
+    ticker: &Ticker,
+    incoming: &IncomingOrder,
+    fees: Option<&FeeSchedule>,
+    now: DateTime<Utc>,
+) -> super::Result<Vec<Fill>> {
+    struct RestingRow {
+        pub order_id: i32,
+        pub user_id: Uuid,
+        pub price: Price,
+        pub remaining: Shares,
+        pub expires_at: Option<DateTime<Utc>>,
+    }
+
+    let resting: Vec<_> = sqlx::query_as!(
+        RestingRow,
+        r#"SELECT order_id, user_id, price as "price: Price",
+            remaining as "remaining: Shares", expires_at FROM orders
+        WHERE ticker = $1 AND status = 'open' AND type = $2
+            AND CASE WHEN $2 THEN price >= $3 ELSE price <= $3 END
+        ORDER BY order_id FOR UPDATE"#,
+        ticker.as_str(),
+        incoming.side.opposite() == Side::Buy,
+        incoming.price.get()
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(unspecified)?
+    .into_iter()
+    .map(|v| RestingOrder {
+        id: v.order_id,
+        user: v.user_id,
+        price: v.price,
+        remaining: v.remaining,
+        expires_at: v.expires_at,
+    })
+    .collect();
+
+    let mut fills = match_order(incoming, &resting, now);
+    let treasury = fees.map(|f| &f.treasury);
+
+    for fill in &mut fills {
+        fill.fee = fees.map_or(Decimal::ZERO, |f| f.fee(fill.notional()));
+        apply_fill(&mut *conn, ticker, fill, treasury).await?;
+    }
+
+    Ok(fills)
+}
+
+async fn load_order(conn: &mut sqlx::PgConnection, id: i32) -> super::Result<Order> {
+    sqlx::query_as!(
+        OrderRow,
+        r#"SELECT order_id, user_id, ticker, price, shares, remaining, type as is_buy,
+            status, created_at, expires_at
+        FROM orders WHERE order_id = $1"#,
+        id
+    )
+    .fetch_one(conn)
+    .await
+    .map_err(unspecified)?
+    .into_order()
+    .ok_or(Error::Unspecified)
+}
+
+/// Settles a single fill: shrinks both orders, releases the escrow backing them, moves shares and
+/// Kromer, pays the fee into `treasury`, and records the trade as a stock event
+async fn apply_fill(
+    conn: &mut sqlx::PgConnection,
+    ticker: &Ticker,
+    fill: &Fill,
+    treasury: Option<&Uuid>,
+) -> super::Result<()> {
+    let shares = i32::from(fill.shares);
+    let value = fill.notional();
+
+    let remaining = sqlx::query!(
+        r#"UPDATE orders SET remaining = remaining - $3,
+            status = CASE WHEN remaining = $3 THEN 'filled' ELSE status END
+        WHERE order_id IN ($1, $2)
+        RETURNING order_id, remaining as "remaining: Shares""#,
+        fill.buy_order,
+        fill.sell_order,
+        shares
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+    let remaining = |order| {
+        remaining
+            .iter()
+            .find(|row| row.order_id == order)
+            .map_or(Shares::ZERO, |row| row.remaining)
+    };
+
+    settle_buyer(&mut *conn, fill, treasury).await?;
+
+    let held = sqlx::query!(
+        r#"SELECT shares + escrow as "held!: Shares", avg_cost FROM holdings
+        WHERE user_id = $1 AND ticker = $2 FOR UPDATE"#,
+        fill.buyer,
+        ticker.as_str()
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    let avg_cost = match held {
+        Some(held) => weighted_avg_cost(held.held, held.avg_cost, fill.shares, fill.price.get()),
+        None => Some(fill.price.get()),
+    };
+
+    let buyer_position = sqlx::query_scalar!(
+        r#"INSERT INTO holdings (ticker, user_id, shares, avg_cost) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (user_id, ticker) DO UPDATE
+            SET shares = holdings.shares + EXCLUDED.shares, avg_cost = EXCLUDED.avg_cost
+        RETURNING shares + escrow as "position!: Shares""#,
+        ticker.as_str(),
+        fill.buyer,
+        shares,
+        avg_cost
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    let seller = sqlx::query!(
+        r#"UPDATE holdings SET escrow = escrow - $3 WHERE user_id = $1 AND ticker = $2
+        RETURNING avg_cost, shares + escrow as "position!: Shares""#,
+        fill.seller,
+        ticker.as_str(),
+        shares
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    sqlx::query!(
+        "DELETE FROM holdings WHERE user_id = $1 AND ticker = $2 AND shares = 0 AND escrow = 0",
+        fill.seller,
+        ticker.as_str()
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    sqlx::query!(
+        "UPDATE users SET balance = balance + $2 WHERE user_id = $1",
+        fill.seller,
+        value
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    let event = sqlx::query!(
+        "INSERT INTO stock_events
+            (seller_id, buyer_id, ticker, price, shares, realized_pl, buy_order_id, sell_order_id,
+            fee)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING event_id, time",
+        fill.seller,
+        fill.buyer,
+        ticker.as_str(),
+        fill.price.get(),
+        shares,
+        realized_pl(seller.avg_cost, fill.shares, fill.price),
+        fill.buy_order,
+        fill.sell_order,
+        fill.fee
+    )
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    update_latest_price(&mut *conn, ticker, fill.price, event.event_id, event.time).await?;
+
+    let after = [
+        (remaining(fill.buy_order), buyer_position),
+        (remaining(fill.sell_order), seller.position),
+    ];
+    queue_receipts(&mut *conn, ticker, fill, after).await
+}
+
+/// Queues a receipt for `fill` to each side of it that wants one using `conn`. `after` holds what
+/// remains of each order and the position of each user once it is applied, buyer first.
+async fn queue_receipts(
+    conn: &mut sqlx::PgConnection,
+    ticker: &Ticker,
+    fill: &Fill,
+    after: [(Shares, Shares); 2],
+) -> super::Result<()> {
+    let wanted = sqlx::query_scalar!(
+        "SELECT user_id FROM users WHERE user_id IN ($1, $2) AND receipts",
+        fill.buyer,
+        fill.seller
+    )
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    let [buy, sell] = after;
+    let sides = [
+        (fill.buyer, fill.buy_order, Side::Buy, fill.fee, buy),
+        (
+            fill.seller,
+            fill.sell_order,
+            Side::Sell,
+            Decimal::ZERO,
+            sell,
+        ),
+    ];
+
+    for (user, order, side, fee, (remaining, position)) in sides {
+        if !wanted.contains(&user) {
+            continue;
+        }
+
+        let receipt = Receipt {
+            user,
+            order,
+            ticker: *ticker,
+            side,
+            fills: vec![ReceiptFill {
+                price: fill.price,
+                shares: fill.shares,
+                fee,
+            }],
+            remaining,
+            position,
+        };
+        queue_receipt(&mut *conn, receipt).await?;
+    }
+
+    Ok(())
+}
+
+/// Queues `receipt` in the outbox using `conn`, adding it to the receipt for the same order still
+/// waiting to be sent if there is one. Receipts wait [`RECEIPT_DELAY`] before being sent, so fills
+/// in quick succession go out together.
+async fn queue_receipt(conn: &mut sqlx::PgConnection, receipt: Receipt) -> super::Result<()> {
+    let key = format!("receipt:{}", receipt.order);
+
+    // Locked so the dispatcher skips it until this transaction is over
+    let pending = sqlx::query!(
+        "SELECT outbox_id, payload FROM outbox WHERE coalesce_key = $1 FOR UPDATE",
+        key
+    )
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    let earlier = pending.and_then(|row| match serde_json::from_value(row.payload) {
+        Ok(Notice::TradeReceipt(earlier)) => Some((row.outbox_id, earlier)),
+        _ => None,
+    });
+
+    let Some((id, mut earlier)) = earlier else {
+        let payload = serde_json::to_value(Notice::TradeReceipt(receipt)).map_err(|err| {
+            tracing::error!(%err, "could not serialize notice");
+            Error::Unspecified
+        })?;
+
+        sqlx::query!(
+            "INSERT INTO outbox (payload, next_attempt_at, coalesce_key)
+            VALUES ($1, timezone('utc', now()) + make_interval(secs => $2), $3)",
+            payload,
+            RECEIPT_DELAY.as_secs_f64(),
+            key
+        )
+        .execute(&mut *conn)
+        .await
+        .map_err(unspecified)?;
+
+        return Ok(());
+    };
+
+    earlier.merge(receipt);
+    let payload = serde_json::to_value(Notice::TradeReceipt(earlier)).map_err(|err| {
+        tracing::error!(%err, "could not serialize notice");
+        Error::Unspecified
+    })?;
+
+    sqlx::query!(
+        "UPDATE outbox SET payload = $2 WHERE outbox_id = $1",
+        id,
+        payload
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    Ok(())
+}
+
+/// How long a receipt waits in the outbox for more fills of the same order
+const RECEIPT_DELAY: Duration = Duration::from_secs(5);
+
+/// Makes a newly recorded stock event its stock's latest price, unless a later one already is.
+/// Must run in the same transaction as the event's insert
+async fn update_latest_price(
+    conn: &mut sqlx::PgConnection,
+    ticker: &Ticker,
+    price: Price,
+    event_id: i32,
+    time: DateTime<Utc>,
+) -> super::Result<()> {
+    sqlx::query!(
+        "INSERT INTO latest_prices (ticker, price, updated_at, event_id)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (ticker) DO UPDATE
+            SET price = EXCLUDED.price, updated_at = EXCLUDED.updated_at,
+                event_id = EXCLUDED.event_id
+            WHERE (latest_prices.updated_at, latest_prices.event_id)
+                < (EXCLUDED.updated_at, EXCLUDED.event_id)",
+        ticker.as_str(),
+        price.get(),
+        time,
+        event_id
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(unspecified)?;
+
+    Ok(())
+}
+
+/// Reads up to `limit` accounts of a snapshot, continuing after `after`
+async fn snapshot_users(
+    conn: &mut sqlx::PgConnection,
+    after: Option<Uuid>,
+    limit: i64,
+) -> super::Result<Vec<SnapshotRecord>> {
+    let rows = sqlx::query!(
+        "SELECT user_id, disc_id, mc_id, balance, escrow, frozen, system, created_at, closed_at
+        FROM users WHERE $1::uuid IS NULL OR user_id > $1
+        ORDER BY user_id LIMIT $2",
+        after,
+        limit
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(unspecified)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            SnapshotRecord::User(SnapshotUser {
+                id: row.user_id,
+                disc_id: row.disc_id.map(snowflake_from_db),
+                mc_id: row.mc_id,
+                balance: row.balance,
+                escrow: row.escrow,
+                frozen: row.frozen,
+                system: row.system,
+                created_at: row.created_at,
+                closed_at: row.closed_at,
+            })
+        })
+        .collect())
+}
+
+/// Reads up to `limit` stocks of a snapshot, continuing after `after`
+async fn snapshot_stocks(
+    conn: &mut sqlx::PgConnection,
+    after: Option<&Ticker>,
+    limit: i64,
+) -> super::Result<Vec<SnapshotRecord>> {
+    let rows = sqlx::query_as!(
+        StockRow,
+        "SELECT ticker, owner_id, shares, created_at, status, name, description, icon_url
+        FROM stocks WHERE ticker > $1
+        ORDER BY ticker LIMIT $2",
+        after.map_or("", Ticker::as_str),
+        limit
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(unspecified)?;
+
+    rows.into_iter()
+        .map(|row| {
+            let info = row.into_info()?;
+            Some(SnapshotRecord::Stock(SnapshotStock {
+                ticker: info.ticker,
+                shares: info.shares,
+                owner: info.owner,
+                status: info.status,
+                name: info.metadata.name,
+                description: info.metadata.description,
+                icon_url: info.metadata.icon_url,
+                created_at: info.created_at,
+            }))
+        })
+        .collect::<Option<_>>()
+        .ok_or(Error::Unspecified)
+}
+
+/// Reads up to `limit` non-empty holdings of a snapshot, continuing after the holding of `after`
+async fn snapshot_holdings(
+    conn: &mut sqlx::PgConnection,
+    after: Option<(Uuid, &Ticker)>,
+    limit: i64,
+) -> super::Result<Vec<SnapshotRecord>> {
+    let rows = sqlx::query!(
+        "SELECT user_id, ticker, shares, escrow, avg_cost FROM holdings
+        WHERE shares > 0 AND ($1::uuid IS NULL OR (user_id, ticker) > ($1, $2::VARCHAR))
+        ORDER BY user_id, ticker LIMIT $3",
+        after.map(|(user, _)| user),
+        after.map_or("", |(_, ticker)| ticker.as_str()),
+        limit
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(unspecified)?;
+
+    rows.into_iter()
+        .map(|row| {
+            Some(SnapshotRecord::Holding(SnapshotHolding {
+                user: row.user_id,
+                ticker: Ticker::try_from(row.ticker.as_str()).ok()?,
+                shares: Shares::try_from(row.shares).ok()?,
+                escrow: Shares::try_from(row.escrow).ok()?,
+                avg_cost: row.avg_cost,
+            }))
+        })
+        .collect::<Option<_>>()
+        .ok_or(Error::Unspecified)
+}
+
+/// Reads up to `limit` open or queued orders of a snapshot, continuing after the order `after`
+async fn snapshot_orders(
+    conn: &mut sqlx::PgConnection,
+    after: Option<i32>,
+    limit: i64,
+) -> super::Result<Vec<SnapshotRecord>> {
+    let rows = sqlx::query!(
+        "SELECT order_id, user_id, ticker, price, shares, remaining, type AS is_buy, status,
+            created_at, expires_at, fee_escrow
+        FROM orders WHERE order_id > $1 AND status IN ('queued', 'open')
+        ORDER BY order_id LIMIT $2",
+        after.unwrap_or_default(),
+        limit
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(unspecified)?;
+
+    rows.into_iter()
+        .map(|row| {
+            let order = OrderRow {
+                order_id: row.order_id,
+                user_id: row.user_id,
+                ticker: row.ticker,
+                price: row.price,
+                shares: row.shares,
+                remaining: row.remaining,
+                is_buy: row.is_buy,
+                status: row.status,
+                created_at: row.created_at,
+                expires_at: row.expires_at,
+            };
+
+            Some(SnapshotRecord::Order(SnapshotOrder {
+                order: order.into_order()?,
+                fee_escrow: row.fee_escrow,
+            }))
+        })
+        .collect::<Option<_>>()
+        .ok_or(Error::Unspecified)
+}
+
+/// Reads up to `limit` latest prices of a snapshot, continuing after the price of `after`
+async fn snapshot_prices(
+    conn: &mut sqlx::PgConnection,
+    after: Option<&Ticker>,
+    limit: i64,
+) -> super::Result<Vec<SnapshotRecord>> {
+    let rows = sqlx::query!(
+        "SELECT latest_prices.ticker, latest_prices.price, updated_at, buyer_id
+        FROM latest_prices JOIN stock_events USING (event_id)
+        WHERE latest_prices.ticker > $1
+        ORDER BY latest_prices.ticker LIMIT $2",
+        after.map_or("", Ticker::as_str),
+        limit
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(unspecified)?;
+
+    rows.into_iter()
+        .map(|row| {
+            Some(SnapshotRecord::Price(SnapshotPrice {
+                ticker: Ticker::try_from(row.ticker.as_str()).ok()?,
+                price: Price::try_from(row.price).ok()?,
+                updated_at: row.updated_at,
+                by: row.buyer_id,
+            }))
+        })
+        .collect::<Option<_>>()
+        .ok_or(Error::Unspecified)
+}
+
+/// Writes a single record of a snapshot back as it is
+async fn restore_record(
+    conn: &mut sqlx::PgConnection,
+    record: &SnapshotRecord,
+) -> super::Result<()> {
+    let query = match record {
+        SnapshotRecord::User(user) => sqlx::query!(
+            "INSERT INTO users (user_id, disc_id, mc_id, balance, escrow, frozen, system,
+                created_at, closed_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            user.id,
+            user.disc_id.map(snowflake_to_db),
+            user.mc_id,
+            user.balance,
+            user.escrow,
+            user.frozen,
+            user.system,
+            user.created_at,
+            user.closed_at
+        ),
+        SnapshotRecord::Stock(stock) => sqlx::query!(
+            "INSERT INTO stocks (ticker, shares, owner_id, status, name, description, icon_url,
+                created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+            stock.ticker.as_str(),
+            i32::from(stock.shares),
+            stock.owner,
+            stock.status.as_str(),
+            stock.name.as_deref(),
+            stock.description.as_deref(),
+            stock.icon_url.as_deref(),
+            stock.created_at
+        ),
+        SnapshotRecord::Holding(holding) => sqlx::query!(
+            "INSERT INTO holdings (user_id, ticker, shares, escrow, avg_cost)
+            VALUES ($1, $2, $3, $4, $5)",
+            holding.user,
+            holding.ticker.as_str(),
+            i32::from(holding.shares),
+            i32::from(holding.escrow),
+            holding.avg_cost
+        ),
+        SnapshotRecord::Order(SnapshotOrder { order, fee_escrow }) => sqlx::query!(
+            "INSERT INTO orders (order_id, user_id, ticker, price, shares, remaining, type,
+                status, created_at, expires_at, fee_escrow)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)",
+            order.id,
+            order.user,
+            order.ticker.as_str(),
+            order.price.get(),
+            i32::from(order.quantity),
+            i32::from(order.remaining),
+            order.side == Side::Buy,
+            order.status.as_str(),
+            order.created_at,
+            order.expires_at,
+            fee_escrow
+        ),
+        SnapshotRecord::Price(price) => {
+            let event_id = sqlx::query_scalar!(
+                "INSERT INTO stock_events (time, seller_id, buyer_id, ticker, price, shares)
+                VALUES ($1, $2, $2, $3, $4, 0)
+                RETURNING event_id",
+                price.updated_at,
+                price.by,
+                price.ticker.as_str(),
+                price.price.get()
+            )
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(unspecified)?;
+
+            return update_latest_price(
+                conn,
+                &price.ticker,
+                price.price,
+                event_id,
+                price.updated_at,
+            )
+            .await;
+        }
+    };
+
+    query.execute(conn).await.map_err(unspecified)?;
+    Ok(())
+}
+
+impl super::StockRepository for PgPort {
+    fn user_exists(&self, id: &uuid::Uuid) -> impl Future<Output = super::Result<bool>> + Send {
+        sqlx::query_scalar!("SELECT EXISTS (SELECT 1 FROM users WHERE user_id = $1)", id)
+            .fetch_one(&self.pool)
+            .map(|res| match res {
+                Ok(b) => Ok(b.unwrap_or_default()),
+                Err(err) => Err(unspecified(err)),
+            })
+            .query("user_exists", self.slow_query)
+    }
+
+    fn stock_exists(&self, stock: &Ticker) -> impl Future<Output = super::Result<bool>> + Send {
+        sqlx::query_scalar!(
+            "SELECT EXISTS (SELECT 1 FROM stocks WHERE ticker = $1)",
+            stock.as_str()
+        )
+        .fetch_one(&self.pool)
+        .map(|res| match res {
+            Ok(b) => Ok(b.unwrap_or_default()),
+            Err(err) => Err(unspecified(err)),
+        })
+        .query("stock_exists", self.slow_query)
+    }
+
+    fn discord_to_id(
+        &self,
+        id: NonZeroU64,
+    ) -> impl Future<Output = super::Result<Option<uuid::Uuid>>> + Send {
+        sqlx::query_scalar!(
+            "SELECT user_id FROM users where disc_id = $1",
+            snowflake_to_db(id)
+        )
+        .fetch_optional(&self.pool)
+        .map_err(unspecified)
+        .query("discord_to_id", self.slow_query)
+    }
+
+    fn mc_to_id(
+        &self,
+        id: &uuid::Uuid,
+    ) -> impl Future<Output = super::Result<Option<uuid::Uuid>>> + Send {
+        sqlx::query_scalar!("SELECT user_id FROM users where mc_id = $1", id)
+            .fetch_optional(&self.pool)
+            .map_err(unspecified)
+            .query("mc_to_id", self.slow_query)
+    }
+
+    fn mc_username_to_uuid(
+        &self,
+        name: &str,
+        since: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Option<uuid::Uuid>>> + Send {
+        sqlx::query_scalar!(
+            "SELECT mc_id FROM mc_usernames WHERE username = lower($1) AND resolved_at >= $2",
+            name,
+            since
+        )
+        .fetch_optional(&self.pool)
+        .map_err(unspecified)
+        .query("mc_username_to_uuid", self.slow_query)
+    }
+
+    fn cache_mc_username(
+        &self,
+        name: &str,
+        id: &uuid::Uuid,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        sqlx::query!(
+            "INSERT INTO mc_usernames (username, mc_id) VALUES (lower($1), $2)
+            ON CONFLICT (username) DO UPDATE SET mc_id = excluded.mc_id, resolved_at = now()",
+            name,
+            id
+        )
+        .execute(&self.pool)
+        .map_ok(|_| ())
+        .map_err(unspecified)
+        .query("cache_mc_username", self.slow_query)
+    }
+
+    fn user_info(
+        &self,
+        id: &uuid::Uuid,
+    ) -> impl Future<Output = super::Result<Option<crate::model::UserInfo>>> + Send {
+        struct TmpUserInfo {
+            /// The internal ID of the user
+            pub user_id: Uuid,
+            /// The Kromer free to spend
+            pub available: Decimal,
+            /// The Kromer on hold
+            pub held: Decimal,
+            pub created_at: DateTime<Utc>,
+            pub mc_id: Option<Uuid>,
+            pub disc_id: Option<i64>,
+            pub public_portfolio: bool,
+            pub privacy: String,
+            pub closed_at: Option<DateTime<Utc>>,
+        }
+
+        sqlx::query_as!(
+            TmpUserInfo,
+            r#"SELECT users.user_id, balances.available as "available!", balances.held as "held!",
+                created_at, mc_id, disc_id, public_portfolio, privacy, closed_at
+            FROM users JOIN account_balances balances ON balances.user_id = users.user_id
+            WHERE users.user_id = $1"#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .map(|res| match res {
+            Ok(Some(u)) => {
+                let info = UserInfo {
+                    id: u.user_id,
+                    balance: Some(u.available + u.held),
+                    available_balance: Some(u.available),
+                    held_balance: Some(u.held),
+                    created_at: u.created_at,
+                    mc_id: u.mc_id,
+                    disc_id: u.disc_id.map(snowflake_from_db),
+                    public_portfolio: u.public_portfolio,
+                    privacy: u.privacy.parse().unwrap_or_default(),
+                    closed_at: u.closed_at,
+                };
+
+                Ok(Some(info))
+            }
+            Ok(None) => Ok(None),
+            Err(err) => Err(unspecified(err)),
+        })
+        .query("user_info", self.slow_query)
+    }
+
+    fn set_privacy(
+        &self,
+        id: &Uuid,
+        privacy: Privacy,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            let res = sqlx::query!(
+                "UPDATE users SET privacy = $2 WHERE user_id = $1",
+                id,
+                privacy.as_str()
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            ensure!(res.rows_affected() == 1, AccountNotFoundSnafu { id: *id });
+
+            Ok(())
+        }
+        .query("set_privacy", self.slow_query)
+    }
+
+    fn set_public_portfolio(
+        &self,
+        id: &Uuid,
+        public: bool,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            let res = sqlx::query!(
+                "UPDATE users SET public_portfolio = $2 WHERE user_id = $1",
+                id,
+                public
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            ensure!(res.rows_affected() == 1, AccountNotFoundSnafu { id: *id });
+
+            Ok(())
+        }
+        .query("set_public_portfolio", self.slow_query)
+    }
+
+    fn set_receipts(
+        &self,
+        id: &Uuid,
+        enabled: bool,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            let res = sqlx::query!(
+                "UPDATE users SET receipts = $2 WHERE user_id = $1",
+                id,
+                enabled
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            ensure!(res.rows_affected() == 1, AccountNotFoundSnafu { id: *id });
+
+            Ok(())
+        }
+        .query("set_receipts", self.slow_query)
+    }
+
+    fn identities(
+        &self,
+        ids: &[Uuid],
+    ) -> impl Future<Output = super::Result<Vec<(Uuid, Option<NonZeroU64>, Option<Uuid>)>>> + Send
+    {
+        sqlx::query!(
+            "SELECT user_id, disc_id, mc_id FROM users WHERE user_id = ANY($1)",
+            ids
+        )
+        .fetch_all(&self.pool)
+        .map(|res| match res {
+            Ok(rows) => Ok(rows
+                .into_iter()
+                .map(|v| (v.user_id, v.disc_id.map(snowflake_from_db), v.mc_id))
+                .collect()),
+            Err(err) => Err(unspecified(err)),
+        })
+        .query("identities", self.slow_query)
+    }
+
+    fn register_user(
+        &self,
+        disc_id: Option<NonZeroU64>,
+        mc_id: Option<&uuid::Uuid>,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Registered>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+            let disc_id_db = disc_id.map(snowflake_to_db);
+
+            // Racing registrations wait on each other here, and all but one insert nothing
+            let inserted = sqlx::query_scalar!(
+                "INSERT INTO users (disc_id, mc_id) VALUES ($1, $2)
+                 ON CONFLICT DO NOTHING
+                 RETURNING user_id",
+                disc_id_db,
+                mc_id
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            let Some(id) = inserted else {
+                let existing = sqlx::query_scalar!(
+                    "SELECT user_id FROM users WHERE disc_id = $1 OR mc_id = $2",
+                    disc_id_db,
+                    mc_id
+                )
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(unspecified)?;
+
+                return match existing.as_slice() {
+                    [id] => Ok(Registered::Existing(*id)),
+                    [] => Err(Error::Unspecified),
+                    _ => Err(Error::AlreadyLinked),
+                };
+            };
+
+            let entry = NewAuditEntry {
+                actor: *actor,
+                action: Action::Register,
+                target: Some(id.to_string()),
+                details: serde_json::json!({ "disc_id": disc_id, "mc_id": mc_id }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(Registered::New(id))
+        }
+        .query("register_user", self.slow_query)
+    }
+
+    fn create_link_code(
+        &self,
+        id: &Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<LinkCode>> + Send {
+        /// How many codes are generated before giving up, should each already be taken
+        const ATTEMPTS: usize = 3;
+
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            sqlx::query!(
+                "DELETE FROM link_codes WHERE user_id = $1 AND used_at IS NULL",
+                id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            for _ in 0..ATTEMPTS {
+                let code = LinkCode::generate();
+
+                // Nothing is inserted for closed accounts, and taken codes are skipped
+                let inserted = sqlx::query!(
+                    "INSERT INTO link_codes (code, user_id, expires_at)
+                    SELECT $1, user_id, $3 FROM users WHERE user_id = $2 AND closed_at IS NULL
+                    ON CONFLICT (code) DO NOTHING",
+                    code.as_str(),
+                    id,
+                    expires_at
+                )
+                .execute(&mut *tx)
+                .await
+                .map_err(unspecified)?
+                .rows_affected();
+
+                if inserted == 1 {
+                    tx.commit().await.map_err(unspecified)?;
+                    return Ok(code);
+                }
+
+                let open = sqlx::query_scalar!(
+                    r#"SELECT EXISTS (SELECT 1 FROM users WHERE user_id = $1 AND closed_at IS NULL)
+                        as "open!""#,
+                    id
+                )
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(unspecified)?;
+                ensure!(open, AccountNotFoundSnafu { id: *id });
+            }
+
+            tracing::error!("every generated link code was already taken");
+            Err(Error::Unspecified)
+        }
+        .query("create_link_code", self.slow_query)
+    }
+
+    fn consume_link_code(
+        &self,
+        code: &LinkCode,
+        identity: Identity,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Uuid>> + Send {
+        async move {
+            let (disc_id, mc_id, actor) = match identity {
+                Identity::Discord(disc_id) => (Some(disc_id), None, Actor::Discord(disc_id)),
+                Identity::Minecraft(mc_id) => (None, Some(mc_id), Actor::Minecraft(mc_id)),
+                // An account can't be linked to another one
+                Identity::Internal(_) => return AlreadyLinkedSnafu.fail(),
+            };
+
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            // A second use of the same code waits on this row, then finds it used
+            let id = sqlx::query_scalar!(
+                "UPDATE link_codes SET used_at = $2
+                WHERE code = $1 AND used_at IS NULL AND expires_at > $2
+                RETURNING user_id",
+                code.as_str(),
+                now
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .ok_or(Error::InvalidLinkCode)?;
+
+            // Only fills in the missing identity, so linking never replaces an existing one
+            let res = sqlx::query!(
+                "UPDATE users SET disc_id = COALESCE(disc_id, $2), mc_id = COALESCE(mc_id, $3)
+                WHERE user_id = $1 AND closed_at IS NULL
+                    AND ($2::BIGINT IS NULL OR disc_id IS NULL)
+                    AND ($3::UUID IS NULL OR mc_id IS NULL)",
+                id,
+                disc_id.map(snowflake_to_db),
+                mc_id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|err| {
+                if err
+                    .as_database_error()
+                    .is_some_and(sqlx::error::DatabaseError::is_unique_violation)
+                {
+                    Error::AlreadyLinked
+                } else {
+                    unspecified(err)
+                }
+            })?;
+            ensure!(res.rows_affected() == 1, AlreadyLinkedSnafu);
+
+            let entry = NewAuditEntry {
+                actor,
+                action: Action::LinkIdentity,
+                target: Some(id.to_string()),
+                details: serde_json::json!({ "disc_id": disc_id, "mc_id": mc_id }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(id)
+        }
+        .query("consume_link_code", self.slow_query)
+    }
+
+    fn purge_link_codes(
+        &self,
+        before: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<u64>> + Send {
+        sqlx::query!("DELETE FROM link_codes WHERE expires_at < $1", before)
+            .execute(&self.pool)
+            .map_ok(|res| res.rows_affected())
+            .map_err(unspecified)
+            .query("purge_link_codes", self.slow_query)
+    }
+
+    fn create_api_key(&self, id: &Uuid) -> impl Future<Output = super::Result<ApiKey>> + Send {
+        async move {
+            let key = ApiKey::generate();
+            let hash = key.hash();
+
+            // Nothing is stored for closed accounts, and an account's old key is replaced
+            let stored = sqlx::query!(
+                "INSERT INTO api_keys (user_id, key_hash)
+                SELECT user_id, $2 FROM users WHERE user_id = $1 AND closed_at IS NULL
+                ON CONFLICT (user_id) DO UPDATE
+                    SET key_hash = EXCLUDED.key_hash, created_at = timezone('utc', now())",
+                id,
+                hash.as_slice()
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(unspecified)?
+            .rows_affected();
+            ensure!(stored == 1, AccountNotFoundSnafu { id: *id });
+
+            Ok(key)
+        }
+        .query("create_api_key", self.slow_query)
+    }
+
+    fn api_key_owner(
+        &self,
+        key: &ApiKey,
+    ) -> impl Future<Output = super::Result<Option<Uuid>>> + Send {
+        let hash = key.hash();
+
+        async move {
+            sqlx::query_scalar!(
+                "SELECT users.user_id FROM api_keys JOIN users ON users.user_id = api_keys.user_id
+                WHERE key_hash = $1 AND closed_at IS NULL",
+                hash.as_slice()
+            )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(unspecified)
+        }
+        .query("api_key_owner", self.slow_query)
+    }
+
+    fn close_account(
+        &self,
+        id: &Uuid,
+        payout: Option<&Address>,
+        force: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<ClosedAccount>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let links = sqlx::query!(
+                "SELECT disc_id, mc_id FROM users
+                WHERE user_id = $1 AND closed_at IS NULL AND NOT system
+                FOR UPDATE",
+                id
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .context(AccountNotFoundSnafu { id: *id })?;
+
+            let (cancelled, liquidated) = if force {
+                let cancelled = cancel_open_orders(&mut tx, id).await?;
+                (cancelled, liquidate_holdings(&mut tx, id).await?)
+            } else {
+                (Vec::new(), Decimal::ZERO)
+            };
+
+            let remaining = sqlx::query!(
+                r#"SELECT balance,
+                    (SELECT COUNT(*) FROM orders
+                        WHERE user_id = $1 AND status IN ('queued', 'open'))
+                        as "open_orders!",
+                    (SELECT COUNT(*) FROM holdings
+                        WHERE user_id = $1 AND (shares > 0 OR escrow > 0)) as "holdings!"
+                FROM users WHERE user_id = $1"#,
+                id
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            let unpaid = if payout.is_some() {
+                Decimal::ZERO
+            } else {
+                remaining.balance
+            };
+            ensure!(
+                remaining.open_orders == 0 && remaining.holdings == 0 && unpaid.is_zero(),
+                AccountNotEmptySnafu {
+                    open_orders: remaining.open_orders.cast_unsigned(),
+                    holdings: remaining.holdings.cast_unsigned(),
+                    balance: unpaid,
+                }
+            );
+
+            let withdrawal = match payout {
+                Some(address) if remaining.balance > Decimal::ZERO => {
+                    Some(hold_withdrawal(&mut tx, id, remaining.balance, address, actor).await?)
+                }
+                _ => None,
+            };
+
+            sqlx::query!(
+                "DELETE FROM holdings WHERE user_id = $1 AND shares = 0 AND escrow = 0",
+                id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            sqlx::query!(
+                "UPDATE users SET disc_id = NULL, mc_id = NULL, closed_at = timezone('utc', now())
+                WHERE user_id = $1",
+                id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            let closed = ClosedAccount {
+                disc_id: links.disc_id.map(snowflake_from_db),
+                mc_id: links.mc_id,
+                cancelled,
+                liquidated,
+                withdrawal,
+            };
+
+            let entry = NewAuditEntry {
+                actor: *actor,
+                action: Action::CloseAccount,
+                target: Some(id.to_string()),
+                details: serde_json::json!({
+                    "disc_id": closed.disc_id,
+                    "mc_id": closed.mc_id,
+                    "force": force,
+                    "cancelled": closed.cancelled.len(),
+                    "liquidated": closed.liquidated,
+                    "withdrawal": closed.withdrawal.map(|v| v.id),
+                }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+            audit_unlinks(
+                &mut tx,
+                id,
+                closed.disc_id,
+                closed.mc_id,
+                Action::CloseAccount,
+                actor,
+            )
+            .await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(closed)
+        }
+        .query("close_account", self.slow_query)
+    }
+
+    fn merge_accounts(
+        &self,
+        survivor: &Uuid,
+        casualty: &Uuid,
+        dry_run: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<AccountMerge>> + Send {
+        async move {
+            ensure!(
+                survivor != casualty,
+                MergeConflictSnafu {
+                    reason: MergeConflict::SameAccount
+                }
+            );
+
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            // Locked in a consistent order, so merges running the other way round can't deadlock
+            let accounts = sqlx::query!(
+                "SELECT user_id, disc_id, mc_id, balance FROM users
+                WHERE user_id IN ($1, $2) AND closed_at IS NULL AND NOT system
+                ORDER BY user_id
+                FOR UPDATE",
+                survivor,
+                casualty
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            let find = |id: &Uuid| {
+                accounts
+                    .iter()
+                    .find(|row| row.user_id == *id)
+                    .context(AccountNotFoundSnafu { id: *id })
+            };
+            let (kept, merged) = (find(survivor)?, find(casualty)?);
+
+            let conflict = if kept.disc_id.is_some() && merged.disc_id.is_some() {
+                Some(MergeConflict::BothDiscord)
+            } else if kept.mc_id.is_some() && merged.mc_id.is_some() {
+                Some(MergeConflict::BothMinecraft)
+            } else {
+                None
+            };
+            if let Some(reason) = conflict {
+                return MergeConflictSnafu { reason }.fail();
+            }
+
+            let pending = sqlx::query!(
+                r#"SELECT
+                    EXISTS (SELECT 1 FROM orders
+                        WHERE user_id = $1 AND status IN ('queued', 'open')) as "open_orders!",
+                    EXISTS (SELECT 1 FROM withdrawal_requests
+                        WHERE user_id = $1 AND status = 'pending') as "withdrawals!""#,
+                casualty
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            ensure!(
+                !pending.open_orders,
+                MergeConflictSnafu {
+                    reason: MergeConflict::OpenOrders
+                }
+            );
+            ensure!(
+                !pending.withdrawals,
+                MergeConflictSnafu {
+                    reason: MergeConflict::PendingWithdrawal
+                }
+            );
+
+            let mut merge = AccountMerge {
+                survivor: *survivor,
+                casualty: *casualty,
+                balance: merged.balance,
+                holdings: move_holdings(&mut tx, survivor, casualty).await?,
+                ledger_entries: 0,
+                trades: 0,
+                stocks: 0,
+                disc_id: merged.disc_id.map(snowflake_from_db),
+                mc_id: merged.mc_id,
+            };
+
+            move_history(&mut tx, &mut merge).await?;
+
+            hand_over_account(&mut tx, &merge).await?;
+
+            audit_merge(&mut tx, &merge, actor).await?;
+
+            if dry_run {
+                tx.rollback().await.map_err(unspecified)?;
+            } else {
+                tx.commit().await.map_err(unspecified)?;
+            }
+
+            Ok(merge)
+        }
+        .query("merge_accounts", self.slow_query)
+    }
+
+    fn ensure_system_account(&self, id: &Uuid) -> impl Future<Output = super::Result<bool>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let created = sqlx::query!(
+                "INSERT INTO users (user_id, system) VALUES ($1, TRUE) ON CONFLICT DO NOTHING",
+                id
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .rows_affected()
+                == 1;
+
+            if created {
+                let entry = NewAuditEntry {
+                    actor: Actor::System,
+                    action: Action::Register,
+                    target: Some(id.to_string()),
+                    details: serde_json::json!({ "system": true }),
+                };
+                insert_audit(&mut tx, &entry).await?;
+            }
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(created)
+        }
+        .query("ensure_system_account", self.slow_query)
+    }
+
+    fn ping(&self) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            sqlx::query!("SELECT 1 as one")
+                .fetch_one(&self.pool)
+                .await
+                .map_err(unspecified)?;
+
+            Ok(())
+        }
+        .query("ping", self.slow_query)
+    }
+
+    fn pool_usage(&self) -> Option<PoolUsage> {
+        Some(PoolUsage {
+            size: self.pool.size(),
+            idle: u32::try_from(self.pool.num_idle()).unwrap_or(u32::MAX),
+            max: self.pool.options().get_max_connections(),
+        })
+    }
+
+    fn schema_version(&self) -> impl Future<Output = super::Result<Option<i64>>> + Send {
+        async move {
+            sqlx::query_scalar!("SELECT MAX(version) FROM _sqlx_migrations WHERE success")
+                .fetch_one(&self.pool)
+                .await
+                .map_err(unspecified)
+        }
+        .query("schema_version", self.slow_query)
+    }
+
+    fn snapshot_chunk(
+        &self,
+        section: SnapshotSection,
+        after: Option<&SnapshotRecord>,
+        limit: i64,
+    ) -> impl Future<Output = super::Result<Vec<SnapshotRecord>>> + Send {
+        async move {
+            let mut tx = self
+                .pool
+                .begin_with("BEGIN ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+                .await
+                .map_err(unspecified)?;
+
+            let records = match (section, after) {
+                (SnapshotSection::Users, Some(SnapshotRecord::User(user))) => {
+                    snapshot_users(&mut tx, Some(user.id), limit).await
+                }
+                (SnapshotSection::Users, _) => snapshot_users(&mut tx, None, limit).await,
+                (SnapshotSection::Stocks, Some(SnapshotRecord::Stock(stock))) => {
+                    snapshot_stocks(&mut tx, Some(&stock.ticker), limit).await
+                }
+                (SnapshotSection::Stocks, _) => snapshot_stocks(&mut tx, None, limit).await,
+                (SnapshotSection::Holdings, Some(SnapshotRecord::Holding(holding))) => {
+                    let after = Some((holding.user, &holding.ticker));
+                    snapshot_holdings(&mut tx, after, limit).await
+                }
+                (SnapshotSection::Holdings, _) => snapshot_holdings(&mut tx, None, limit).await,
+                (SnapshotSection::Orders, Some(SnapshotRecord::Order(order))) => {
+                    snapshot_orders(&mut tx, Some(order.order.id), limit).await
+                }
+                (SnapshotSection::Orders, _) => snapshot_orders(&mut tx, None, limit).await,
+                (SnapshotSection::Prices, Some(SnapshotRecord::Price(price))) => {
+                    snapshot_prices(&mut tx, Some(&price.ticker), limit).await
+                }
+                (SnapshotSection::Prices, _) => snapshot_prices(&mut tx, None, limit).await,
+            }?;
+
+            tx.commit().await.map_err(unspecified)?;
+            Ok(records)
+        }
+        .query("snapshot_chunk", self.slow_query)
+    }
+
+    fn restore_snapshot(
+        &self,
+        records: &[SnapshotRecord],
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            for record in records {
+                restore_record(&mut tx, record).await?;
+            }
+
+            if records
+                .iter()
+                .any(|record| matches!(record, SnapshotRecord::Order(_)))
+            {
+                sqlx::query_scalar!(
+                    "SELECT setval('orders_order_id_seq', (SELECT MAX(order_id) FROM orders))"
+                )
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(unspecified)?;
+            }
+
+            tx.commit().await.map_err(unspecified)?;
+            Ok(())
+        }
+        .query("restore_snapshot", self.slow_query)
+    }
+
+    fn exchange_counts(&self) -> impl Future<Output = super::Result<ExchangeCounts>> + Send {
+        async move {
+            let counts = sqlx::query!(
+                r#"SELECT
+                    (SELECT COUNT(*) FROM users
+                        WHERE closed_at IS NULL AND (disc_id IS NOT NULL OR mc_id IS NOT NULL))
+                        as "accounts!",
+                    (SELECT COUNT(*) FROM stocks WHERE status <> 'delisted') as "stocks!""#
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            Ok(ExchangeCounts {
+                accounts: counts.accounts.cast_unsigned(),
+                stocks: counts.stocks.cast_unsigned(),
+            })
+        }
+        .query("exchange_counts", self.slow_query)
+    }
+
+    fn get_holdings(
+        &self,
+        id: &uuid::Uuid,
+        page: &Pager,
+        order: HoldingOrdering,
+    ) -> impl Future<Output = super::Result<Option<Page<(Ticker, Shares, Option<Price>)>>>> + Send
+    {
+        struct StockValues {
+            pub ticker: String,
+            pub shares: Shares,
+            pub price: Option<Price>,
+            pub total: i64,
+        }
+        async move {
+            let res = sqlx::query_as!(
+                StockValues,
+                r#"SELECT holdings.ticker, holdings.shares as "shares: Shares",
+                    latest.price as "price?: Price",
+                    COUNT(*) OVER () as "total!"
+                FROM holdings LEFT JOIN latest_prices latest ON latest.ticker = holdings.ticker
+                WHERE user_id = $1
+                ORDER BY
+                    CASE $4 WHEN 'shares' THEN holdings.shares END DESC,
+                    CASE $4 WHEN 'value' THEN holdings.shares * latest.price END DESC NULLS LAST,
+                    holdings.ticker
+                LIMIT $2 OFFSET $3"#,
+                id,
+                page.limit(),
+                page.offset(),
+                order.as_str()
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_or_else(
+                |err| match err {
+                    sqlx::Error::RowNotFound => Ok(None),
+                    err => Err(unspecified(err)),
+                },
+                |v| Ok(Some(v)),
+            )?
+            .unwrap_or_default();
+
+            let num = page_total(
+                res.first().map(|v| v.total),
+                page,
+                sqlx::query_scalar!("SELECT COUNT(*) FROM holdings WHERE user_id = $1", id)
+                    .fetch_one(&self.pool),
+            )
+            .await?;
+
+            let res: Vec<_> = res
+                .into_iter()
+                .filter_map(|v| {
+                    let ticker = Ticker::try_from(v.ticker.as_str());
+
+                    match ticker {
+                        Ok(ticker) => Some((ticker, v.shares, v.price)),
+                        Err(_) => None,
+                    }
+                })
+                .collect();
+
+            Ok(Some(Page::new(res, num, page)))
+        }
+        .query("get_holdings", self.slow_query)
+    }
+
+    fn get_holdings_pl(
+        &self,
+        id: &uuid::Uuid,
+        page: &Pager,
+        order: HoldingOrdering,
+    ) -> impl Future<Output = super::Result<Option<Page<HoldingPl>>>> + Send {
+        struct StockValues {
+            pub ticker: String,
+            pub shares: Shares,
+            pub avg_cost: Option<Decimal>,
+            pub price: Option<Price>,
+            pub total: i64,
+        }
+        async move {
+            let res = sqlx::query_as!(
+                StockValues,
+                r#"SELECT holdings.ticker, holdings.shares as "shares: Shares", holdings.avg_cost,
+                    latest.price as "price?: Price", COUNT(*) OVER () as "total!"
+                FROM holdings LEFT JOIN latest_prices latest ON latest.ticker = holdings.ticker
+                WHERE user_id = $1
+                ORDER BY
+                    CASE $4 WHEN 'shares' THEN holdings.shares END DESC,
+                    CASE $4 WHEN 'value' THEN holdings.shares * latest.price END DESC NULLS LAST,
+                    holdings.ticker
+                LIMIT $2 OFFSET $3"#,
+                id,
+                page.limit(),
+                page.offset(),
+                order.as_str()
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            let num = page_total(
+                res.first().map(|v| v.total),
+                page,
+                sqlx::query_scalar!("SELECT COUNT(*) FROM holdings WHERE user_id = $1", id)
+                    .fetch_one(&self.pool),
+            )
+            .await?;
+
+            let res: Vec<_> = res
+                .into_iter()
+                .filter_map(|v| {
+                    Some(HoldingPl {
+                        ticker: Ticker::try_from(v.ticker.as_str()).ok()?,
+                        shares: v.shares,
+                        avg_cost: v.avg_cost,
+                        price: v.price,
+                    })
+                })
+                .collect();
+
+            Ok(Some(Page::new(res, num, page)))
+        }
+        .query("get_holdings_pl", self.slow_query)
+    }
+
+    fn holdings_value(
+        &self,
+        id: &uuid::Uuid,
+    ) -> impl Future<Output = super::Result<Decimal>> + Send {
+        async move {
+            let values = portfolio_values(&self.pool, std::slice::from_ref(id))
+                .await
+                .map_err(unspecified)?;
+
+            Ok(values.first().map(|&(_, value)| value).unwrap_or_default())
+        }
+        .query("holdings_value", self.slow_query)
+    }
+
+    fn portfolio_values(
+        &self,
+        ids: &[Uuid],
+    ) -> impl Future<Output = super::Result<Vec<(Uuid, Decimal)>>> + Send {
+        portfolio_values(&self.pool, ids)
+            .map_err(unspecified)
+            .query("portfolio_values", self.slow_query)
+    }
+
+    fn account_summary(
+        &self,
+        id: &Uuid,
+        recent: u32,
+    ) -> impl Future<Output = super::Result<AccountSummary>> + Send {
+        async move {
+            let totals = sqlx::query!(
+                r#"SELECT COUNT(*) as "stocks_held!",
+                    (SELECT COUNT(*) FROM orders
+                        WHERE user_id = $1 AND status IN ('queued', 'open'))
+                        as "open_orders!"
+                FROM holdings WHERE user_id = $1"#,
+                id
+            )
+            .fetch_one(&self.pool);
+            let values = portfolio_values(&self.pool, std::slice::from_ref(id));
+
+            // Trades never touch the ledger, so are merged in with the same sign convention
+            let recent = sqlx::query!(
+                r#"SELECT time as "time!", kind as "kind!", delta as "delta!", ticker
+                FROM (
+                    (SELECT time, kind, delta, ticker FROM ledger
+                    WHERE user_id = $1 ORDER BY ledger_id DESC LIMIT $2)
+                    UNION ALL
+                    (SELECT time,
+                        CASE WHEN buyer_id = $1 THEN 'buy' ELSE 'sell' END,
+                        CASE WHEN buyer_id = $1 THEN -(price * shares + fee)
+                            ELSE price * shares END,
+                        ticker
+                    FROM stock_events
+                    WHERE (buyer_id = $1 OR seller_id = $1) AND shares > 0
+                    ORDER BY event_id DESC LIMIT $2)
+                ) recent (time, kind, delta, ticker)
+                ORDER BY time DESC LIMIT $2"#,
+                id,
+                i64::from(recent)
+            )
+            .fetch_all(&self.pool);
+
+            let (totals, values, recent) =
+                tokio::try_join!(totals, values, recent).map_err(unspecified)?;
+
+            Ok(AccountSummary {
+                holdings_value: values.first().map(|&(_, value)| value).unwrap_or_default(),
+                stocks_held: totals.stocks_held.try_into().unwrap_or(u32::MAX),
+                open_orders: totals.open_orders.try_into().unwrap_or_default(),
+                recent: recent
+                    .into_iter()
+                    .filter_map(|row| {
+                        Some(Transaction {
+                            time: row.time,
+                            kind: row.kind.parse().ok()?,
+                            delta: row.delta,
+                            ticker: match row.ticker {
+                                Some(ticker) => Some(Ticker::try_from(ticker.as_str()).ok()?),
+                                None => None,
+                            },
+                        })
+                    })
+                    .collect(),
+            })
+        }
+        .query("account_summary", self.slow_query)
+    }
+
+    fn statement(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        filter: Option<LedgerKind>,
+        snapshot: Option<StatementSnapshot>,
+    ) -> impl Future<Output = super::Result<Statement>> + Send {
+        async move {
+            let snapshot = match snapshot {
+                Some(snapshot) => snapshot,
+                None => sqlx::query!(
+                    r#"SELECT COALESCE((SELECT MAX(ledger_id) FROM ledger), 0) as "ledger!",
+                        COALESCE((SELECT MAX(event_id) FROM stock_events), 0) as "event!""#
+                )
+                .fetch_one(&self.pool)
+                .await
+                .map(|row| StatementSnapshot {
+                    ledger: row.ledger,
+                    event: row.event,
+                })
+                .map_err(unspecified)?,
+            };
+            let kinds: Option<Vec<_>> =
+                filter.map(|filter| filter.kinds().iter().map(|kind| kind.as_str()).collect());
+
+            // The running balance is summed before filtering, so it always counts every entry.
+            // Counting in the same statement as the page keeps the total in step with it, and
+            // still yields a row for pages past the end.
+            let rows = sqlx::query!(
+                r#"WITH entries AS (
+                    SELECT time, kind, delta, ticker, 0 as source, ledger_id as id FROM ledger
+                    WHERE user_id = $1 AND ledger_id <= $2
+                    UNION ALL
+                    SELECT time, CASE WHEN buyer_id = $1 THEN 'buy' ELSE 'sell' END,
+                        CASE WHEN buyer_id = $1 THEN -(price * shares) ELSE price * shares END,
+                        ticker, 1, event_id
+                    FROM stock_events
+                    WHERE (buyer_id = $1 OR seller_id = $1) AND shares > 0 AND event_id <= $3
+                    UNION ALL
+                    SELECT time, 'fee', -fee, ticker, 2, event_id FROM stock_events
+                    WHERE buyer_id = $1 AND shares > 0 AND fee > 0 AND event_id <= $3
+                ), running AS (
+                    SELECT *, SUM(delta) OVER (
+                        ORDER BY time, source, id ROWS UNBOUNDED PRECEDING
+                    ) as balance
+                    FROM entries
+                ), listed AS (
+                    SELECT * FROM running WHERE $4::TEXT[] IS NULL OR kind = ANY($4)
+                )
+                SELECT total.count as "total!", listed.time as "time?", listed.kind as "kind?",
+                    listed.delta as "delta?", listed.ticker as "ticker?",
+                    listed.balance as "balance?"
+                FROM (SELECT COUNT(*) FROM listed) total
+                LEFT JOIN LATERAL (
+                    SELECT * FROM listed ORDER BY time DESC, source DESC, id DESC
+                    LIMIT $5 OFFSET $6
+                ) listed ON TRUE"#,
+                id,
+                snapshot.ledger,
+                snapshot.event,
+                kinds.as_deref() as Option<&[&str]>,
+                page.limit(),
+                page.offset()
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            let total = rows.first().map_or(0, |row| row.total.cast_unsigned());
+            let items = rows
+                .into_iter()
+                .filter_map(|row| {
+                    Some(StatementEntry {
+                        transaction: Transaction {
+                            time: row.time?,
+                            kind: row.kind?.parse().ok()?,
+                            delta: row.delta?,
+                            ticker: match row.ticker {
+                                Some(ticker) => Some(Ticker::try_from(ticker.as_str()).ok()?),
+                                None => None,
+                            },
+                        },
+                        balance: row.balance?,
+                    })
+                })
+                .collect();
+
+            Ok(Statement {
+                page: Page::new(items, total, page),
+                snapshot,
+            })
+        }
+        .query("statement", self.slow_query)
+    }
+
+    fn trade_history(
+        &self,
+        id: &Uuid,
+        after: Option<i32>,
+        limit: i64,
+    ) -> impl Future<Output = super::Result<Vec<UserTrade>>> + Send {
+        async move {
+            let rows = sqlx::query!(
+                r#"SELECT event_id, time, ticker, price, shares, buyer_id = $1 AS "is_buy!",
+                    CASE WHEN buyer_id = $1 THEN fee ELSE 0 END AS "fee!",
+                    CASE WHEN buyer_id = $1 THEN NULL ELSE realized_pl END AS realized_pl
+                FROM stock_events
+                WHERE (buyer_id = $1 OR seller_id = $1) AND event_id > $2 AND shares > 0
+                ORDER BY event_id LIMIT $3"#,
+                id,
+                after.unwrap_or_default(),
+                limit
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            Ok(rows
+                .into_iter()
+                .filter_map(|row| {
+                    Some(UserTrade {
+                        id: row.event_id,
+                        time: row.time,
+                        ticker: Ticker::try_from(row.ticker.as_str()).ok()?,
+                        side: if row.is_buy { Side::Buy } else { Side::Sell },
+                        price: Price::try_from(row.price).ok()?,
+                        shares: Shares::try_from(row.shares).ok()?,
+                        fee: row.fee,
+                        realized_pl: row.realized_pl,
+                    })
+                })
+                .collect())
+        }
+        .query("trade_history", self.slow_query)
+    }
+
+    fn ticker_trades(
+        &self,
+        ticker: &Ticker,
+        page: &Pager,
+    ) -> impl Future<Output = super::Result<Page<TickerTrade>>> + Send {
+        async move {
+            let rows = sqlx::query!(
+                r#"SELECT event_id, time, price, shares, buyer_id, seller_id,
+                    COUNT(*) OVER () as "total!"
+                FROM stock_events WHERE ticker = $1 AND shares > 0
+                ORDER BY time DESC, event_id DESC LIMIT $2 OFFSET $3"#,
+                ticker.as_str(),
+                page.limit(),
+                page.offset()
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            let total = page_total(
+                rows.first().map(|row| row.total),
+                page,
+                sqlx::query_scalar!(
+                    "SELECT COUNT(*) FROM stock_events WHERE ticker = $1 AND shares > 0",
+                    ticker.as_str()
+                )
+                .fetch_one(&self.pool),
+            )
+            .await?;
+
+            let trades = rows
+                .into_iter()
+                .filter_map(|row| {
+                    Some(TickerTrade {
+                        id: row.event_id,
+                        time: row.time,
+                        price: Price::try_from(row.price).ok()?,
+                        shares: Shares::try_from(row.shares).ok()?,
+                        buyer: row.buyer_id,
+                        seller: row.seller_id,
+                    })
+                })
+                .collect();
+
+            Ok(Page::new(trades, total, page))
+        }
+        .query("ticker_trades", self.slow_query)
+    }
+
+    fn top_movers(
+        &self,
+        date: NaiveDate,
+        until: DateTime<Utc>,
+        count: u8,
+    ) -> impl Future<Output = super::Result<Movers>> + Send {
+        async move {
+            let rows = sqlx::query!(
+                r#"WITH prices AS (
+                    SELECT stocks.ticker,
+                        (SELECT price FROM stock_events
+                            WHERE ticker = stocks.ticker AND time < $3
+                            ORDER BY time DESC, event_id DESC LIMIT 1) AS last,
+                        COALESCE(
+                            (SELECT close FROM daily_closes
+                                WHERE ticker = stocks.ticker AND date < $1
+                                ORDER BY date DESC LIMIT 1),
+                            (SELECT price FROM stock_events
+                                WHERE ticker = stocks.ticker AND time <= $3 - INTERVAL '1 day'
+                                ORDER BY time DESC, event_id DESC LIMIT 1),
+                            (SELECT price FROM stock_events
+                                WHERE ticker = stocks.ticker AND time < $3
+                                ORDER BY time, event_id LIMIT 1)
+                        ) AS base
+                    FROM stocks
+                ), changes AS (
+                    SELECT ticker, last, (last - base) / base * 100 AS pct
+                    FROM prices WHERE last IS NOT NULL AND last <> base
+                ), ranked AS (
+                    SELECT ticker, last, pct,
+                        ROW_NUMBER() OVER (ORDER BY pct DESC, ticker) AS gain_rank,
+                        ROW_NUMBER() OVER (ORDER BY pct, ticker) AS loss_rank
+                    FROM changes
+                )
+                SELECT ticker as "ticker!", last as "last!", pct as "pct!" FROM ranked
+                WHERE (pct > 0 AND gain_rank <= $2) OR (pct < 0 AND loss_rank <= $2)
+                ORDER BY pct DESC"#,
+                date,
+                i64::from(count),
+                until
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            let mut movers = Movers::default();
+
+            for row in rows {
+                let mover = Mover {
+                    ticker: Ticker::try_from(row.ticker.as_str())
+                        .map_err(|_| Error::Unspecified)?,
+                    last_price: row.last,
+                    pct_change: row.pct,
+                };
+
+                if mover.pct_change.is_sign_positive() {
+                    movers.gainers.push(mover);
+                } else {
+                    movers.losers.push(mover);
+                }
+            }
+
+            // Rows come biggest rise first, so the biggest fall is at the end
+            movers.losers.reverse();
+
+            Ok(movers)
+        }
+        .query("top_movers", self.slow_query)
+    }
+
+    fn daily_summary(
+        &self,
+        date: NaiveDate,
+    ) -> impl Future<Output = super::Result<DailySummary>> + Send {
+        async move {
+            let start = date.and_time(NaiveTime::MIN).and_utc();
+            let end = start + TimeDelta::days(1);
+
+            let row = sqlx::query!(
+                r#"SELECT totals.trades as "trades!", totals.volume as "volume!",
+                    biggest.ticker as "ticker?", biggest.price as "price?",
+                    biggest.shares as "shares?", biggest.time as "time?"
+                FROM (
+                    SELECT COUNT(*) AS trades, COALESCE(SUM(price * shares), 0) AS volume
+                    FROM stock_events WHERE time >= $1 AND time < $2 AND shares > 0
+                ) totals
+                LEFT JOIN LATERAL (
+                    SELECT ticker, price, shares, time FROM stock_events
+                    WHERE time >= $1 AND time < $2 AND shares > 0
+                    ORDER BY price * shares DESC, event_id LIMIT 1
+                ) biggest ON TRUE"#,
+                start,
+                end
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            let biggest_trade = match (row.ticker, row.price, row.shares, row.time) {
+                (Some(ticker), Some(price), Some(shares), Some(time)) => Some(Trade {
+                    ticker: Ticker::try_from(ticker.as_str()).map_err(|_| Error::Unspecified)?,
+                    price,
+                    shares: shares.try_into().map_err(|_| Error::Unspecified)?,
+                    time,
+                }),
+                _ => None,
+            };
+
+            let movers = self.top_movers(date, end, 3).await?;
+
+            Ok(DailySummary {
+                date,
+                trades: row.trades.try_into().map_err(|_| Error::Unspecified)?,
+                volume: row.volume,
+                biggest_trade,
+                movers,
+            })
+        }
+        .query("daily_summary", self.slow_query)
+    }
+
+    fn summary_sent(&self, date: NaiveDate) -> impl Future<Output = super::Result<bool>> + Send {
+        sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM daily_summaries WHERE date = $1) as "sent!""#,
+            date
+        )
+        .fetch_one(&self.pool)
+        .map_err(unspecified)
+        .query("summary_sent", self.slow_query)
+    }
+
+    fn record_summary_sent(
+        &self,
+        date: NaiveDate,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        sqlx::query!(
+            "INSERT INTO daily_summaries (date) VALUES ($1) ON CONFLICT (date) DO NOTHING",
+            date
+        )
+        .execute(&self.pool)
+        .map_ok(|_| ())
+        .map_err(unspecified)
+        .query("record_summary_sent", self.slow_query)
+    }
+
+    fn record_daily_closes(
+        &self,
+        date: NaiveDate,
+    ) -> impl Future<Output = super::Result<u64>> + Send {
+        let start = date.and_time(NaiveTime::MIN).and_utc();
+        let end = start + TimeDelta::days(1);
+
+        sqlx::query!(
+            "INSERT INTO daily_closes (ticker, date, close, volume)
+            SELECT stocks.ticker, $1, last.price, day.volume
+            FROM stocks
+            JOIN LATERAL (
+                SELECT price FROM stock_events
+                WHERE ticker = stocks.ticker AND time < $3
+                ORDER BY time DESC, event_id DESC LIMIT 1
+            ) last ON TRUE
+            CROSS JOIN LATERAL (
+                SELECT COALESCE(SUM(shares + COALESCE(imported_volume, 0)), 0) AS volume
+                FROM stock_events
+                WHERE ticker = stocks.ticker AND time >= $2 AND time < $3
+            ) day
+            ON CONFLICT (ticker, date) DO NOTHING",
+            date,
+            start,
+            end
+        )
+        .execute(&self.pool)
+        .map_ok(|res| res.rows_affected())
+        .map_err(unspecified)
+        .query("record_daily_closes", self.slow_query)
+    }
+
+    fn record_portfolio_snapshots(
+        &self,
+        date: NaiveDate,
+        after: Option<&Uuid>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<(Option<Uuid>, u64)>> + Send {
+        async move {
+            let accounts = sqlx::query!(
+                r#"SELECT user_id, balance + escrow as "balance!" FROM users
+                WHERE NOT system AND closed_at IS NULL AND ($1::UUID IS NULL OR user_id > $1)
+                ORDER BY user_id
+                LIMIT $2"#,
+                after,
+                i64::from(limit)
+            )
+            .fetch_all(&self.pool)
+            .await?;
+
+            let ids: Vec<_> = accounts.iter().map(|row| row.user_id).collect();
+            let balances: Vec<_> = accounts.iter().map(|row| row.balance).collect();
+            let values: Vec<_> = portfolio_values(&self.pool, &ids)
+                .await?
+                .into_iter()
+                .map(|(_, value)| value)
+                .collect();
+
+            let recorded = sqlx::query!(
+                "INSERT INTO portfolio_snapshots (user_id, date, balance, holdings_value)
+                SELECT id, $1, balance, value
+                FROM UNNEST($2::uuid[], $3::numeric[], $4::numeric[]) rows (id, balance, value)
+                ON CONFLICT (user_id, date) DO NOTHING",
+                date,
+                &ids,
+                &balances,
+                &values
+            )
+            .execute(&self.pool)
+            .await?
+            .rows_affected();
+
+            Ok((ids.last().copied(), recorded))
+        }
+        .map_err(unspecified)
+        .query("record_portfolio_snapshots", self.slow_query)
+    }
+
+    fn portfolio_history(
+        &self,
+        id: &Uuid,
+        since: NaiveDate,
+    ) -> impl Future<Output = super::Result<Vec<PortfolioSnapshot>>> + Send {
+        sqlx::query_as!(
+            PortfolioSnapshot,
+            "SELECT date, balance, holdings_value FROM portfolio_snapshots
+            WHERE user_id = $1 AND date >= $2
+            ORDER BY date",
+            id,
+            since
+        )
+        .fetch_all(&self.pool)
+        .map_err(unspecified)
+        .query("portfolio_history", self.slow_query)
+    }
+
+    fn price_change(
+        &self,
+        ticker: &Ticker,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Option<PriceChange>>> + Send {
+        sqlx::query!(
+            r#"SELECT latest.price as "last!",
+                (SELECT close FROM daily_closes
+                    WHERE ticker = $1 AND date < $2
+                    ORDER BY date DESC LIMIT 1) as "close?",
+                COALESCE(
+                    (SELECT price FROM stock_events
+                        WHERE ticker = $1 AND time <= $3::TIMESTAMPTZ - INTERVAL '1 day'
+                        ORDER BY time DESC, event_id DESC LIMIT 1),
+                    (SELECT price FROM stock_events
+                        WHERE ticker = $1 ORDER BY time, event_id LIMIT 1)
+                ) as "day_ago!"
+            FROM latest_prices latest WHERE latest.ticker = $1"#,
+            ticker.as_str(),
+            now.date_naive(),
+            now
+        )
+        .fetch_optional(&self.pool)
+        .map_ok(|row| {
+            row.map(|row| PriceChange {
+                last: row.last,
+                reference: row.close.unwrap_or(row.day_ago),
+                since_close: row.close.is_some(),
+            })
+        })
+        .map_err(unspecified)
+        .query("price_change", self.slow_query)
+    }
+
+    fn candles(
+        &self,
+        ticker: &Ticker,
+        interval: CandleInterval,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Vec<Candle>>> + Send {
+        sqlx::query!(
+            r#"SELECT date_bin($2::BIGINT * INTERVAL '1 second', time, 'epoch'::TIMESTAMPTZ)
+                    as "start!",
+                (array_agg(price ORDER BY time, event_id))[1] as "open!: Price",
+                MAX(price) as "high!: Price",
+                MIN(price) as "low!: Price",
+                (array_agg(price ORDER BY time DESC, event_id DESC))[1] as "close!: Price",
+                SUM(shares + COALESCE(imported_volume, 0))::BIGINT as "volume!"
+            FROM stock_events
+            WHERE ticker = $1 AND time >= $3 AND time < $4
+                AND (shares > 0 OR imported_volume IS NOT NULL)
+            GROUP BY 1 ORDER BY 1"#,
+            ticker.as_str(),
+            interval.duration().num_seconds(),
+            from,
+            to
+        )
+        .fetch_all(&self.pool)
+        .map_ok(|rows| {
+            rows.into_iter()
+                .map(|row| Candle {
+                    start: row.start,
+                    open: row.open,
+                    high: row.high,
+                    low: row.low,
+                    close: row.close,
+                    volume: row.volume.unsigned_abs(),
+                })
+                .collect()
+        })
+        .map_err(unspecified)
+        .query("candles", self.slow_query)
+    }
+
+    fn list_stocks(
+        &self,
+        page: &Pager,
+        order: StockOrdering,
+        prefix: &str,
+    ) -> impl Future<
+        Output = super::Result<
+            Option<
+                Page<(
+                    Ticker,
+                    Shares,
+                    Option<Price>,
+                    Option<DateTime<Utc>>,
+                    Option<String>,
+                )>,
+            >,
+        >,
+    > + Send {
+        struct StockValues {
+            pub ticker: String,
+            pub shares: Shares,
+            pub price: Option<Price>,
+            pub time: Option<DateTime<Utc>>,
+            pub name: Option<String>,
+            pub total: i64,
+        }
+
+        async move {
+            let res = sqlx::query_as!(
+                StockValues,
+                r#"SELECT stocks.ticker as "ticker!: String",
+                stocks.shares as "shares!: Shares",
+                latest.price as "price?: Price",
+                latest.updated_at as "time?",
+                stocks.name,
+                COUNT(*) OVER () as "total!"
+                FROM stocks LEFT JOIN latest_prices latest ON latest.ticker = stocks.ticker
+                LEFT JOIN LATERAL (
+                    SELECT COALESCE(SUM(shares), 0) AS volume FROM stock_events
+                    WHERE stock_events.ticker = stocks.ticker
+                        AND time > now() - INTERVAL '1 day'
+                ) day ON TRUE
+                LEFT JOIN LATERAL (
+                    SELECT COALESCE(
+                        (SELECT close FROM daily_closes
+                            WHERE ticker = stocks.ticker
+                                AND date < (now() AT TIME ZONE 'UTC')::DATE
+                            ORDER BY date DESC LIMIT 1),
+                        (SELECT price FROM stock_events
+                            WHERE ticker = stocks.ticker AND time <= now() - INTERVAL '1 day'
+                            ORDER BY time DESC, event_id DESC LIMIT 1),
+                        (SELECT price FROM stock_events
+                            WHERE ticker = stocks.ticker
+                            ORDER BY time, event_id LIMIT 1)
+                    ) AS price
+                ) base ON TRUE
+                WHERE starts_with(stocks.ticker, $3)
+                ORDER BY
+                    CASE $4 WHEN 'price' THEN latest.price END DESC NULLS LAST,
+                    CASE $4 WHEN 'volume' THEN day.volume END DESC,
+                    CASE $4 WHEN 'change' THEN (latest.price - base.price) / base.price END
+                        DESC NULLS LAST,
+                    stocks.ticker
+                LIMIT $1 OFFSET $2"#,
+                page.limit(),
+                page.offset(),
+                prefix,
+                order.as_str()
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_or_else(
+                |err| match err {
+                    sqlx::Error::RowNotFound => Ok(None),
+                    err => Err(unspecified(err)),
+                },
+                |v| Ok(Some(v)),
+            )?
+            .unwrap_or_default();
+
+            let Some(num) = res.first().map(|v| v.total.cast_unsigned()) else {
+                return Ok(None);
+            };
+
+            let res: Vec<_> = res
+                .into_iter()
+                .filter_map(|v| {
+                    let ticker = Ticker::try_from(v.ticker.as_str());
+
+                    match ticker {
+                        Ok(ticker) => Some((ticker, v.shares, v.price, v.time, v.name)),
+                        Err(_) => None,
+                    }
+                })
+                .collect();
+
+            Ok(Some(Page::new(res, num, page)))
+        }
+        .query("list_stocks", self.slow_query)
+    }
+
+    fn search_tickers(
+        &self,
+        prefix: &str,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<(Ticker, Option<String>)>>> + Send {
+        sqlx::query!(
+            "SELECT ticker, name FROM stocks WHERE starts_with(ticker, $1)
+            ORDER BY ticker LIMIT $2",
+            prefix,
+            i64::from(limit)
+        )
+        .fetch_all(&self.pool)
+        .map_ok(|rows| {
+            rows.into_iter()
+                .map(|row| {
+                    (
+                        Ticker::try_from(row.ticker.as_str()).expect("Enforced by DB"),
+                        row.name,
+                    )
+                })
+                .collect()
+        })
+        .map_err(unspecified)
+        .query("search_tickers", self.slow_query)
+    }
+
+    fn search_stocks(
+        &self,
+        query: &str,
+        page: &Pager,
+    ) -> impl Future<Output = super::Result<Page<StockMatch>>> + Send {
+        let pattern = contains_pattern(query);
+
+        async move {
+            // Containing the query outright beats being close to it, and earlier fields win ties
+            let rows = sqlx::query!(
+                r#"SELECT stocks.ticker, stocks.name, stocks.description,
+                    latest.price as "price?: Price", best.field as "field!",
+                    COUNT(*) OVER () as "total!"
+                FROM stocks
+                LEFT JOIN latest_prices latest ON latest.ticker = stocks.ticker
+                CROSS JOIN LATERAL (
+                    SELECT field, score FROM (VALUES
+                        ('ticker', 1, CASE WHEN stocks.ticker ILIKE $2 THEN 1 ELSE 0 END),
+                        ('name', 2, CASE WHEN stocks.name ILIKE $2 THEN 1
+                            ELSE COALESCE(word_similarity($1, stocks.name), 0) END),
+                        ('description', 3, CASE WHEN stocks.description ILIKE $2 THEN 1
+                            ELSE COALESCE(word_similarity($1, stocks.description), 0) END)
+                    ) fields (field, priority, score)
+                    ORDER BY score DESC, priority
+                    LIMIT 1
+                ) best
+                WHERE stocks.ticker ILIKE $2 OR stocks.name ILIKE $2
+                    OR stocks.description ILIKE $2
+                    OR $1 <% stocks.name OR $1 <% stocks.description
+                ORDER BY best.score DESC, stocks.ticker
+                LIMIT $3 OFFSET $4"#,
+                query,
+                pattern,
+                page.limit(),
+                page.offset()
+            )
+            .fetch_all(&self.pool)
+            .await?;
+
+            let total = rows.first().map_or(0, |row| row.total.cast_unsigned());
+            let items = rows
+                .into_iter()
+                .map(|row| StockMatch {
+                    ticker: Ticker::try_from(row.ticker.as_str()).expect("Enforced by DB"),
+                    name: row.name,
+                    description: row.description,
+                    price: row.price,
+                    matched: row.field.parse().expect("Set by the query"),
+                })
+                .collect();
+
+            Ok(Page::new(items, total, page))
+        }
+        .map_err(unspecified)
+        .query("search_stocks", self.slow_query)
+    }
+
+    fn list_users(
+        &self,
+        page: &Pager,
+        filter: UserFilter,
+    ) -> impl Future<Output = super::Result<Page<UserInfo>>> + Send {
+        struct UserRow {
+            pub user_id: Uuid,
+            pub available: Decimal,
+            pub held: Decimal,
+            pub created_at: DateTime<Utc>,
+            pub mc_id: Option<Uuid>,
+            pub disc_id: Option<i64>,
+            pub public_portfolio: bool,
+            pub privacy: String,
+            pub closed_at: Option<DateTime<Utc>>,
+            pub total: i64,
+        }
+
+        let links = filter.links.as_str();
+        let order = filter.order.as_str();
+
+        async move {
+            let res = sqlx::query_as!(
+                UserRow,
+                r#"SELECT users.user_id, balances.available as "available!",
+                    balances.held as "held!", created_at, mc_id, disc_id, public_portfolio,
+                    privacy, closed_at, COUNT(*) OVER () as "total!"
+                FROM users JOIN account_balances balances ON balances.user_id = users.user_id
+                WHERE CASE $1
+                    WHEN 'discord' THEN disc_id IS NOT NULL
+                    WHEN 'minecraft' THEN mc_id IS NOT NULL
+                    WHEN 'one_sided' THEN (disc_id IS NULL) <> (mc_id IS NULL)
+                    ELSE TRUE
+                END
+                ORDER BY
+                    CASE $2 WHEN 'balance' THEN balances.available + balances.held END DESC,
+                    created_at DESC,
+                    users.user_id
+                LIMIT $3 OFFSET $4"#,
+                links,
+                order,
+                page.limit(),
+                page.offset()
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            let num = page_total(
+                res.first().map(|v| v.total),
+                page,
+                sqlx::query_scalar!(
+                    "SELECT COUNT(*) FROM users
+                    WHERE CASE $1
+                        WHEN 'discord' THEN disc_id IS NOT NULL
+                        WHEN 'minecraft' THEN mc_id IS NOT NULL
+                        WHEN 'one_sided' THEN (disc_id IS NULL) <> (mc_id IS NULL)
+                        ELSE TRUE
+                    END",
+                    links
+                )
+                .fetch_one(&self.pool),
+            )
+            .await?;
+
+            let res = res
+                .into_iter()
+                .map(|u| UserInfo {
+                    id: u.user_id,
+                    balance: Some(u.available + u.held),
+                    available_balance: Some(u.available),
+                    held_balance: Some(u.held),
+                    created_at: u.created_at,
+                    mc_id: u.mc_id,
+                    disc_id: u.disc_id.map(snowflake_from_db),
+                    public_portfolio: u.public_portfolio,
+                    privacy: u.privacy.parse().unwrap_or_default(),
+                    closed_at: u.closed_at,
+                })
+                .collect();
+
+            Ok(Page::new(res, num, page))
+        }
+        .query("list_users", self.slow_query)
+    }
+
+    fn record_audit(
+        &self,
+        entry: &NewAuditEntry,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            let mut conn = self.pool.acquire().await.map_err(unspecified)?;
+            insert_audit(&mut conn, entry).await
+        }
+        .query("record_audit", self.slow_query)
+    }
+
+    fn audit_log(
+        &self,
+        page: &Pager,
+        filter: &AuditFilter,
+    ) -> impl Future<Output = super::Result<Page<AuditEntry>>> + Send {
+        struct AuditRow {
+            pub audit_id: i64,
+            pub time: DateTime<Utc>,
+            pub actor: String,
+            pub action: String,
+            pub target: Option<String>,
+            pub details: serde_json::Value,
+            pub total: i64,
+        }
+
+        let actor = filter.actor.map(|v| v.to_string());
+        let action = filter.action.map(|v| v.as_str());
+        let target = filter.target.clone();
+
+        async move {
+            let res = sqlx::query_as!(
+                AuditRow,
+                r#"SELECT audit_id, time, actor, action, target, details,
+                    COUNT(*) OVER () as "total!"
+                FROM audit_log
+                WHERE ($1::TEXT IS NULL OR actor = $1)
+                    AND ($2::TEXT IS NULL OR action = $2)
+                    AND ($3::TEXT IS NULL OR target = $3)
+                ORDER BY audit_id DESC LIMIT $4 OFFSET $5"#,
+                actor,
+                action,
+                target,
+                page.limit(),
+                page.offset()
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            let num = page_total(
+                res.first().map(|v| v.total),
+                page,
+                sqlx::query_scalar!(
+                    "SELECT COUNT(*) FROM audit_log
+                    WHERE ($1::TEXT IS NULL OR actor = $1)
+                        AND ($2::TEXT IS NULL OR action = $2)
+                        AND ($3::TEXT IS NULL OR target = $3)",
+                    actor,
+                    action,
+                    target
+                )
+                .fetch_one(&self.pool),
+            )
+            .await?;
+
+            let res: Vec<_> = res
+                .into_iter()
+                .filter_map(|v| {
+                    Some(AuditEntry {
+                        id: v.audit_id,
+                        time: v.time,
+                        actor: v.actor.parse().ok()?,
+                        action: v.action.parse().ok()?,
+                        target: v.target,
+                        details: v.details,
+                    })
+                })
+                .collect();
+
+            Ok(Page::new(res, num, page))
+        }
+        .query("audit_log", self.slow_query)
+    }
+
+    fn place_order(
+        &self,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<(Order, Vec<Fill>)>> + Send {
+        retry_conflicts(move || async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+            let placed = enter_order(&mut tx, order, fees, false, now).await?;
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(placed)
+        })
+        .query("place_order", self.slow_query)
+    }
+
+    fn queue_order(
+        &self,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Order>> + Send {
+        retry_conflicts(move || async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+            let (order, _) = enter_order(&mut tx, order, fees, true, now).await?;
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(order)
+        })
+        .query("queue_order", self.slow_query)
+    }
+
+    fn release_queued_orders(
+        &self,
+        limit: u32,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Vec<(Order, Vec<Fill>)>>> + Send {
+        retry_conflicts(move || async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let queued = sqlx::query!(
+                "SELECT order_id, ticker FROM orders WHERE status = 'queued'
+                ORDER BY order_id LIMIT $1",
+                i64::from(limit)
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            let mut released = Vec::with_capacity(queued.len());
+
+            for row in queued {
+                let ticker =
+                    Ticker::try_from(row.ticker.as_str()).map_err(|_| Error::Unspecified)?;
+
+                // Locked before the order, like placing one does. Halted stocks just don't match
+                let tradable = match lock_tradable(&mut tx, &ticker).await {
+                    Ok(()) => true,
+                    Err(Error::StockHalted { .. }) => false,
+                    Err(err) => return Err(err),
+                };
+
+                // Cancelled or expired since it was read
+                let Some(order) = sqlx::query_as!(
+                    OrderRow,
+                    r#"UPDATE orders SET status = 'open' WHERE order_id = $1 AND status = 'queued'
+                    RETURNING order_id, user_id, ticker, price, shares, remaining, type as is_buy,
+                        status, created_at, expires_at"#,
+                    row.order_id
+                )
+                .fetch_optional(&mut *tx)
+                .await
+                .map_err(unspecified)?
+                .and_then(OrderRow::into_order) else {
+                    continue;
+                };
+
+                if !tradable {
+                    released.push((order, Vec::new()));
+                    continue;
+                }
+
+                let incoming = IncomingOrder {
+                    id: order.id,
+                    user: order.user,
+                    side: order.side,
+                    price: order.price,
+                    remaining: order.remaining,
+                };
+                let fills = match_on_book(&mut tx, &ticker, &incoming, fees, now).await?;
+
+                released.push((load_order(&mut tx, order.id).await?, fills));
+            }
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(released)
+        })
+        .query("release_queued_orders", self.slow_query)
+    }
+
+    fn place_order_once(
+        &self,
+        key: &IdempotencyKey,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        since: DateTime<Utc>,
+        queue: bool,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Idempotent<(Order, Vec<Fill>)>>> + Send {
+        retry_conflicts(move || async move {
+            let hash = order_hash(order);
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            // Waits on any concurrent request holding the same key until it commits or rolls back
+            let claimed = sqlx::query_scalar!(
+                r#"INSERT INTO idempotency_keys (user_id, key, request_hash) VALUES ($1, $2, $3)
+                ON CONFLICT (user_id, key) DO UPDATE
+                    SET request_hash = EXCLUDED.request_hash, response = NULL, created_at = now()
+                    WHERE idempotency_keys.created_at < $4
+                RETURNING TRUE as "claimed!""#,
+                order.user,
+                key.as_str(),
+                hash.as_slice(),
+                since
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .is_some();
+
+            if !claimed {
+                let row = sqlx::query!(
+                    "SELECT request_hash, response FROM idempotency_keys
+                    WHERE user_id = $1 AND key = $2",
+                    order.user,
+                    key.as_str()
+                )
+                .fetch_one(&mut *tx)
+                .await
+                .map_err(unspecified)?;
+
+                ensure!(row.request_hash == hash, IdempotencyKeyReusedSnafu);
+
+                let placed = row
+                    .response
+                    .and_then(|response| serde_json::from_value(response).ok())
+                    .ok_or_else(|| {
+                        tracing::error!(key = %key, "could not decode stored response");
+                        Error::Unspecified
+                    })?;
+
+                return Ok(Idempotent::Replayed(placed));
+            }
+
+            let placed = enter_order(&mut tx, order, fees, queue, now).await?;
+
+            let response = serde_json::to_value(&placed).map_err(|err| {
+                tracing::error!(%err, "could not serialize response");
+                Error::Unspecified
+            })?;
+
+            sqlx::query!(
+                "UPDATE idempotency_keys SET response = $3 WHERE user_id = $1 AND key = $2",
+                order.user,
+                key.as_str(),
+                response
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(Idempotent::Executed(placed))
+        })
+        .query("place_order_once", self.slow_query)
+    }
+
+    fn purge_idempotency_keys(
+        &self,
+        before: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<u64>> + Send {
+        sqlx::query!("DELETE FROM idempotency_keys WHERE created_at < $1", before)
+            .execute(&self.pool)
+            .map_ok(|res| res.rows_affected())
+            .map_err(unspecified)
+            .query("purge_idempotency_keys", self.slow_query)
+    }
+
+    fn cancel_order(
+        &self,
+        id: i32,
+        user: &Uuid,
+    ) -> impl Future<Output = super::Result<Order>> + Send {
+        retry_conflicts(move || async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let order = sqlx::query_as!(
+                OrderRow,
+                r#"UPDATE orders SET status = 'cancelled'
+                WHERE order_id = $1 AND user_id = $2 AND status IN ('queued', 'open')
+                RETURNING order_id, user_id, ticker, price, shares, remaining, type as is_buy,
+                    status, created_at, expires_at"#,
+                id,
+                user
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .context(super::OrderNotFoundSnafu { id })?
+            .into_order()
+            .ok_or(Error::Unspecified)?;
+
+            release_escrow(&mut tx, &order).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(order)
+        })
+        .query("cancel_order", self.slow_query)
+    }
+
+    fn expire_orders(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<Order>>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            // Orders locked by a concurrent match are left for the next sweep
+            let orders: Vec<_> = sqlx::query_as!(
+                OrderRow,
+                r#"UPDATE orders SET status = 'expired'
+                WHERE order_id IN (
+                    SELECT order_id FROM orders
+                    WHERE status IN ('queued', 'open') AND expires_at <= $1
+                    ORDER BY expires_at LIMIT $2 FOR UPDATE SKIP LOCKED
+                )
+                RETURNING order_id, user_id, ticker, price, shares, remaining, type as is_buy,
+                    status, created_at, expires_at"#,
+                now,
+                i64::from(limit)
+            )
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .into_iter()
+            .filter_map(OrderRow::into_order)
+            .collect();
+
+            for order in &orders {
+                release_escrow(&mut tx, order).await?;
+            }
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(orders)
+        }
+        .query("expire_orders", self.slow_query)
+    }
+
+    fn stock_info(&self, ticker: &Ticker) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        sqlx::query_as!(
+            StockRow,
+            "SELECT ticker, owner_id, shares, created_at, status, name, description, icon_url
+            FROM stocks WHERE ticker = $1",
+            ticker.as_str()
+        )
+        .fetch_optional(&self.pool)
+        .map(|res| match res {
+            Ok(Some(row)) => row.into_info().ok_or(Error::Unspecified),
+            Ok(None) => StockNotFoundSnafu { ticker: *ticker }.fail(),
+            Err(err) => Err(unspecified(err)),
+        })
+        .query("stock_info", self.slow_query)
+    }
+
+    fn create_stock(
+        &self,
+        ticker: &Ticker,
+        shares: Shares,
+        owner: &Uuid,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        async move {
+            let shares = i32::from(shares);
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let info = sqlx::query_as!(
+                StockRow,
+                "INSERT INTO stocks (ticker, shares, owner_id) VALUES ($1, $2, $3)
+                RETURNING ticker, owner_id, shares, created_at, status, name, description,
+                    icon_url",
+                ticker.as_str(),
+                shares,
+                owner
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|err| {
+                if let Some(dberr) = err.as_database_error()
+                    && dberr.is_unique_violation()
+                {
+                    Error::StockExists { ticker: *ticker }
+                } else if is_foreign_key_violation(&err) {
+                    Error::AccountNotFound { id: *owner }
+                } else {
+                    unspecified(err)
+                }
+            })?
+            .into_info()
+            .ok_or(Error::Unspecified)?;
+
+            sqlx::query!(
+                "INSERT INTO holdings (ticker, user_id, shares) VALUES ($1, $2, $3)",
+                ticker.as_str(),
+                owner,
+                shares
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            let entry = NewAuditEntry {
+                actor: *actor,
+                action: Action::CreateStock,
+                target: Some(ticker.to_string()),
+                details: serde_json::json!({ "owner": owner, "shares": shares }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(info)
+        }
+        .query("create_stock", self.slow_query)
+    }
+
+    fn set_listing_price(
+        &self,
+        ticker: &Ticker,
+        price: Price,
+    ) -> impl Future<Output = super::Result<bool>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let owner = sqlx::query_scalar!(
+                "SELECT owner_id FROM stocks WHERE ticker = $1 FOR UPDATE",
+                ticker.as_str()
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .context(StockNotFoundSnafu { ticker: *ticker })?;
+
+            // Stocks listed before owners were tracked have nobody to record the price against
+            let Some(owner) = owner else {
+                return Ok(false);
+            };
+
+            let event = sqlx::query!(
+                "INSERT INTO stock_events (seller_id, buyer_id, ticker, price, shares)
+                SELECT $1, $1, $2::VARCHAR, $3, 0
+                WHERE NOT EXISTS (SELECT 1 FROM stock_events WHERE ticker = $2::VARCHAR)
+                RETURNING event_id, time",
+                owner,
+                ticker.as_str(),
+                price.get()
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            if let Some(event) = &event {
+                update_latest_price(&mut tx, ticker, price, event.event_id, event.time).await?;
+            }
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(event.is_some())
+        }
+        .query("set_listing_price", self.slow_query)
     }
-}
-impl super::StockRepository for PgPort {
-    fn user_exists(&self, id: &uuid::Uuid) -> impl Future<Output = super::Result<bool>> + Send {
-        sqlx::query_scalar!("SELECT EXISTS (SELECT 1 FROM users WHERE user_id = $1)", id)
+
+    fn import_prices(
+        &self,
+        prices: &[ImportedPrice],
+    ) -> impl Future<Output = super::Result<u64>> + Send {
+        /// How many prices each statement inserts
+        const BATCH: usize = 1000;
+
+        let tickers: Vec<&str> = prices.iter().map(|p| p.ticker.as_str()).collect();
+        let times: Vec<DateTime<Utc>> = prices.iter().map(|p| p.time).collect();
+
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+            let mut inserted = 0;
+
+            for (batch, (tickers, times)) in prices
+                .chunks(BATCH)
+                .zip(tickers.chunks(BATCH).zip(times.chunks(BATCH)))
+            {
+                let prices: Vec<Decimal> = batch.iter().map(|p| p.price.get()).collect();
+                let volumes: Vec<i64> = batch
+                    .iter()
+                    .map(|p| i64::try_from(p.volume).unwrap_or(i64::MAX))
+                    .collect();
+
+                // Stocks that don't exist or have no owner fail the insert rather than being
+                // skipped, so nothing is silently left out
+                inserted += sqlx::query!(
+                    "INSERT INTO stock_events
+                        (seller_id, buyer_id, ticker, price, shares, time, imported_volume)
+                    SELECT stocks.owner_id, stocks.owner_id, imported.ticker, imported.price, 0,
+                        imported.time, imported.volume
+                    FROM UNNEST($1::VARCHAR[], $2::TIMESTAMPTZ[], $3::NUMERIC[], $4::BIGINT[])
+                        AS imported (ticker, time, price, volume)
+                    LEFT JOIN stocks ON stocks.ticker = imported.ticker
+                    ON CONFLICT (ticker, time) WHERE imported_volume IS NOT NULL DO NOTHING",
+                    tickers as &[&str],
+                    times,
+                    &prices,
+                    &volumes
+                )
+                .execute(&mut *tx)
+                .await
+                .map_err(unspecified)?
+                .rows_affected();
+            }
+
+            sqlx::query!(
+                "INSERT INTO latest_prices (ticker, price, updated_at, event_id)
+                SELECT DISTINCT ON (ticker) ticker, price, time, event_id FROM stock_events
+                WHERE ticker = ANY($1) AND imported_volume IS NOT NULL
+                ORDER BY ticker, time DESC, event_id DESC
+                ON CONFLICT (ticker) DO UPDATE
+                    SET price = EXCLUDED.price, updated_at = EXCLUDED.updated_at,
+                        event_id = EXCLUDED.event_id
+                    WHERE (latest_prices.updated_at, latest_prices.event_id)
+                        < (EXCLUDED.updated_at, EXCLUDED.event_id)",
+                &tickers as &[&str]
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            // Closes already recorded from the first imported day on may have carried an older
+            // price forward, so are reclosed along with the days prices were imported for
+            sqlx::query!(
+                "WITH imported AS (
+                    SELECT * FROM UNNEST($1::VARCHAR[], $2::TIMESTAMPTZ[]) AS imported (ticker, time)
+                ), days AS (
+                    SELECT ticker, (time AT TIME ZONE 'UTC')::DATE AS date FROM imported
+                    UNION
+                    SELECT closes.ticker, closes.date FROM daily_closes closes
+                    JOIN (
+                        SELECT ticker, MIN((time AT TIME ZONE 'UTC')::DATE) AS since
+                        FROM imported GROUP BY ticker
+                    ) first ON first.ticker = closes.ticker AND closes.date >= first.since
+                )
+                INSERT INTO daily_closes (ticker, date, close, volume)
+                SELECT days.ticker, days.date, last.price, day.volume
+                FROM days
+                JOIN LATERAL (
+                    SELECT price FROM stock_events
+                    WHERE ticker = days.ticker
+                        AND time < (days.date + 1)::TIMESTAMP AT TIME ZONE 'UTC'
+                    ORDER BY time DESC, event_id DESC LIMIT 1
+                ) last ON TRUE
+                CROSS JOIN LATERAL (
+                    SELECT COALESCE(SUM(shares + COALESCE(imported_volume, 0)), 0) AS volume
+                    FROM stock_events
+                    WHERE ticker = days.ticker
+                        AND time >= days.date::TIMESTAMP AT TIME ZONE 'UTC'
+                        AND time < (days.date + 1)::TIMESTAMP AT TIME ZONE 'UTC'
+                ) day
+                WHERE days.date < (now() AT TIME ZONE 'UTC')::DATE
+                ON CONFLICT (ticker, date) DO UPDATE
+                    SET close = EXCLUDED.close, volume = EXCLUDED.volume",
+                &tickers as &[&str],
+                &times
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(inserted)
+        }
+        .query("import_prices", self.slow_query)
+    }
+
+    fn grant(
+        &self,
+        id: &Uuid,
+        amount: Decimal,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Decimal>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let balance = sqlx::query_scalar!(
+                "UPDATE users SET balance = balance + $2 WHERE user_id = $1 RETURNING balance",
+                id,
+                amount
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .ok_or(Error::AccountNotFound { id: *id })?;
+
+            sqlx::query!(
+                "INSERT INTO ledger (user_id, kind, delta) VALUES ($1, 'grant', $2)",
+                id,
+                amount
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            let entry = NewAuditEntry {
+                actor: *actor,
+                action: Action::Grant,
+                target: Some(id.to_string()),
+                details: serde_json::json!({ "amount": amount }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(balance)
+        }
+        .query("grant", self.slow_query)
+    }
+
+    fn credit_deposit(
+        &self,
+        wallet: &Address,
+        deposit: &Deposit,
+    ) -> impl Future<Output = super::Result<Option<Decimal>>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let balance = sqlx::query_scalar!(
+                "UPDATE users SET balance = balance + $2
+                WHERE user_id = $1 AND closed_at IS NULL
+                RETURNING balance",
+                deposit.user,
+                deposit.amount
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .context(AccountNotFoundSnafu { id: deposit.user })?;
+
+            // Claiming the transaction last means one credited before is rolled back along with
+            // everything else, however many times it's seen
+            let claimed = sqlx::query_scalar!(
+                "WITH entry AS (
+                    INSERT INTO ledger (user_id, kind, delta) VALUES ($1, 'deposit', $2)
+                    RETURNING ledger_id
+                )
+                INSERT INTO kromer_deposits
+                    (transaction_id, time, user_id, amount, from_address, ledger_id)
+                SELECT $3, $4, $1, $2, $5, ledger_id FROM entry
+                ON CONFLICT (transaction_id) DO NOTHING
+                RETURNING transaction_id",
+                deposit.user,
+                deposit.amount,
+                deposit.transaction,
+                deposit.time,
+                deposit.from.as_str()
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            if claimed.is_none() {
+                tx.rollback().await.map_err(unspecified)?;
+                return Ok(None);
+            }
+
+            upsert_kromer_cursor(&mut tx, wallet, deposit.transaction).await?;
+
+            let entry = NewAuditEntry {
+                actor: Actor::System,
+                action: Action::Deposit,
+                target: Some(deposit.user.to_string()),
+                details: serde_json::json!({
+                    "transaction": deposit.transaction,
+                    "from": deposit.from.as_str(),
+                    "amount": deposit.amount,
+                }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(Some(balance))
+        }
+        .query("credit_deposit", self.slow_query)
+    }
+
+    fn record_uncredited_deposit(
+        &self,
+        wallet: &Address,
+        deposit: &Deposit,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            upsert_kromer_cursor(&mut tx, wallet, deposit.transaction).await?;
+
+            let entry = NewAuditEntry {
+                actor: Actor::System,
+                action: Action::UncreditedDeposit,
+                target: Some(deposit.user.to_string()),
+                details: serde_json::json!({
+                    "transaction": deposit.transaction,
+                    "from": deposit.from.as_str(),
+                    "amount": deposit.amount,
+                }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(())
+        }
+        .query("record_uncredited_deposit", self.slow_query)
+    }
+
+    fn kromer_cursor(
+        &self,
+        wallet: &Address,
+    ) -> impl Future<Output = super::Result<Option<i64>>> + Send {
+        sqlx::query_scalar!(
+            "SELECT transaction_id FROM kromer_cursor WHERE address = $1",
+            wallet.as_str()
+        )
+        .fetch_optional(&self.pool)
+        .map_err(unspecified)
+        .query("kromer_cursor", self.slow_query)
+    }
+
+    fn advance_kromer_cursor(
+        &self,
+        wallet: &Address,
+        transaction: i64,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            let mut conn = self.pool.acquire().await.map_err(unspecified)?;
+            upsert_kromer_cursor(&mut conn, wallet, transaction).await
+        }
+        .query("advance_kromer_cursor", self.slow_query)
+    }
+
+    fn adjust_balance(
+        &self,
+        id: &Uuid,
+        delta: Decimal,
+        reason: AdjustmentReason,
+        allow_negative: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Adjustment>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let adjustment =
+                apply_adjustment(&mut tx, id, delta, reason, None, allow_negative, actor).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(adjustment)
+        }
+        .query("adjust_balance", self.slow_query)
+    }
+
+    fn reverse_adjustment(
+        &self,
+        id: i64,
+        allow_negative: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Adjustment>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            // Locked so a reversal racing this one waits, then trips over the first's link
+            let original = sqlx::query!(
+                "SELECT user_id, delta, reason FROM balance_adjustments
+                WHERE adjustment_id = $1 AND reverses IS NULL
+                FOR UPDATE",
+                id
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .context(AdjustmentNotFoundSnafu { id })?;
+            let reason = original.reason.parse().map_err(|()| Error::Unspecified)?;
+
+            let adjustment = apply_adjustment(
+                &mut tx,
+                &original.user_id,
+                -original.delta,
+                reason,
+                Some(id),
+                allow_negative,
+                actor,
+            )
+            .await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(adjustment)
+        }
+        .query("reverse_adjustment", self.slow_query)
+    }
+
+    fn set_stock_status(
+        &self,
+        ticker: &Ticker,
+        status: StockStatus,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let info = sqlx::query_as!(
+                StockRow,
+                "UPDATE stocks SET status = $2 WHERE ticker = $1
+                RETURNING ticker, owner_id, shares, created_at, status, name, description,
+                    icon_url",
+                ticker.as_str(),
+                status.as_str()
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .context(StockNotFoundSnafu { ticker: *ticker })?
+            .into_info()
+            .ok_or(Error::Unspecified)?;
+
+            let entry = NewAuditEntry {
+                actor: *actor,
+                action: Action::SetStockStatus,
+                target: Some(ticker.to_string()),
+                details: serde_json::json!({ "status": status.as_str() }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(info)
+        }
+        .query("set_stock_status", self.slow_query)
+    }
+
+    fn transfer_ownership(
+        &self,
+        ticker: &Ticker,
+        from: &Uuid,
+        to: &Uuid,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let owner = sqlx::query_scalar!(
+                "SELECT owner_id FROM stocks WHERE ticker = $1 FOR UPDATE",
+                ticker.as_str()
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .context(StockNotFoundSnafu { ticker: *ticker })?;
+
+            ensure!(
+                owner.as_ref() == Some(from),
+                NotStockOwnerSnafu { ticker: *ticker }
+            );
+
+            let info = sqlx::query_as!(
+                StockRow,
+                "UPDATE stocks SET owner_id = $2 WHERE ticker = $1
+                RETURNING ticker, owner_id, shares, created_at, status, name, description,
+                    icon_url",
+                ticker.as_str(),
+                to
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|err| {
+                if is_foreign_key_violation(&err) {
+                    Error::AccountNotFound { id: *to }
+                } else {
+                    unspecified(err)
+                }
+            })?
+            .into_info()
+            .ok_or(Error::Unspecified)?;
+
+            let entry = NewAuditEntry {
+                actor: Actor::Account(*from),
+                action: Action::TransferOwnership,
+                target: Some(ticker.to_string()),
+                details: serde_json::json!({ "from": from, "to": to }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(info)
+        }
+        .query("transfer_ownership", self.slow_query)
+    }
+
+    fn update_stock_metadata(
+        &self,
+        ticker: &Ticker,
+        metadata: &StockMetadata,
+        owner: &Uuid,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let current = sqlx::query_scalar!(
+                "SELECT owner_id FROM stocks WHERE ticker = $1 FOR UPDATE",
+                ticker.as_str()
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .context(StockNotFoundSnafu { ticker: *ticker })?;
+
+            ensure!(
+                current.as_ref() == Some(owner),
+                NotStockOwnerSnafu { ticker: *ticker }
+            );
+
+            let info = sqlx::query_as!(
+                StockRow,
+                "UPDATE stocks SET name = $2, description = $3, icon_url = $4 WHERE ticker = $1
+                RETURNING ticker, owner_id, shares, created_at, status, name, description,
+                    icon_url",
+                ticker.as_str(),
+                metadata.name.as_deref(),
+                metadata.description.as_deref(),
+                metadata.icon_url.as_deref(),
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .into_info()
+            .ok_or(Error::Unspecified)?;
+
+            let entry = NewAuditEntry {
+                actor: Actor::Account(*owner),
+                action: Action::UpdateStockMetadata,
+                target: Some(ticker.to_string()),
+                details: serde_json::json!({
+                    "name": metadata.name,
+                    "description": metadata.description,
+                    "icon_url": metadata.icon_url,
+                }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(info)
+        }
+        .query("update_stock_metadata", self.slow_query)
+    }
+
+    fn issue_shares(
+        &self,
+        ticker: &Ticker,
+        quantity: Shares,
+        owner: &Uuid,
+        daily_cap_pct: u16,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            // Locking the stock serializes issuances, so two can't both fit under the cap
+            let outstanding = lock_owned_stock(&mut tx, ticker, owner).await?;
+
+            let issued_today = sqlx::query_scalar!(
+                r#"SELECT COALESCE(SUM((details->>'quantity')::BIGINT), 0)::BIGINT as "issued!"
+                FROM audit_log
+                WHERE action = 'issue_shares' AND target = $1 AND time > NOW() - INTERVAL '1 day'"#,
+                ticker.as_str()
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            let cap = i64::from(outstanding) * i64::from(daily_cap_pct) / 100;
+            // Shares are stored as INTEGER, so the total can't grow past that either
+            let available = (cap - issued_today)
+                .min(i64::from(i32::MAX - outstanding))
+                .max(0);
+
+            ensure!(
+                i64::from(u32::from(quantity)) <= available,
+                IssuanceCapExceededSnafu {
+                    available: u64::try_from(available).unwrap_or_default()
+                }
+            );
+
+            let qty = i32::from(quantity);
+
+            let info = sqlx::query_as!(
+                StockRow,
+                "UPDATE stocks SET shares = shares + $2 WHERE ticker = $1
+                RETURNING ticker, owner_id, shares, created_at, status, name, description,
+                    icon_url",
+                ticker.as_str(),
+                qty
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .into_info()
+            .ok_or(Error::Unspecified)?;
+
+            let held = sqlx::query!(
+                r#"SELECT shares + escrow as "held!: Shares", avg_cost FROM holdings
+                WHERE user_id = $1 AND ticker = $2 FOR UPDATE"#,
+                owner,
+                ticker.as_str()
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            // New shares cost the owner nothing, diluting their basis
+            let avg_cost = match held {
+                Some(held) => weighted_avg_cost(
+                    held.held,
+                    held.avg_cost,
+                    quantity,
+                    Decimal::ZERO,
+                ),
+                None => Some(Decimal::ZERO),
+            };
+
+            sqlx::query!(
+                "INSERT INTO holdings (ticker, user_id, shares, avg_cost) VALUES ($1, $2, $3, $4)
+                ON CONFLICT (user_id, ticker) DO UPDATE
+                    SET shares = holdings.shares + EXCLUDED.shares, avg_cost = EXCLUDED.avg_cost",
+                ticker.as_str(),
+                owner,
+                qty,
+                avg_cost
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            let entry = NewAuditEntry {
+                actor: Actor::Account(*owner),
+                action: Action::IssueShares,
+                target: Some(ticker.to_string()),
+                details: serde_json::json!({ "quantity": qty, "outstanding": i32::from(info.shares) }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(info)
+        }
+        .query("issue_shares", self.slow_query)
+    }
+
+    fn buyback(
+        &self,
+        ticker: &Ticker,
+        quantity: Shares,
+        owner: &Uuid,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        async move {
+            let qty = i32::from(quantity);
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            lock_owned_stock(&mut tx, ticker, owner).await?;
+
+            let res = sqlx::query!(
+                "UPDATE holdings SET shares = shares - $3 WHERE user_id = $1 AND ticker = $2",
+                owner,
+                ticker.as_str(),
+                qty
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|err| {
+                if is_check_violation(&err) {
+                    Error::InsufficientShares
+                } else {
+                    unspecified(err)
+                }
+            })?;
+
+            ensure!(res.rows_affected() == 1, InsufficientSharesSnafu);
+
+            // A stock always has at least one share issued
+            let info = sqlx::query_as!(
+                StockRow,
+                "UPDATE stocks SET shares = shares - $2 WHERE ticker = $1
+                RETURNING ticker, owner_id, shares, created_at, status, name, description,
+                    icon_url",
+                ticker.as_str(),
+                qty
+            )
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|err| {
+                if is_check_violation(&err) {
+                    Error::InsufficientShares
+                } else {
+                    unspecified(err)
+                }
+            })?
+            .into_info()
+            .ok_or(Error::Unspecified)?;
+
+            sqlx::query!(
+                "DELETE FROM holdings WHERE user_id = $1 AND ticker = $2 AND shares = 0 AND escrow = 0",
+                owner,
+                ticker.as_str()
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            let entry = NewAuditEntry {
+                actor: Actor::Account(*owner),
+                action: Action::Buyback,
+                target: Some(ticker.to_string()),
+                details: serde_json::json!({ "quantity": qty, "outstanding": i32::from(info.shares) }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(info)
+        }
+        .query("buyback", self.slow_query)
+    }
+
+    fn shareholders(
+        &self,
+        ticker: &Ticker,
+    ) -> impl Future<Output = super::Result<Shareholders>> + Send {
+        async move {
+            let mut conn = self.pool.acquire().await.map_err(unspecified)?;
+            load_shareholders(&mut conn, ticker).await
+        }
+        .query("shareholders", self.slow_query)
+    }
+
+    fn pay_dividend(
+        &self,
+        ticker: &Ticker,
+        per_share: Decimal,
+        payer: &Uuid,
+    ) -> impl Future<Output = super::Result<Dividend>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            // Locking the stock stops trades moving shares around while holders are paid
+            let owner = sqlx::query_scalar!(
+                "SELECT owner_id FROM stocks WHERE ticker = $1 FOR UPDATE",
+                ticker.as_str()
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .context(StockNotFoundSnafu { ticker: *ticker })?;
+
+            ensure!(
+                owner.as_ref() == Some(payer),
+                NotStockOwnerSnafu { ticker: *ticker }
+            );
+
+            let shareholders = load_shareholders(&mut tx, ticker).await?;
+
+            let plan = shareholders.plan_dividend(payer, per_share);
+            ensure!(
+                !plan.payouts.is_empty(),
+                NoShareholdersSnafu { ticker: *ticker }
+            );
+
+            let res = sqlx::query!(
+                "UPDATE users SET balance = balance - $2 WHERE user_id = $1",
+                payer,
+                plan.total
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(|err| {
+                if is_check_violation(&err) {
+                    Error::InsufficientFunds
+                } else {
+                    unspecified(err)
+                }
+            })?;
+            ensure!(
+                res.rows_affected() == 1,
+                AccountNotFoundSnafu { id: *payer }
+            );
+
+            let (holders, amounts): (Vec<_>, Vec<_>) = plan.payouts.iter().copied().unzip();
+
+            sqlx::query!(
+                "UPDATE users SET balance = balance + payout.amount
+                FROM UNNEST($1::UUID[], $2::NUMERIC[]) AS payout (user_id, amount)
+                WHERE users.user_id = payout.user_id",
+                &holders,
+                &amounts
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            sqlx::query!(
+                "INSERT INTO ledger (user_id, kind, delta, ticker)
+                SELECT entry.user_id, 'dividend', entry.delta, $3
+                FROM UNNEST($1::UUID[], $2::NUMERIC[]) AS entry (user_id, delta)",
+                &[&[*payer], holders.as_slice()].concat(),
+                &[&[-plan.total], amounts.as_slice()].concat(),
+                ticker.as_str()
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            let dividend = Dividend {
+                ticker: *ticker,
+                payer: *payer,
+                per_share,
+                shares: plan.shares,
+                holders: holders.len().try_into().unwrap_or(u32::MAX),
+                total: plan.total,
+            };
+            insert_outbox(&mut tx, &Notice::DividendPaid(dividend)).await?;
+
+            // Only the owner may pay one, so they are always who paid it
+            let entry = NewAuditEntry {
+                actor: Actor::Account(*payer),
+                action: Action::PayDividend,
+                target: Some(ticker.to_string()),
+                details: serde_json::json!({
+                    "per_share": per_share,
+                    "shares": dividend.shares,
+                    "holders": dividend.holders,
+                    "total": dividend.total,
+                }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(dividend)
+        }
+        .query("pay_dividend", self.slow_query)
+    }
+
+    fn open_orders(
+        &self,
+        user: &Uuid,
+        page: &Pager,
+    ) -> impl Future<Output = super::Result<Page<Order>>> + Send {
+        async move {
+            let res = sqlx::query!(
+                r#"SELECT order_id, user_id, ticker, price, shares, remaining, type as is_buy,
+                    status, created_at, expires_at, COUNT(*) OVER () as "total!"
+                FROM orders WHERE user_id = $1 AND status IN ('queued', 'open')
+                ORDER BY order_id DESC LIMIT $2 OFFSET $3"#,
+                user,
+                page.limit(),
+                page.offset()
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            let num = page_total(
+                res.first().map(|v| v.total),
+                page,
+                sqlx::query_scalar!(
+                    "SELECT COUNT(*) FROM orders
+                    WHERE user_id = $1 AND status IN ('queued', 'open')",
+                    user
+                )
+                .fetch_one(&self.pool),
+            )
+            .await?;
+
+            let res: Vec<_> = res
+                .into_iter()
+                .filter_map(|v| {
+                    OrderRow {
+                        order_id: v.order_id,
+                        user_id: v.user_id,
+                        ticker: v.ticker,
+                        price: v.price,
+                        shares: v.shares,
+                        remaining: v.remaining,
+                        is_buy: v.is_buy,
+                        status: v.status,
+                        created_at: v.created_at,
+                        expires_at: v.expires_at,
+                    }
+                    .into_order()
+                })
+                .collect();
+
+            Ok(Page::new(res, num, page))
+        }
+        .query("open_orders", self.slow_query)
+    }
+
+    fn book(
+        &self,
+        ticker: &Ticker,
+        depth: u32,
+    ) -> impl Future<Output = super::Result<Book>> + Send {
+        async move {
+            let exists = sqlx::query_scalar!(
+                "SELECT EXISTS (SELECT 1 FROM stocks WHERE ticker = $1)",
+                ticker.as_str()
+            )
             .fetch_one(&self.pool)
-            .map(|res| match res {
-                Ok(b) => Ok(b.unwrap_or_default()),
-                Err(_) => Err(Error::Unspecified),
+            .await
+            .map_err(unspecified)?
+            .unwrap_or_default();
+
+            ensure!(exists, StockNotFoundSnafu { ticker: *ticker });
+
+            let bids = sqlx::query_as!(
+                LevelRow,
+                r#"SELECT price, SUM(remaining)::BIGINT as "shares!", COUNT(*) as "orders!"
+                FROM orders WHERE ticker = $1 AND status = 'open' AND type = TRUE
+                    AND (expires_at IS NULL OR expires_at > NOW())
+                GROUP BY price ORDER BY price DESC LIMIT $2"#,
+                ticker.as_str(),
+                i64::from(depth)
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            let asks = sqlx::query_as!(
+                LevelRow,
+                r#"SELECT price, SUM(remaining)::BIGINT as "shares!", COUNT(*) as "orders!"
+                FROM orders WHERE ticker = $1 AND status = 'open' AND type = FALSE
+                    AND (expires_at IS NULL OR expires_at > NOW())
+                GROUP BY price ORDER BY price ASC LIMIT $2"#,
+                ticker.as_str(),
+                i64::from(depth)
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            let last_price = sqlx::query_scalar!(
+                "SELECT price FROM latest_prices WHERE ticker = $1",
+                ticker.as_str()
+            )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            Ok(Book {
+                bids: bids.into_iter().map(BookLevel::from).collect(),
+                asks: asks.into_iter().map(BookLevel::from).collect(),
+                last_price,
             })
+        }
+        .query("book", self.slow_query)
     }
 
-    fn stock_exists(&self, stock: &Ticker) -> impl Future<Output = super::Result<bool>> + Send {
+    fn levels_against(
+        &self,
+        ticker: &Ticker,
+        side: Side,
+        user: &Uuid,
+        depth: u32,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Vec<BookLevel>>> + Send {
+        async move {
+            let exists = sqlx::query_scalar!(
+                "SELECT EXISTS (SELECT 1 FROM stocks WHERE ticker = $1)",
+                ticker.as_str()
+            )
+            .fetch_one(&self.pool)
+            .await
+            .map_err(unspecified)?
+            .unwrap_or_default();
+
+            ensure!(exists, StockNotFoundSnafu { ticker: *ticker });
+
+            let levels = sqlx::query_as!(
+                LevelRow,
+                r#"SELECT price, SUM(remaining)::BIGINT as "shares!", COUNT(*) as "orders!"
+                FROM orders WHERE ticker = $1 AND status = 'open' AND type = $2
+                    AND user_id <> $3 AND (expires_at IS NULL OR expires_at > $4)
+                GROUP BY price
+                ORDER BY CASE WHEN $2 THEN price END DESC, price ASC LIMIT $5"#,
+                ticker.as_str(),
+                side.opposite() == Side::Buy,
+                user,
+                now,
+                i64::from(depth)
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            Ok(levels.into_iter().map(BookLevel::from).collect())
+        }
+        .query("levels_against", self.slow_query)
+    }
+
+    fn reference_price(
+        &self,
+        ticker: &Ticker,
+        since: DateTime<Utc>,
+        close: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Option<Price>>> + Send {
         sqlx::query_scalar!(
-            "SELECT EXISTS (SELECT 1 FROM stocks WHERE ticker = $1)",
-            stock.as_str()
+            r#"SELECT COALESCE(
+                (SELECT price FROM stock_events
+                    WHERE ticker = $1 AND time >= $2
+                    ORDER BY time DESC, event_id DESC LIMIT 1),
+                (SELECT price FROM stock_events
+                    WHERE ticker = $1 AND time < $3
+                    ORDER BY time DESC, event_id DESC LIMIT 1)
+            ) as "price: Price""#,
+            ticker.as_str(),
+            since,
+            close
         )
         .fetch_one(&self.pool)
-        .map(|res| match res {
-            Ok(b) => Ok(b.unwrap_or_default()),
-            Err(_) => Err(Error::Unspecified),
-        })
+        .map_err(unspecified)
+        .query("reference_price", self.slow_query)
     }
 
-    fn discord_to_id(
+    fn latest_prices(
         &self,
-        id: i64,
-    ) -> impl Future<Output = super::Result<Option<uuid::Uuid>>> + Send {
-        sqlx::query_scalar!("SELECT user_id FROM users where disc_id = $1", id)
-            .fetch_optional(&self.pool)
-            .map_err(|_| Error::Unspecified)
-    }
+        tickers: Option<&[Ticker]>,
+    ) -> impl Future<Output = super::Result<Vec<LatestPrice>>> + Send {
+        async move {
+            let tickers: Option<Vec<&str>> =
+                tickers.map(|tickers| tickers.iter().map(Ticker::as_str).collect());
 
-    fn mc_to_id(
-        &self,
-        id: &uuid::Uuid,
-    ) -> impl Future<Output = super::Result<Option<uuid::Uuid>>> + Send {
-        sqlx::query_scalar!("SELECT user_id FROM users where mc_id = $1", id)
-            .fetch_optional(&self.pool)
-            .map_err(|_| Error::Unspecified)
-    }
+            let rows = sqlx::query!(
+                r#"SELECT ticker, price as "price: Price", updated_at FROM latest_prices
+                WHERE $1::VARCHAR[] IS NULL OR ticker = ANY($1)
+                ORDER BY ticker"#,
+                tickers.as_deref() as Option<&[&str]>
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
 
-    fn user_info(
-        &self,
-        id: &uuid::Uuid,
-    ) -> impl Future<Output = super::Result<Option<crate::model::UserInfo>>> + Send {
-        struct TmpUserInfo {
-            /// The internal ID of the user
-            pub user_id: Uuid,
-            /// The Kromer balance of this user
-            pub balance: Decimal,
-            pub created_at: DateTime<Utc>,
-            pub mc_id: Option<Uuid>,
-            pub disc_id: Option<i64>,
+            Ok(rows
+                .into_iter()
+                .filter_map(|row| {
+                    Some(LatestPrice {
+                        ticker: Ticker::try_from(row.ticker.as_str()).ok()?,
+                        price: row.price,
+                        updated_at: row.updated_at,
+                    })
+                })
+                .collect())
         }
+        .query("latest_prices", self.slow_query)
+    }
 
-        sqlx::query_as!(
-            TmpUserInfo,
-            "SELECT user_id, balance, created_at, mc_id, disc_id FROM users WHERE user_id = $1",
-            id
-        )
-        .fetch_optional(&self.pool)
-        .map(|res| match res {
-            Ok(Some(u)) => {
-                let info = UserInfo {
-                    id: u.user_id,
-                    balance: u.balance,
-                    created_at: u.created_at,
-                    mc_id: u.mc_id,
-                    disc_id: u.disc_id.map(|v| {
-                        let tmp: u64 = v.try_into().expect("Enforced by DB");
+    fn request_withdrawal(
+        &self,
+        user: &Uuid,
+        amount: Decimal,
+        address: &Address,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Withdrawal>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
 
-                        NonZeroU64::try_from(tmp).expect("Enforced by DB")
-                    }),
-                };
+            let withdrawal = hold_withdrawal(&mut tx, user, amount, address, actor).await?;
 
-                Ok(Some(info))
-            }
-            Ok(None) => Ok(None),
-            Err(_) => Err(Error::Unspecified),
-        })
-    }
+            tx.commit().await.map_err(unspecified)?;
 
-    fn register_user(
-        &self,
-        disc_id: Option<i64>,
-        mc_id: Option<&uuid::Uuid>,
-    ) -> impl Future<Output = super::Result<uuid::Uuid>> + Send {
-        sqlx::query_scalar!(
-            "INSERT INTO users (disc_id, mc_id) VALUES ($1, $2) RETURNING user_id",
-            disc_id,
-            mc_id
-        )
-        .fetch_one(&self.pool)
-        .map_err(|err| {
-            if let Some(dberr) = err.as_database_error()
-                && dberr.is_unique_violation()
-            {
-                Error::AlreadyLinked
-            } else {
-                Error::Unspecified
-            }
-        })
+            Ok(withdrawal)
+        }
+        .query("request_withdrawal", self.slow_query)
     }
 
-    fn get_holdings(
+    fn pending_withdrawals(
         &self,
-        id: &uuid::Uuid,
         page: &Pager,
-    ) -> impl Future<Output = super::Result<Option<(Vec<(Ticker, u32)>, i64)>>> + Send {
-        struct StockValues {
-            pub ticker: String,
-            pub shares: i32,
-        }
+    ) -> impl Future<Output = super::Result<Page<Withdrawal>>> + Send {
         async move {
-            let res = sqlx::query_as!(
-                StockValues,
-                "SELECT ticker, shares FROM holdings WHERE user_id = $1 ORDER BY ticker LIMIT $2 OFFSET $3",
-                id,
+            let res = sqlx::query!(
+                r#"SELECT withdrawal_id, user_id, amount, address as "address!", status,
+                    requested_at, resolved_at, COUNT(*) OVER () as "total!"
+                FROM withdrawal_requests WHERE status = 'pending'
+                ORDER BY withdrawal_id LIMIT $1 OFFSET $2"#,
                 page.limit(),
                 page.offset()
             )
             .fetch_all(&self.pool)
             .await
-            .map_or_else(
-                |err| match err {
-                    sqlx::Error::RowNotFound => Ok(None),
-                    _ => Err(Error::Unspecified),
-                },
-                |v| Ok(Some(v)),
-            )?.unwrap_or_default();
+            .map_err(unspecified)?;
+
+            let num = page_total(
+                res.first().map(|v| v.total),
+                page,
+                sqlx::query_scalar!(
+                    "SELECT COUNT(*) FROM withdrawal_requests WHERE status = 'pending'"
+                )
+                .fetch_one(&self.pool),
+            )
+            .await?;
 
             let res: Vec<_> = res
                 .into_iter()
                 .filter_map(|v| {
-                    let ticker = Ticker::try_from(v.ticker.as_str());
-
-                    match ticker {
-                        Ok(ticker) => Some((ticker, v.shares.try_into().expect("Always works"))),
-                        Err(_) => None,
+                    WithdrawalRow {
+                        withdrawal_id: v.withdrawal_id,
+                        user_id: v.user_id,
+                        amount: v.amount,
+                        address: v.address,
+                        status: v.status,
+                        requested_at: v.requested_at,
+                        resolved_at: v.resolved_at,
                     }
+                    .into_withdrawal()
                 })
                 .collect();
 
-            let num = sqlx::query_scalar!("SELECT COUNT(*) FROM holdings WHERE user_id = $1", id)
-                .fetch_one(&self.pool)
+            Ok(Page::new(res, num, page))
+        }
+        .query("pending_withdrawals", self.slow_query)
+    }
+
+    fn resolve_withdrawal(
+        &self,
+        id: i64,
+        approve: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Withdrawal>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let withdrawal = sqlx::query_as!(
+                WithdrawalRow,
+                r#"UPDATE withdrawal_requests
+                SET status = CASE WHEN $2 THEN 'processed' ELSE 'denied' END,
+                    resolved_at = timezone ('utc', now ())
+                WHERE withdrawal_id = $1 AND status = 'pending'
+                RETURNING withdrawal_id, user_id, amount, address as "address!", status,
+                    requested_at, resolved_at"#,
+                id,
+                approve
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .context(super::WithdrawalNotFoundSnafu { id })?
+            .into_withdrawal()
+            .ok_or(Error::Unspecified)?;
+
+            if !approve {
+                sqlx::query!(
+                    "UPDATE users SET balance = balance + $2 WHERE user_id = $1",
+                    withdrawal.user,
+                    withdrawal.amount
+                )
+                .execute(&mut *tx)
+                .await
+                .map_err(unspecified)?;
+
+                sqlx::query!(
+                    "INSERT INTO ledger (user_id, kind, delta)
+                    VALUES ($1, 'withdrawal_released', $2)",
+                    withdrawal.user,
+                    withdrawal.amount
+                )
+                .execute(&mut *tx)
                 .await
-                .map_err(|_| Error::Unspecified)?
-                .unwrap_or_default();
+                .map_err(unspecified)?;
+            }
+
+            let entry = NewAuditEntry {
+                actor: *actor,
+                action: Action::ResolveWithdrawal,
+                target: Some(id.to_string()),
+                details: serde_json::json!({
+                    "status": withdrawal.status.as_str(),
+                    "user": withdrawal.user,
+                    "amount": withdrawal.amount,
+                }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+            insert_outbox(&mut tx, &Notice::WithdrawalResolved(withdrawal)).await?;
+
+            tx.commit().await.map_err(unspecified)?;
 
-            Ok(Some((res, num)))
+            Ok(withdrawal)
         }
+        .query("resolve_withdrawal", self.slow_query)
     }
 
-    fn list_stocks(
+    fn claim_payout(&self) -> impl Future<Output = super::Result<Option<Withdrawal>>> + Send {
+        async move {
+            let row = sqlx::query_as!(
+                WithdrawalRow,
+                r#"UPDATE withdrawal_requests SET payout_claimed_at = timezone ('utc', now ())
+                WHERE withdrawal_id = (
+                    SELECT withdrawal_id FROM withdrawal_requests
+                    WHERE status = 'processed' AND payout_claimed_at IS NULL
+                    ORDER BY withdrawal_id LIMIT 1
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING withdrawal_id, user_id, amount, address as "address!", status,
+                    requested_at, resolved_at"#
+            )
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            row.map(|row| row.into_withdrawal().ok_or(Error::Unspecified))
+                .transpose()
+        }
+        .query("claim_payout", self.slow_query)
+    }
+
+    fn record_payout(
         &self,
-        page: &Pager,
-    ) -> impl Future<
-        Output = super::Result<Option<(Vec<(Ticker, u32, Decimal, DateTime<Utc>)>, i64)>>,
-    > + Send {
-        struct StockValues {
-            pub ticker: String,
-            pub shares: i32,
-            pub price: Decimal,
-            pub time: DateTime<Utc>,
+        id: i64,
+        transaction: i64,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            let row = sqlx::query!(
+                r#"UPDATE withdrawal_requests SET payout_transaction = $2
+                WHERE withdrawal_id = $1 AND payout_claimed_at IS NOT NULL
+                    AND payout_transaction IS NULL
+                RETURNING user_id, amount, address as "address!""#,
+                id,
+                transaction
+            )
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(unspecified)?
+            .context(super::WithdrawalNotFoundSnafu { id })?;
+
+            let entry = NewAuditEntry {
+                actor: Actor::System,
+                action: Action::PayWithdrawal,
+                target: Some(id.to_string()),
+                details: serde_json::json!({
+                    "transaction": transaction,
+                    "user": row.user_id,
+                    "amount": row.amount,
+                    "to": row.address,
+                }),
+            };
+            insert_audit(&mut tx, &entry).await?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(())
         }
+        .query("record_payout", self.slow_query)
+    }
 
+    fn release_payout(&self, id: i64) -> impl Future<Output = super::Result<()>> + Send {
         async move {
-            let res = sqlx::query_as!(
-                StockValues,
-                r#"SELECT  stocks.ticker as "ticker!: String", 
-                stocks.shares as "shares!: i32", 
-                stock_events.price, 
-                stock_events.time 
-                FROM stocks LEFT JOIN stock_events on 
-                    stocks.ticker = stock_events.ticker 
-                    AND stock_events.time = (
-                        SELECT max(stock_events.time) 
-                        FROM stock_events 
-                            WHERE ticker = stocks.ticker
-                    ) LIMIT $1 OFFSET $2"#,
-                page.limit(),
-                page.offset()
+            sqlx::query!(
+                "UPDATE withdrawal_requests SET payout_claimed_at = NULL
+                WHERE withdrawal_id = $1 AND payout_claimed_at IS NOT NULL
+                    AND payout_transaction IS NULL
+                RETURNING withdrawal_id",
+                id
             )
-            .fetch_all(&self.pool)
+            .fetch_optional(&self.pool)
             .await
-            .map_or_else(
-                |err| match err {
-                    sqlx::Error::RowNotFound => Ok(None),
-                    _ => Err(Error::Unspecified),
-                },
-                |v| Ok(Some(v)),
-            )?
-            .unwrap_or_default();
+            .map_err(unspecified)?
+            .context(super::WithdrawalNotFoundSnafu { id })?;
 
-            if res.is_empty() {
-                return Ok(None);
-            }
+            Ok(())
+        }
+        .query("release_payout", self.slow_query)
+    }
 
-            let res: Vec<_> = res
+    fn enqueue_outbox(
+        &self,
+        payload: &serde_json::Value,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            let mut conn = self.pool.acquire().await.map_err(unspecified)?;
+            insert_outbox_payload(&mut conn, payload).await
+        }
+        .query("enqueue_outbox", self.slow_query)
+    }
+
+    fn claim_outbox(
+        &self,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<OutboxEntry>>> + Send {
+        async move {
+            // Skipping locked rows lets several dispatchers share the outbox without handing the
+            // same entry to more than one of them
+            let rows = sqlx::query!(
+                "UPDATE outbox SET next_attempt_at = $2, coalesce_key = NULL
+                WHERE outbox_id IN (
+                    SELECT outbox_id FROM outbox
+                    WHERE sent_at IS NULL AND parked_at IS NULL AND next_attempt_at <= $1
+                    ORDER BY next_attempt_at, outbox_id
+                    LIMIT $3
+                    FOR UPDATE SKIP LOCKED
+                )
+                RETURNING outbox_id, payload, attempts",
+                now,
+                until,
+                i64::from(limit)
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            let mut entries: Vec<_> = rows
                 .into_iter()
-                .filter_map(|v| {
-                    let ticker = Ticker::try_from(v.ticker.as_str());
+                .map(|row| OutboxEntry {
+                    id: row.outbox_id,
+                    payload: row.payload,
+                    attempts: row.attempts.try_into().unwrap_or(0),
+                })
+                .collect();
+            entries.sort_by_key(|entry| entry.id);
 
-                    match ticker {
-                        Ok(ticker) => Some((
-                            ticker,
-                            v.shares.try_into().expect("Always works"),
-                            v.price,
-                            v.time,
-                        )),
-                        Err(_) => None,
-                    }
+            Ok(entries)
+        }
+        .query("claim_outbox", self.slow_query)
+    }
+
+    fn outbox_sent(&self, id: i64) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            sqlx::query!(
+                "UPDATE outbox SET sent_at = timezone ('utc', now ()) WHERE outbox_id = $1",
+                id
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            Ok(())
+        }
+        .query("outbox_sent", self.slow_query)
+    }
+
+    fn outbox_failed(
+        &self,
+        id: i64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            sqlx::query!(
+                "UPDATE outbox
+                SET attempts = attempts + 1,
+                    last_error = $2,
+                    next_attempt_at = COALESCE($3, next_attempt_at),
+                    parked_at = CASE WHEN $3 IS NULL THEN timezone ('utc', now ()) END
+                WHERE outbox_id = $1",
+                id,
+                error,
+                retry_at
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(unspecified)?;
+
+            Ok(())
+        }
+        .query("outbox_failed", self.slow_query)
+    }
+
+    fn guild_settings(
+        &self,
+        guild: NonZeroU64,
+    ) -> impl Future<Output = super::Result<Option<GuildSettings>>> + Send {
+        sqlx::query_as!(
+            GuildSettingsRow,
+            r#"SELECT guild_id, market_feed_channel, trading_enabled, locale, admin_role,
+                ARRAY(SELECT role_id FROM guild_role_permissions p
+                    WHERE p.guild_id = s.guild_id ORDER BY role_id, permission) as "role_ids!",
+                ARRAY(SELECT permission FROM guild_role_permissions p
+                    WHERE p.guild_id = s.guild_id ORDER BY role_id, permission) as "permissions!"
+            FROM guild_settings s WHERE guild_id = $1"#,
+            snowflake_to_db(guild)
+        )
+        .fetch_optional(&self.pool)
+        .map_ok(|row| row.map(|row| <(NonZeroU64, GuildSettings)>::from(row).1))
+        .map_err(unspecified)
+        .query("guild_settings", self.slow_query)
+    }
+
+    fn all_guild_settings(
+        &self,
+    ) -> impl Future<Output = super::Result<Vec<(NonZeroU64, GuildSettings)>>> + Send {
+        sqlx::query_as!(
+            GuildSettingsRow,
+            r#"SELECT guild_id, market_feed_channel, trading_enabled, locale, admin_role,
+                ARRAY(SELECT role_id FROM guild_role_permissions p
+                    WHERE p.guild_id = s.guild_id ORDER BY role_id, permission) as "role_ids!",
+                ARRAY(SELECT permission FROM guild_role_permissions p
+                    WHERE p.guild_id = s.guild_id ORDER BY role_id, permission) as "permissions!"
+            FROM guild_settings s"#
+        )
+        .fetch_all(&self.pool)
+        .map_ok(|rows| {
+            rows.into_iter()
+                .map(<(NonZeroU64, GuildSettings)>::from)
+                .collect()
+        })
+        .map_err(unspecified)
+        .query("all_guild_settings", self.slow_query)
+    }
+
+    fn set_guild_settings(
+        &self,
+        guild: NonZeroU64,
+        settings: &GuildSettings,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            let mut tx = self.pool.begin().await.map_err(unspecified)?;
+
+            sqlx::query!(
+                "INSERT INTO guild_settings
+                    (guild_id, market_feed_channel, trading_enabled, locale, admin_role)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (guild_id) DO UPDATE
+                    SET market_feed_channel = EXCLUDED.market_feed_channel,
+                        trading_enabled = EXCLUDED.trading_enabled,
+                        locale = EXCLUDED.locale,
+                        admin_role = EXCLUDED.admin_role,
+                        updated_at = now()",
+                snowflake_to_db(guild),
+                settings.market_feed_channel.map(snowflake_to_db),
+                settings.trading_enabled,
+                settings.locale,
+                settings.admin_role.map(snowflake_to_db)
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            let (role_ids, permissions): (Vec<_>, Vec<_>) = settings
+                .role_permissions
+                .iter()
+                .flat_map(|(role, permissions)| {
+                    permissions
+                        .iter()
+                        .map(|permission| (snowflake_to_db(*role), permission.as_str()))
+                })
+                .unzip();
+
+            sqlx::query!(
+                "DELETE FROM guild_role_permissions WHERE guild_id = $1",
+                snowflake_to_db(guild)
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            sqlx::query!(
+                "INSERT INTO guild_role_permissions (guild_id, role_id, permission)
+                SELECT $1, role_id, permission
+                FROM UNNEST($2::BIGINT[], $3::TEXT[]) AS granted (role_id, permission)",
+                snowflake_to_db(guild),
+                &role_ids,
+                &permissions as &[&str]
+            )
+            .execute(&mut *tx)
+            .await
+            .map_err(unspecified)?;
+
+            tx.commit().await.map_err(unspecified)?;
+
+            Ok(())
+        }
+        .query("set_guild_settings", self.slow_query)
+    }
+
+    fn account_totals(
+        &self,
+        after: Option<&Uuid>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<AccountTotals>>> + Send {
+        sqlx::query!(
+            r#"SELECT user_id, system, balance, escrow,
+                (SELECT COALESCE(SUM(delta), 0) FROM ledger WHERE ledger.user_id = users.user_id)
+                    as "ledger!",
+                (SELECT COALESCE(SUM(price * shares), 0) FROM stock_events
+                    WHERE seller_id = users.user_id)
+                - (SELECT COALESCE(SUM(price * shares + fee), 0) FROM stock_events
+                    WHERE buyer_id = users.user_id) as "traded!",
+                (SELECT COALESCE(SUM(price * remaining + fee_escrow), 0) FROM orders
+                    WHERE orders.user_id = users.user_id AND status IN ('queued', 'open')
+                        AND type = TRUE)
+                    as "open_buys!"
+            FROM users
+            WHERE $1::UUID IS NULL OR user_id > $1
+            ORDER BY user_id
+            LIMIT $2"#,
+            after,
+            i64::from(limit)
+        )
+        .fetch_all(&self.pool)
+        .map_ok(|rows| {
+            rows.into_iter()
+                .map(|row| AccountTotals {
+                    user: row.user_id,
+                    system: row.system,
+                    balance: row.balance,
+                    escrow: row.escrow,
+                    ledger: row.ledger,
+                    traded: row.traded,
+                    open_buys: row.open_buys,
+                })
+                .collect()
+        })
+        .map_err(unspecified)
+        .query("account_totals", self.slow_query)
+    }
+
+    fn stock_totals(
+        &self,
+        after: Option<&Ticker>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<StockTotals>>> + Send {
+        sqlx::query!(
+            r#"SELECT ticker, shares as issued,
+                (SELECT COALESCE(SUM(holdings.shares + holdings.escrow), 0) FROM holdings
+                    WHERE holdings.ticker = stocks.ticker) as "held!",
+                (SELECT COALESCE(SUM(holdings.escrow), 0) FROM holdings
+                    WHERE holdings.ticker = stocks.ticker) as "escrow!",
+                (SELECT COALESCE(SUM(remaining), 0) FROM orders
+                    WHERE orders.ticker = stocks.ticker AND status IN ('queued', 'open')
+                        AND type = FALSE)
+                    as "open_sells!"
+            FROM stocks
+            WHERE $1::VARCHAR IS NULL OR ticker > $1
+            ORDER BY ticker
+            LIMIT $2"#,
+            after.map(Ticker::as_str),
+            i64::from(limit)
+        )
+        .fetch_all(&self.pool)
+        .map_ok(|rows| {
+            rows.into_iter()
+                .filter_map(|row| {
+                    Some(StockTotals {
+                        ticker: Ticker::try_from(row.ticker.as_str()).ok()?,
+                        issued: row.issued.into(),
+                        held: row.held,
+                        escrow: row.escrow,
+                        open_sells: row.open_sells,
+                    })
                 })
+                .collect()
+        })
+        .map_err(unspecified)
+        .query("stock_totals", self.slow_query)
+    }
+
+    fn record_command_uses(
+        &self,
+        uses: &[CommandUse],
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        async move {
+            let commands: Vec<_> = uses.iter().map(|used| used.command.as_str()).collect();
+            let guilds: Vec<_> = uses
+                .iter()
+                .map(|used| used.guild.map(snowflake_to_db))
+                .collect();
+            let successes: Vec<_> = uses.iter().map(|used| used.success).collect();
+            let durations: Vec<_> = uses
+                .iter()
+                .map(|used| i64::try_from(used.duration.as_millis()).unwrap_or(i64::MAX))
                 .collect();
+            let times: Vec<_> = uses.iter().map(|used| used.time).collect();
 
-            let num = sqlx::query_scalar!("SELECT COUNT(*) FROM stocks")
-                .fetch_one(&self.pool)
-                .await
-                .map_err(|_| Error::Unspecified)?
-                .unwrap_or_default();
+            sqlx::query!(
+                "INSERT INTO command_stats (command, guild_id, success, duration_ms, time)
+                SELECT * FROM UNNEST($1::TEXT[], $2::BIGINT[], $3::BOOLEAN[], $4::BIGINT[],
+                    $5::TIMESTAMPTZ[])",
+                &commands as &[&str],
+                &guilds as &[Option<i64>],
+                &successes,
+                &durations,
+                &times
+            )
+            .execute(&self.pool)
+            .await
+            .map_err(unspecified)?;
 
-            Ok(Some((res, num)))
+            Ok(())
         }
+        .query("record_command_uses", self.slow_query)
+    }
+
+    fn command_stats(
+        &self,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Vec<CommandStats>>> + Send {
+        sqlx::query!(
+            r#"SELECT command,
+                COUNT(*) FILTER (WHERE time > $1::TIMESTAMPTZ - INTERVAL '1 day') as "day_uses!",
+                COUNT(*) FILTER (WHERE time > $1::TIMESTAMPTZ - INTERVAL '1 day' AND NOT success)
+                    as "day_failures!",
+                COUNT(*) as "week_uses!",
+                COUNT(*) FILTER (WHERE NOT success) as "week_failures!"
+            FROM command_stats
+            WHERE time > $1::TIMESTAMPTZ - INTERVAL '7 days' AND time <= $1
+            GROUP BY command
+            ORDER BY "week_uses!" DESC, command"#,
+            now
+        )
+        .fetch_all(&self.pool)
+        .map_ok(|rows| {
+            rows.into_iter()
+                .map(|row| CommandStats {
+                    command: row.command,
+                    day: UsageTotals {
+                        uses: row.day_uses.cast_unsigned(),
+                        failures: row.day_failures.cast_unsigned(),
+                    },
+                    week: UsageTotals {
+                        uses: row.week_uses.cast_unsigned(),
+                        failures: row.week_failures.cast_unsigned(),
+                    },
+                })
+                .collect()
+        })
+        .map_err(unspecified)
+        .query("command_stats", self.slow_query)
     }
 }
@@ -0,0 +1,1013 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Retrying reads that fail for transient reasons
+
+use std::{
+    num::{NonZeroU32, NonZeroU64},
+    time::Duration,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::model::{
+    AccountMerge, AccountSummary, Candle, CandleInterval, ClosedAccount, ExchangeCounts,
+    HoldingOrdering, HoldingPl, ImportedPrice, LatestPrice, LedgerKind, Movers, Page, Pager,
+    PoolUsage, PortfolioSnapshot, Price, PriceChange, Privacy, Registered, Shares, Statement,
+    StatementSnapshot, StockInfo, StockMatch, StockMetadata, StockOrdering, StockStatus,
+    UserFilter, UserInfo,
+    adjustment::{Adjustment, AdjustmentReason},
+    api_key::ApiKey,
+    audit::{Actor, AuditEntry, AuditFilter, NewAuditEntry},
+    deposit::Deposit,
+    dividend::{Dividend, Shareholders},
+    fee::FeeSchedule,
+    guild::GuildSettings,
+    idempotency::{IdempotencyKey, Idempotent},
+    link::{Identity, LinkCode},
+    order::{Book, BookLevel, Fill, NewOrder, Order, Side, TickerTrade, UserTrade},
+    outbox::OutboxEntry,
+    reconcile::{AccountTotals, StockTotals},
+    snapshot::{SnapshotRecord, SnapshotSection},
+    summary::DailySummary,
+    ticker::Ticker,
+    usage::{CommandStats, CommandUse},
+    withdrawal::{Address, Withdrawal},
+};
+use crate::repo::StockRepository;
+
+/// The longest a single backoff may last, however many attempts came before it
+const MAX_DELAY: Duration = Duration::from_secs(5);
+
+/// How often and how patiently a [`RetryingRepo`] tries a read again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a read is tried in total, including the first attempt
+    pub max_attempts: NonZeroU32,
+    /// The backoff before the first retry, doubling with every retry after it
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: NonZeroU32::new(3).expect("Non-zero"),
+            base_delay: Duration::from_millis(50),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to back off after the `attempt`th attempt failed. Doubles from the base delay with
+    /// every attempt up to 5 seconds, with up to half of it randomized so retries racing each other
+    /// spread out.
+    #[must_use]
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(MAX_DELAY);
+        let half = exp / 2;
+
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+/// Wraps a [`StockRepository`], trying reads again with exponential backoff when they fail with a
+/// [transient](super::Error::is_transient) error. Writes are never retried, as a write that
+/// failed with a dropped connection may still have been committed.
+#[derive(Debug, Clone)]
+pub struct RetryingRepo<R> {
+    inner: R,
+    policy: RetryPolicy,
+}
+
+impl<R: StockRepository> RetryingRepo<R> {
+    /// Wraps `inner`, retrying reads following `policy`
+    #[must_use]
+    pub const fn new(inner: R, policy: RetryPolicy) -> Self {
+        Self { inner, policy }
+    }
+
+    /// Runs `read`, running it again after a backoff for as long as it fails with a transient
+    /// error and attempts are left
+    async fn retry<T, F: Future<Output = super::Result<T>>>(
+        &self,
+        op: &'static str,
+        mut read: impl FnMut() -> F,
+    ) -> super::Result<T> {
+        let mut attempt = 1;
+
+        loop {
+            match read().await {
+                Err(err) if err.is_transient() && attempt < self.policy.max_attempts.get() => {
+                    let delay = self.policy.delay(attempt);
+                    warn!(op, attempt, ?delay, "Transient DB error, retrying: {err}");
+
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+impl<R: StockRepository> StockRepository for RetryingRepo<R> {
+    fn stock_exists(&self, stock: &Ticker) -> impl Future<Output = super::Result<bool>> + Send {
+        self.retry("stock_exists", move || self.inner.stock_exists(stock))
+    }
+
+    fn discord_to_id(
+        &self,
+        id: NonZeroU64,
+    ) -> impl Future<Output = super::Result<Option<Uuid>>> + Send {
+        self.retry("discord_to_id", move || self.inner.discord_to_id(id))
+    }
+
+    fn mc_to_id(&self, id: &Uuid) -> impl Future<Output = super::Result<Option<Uuid>>> + Send {
+        self.retry("mc_to_id", move || self.inner.mc_to_id(id))
+    }
+
+    fn mc_username_to_uuid(
+        &self,
+        name: &str,
+        since: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Option<Uuid>>> + Send {
+        self.retry("mc_username_to_uuid", move || {
+            self.inner.mc_username_to_uuid(name, since)
+        })
+    }
+
+    fn cache_mc_username(
+        &self,
+        name: &str,
+        id: &Uuid,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.cache_mc_username(name, id)
+    }
+
+    fn identities(
+        &self,
+        ids: &[Uuid],
+    ) -> impl Future<Output = super::Result<Vec<(Uuid, Option<NonZeroU64>, Option<Uuid>)>>> + Send
+    {
+        self.retry("identities", move || self.inner.identities(ids))
+    }
+
+    fn register_user(
+        &self,
+        disc_id: Option<NonZeroU64>,
+        mc_id: Option<&Uuid>,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Registered>> + Send {
+        self.inner.register_user(disc_id, mc_id, actor)
+    }
+
+    fn create_link_code(
+        &self,
+        id: &Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<LinkCode>> + Send {
+        self.inner.create_link_code(id, expires_at)
+    }
+
+    fn consume_link_code(
+        &self,
+        code: &LinkCode,
+        identity: Identity,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Uuid>> + Send {
+        self.inner.consume_link_code(code, identity, now)
+    }
+
+    fn purge_link_codes(
+        &self,
+        before: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<u64>> + Send {
+        self.inner.purge_link_codes(before)
+    }
+
+    fn create_api_key(&self, id: &Uuid) -> impl Future<Output = super::Result<ApiKey>> + Send {
+        self.inner.create_api_key(id)
+    }
+
+    fn api_key_owner(
+        &self,
+        key: &ApiKey,
+    ) -> impl Future<Output = super::Result<Option<Uuid>>> + Send {
+        self.retry("api_key_owner", move || self.inner.api_key_owner(key))
+    }
+
+    fn close_account(
+        &self,
+        id: &Uuid,
+        payout: Option<&Address>,
+        force: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<ClosedAccount>> + Send {
+        self.inner.close_account(id, payout, force, actor)
+    }
+
+    fn merge_accounts(
+        &self,
+        survivor: &Uuid,
+        casualty: &Uuid,
+        dry_run: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<AccountMerge>> + Send {
+        self.inner
+            .merge_accounts(survivor, casualty, dry_run, actor)
+    }
+
+    fn create_stock(
+        &self,
+        ticker: &Ticker,
+        shares: Shares,
+        owner: &Uuid,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner.create_stock(ticker, shares, owner, actor)
+    }
+
+    fn user_exists(&self, id: &Uuid) -> impl Future<Output = super::Result<bool>> + Send {
+        self.retry("user_exists", move || self.inner.user_exists(id))
+    }
+
+    fn user_info(&self, id: &Uuid) -> impl Future<Output = super::Result<Option<UserInfo>>> + Send {
+        self.retry("user_info", move || self.inner.user_info(id))
+    }
+
+    fn set_privacy(
+        &self,
+        id: &Uuid,
+        privacy: Privacy,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.set_privacy(id, privacy)
+    }
+
+    fn set_public_portfolio(
+        &self,
+        id: &Uuid,
+        public: bool,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.set_public_portfolio(id, public)
+    }
+
+    fn set_receipts(
+        &self,
+        id: &Uuid,
+        enabled: bool,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.set_receipts(id, enabled)
+    }
+
+    fn ensure_system_account(&self, id: &Uuid) -> impl Future<Output = super::Result<bool>> + Send {
+        self.inner.ensure_system_account(id)
+    }
+
+    // Not retried, a readiness check should report the repository as it is right now
+    fn ping(&self) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.ping()
+    }
+
+    fn pool_usage(&self) -> Option<PoolUsage> {
+        self.inner.pool_usage()
+    }
+
+    fn schema_version(&self) -> impl Future<Output = super::Result<Option<i64>>> + Send {
+        self.retry("schema_version", move || self.inner.schema_version())
+    }
+
+    fn snapshot_chunk(
+        &self,
+        section: SnapshotSection,
+        after: Option<&SnapshotRecord>,
+        limit: i64,
+    ) -> impl Future<Output = super::Result<Vec<SnapshotRecord>>> + Send {
+        self.retry("snapshot_chunk", move || {
+            self.inner.snapshot_chunk(section, after, limit)
+        })
+    }
+
+    fn restore_snapshot(
+        &self,
+        records: &[SnapshotRecord],
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.restore_snapshot(records)
+    }
+
+    fn exchange_counts(&self) -> impl Future<Output = super::Result<ExchangeCounts>> + Send {
+        self.retry("exchange_counts", move || self.inner.exchange_counts())
+    }
+
+    fn get_holdings(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        order: HoldingOrdering,
+    ) -> impl Future<Output = super::Result<Option<Page<(Ticker, Shares, Option<Price>)>>>> + Send
+    {
+        self.retry("get_holdings", move || {
+            self.inner.get_holdings(id, page, order)
+        })
+    }
+
+    fn get_holdings_pl(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        order: HoldingOrdering,
+    ) -> impl Future<Output = super::Result<Option<Page<HoldingPl>>>> + Send {
+        self.retry("get_holdings_pl", move || {
+            self.inner.get_holdings_pl(id, page, order)
+        })
+    }
+
+    fn holdings_value(&self, id: &Uuid) -> impl Future<Output = super::Result<Decimal>> + Send {
+        self.retry("holdings_value", move || self.inner.holdings_value(id))
+    }
+
+    fn portfolio_values(
+        &self,
+        ids: &[Uuid],
+    ) -> impl Future<Output = super::Result<Vec<(Uuid, Decimal)>>> + Send {
+        self.retry("portfolio_values", move || self.inner.portfolio_values(ids))
+    }
+
+    fn account_summary(
+        &self,
+        id: &Uuid,
+        recent: u32,
+    ) -> impl Future<Output = super::Result<AccountSummary>> + Send {
+        self.retry("account_summary", move || {
+            self.inner.account_summary(id, recent)
+        })
+    }
+
+    fn statement(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        filter: Option<LedgerKind>,
+        snapshot: Option<StatementSnapshot>,
+    ) -> impl Future<Output = super::Result<Statement>> + Send {
+        self.retry("statement", move || {
+            self.inner.statement(id, page, filter, snapshot)
+        })
+    }
+
+    fn trade_history(
+        &self,
+        id: &Uuid,
+        after: Option<i32>,
+        limit: i64,
+    ) -> impl Future<Output = super::Result<Vec<UserTrade>>> + Send {
+        self.retry("trade_history", move || {
+            self.inner.trade_history(id, after, limit)
+        })
+    }
+
+    fn ticker_trades(
+        &self,
+        ticker: &Ticker,
+        page: &Pager,
+    ) -> impl Future<Output = super::Result<Page<TickerTrade>>> + Send {
+        self.retry("ticker_trades", move || {
+            self.inner.ticker_trades(ticker, page)
+        })
+    }
+
+    fn top_movers(
+        &self,
+        date: NaiveDate,
+        until: DateTime<Utc>,
+        count: u8,
+    ) -> impl Future<Output = super::Result<Movers>> + Send {
+        self.retry("top_movers", move || {
+            self.inner.top_movers(date, until, count)
+        })
+    }
+
+    fn daily_summary(
+        &self,
+        date: NaiveDate,
+    ) -> impl Future<Output = super::Result<DailySummary>> + Send {
+        self.retry("daily_summary", move || self.inner.daily_summary(date))
+    }
+
+    fn summary_sent(&self, date: NaiveDate) -> impl Future<Output = super::Result<bool>> + Send {
+        self.retry("summary_sent", move || self.inner.summary_sent(date))
+    }
+
+    fn record_summary_sent(
+        &self,
+        date: NaiveDate,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.record_summary_sent(date)
+    }
+
+    fn record_daily_closes(
+        &self,
+        date: NaiveDate,
+    ) -> impl Future<Output = super::Result<u64>> + Send {
+        self.inner.record_daily_closes(date)
+    }
+
+    fn record_portfolio_snapshots(
+        &self,
+        date: NaiveDate,
+        after: Option<&Uuid>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<(Option<Uuid>, u64)>> + Send {
+        self.inner.record_portfolio_snapshots(date, after, limit)
+    }
+
+    fn portfolio_history(
+        &self,
+        id: &Uuid,
+        since: NaiveDate,
+    ) -> impl Future<Output = super::Result<Vec<PortfolioSnapshot>>> + Send {
+        self.retry("portfolio_history", move || {
+            self.inner.portfolio_history(id, since)
+        })
+    }
+
+    fn price_change(
+        &self,
+        ticker: &Ticker,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Option<PriceChange>>> + Send {
+        self.retry("price_change", move || self.inner.price_change(ticker, now))
+    }
+
+    fn candles(
+        &self,
+        ticker: &Ticker,
+        interval: CandleInterval,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Vec<Candle>>> + Send {
+        self.retry("candles", move || {
+            self.inner.candles(ticker, interval, from, to)
+        })
+    }
+
+    fn list_stocks(
+        &self,
+        page: &Pager,
+        order: StockOrdering,
+        prefix: &str,
+    ) -> impl Future<
+        Output = super::Result<
+            Option<
+                Page<(
+                    Ticker,
+                    Shares,
+                    Option<Price>,
+                    Option<DateTime<Utc>>,
+                    Option<String>,
+                )>,
+            >,
+        >,
+    > + Send {
+        self.retry("list_stocks", move || {
+            self.inner.list_stocks(page, order, prefix)
+        })
+    }
+
+    fn search_tickers(
+        &self,
+        prefix: &str,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<(Ticker, Option<String>)>>> + Send {
+        self.retry("search_tickers", move || {
+            self.inner.search_tickers(prefix, limit)
+        })
+    }
+
+    fn search_stocks(
+        &self,
+        query: &str,
+        page: &Pager,
+    ) -> impl Future<Output = super::Result<Page<StockMatch>>> + Send {
+        self.retry("search_stocks", move || {
+            self.inner.search_stocks(query, page)
+        })
+    }
+
+    fn list_users(
+        &self,
+        page: &Pager,
+        filter: UserFilter,
+    ) -> impl Future<Output = super::Result<Page<UserInfo>>> + Send {
+        self.retry("list_users", move || self.inner.list_users(page, filter))
+    }
+
+    fn record_audit(
+        &self,
+        entry: &NewAuditEntry,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.record_audit(entry)
+    }
+
+    fn audit_log(
+        &self,
+        page: &Pager,
+        filter: &AuditFilter,
+    ) -> impl Future<Output = super::Result<Page<AuditEntry>>> + Send {
+        self.retry("audit_log", move || self.inner.audit_log(page, filter))
+    }
+
+    fn place_order(
+        &self,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<(Order, Vec<Fill>)>> + Send {
+        self.inner.place_order(order, fees, now)
+    }
+
+    fn queue_order(
+        &self,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Order>> + Send {
+        self.inner.queue_order(order, fees, now)
+    }
+
+    fn release_queued_orders(
+        &self,
+        limit: u32,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Vec<(Order, Vec<Fill>)>>> + Send {
+        self.inner.release_queued_orders(limit, fees, now)
+    }
+
+    fn place_order_once(
+        &self,
+        key: &IdempotencyKey,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        since: DateTime<Utc>,
+        queue: bool,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Idempotent<(Order, Vec<Fill>)>>> + Send {
+        self.inner
+            .place_order_once(key, order, fees, since, queue, now)
+    }
+
+    fn purge_idempotency_keys(
+        &self,
+        before: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<u64>> + Send {
+        self.inner.purge_idempotency_keys(before)
+    }
+
+    fn cancel_order(
+        &self,
+        id: i32,
+        user: &Uuid,
+    ) -> impl Future<Output = super::Result<Order>> + Send {
+        self.inner.cancel_order(id, user)
+    }
+
+    fn expire_orders(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<Order>>> + Send {
+        self.inner.expire_orders(now, limit)
+    }
+
+    fn stock_info(&self, ticker: &Ticker) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.retry("stock_info", move || self.inner.stock_info(ticker))
+    }
+
+    fn set_listing_price(
+        &self,
+        ticker: &Ticker,
+        price: Price,
+    ) -> impl Future<Output = super::Result<bool>> + Send {
+        self.inner.set_listing_price(ticker, price)
+    }
+
+    fn import_prices(
+        &self,
+        prices: &[ImportedPrice],
+    ) -> impl Future<Output = super::Result<u64>> + Send {
+        self.inner.import_prices(prices)
+    }
+
+    fn grant(
+        &self,
+        id: &Uuid,
+        amount: Decimal,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Decimal>> + Send {
+        self.inner.grant(id, amount, actor)
+    }
+
+    fn credit_deposit(
+        &self,
+        wallet: &Address,
+        deposit: &Deposit,
+    ) -> impl Future<Output = super::Result<Option<Decimal>>> + Send {
+        self.inner.credit_deposit(wallet, deposit)
+    }
+
+    fn record_uncredited_deposit(
+        &self,
+        wallet: &Address,
+        deposit: &Deposit,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.record_uncredited_deposit(wallet, deposit)
+    }
+
+    fn kromer_cursor(
+        &self,
+        wallet: &Address,
+    ) -> impl Future<Output = super::Result<Option<i64>>> + Send {
+        self.retry("kromer_cursor", move || self.inner.kromer_cursor(wallet))
+    }
+
+    fn advance_kromer_cursor(
+        &self,
+        wallet: &Address,
+        transaction: i64,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.advance_kromer_cursor(wallet, transaction)
+    }
+
+    fn adjust_balance(
+        &self,
+        id: &Uuid,
+        delta: Decimal,
+        reason: AdjustmentReason,
+        allow_negative: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Adjustment>> + Send {
+        self.inner
+            .adjust_balance(id, delta, reason, allow_negative, actor)
+    }
+
+    fn reverse_adjustment(
+        &self,
+        id: i64,
+        allow_negative: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Adjustment>> + Send {
+        self.inner.reverse_adjustment(id, allow_negative, actor)
+    }
+
+    fn set_stock_status(
+        &self,
+        ticker: &Ticker,
+        status: StockStatus,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner.set_stock_status(ticker, status, actor)
+    }
+
+    fn transfer_ownership(
+        &self,
+        ticker: &Ticker,
+        from: &Uuid,
+        to: &Uuid,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner.transfer_ownership(ticker, from, to)
+    }
+
+    fn update_stock_metadata(
+        &self,
+        ticker: &Ticker,
+        metadata: &StockMetadata,
+        owner: &Uuid,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner.update_stock_metadata(ticker, metadata, owner)
+    }
+
+    fn issue_shares(
+        &self,
+        ticker: &Ticker,
+        quantity: Shares,
+        owner: &Uuid,
+        daily_cap_pct: u16,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner
+            .issue_shares(ticker, quantity, owner, daily_cap_pct)
+    }
+
+    fn buyback(
+        &self,
+        ticker: &Ticker,
+        quantity: Shares,
+        owner: &Uuid,
+    ) -> impl Future<Output = super::Result<StockInfo>> + Send {
+        self.inner.buyback(ticker, quantity, owner)
+    }
+
+    fn shareholders(
+        &self,
+        ticker: &Ticker,
+    ) -> impl Future<Output = super::Result<Shareholders>> + Send {
+        self.retry("shareholders", move || self.inner.shareholders(ticker))
+    }
+
+    fn pay_dividend(
+        &self,
+        ticker: &Ticker,
+        per_share: Decimal,
+        payer: &Uuid,
+    ) -> impl Future<Output = super::Result<Dividend>> + Send {
+        self.inner.pay_dividend(ticker, per_share, payer)
+    }
+
+    fn open_orders(
+        &self,
+        user: &Uuid,
+        page: &Pager,
+    ) -> impl Future<Output = super::Result<Page<Order>>> + Send {
+        self.retry("open_orders", move || self.inner.open_orders(user, page))
+    }
+
+    fn book(
+        &self,
+        ticker: &Ticker,
+        depth: u32,
+    ) -> impl Future<Output = super::Result<Book>> + Send {
+        self.retry("book", move || self.inner.book(ticker, depth))
+    }
+
+    fn levels_against(
+        &self,
+        ticker: &Ticker,
+        side: Side,
+        user: &Uuid,
+        depth: u32,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Vec<BookLevel>>> + Send {
+        self.retry("levels_against", move || {
+            self.inner.levels_against(ticker, side, user, depth, now)
+        })
+    }
+
+    fn reference_price(
+        &self,
+        ticker: &Ticker,
+        since: DateTime<Utc>,
+        close: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Option<Price>>> + Send {
+        self.retry("reference_price", move || {
+            self.inner.reference_price(ticker, since, close)
+        })
+    }
+
+    fn latest_prices(
+        &self,
+        tickers: Option<&[Ticker]>,
+    ) -> impl Future<Output = super::Result<Vec<LatestPrice>>> + Send {
+        self.retry("latest_prices", move || self.inner.latest_prices(tickers))
+    }
+
+    fn request_withdrawal(
+        &self,
+        user: &Uuid,
+        amount: Decimal,
+        address: &Address,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Withdrawal>> + Send {
+        self.inner.request_withdrawal(user, amount, address, actor)
+    }
+
+    fn pending_withdrawals(
+        &self,
+        page: &Pager,
+    ) -> impl Future<Output = super::Result<Page<Withdrawal>>> + Send {
+        self.retry("pending_withdrawals", move || {
+            self.inner.pending_withdrawals(page)
+        })
+    }
+
+    fn resolve_withdrawal(
+        &self,
+        id: i64,
+        approve: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = super::Result<Withdrawal>> + Send {
+        self.inner.resolve_withdrawal(id, approve, actor)
+    }
+
+    fn claim_payout(&self) -> impl Future<Output = super::Result<Option<Withdrawal>>> + Send {
+        self.inner.claim_payout()
+    }
+
+    fn record_payout(
+        &self,
+        id: i64,
+        transaction: i64,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.record_payout(id, transaction)
+    }
+
+    fn release_payout(&self, id: i64) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.release_payout(id)
+    }
+
+    fn enqueue_outbox(
+        &self,
+        payload: &serde_json::Value,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.enqueue_outbox(payload)
+    }
+
+    fn claim_outbox(
+        &self,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<OutboxEntry>>> + Send {
+        self.inner.claim_outbox(now, until, limit)
+    }
+
+    fn outbox_sent(&self, id: i64) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.outbox_sent(id)
+    }
+
+    fn outbox_failed(
+        &self,
+        id: i64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.outbox_failed(id, error, retry_at)
+    }
+
+    fn guild_settings(
+        &self,
+        guild: NonZeroU64,
+    ) -> impl Future<Output = super::Result<Option<GuildSettings>>> + Send {
+        self.retry("guild_settings", move || self.inner.guild_settings(guild))
+    }
+
+    fn all_guild_settings(
+        &self,
+    ) -> impl Future<Output = super::Result<Vec<(NonZeroU64, GuildSettings)>>> + Send {
+        self.retry("all_guild_settings", move || {
+            self.inner.all_guild_settings()
+        })
+    }
+
+    fn set_guild_settings(
+        &self,
+        guild: NonZeroU64,
+        settings: &GuildSettings,
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.set_guild_settings(guild, settings)
+    }
+
+    fn account_totals(
+        &self,
+        after: Option<&Uuid>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<AccountTotals>>> + Send {
+        self.retry("account_totals", move || {
+            self.inner.account_totals(after, limit)
+        })
+    }
+
+    fn stock_totals(
+        &self,
+        after: Option<&Ticker>,
+        limit: u32,
+    ) -> impl Future<Output = super::Result<Vec<StockTotals>>> + Send {
+        self.retry("stock_totals", move || {
+            self.inner.stock_totals(after, limit)
+        })
+    }
+
+    fn record_command_uses(
+        &self,
+        uses: &[CommandUse],
+    ) -> impl Future<Output = super::Result<()>> + Send {
+        self.inner.record_command_uses(uses)
+    }
+
+    fn command_stats(
+        &self,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = super::Result<Vec<CommandStats>>> + Send {
+        self.retry("command_stats", move || self.inner.command_stats(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        repo::Error,
+        test_util::{ChaosRepo, Stub},
+    };
+
+    fn repo(chaos: &ChaosRepo<Stub>, max_attempts: u32) -> RetryingRepo<ChaosRepo<Stub>> {
+        RetryingRepo::new(
+            chaos.clone(),
+            RetryPolicy {
+                max_attempts: NonZeroU32::new(max_attempts).expect("Non-zero"),
+                base_delay: Duration::ZERO,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried() {
+        let chaos = ChaosRepo::new(Stub::default());
+        chaos.fail_next("discord_to_id", Error::Unavailable);
+        chaos.fail_next("discord_to_id", Error::Unavailable);
+
+        assert_eq!(
+            repo(&chaos, 3).discord_to_id(NonZeroU64::MIN).await,
+            Ok(None)
+        );
+        assert_eq!(chaos.total_calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let chaos = ChaosRepo::new(Stub::default());
+        for _ in 0..3 {
+            chaos.fail_next("stock_exists", Error::Unavailable);
+        }
+
+        assert_eq!(
+            repo(&chaos, 3)
+                .stock_exists(&Ticker::try_from("ABC").expect("Valid ticker"))
+                .await,
+            Err(Error::Unavailable)
+        );
+        assert_eq!(chaos.total_calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn permanent_failures_are_not_retried() {
+        let chaos = ChaosRepo::new(Stub::default());
+        chaos.fail_next("mc_to_id", Error::Unspecified);
+
+        assert_eq!(
+            repo(&chaos, 3).mc_to_id(&Uuid::nil()).await,
+            Err(Error::Unspecified)
+        );
+        assert_eq!(chaos.total_calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn writes_are_not_retried() {
+        let chaos = ChaosRepo::new(Stub::default());
+        chaos.fail_next("register_user", Error::Unavailable);
+
+        let res = repo(&chaos, 3)
+            .register_user(Some(NonZeroU64::MIN), None, &Actor::System)
+            .await;
+
+        assert_eq!(res, Err(Error::Unavailable));
+        assert_eq!(chaos.total_calls(), 1);
+    }
+
+    #[test]
+    fn delay_doubles_within_jitter() {
+        let policy = RetryPolicy {
+            max_attempts: NonZeroU32::MIN,
+            base_delay: Duration::from_millis(100),
+        };
+
+        for (attempt, full) in [(1, 100), (2, 200), (3, 400), (20, 5000)] {
+            let delay = policy.delay(attempt);
+            let full = Duration::from_millis(full);
+
+            assert!(
+                delay >= full / 2 && delay <= full,
+                "{delay:?} for attempt {attempt}"
+            );
+        }
+    }
+}
@@ -0,0 +1,193 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Bootstraps an exchange with accounts, balances, and stocks described by a seed file, such as
+//! for staging environments or tests
+
+use std::num::NonZeroU64;
+
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use snafu::ResultExt;
+
+use crate::{
+    Service,
+    error::{Error, InvalidStockSnafu, InvalidTickerSnafu, Result},
+    model::{Price, Registered, Shares, audit::Actor, ticker::Ticker},
+    repo::StockRepository,
+};
+
+/// Everything a seed sets up. Entries that already exist are skipped, so a seed can be applied any
+/// number of times.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Seed {
+    /// Accounts to create
+    #[serde(default)]
+    pub users: Vec<SeedUser>,
+    /// Stocks to list
+    #[serde(default)]
+    pub stocks: Vec<SeedStock>,
+}
+
+/// An account to create, linked to a Discord user
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedUser {
+    /// The Discord snowflake the account is linked to
+    pub disc_id: NonZeroU64,
+    /// The Kromer the account starts out with, only credited when the account is created
+    #[serde(default)]
+    pub balance: Decimal,
+}
+
+/// A stock to list
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeedStock {
+    /// The ticker to list under
+    pub ticker: String,
+    /// The number of shares issued, all held by the owner
+    pub shares: u32,
+    /// The Discord snowflake of the owner, who is given an account if they don't have one
+    pub owner: NonZeroU64,
+    /// The price the stock starts out at, before it first trades
+    #[serde(default)]
+    pub price: Option<Decimal>,
+}
+
+/// What applying a seed did to each of its entries
+#[derive(Debug, Clone, Default)]
+pub struct SeedReport {
+    /// Entries that were set up
+    pub created: Vec<String>,
+    /// Entries that already existed
+    pub skipped: Vec<String>,
+    /// Entries that couldn't be set up, and why
+    pub failed: Vec<(String, Error)>,
+}
+
+impl SeedReport {
+    fn record(&mut self, entry: String, res: Result<bool>) {
+        match res {
+            Ok(true) => self.created.push(entry),
+            Ok(false) => self.skipped.push(entry),
+            Err(err) => self.failed.push((entry, err)),
+        }
+    }
+}
+
+impl std::fmt::Display for SeedReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} created, {} skipped, {} failed",
+            self.created.len(),
+            self.skipped.len(),
+            self.failed.len()
+        )
+    }
+}
+
+impl<R: StockRepository> Service<R> {
+    /// Applies a seed, creating accounts before stocks so owners can be seeded with a balance.
+    /// Entries that already exist are skipped, and a failed entry doesn't stop the rest.
+    /// Everything is recorded in the audit log as done by the system.
+    #[tracing::instrument(skip_all, fields(users = seed.users.len(), stocks = seed.stocks.len()))]
+    pub async fn apply_seed(&self, seed: &Seed) -> SeedReport {
+        let mut report = SeedReport::default();
+
+        for user in &seed.users {
+            let res = self.seed_user(user).await;
+            report.record(format!("user {}", user.disc_id), res);
+        }
+
+        for stock in &seed.stocks {
+            let res = self.seed_stock(stock).await;
+            report.record(format!("stock {}", stock.ticker), res);
+        }
+
+        report
+    }
+
+    /// Creates an account with its starting balance, returning whether it was created
+    async fn seed_user(&self, user: &SeedUser) -> Result<bool> {
+        if self.repo.discord_to_id(user.disc_id).await?.is_some() {
+            return Ok(false);
+        }
+
+        // Checked first, so a bad balance doesn't leave an account that would be skipped next time
+        if !user.balance.is_zero() {
+            crate::validate_grant(user.balance)?;
+        }
+
+        let Registered::New(id) = self
+            .repo
+            .register_user(Some(user.disc_id), None, &Actor::System)
+            .await?
+        else {
+            return Ok(false);
+        };
+
+        if !user.balance.is_zero() {
+            self.grant(&id, user.balance, &Actor::System).await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Lists a stock at its starting price, returning whether it was listed
+    async fn seed_stock(&self, stock: &SeedStock) -> Result<bool> {
+        let ticker = Ticker::try_from(stock.ticker.trim()).context(InvalidTickerSnafu)?;
+
+        if self.repo.stock_exists(&ticker).await? {
+            return Ok(false);
+        }
+
+        let shares = Shares::new(stock.shares).context(InvalidStockSnafu { what: "shares" })?;
+        let price = stock
+            .price
+            .map(Price::new)
+            .transpose()
+            .context(InvalidStockSnafu { what: "price" })?;
+
+        let owner = match self.repo.discord_to_id(stock.owner).await? {
+            Some(id) => id,
+            None => self
+                .repo
+                .register_user(Some(stock.owner), None, &Actor::System)
+                .await?
+                .id(),
+        };
+
+        match self
+            .create_stock(&ticker, shares, &owner, &Actor::System)
+            .await
+        {
+            Ok(_) => {}
+            Err(Error::StockExists { .. }) => return Ok(false),
+            Err(err) => return Err(err),
+        }
+
+        if let Some(price) = price {
+            self.set_listing_price(&ticker, price).await?;
+        }
+
+        Ok(true)
+    }
+}
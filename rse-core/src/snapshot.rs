@@ -0,0 +1,307 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Point in time exports of the whole exchange, and their restoration into an empty data store,
+//! such as to reproduce production on staging.
+//!
+//! A snapshot is newline delimited JSON. Its first line is a [`SnapshotHeader`], followed by one
+//! [`SnapshotRecord`] per line, section by section in the order of [`SnapshotSection::ALL`].
+//! Sections are read in chunks, each in a read only transaction of its own, so taking one never
+//! holds locks the exchange is waiting on. Trades between chunks can leave the sections slightly
+//! out of step with each other.
+
+use std::io::{self, BufRead, Write};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use snafu::{OptionExt, ResultExt, Snafu, ensure};
+
+use crate::{
+    Service,
+    error::Error,
+    model::snapshot::{SnapshotRecord, SnapshotSection},
+    repo::StockRepository,
+};
+
+/// The version of the snapshot format this build writes and reads
+pub const FORMAT_VERSION: u32 = 1;
+
+/// How many records are read or restored at a time
+const CHUNK_SIZE: u16 = 500;
+
+/// The first line of a snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotHeader {
+    /// The version of the snapshot format, see [`FORMAT_VERSION`]
+    pub format: u32,
+    /// The most recent migration applied to the data store the snapshot was taken from
+    pub schema_version: Option<i64>,
+    /// When the snapshot was started
+    pub taken_at: DateTime<Utc>,
+}
+
+/// The number of records in each section of a snapshot
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotCounts {
+    /// Accounts
+    pub users: u64,
+    /// Stocks
+    pub stocks: u64,
+    /// Non-empty holdings
+    pub holdings: u64,
+    /// Open or queued orders
+    pub orders: u64,
+    /// Latest prices
+    pub prices: u64,
+}
+
+impl SnapshotCounts {
+    fn add(&mut self, section: SnapshotSection, count: usize) {
+        let count = count as u64;
+
+        match section {
+            SnapshotSection::Users => self.users += count,
+            SnapshotSection::Stocks => self.stocks += count,
+            SnapshotSection::Holdings => self.holdings += count,
+            SnapshotSection::Orders => self.orders += count,
+            SnapshotSection::Prices => self.prices += count,
+        }
+    }
+}
+
+impl std::fmt::Display for SnapshotCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} users, {} stocks, {} holdings, {} orders, {} prices",
+            self.users, self.stocks, self.holdings, self.orders, self.prices
+        )
+    }
+}
+
+/// Errors when taking or restoring a snapshot
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub(crate)))]
+#[allow(missing_docs)]
+pub enum SnapshotError {
+    /// An issue with the underlying data store
+    #[snafu(display("{source}"), context(false))]
+    Service { source: Error },
+    /// The snapshot couldn't be read or written
+    #[snafu(display("Couldn't read or write the snapshot: {source}"))]
+    Io { source: io::Error },
+    /// A line of the snapshot isn't a record
+    #[snafu(display("Line {line} isn't a valid snapshot record: {source}"))]
+    InvalidLine {
+        line: u64,
+        source: serde_json::Error,
+    },
+    /// A record comes after one of a later section
+    #[snafu(display("Line {line} is out of order, records must be grouped by section"))]
+    OutOfOrder { line: u64 },
+    /// The snapshot doesn't start with a header
+    #[snafu(display("The snapshot doesn't start with a header"))]
+    MissingHeader,
+    /// The snapshot was written in a format this build can't read
+    #[snafu(display("Snapshot format {found} isn't supported, expected {FORMAT_VERSION}"))]
+    UnsupportedFormat { found: u32 },
+    /// The snapshot was taken from a data store at a different migration
+    #[snafu(display(
+        "The snapshot was taken at schema version {found:?}, but the data store is at {expected:?}"
+    ))]
+    SchemaMismatch {
+        found: Option<i64>,
+        expected: Option<i64>,
+    },
+    /// The data store already has accounts
+    #[snafu(display("Snapshots can only be restored into an empty data store"))]
+    NotEmpty,
+}
+
+/// Writes `value` as a single line of JSON
+fn write_line(out: &mut impl Write, value: &impl Serialize) -> Result<(), SnapshotError> {
+    serde_json::to_writer(&mut *out, value)
+        .map_err(io::Error::from)
+        .context(IoSnafu)?;
+    out.write_all(b"\n").context(IoSnafu)
+}
+
+/// Reads the header from the first line of a snapshot, checking this build can read it
+fn parse_header(line: &str) -> Result<SnapshotHeader, SnapshotError> {
+    let header: SnapshotHeader =
+        serde_json::from_str(line).map_err(|_| SnapshotError::MissingHeader)?;
+    ensure!(
+        header.format == FORMAT_VERSION,
+        UnsupportedFormatSnafu {
+            found: header.format
+        }
+    );
+    Ok(header)
+}
+
+impl<R: StockRepository> Service<R> {
+    /// Writes a snapshot of every account, stock, non-empty holding, open or queued order and
+    /// latest price to `out`, returning how many of each it holds
+    ///
+    /// # Errors
+    /// * [`Service`](SnapshotError::Service) - An issue with the underlying data store
+    /// * [`Io`](SnapshotError::Io) - `out` couldn't be written to
+    #[tracing::instrument(skip_all)]
+    pub async fn export_snapshot(
+        &self,
+        out: &mut impl Write,
+    ) -> Result<SnapshotCounts, SnapshotError> {
+        let header = SnapshotHeader {
+            format: FORMAT_VERSION,
+            schema_version: self.repo.schema_version().await.map_err(Error::from)?,
+            taken_at: self.now(),
+        };
+        write_line(out, &header)?;
+
+        let mut counts = SnapshotCounts::default();
+
+        for section in SnapshotSection::ALL {
+            let mut after = None;
+
+            loop {
+                let chunk = self
+                    .repo
+                    .snapshot_chunk(section, after.as_ref(), i64::from(CHUNK_SIZE))
+                    .await
+                    .map_err(Error::from)?;
+
+                for record in &chunk {
+                    write_line(out, record)?;
+                }
+                counts.add(section, chunk.len());
+
+                if chunk.len() < usize::from(CHUNK_SIZE) {
+                    break;
+                }
+                after = chunk.into_iter().last();
+            }
+        }
+
+        out.flush().context(IoSnafu)?;
+        tracing::info!(%counts, "Took snapshot");
+        Ok(counts)
+    }
+
+    /// Restores a snapshot written by [`export_snapshot`](Self::export_snapshot), returning how
+    /// many records of each section it held. The data store must have no accounts, and be at the
+    /// same migration the snapshot was taken at. Records are restored in chunks, so a snapshot
+    /// that fails partway is left partly restored.
+    ///
+    /// # Errors
+    /// * [`Service`](SnapshotError::Service) - An issue with the underlying data store
+    /// * [`NotEmpty`](SnapshotError::NotEmpty) - The data store already has accounts
+    /// * [`SchemaMismatch`](SnapshotError::SchemaMismatch) - The data store is at a different
+    ///   migration
+    /// * [`Io`](SnapshotError::Io), [`MissingHeader`](SnapshotError::MissingHeader),
+    ///   [`UnsupportedFormat`](SnapshotError::UnsupportedFormat),
+    ///   [`InvalidLine`](SnapshotError::InvalidLine), [`OutOfOrder`](SnapshotError::OutOfOrder) -
+    ///   The snapshot couldn't be read
+    #[tracing::instrument(skip_all)]
+    pub async fn import_snapshot(
+        &self,
+        reader: impl BufRead,
+    ) -> Result<SnapshotCounts, SnapshotError> {
+        let mut lines = reader.lines();
+        let first = lines.next().context(MissingHeaderSnafu)?.context(IoSnafu)?;
+        let header = parse_header(&first)?;
+
+        let expected = self.repo.schema_version().await.map_err(Error::from)?;
+        ensure!(
+            header.schema_version == expected,
+            SchemaMismatchSnafu {
+                found: header.schema_version,
+                expected
+            }
+        );
+
+        let existing = self
+            .repo
+            .snapshot_chunk(SnapshotSection::Users, None, 1)
+            .await
+            .map_err(Error::from)?;
+        ensure!(existing.is_empty(), NotEmptySnafu);
+
+        let mut counts = SnapshotCounts::default();
+        let mut chunk = Vec::with_capacity(usize::from(CHUNK_SIZE));
+        let mut section = SnapshotSection::Users;
+
+        for (i, line) in (2u64..).zip(lines) {
+            let line = line.context(IoSnafu)?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let record: SnapshotRecord =
+                serde_json::from_str(&line).context(InvalidLineSnafu { line: i })?;
+            let position = |s| SnapshotSection::ALL.iter().position(|other| *other == s);
+            ensure!(
+                position(record.section()) >= position(section),
+                OutOfOrderSnafu { line: i }
+            );
+            section = record.section();
+            counts.add(section, 1);
+            chunk.push(record);
+
+            if chunk.len() == usize::from(CHUNK_SIZE) {
+                self.repo
+                    .restore_snapshot(&chunk)
+                    .await
+                    .map_err(Error::from)?;
+                chunk.clear();
+            }
+        }
+
+        if !chunk.is_empty() {
+            self.repo
+                .restore_snapshot(&chunk)
+                .await
+                .map_err(Error::from)?;
+        }
+
+        tracing::info!(%counts, taken_at = %header.taken_at, "Restored snapshot");
+        Ok(counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers_of_other_formats_are_refused() {
+        let header = parse_header(
+            r#"{"format":1,"schema_version":20251015000035,"taken_at":"2025-10-15T00:00:00Z"}"#,
+        )
+        .expect("Current format");
+        assert_eq!(header.schema_version, Some(20_251_015_000_035));
+
+        assert!(matches!(
+            parse_header(r#"{"format":2,"schema_version":null,"taken_at":"2025-10-15T00:00:00Z"}"#),
+            Err(SnapshotError::UnsupportedFormat { found: 2 })
+        ));
+        assert!(matches!(
+            parse_header(r#"{"kind":"user"}"#),
+            Err(SnapshotError::MissingHeader)
+        ));
+    }
+}
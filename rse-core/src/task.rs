@@ -0,0 +1,147 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Tracking of long running background tasks, so that shutdown can wait for them to finish
+
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::task::JoinHandle;
+use tokio_util::task::TaskTracker;
+
+/// A cheaply cloneable registry that every spawned subsystem should register its tasks with.
+#[derive(Debug, Clone, Default)]
+pub struct TaskRegistry {
+    tracker: TaskTracker,
+    running: Arc<Mutex<HashMap<u64, &'static str>>>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl TaskRegistry {
+    /// Creates a new, empty [`TaskRegistry`]
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawns a task onto the runtime, tracking it under `name` until it completes
+    pub fn spawn<F>(&self, name: &'static str, task: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.running
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(id, name);
+
+        let guard = Deregister {
+            running: self.running.clone(),
+            id,
+        };
+
+        self.tracker.spawn(async move {
+            // Dropped on completion, panic, or cancellation alike
+            let _guard = guard;
+            task.await
+        })
+    }
+
+    /// Stops accepting new tasks and waits up to `timeout` for all registered tasks to finish.
+    ///
+    /// # Errors
+    /// Returns the names of every task that was still running once `timeout` elapsed
+    pub async fn shutdown(&self, timeout: Duration) -> Result<(), Vec<&'static str>> {
+        self.tracker.close();
+
+        if tokio::time::timeout(timeout, self.tracker.wait())
+            .await
+            .is_ok()
+        {
+            return Ok(());
+        }
+
+        let mut unfinished: Vec<_> = self
+            .running
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .copied()
+            .collect();
+        unfinished.sort_unstable();
+
+        Err(unfinished)
+    }
+}
+
+struct Deregister {
+    running: Arc<Mutex<HashMap<u64, &'static str>>>,
+    id: u64,
+}
+
+impl Drop for Deregister {
+    fn drop(&mut self) {
+        self.running
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_waits_for_tasks_to_finish() {
+        let tasks = TaskRegistry::new();
+        let done = tasks.spawn("quick", tokio::time::sleep(Duration::from_secs(1)));
+
+        assert_eq!(tasks.shutdown(Duration::from_secs(5)).await, Ok(()));
+        assert!(done.is_finished());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_names_tasks_still_running() {
+        let tasks = TaskRegistry::new();
+        tasks.spawn("quick", tokio::time::sleep(Duration::from_secs(1)));
+        tasks.spawn("stuck", tokio::time::sleep(Duration::from_mins(1)));
+        tasks.spawn("also-stuck", std::future::pending::<()>());
+
+        assert_eq!(
+            tasks.shutdown(Duration::from_secs(5)).await,
+            Err(vec!["also-stuck", "stuck"])
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn panicking_tasks_are_deregistered() {
+        let tasks = TaskRegistry::new();
+        let handle = tasks.spawn("doomed", async { panic!("Boom") });
+
+        assert!(handle.await.is_err());
+        assert_eq!(tasks.shutdown(Duration::from_secs(1)).await, Ok(()));
+    }
+}
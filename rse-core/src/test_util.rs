@@ -0,0 +1,1113 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Utilities for testing code built on top of a [`StockRepository`]
+
+use std::{
+    collections::{HashMap, VecDeque},
+    num::NonZeroU64,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{
+    clock::Clock,
+    model::{
+        AccountMerge, AccountSummary, Candle, CandleInterval, ClosedAccount, ExchangeCounts,
+        HoldingOrdering, HoldingPl, ImportedPrice, LatestPrice, LedgerKind, Movers, Page, Pager,
+        PoolUsage, PortfolioSnapshot, Price, PriceChange, Privacy, Registered, Shares, Statement,
+        StatementSnapshot, StockInfo, StockMatch, StockMetadata, StockOrdering, StockStatus,
+        UserFilter, UserInfo,
+        adjustment::{Adjustment, AdjustmentReason},
+        api_key::ApiKey,
+        audit::{Actor, AuditEntry, AuditFilter, NewAuditEntry},
+        deposit::Deposit,
+        dividend::{Dividend, Shareholders},
+        fee::FeeSchedule,
+        guild::GuildSettings,
+        idempotency::{IdempotencyKey, Idempotent},
+        link::{Identity, LinkCode},
+        order::{Book, BookLevel, Fill, NewOrder, Order, Side, TickerTrade, UserTrade},
+        outbox::OutboxEntry,
+        reconcile::{AccountTotals, StockTotals},
+        snapshot::{SnapshotRecord, SnapshotSection},
+        summary::DailySummary,
+        ticker::Ticker,
+        usage::{CommandStats, CommandUse},
+        withdrawal::{Address, Withdrawal},
+    },
+    repo::{Error, Result, StockRepository},
+};
+
+pub use stub::Stub;
+pub mod spec;
+mod stub;
+
+/// A [`Clock`] that only moves when told to. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<DateTime<Utc>>>,
+}
+
+impl MockClock {
+    /// Starts a clock stopped at `now`
+    #[must_use]
+    pub fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: Arc::new(Mutex::new(now)),
+        }
+    }
+
+    /// Moves the clock forward by `by`
+    pub fn advance(&self, by: TimeDelta) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) += by;
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Wraps a [`StockRepository`], counting calls to each of its methods and failing or slowing them
+/// down on demand. Methods are named as they are on the trait. Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct ChaosRepo<R> {
+    inner: R,
+    state: Arc<Mutex<ChaosState>>,
+}
+
+#[derive(Debug, Default)]
+struct ChaosState {
+    calls: HashMap<&'static str, usize>,
+    failures: HashMap<&'static str, VecDeque<Error>>,
+    latency: HashMap<&'static str, Duration>,
+}
+
+impl<R: StockRepository> ChaosRepo<R> {
+    /// Wraps `inner`, passing every call through to it until told otherwise
+    #[must_use]
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            state: Arc::default(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ChaosState> {
+        // A panicking test is already failing, the state it leaves behind doesn't matter
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Fails the next call to `method` with `err` without calling the wrapped repository. Queues
+    /// up behind failures already set up for it.
+    pub fn fail_next(&self, method: &'static str, err: Error) {
+        self.lock()
+            .failures
+            .entry(method)
+            .or_default()
+            .push_back(err);
+    }
+
+    /// Delays every call to `method` by `latency` from now on, including those set up to fail
+    pub fn set_latency(&self, method: &'static str, latency: Duration) {
+        self.lock().latency.insert(method, latency);
+    }
+
+    /// How many times `method` has been called, including calls that were failed
+    #[must_use]
+    pub fn calls(&self, method: &'static str) -> usize {
+        self.lock().calls.get(method).copied().unwrap_or_default()
+    }
+
+    /// How many times any method has been called
+    #[must_use]
+    pub fn total_calls(&self) -> usize {
+        self.lock().calls.values().sum()
+    }
+
+    /// Counts a call to `method`, then waits out its latency and either fails it or runs `call`
+    async fn chaos<T>(
+        &self,
+        method: &'static str,
+        call: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let (latency, failure) = {
+            let mut state = self.lock();
+            *state.calls.entry(method).or_default() += 1;
+
+            (
+                state.latency.get(method).copied(),
+                state.failures.get_mut(method).and_then(VecDeque::pop_front),
+            )
+        };
+
+        if let Some(latency) = latency {
+            tokio::time::sleep(latency).await;
+        }
+
+        match failure {
+            Some(err) => Err(err),
+            None => call.await,
+        }
+    }
+}
+
+impl<R: StockRepository> StockRepository for ChaosRepo<R> {
+    fn stock_exists(&self, stock: &Ticker) -> impl Future<Output = Result<bool>> + Send {
+        self.chaos("stock_exists", self.inner.stock_exists(stock))
+    }
+
+    fn discord_to_id(&self, id: NonZeroU64) -> impl Future<Output = Result<Option<Uuid>>> + Send {
+        self.chaos("discord_to_id", self.inner.discord_to_id(id))
+    }
+
+    fn mc_to_id(&self, id: &Uuid) -> impl Future<Output = Result<Option<Uuid>>> + Send {
+        self.chaos("mc_to_id", self.inner.mc_to_id(id))
+    }
+
+    fn mc_username_to_uuid(
+        &self,
+        name: &str,
+        since: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Option<Uuid>>> + Send {
+        self.chaos(
+            "mc_username_to_uuid",
+            self.inner.mc_username_to_uuid(name, since),
+        )
+    }
+
+    fn cache_mc_username(&self, name: &str, id: &Uuid) -> impl Future<Output = Result<()>> + Send {
+        self.chaos("cache_mc_username", self.inner.cache_mc_username(name, id))
+    }
+
+    fn identities(
+        &self,
+        ids: &[Uuid],
+    ) -> impl Future<Output = Result<Vec<(Uuid, Option<NonZeroU64>, Option<Uuid>)>>> + Send {
+        self.chaos("identities", self.inner.identities(ids))
+    }
+
+    fn register_user(
+        &self,
+        disc_id: Option<NonZeroU64>,
+        mc_id: Option<&Uuid>,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<Registered>> + Send {
+        self.chaos(
+            "register_user",
+            self.inner.register_user(disc_id, mc_id, actor),
+        )
+    }
+
+    fn create_link_code(
+        &self,
+        id: &Uuid,
+        expires_at: DateTime<Utc>,
+    ) -> impl Future<Output = Result<LinkCode>> + Send {
+        self.chaos(
+            "create_link_code",
+            self.inner.create_link_code(id, expires_at),
+        )
+    }
+
+    fn consume_link_code(
+        &self,
+        code: &LinkCode,
+        identity: Identity,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Uuid>> + Send {
+        self.chaos(
+            "consume_link_code",
+            self.inner.consume_link_code(code, identity, now),
+        )
+    }
+
+    fn purge_link_codes(&self, before: DateTime<Utc>) -> impl Future<Output = Result<u64>> + Send {
+        self.chaos("purge_link_codes", self.inner.purge_link_codes(before))
+    }
+
+    fn create_api_key(&self, id: &Uuid) -> impl Future<Output = Result<ApiKey>> + Send {
+        self.chaos("create_api_key", self.inner.create_api_key(id))
+    }
+
+    fn api_key_owner(&self, key: &ApiKey) -> impl Future<Output = Result<Option<Uuid>>> + Send {
+        self.chaos("api_key_owner", self.inner.api_key_owner(key))
+    }
+
+    fn close_account(
+        &self,
+        id: &Uuid,
+        payout: Option<&Address>,
+        force: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<ClosedAccount>> + Send {
+        self.chaos(
+            "close_account",
+            self.inner.close_account(id, payout, force, actor),
+        )
+    }
+
+    fn merge_accounts(
+        &self,
+        survivor: &Uuid,
+        casualty: &Uuid,
+        dry_run: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<AccountMerge>> + Send {
+        self.chaos(
+            "merge_accounts",
+            self.inner
+                .merge_accounts(survivor, casualty, dry_run, actor),
+        )
+    }
+
+    fn create_stock(
+        &self,
+        ticker: &Ticker,
+        shares: Shares,
+        owner: &Uuid,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<StockInfo>> + Send {
+        self.chaos(
+            "create_stock",
+            self.inner.create_stock(ticker, shares, owner, actor),
+        )
+    }
+
+    fn user_exists(&self, id: &Uuid) -> impl Future<Output = Result<bool>> + Send {
+        self.chaos("user_exists", self.inner.user_exists(id))
+    }
+
+    fn user_info(&self, id: &Uuid) -> impl Future<Output = Result<Option<UserInfo>>> + Send {
+        self.chaos("user_info", self.inner.user_info(id))
+    }
+
+    fn set_privacy(&self, id: &Uuid, privacy: Privacy) -> impl Future<Output = Result<()>> + Send {
+        self.chaos("set_privacy", self.inner.set_privacy(id, privacy))
+    }
+
+    fn set_public_portfolio(
+        &self,
+        id: &Uuid,
+        public: bool,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.chaos(
+            "set_public_portfolio",
+            self.inner.set_public_portfolio(id, public),
+        )
+    }
+
+    fn set_receipts(&self, id: &Uuid, enabled: bool) -> impl Future<Output = Result<()>> + Send {
+        self.chaos("set_receipts", self.inner.set_receipts(id, enabled))
+    }
+
+    fn ensure_system_account(&self, id: &Uuid) -> impl Future<Output = Result<bool>> + Send {
+        self.chaos(
+            "ensure_system_account",
+            self.inner.ensure_system_account(id),
+        )
+    }
+
+    fn ping(&self) -> impl Future<Output = Result<()>> + Send {
+        self.chaos("ping", self.inner.ping())
+    }
+
+    fn pool_usage(&self) -> Option<PoolUsage> {
+        self.inner.pool_usage()
+    }
+
+    fn schema_version(&self) -> impl Future<Output = Result<Option<i64>>> + Send {
+        self.chaos("schema_version", self.inner.schema_version())
+    }
+
+    fn snapshot_chunk(
+        &self,
+        section: SnapshotSection,
+        after: Option<&SnapshotRecord>,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<SnapshotRecord>>> + Send {
+        self.chaos(
+            "snapshot_chunk",
+            self.inner.snapshot_chunk(section, after, limit),
+        )
+    }
+
+    fn restore_snapshot(
+        &self,
+        records: &[SnapshotRecord],
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.chaos("restore_snapshot", self.inner.restore_snapshot(records))
+    }
+
+    fn exchange_counts(&self) -> impl Future<Output = Result<ExchangeCounts>> + Send {
+        self.chaos("exchange_counts", self.inner.exchange_counts())
+    }
+
+    fn get_holdings(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        order: HoldingOrdering,
+    ) -> impl Future<Output = Result<Option<Page<(Ticker, Shares, Option<Price>)>>>> + Send {
+        self.chaos("get_holdings", self.inner.get_holdings(id, page, order))
+    }
+
+    fn get_holdings_pl(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        order: HoldingOrdering,
+    ) -> impl Future<Output = Result<Option<Page<HoldingPl>>>> + Send {
+        self.chaos(
+            "get_holdings_pl",
+            self.inner.get_holdings_pl(id, page, order),
+        )
+    }
+
+    fn holdings_value(&self, id: &Uuid) -> impl Future<Output = Result<Decimal>> + Send {
+        self.chaos("holdings_value", self.inner.holdings_value(id))
+    }
+
+    fn portfolio_values(
+        &self,
+        ids: &[Uuid],
+    ) -> impl Future<Output = Result<Vec<(Uuid, Decimal)>>> + Send {
+        self.chaos("portfolio_values", self.inner.portfolio_values(ids))
+    }
+
+    fn account_summary(
+        &self,
+        id: &Uuid,
+        recent: u32,
+    ) -> impl Future<Output = Result<AccountSummary>> + Send {
+        self.chaos("account_summary", self.inner.account_summary(id, recent))
+    }
+
+    fn statement(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        filter: Option<LedgerKind>,
+        snapshot: Option<StatementSnapshot>,
+    ) -> impl Future<Output = Result<Statement>> + Send {
+        self.chaos(
+            "statement",
+            self.inner.statement(id, page, filter, snapshot),
+        )
+    }
+
+    fn trade_history(
+        &self,
+        id: &Uuid,
+        after: Option<i32>,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<UserTrade>>> + Send {
+        self.chaos("trade_history", self.inner.trade_history(id, after, limit))
+    }
+
+    fn ticker_trades(
+        &self,
+        ticker: &Ticker,
+        page: &Pager,
+    ) -> impl Future<Output = Result<Page<TickerTrade>>> + Send {
+        self.chaos("ticker_trades", self.inner.ticker_trades(ticker, page))
+    }
+
+    fn top_movers(
+        &self,
+        date: NaiveDate,
+        until: DateTime<Utc>,
+        count: u8,
+    ) -> impl Future<Output = Result<Movers>> + Send {
+        self.chaos("top_movers", self.inner.top_movers(date, until, count))
+    }
+
+    fn daily_summary(&self, date: NaiveDate) -> impl Future<Output = Result<DailySummary>> + Send {
+        self.chaos("daily_summary", self.inner.daily_summary(date))
+    }
+
+    fn summary_sent(&self, date: NaiveDate) -> impl Future<Output = Result<bool>> + Send {
+        self.chaos("summary_sent", self.inner.summary_sent(date))
+    }
+
+    fn record_summary_sent(&self, date: NaiveDate) -> impl Future<Output = Result<()>> + Send {
+        self.chaos("record_summary_sent", self.inner.record_summary_sent(date))
+    }
+
+    fn record_daily_closes(&self, date: NaiveDate) -> impl Future<Output = Result<u64>> + Send {
+        self.chaos("record_daily_closes", self.inner.record_daily_closes(date))
+    }
+
+    fn record_portfolio_snapshots(
+        &self,
+        date: NaiveDate,
+        after: Option<&Uuid>,
+        limit: u32,
+    ) -> impl Future<Output = Result<(Option<Uuid>, u64)>> + Send {
+        self.chaos(
+            "record_portfolio_snapshots",
+            self.inner.record_portfolio_snapshots(date, after, limit),
+        )
+    }
+
+    fn portfolio_history(
+        &self,
+        id: &Uuid,
+        since: NaiveDate,
+    ) -> impl Future<Output = Result<Vec<PortfolioSnapshot>>> + Send {
+        self.chaos("portfolio_history", self.inner.portfolio_history(id, since))
+    }
+
+    fn price_change(
+        &self,
+        ticker: &Ticker,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Option<PriceChange>>> + Send {
+        self.chaos("price_change", self.inner.price_change(ticker, now))
+    }
+
+    fn candles(
+        &self,
+        ticker: &Ticker,
+        interval: CandleInterval,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<Candle>>> + Send {
+        self.chaos("candles", self.inner.candles(ticker, interval, from, to))
+    }
+
+    fn list_stocks(
+        &self,
+        page: &Pager,
+        order: StockOrdering,
+        prefix: &str,
+    ) -> impl Future<
+        Output = Result<
+            Option<
+                Page<(
+                    Ticker,
+                    Shares,
+                    Option<Price>,
+                    Option<DateTime<Utc>>,
+                    Option<String>,
+                )>,
+            >,
+        >,
+    > + Send {
+        self.chaos("list_stocks", self.inner.list_stocks(page, order, prefix))
+    }
+
+    fn search_tickers(
+        &self,
+        prefix: &str,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<(Ticker, Option<String>)>>> + Send {
+        self.chaos("search_tickers", self.inner.search_tickers(prefix, limit))
+    }
+
+    fn search_stocks(
+        &self,
+        query: &str,
+        page: &Pager,
+    ) -> impl Future<Output = Result<Page<StockMatch>>> + Send {
+        self.chaos("search_stocks", self.inner.search_stocks(query, page))
+    }
+
+    fn list_users(
+        &self,
+        page: &Pager,
+        filter: UserFilter,
+    ) -> impl Future<Output = Result<Page<UserInfo>>> + Send {
+        self.chaos("list_users", self.inner.list_users(page, filter))
+    }
+
+    fn record_audit(&self, entry: &NewAuditEntry) -> impl Future<Output = Result<()>> + Send {
+        self.chaos("record_audit", self.inner.record_audit(entry))
+    }
+
+    fn audit_log(
+        &self,
+        page: &Pager,
+        filter: &AuditFilter,
+    ) -> impl Future<Output = Result<Page<AuditEntry>>> + Send {
+        self.chaos("audit_log", self.inner.audit_log(page, filter))
+    }
+
+    fn place_order(
+        &self,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<(Order, Vec<Fill>)>> + Send {
+        self.chaos("place_order", self.inner.place_order(order, fees, now))
+    }
+
+    fn queue_order(
+        &self,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Order>> + Send {
+        self.chaos("queue_order", self.inner.queue_order(order, fees, now))
+    }
+
+    fn release_queued_orders(
+        &self,
+        limit: u32,
+        fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<(Order, Vec<Fill>)>>> + Send {
+        self.chaos(
+            "release_queued_orders",
+            self.inner.release_queued_orders(limit, fees, now),
+        )
+    }
+
+    fn place_order_once(
+        &self,
+        key: &IdempotencyKey,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        since: DateTime<Utc>,
+        queue: bool,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Idempotent<(Order, Vec<Fill>)>>> + Send {
+        self.chaos(
+            "place_order_once",
+            self.inner
+                .place_order_once(key, order, fees, since, queue, now),
+        )
+    }
+
+    fn purge_idempotency_keys(
+        &self,
+        before: DateTime<Utc>,
+    ) -> impl Future<Output = Result<u64>> + Send {
+        self.chaos(
+            "purge_idempotency_keys",
+            self.inner.purge_idempotency_keys(before),
+        )
+    }
+
+    fn cancel_order(&self, id: i32, user: &Uuid) -> impl Future<Output = Result<Order>> + Send {
+        self.chaos("cancel_order", self.inner.cancel_order(id, user))
+    }
+
+    fn expire_orders(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<Order>>> + Send {
+        self.chaos("expire_orders", self.inner.expire_orders(now, limit))
+    }
+
+    fn stock_info(&self, ticker: &Ticker) -> impl Future<Output = Result<StockInfo>> + Send {
+        self.chaos("stock_info", self.inner.stock_info(ticker))
+    }
+
+    fn set_listing_price(
+        &self,
+        ticker: &Ticker,
+        price: Price,
+    ) -> impl Future<Output = Result<bool>> + Send {
+        self.chaos(
+            "set_listing_price",
+            self.inner.set_listing_price(ticker, price),
+        )
+    }
+
+    fn import_prices(&self, prices: &[ImportedPrice]) -> impl Future<Output = Result<u64>> + Send {
+        self.chaos("import_prices", self.inner.import_prices(prices))
+    }
+
+    fn grant(
+        &self,
+        id: &Uuid,
+        amount: Decimal,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<Decimal>> + Send {
+        self.chaos("grant", self.inner.grant(id, amount, actor))
+    }
+
+    fn credit_deposit(
+        &self,
+        wallet: &Address,
+        deposit: &Deposit,
+    ) -> impl Future<Output = Result<Option<Decimal>>> + Send {
+        self.chaos("credit_deposit", self.inner.credit_deposit(wallet, deposit))
+    }
+
+    fn record_uncredited_deposit(
+        &self,
+        wallet: &Address,
+        deposit: &Deposit,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.chaos(
+            "record_uncredited_deposit",
+            self.inner.record_uncredited_deposit(wallet, deposit),
+        )
+    }
+
+    fn kromer_cursor(&self, wallet: &Address) -> impl Future<Output = Result<Option<i64>>> + Send {
+        self.chaos("kromer_cursor", self.inner.kromer_cursor(wallet))
+    }
+
+    fn advance_kromer_cursor(
+        &self,
+        wallet: &Address,
+        transaction: i64,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.chaos(
+            "advance_kromer_cursor",
+            self.inner.advance_kromer_cursor(wallet, transaction),
+        )
+    }
+
+    fn adjust_balance(
+        &self,
+        id: &Uuid,
+        delta: Decimal,
+        reason: AdjustmentReason,
+        allow_negative: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<Adjustment>> + Send {
+        self.chaos(
+            "adjust_balance",
+            self.inner
+                .adjust_balance(id, delta, reason, allow_negative, actor),
+        )
+    }
+
+    fn reverse_adjustment(
+        &self,
+        id: i64,
+        allow_negative: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<Adjustment>> + Send {
+        self.chaos(
+            "reverse_adjustment",
+            self.inner.reverse_adjustment(id, allow_negative, actor),
+        )
+    }
+
+    fn set_stock_status(
+        &self,
+        ticker: &Ticker,
+        status: StockStatus,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<StockInfo>> + Send {
+        self.chaos(
+            "set_stock_status",
+            self.inner.set_stock_status(ticker, status, actor),
+        )
+    }
+
+    fn transfer_ownership(
+        &self,
+        ticker: &Ticker,
+        from: &Uuid,
+        to: &Uuid,
+    ) -> impl Future<Output = Result<StockInfo>> + Send {
+        self.chaos(
+            "transfer_ownership",
+            self.inner.transfer_ownership(ticker, from, to),
+        )
+    }
+
+    fn update_stock_metadata(
+        &self,
+        ticker: &Ticker,
+        metadata: &StockMetadata,
+        owner: &Uuid,
+    ) -> impl Future<Output = Result<StockInfo>> + Send {
+        self.chaos(
+            "update_stock_metadata",
+            self.inner.update_stock_metadata(ticker, metadata, owner),
+        )
+    }
+
+    fn issue_shares(
+        &self,
+        ticker: &Ticker,
+        quantity: Shares,
+        owner: &Uuid,
+        daily_cap_pct: u16,
+    ) -> impl Future<Output = Result<StockInfo>> + Send {
+        self.chaos(
+            "issue_shares",
+            self.inner
+                .issue_shares(ticker, quantity, owner, daily_cap_pct),
+        )
+    }
+
+    fn buyback(
+        &self,
+        ticker: &Ticker,
+        quantity: Shares,
+        owner: &Uuid,
+    ) -> impl Future<Output = Result<StockInfo>> + Send {
+        self.chaos("buyback", self.inner.buyback(ticker, quantity, owner))
+    }
+
+    fn shareholders(&self, ticker: &Ticker) -> impl Future<Output = Result<Shareholders>> + Send {
+        self.chaos("shareholders", self.inner.shareholders(ticker))
+    }
+
+    fn pay_dividend(
+        &self,
+        ticker: &Ticker,
+        per_share: Decimal,
+        payer: &Uuid,
+    ) -> impl Future<Output = Result<Dividend>> + Send {
+        self.chaos(
+            "pay_dividend",
+            self.inner.pay_dividend(ticker, per_share, payer),
+        )
+    }
+
+    fn open_orders(
+        &self,
+        user: &Uuid,
+        page: &Pager,
+    ) -> impl Future<Output = Result<Page<Order>>> + Send {
+        self.chaos("open_orders", self.inner.open_orders(user, page))
+    }
+
+    fn book(&self, ticker: &Ticker, depth: u32) -> impl Future<Output = Result<Book>> + Send {
+        self.chaos("book", self.inner.book(ticker, depth))
+    }
+
+    fn levels_against(
+        &self,
+        ticker: &Ticker,
+        side: Side,
+        user: &Uuid,
+        depth: u32,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<BookLevel>>> + Send {
+        self.chaos(
+            "levels_against",
+            self.inner.levels_against(ticker, side, user, depth, now),
+        )
+    }
+
+    fn reference_price(
+        &self,
+        ticker: &Ticker,
+        since: DateTime<Utc>,
+        close: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Option<Price>>> + Send {
+        self.chaos(
+            "reference_price",
+            self.inner.reference_price(ticker, since, close),
+        )
+    }
+
+    fn latest_prices(
+        &self,
+        tickers: Option<&[Ticker]>,
+    ) -> impl Future<Output = Result<Vec<LatestPrice>>> + Send {
+        self.chaos("latest_prices", self.inner.latest_prices(tickers))
+    }
+
+    fn request_withdrawal(
+        &self,
+        user: &Uuid,
+        amount: Decimal,
+        address: &Address,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<Withdrawal>> + Send {
+        self.chaos(
+            "request_withdrawal",
+            self.inner.request_withdrawal(user, amount, address, actor),
+        )
+    }
+
+    fn pending_withdrawals(
+        &self,
+        page: &Pager,
+    ) -> impl Future<Output = Result<Page<Withdrawal>>> + Send {
+        self.chaos("pending_withdrawals", self.inner.pending_withdrawals(page))
+    }
+
+    fn resolve_withdrawal(
+        &self,
+        id: i64,
+        approve: bool,
+        actor: &Actor,
+    ) -> impl Future<Output = Result<Withdrawal>> + Send {
+        self.chaos(
+            "resolve_withdrawal",
+            self.inner.resolve_withdrawal(id, approve, actor),
+        )
+    }
+
+    fn claim_payout(&self) -> impl Future<Output = Result<Option<Withdrawal>>> + Send {
+        self.chaos("claim_payout", self.inner.claim_payout())
+    }
+
+    fn record_payout(&self, id: i64, transaction: i64) -> impl Future<Output = Result<()>> + Send {
+        self.chaos("record_payout", self.inner.record_payout(id, transaction))
+    }
+
+    fn release_payout(&self, id: i64) -> impl Future<Output = Result<()>> + Send {
+        self.chaos("release_payout", self.inner.release_payout(id))
+    }
+
+    fn enqueue_outbox(
+        &self,
+        payload: &serde_json::Value,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.chaos("enqueue_outbox", self.inner.enqueue_outbox(payload))
+    }
+
+    fn claim_outbox(
+        &self,
+        now: DateTime<Utc>,
+        until: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<OutboxEntry>>> + Send {
+        self.chaos("claim_outbox", self.inner.claim_outbox(now, until, limit))
+    }
+
+    fn outbox_sent(&self, id: i64) -> impl Future<Output = Result<()>> + Send {
+        self.chaos("outbox_sent", self.inner.outbox_sent(id))
+    }
+
+    fn outbox_failed(
+        &self,
+        id: i64,
+        error: &str,
+        retry_at: Option<DateTime<Utc>>,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.chaos(
+            "outbox_failed",
+            self.inner.outbox_failed(id, error, retry_at),
+        )
+    }
+
+    fn guild_settings(
+        &self,
+        guild: NonZeroU64,
+    ) -> impl Future<Output = Result<Option<GuildSettings>>> + Send {
+        self.chaos("guild_settings", self.inner.guild_settings(guild))
+    }
+
+    fn all_guild_settings(
+        &self,
+    ) -> impl Future<Output = Result<Vec<(NonZeroU64, GuildSettings)>>> + Send {
+        self.chaos("all_guild_settings", self.inner.all_guild_settings())
+    }
+
+    fn set_guild_settings(
+        &self,
+        guild: NonZeroU64,
+        settings: &GuildSettings,
+    ) -> impl Future<Output = Result<()>> + Send {
+        self.chaos(
+            "set_guild_settings",
+            self.inner.set_guild_settings(guild, settings),
+        )
+    }
+
+    fn account_totals(
+        &self,
+        after: Option<&Uuid>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<AccountTotals>>> + Send {
+        self.chaos("account_totals", self.inner.account_totals(after, limit))
+    }
+
+    fn stock_totals(
+        &self,
+        after: Option<&Ticker>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<StockTotals>>> + Send {
+        self.chaos("stock_totals", self.inner.stock_totals(after, limit))
+    }
+
+    fn record_command_uses(&self, uses: &[CommandUse]) -> impl Future<Output = Result<()>> + Send {
+        self.chaos("record_command_uses", self.inner.record_command_uses(uses))
+    }
+
+    fn command_stats(
+        &self,
+        now: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<CommandStats>>> + Send {
+        self.chaos("command_stats", self.inner.command_stats(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU32;
+
+    use crate::{
+        Service,
+        error::Error as ServiceError,
+        limiter::{Rate, RateLimits},
+        model::{Price, Shares, order::Side},
+    };
+
+    use super::*;
+
+    fn ticker() -> Ticker {
+        Ticker::try_from("ABC").expect("Valid ticker")
+    }
+
+    #[tokio::test]
+    async fn stub_meets_spec() {
+        spec::registered_accounts_are_found(&Stub::default()).await;
+        spec::linking_twice_returns_the_original(&Stub::default()).await;
+        spec::racing_registrations_agree(&Stub::default()).await;
+        spec::listed_stocks_exist(&Stub::default()).await;
+        spec::listing_twice_is_rejected(&Stub::default()).await;
+        spec::api_keys_find_their_account(&Stub::default()).await;
+        spec::idempotent_orders_are_placed_once(&Stub::default()).await;
+        spec::deposits_move_the_cursor(&Stub::default()).await;
+        spec::payouts_are_claimed_once(&Stub::default()).await;
+    }
+
+    #[tokio::test]
+    async fn failures_only_hit_their_method() {
+        let repo = ChaosRepo::new(Stub::default());
+        repo.fail_next("stock_exists", Error::Unavailable);
+
+        assert_eq!(repo.discord_to_id(NonZeroU64::MIN).await, Ok(None));
+        assert_eq!(repo.stock_exists(&ticker()).await, Err(Error::Unavailable));
+        assert_eq!(repo.stock_exists(&ticker()).await, Ok(false));
+
+        assert_eq!(repo.calls("stock_exists"), 2);
+        assert_eq!(repo.total_calls(), 3);
+    }
+
+    #[tokio::test]
+    async fn failed_calls_never_reach_the_inner_repo() {
+        let repo = ChaosRepo::new(Stub::default());
+        repo.fail_next("register_user", Error::Unspecified);
+
+        let res = repo
+            .register_user(Some(NonZeroU64::MIN), None, &Actor::System)
+            .await;
+
+        assert_eq!(res, Err(Error::Unspecified));
+        assert_eq!(repo.discord_to_id(NonZeroU64::MIN).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn latency_delays_calls() {
+        let repo = ChaosRepo::new(Stub::default());
+        repo.set_latency("mc_to_id", Duration::from_millis(20));
+
+        let start = std::time::Instant::now();
+        repo.mc_to_id(&Uuid::nil()).await.expect("Lookup");
+
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn order_expiries_are_checked_against_the_service_clock() {
+        let start = DateTime::<Utc>::UNIX_EPOCH;
+        let clock = MockClock::new(start);
+        let repo = ChaosRepo::new(Stub::default());
+        let service = Service::new(repo.clone()).with_clock(clock.clone());
+        let (user, ticker) = (Uuid::nil(), ticker());
+        let place = || {
+            service.place_order(
+                &user,
+                &ticker,
+                Side::Buy,
+                Price::new(Decimal::ONE).expect("Valid price"),
+                Shares::new(1).expect("Valid shares"),
+                Some(start + TimeDelta::hours(1)),
+            )
+        };
+
+        // Stops the order once it reaches the repository, which the stub can't place
+        repo.fail_next("place_order", Error::Unavailable);
+        assert!(matches!(
+            place().await,
+            Err(ServiceError::DatabaseError { .. })
+        ));
+
+        clock.advance(TimeDelta::hours(2));
+        assert_eq!(service.now(), start + TimeDelta::hours(2));
+        assert!(matches!(
+            place().await,
+            Err(ServiceError::InvalidOrder { .. })
+        ));
+        assert_eq!(repo.calls("place_order"), 1);
+    }
+
+    #[tokio::test]
+    async fn trades_are_rate_limited_by_the_service_clock() {
+        let start = DateTime::<Utc>::UNIX_EPOCH;
+        let clock = MockClock::new(start);
+        let repo = ChaosRepo::new(Stub::default());
+        let trades = Rate::per_minute(NonZeroU32::new(2).expect("Not zero"));
+        let service = Service::new(repo.clone())
+            .with_clock(clock.clone())
+            .with_rate_limits(RateLimits {
+                trades,
+                ..RateLimits::DISCORD
+            });
+        let (user, ticker) = (Uuid::nil(), ticker());
+        let place = || {
+            // Stops the order once it reaches the repository, which the stub can't place
+            repo.fail_next("place_order", Error::Unavailable);
+            service.place_order(
+                &user,
+                &ticker,
+                Side::Buy,
+                Price::new(Decimal::ONE).expect("Valid price"),
+                Shares::new(1).expect("Valid shares"),
+                None,
+            )
+        };
+
+        for _ in 0..2 {
+            assert!(matches!(
+                place().await,
+                Err(ServiceError::DatabaseError { .. })
+            ));
+        }
+        assert_eq!(
+            place().await.expect_err("Rate limited"),
+            ServiceError::RateLimited {
+                retry_after: Duration::from_secs(30)
+            }
+        );
+        assert_eq!(repo.calls("place_order"), 2);
+
+        clock.advance(TimeDelta::seconds(30));
+        assert!(matches!(
+            place().await,
+            Err(ServiceError::DatabaseError { .. })
+        ));
+        assert_eq!(service.prune_rate_limits(), 0);
+
+        clock.advance(TimeDelta::minutes(1));
+        assert_eq!(service.prune_rate_limits(), 1);
+    }
+}
@@ -0,0 +1,322 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//! Behaviour every [`StockRepository`] should share, written once so each backend can be checked
+//! against it. Each check starts from an empty repository and panics if the repository doesn't
+//! behave.
+
+use std::num::NonZeroU64;
+
+use chrono::{TimeDelta, Utc};
+
+use rust_decimal::Decimal;
+
+use crate::{
+    model::{
+        Price, Registered, Shares,
+        audit::Actor,
+        deposit::Deposit,
+        idempotency::{IdempotencyKey, Idempotent},
+        order::{NewOrder, Side},
+        ticker::Ticker,
+        withdrawal::{Address, WithdrawalStatus},
+    },
+    repo::{Error, StockRepository},
+};
+
+/// Too big to fit in an [`i64`], which snowflakes will eventually be
+const FLAKE: NonZeroU64 = NonZeroU64::MAX;
+
+fn ticker() -> Ticker {
+    Ticker::try_from("SPEC").expect("Valid ticker")
+}
+
+fn shares() -> Shares {
+    Shares::new(100).expect("Valid shares")
+}
+
+fn wallet() -> Address {
+    Address::try_from("kexchange0").expect("Valid address")
+}
+
+fn deposit(user: uuid::Uuid, transaction: i64) -> Deposit {
+    Deposit {
+        transaction,
+        user,
+        amount: Decimal::TEN,
+        from: Address::try_from("kabcdefghi").expect("Valid address"),
+        time: Utc::now(),
+    }
+}
+
+/// Registered accounts can be looked up by their Discord snowflake, and unknown snowflakes can't
+pub async fn registered_accounts_are_found(repo: &impl StockRepository) {
+    assert_eq!(repo.discord_to_id(FLAKE).await, Ok(None));
+
+    let id = repo
+        .register_user(Some(FLAKE), None, &Actor::System)
+        .await
+        .expect("Registered")
+        .id();
+
+    assert_eq!(repo.discord_to_id(FLAKE).await, Ok(Some(id)));
+    assert_eq!(repo.discord_to_id(NonZeroU64::MIN).await, Ok(None));
+}
+
+/// Registering a snowflake already linked to an account creates nothing, returning the original
+/// account
+pub async fn linking_twice_returns_the_original(repo: &impl StockRepository) {
+    let id = repo
+        .register_user(Some(FLAKE), None, &Actor::System)
+        .await
+        .expect("Registered")
+        .id();
+
+    assert_eq!(
+        repo.register_user(Some(FLAKE), None, &Actor::System).await,
+        Ok(Registered::Existing(id))
+    );
+    assert_eq!(repo.discord_to_id(FLAKE).await, Ok(Some(id)));
+}
+
+/// Racing registrations of the same snowflake agree on one account, which only one of them
+/// created
+pub async fn racing_registrations_agree(repo: &impl StockRepository) {
+    let (a, b) = tokio::join!(
+        repo.register_user(Some(FLAKE), None, &Actor::System),
+        repo.register_user(Some(FLAKE), None, &Actor::System),
+    );
+    let (a, b) = (a.expect("Registered"), b.expect("Registered"));
+
+    assert_eq!(a.id(), b.id());
+    assert!(
+        matches!(
+            (a, b),
+            (Registered::New(_), Registered::Existing(_))
+                | (Registered::Existing(_), Registered::New(_))
+        ),
+        "{a:?}, {b:?}"
+    );
+    assert_eq!(repo.discord_to_id(FLAKE).await, Ok(Some(a.id())));
+}
+
+/// Listed stocks exist, and others don't
+pub async fn listed_stocks_exist(repo: &impl StockRepository) {
+    let owner = repo
+        .register_user(Some(FLAKE), None, &Actor::System)
+        .await
+        .expect("Registered")
+        .id();
+
+    assert_eq!(repo.stock_exists(&ticker()).await, Ok(false));
+
+    let info = repo
+        .create_stock(&ticker(), shares(), &owner, &Actor::System)
+        .await
+        .expect("Listed");
+
+    assert_eq!(info.ticker, ticker());
+    assert_eq!(info.owner, Some(owner));
+    assert_eq!(info.shares, shares());
+    assert_eq!(repo.stock_exists(&ticker()).await, Ok(true));
+}
+
+/// Listing a ticker that is already listed fails
+pub async fn listing_twice_is_rejected(repo: &impl StockRepository) {
+    let owner = repo
+        .register_user(Some(FLAKE), None, &Actor::System)
+        .await
+        .expect("Registered")
+        .id();
+
+    repo.create_stock(&ticker(), shares(), &owner, &Actor::System)
+        .await
+        .expect("Listed");
+
+    let res = repo
+        .create_stock(&ticker(), shares(), &owner, &Actor::System)
+        .await;
+
+    assert!(
+        matches!(res, Err(Error::StockExists { ticker: t }) if t == ticker()),
+        "{res:?}"
+    );
+}
+
+/// API keys lead back to the account they were given to until it is given another, and accounts
+/// that don't exist can't be given one
+pub async fn api_keys_find_their_account(repo: &impl StockRepository) {
+    let id = repo
+        .register_user(Some(FLAKE), None, &Actor::System)
+        .await
+        .expect("Registered")
+        .id();
+
+    let first = repo.create_api_key(&id).await.expect("Created");
+    assert_eq!(repo.api_key_owner(&first).await, Ok(Some(id)));
+
+    let second = repo.create_api_key(&id).await.expect("Created");
+    assert_eq!(repo.api_key_owner(&first).await, Ok(None));
+    assert_eq!(repo.api_key_owner(&second).await, Ok(Some(id)));
+
+    let nobody = uuid::Uuid::from_u128(1);
+    let res = repo.create_api_key(&nobody).await;
+    assert!(
+        matches!(res, Err(Error::AccountNotFound { id }) if id == nobody),
+        "{res:?}"
+    );
+}
+
+/// An order placed with an idempotency key is only placed once, however often it is sent, and the
+/// key can't be sent again with a different order
+pub async fn idempotent_orders_are_placed_once(repo: &impl StockRepository) {
+    let owner = repo
+        .register_user(Some(FLAKE), None, &Actor::System)
+        .await
+        .expect("Registered")
+        .id();
+    repo.create_stock(&ticker(), shares(), &owner, &Actor::System)
+        .await
+        .expect("Listed");
+
+    let key = IdempotencyKey::new("spec").expect("Valid key");
+    let order = NewOrder {
+        user: owner,
+        ticker: ticker(),
+        side: Side::Sell,
+        price: Price::new(1.into()).expect("Valid price"),
+        quantity: Shares::new(1).expect("Valid shares"),
+        expires_at: None,
+        immediate_or_cancel: false,
+    };
+    let since = Utc::now() - TimeDelta::days(1);
+
+    let placed = repo
+        .place_order_once(&key, &order, None, since, false, Utc::now())
+        .await
+        .expect("Placed");
+    let Idempotent::Executed(placed) = placed else {
+        panic!("Expected the order to be placed, got {placed:?}");
+    };
+    assert_eq!(
+        repo.place_order_once(&key, &order, None, since, false, Utc::now())
+            .await,
+        Ok(Idempotent::Replayed(placed))
+    );
+
+    let other = NewOrder {
+        quantity: Shares::new(2).expect("Valid shares"),
+        ..order
+    };
+    assert_eq!(
+        repo.place_order_once(&key, &other, None, since, false, Utc::now())
+            .await,
+        Err(Error::IdempotencyKeyReused)
+    );
+}
+
+/// Each deposit is credited once, moving its wallet's cursor up to it, and the cursor never moves
+/// back however it is moved
+pub async fn deposits_move_the_cursor(repo: &impl StockRepository) {
+    let user = repo
+        .register_user(Some(FLAKE), None, &Actor::System)
+        .await
+        .expect("Registered")
+        .id();
+    assert_eq!(repo.kromer_cursor(&wallet()).await, Ok(None));
+
+    assert_eq!(
+        repo.credit_deposit(&wallet(), &deposit(user, 5)).await,
+        Ok(Some(Decimal::TEN))
+    );
+    assert_eq!(
+        repo.credit_deposit(&wallet(), &deposit(user, 5)).await,
+        Ok(None)
+    );
+    assert_eq!(repo.kromer_cursor(&wallet()).await, Ok(Some(5)));
+
+    repo.advance_kromer_cursor(&wallet(), 9)
+        .await
+        .expect("Advanced");
+    repo.advance_kromer_cursor(&wallet(), 7)
+        .await
+        .expect("Advanced");
+    assert_eq!(repo.kromer_cursor(&wallet()).await, Ok(Some(9)));
+
+    // A deposit found late doesn't send the cursor back
+    assert_eq!(
+        repo.credit_deposit(&wallet(), &deposit(user, 8)).await,
+        Ok(Some(Decimal::from(20)))
+    );
+    assert_eq!(repo.kromer_cursor(&wallet()).await, Ok(Some(9)));
+
+    let other = Address::try_from("kotherwal1").expect("Valid address");
+    assert_eq!(repo.kromer_cursor(&other).await, Ok(None));
+
+    let nobody = uuid::Uuid::from_u128(1);
+    let res = repo.credit_deposit(&wallet(), &deposit(nobody, 10)).await;
+    assert!(
+        matches!(res, Err(Error::AccountNotFound { id }) if id == nobody),
+        "{res:?}"
+    );
+}
+
+/// Only approved withdrawals are claimed for payout, each once unless released, and a payout is
+/// only recorded once
+pub async fn payouts_are_claimed_once(repo: &impl StockRepository) {
+    let user = repo
+        .register_user(Some(FLAKE), None, &Actor::System)
+        .await
+        .expect("Registered")
+        .id();
+    repo.credit_deposit(&wallet(), &deposit(user, 1))
+        .await
+        .expect("Credited");
+
+    let to = Address::try_from("kabcdefghi").expect("Valid address");
+    let request = async || {
+        repo.request_withdrawal(&user, Decimal::ONE, &to, &Actor::Account(user))
+            .await
+            .expect("Requested")
+    };
+    let (approved, denied, pending) = (request().await, request().await, request().await);
+    let approved = repo
+        .resolve_withdrawal(approved.id, true, &Actor::System)
+        .await
+        .expect("Approved");
+    repo.resolve_withdrawal(denied.id, false, &Actor::System)
+        .await
+        .expect("Denied");
+    assert_eq!(pending.status, WithdrawalStatus::Pending);
+
+    assert_eq!(repo.claim_payout().await, Ok(Some(approved)));
+    assert_eq!(repo.claim_payout().await, Ok(None));
+
+    repo.release_payout(approved.id).await.expect("Released");
+    assert_eq!(repo.claim_payout().await, Ok(Some(approved)));
+
+    repo.record_payout(approved.id, 42).await.expect("Recorded");
+    assert_eq!(
+        repo.record_payout(approved.id, 43).await,
+        Err(Error::WithdrawalNotFound { id: approved.id })
+    );
+    assert_eq!(
+        repo.release_payout(approved.id).await,
+        Err(Error::WithdrawalNotFound { id: approved.id })
+    );
+    assert_eq!(repo.claim_payout().await, Ok(None));
+}
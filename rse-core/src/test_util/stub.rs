@@ -0,0 +1,983 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A repository for tests, knowing only about accounts, stocks and the orders placed on them
+
+use std::{
+    collections::HashMap,
+    num::NonZeroU64,
+    sync::{Arc, Mutex},
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use snafu::{OptionExt, ensure};
+use uuid::Uuid;
+
+use crate::{
+    model::{
+        AccountMerge, AccountSummary, Candle, CandleInterval, ClosedAccount, ExchangeCounts,
+        HoldingOrdering, HoldingPl, ImportedPrice, LatestPrice, LedgerKind, Movers, Page, Pager,
+        PoolUsage, PortfolioSnapshot, Price, PriceChange, Privacy, Registered, Shares, Statement,
+        StatementSnapshot, StockInfo, StockMatch, StockMetadata, StockOrdering, StockStatus,
+        UserFilter, UserInfo,
+        adjustment::{Adjustment, AdjustmentReason},
+        api_key::ApiKey,
+        audit::{Actor, AuditEntry, AuditFilter, NewAuditEntry},
+        deposit::Deposit,
+        dividend::{Dividend, Shareholders},
+        fee::FeeSchedule,
+        guild::GuildSettings,
+        idempotency::{IdempotencyKey, Idempotent, RequestHash, order_hash},
+        link::{Identity, LinkCode},
+        order::{
+            Book, BookLevel, Fill, NewOrder, Order, OrderStatus, Side, TickerTrade, UserTrade,
+        },
+        outbox::OutboxEntry,
+        reconcile::{AccountTotals, StockTotals},
+        snapshot::{SnapshotRecord, SnapshotSection},
+        summary::DailySummary,
+        ticker::Ticker,
+        usage::{CommandStats, CommandUse},
+        withdrawal::{Address, Withdrawal, WithdrawalStatus},
+    },
+    repo::{
+        AccountNotFoundSnafu, IdempotencyKeyReusedSnafu, InsufficientFundsSnafu, Result,
+        StockExistsSnafu, StockNotFoundSnafu, StockRepository, WithdrawalNotFoundSnafu,
+    },
+};
+
+/// Tracks accounts linked to Discord snowflakes and their API keys, listed stocks, orders placed
+/// on them, Kromer deposits and withdrawals and Discord server settings in memory, meeting the
+/// parts of the [`spec`](super::spec) that cover them. Nothing ever trades, so orders rest without
+/// fills and accounts hold only the Kromer deposited to them. Every other method panics, so wrap
+/// it in a [`ChaosRepo`](super::ChaosRepo) to fail them instead. Clones share state.
+#[derive(Debug, Clone, Default)]
+pub struct Stub {
+    accounts: Arc<Mutex<HashMap<NonZeroU64, Uuid>>>,
+    api_keys: Arc<Mutex<HashMap<Uuid, [u8; 32]>>>,
+    stocks: Arc<Mutex<HashMap<Ticker, StockInfo>>>,
+    orders: Arc<Mutex<Vec<Order>>>,
+    #[allow(clippy::type_complexity)]
+    idempotency_keys:
+        Arc<Mutex<HashMap<(Uuid, IdempotencyKey), (RequestHash, (Order, Vec<Fill>))>>>,
+    guilds: Arc<Mutex<HashMap<NonZeroU64, GuildSettings>>>,
+    deposits: Arc<Mutex<HashMap<i64, Deposit>>>,
+    kromer_cursors: Arc<Mutex<HashMap<Address, i64>>>,
+    withdrawals: Arc<Mutex<Vec<StubWithdrawal>>>,
+}
+
+/// A withdrawal request along with how far its payout has got
+#[derive(Debug, Clone, Copy)]
+struct StubWithdrawal {
+    withdrawal: Withdrawal,
+    claimed: bool,
+    transaction: Option<i64>,
+}
+
+impl Stub {
+    /// Whether `id` is a registered account
+    fn registered(&self, id: &Uuid) -> bool {
+        self.accounts
+            .lock()
+            .expect("Not poisoned")
+            .values()
+            .any(|user| user == id)
+    }
+
+    /// The Kromer deposited to `id` less what they've asked to withdraw, and what is held by their
+    /// pending withdrawals
+    fn balances(&self, id: &Uuid) -> (Decimal, Decimal) {
+        let deposited: Decimal = self
+            .deposits
+            .lock()
+            .expect("Not poisoned")
+            .values()
+            .filter(|deposit| deposit.user == *id)
+            .map(|deposit| deposit.amount)
+            .sum();
+
+        let withdrawals = self.withdrawals.lock().expect("Not poisoned");
+        let mine = || {
+            withdrawals
+                .iter()
+                .map(|w| w.withdrawal)
+                .filter(|w| w.user == *id)
+        };
+        let withdrawn: Decimal = mine()
+            .filter(|w| w.status != WithdrawalStatus::Denied)
+            .map(|w| w.amount)
+            .sum();
+        let held = mine()
+            .filter(|w| w.status == WithdrawalStatus::Pending)
+            .map(|w| w.amount)
+            .sum();
+
+        (deposited - withdrawn, held)
+    }
+}
+
+impl StockRepository for Stub {
+    async fn stock_exists(&self, stock: &Ticker) -> Result<bool> {
+        Ok(self
+            .stocks
+            .lock()
+            .expect("Not poisoned")
+            .contains_key(stock))
+    }
+
+    async fn discord_to_id(&self, id: NonZeroU64) -> Result<Option<Uuid>> {
+        Ok(self
+            .accounts
+            .lock()
+            .expect("Not poisoned")
+            .get(&id)
+            .copied())
+    }
+
+    async fn mc_to_id(&self, _id: &Uuid) -> Result<Option<Uuid>> {
+        Ok(None)
+    }
+
+    async fn mc_username_to_uuid(
+        &self,
+        _name: &str,
+        _since: DateTime<Utc>,
+    ) -> Result<Option<Uuid>> {
+        unimplemented!()
+    }
+
+    async fn cache_mc_username(&self, _name: &str, _id: &Uuid) -> Result<()> {
+        unimplemented!()
+    }
+
+    async fn user_info(&self, id: &Uuid) -> Result<Option<UserInfo>> {
+        let disc_id = self
+            .accounts
+            .lock()
+            .expect("Not poisoned")
+            .iter()
+            .find_map(|(disc_id, user)| (user == id).then_some(*disc_id));
+
+        let (available, held) = self.balances(id);
+
+        Ok(disc_id.map(|disc_id| UserInfo {
+            id: *id,
+            balance: Some(available + held),
+            available_balance: Some(available),
+            held_balance: Some(held),
+            created_at: DateTime::UNIX_EPOCH,
+            mc_id: None,
+            disc_id: Some(disc_id),
+            public_portfolio: false,
+            privacy: Privacy::default(),
+            closed_at: None,
+        }))
+    }
+
+    async fn set_privacy(&self, _id: &Uuid, _privacy: Privacy) -> Result<()> {
+        unimplemented!()
+    }
+
+    async fn set_public_portfolio(&self, _id: &Uuid, _public: bool) -> Result<()> {
+        unimplemented!()
+    }
+
+    async fn set_receipts(&self, _id: &Uuid, _enabled: bool) -> Result<()> {
+        unimplemented!()
+    }
+
+    async fn register_user(
+        &self,
+        disc_id: Option<NonZeroU64>,
+        _mc_id: Option<&Uuid>,
+        _actor: &Actor,
+    ) -> Result<Registered> {
+        let id = Uuid::from_u128(disc_id.map_or(0, NonZeroU64::get).into());
+
+        if let Some(disc_id) = disc_id {
+            let mut accounts = self.accounts.lock().expect("Not poisoned");
+
+            if let Some(existing) = accounts.get(&disc_id) {
+                return Ok(Registered::Existing(*existing));
+            }
+
+            accounts.insert(disc_id, id);
+        }
+
+        Ok(Registered::New(id))
+    }
+
+    async fn create_link_code(&self, _id: &Uuid, _expires_at: DateTime<Utc>) -> Result<LinkCode> {
+        unimplemented!()
+    }
+
+    async fn consume_link_code(
+        &self,
+        _code: &LinkCode,
+        _identity: Identity,
+        _now: DateTime<Utc>,
+    ) -> Result<Uuid> {
+        unimplemented!()
+    }
+
+    async fn purge_link_codes(&self, _before: DateTime<Utc>) -> Result<u64> {
+        unimplemented!()
+    }
+
+    async fn create_api_key(&self, id: &Uuid) -> Result<ApiKey> {
+        ensure!(self.registered(id), AccountNotFoundSnafu { id: *id });
+        let key = ApiKey::generate();
+        self.api_keys
+            .lock()
+            .expect("Not poisoned")
+            .insert(*id, key.hash());
+
+        Ok(key)
+    }
+
+    async fn api_key_owner(&self, key: &ApiKey) -> Result<Option<Uuid>> {
+        let hash = key.hash();
+
+        Ok(self
+            .api_keys
+            .lock()
+            .expect("Not poisoned")
+            .iter()
+            .find_map(|(id, stored)| (*stored == hash).then_some(*id)))
+    }
+
+    async fn close_account(
+        &self,
+        _id: &Uuid,
+        _payout: Option<&Address>,
+        _force: bool,
+        _actor: &Actor,
+    ) -> Result<ClosedAccount> {
+        unimplemented!()
+    }
+
+    async fn merge_accounts(
+        &self,
+        _survivor: &Uuid,
+        _casualty: &Uuid,
+        _dry_run: bool,
+        _actor: &Actor,
+    ) -> Result<AccountMerge> {
+        unimplemented!()
+    }
+
+    async fn create_stock(
+        &self,
+        ticker: &Ticker,
+        shares: Shares,
+        owner: &Uuid,
+        _actor: &Actor,
+    ) -> Result<StockInfo> {
+        let mut stocks = self.stocks.lock().expect("Not poisoned");
+        ensure!(
+            !stocks.contains_key(ticker),
+            StockExistsSnafu { ticker: *ticker }
+        );
+
+        let info = StockInfo {
+            ticker: *ticker,
+            owner: Some(*owner),
+            shares,
+            created_at: Utc::now(),
+            status: StockStatus::Active,
+            metadata: StockMetadata::default(),
+        };
+        stocks.insert(*ticker, info.clone());
+
+        Ok(info)
+    }
+
+    async fn user_exists(&self, id: &Uuid) -> Result<bool> {
+        Ok(self.registered(id))
+    }
+
+    async fn identities(
+        &self,
+        _ids: &[Uuid],
+    ) -> Result<Vec<(Uuid, Option<NonZeroU64>, Option<Uuid>)>> {
+        unimplemented!()
+    }
+
+    async fn ensure_system_account(&self, _id: &Uuid) -> Result<bool> {
+        unimplemented!()
+    }
+
+    async fn ping(&self) -> Result<()> {
+        unimplemented!()
+    }
+
+    fn pool_usage(&self) -> Option<PoolUsage> {
+        unimplemented!()
+    }
+
+    async fn schema_version(&self) -> Result<Option<i64>> {
+        unimplemented!()
+    }
+
+    async fn snapshot_chunk(
+        &self,
+        _section: SnapshotSection,
+        _after: Option<&SnapshotRecord>,
+        _limit: i64,
+    ) -> Result<Vec<SnapshotRecord>> {
+        unimplemented!()
+    }
+
+    async fn restore_snapshot(&self, _records: &[SnapshotRecord]) -> Result<()> {
+        unimplemented!()
+    }
+
+    async fn exchange_counts(&self) -> Result<ExchangeCounts> {
+        Ok(ExchangeCounts {
+            accounts: self.accounts.lock().expect("Not poisoned").len() as u64,
+            stocks: self.stocks.lock().expect("Not poisoned").len() as u64,
+        })
+    }
+
+    async fn get_holdings(
+        &self,
+        _id: &Uuid,
+        _page: &Pager,
+        _order: HoldingOrdering,
+    ) -> Result<Option<Page<(Ticker, Shares, Option<Price>)>>> {
+        unimplemented!()
+    }
+
+    async fn get_holdings_pl(
+        &self,
+        id: &Uuid,
+        page: &Pager,
+        _order: HoldingOrdering,
+    ) -> Result<Option<Page<HoldingPl>>> {
+        Ok(self.registered(id).then(|| Page::new(Vec::new(), 0, page)))
+    }
+
+    async fn holdings_value(&self, _id: &Uuid) -> Result<Decimal> {
+        unimplemented!()
+    }
+
+    async fn portfolio_values(&self, _ids: &[Uuid]) -> Result<Vec<(Uuid, Decimal)>> {
+        unimplemented!()
+    }
+
+    async fn account_summary(&self, _id: &Uuid, _recent: u32) -> Result<AccountSummary> {
+        unimplemented!()
+    }
+
+    async fn statement(
+        &self,
+        _id: &Uuid,
+        _page: &Pager,
+        _filter: Option<LedgerKind>,
+        _snapshot: Option<StatementSnapshot>,
+    ) -> Result<Statement> {
+        unimplemented!()
+    }
+
+    async fn trade_history(
+        &self,
+        _id: &Uuid,
+        _after: Option<i32>,
+        _limit: i64,
+    ) -> Result<Vec<UserTrade>> {
+        unimplemented!()
+    }
+
+    async fn ticker_trades(&self, _ticker: &Ticker, _page: &Pager) -> Result<Page<TickerTrade>> {
+        unimplemented!()
+    }
+
+    async fn top_movers(
+        &self,
+        _date: NaiveDate,
+        _until: DateTime<Utc>,
+        _count: u8,
+    ) -> Result<Movers> {
+        unimplemented!()
+    }
+
+    async fn daily_summary(&self, _date: NaiveDate) -> Result<DailySummary> {
+        unimplemented!()
+    }
+
+    async fn summary_sent(&self, _date: NaiveDate) -> Result<bool> {
+        unimplemented!()
+    }
+
+    async fn record_summary_sent(&self, _date: NaiveDate) -> Result<()> {
+        unimplemented!()
+    }
+
+    async fn record_daily_closes(&self, _date: NaiveDate) -> Result<u64> {
+        unimplemented!()
+    }
+
+    async fn record_portfolio_snapshots(
+        &self,
+        _date: NaiveDate,
+        _after: Option<&Uuid>,
+        _limit: u32,
+    ) -> Result<(Option<Uuid>, u64)> {
+        unimplemented!()
+    }
+
+    async fn portfolio_history(
+        &self,
+        _id: &Uuid,
+        _since: NaiveDate,
+    ) -> Result<Vec<PortfolioSnapshot>> {
+        unimplemented!()
+    }
+
+    async fn price_change(
+        &self,
+        _ticker: &Ticker,
+        _now: DateTime<Utc>,
+    ) -> Result<Option<PriceChange>> {
+        unimplemented!()
+    }
+
+    async fn candles(
+        &self,
+        _ticker: &Ticker,
+        _interval: CandleInterval,
+        _from: DateTime<Utc>,
+        _to: DateTime<Utc>,
+    ) -> Result<Vec<Candle>> {
+        unimplemented!()
+    }
+
+    async fn list_stocks(
+        &self,
+        page: &Pager,
+        _order: StockOrdering,
+        prefix: &str,
+    ) -> Result<
+        Option<
+            Page<(
+                Ticker,
+                Shares,
+                Option<Price>,
+                Option<DateTime<Utc>>,
+                Option<String>,
+            )>,
+        >,
+    > {
+        let mut stocks: Vec<_> = self
+            .stocks
+            .lock()
+            .expect("Not poisoned")
+            .values()
+            .filter(|info| info.ticker.as_str().starts_with(prefix))
+            .map(|info| {
+                (
+                    info.ticker,
+                    info.shares,
+                    None,
+                    None,
+                    info.metadata.name.clone(),
+                )
+            })
+            .collect();
+        stocks.sort_by(|(a, ..), (b, ..)| a.as_str().cmp(b.as_str()));
+
+        let total = stocks.len() as u64;
+        let items: Vec<_> = stocks
+            .into_iter()
+            .skip(page.offset().try_into().unwrap_or_default())
+            .take(page.limit().try_into().unwrap_or_default())
+            .collect();
+
+        Ok((!items.is_empty()).then(|| Page::new(items, total, page)))
+    }
+
+    async fn search_tickers(
+        &self,
+        _prefix: &str,
+        _limit: u32,
+    ) -> Result<Vec<(Ticker, Option<String>)>> {
+        unimplemented!()
+    }
+
+    async fn search_stocks(&self, _query: &str, _page: &Pager) -> Result<Page<StockMatch>> {
+        unimplemented!()
+    }
+
+    async fn list_users(&self, _page: &Pager, _filter: UserFilter) -> Result<Page<UserInfo>> {
+        unimplemented!()
+    }
+
+    async fn record_audit(&self, _entry: &NewAuditEntry) -> Result<()> {
+        unimplemented!()
+    }
+
+    async fn audit_log(&self, _page: &Pager, _filter: &AuditFilter) -> Result<Page<AuditEntry>> {
+        unimplemented!()
+    }
+
+    async fn place_order(
+        &self,
+        order: &NewOrder,
+        _fees: Option<&FeeSchedule>,
+        now: DateTime<Utc>,
+    ) -> Result<(Order, Vec<Fill>)> {
+        ensure!(
+            self.stocks
+                .lock()
+                .expect("Not poisoned")
+                .contains_key(&order.ticker),
+            StockNotFoundSnafu {
+                ticker: order.ticker
+            }
+        );
+        ensure!(
+            self.registered(&order.user),
+            AccountNotFoundSnafu { id: order.user }
+        );
+
+        let mut orders = self.orders.lock().expect("Not poisoned");
+        let placed = Order {
+            id: i32::try_from(orders.len()).expect("Few orders") + 1,
+            user: order.user,
+            ticker: order.ticker,
+            side: order.side,
+            price: order.price,
+            quantity: order.quantity,
+            remaining: order.quantity,
+            status: if order.immediate_or_cancel {
+                OrderStatus::Cancelled
+            } else {
+                OrderStatus::Open
+            },
+            created_at: now,
+            expires_at: order.expires_at,
+        };
+        orders.push(placed);
+
+        Ok((placed, Vec::new()))
+    }
+
+    async fn queue_order(
+        &self,
+        _order: &NewOrder,
+        _fees: Option<&FeeSchedule>,
+        _now: DateTime<Utc>,
+    ) -> Result<Order> {
+        unimplemented!()
+    }
+
+    async fn release_queued_orders(
+        &self,
+        _limit: u32,
+        _fees: Option<&FeeSchedule>,
+        _now: DateTime<Utc>,
+    ) -> Result<Vec<(Order, Vec<Fill>)>> {
+        unimplemented!()
+    }
+
+    async fn place_order_once(
+        &self,
+        key: &IdempotencyKey,
+        order: &NewOrder,
+        fees: Option<&FeeSchedule>,
+        _since: DateTime<Utc>,
+        _queue: bool,
+        now: DateTime<Utc>,
+    ) -> Result<Idempotent<(Order, Vec<Fill>)>> {
+        let hash = order_hash(order);
+        let sent = (order.user, key.clone());
+
+        if let Some((stored, placed)) = self
+            .idempotency_keys
+            .lock()
+            .expect("Not poisoned")
+            .get(&sent)
+        {
+            ensure!(*stored == hash, IdempotencyKeyReusedSnafu);
+            return Ok(Idempotent::Replayed(placed.clone()));
+        }
+
+        let placed = self.place_order(order, fees, now).await?;
+        self.idempotency_keys
+            .lock()
+            .expect("Not poisoned")
+            .insert(sent, (hash, placed.clone()));
+
+        Ok(Idempotent::Executed(placed))
+    }
+
+    async fn purge_idempotency_keys(&self, _before: DateTime<Utc>) -> Result<u64> {
+        unimplemented!()
+    }
+
+    async fn cancel_order(&self, _id: i32, _user: &Uuid) -> Result<Order> {
+        unimplemented!()
+    }
+
+    async fn expire_orders(&self, _now: DateTime<Utc>, _limit: u32) -> Result<Vec<Order>> {
+        unimplemented!()
+    }
+
+    async fn stock_info(&self, ticker: &Ticker) -> Result<StockInfo> {
+        self.stocks
+            .lock()
+            .expect("Not poisoned")
+            .get(ticker)
+            .cloned()
+            .context(StockNotFoundSnafu { ticker: *ticker })
+    }
+
+    async fn set_listing_price(&self, _ticker: &Ticker, _price: Price) -> Result<bool> {
+        unimplemented!()
+    }
+
+    async fn import_prices(&self, _prices: &[ImportedPrice]) -> Result<u64> {
+        unimplemented!()
+    }
+
+    async fn grant(&self, _id: &Uuid, _amount: Decimal, _actor: &Actor) -> Result<Decimal> {
+        unimplemented!()
+    }
+
+    async fn credit_deposit(&self, wallet: &Address, deposit: &Deposit) -> Result<Option<Decimal>> {
+        ensure!(
+            self.registered(&deposit.user),
+            AccountNotFoundSnafu { id: deposit.user }
+        );
+
+        {
+            let mut deposits = self.deposits.lock().expect("Not poisoned");
+            if deposits.contains_key(&deposit.transaction) {
+                return Ok(None);
+            }
+            deposits.insert(deposit.transaction, *deposit);
+        }
+
+        self.advance_kromer_cursor(wallet, deposit.transaction)
+            .await?;
+
+        Ok(Some(self.balances(&deposit.user).0))
+    }
+
+    async fn record_uncredited_deposit(&self, wallet: &Address, deposit: &Deposit) -> Result<()> {
+        self.advance_kromer_cursor(wallet, deposit.transaction)
+            .await
+    }
+
+    async fn kromer_cursor(&self, wallet: &Address) -> Result<Option<i64>> {
+        Ok(self
+            .kromer_cursors
+            .lock()
+            .expect("Not poisoned")
+            .get(wallet)
+            .copied())
+    }
+
+    async fn advance_kromer_cursor(&self, wallet: &Address, transaction: i64) -> Result<()> {
+        let mut cursors = self.kromer_cursors.lock().expect("Not poisoned");
+        let cursor = cursors.entry(*wallet).or_insert(transaction);
+        *cursor = (*cursor).max(transaction);
+
+        Ok(())
+    }
+
+    async fn adjust_balance(
+        &self,
+        _id: &Uuid,
+        _delta: Decimal,
+        _reason: AdjustmentReason,
+        _allow_negative: bool,
+        _actor: &Actor,
+    ) -> Result<Adjustment> {
+        unimplemented!()
+    }
+
+    async fn reverse_adjustment(
+        &self,
+        _id: i64,
+        _allow_negative: bool,
+        _actor: &Actor,
+    ) -> Result<Adjustment> {
+        unimplemented!()
+    }
+
+    async fn set_stock_status(
+        &self,
+        _ticker: &Ticker,
+        _status: StockStatus,
+        _actor: &Actor,
+    ) -> Result<StockInfo> {
+        unimplemented!()
+    }
+
+    async fn transfer_ownership(
+        &self,
+        _ticker: &Ticker,
+        _from: &Uuid,
+        _to: &Uuid,
+    ) -> Result<StockInfo> {
+        unimplemented!()
+    }
+
+    async fn update_stock_metadata(
+        &self,
+        _ticker: &Ticker,
+        _metadata: &StockMetadata,
+        _owner: &Uuid,
+    ) -> Result<StockInfo> {
+        unimplemented!()
+    }
+
+    async fn issue_shares(
+        &self,
+        _ticker: &Ticker,
+        _quantity: Shares,
+        _owner: &Uuid,
+        _daily_cap_pct: u16,
+    ) -> Result<StockInfo> {
+        unimplemented!()
+    }
+
+    async fn buyback(
+        &self,
+        _ticker: &Ticker,
+        _quantity: Shares,
+        _owner: &Uuid,
+    ) -> Result<StockInfo> {
+        unimplemented!()
+    }
+
+    async fn shareholders(&self, _ticker: &Ticker) -> Result<Shareholders> {
+        unimplemented!()
+    }
+
+    async fn pay_dividend(
+        &self,
+        _ticker: &Ticker,
+        _per_share: Decimal,
+        _payer: &Uuid,
+    ) -> Result<Dividend> {
+        unimplemented!()
+    }
+
+    async fn open_orders(&self, _user: &Uuid, _page: &Pager) -> Result<Page<Order>> {
+        unimplemented!()
+    }
+
+    async fn book(&self, _ticker: &Ticker, _depth: u32) -> Result<Book> {
+        unimplemented!()
+    }
+
+    async fn levels_against(
+        &self,
+        _ticker: &Ticker,
+        _side: Side,
+        _user: &Uuid,
+        _depth: u32,
+        _now: DateTime<Utc>,
+    ) -> Result<Vec<BookLevel>> {
+        unimplemented!()
+    }
+
+    async fn reference_price(
+        &self,
+        _ticker: &Ticker,
+        _since: DateTime<Utc>,
+        _close: DateTime<Utc>,
+    ) -> Result<Option<Price>> {
+        unimplemented!()
+    }
+
+    async fn latest_prices(&self, _tickers: Option<&[Ticker]>) -> Result<Vec<LatestPrice>> {
+        Ok(Vec::new())
+    }
+
+    async fn request_withdrawal(
+        &self,
+        user: &Uuid,
+        amount: Decimal,
+        address: &Address,
+        _actor: &Actor,
+    ) -> Result<Withdrawal> {
+        ensure!(self.registered(user), AccountNotFoundSnafu { id: *user });
+        ensure!(self.balances(user).0 >= amount, InsufficientFundsSnafu);
+
+        let mut withdrawals = self.withdrawals.lock().expect("Not poisoned");
+        let withdrawal = Withdrawal {
+            id: i64::try_from(withdrawals.len()).expect("Fewer than i64::MAX withdrawals") + 1,
+            user: *user,
+            amount,
+            address: *address,
+            status: WithdrawalStatus::Pending,
+            requested_at: Utc::now(),
+            resolved_at: None,
+        };
+        withdrawals.push(StubWithdrawal {
+            withdrawal,
+            claimed: false,
+            transaction: None,
+        });
+
+        Ok(withdrawal)
+    }
+
+    async fn pending_withdrawals(&self, _page: &Pager) -> Result<Page<Withdrawal>> {
+        unimplemented!()
+    }
+
+    async fn resolve_withdrawal(
+        &self,
+        id: i64,
+        approve: bool,
+        _actor: &Actor,
+    ) -> Result<Withdrawal> {
+        let mut withdrawals = self.withdrawals.lock().expect("Not poisoned");
+        let w = withdrawals
+            .iter_mut()
+            .map(|w| &mut w.withdrawal)
+            .find(|w| w.id == id && w.status == WithdrawalStatus::Pending)
+            .context(WithdrawalNotFoundSnafu { id })?;
+
+        w.status = if approve {
+            WithdrawalStatus::Processed
+        } else {
+            WithdrawalStatus::Denied
+        };
+        w.resolved_at = Some(Utc::now());
+
+        Ok(*w)
+    }
+
+    async fn claim_payout(&self) -> Result<Option<Withdrawal>> {
+        let mut withdrawals = self.withdrawals.lock().expect("Not poisoned");
+        let Some(w) = withdrawals
+            .iter_mut()
+            .find(|w| w.withdrawal.status == WithdrawalStatus::Processed && !w.claimed)
+        else {
+            return Ok(None);
+        };
+
+        w.claimed = true;
+        Ok(Some(w.withdrawal))
+    }
+
+    async fn record_payout(&self, id: i64, transaction: i64) -> Result<()> {
+        let mut withdrawals = self.withdrawals.lock().expect("Not poisoned");
+        let w = withdrawals
+            .iter_mut()
+            .find(|w| w.withdrawal.id == id && w.claimed && w.transaction.is_none())
+            .context(WithdrawalNotFoundSnafu { id })?;
+
+        w.transaction = Some(transaction);
+        Ok(())
+    }
+
+    async fn release_payout(&self, id: i64) -> Result<()> {
+        let mut withdrawals = self.withdrawals.lock().expect("Not poisoned");
+        let w = withdrawals
+            .iter_mut()
+            .find(|w| w.withdrawal.id == id && w.claimed && w.transaction.is_none())
+            .context(WithdrawalNotFoundSnafu { id })?;
+
+        w.claimed = false;
+        Ok(())
+    }
+
+    async fn enqueue_outbox(&self, _payload: &serde_json::Value) -> Result<()> {
+        unimplemented!()
+    }
+
+    async fn claim_outbox(
+        &self,
+        _now: DateTime<Utc>,
+        _until: DateTime<Utc>,
+        _limit: u32,
+    ) -> Result<Vec<OutboxEntry>> {
+        unimplemented!()
+    }
+
+    async fn outbox_sent(&self, _id: i64) -> Result<()> {
+        unimplemented!()
+    }
+
+    async fn outbox_failed(
+        &self,
+        _id: i64,
+        _error: &str,
+        _retry_at: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        unimplemented!()
+    }
+
+    async fn guild_settings(&self, guild: NonZeroU64) -> Result<Option<GuildSettings>> {
+        Ok(self
+            .guilds
+            .lock()
+            .expect("Not poisoned")
+            .get(&guild)
+            .cloned())
+    }
+
+    async fn all_guild_settings(&self) -> Result<Vec<(NonZeroU64, GuildSettings)>> {
+        let mut all: Vec<_> = self
+            .guilds
+            .lock()
+            .expect("Not poisoned")
+            .iter()
+            .map(|(guild, settings)| (*guild, settings.clone()))
+            .collect();
+        all.sort_by_key(|(guild, _)| *guild);
+
+        Ok(all)
+    }
+
+    async fn set_guild_settings(&self, guild: NonZeroU64, settings: &GuildSettings) -> Result<()> {
+        self.guilds
+            .lock()
+            .expect("Not poisoned")
+            .insert(guild, settings.clone());
+
+        Ok(())
+    }
+
+    async fn account_totals(
+        &self,
+        _after: Option<&Uuid>,
+        _limit: u32,
+    ) -> Result<Vec<AccountTotals>> {
+        unimplemented!()
+    }
+
+    async fn stock_totals(&self, _after: Option<&Ticker>, _limit: u32) -> Result<Vec<StockTotals>> {
+        unimplemented!()
+    }
+
+    async fn record_command_uses(&self, _uses: &[CommandUse]) -> Result<()> {
+        unimplemented!()
+    }
+
+    async fn command_stats(&self, _now: DateTime<Utc>) -> Result<Vec<CommandStats>> {
+        unimplemented!()
+    }
+}
@@ -0,0 +1,4553 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+//! Runs [`PgPort`] against a real Postgres, started in a container through Docker. Set
+//! `RSE_TEST_DATABASE_URL` to use an existing server instead, or `RSE_SKIP_PG_TESTS` to skip
+//! these tests entirely. Every test gets a fresh database of its own.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    num::{NonZeroU16, NonZeroU32, NonZeroU64},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, Utc, Weekday};
+use rse_core::{
+    Service,
+    blocklist::TickerBlocklist,
+    calendar::{AfterHours, MarketCalendar},
+    error::Error as ServiceError,
+    event::Event,
+    import::PriceFile,
+    limiter::AccountMaturity,
+    matching::PriceBand,
+    model::{
+        AccountSummary, Candle, CandleInterval, ExchangeCounts, HoldingOrdering, LedgerKind,
+        MergeConflict, Page, Pager, PortfolioSnapshot, Price, PriceChange, Privacy, Registered,
+        SearchField, Shares, Statement, StockMatch, StockMetadata, StockOrdering, StockStatus,
+        TransactionKind, UserFilter, UserLinks, UserOrdering,
+        adjustment::AdjustmentReason,
+        audit::{Action, Actor, AuditFilter, NewAuditEntry},
+        deposit::Deposit,
+        fee::FeeSchedule,
+        guild::{GuildSettings, Permission},
+        idempotency::IdempotencyKey,
+        link::{Identity, IdentityKind},
+        order::{NewOrder, OrderStatus, Receipt, Side},
+        outbox::Notice,
+        reconcile::Discrepancy,
+        ticker::Ticker,
+        usage::{CommandStats, CommandUse, UsageTotals},
+        withdrawal::{Address, WithdrawalStatus},
+    },
+    outbox::{DispatchPolicy, Notifier},
+    repo::{Error, PgPort, StockRepository},
+    seed::{Seed, SeedStock, SeedUser},
+    snapshot::{SnapshotCounts, SnapshotError},
+    test_util::{MockClock, spec},
+};
+use rust_decimal::Decimal;
+use sqlx::{PgPool, postgres::PgConnectOptions};
+use testcontainers_modules::{
+    postgres::Postgres,
+    testcontainers::{ContainerAsync, ImageExt, runners::AsyncRunner},
+};
+use tokio::sync::broadcast::Receiver;
+use uuid::Uuid;
+
+/// Skips every test when set
+const SKIP_VAR: &str = "RSE_SKIP_PG_TESTS";
+
+/// Runs against this server instead of starting a container when set
+const URL_VAR: &str = "RSE_TEST_DATABASE_URL";
+
+/// Numbers the databases created by this run, so tests sharing a server don't collide
+static NEXT_DB: AtomicUsize = AtomicUsize::new(0);
+
+struct TestDb {
+    repo: PgPort,
+    pool: PgPool,
+    /// Stopped once the test is over
+    _container: Option<ContainerAsync<Postgres>>,
+}
+
+/// Sets up a migrated database for a test, or returns [`None`] if the tests are being skipped
+async fn test_db() -> Option<TestDb> {
+    if std::env::var_os(SKIP_VAR).is_some() {
+        eprintln!("{SKIP_VAR} is set, skipping");
+        return None;
+    }
+
+    let (container, url) = if let Ok(url) = std::env::var(URL_VAR) {
+        (None, url)
+    } else {
+        let container = Postgres::default()
+            .with_tag("17-alpine")
+            .start()
+            .await
+            .unwrap_or_else(|err| {
+                panic!("Could not start Postgres, set {SKIP_VAR} to skip these tests: {err}")
+            });
+        let host = container.get_host().await.expect("Container has a host");
+        let port = container
+            .get_host_port_ipv4(5432)
+            .await
+            .expect("Postgres port is exposed");
+
+        (
+            Some(container),
+            format!("postgres://postgres:postgres@{host}:{port}/postgres"),
+        )
+    };
+
+    let name = format!("rse_test_{}", NEXT_DB.fetch_add(1, Ordering::SeqCst));
+    let admin = PgPool::connect(&url).await.expect("Connected");
+
+    // Left over if an earlier run failed before the test ended
+    sqlx::query(&format!("DROP DATABASE IF EXISTS {name} WITH (FORCE)"))
+        .execute(&admin)
+        .await
+        .expect("Dropped stale database");
+    sqlx::query(&format!("CREATE DATABASE {name}"))
+        .execute(&admin)
+        .await
+        .expect("Created database");
+    admin.close().await;
+
+    let options: PgConnectOptions = url.parse().expect("Valid database URL");
+    let pool = PgPool::connect_with(options.database(&name))
+        .await
+        .expect("Connected");
+
+    sqlx::migrate!("../migrations")
+        .run(&pool)
+        .await
+        .expect("Migrated");
+
+    Some(TestDb {
+        repo: PgPort::new(pool.clone()),
+        pool,
+        _container: container,
+    })
+}
+
+fn ticker(s: &str) -> Ticker {
+    Ticker::try_from(s).expect("Valid ticker")
+}
+
+fn shares(n: u32) -> Shares {
+    Shares::new(n).expect("Valid shares")
+}
+
+fn price(n: i64) -> Price {
+    Price::new(Decimal::from(n)).expect("Valid price")
+}
+
+async fn account(repo: &PgPort, disc_id: u64) -> Uuid {
+    let disc_id = NonZeroU64::new(disc_id).expect("Non-zero");
+
+    repo.register_user(Some(disc_id), None, &Actor::System)
+        .await
+        .expect("Registered")
+        .id()
+}
+
+/// Gives `user` some Kromer, which no repository method does by itself
+async fn fund(pool: &PgPool, user: &Uuid, amount: i64) {
+    sqlx::query("UPDATE users SET balance = balance + $2 WHERE user_id = $1")
+        .bind(user)
+        .bind(Decimal::from(amount))
+        .execute(pool)
+        .await
+        .expect("Funded");
+}
+
+fn order(user: Uuid, ticker: Ticker, side: Side, price: i64, quantity: u32) -> NewOrder {
+    NewOrder {
+        user,
+        ticker,
+        side,
+        price: self::price(price),
+        quantity: shares(quantity),
+        expires_at: None,
+        immediate_or_cancel: false,
+    }
+}
+
+/// Records a trade of `ticker` between `user` and themselves at `time`, keeping its latest price
+/// up to date as the repository would
+async fn trade(
+    pool: &PgPool,
+    user: &Uuid,
+    ticker: Ticker,
+    price: i64,
+    shares: i32,
+    time: DateTime<Utc>,
+) {
+    sqlx::query(
+        "WITH event AS (
+            INSERT INTO stock_events (seller_id, buyer_id, ticker, price, shares, time)
+            VALUES ($1, $1, $2, $3, $4, $5)
+            RETURNING ticker, price, time, event_id
+        )
+        INSERT INTO latest_prices (ticker, price, updated_at, event_id)
+        SELECT * FROM event
+        ON CONFLICT (ticker) DO UPDATE
+            SET price = EXCLUDED.price, updated_at = EXCLUDED.updated_at,
+                event_id = EXCLUDED.event_id
+            WHERE (latest_prices.updated_at, latest_prices.event_id)
+                < (EXCLUDED.updated_at, EXCLUDED.event_id)",
+    )
+    .bind(user)
+    .bind(ticker.as_str())
+    .bind(Decimal::from(price))
+    .bind(shares)
+    .bind(time)
+    .execute(pool)
+    .await
+    .expect("Traded");
+}
+
+/// Lists the details of every `action` in the audit log concerning `target`, oldest first
+async fn audited(repo: &PgPort, action: Action, target: &str) -> Vec<serde_json::Value> {
+    let mut entries = repo
+        .audit_log(
+            &Pager::new(0, 50),
+            &AuditFilter {
+                action: Some(action),
+                target: Some(target.to_owned()),
+                ..AuditFilter::default()
+            },
+        )
+        .await
+        .expect("Listed")
+        .items;
+    entries.reverse();
+
+    entries.into_iter().map(|entry| entry.details).collect()
+}
+
+/// Takes every event published since the last call
+fn published(events: &mut Receiver<Event>) -> Vec<Event> {
+    std::iter::from_fn(|| events.try_recv().ok()).collect()
+}
+
+/// Lists `ticker` with 100 shares, returning the fresh account that owns them
+async fn listed(repo: &PgPort, ticker: Ticker) -> Uuid {
+    let owner = account(repo, 1).await;
+
+    repo.create_stock(&ticker, shares(100), &owner, &Actor::System)
+        .await
+        .expect("Listed");
+
+    owner
+}
+
+#[tokio::test]
+async fn spec_registered_accounts_are_found() {
+    let Some(db) = test_db().await else { return };
+    spec::registered_accounts_are_found(&db.repo).await;
+}
+
+#[tokio::test]
+async fn spec_linking_twice_returns_the_original() {
+    let Some(db) = test_db().await else { return };
+    spec::linking_twice_returns_the_original(&db.repo).await;
+}
+
+#[tokio::test]
+async fn spec_racing_registrations_agree() {
+    let Some(db) = test_db().await else { return };
+    spec::racing_registrations_agree(&db.repo).await;
+}
+
+#[tokio::test]
+async fn spec_listed_stocks_exist() {
+    let Some(db) = test_db().await else { return };
+    spec::listed_stocks_exist(&db.repo).await;
+}
+
+#[tokio::test]
+async fn spec_listing_twice_is_rejected() {
+    let Some(db) = test_db().await else { return };
+    spec::listing_twice_is_rejected(&db.repo).await;
+}
+
+#[tokio::test]
+async fn spec_api_keys_find_their_account() {
+    let Some(db) = test_db().await else { return };
+    spec::api_keys_find_their_account(&db.repo).await;
+}
+
+#[tokio::test]
+async fn spec_idempotent_orders_are_placed_once() {
+    let Some(db) = test_db().await else { return };
+    spec::idempotent_orders_are_placed_once(&db.repo).await;
+}
+
+#[tokio::test]
+async fn spec_deposits_move_the_cursor() {
+    let Some(db) = test_db().await else { return };
+    spec::deposits_move_the_cursor(&db.repo).await;
+}
+
+#[tokio::test]
+async fn spec_payouts_are_claimed_once() {
+    let Some(db) = test_db().await else { return };
+    spec::payouts_are_claimed_once(&db.repo).await;
+}
+
+#[tokio::test]
+async fn pings_measure_the_round_trip() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+
+    db.repo.ping().await.expect("Ping");
+    let latency = service.ping(Duration::from_secs(2)).await.expect("Ping");
+
+    assert!(latency < Duration::from_secs(2));
+}
+
+#[tokio::test]
+async fn guild_settings_default_until_saved() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let guild = NonZeroU64::new(u64::MAX).expect("Non-zero");
+
+    assert_eq!(
+        service.guild_settings(guild).await,
+        Ok(GuildSettings::default())
+    );
+
+    let mut settings = GuildSettings {
+        market_feed_channel: NonZeroU64::new(2),
+        trading_enabled: false,
+        locale: Some("fr".to_owned()),
+        admin_role: NonZeroU64::new(u64::MAX - 1),
+        role_permissions: BTreeMap::from([
+            (
+                NonZeroU64::new(3).expect("Non-zero"),
+                BTreeSet::from([Permission::Halt, Permission::ViewAdmin]),
+            ),
+            (
+                NonZeroU64::new(u64::MAX - 2).expect("Non-zero"),
+                BTreeSet::from([Permission::ManageBalances]),
+            ),
+        ]),
+    };
+    service
+        .set_guild_settings(guild, &settings)
+        .await
+        .expect("Saved");
+    service
+        .set_guild_settings(NonZeroU64::MIN, &GuildSettings::default())
+        .await
+        .expect("Saved");
+
+    assert_eq!(service.guild_settings(guild).await, Ok(settings.clone()));
+
+    // Revoking replaces what was granted rather than adding to it
+    settings.role_permissions.retain(|role, _| role.get() == 3);
+    service
+        .set_guild_settings(guild, &settings)
+        .await
+        .expect("Saved");
+    assert_eq!(service.guild_settings(guild).await, Ok(settings.clone()));
+
+    let mut all = service.all_guild_settings().await.expect("Lookup");
+    all.sort_by_key(|(guild, _)| *guild);
+    assert_eq!(
+        all,
+        vec![
+            (NonZeroU64::MIN, GuildSettings::default()),
+            (guild, settings)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn command_stats_total_the_last_day_and_week() {
+    let Some(db) = test_db().await else { return };
+    let now = Utc::now();
+    let used = |command: &str, success, days_ago| CommandUse {
+        command: command.to_owned(),
+        guild: (days_ago % 2 == 0).then_some(NonZeroU64::MIN),
+        success,
+        duration: Duration::from_millis(120),
+        time: now - TimeDelta::hours(1) - TimeDelta::days(days_ago),
+    };
+
+    db.repo
+        .record_command_uses(&[
+            used("me", true, 0),
+            used("me", false, 0),
+            used("me", true, 3),
+            used("order", false, 6),
+            used("order", true, 6),
+            used("order", true, 5),
+            used("order", true, 4),
+            used("stocks", true, 8),
+        ])
+        .await
+        .expect("Recorded");
+
+    assert_eq!(
+        db.repo.command_stats(now).await,
+        Ok(vec![
+            CommandStats {
+                command: "order".to_owned(),
+                day: UsageTotals::default(),
+                week: UsageTotals {
+                    uses: 4,
+                    failures: 1
+                },
+            },
+            CommandStats {
+                command: "me".to_owned(),
+                day: UsageTotals {
+                    uses: 2,
+                    failures: 1
+                },
+                week: UsageTotals {
+                    uses: 3,
+                    failures: 1
+                },
+            },
+        ])
+    );
+}
+
+#[tokio::test]
+async fn racing_retries_with_one_key_place_one_order() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 1000).await;
+    service
+        .place_order(&owner, &abc, Side::Sell, price(10), shares(50), None)
+        .await
+        .expect("Placed");
+    let mut events = service.subscribe();
+
+    let key = IdempotencyKey::new("buy-abc-1").expect("Valid key");
+    let buy = order(buyer, abc, Side::Buy, 10, 20);
+    let (first, second) = tokio::join!(
+        service.place_order_once(&key, &buy),
+        service.place_order_once(&key, &buy)
+    );
+    let (first, second) = (first.expect("Placed"), second.expect("Placed"));
+
+    assert_ne!(first.is_replay(), second.is_replay());
+    let ((first, first_fills), (second, second_fills)) = (first.into_inner(), second.into_inner());
+    assert_eq!(first.id, second.id);
+    assert_eq!(first_fills, second_fills);
+    assert_eq!(first_fills.len(), 1);
+    assert_eq!(published(&mut events).len(), 1);
+
+    let orders: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM orders WHERE user_id = $1")
+        .bind(buyer)
+        .fetch_one(&db.pool)
+        .await
+        .expect("Counted");
+    assert_eq!(orders, 1);
+
+    assert_eq!(
+        service
+            .place_order_once(&key, &order(buyer, abc, Side::Buy, 10, 21))
+            .await
+            .err(),
+        Some(ServiceError::IdempotencyKeyReused)
+    );
+
+    assert_eq!(
+        service
+            .purge_idempotency_keys(Utc::now() + TimeDelta::days(2))
+            .await,
+        Ok(1)
+    );
+    let again = service.place_order_once(&key, &buy).await.expect("Placed");
+    assert!(!again.is_replay());
+}
+
+#[tokio::test]
+async fn reserved_tickers_are_never_listed() {
+    let Some(db) = test_db().await else { return };
+    let service =
+        Service::new(db.repo.clone()).with_ticker_blocklist(TickerBlocklist::new(["b4d"]));
+    let owner = account(&db.repo, 1).await;
+
+    for (t, reason) in [
+        ("admin", "it is reserved by the exchange"),
+        ("XBADX", "it contains a blocked word"),
+    ] {
+        assert_eq!(
+            service
+                .create_stock(&ticker(t), shares(100), &owner, &Actor::System)
+                .await
+                .map(|info| info.ticker),
+            Err(ServiceError::TickerReserved {
+                ticker: ticker(t),
+                reason,
+            })
+        );
+        assert!(!db.repo.stock_exists(&ticker(t)).await.expect("Lookup"));
+    }
+
+    service
+        .create_stock(&ticker("BDA"), shares(100), &owner, &Actor::System)
+        .await
+        .expect("Listed");
+}
+
+#[tokio::test]
+async fn reconciliation_catches_corrupted_books() {
+    let Some(db) = test_db().await else { return };
+    let treasury = Uuid::from_u128(1);
+    let service = Service::new(db.repo.clone()).with_fees(FeeSchedule { bps: 100, treasury });
+    service.ensure_treasury().await.expect("Treasury created");
+
+    let abc = ticker("ABC");
+    let owner = account(&db.repo, 1).await;
+    let buyer = account(&db.repo, 2).await;
+    service
+        .create_stock(&abc, shares(100), &owner, &Actor::System)
+        .await
+        .expect("Listed");
+    service
+        .grant(&buyer, Decimal::from(1000), &Actor::System)
+        .await
+        .expect("Granted");
+
+    // A partial fill, leaving both a buy and a sell order resting with escrow behind them
+    service
+        .place_order(&owner, &abc, Side::Sell, price(10), shares(30), None)
+        .await
+        .expect("Placed");
+    service
+        .place_order(&buyer, &abc, Side::Buy, price(10), shares(20), None)
+        .await
+        .expect("Placed");
+    service
+        .place_order(&buyer, &abc, Side::Buy, price(5), shares(10), None)
+        .await
+        .expect("Placed");
+
+    // Enough accounts to need more than one chunk
+    sqlx::query("INSERT INTO users (disc_id) SELECT generate_series(1000, 1600)")
+        .execute(&db.pool)
+        .await
+        .expect("Registered");
+
+    let mut events = service.subscribe();
+    let report = service.reconcile().await.expect("Reconciled");
+    assert_eq!((report.accounts, report.stocks), (604, 1));
+    assert_eq!(report.discrepancies, []);
+    assert!(published(&mut events).is_empty());
+
+    for corruption in [
+        "UPDATE users SET balance = balance + 1 WHERE disc_id = 1",
+        "UPDATE users SET escrow = escrow - 5, balance = balance + 5 WHERE disc_id = 2",
+        "UPDATE holdings SET escrow = escrow + 1, shares = shares + 100
+        WHERE user_id = (SELECT user_id FROM users WHERE disc_id = 1)",
+    ] {
+        sqlx::query(corruption)
+            .execute(&db.pool)
+            .await
+            .expect("Corrupted");
+    }
+
+    let report = service.reconcile().await.expect("Reconciled");
+    let mut expected = vec![
+        Discrepancy::Balance {
+            user: owner,
+            held: Decimal::from(201),
+            expected: Decimal::from(200),
+        },
+        Discrepancy::Escrow {
+            user: buyer,
+            escrow: Decimal::new(4550, 2),
+            open_orders: Decimal::new(5050, 2),
+        },
+        Discrepancy::OverHeld {
+            ticker: abc,
+            held: 201,
+            issued: 100,
+        },
+        Discrepancy::ShareEscrow {
+            ticker: abc,
+            escrow: 11,
+            open_orders: 10,
+        },
+    ];
+    // Accounts are reported in order of ID
+    if buyer < owner {
+        expected.swap(0, 1);
+    }
+    assert_eq!(report.discrepancies, expected);
+    assert!(matches!(
+        &published(&mut events)[..],
+        [Event::ReconciliationFailed(published)] if *published == report
+    ));
+}
+
+#[tokio::test]
+async fn mc_usernames_are_cached_case_insensitively() {
+    let Some(db) = test_db().await else { return };
+    let mc_id = Uuid::from_u128(1);
+    let before = Utc::now() - TimeDelta::seconds(1);
+
+    db.repo
+        .cache_mc_username("Notch", &mc_id)
+        .await
+        .expect("Cache");
+
+    let cached = db.repo.mc_username_to_uuid("NOTCH", before).await;
+    let stale = db
+        .repo
+        .mc_username_to_uuid("notch", Utc::now() + TimeDelta::seconds(1))
+        .await;
+
+    assert_eq!(cached.expect("Lookup"), Some(mc_id));
+    assert_eq!(stale.expect("Lookup"), None);
+
+    // Names change hands, so a later resolution replaces the old one
+    let new_id = Uuid::from_u128(2);
+    db.repo
+        .cache_mc_username("notch", &new_id)
+        .await
+        .expect("Cache");
+
+    let cached = db.repo.mc_username_to_uuid("Notch", before).await;
+    assert_eq!(cached.expect("Lookup"), Some(new_id));
+}
+
+#[tokio::test]
+async fn user_info_round_trips() {
+    let Some(db) = test_db().await else { return };
+    let before = Utc::now();
+
+    let id = account(&db.repo, 1).await;
+    let info = db
+        .repo
+        .user_info(&id)
+        .await
+        .expect("Lookup")
+        .expect("Registered");
+
+    assert_eq!(info.id, id);
+    assert_eq!(info.disc_id, Some(NonZeroU64::MIN));
+    assert_eq!(info.mc_id, None);
+    assert_eq!(info.balance, Some(Decimal::ZERO));
+    assert!(info.created_at >= before - TimeDelta::seconds(5) && info.created_at <= Utc::now());
+    assert!(!info.public_portfolio);
+    assert_eq!(info.privacy, Privacy::HoldingsOnly);
+    assert_eq!(db.repo.user_exists(&id).await, Ok(true));
+
+    db.repo
+        .set_public_portfolio(&id, true)
+        .await
+        .expect("Opted in");
+    let info = db
+        .repo
+        .user_info(&id)
+        .await
+        .expect("Lookup")
+        .expect("Registered");
+    assert!(info.public_portfolio);
+    assert_eq!(
+        db.repo.set_public_portfolio(&Uuid::nil(), true).await,
+        Err(Error::AccountNotFound { id: Uuid::nil() })
+    );
+
+    assert!(
+        db.repo
+            .user_info(&Uuid::nil())
+            .await
+            .expect("Lookup")
+            .is_none()
+    );
+    assert_eq!(db.repo.user_exists(&Uuid::nil()).await, Ok(false));
+}
+
+#[tokio::test]
+async fn privacy_hides_accounts_from_others() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let owner = account(&db.repo, 1).await;
+    let other = account(&db.repo, 2).await;
+    let page = Pager::new(0, 10);
+
+    let visible = async |viewer: Option<&Uuid>| {
+        let info = service
+            .get_account_info(&owner, viewer)
+            .await
+            .expect("Exists");
+        let holdings = service
+            .get_holdings(&owner, &page, HoldingOrdering::Ticker, viewer)
+            .await;
+        (info.balance.is_some(), holdings.is_ok())
+    };
+
+    assert_eq!(visible(Some(&owner)).await, (true, true));
+    assert_eq!(visible(Some(&other)).await, (false, true));
+    assert_eq!(visible(None).await, (false, true));
+
+    for (privacy, expected) in [
+        (Privacy::Public, (true, true)),
+        (Privacy::Private, (false, false)),
+    ] {
+        service.set_privacy(&owner, privacy).await.expect("Updated");
+        assert_eq!(service.get_privacy(&owner).await, Ok(privacy));
+        assert_eq!(visible(Some(&other)).await, expected);
+        assert_eq!(visible(Some(&owner)).await, (true, true));
+    }
+
+    assert_eq!(
+        service.get_holdings_value(&owner, None).await,
+        Err(ServiceError::PrivateAccount)
+    );
+    assert_eq!(
+        service.set_privacy(&Uuid::nil(), Privacy::Public).await,
+        Err(ServiceError::UserNotFound {
+            kind: IdentityKind::Internal
+        })
+    );
+}
+
+#[tokio::test]
+async fn snowflakes_round_trip_whole_range() {
+    let Some(db) = test_db().await else { return };
+    let max = i64::MAX.cast_unsigned();
+
+    for flake in [1, max - 1, max, max + 1, u64::MAX - 1, u64::MAX] {
+        let id = account(&db.repo, flake).await;
+        let flake = NonZeroU64::new(flake);
+
+        assert_eq!(
+            db.repo.discord_to_id(flake.expect("Non-zero")).await,
+            Ok(Some(id))
+        );
+        assert_eq!(
+            db.repo
+                .user_info(&id)
+                .await
+                .map(|v| v.and_then(|v| v.disc_id)),
+            Ok(flake)
+        );
+    }
+}
+
+#[tokio::test]
+async fn identities_resolve_in_bulk() {
+    let Some(db) = test_db().await else { return };
+    let mc_id = Uuid::from_u128(42);
+
+    let discord = account(&db.repo, u64::MAX).await;
+    let minecraft = db
+        .repo
+        .register_user(None, Some(&mc_id), &Actor::Minecraft(mc_id))
+        .await
+        .expect("Registered")
+        .id();
+
+    let mut found = db
+        .repo
+        .identities(&[minecraft, Uuid::nil(), discord])
+        .await
+        .expect("Lookup");
+    found.sort_by_key(|(id, _, _)| *id == minecraft);
+
+    assert_eq!(
+        found,
+        [
+            (discord, Some(NonZeroU64::MAX), None),
+            (minecraft, None, Some(mc_id))
+        ]
+    );
+    assert_eq!(db.repo.identities(&[]).await, Ok(Vec::new()));
+}
+
+#[tokio::test]
+async fn every_identity_resolves_to_its_account() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let disc_id = NonZeroU64::new(7).expect("Non-zero");
+    let mc_id = Uuid::from_u128(42);
+
+    let id = db
+        .repo
+        .register_user(Some(disc_id), Some(&mc_id), &Actor::System)
+        .await
+        .expect("Registered")
+        .id();
+
+    for identity in [
+        Identity::Discord(disc_id),
+        Identity::Minecraft(mc_id),
+        Identity::Internal(id),
+    ] {
+        assert_eq!(service.resolve(&identity).await, Ok(id));
+
+        let info = service
+            .account_info_by(&identity, Some(&id))
+            .await
+            .expect("Lookup");
+        assert_eq!(
+            (info.id, info.disc_id, info.mc_id),
+            (id, Some(disc_id), Some(mc_id))
+        );
+    }
+
+    for identity in [
+        Identity::Discord(NonZeroU64::MIN),
+        Identity::Minecraft(Uuid::nil()),
+        Identity::Internal(Uuid::nil()),
+    ] {
+        assert_eq!(
+            service.resolve(&identity).await,
+            Err(ServiceError::UserNotFound {
+                kind: identity.kind()
+            })
+        );
+    }
+}
+
+#[tokio::test]
+async fn minecraft_accounts_link_once() {
+    let Some(db) = test_db().await else { return };
+    let mc_id = Uuid::from_u128(42);
+
+    let id = db
+        .repo
+        .register_user(None, Some(&mc_id), &Actor::Minecraft(mc_id))
+        .await
+        .expect("Registered")
+        .id();
+
+    assert_eq!(db.repo.mc_to_id(&mc_id).await, Ok(Some(id)));
+    assert_eq!(
+        db.repo
+            .register_user(None, Some(&mc_id), &Actor::Minecraft(mc_id))
+            .await,
+        Ok(Registered::Existing(id))
+    );
+
+    let info = db
+        .repo
+        .user_info(&id)
+        .await
+        .expect("Lookup")
+        .expect("Registered");
+    assert_eq!(info.mc_id, Some(mc_id));
+    assert_eq!(info.disc_id, None);
+}
+
+#[tokio::test]
+async fn system_accounts_are_created_once() {
+    let Some(db) = test_db().await else { return };
+    let id = Uuid::from_u128(7);
+
+    assert_eq!(db.repo.ensure_system_account(&id).await, Ok(true));
+    assert_eq!(db.repo.ensure_system_account(&id).await, Ok(false));
+    assert_eq!(db.repo.user_exists(&id).await, Ok(true));
+}
+
+#[tokio::test]
+async fn holdings_page_across_boundaries() {
+    let Some(db) = test_db().await else { return };
+    let owner = account(&db.repo, 1).await;
+    let other = account(&db.repo, 2).await;
+
+    for t in ["EEE", "AAA", "DDD", "BBB", "CCC"] {
+        db.repo
+            .create_stock(&ticker(t), shares(100), &owner, &Actor::System)
+            .await
+            .expect("Listed");
+    }
+
+    for (offset, expected) in [
+        (0, &["AAA", "BBB"][..]),
+        (2, &["CCC", "DDD"]),
+        (4, &["EEE"]),
+        (6, &[]),
+    ] {
+        let Page {
+            items: holdings,
+            total,
+            ..
+        } = db
+            .repo
+            .get_holdings(&owner, &Pager::new(offset, 2), HoldingOrdering::Ticker)
+            .await
+            .expect("Lookup")
+            .expect("Has holdings");
+        let tickers: Vec<_> = holdings.iter().map(|(t, _, _)| t.to_string()).collect();
+
+        assert_eq!(tickers, expected, "offset {offset}");
+        assert!(
+            holdings
+                .iter()
+                .all(|(_, held, price)| *held == shares(100) && price.is_none())
+        );
+        assert_eq!(total, 5);
+
+        let Page {
+            items: holdings,
+            total,
+            ..
+        } = db
+            .repo
+            .get_holdings_pl(&owner, &Pager::new(offset, 2), HoldingOrdering::Ticker)
+            .await
+            .expect("Lookup")
+            .expect("Has holdings");
+
+        assert_eq!(holdings.len(), expected.len());
+        assert_eq!(total, 5);
+    }
+
+    let Page {
+        items: holdings,
+        total,
+        ..
+    } = db
+        .repo
+        .get_holdings(&other, &Pager::new(0, 2), HoldingOrdering::Ticker)
+        .await
+        .expect("Lookup")
+        .expect("Has holdings");
+    assert!(holdings.is_empty());
+    assert_eq!(total, 0);
+}
+
+#[tokio::test]
+async fn stocks_page_across_boundaries() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    assert_eq!(
+        service.all_stocks(StockOrdering::Ticker).await,
+        Ok(Vec::new())
+    );
+
+    assert!(
+        db.repo
+            .list_stocks(&Pager::new(0, 2), StockOrdering::Ticker, "")
+            .await
+            .expect("Lookup")
+            .is_none()
+    );
+
+    let owner = account(&db.repo, 1).await;
+    for t in ["CCC", "AAA", "BBB"] {
+        db.repo
+            .create_stock(&ticker(t), shares(100), &owner, &Actor::System)
+            .await
+            .expect("Listed");
+    }
+
+    let Page {
+        items: stocks,
+        total,
+        ..
+    } = db
+        .repo
+        .list_stocks(&Pager::new(2, 2), StockOrdering::Ticker, "")
+        .await
+        .expect("Lookup")
+        .expect("Stocks exist");
+    assert_eq!(stocks.len(), 1);
+    assert_eq!(stocks[0].0, ticker("CCC"));
+    assert_eq!(total, 3);
+
+    let all: Vec<_> = service
+        .all_stocks(StockOrdering::Ticker)
+        .await
+        .expect("Lookup")
+        .into_iter()
+        .map(|(ticker, ..)| ticker)
+        .collect();
+    assert_eq!(all, [ticker("AAA"), ticker("BBB"), ticker("CCC")]);
+
+    assert!(
+        db.repo
+            .list_stocks(&Pager::new(4, 2), StockOrdering::Ticker, "")
+            .await
+            .expect("Lookup")
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn holdings_sort_in_sql() {
+    let Some(db) = test_db().await else { return };
+    let owner = account(&db.repo, 1).await;
+    let now = Utc::now();
+
+    for (t, n) in [("AAA", 10), ("BBB", 30), ("CCC", 20)] {
+        db.repo
+            .create_stock(&ticker(t), shares(n), &owner, &Actor::System)
+            .await
+            .expect("Listed");
+    }
+    trade(&db.pool, &owner, ticker("AAA"), 5, 1, now).await;
+    trade(&db.pool, &owner, ticker("BBB"), 1, 1, now).await;
+
+    for (order, expected) in [
+        (HoldingOrdering::Ticker, ["AAA", "BBB", "CCC"]),
+        (HoldingOrdering::Shares, ["BBB", "CCC", "AAA"]),
+        (HoldingOrdering::Value, ["AAA", "BBB", "CCC"]),
+    ] {
+        for (offset, expected) in (0..).zip(expected) {
+            let Page {
+                items: holdings,
+                total,
+                ..
+            } = db
+                .repo
+                .get_holdings(&owner, &Pager::new(offset, 1), order)
+                .await
+                .expect("Lookup")
+                .expect("Has holdings");
+            assert_eq!(holdings[0].0, ticker(expected), "{order:?}");
+            assert_eq!(total, 3);
+
+            let Page {
+                items: holdings, ..
+            } = db
+                .repo
+                .get_holdings_pl(&owner, &Pager::new(offset, 1), order)
+                .await
+                .expect("Lookup")
+                .expect("Has holdings");
+            assert_eq!(holdings[0].ticker, ticker(expected), "{order:?}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn stocks_sort_and_filter_in_sql() {
+    let Some(db) = test_db().await else { return };
+    let owner = account(&db.repo, 1).await;
+    let now = Utc::now();
+
+    for t in ["AAA", "ABB", "BBB"] {
+        db.repo
+            .create_stock(&ticker(t), shares(100), &owner, &Actor::System)
+            .await
+            .expect("Listed");
+    }
+    trade(
+        &db.pool,
+        &owner,
+        ticker("AAA"),
+        10,
+        1,
+        now - TimeDelta::days(2),
+    )
+    .await;
+    trade(&db.pool, &owner, ticker("AAA"), 20, 1, now).await;
+    trade(
+        &db.pool,
+        &owner,
+        ticker("ABB"),
+        30,
+        5,
+        now - TimeDelta::hours(1),
+    )
+    .await;
+    trade(&db.pool, &owner, ticker("ABB"), 30, 5, now).await;
+
+    for (order, prefix, expected) in [
+        (StockOrdering::Ticker, "", &["AAA", "ABB", "BBB"][..]),
+        (StockOrdering::Price, "", &["ABB", "AAA", "BBB"]),
+        (StockOrdering::Volume, "", &["ABB", "AAA", "BBB"]),
+        (StockOrdering::Change, "", &["AAA", "ABB", "BBB"]),
+        (StockOrdering::Price, "A", &["ABB", "AAA"]),
+        (StockOrdering::Ticker, "AB", &["ABB"]),
+    ] {
+        let mut tickers = Vec::new();
+
+        for offset in (0..).take(expected.len()) {
+            let Page {
+                items: stocks,
+                total,
+                ..
+            } = db
+                .repo
+                .list_stocks(&Pager::new(offset, 1), order, prefix)
+                .await
+                .expect("Lookup")
+                .expect("Stocks match");
+            assert_eq!(
+                usize::try_from(total),
+                Ok(expected.len()),
+                "{order:?} {prefix:?}"
+            );
+            tickers.push(stocks[0].0.to_string());
+        }
+
+        assert_eq!(tickers, expected, "{order:?} {prefix:?}");
+    }
+
+    assert!(
+        db.repo
+            .list_stocks(&Pager::new(0, 1), StockOrdering::Ticker, "C")
+            .await
+            .expect("Lookup")
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn trade_history_pages_by_id() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 1_000).await;
+
+    db.repo
+        .place_order(&order(seller, abc, Side::Sell, 10, 4), None, Utc::now())
+        .await
+        .expect("Placed");
+    for _ in 0..3 {
+        db.repo
+            .place_order(&order(buyer, abc, Side::Buy, 10, 1), None, Utc::now())
+            .await
+            .expect("Placed");
+    }
+
+    let history = db
+        .repo
+        .trade_history(&buyer, None, 2)
+        .await
+        .expect("Lookup");
+    assert_eq!(history.len(), 2);
+    assert!(
+        history
+            .iter()
+            .all(|t| t.side == Side::Buy && t.shares.get() == 1)
+    );
+    assert!(history[0].id < history[1].id);
+
+    let rest = db
+        .repo
+        .trade_history(&buyer, Some(history[1].id), 2)
+        .await
+        .expect("Lookup");
+    assert_eq!(rest.len(), 1);
+    assert!(rest[0].id > history[1].id);
+
+    let sold = db
+        .repo
+        .trade_history(&seller, None, 10)
+        .await
+        .expect("Lookup");
+    assert_eq!(sold.len(), 3);
+    assert!(sold.iter().all(|t| t.side == Side::Sell && t.fee.is_zero()));
+
+    let other = account(&db.repo, 3).await;
+    assert_eq!(
+        db.repo.trade_history(&other, None, 10).await,
+        Ok(Vec::new())
+    );
+}
+
+#[tokio::test]
+async fn portfolio_values_match_each_users_holdings() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 100).await;
+
+    db.repo
+        .place_order(&order(seller, abc, Side::Sell, 10, 4), None, Utc::now())
+        .await
+        .expect("Placed");
+    db.repo
+        .place_order(&order(buyer, abc, Side::Buy, 10, 3), None, Utc::now())
+        .await
+        .expect("Placed");
+
+    let seller_value = db.repo.holdings_value(&seller).await.expect("Lookup");
+    assert_eq!(
+        db.repo
+            .portfolio_values(&[buyer, Uuid::nil(), seller])
+            .await,
+        Ok(vec![
+            (buyer, Decimal::from(30)),
+            (Uuid::nil(), Decimal::ZERO),
+            (seller, seller_value),
+        ])
+    );
+    assert_eq!(db.repo.portfolio_values(&[]).await, Ok(Vec::new()));
+}
+
+#[tokio::test]
+async fn latest_prices_follow_random_trades() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let mut rng = fastrand::Rng::with_seed(1339);
+    let tickers = [ticker("ABC"), ticker("DEF"), ticker("GHI")];
+    let seller = account(&db.repo, 1).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 10_000).await;
+
+    for ticker in &tickers {
+        db.repo
+            .create_stock(ticker, shares(100), &seller, &Actor::System)
+            .await
+            .expect("Listed");
+    }
+    assert_eq!(service.latest_prices(None).await, Ok(Vec::new()));
+
+    for _ in 0..60 {
+        let ticker = tickers[rng.usize(..tickers.len())];
+        let price = price(rng.i64(1..=20));
+        let quantity = shares(rng.u32(1..=2));
+        let (first, second) = if rng.bool() {
+            ((seller, Side::Sell), (buyer, Side::Buy))
+        } else {
+            ((buyer, Side::Buy), (seller, Side::Sell))
+        };
+
+        for (user, side) in [first, second] {
+            service
+                .place_order(&user, &ticker, side, price, quantity, None)
+                .await
+                .expect("Placed");
+        }
+    }
+
+    let expected: Vec<(String, Decimal, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT DISTINCT ON (ticker) ticker, price, time FROM stock_events
+        ORDER BY ticker, time DESC, event_id DESC",
+    )
+    .fetch_all(&db.pool)
+    .await
+    .expect("Lookup");
+    let latest = service.latest_prices(None).await.expect("Lookup");
+    assert_eq!(
+        latest
+            .iter()
+            .map(|p| (p.ticker.to_string(), p.price.get(), p.updated_at))
+            .collect::<Vec<_>>(),
+        expected
+    );
+
+    let some = service
+        .latest_prices(Some(&tickers[1..]))
+        .await
+        .expect("Lookup");
+    assert_eq!(some, latest[1..]);
+}
+
+#[tokio::test]
+async fn account_summaries_merge_trades_and_ledger() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+
+    db.repo
+        .grant(&buyer, Decimal::from(100), &Actor::System)
+        .await
+        .expect("Granted");
+    db.repo
+        .place_order(&order(seller, abc, Side::Sell, 10, 4), None, Utc::now())
+        .await
+        .expect("Placed");
+    for _ in 0..3 {
+        db.repo
+            .place_order(&order(buyer, abc, Side::Buy, 10, 1), None, Utc::now())
+            .await
+            .expect("Placed");
+    }
+    db.repo
+        .place_order(&order(buyer, abc, Side::Buy, 5, 2), None, Utc::now())
+        .await
+        .expect("Placed");
+
+    let summary = db.repo.account_summary(&buyer, 3).await.expect("Lookup");
+    assert_eq!(summary.holdings_value, Decimal::from(30));
+    assert_eq!(summary.stocks_held, 1);
+    assert_eq!(summary.open_orders, 1);
+    assert_eq!(summary.recent.len(), 3);
+    assert!(summary.recent.iter().all(|t| t.kind == TransactionKind::Buy
+        && t.delta == Decimal::from(-10)
+        && t.ticker == Some(abc)));
+    assert!(summary.recent.is_sorted_by(|a, b| a.time >= b.time));
+
+    let everything = db.repo.account_summary(&buyer, 10).await.expect("Lookup");
+    assert_eq!(everything.recent.len(), 4);
+    let grant = everything.recent.last().expect("Has transactions");
+    assert_eq!(
+        (grant.kind, grant.delta, grant.ticker),
+        (TransactionKind::Grant, Decimal::from(100), None)
+    );
+
+    let sold = db.repo.account_summary(&seller, 1).await.expect("Lookup");
+    assert_eq!(sold.recent[0].kind, TransactionKind::Sell);
+    assert_eq!(sold.recent[0].delta, Decimal::from(10));
+
+    assert_eq!(
+        db.repo.account_summary(&Uuid::nil(), 3).await,
+        Ok(AccountSummary {
+            holdings_value: Decimal::ZERO,
+            stocks_held: 0,
+            open_orders: 0,
+            recent: Vec::new(),
+        })
+    );
+}
+
+#[tokio::test]
+async fn orders_and_withdrawals_put_kromer_on_hold() {
+    let Some(db) = test_db().await else { return };
+    let treasury = Uuid::from_u128(1);
+    let service = Service::new(db.repo.clone()).with_fees(FeeSchedule { bps: 100, treasury });
+    service.ensure_treasury().await.expect("Treasury created");
+    let abc = ticker("ABC");
+    listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    service
+        .grant(&buyer, Decimal::from(1000), &Actor::System)
+        .await
+        .expect("Granted");
+
+    let balances = async || {
+        let info = service
+            .get_account_info(&buyer, Some(&buyer))
+            .await
+            .expect("Lookup");
+        (info.balance, info.available_balance, info.held_balance)
+    };
+    let some = |n| Some(Decimal::from(n));
+    let address = Address::try_from("k123456789").expect("Valid address");
+
+    // Nothing to match against, so the whole notional and its 1% fee stay in escrow
+    service
+        .place_order(&buyer, &abc, Side::Buy, price(10), shares(20), None)
+        .await
+        .expect("Placed");
+    assert_eq!(balances().await, (some(1000), some(798), some(202)));
+
+    service
+        .request_withdrawal(&buyer, Decimal::from(98), &address, &Actor::System)
+        .await
+        .expect("Requested");
+    assert_eq!(balances().await, (some(1000), some(700), some(300)));
+
+    // Only what's available can be spent, even though the account owns more
+    assert_eq!(
+        service
+            .request_withdrawal(&buyer, Decimal::from(701), &address, &Actor::System)
+            .await
+            .err(),
+        Some(ServiceError::InsufficientFunds)
+    );
+    assert_eq!(
+        service
+            .place_order(&buyer, &abc, Side::Buy, price(10), shares(70), None)
+            .await
+            .err(),
+        Some(ServiceError::InsufficientFunds)
+    );
+}
+
+#[tokio::test]
+async fn statements_keep_their_running_balance_across_pages() {
+    let Some(db) = test_db().await else { return };
+    let treasury = Uuid::from_u128(1);
+    let service = Service::new(db.repo.clone()).with_fees(FeeSchedule { bps: 100, treasury });
+    service.ensure_treasury().await.expect("Treasury created");
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+
+    service
+        .grant(&buyer, Decimal::from(1000), &Actor::System)
+        .await
+        .expect("Granted");
+    service
+        .place_order(&seller, &abc, Side::Sell, price(10), shares(30), None)
+        .await
+        .expect("Placed");
+    for _ in 0..3 {
+        service
+            .place_order(&buyer, &abc, Side::Buy, price(10), shares(10), None)
+            .await
+            .expect("Placed");
+    }
+
+    let balances = |statement: &Statement| -> Vec<(TransactionKind, i64)> {
+        statement
+            .page
+            .items
+            .iter()
+            .map(|entry| {
+                (
+                    entry.transaction.kind,
+                    entry.balance.try_into().expect("Whole number"),
+                )
+            })
+            .collect()
+    };
+    let (buy, fee) = (TransactionKind::Buy, TransactionKind::Fee);
+
+    let first = service
+        .get_ledger(&buyer, &Pager::new(0, 3), None, None)
+        .await
+        .expect("Lookup");
+    assert_eq!(first.page.total, 7);
+    assert_eq!(balances(&first), [(fee, 697), (buy, 698), (fee, 798)]);
+
+    // Entries added since the first page don't shift the later ones
+    service
+        .grant(&buyer, Decimal::from(50), &Actor::System)
+        .await
+        .expect("Granted");
+
+    let second = service
+        .get_ledger(&buyer, &Pager::new(3, 3), None, Some(first.snapshot))
+        .await
+        .expect("Lookup");
+    assert_eq!(second.page.total, 7);
+    assert_eq!(balances(&second), [(buy, 799), (fee, 899), (buy, 900)]);
+
+    let last = service
+        .get_ledger(&buyer, &Pager::new(6, 3), None, Some(first.snapshot))
+        .await
+        .expect("Lookup");
+    assert_eq!(balances(&last), [(TransactionKind::Grant, 1000)]);
+    assert!(last.page.is_last());
+
+    let fees = service
+        .get_ledger(&buyer, &Pager::new(0, 10), Some(LedgerKind::Fees), None)
+        .await
+        .expect("Lookup");
+    assert_eq!(balances(&fees), [(fee, 697), (fee, 798), (fee, 899)]);
+    assert!(
+        fees.page
+            .items
+            .iter()
+            .all(|entry| entry.transaction.delta == Decimal::from(-1)
+                && entry.transaction.ticker == Some(abc))
+    );
+
+    let past_the_end = service
+        .get_ledger(&buyer, &Pager::new(30, 3), None, None)
+        .await
+        .expect("Lookup");
+    assert_eq!(past_the_end.page.total, 8);
+    assert!(past_the_end.page.items.is_empty());
+}
+
+#[tokio::test]
+async fn orders_match_against_the_book() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 100).await;
+
+    let (ask, fills) = db
+        .repo
+        .place_order(&order(seller, abc, Side::Sell, 2, 10), None, Utc::now())
+        .await
+        .expect("Placed");
+    assert_eq!(ask.status, OrderStatus::Open);
+    assert!(fills.is_empty());
+
+    let (bid, fills) = db
+        .repo
+        .place_order(&order(buyer, abc, Side::Buy, 3, 4), None, Utc::now())
+        .await
+        .expect("Placed");
+    assert_eq!(bid.status, OrderStatus::Filled);
+    assert_eq!(fills.len(), 1);
+    assert_eq!(fills[0].sell_order, ask.id);
+    assert_eq!(fills[0].price, price(2));
+    assert_eq!(fills[0].shares, shares(4));
+
+    let info = db
+        .repo
+        .user_info(&buyer)
+        .await
+        .expect("Lookup")
+        .expect("Registered");
+    assert_eq!(info.balance, Some(Decimal::from(92)));
+    assert_eq!(db.repo.holdings_value(&buyer).await, Ok(Decimal::from(8)));
+
+    let Page {
+        items: open, total, ..
+    } = db
+        .repo
+        .open_orders(&seller, &Pager::new(0, 10))
+        .await
+        .expect("Lookup");
+    assert_eq!(total, 1);
+    assert_eq!(open[0].remaining, shares(6));
+
+    let book = db.repo.book(&abc, 5).await.expect("Lookup");
+    assert!(book.bids.is_empty());
+    assert_eq!(book.asks.len(), 1);
+    assert_eq!(book.asks[0].shares, 6);
+    assert_eq!(book.last_price, Some(Decimal::from(2)));
+
+    let holders = db.repo.shareholders(&abc).await.expect("Lookup");
+    assert_eq!(holders.issued, 100);
+    assert_eq!(holders.held_by(&seller), 96);
+    assert_eq!(holders.held_by(&buyer), 4);
+}
+
+#[tokio::test]
+async fn ticker_trades_list_both_sides_newest_first() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 100).await;
+    assert_eq!(db.repo.set_listing_price(&abc, price(1)).await, Ok(true));
+
+    for (price, quantity) in [(2, 3), (4, 1)] {
+        db.repo
+            .place_order(
+                &order(seller, abc, Side::Sell, price, quantity),
+                None,
+                Utc::now(),
+            )
+            .await
+            .expect("Placed");
+        db.repo
+            .place_order(
+                &order(buyer, abc, Side::Buy, price, quantity),
+                None,
+                Utc::now(),
+            )
+            .await
+            .expect("Placed");
+    }
+
+    // The listing price isn't a trade
+    let Page { items, total, .. } = db
+        .repo
+        .ticker_trades(&abc, &Pager::new(0, 1))
+        .await
+        .expect("Lookup");
+    assert_eq!(total, 2);
+    assert_eq!(
+        (
+            items[0].price,
+            items[0].shares,
+            items[0].buyer,
+            items[0].seller
+        ),
+        (price(4), shares(1), buyer, seller)
+    );
+
+    let older = db
+        .repo
+        .ticker_trades(&abc, &Pager::new(1, 1))
+        .await
+        .expect("Lookup");
+    assert_eq!(older.items[0].price, price(2));
+    assert!(older.items[0].id < items[0].id);
+
+    let none = db
+        .repo
+        .ticker_trades(&ticker("XYZ"), &Pager::new(0, 10))
+        .await
+        .expect("Lookup");
+    assert_eq!((none.items.len(), none.total), (0, 0));
+}
+
+#[tokio::test]
+async fn candles_aggregate_trades_per_interval() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 100).await;
+    assert_eq!(db.repo.set_listing_price(&abc, price(1)).await, Ok(true));
+
+    for (price, quantity) in [(2, 3), (4, 1), (3, 2)] {
+        db.repo
+            .place_order(
+                &order(seller, abc, Side::Sell, price, quantity),
+                None,
+                Utc::now(),
+            )
+            .await
+            .expect("Placed");
+        db.repo
+            .place_order(
+                &order(buyer, abc, Side::Buy, price, quantity),
+                None,
+                Utc::now(),
+            )
+            .await
+            .expect("Placed");
+    }
+
+    // Pin every event, listing price included, to a known minute past 10:00
+    let ten = NaiveDate::from_ymd_opt(2025, 1, 1)
+        .and_then(|d| d.and_hms_opt(10, 0, 0))
+        .expect("Valid")
+        .and_utc();
+    sqlx::query(
+        "UPDATE stock_events SET time = $1 + make_interval(mins => CASE price::INT
+            WHEN 1 THEN 0 WHEN 2 THEN 1 WHEN 4 THEN 3 ELSE 7 END)",
+    )
+    .bind(ten)
+    .execute(&db.pool)
+    .await
+    .expect("Pinned");
+
+    let candle = |start: i64, [open, high, low, close]: [i64; 4], volume| Candle {
+        start: ten + TimeDelta::minutes(start),
+        open: price(open),
+        high: price(high),
+        low: price(low),
+        close: price(close),
+        volume,
+    };
+    let hour = ten + TimeDelta::hours(1);
+
+    assert_eq!(
+        db.repo
+            .candles(&abc, CandleInterval::FiveMinutes, ten, hour)
+            .await,
+        Ok(vec![candle(0, [2, 4, 2, 4], 4), candle(5, [3, 3, 3, 3], 2)])
+    );
+    assert_eq!(
+        db.repo.candles(&abc, CandleInterval::Hour, ten, hour).await,
+        Ok(vec![candle(0, [2, 4, 2, 3], 6)])
+    );
+
+    // An hour back from a few minutes past the hour still covers the whole of the first one
+    let service =
+        Service::new(db.repo.clone()).with_clock(MockClock::new(hour + TimeDelta::minutes(2)));
+    assert_eq!(
+        service
+            .candles(&abc, CandleInterval::Hour, TimeDelta::hours(1))
+            .await,
+        Ok(vec![candle(0, [2, 4, 2, 3], 6)])
+    );
+    assert_eq!(
+        db.repo
+            .candles(&abc, CandleInterval::Day, hour, hour + TimeDelta::days(1))
+            .await,
+        Ok(vec![])
+    );
+}
+
+#[tokio::test]
+async fn market_orders_fill_what_they_can_and_never_rest() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 100).await;
+    let service = Service::new(db.repo.clone());
+
+    assert_eq!(
+        service
+            .place_market_order(&seller, &abc, Side::Sell, shares(1))
+            .await
+            .err(),
+        Some(ServiceError::InvalidOrder {
+            reason: "there are no orders to trade with"
+        })
+    );
+
+    for (price, quantity) in [(5, 3), (6, 4)] {
+        db.repo
+            .place_order(
+                &order(seller, abc, Side::Sell, price, quantity),
+                None,
+                Utc::now(),
+            )
+            .await
+            .expect("Placed");
+    }
+
+    let mut events = service.subscribe();
+    let (bought, fills) = service
+        .place_market_order(&buyer, &abc, Side::Buy, shares(10))
+        .await
+        .expect("Placed");
+    let traded: Vec<_> = fills.iter().map(|fill| (fill.price, fill.shares)).collect();
+    assert_eq!(traded, [(price(5), shares(3)), (price(6), shares(4))]);
+    assert_eq!(
+        (bought.status, bought.remaining),
+        (OrderStatus::Cancelled, shares(3))
+    );
+
+    // The remainder was dropped as the order was placed, not cancelled after the fact
+    assert!(matches!(
+        &published(&mut events)[..],
+        [Event::OrderPlaced { order, .. }] if *order == bought
+    ));
+
+    // Only what filled was paid for, with nothing left on hold or on the book
+    let info = db
+        .repo
+        .user_info(&buyer)
+        .await
+        .expect("Lookup")
+        .expect("Registered");
+    assert_eq!(info.balance, Some(Decimal::from(61)));
+    let escrow: Decimal = sqlx::query_scalar("SELECT escrow FROM users WHERE user_id = $1")
+        .bind(buyer)
+        .fetch_one(&db.pool)
+        .await
+        .expect("Lookup");
+    assert_eq!(escrow, Decimal::ZERO);
+    let book = db.repo.book(&abc, 5).await.expect("Lookup");
+    assert!(book.bids.is_empty() && book.asks.is_empty());
+}
+
+#[tokio::test]
+async fn market_orders_are_not_priced_by_the_users_own_orders() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &seller, 100).await;
+    fund(&db.pool, &buyer, 100).await;
+    let service = Service::new(db.repo.clone());
+
+    // The seller's own bid is the best on the book, but matching would skip it
+    for (user, price) in [(seller, 9), (buyer, 5)] {
+        db.repo
+            .place_order(&order(user, abc, Side::Buy, price, 3), None, Utc::now())
+            .await
+            .expect("Placed");
+    }
+
+    let (sold, fills) = service
+        .place_market_order(&seller, &abc, Side::Sell, shares(3))
+        .await
+        .expect("Placed");
+    let traded: Vec<_> = fills
+        .iter()
+        .map(|fill| (fill.buyer, fill.price, fill.shares))
+        .collect();
+    assert_eq!(traded, [(buyer, price(5), shares(3))]);
+    assert_eq!(sold.status, OrderStatus::Filled);
+}
+
+#[tokio::test]
+async fn cached_books_reflect_orders_straight_away() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    service
+        .grant(&buyer, Decimal::from(100), &Actor::System)
+        .await
+        .expect("Granted");
+
+    let book = service.order_book(&abc, 5).await.expect("Book");
+    assert!(book.asks.is_empty());
+
+    // Each read is well within the cache's lifetime of the one before it
+    let (ask, _) = service
+        .place_order(&seller, &abc, Side::Sell, price(10), shares(5), None)
+        .await
+        .expect("Placed");
+    let book = service.order_book(&abc, 5).await.expect("Book");
+    assert_eq!(book.asks.len(), 1);
+    assert_eq!(book.asks[0].shares, 5);
+
+    service
+        .place_order(&buyer, &abc, Side::Buy, price(10), shares(2), None)
+        .await
+        .expect("Placed");
+    let book = service.order_book(&abc, 1).await.expect("Book");
+    assert_eq!(book.asks[0].shares, 3);
+    assert_eq!(book.last_price, Some(Decimal::from(10)));
+
+    service
+        .cancel_order(ask.id, &seller)
+        .await
+        .expect("Cancelled");
+    let book = service.order_book(&abc, 5).await.expect("Book");
+    assert!(book.asks.is_empty());
+}
+
+#[tokio::test]
+async fn halted_stocks_do_not_trade() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 100).await;
+
+    let (ask, _) = db
+        .repo
+        .place_order(&order(seller, abc, Side::Sell, 2, 10), None, Utc::now())
+        .await
+        .expect("Placed");
+
+    let info = db
+        .repo
+        .set_stock_status(&abc, StockStatus::Halted, &Actor::System)
+        .await
+        .expect("Halted");
+    assert_eq!(info.status, StockStatus::Halted);
+
+    assert_eq!(
+        db.repo
+            .place_order(&order(buyer, abc, Side::Buy, 2, 4), None, Utc::now())
+            .await
+            .map(|_| ()),
+        Err(Error::StockHalted { ticker: abc })
+    );
+
+    let book = db.repo.book(&abc, 5).await.expect("Lookup");
+    assert_eq!(book.asks.len(), 1);
+    assert_eq!(book.asks[0].shares, 10);
+
+    db.repo
+        .set_stock_status(&abc, StockStatus::Active, &Actor::System)
+        .await
+        .expect("Resumed");
+
+    let (_, fills) = db
+        .repo
+        .place_order(&order(buyer, abc, Side::Buy, 2, 4), None, Utc::now())
+        .await
+        .expect("Placed");
+    assert_eq!(fills.len(), 1);
+    assert_eq!(fills[0].sell_order, ask.id);
+
+    assert_eq!(
+        db.repo
+            .set_stock_status(&ticker("XYZ"), StockStatus::Halted, &Actor::System)
+            .await
+            .map(|_| ()),
+        Err(Error::StockNotFound {
+            ticker: ticker("XYZ")
+        })
+    );
+}
+
+#[tokio::test]
+async fn prices_stay_in_band() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 1000).await;
+
+    let band = |lookback| PriceBand {
+        pct: NonZeroU16::new(10).expect("Non zero"),
+        lookback,
+    };
+    let service = Service::new(db.repo.clone())
+        .with_price_band(band(Duration::from_hours(48)))
+        .with_admins([NonZeroU64::new(1).expect("Non zero")]);
+    let stale = Service::new(db.repo.clone()).with_price_band(band(Duration::from_secs(1)));
+    let buy = |service: &Service<PgPort>, price: &str| {
+        let service = service.clone();
+        let price = Price::new(price.parse().expect("Valid decimal")).expect("Valid price");
+        async move {
+            service
+                .place_order(&buyer, &abc, Side::Buy, price, shares(1), None)
+                .await
+                .map(|(order, _)| order)
+        }
+    };
+
+    // Nothing to compare against before the first trade
+    let order = buy(&service, "500").await.expect("Placed");
+    service
+        .cancel_order(order.id, &buyer)
+        .await
+        .expect("Cancelled");
+
+    let today = Utc::now().date_naive().and_time(NaiveTime::MIN).and_utc();
+    trade(&db.pool, &owner, abc, 20, 1, today - TimeDelta::days(1)).await;
+    trade(&db.pool, &owner, abc, 10, 1, today).await;
+
+    assert_eq!(
+        buy(&service, "11.01").await.map(|_| ()),
+        Err(ServiceError::PriceOutOfBand {
+            limit: Decimal::from(11)
+        })
+    );
+    buy(&service, "11.00").await.expect("Placed");
+
+    // Without a trade inside the lookback, yesterday's close is the reference
+    assert_eq!(
+        buy(&stale, "11.00").await.map(|_| ()),
+        Err(ServiceError::PriceOutOfBand {
+            limit: Decimal::from(18)
+        })
+    );
+
+    // The owner is linked to an admin, so may price anywhere
+    service
+        .place_order(&owner, &abc, Side::Sell, price(20), shares(1), None)
+        .await
+        .expect("Placed");
+}
+
+#[tokio::test]
+async fn snapshots_restore_into_an_empty_database() {
+    let Some(db) = test_db().await else { return };
+    let (abc, xyz) = (ticker("ABC"), ticker("XYZ"));
+    let owner = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 1000).await;
+    db.repo
+        .create_stock(&xyz, shares(50), &buyer, &Actor::System)
+        .await
+        .expect("Listed");
+    assert_eq!(db.repo.set_listing_price(&xyz, price(3)).await, Ok(true));
+
+    let service = Service::new(db.repo.clone());
+    for (user, side, price, quantity) in [
+        (owner, Side::Sell, 5, 10),
+        (buyer, Side::Buy, 5, 4),
+        (buyer, Side::Buy, 2, 3),
+    ] {
+        service
+            .place_order(
+                &user,
+                &abc,
+                side,
+                self::price(price),
+                shares(quantity),
+                None,
+            )
+            .await
+            .expect("Placed");
+    }
+
+    let mut exported = Vec::new();
+    let counts = service
+        .export_snapshot(&mut exported)
+        .await
+        .expect("Exported");
+    assert_eq!(
+        counts,
+        SnapshotCounts {
+            users: 2,
+            stocks: 2,
+            holdings: 3,
+            orders: 2,
+            prices: 2,
+        }
+    );
+
+    let Some(staging) = test_db().await else {
+        return;
+    };
+    let restored = Service::new(staging.repo.clone());
+    assert_eq!(
+        restored.import_snapshot(exported.as_slice()).await.ok(),
+        Some(counts)
+    );
+    assert!(matches!(
+        restored.import_snapshot(exported.as_slice()).await,
+        Err(SnapshotError::NotEmpty)
+    ));
+
+    // Everything but the time it was taken comes back as it was
+    let mut reexported = Vec::new();
+    restored
+        .export_snapshot(&mut reexported)
+        .await
+        .expect("Exported");
+    let records = |data: &[u8]| -> Vec<String> {
+        String::from_utf8(data.to_vec())
+            .expect("UTF-8")
+            .lines()
+            .skip(1)
+            .map(str::to_owned)
+            .collect()
+    };
+    assert_eq!(records(&reexported), records(&exported));
+
+    // New orders carry on from the restored ones
+    let (order, _) = restored
+        .place_order(&buyer, &xyz, Side::Buy, price(1), shares(1), None)
+        .await
+        .expect("Placed");
+    assert_eq!(order.id, 4);
+}
+
+#[tokio::test]
+async fn new_accounts_are_held_back_until_they_mature() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    listed(&db.repo, abc).await;
+    let newcomer = account(&db.repo, 2).await;
+    let admin = account(&db.repo, 3).await;
+    fund(&db.pool, &newcomer, 1000).await;
+    fund(&db.pool, &admin, 1000).await;
+
+    let created_at = db
+        .repo
+        .user_info(&newcomer)
+        .await
+        .expect("Read")
+        .expect("Registered")
+        .created_at;
+    let eligible_at = created_at + TimeDelta::hours(24);
+    let clock = MockClock::new(created_at);
+    let service = Service::new(db.repo.clone())
+        .with_clock(clock.clone())
+        .with_admins([NonZeroU64::new(3).expect("Non zero")])
+        .with_account_maturity(AccountMaturity {
+            age: TimeDelta::hours(24),
+            order_cap: Decimal::from(50),
+        });
+    let buy = |user: Uuid, price: i64| {
+        let service = service.clone();
+        async move {
+            service
+                .place_order(&user, &abc, Side::Buy, self::price(price), shares(5), None)
+                .await
+                .map(|(order, _)| order.status)
+        }
+    };
+    let too_new = ServiceError::AccountTooNew { eligible_at };
+
+    // Small orders go through from the start, anything bigger waits
+    assert_eq!(buy(newcomer, 10).await, Ok(OrderStatus::Open));
+    assert_eq!(buy(newcomer, 11).await, Err(too_new));
+    assert_eq!(
+        service
+            .create_stock(&ticker("NEW"), shares(100), &newcomer, &Actor::System)
+            .await
+            .err(),
+        Some(too_new)
+    );
+    assert_eq!(buy(admin, 11).await, Ok(OrderStatus::Open));
+
+    let info = service
+        .get_account_info(&newcomer, Some(&newcomer))
+        .await
+        .expect("Read");
+    assert_eq!(service.matures_at(&info), Some(eligible_at));
+
+    clock.advance(TimeDelta::hours(24) - TimeDelta::seconds(1));
+    assert_eq!(buy(newcomer, 11).await, Err(too_new));
+
+    // Held back up to, but not including, the moment it turns a day old
+    clock.advance(TimeDelta::seconds(1));
+    assert_eq!(service.matures_at(&info), None);
+    assert_eq!(buy(newcomer, 11).await, Ok(OrderStatus::Open));
+    service
+        .create_stock(&ticker("NEW"), shares(100), &newcomer, &Actor::System)
+        .await
+        .expect("Listed");
+}
+
+#[tokio::test]
+async fn orders_need_cover() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+    let poor = account(&db.repo, 2).await;
+
+    assert_eq!(
+        db.repo
+            .place_order(&order(poor, abc, Side::Buy, 1, 1), None, Utc::now())
+            .await
+            .map(|_| ()),
+        Err(Error::InsufficientFunds)
+    );
+    assert_eq!(
+        db.repo
+            .place_order(&order(owner, abc, Side::Sell, 1, 101), None, Utc::now())
+            .await
+            .map(|_| ()),
+        Err(Error::InsufficientShares)
+    );
+    assert_eq!(
+        db.repo
+            .place_order(
+                &order(owner, ticker("XYZ"), Side::Sell, 1, 1),
+                None,
+                Utc::now()
+            )
+            .await
+            .map(|_| ()),
+        Err(Error::StockNotFound {
+            ticker: ticker("XYZ")
+        })
+    );
+}
+
+#[tokio::test]
+async fn concurrent_sells_never_oversell() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    service
+        .grant(&buyer, Decimal::from(150), &Actor::System)
+        .await
+        .expect("Granted");
+    db.repo
+        .place_order(&order(buyer, abc, Side::Buy, 1, 150), None, Utc::now())
+        .await
+        .expect("Placed");
+
+    // 150 shares asked for out of 100, so only 33 sells can go through
+    let mut sells = tokio::task::JoinSet::new();
+    for _ in 0..50 {
+        let repo = db.repo.clone();
+        sells.spawn(async move {
+            repo.place_order(&order(seller, abc, Side::Sell, 1, 3), None, Utc::now())
+                .await
+                .map(|_| ())
+        });
+    }
+
+    let mut filled = 0;
+    while let Some(res) = sells.join_next().await {
+        match res.expect("Didn't panic") {
+            Ok(()) => filled += 1,
+            Err(err) => assert_eq!(err, Error::InsufficientShares),
+        }
+    }
+    assert_eq!(filled, 33);
+
+    let holders = db.repo.shareholders(&abc).await.expect("Lookup");
+    assert_eq!(holders.held_by(&seller), 1);
+    assert_eq!(holders.held_by(&buyer), 99);
+
+    let overdrawn: i64 = sqlx::query_scalar("SELECT count(*) FROM users WHERE balance < 0")
+        .fetch_one(&db.pool)
+        .await
+        .expect("Counted");
+    assert_eq!(overdrawn, 0);
+    assert_eq!(
+        service.reconcile().await.expect("Reconciled").discrepancies,
+        []
+    );
+}
+
+#[tokio::test]
+async fn cancelling_releases_escrow() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+
+    let (ask, _) = db
+        .repo
+        .place_order(&order(owner, abc, Side::Sell, 2, 10), None, Utc::now())
+        .await
+        .expect("Placed");
+
+    let Page {
+        items: holdings, ..
+    } = db
+        .repo
+        .get_holdings(&owner, &Pager::new(0, 1), HoldingOrdering::Ticker)
+        .await
+        .expect("Lookup")
+        .expect("Has holdings");
+    assert_eq!(holdings[0].1, shares(90));
+
+    let cancelled = db
+        .repo
+        .cancel_order(ask.id, &owner)
+        .await
+        .expect("Cancelled");
+    assert_eq!(cancelled.status, OrderStatus::Cancelled);
+
+    let Page {
+        items: holdings, ..
+    } = db
+        .repo
+        .get_holdings(&owner, &Pager::new(0, 1), HoldingOrdering::Ticker)
+        .await
+        .expect("Lookup")
+        .expect("Has holdings");
+    assert_eq!(holdings[0].1, shares(100));
+
+    assert_eq!(
+        db.repo.cancel_order(ask.id, &owner).await.map(|_| ()),
+        Err(Error::OrderNotFound { id: ask.id })
+    );
+}
+
+#[tokio::test]
+async fn withdrawals_hold_until_resolved() {
+    let Some(db) = test_db().await else { return };
+    let user = account(&db.repo, 1).await;
+    let actor = Actor::Account(user);
+    let address = Address::try_from("k123456789").expect("Valid address");
+    fund(&db.pool, &user, 100).await;
+
+    let balance = async || {
+        db.repo
+            .user_info(&user)
+            .await
+            .expect("Lookup")
+            .expect("Exists")
+            .available_balance
+    };
+
+    assert_eq!(
+        db.repo
+            .request_withdrawal(&user, Decimal::from(101), &address, &actor)
+            .await
+            .map(|_| ()),
+        Err(Error::InsufficientFunds)
+    );
+
+    let denied = db
+        .repo
+        .request_withdrawal(&user, Decimal::from(60), &address, &actor)
+        .await
+        .expect("Requested");
+    let approved = db
+        .repo
+        .request_withdrawal(&user, Decimal::from(30), &address, &actor)
+        .await
+        .expect("Requested");
+    assert_eq!(denied.status, WithdrawalStatus::Pending);
+    assert_eq!(balance().await, Some(Decimal::from(10)));
+
+    let pending = db
+        .repo
+        .pending_withdrawals(&Pager::new(0, 10))
+        .await
+        .expect("Listed");
+    assert_eq!(pending.total, 2);
+    assert_eq!(pending.items[0].id, denied.id);
+
+    let res = db
+        .repo
+        .resolve_withdrawal(denied.id, false, &Actor::System)
+        .await
+        .expect("Denied");
+    assert_eq!(res.status, WithdrawalStatus::Denied);
+    assert_eq!(balance().await, Some(Decimal::from(70)));
+
+    let res = db
+        .repo
+        .resolve_withdrawal(approved.id, true, &Actor::System)
+        .await
+        .expect("Approved");
+    assert_eq!(res.status, WithdrawalStatus::Processed);
+    assert!(res.resolved_at.is_some());
+    assert_eq!(balance().await, Some(Decimal::from(70)));
+
+    assert_eq!(
+        db.repo
+            .resolve_withdrawal(denied.id, true, &Actor::System)
+            .await
+            .map(|_| ()),
+        Err(Error::WithdrawalNotFound { id: denied.id })
+    );
+    assert_eq!(
+        db.repo
+            .pending_withdrawals(&Pager::new(0, 10))
+            .await
+            .expect("Listed")
+            .total,
+        0
+    );
+}
+
+#[tokio::test]
+async fn adjustments_reverse_once_and_only_overdraw_when_allowed() {
+    let Some(db) = test_db().await else { return };
+    let user = account(&db.repo, 1).await;
+    let actor = Actor::Discord(NonZeroU64::new(2).expect("Non-zero"));
+    fund(&db.pool, &user, 10).await;
+
+    let prize = db
+        .repo
+        .adjust_balance(
+            &user,
+            Decimal::from(50),
+            AdjustmentReason::Prize,
+            false,
+            &actor,
+        )
+        .await
+        .expect("Adjusted");
+    assert_eq!(prize.balance, Decimal::from(60));
+    assert_eq!(prize.reverses, None);
+
+    assert_eq!(
+        db.repo
+            .adjust_balance(
+                &user,
+                Decimal::from(-61),
+                AdjustmentReason::Penalty,
+                false,
+                &actor,
+            )
+            .await
+            .map(|_| ()),
+        Err(Error::InsufficientFunds)
+    );
+    let penalty = db
+        .repo
+        .adjust_balance(
+            &user,
+            Decimal::from(-61),
+            AdjustmentReason::Penalty,
+            true,
+            &actor,
+        )
+        .await
+        .expect("Overdrawn");
+    assert_eq!(penalty.balance, Decimal::from(-1));
+
+    // Allowing it once doesn't leave the account open to being overdrawn further
+    assert_eq!(
+        db.repo
+            .request_withdrawal(
+                &user,
+                Decimal::ONE,
+                &Address::try_from("k123456789").expect("Valid address"),
+                &Actor::Account(user),
+            )
+            .await
+            .map(|_| ()),
+        Err(Error::InsufficientFunds)
+    );
+
+    let reversal = db
+        .repo
+        .reverse_adjustment(penalty.id, false, &actor)
+        .await
+        .expect("Reversed");
+    assert_eq!(reversal.delta, Decimal::from(61));
+    assert_eq!(reversal.reason, AdjustmentReason::Penalty);
+    assert_eq!(reversal.reverses, Some(penalty.id));
+    assert_eq!(reversal.balance, Decimal::from(60));
+
+    assert_eq!(
+        db.repo
+            .reverse_adjustment(penalty.id, false, &actor)
+            .await
+            .map(|_| ()),
+        Err(Error::AlreadyReversed { id: penalty.id })
+    );
+    assert_eq!(
+        db.repo
+            .reverse_adjustment(reversal.id, false, &actor)
+            .await
+            .map(|_| ()),
+        Err(Error::AdjustmentNotFound { id: reversal.id })
+    );
+
+    let statement = db
+        .repo
+        .statement(&user, &Pager::new(0, 10), Some(LedgerKind::Deposits), None)
+        .await
+        .expect("Listed");
+    assert_eq!(statement.page.total, 3);
+
+    let audited = db
+        .repo
+        .audit_log(
+            &Pager::new(0, 10),
+            &AuditFilter {
+                action: Some(Action::ReverseAdjustment),
+                ..AuditFilter::default()
+            },
+        )
+        .await
+        .expect("Listed");
+    assert_eq!(audited.total, 1);
+}
+
+#[tokio::test]
+async fn uncredited_deposits_are_audited() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let nobody = Uuid::from_u128(1);
+    let deposit = Deposit {
+        transaction: 42,
+        user: nobody,
+        amount: Decimal::new(1050, 2),
+        from: Address::try_from("kabcdefghi").expect("Valid address"),
+        time: Utc::now(),
+    };
+    let wallet = Address::try_from("kexchange0").expect("Valid address");
+
+    assert!(matches!(
+        service.credit_deposit(&wallet, &deposit).await,
+        Err(ServiceError::UserNotFound { .. })
+    ));
+    service
+        .record_uncredited_deposit(&wallet, &deposit)
+        .await
+        .expect("Recorded");
+    assert_eq!(service.kromer_cursor(&wallet).await, Ok(Some(42)));
+
+    let audited = db
+        .repo
+        .audit_log(
+            &Pager::new(0, 10),
+            &AuditFilter {
+                action: Some(Action::UncreditedDeposit),
+                ..AuditFilter::default()
+            },
+        )
+        .await
+        .expect("Listed");
+    assert_eq!(audited.total, 1);
+    assert_eq!(audited.items[0].actor, Actor::System);
+    assert_eq!(audited.items[0].target, Some(nobody.to_string()));
+    assert_eq!(audited.items[0].details["transaction"], 42);
+    assert_eq!(audited.items[0].details["from"], "kabcdefghi");
+    assert_eq!(audited.items[0].details["amount"], "10.50");
+}
+
+#[tokio::test]
+async fn deposits_are_credited_once() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let mut events = service.subscribe();
+    let user = account(&db.repo, 1).await;
+    let deposit = Deposit {
+        transaction: 42,
+        user,
+        amount: Decimal::new(1050, 2),
+        from: Address::try_from("kabcdefghi").expect("Valid address"),
+        time: Utc::now(),
+    };
+
+    let wallet = Address::try_from("kexchange0").expect("Valid address");
+
+    assert_eq!(service.kromer_cursor(&wallet).await, Ok(None));
+    assert_eq!(
+        service.credit_deposit(&wallet, &deposit).await,
+        Ok(Some(Decimal::new(1050, 2)))
+    );
+    assert_eq!(service.credit_deposit(&wallet, &deposit).await, Ok(None));
+    assert_eq!(service.kromer_cursor(&wallet).await, Ok(Some(42)));
+    assert!(matches!(
+        published(&mut events).as_slice(),
+        [Event::Granted { amount, .. }] if *amount == deposit.amount
+    ));
+
+    let audited = db
+        .repo
+        .audit_log(
+            &Pager::new(0, 10),
+            &AuditFilter {
+                action: Some(Action::Deposit),
+                ..AuditFilter::default()
+            },
+        )
+        .await
+        .expect("Listed");
+    assert_eq!(audited.total, 1);
+    assert_eq!(audited.items[0].actor, Actor::System);
+    assert_eq!(audited.items[0].details["transaction"], 42);
+    assert_eq!(audited.items[0].details["from"], "kabcdefghi");
+
+    let statement = db
+        .repo
+        .statement(&user, &Pager::new(0, 10), Some(LedgerKind::Deposits), None)
+        .await
+        .expect("Listed");
+    assert_eq!(statement.page.total, 1);
+    assert_eq!(
+        statement.page.items[0].transaction.kind,
+        TransactionKind::Deposit
+    );
+
+    db.repo
+        .close_account(&user, Some(&deposit.from), false, &Actor::Account(user))
+        .await
+        .expect("Closed");
+    assert_eq!(
+        db.repo
+            .credit_deposit(
+                &wallet,
+                &Deposit {
+                    transaction: 43,
+                    ..deposit
+                }
+            )
+            .await,
+        Err(Error::AccountNotFound { id: user })
+    );
+    assert_eq!(service.kromer_cursor(&wallet).await, Ok(Some(42)));
+}
+
+#[tokio::test]
+async fn link_codes_are_single_use_and_expire() {
+    let Some(db) = test_db().await else { return };
+    let discord_first = account(&db.repo, 1).await;
+    let now = Utc::now();
+    let player = Uuid::from_u128(1);
+    let flake = |id| NonZeroU64::new(id).expect("Non-zero");
+
+    let expired = db
+        .repo
+        .create_link_code(&discord_first, now + TimeDelta::minutes(1))
+        .await
+        .expect("Created");
+    assert_eq!(
+        db.repo
+            .consume_link_code(
+                &expired,
+                Identity::Minecraft(player),
+                now + TimeDelta::minutes(2)
+            )
+            .await,
+        Err(Error::InvalidLinkCode)
+    );
+
+    // A new code replaces the unused one
+    let code = db
+        .repo
+        .create_link_code(&discord_first, now + TimeDelta::minutes(10))
+        .await
+        .expect("Created");
+    assert_eq!(
+        db.repo
+            .consume_link_code(&expired, Identity::Minecraft(player), now)
+            .await,
+        Err(Error::InvalidLinkCode)
+    );
+
+    // Used from two places at once, only one links
+    let other = Uuid::from_u128(2);
+    let (first, second) = tokio::join!(
+        db.repo
+            .consume_link_code(&code, Identity::Minecraft(player), now),
+        db.repo
+            .consume_link_code(&code, Identity::Minecraft(other), now),
+    );
+    let linked = if first.is_ok() { player } else { other };
+    let mut results = [first, second];
+    results.sort_by_key(Result::is_err);
+    assert_eq!(results, [Ok(discord_first), Err(Error::InvalidLinkCode)]);
+    assert_eq!(db.repo.mc_to_id(&linked).await, Ok(Some(discord_first)));
+
+    assert_eq!(
+        db.repo
+            .consume_link_code(&code, Identity::Minecraft(Uuid::from_u128(3)), now)
+            .await,
+        Err(Error::InvalidLinkCode)
+    );
+
+    // Registered from Minecraft first, then linked from Discord
+    let minecraft_first = db
+        .repo
+        .register_user(None, Some(&Uuid::from_u128(4)), &Actor::System)
+        .await
+        .expect("Registered")
+        .id();
+    let code = db
+        .repo
+        .create_link_code(&minecraft_first, now + TimeDelta::minutes(10))
+        .await
+        .expect("Created");
+    assert_eq!(
+        db.repo
+            .consume_link_code(&code, Identity::Discord(flake(1)), now)
+            .await,
+        Err(Error::AlreadyLinked)
+    );
+    assert_eq!(
+        db.repo
+            .consume_link_code(&code, Identity::Discord(flake(2)), now)
+            .await,
+        Ok(minecraft_first)
+    );
+    assert_eq!(
+        db.repo.discord_to_id(flake(2)).await,
+        Ok(Some(minecraft_first))
+    );
+
+    assert_eq!(
+        db.repo.purge_link_codes(now + TimeDelta::minutes(11)).await,
+        Ok(2)
+    );
+}
+
+#[tokio::test]
+#[allow(clippy::too_many_lines)]
+async fn orders_queue_after_hours_and_match_at_the_open() {
+    let Some(db) = test_db().await else { return };
+    let time = |h| NaiveTime::from_hms_opt(h, 0, 0).expect("Valid time");
+    let calendar = MarketCalendar::new(
+        [
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ],
+        time(14),
+        time(22),
+    )
+    .expect("Valid calendar");
+
+    // A Sunday evening, with the market next opening on Monday afternoon
+    let sunday = NaiveDate::from_ymd_opt(2025, 10, 19)
+        .expect("Valid date")
+        .and_time(time(20))
+        .and_utc();
+    let opens_at = sunday + TimeDelta::hours(18);
+    let clock = MockClock::new(sunday);
+    let rejecting = Service::new(db.repo.clone())
+        .with_clock(clock.clone())
+        .with_calendar(calendar.clone(), AfterHours::Reject);
+    let service = Service::new(db.repo.clone())
+        .with_clock(clock.clone())
+        .with_calendar(calendar, AfterHours::Queue);
+
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    service
+        .grant(&buyer, Decimal::from(1000), &Actor::System)
+        .await
+        .expect("Granted");
+
+    assert_eq!(
+        rejecting
+            .place_order(&seller, &abc, Side::Sell, price(10), shares(30), None)
+            .await
+            .err(),
+        Some(ServiceError::MarketClosed { opens_at })
+    );
+    assert_eq!(service.market_opens_at(), Some(opens_at));
+
+    // Escrowed but kept off the book, even though they cross
+    let (sell, fills) = service
+        .place_order(&seller, &abc, Side::Sell, price(10), shares(30), None)
+        .await
+        .expect("Queued");
+    assert_eq!((sell.status, fills.len()), (OrderStatus::Queued, 0));
+    let (buy, _) = service
+        .place_order(&buyer, &abc, Side::Buy, price(12), shares(20), None)
+        .await
+        .expect("Queued");
+    let (cancelled, _) = service
+        .place_order(&buyer, &abc, Side::Buy, price(11), shares(5), None)
+        .await
+        .expect("Queued");
+    let book = service.order_book(&abc, 5).await.expect("Book");
+    assert!(book.bids.is_empty() && book.asks.is_empty());
+    assert_eq!(
+        service.reconcile().await.expect("Reconciled").discrepancies,
+        []
+    );
+    assert_eq!(
+        service
+            .cancel_order(cancelled.id, &buyer)
+            .await
+            .expect("Cancelled")
+            .status,
+        OrderStatus::Cancelled
+    );
+
+    // Nothing is released until the open
+    assert_eq!(service.release_queued_orders().await, Ok(0));
+    clock.advance(TimeDelta::hours(18));
+    assert_eq!(service.market_opens_at(), None);
+
+    let mut events = service.subscribe();
+    assert_eq!(service.release_queued_orders().await, Ok(2));
+    assert_eq!(service.release_queued_orders().await, Ok(0));
+
+    // The sell was queued first, so it rests and the buy trades at its price
+    let events = published(&mut events);
+    let [
+        Event::OrderPlaced {
+            order: rested,
+            fills: none,
+        },
+        Event::OrderPlaced {
+            order: filled,
+            fills,
+        },
+    ] = &events[..]
+    else {
+        panic!("Expected two orders placed, got {events:?}");
+    };
+    assert_eq!(
+        (rested.id, rested.status, none.len()),
+        (sell.id, OrderStatus::Open, 0)
+    );
+    assert_eq!((filled.id, filled.status), (buy.id, OrderStatus::Filled));
+    assert_eq!(
+        fills
+            .iter()
+            .map(|fill| (fill.price, fill.shares))
+            .collect::<Vec<_>>(),
+        [(price(10), shares(20))]
+    );
+
+    // Orders match as soon as they are placed while the market is open
+    let (order, fills) = service
+        .place_order(&buyer, &abc, Side::Buy, price(10), shares(10), None)
+        .await
+        .expect("Placed");
+    assert_eq!((order.status, fills.len()), (OrderStatus::Filled, 1));
+}
+
+/// Records every notice it is handed, failing the first `failures` deliveries
+#[derive(Default)]
+struct Flaky {
+    failures: AtomicUsize,
+    delivered: std::sync::Mutex<Vec<Notice>>,
+}
+
+impl Notifier for Flaky {
+    type Error = &'static str;
+
+    async fn deliver(&self, notice: &Notice) -> Result<(), Self::Error> {
+        let failing = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if failing {
+            return Err("Notifier is down");
+        }
+
+        self.delivered
+            .lock()
+            .expect("Not poisoned")
+            .push(notice.clone());
+        Ok(())
+    }
+}
+
+/// Counts outbox rows that are sent and that are parked
+async fn outbox_state(pool: &PgPool) -> (i64, i64) {
+    sqlx::query_as("SELECT COUNT(sent_at), COUNT(parked_at) FROM outbox")
+        .fetch_one(pool)
+        .await
+        .expect("Counted")
+}
+
+#[tokio::test]
+async fn outbox_retries_then_parks() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let user = account(&db.repo, 1).await;
+    let actor = Actor::Account(user);
+    let address = Address::try_from("k123456789").expect("Valid address");
+    fund(&db.pool, &user, 100).await;
+
+    let policy = DispatchPolicy {
+        max_attempts: NonZeroU32::new(2).expect("Non-zero"),
+        base_delay: Duration::from_secs(10),
+        lease: Duration::from_mins(1),
+        ..DispatchPolicy::default()
+    };
+    let notifier = Flaky::default();
+
+    let first = db
+        .repo
+        .request_withdrawal(&user, Decimal::from(10), &address, &actor)
+        .await
+        .expect("Requested");
+    let first = db
+        .repo
+        .resolve_withdrawal(first.id, true, &Actor::System)
+        .await
+        .expect("Approved");
+    // A rolled back change queues nothing
+    assert_eq!(
+        db.repo
+            .resolve_withdrawal(first.id, true, &Actor::System)
+            .await
+            .map(|_| ()),
+        Err(Error::WithdrawalNotFound { id: first.id })
+    );
+    db.repo
+        .enqueue_outbox(&serde_json::json!({ "kind": "unheard_of" }))
+        .await
+        .expect("Queued");
+
+    let now = Utc::now();
+    let later = |secs| now + TimeDelta::seconds(secs);
+
+    // The malformed notice is parked straight away, the other backs off
+    notifier.failures.store(1, Ordering::SeqCst);
+    assert_eq!(
+        service.dispatch_outbox(&notifier, &policy, now).await,
+        Ok(0)
+    );
+    assert_eq!(outbox_state(&db.pool).await, (0, 1));
+    assert_eq!(
+        service.dispatch_outbox(&notifier, &policy, later(5)).await,
+        Ok(0)
+    );
+
+    assert_eq!(
+        service.dispatch_outbox(&notifier, &policy, later(11)).await,
+        Ok(1)
+    );
+    assert_eq!(
+        *notifier.delivered.lock().expect("Not poisoned"),
+        [Notice::WithdrawalResolved(first)]
+    );
+    assert_eq!(outbox_state(&db.pool).await, (1, 1));
+
+    let second = db
+        .repo
+        .request_withdrawal(&user, Decimal::from(10), &address, &actor)
+        .await
+        .expect("Requested");
+    db.repo
+        .resolve_withdrawal(second.id, false, &Actor::System)
+        .await
+        .expect("Denied");
+
+    // Failing every attempt parks it, after which it is never tried again
+    notifier.failures.store(usize::MAX, Ordering::SeqCst);
+    assert_eq!(
+        service.dispatch_outbox(&notifier, &policy, later(11)).await,
+        Ok(0)
+    );
+    assert_eq!(
+        service.dispatch_outbox(&notifier, &policy, later(30)).await,
+        Ok(0)
+    );
+    assert_eq!(outbox_state(&db.pool).await, (1, 2));
+
+    notifier.failures.store(0, Ordering::SeqCst);
+    assert_eq!(
+        service
+            .dispatch_outbox(&notifier, &policy, later(3600))
+            .await,
+        Ok(0)
+    );
+    assert_eq!(notifier.delivered.lock().expect("Not poisoned").len(), 1);
+}
+
+#[tokio::test]
+async fn outbox_leases_claimed_entries() {
+    let Some(db) = test_db().await else { return };
+    let payload = serde_json::json!({ "kind": "unheard_of" });
+    db.repo.enqueue_outbox(&payload).await.expect("Queued");
+    let now = Utc::now();
+    let until = now + TimeDelta::minutes(1);
+
+    let claimed = db.repo.claim_outbox(now, until, 10).await.expect("Claimed");
+    assert_eq!(claimed.len(), 1);
+    assert_eq!(claimed[0].payload, payload);
+    assert_eq!(claimed[0].attempts, 0);
+
+    assert_eq!(db.repo.claim_outbox(now, until, 10).await, Ok(Vec::new()));
+
+    // A dispatcher that died mid-delivery has its entries handed out again
+    let reclaimed = db
+        .repo
+        .claim_outbox(until, until + TimeDelta::minutes(1), 10)
+        .await
+        .expect("Claimed");
+    assert_eq!(reclaimed, claimed);
+
+    db.repo.outbox_sent(claimed[0].id).await.expect("Sent");
+    assert_eq!(
+        db.repo
+            .claim_outbox(until + TimeDelta::hours(1), until, 10)
+            .await,
+        Ok(Vec::new())
+    );
+}
+
+/// The receipts waiting in the outbox, oldest first
+async fn queued_receipts(pool: &PgPool) -> Vec<Receipt> {
+    let payloads: Vec<serde_json::Value> =
+        sqlx::query_scalar("SELECT payload FROM outbox ORDER BY outbox_id")
+            .fetch_all(pool)
+            .await
+            .expect("Listed");
+
+    payloads
+        .into_iter()
+        .filter_map(|payload| match serde_json::from_value(payload) {
+            Ok(Notice::TradeReceipt(receipt)) => Some(receipt),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn fills_in_quick_succession_share_a_receipt() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 1000).await;
+    service
+        .set_receipts(&buyer, false)
+        .await
+        .expect("Opted out");
+
+    let (ask, _) = db
+        .repo
+        .place_order(&order(seller, abc, Side::Sell, 2, 10), None, Utc::now())
+        .await
+        .expect("Placed");
+    for quantity in [3, 4] {
+        db.repo
+            .place_order(&order(buyer, abc, Side::Buy, 2, quantity), None, Utc::now())
+            .await
+            .expect("Placed");
+    }
+
+    // Only the seller wants receipts, and both fills of their ask are on the same one
+    let receipts = queued_receipts(&db.pool).await;
+    assert_eq!(receipts.len(), 1);
+    let receipt = &receipts[0];
+    assert_eq!(
+        (receipt.user, receipt.order, receipt.side),
+        (seller, ask.id, Side::Sell)
+    );
+    assert_eq!(receipt.shares(), 7);
+    assert_eq!(receipt.remaining, shares(3));
+    assert_eq!(receipt.position, shares(93));
+    assert_eq!(receipt.average_price(), Decimal::from(2));
+
+    // Once claimed for delivery, later fills start a receipt of their own
+    let now = Utc::now() + TimeDelta::minutes(1);
+    let claimed = db
+        .repo
+        .claim_outbox(now, now + TimeDelta::minutes(1), 10)
+        .await
+        .expect("Claimed");
+    assert_eq!(claimed.len(), 1);
+
+    service.set_receipts(&buyer, true).await.expect("Opted in");
+    let (bid, _) = db
+        .repo
+        .place_order(&order(buyer, abc, Side::Buy, 2, 3), None, Utc::now())
+        .await
+        .expect("Placed");
+
+    let receipts = queued_receipts(&db.pool).await;
+    assert_eq!(receipts.len(), 3);
+    let (bought, sold) = (&receipts[1], &receipts[2]);
+    assert_eq!((bought.user, bought.order), (buyer, bid.id));
+    assert_eq!(bought.position, shares(10));
+    assert_eq!((sold.user, sold.fills.len()), (seller, 1));
+    assert_eq!(sold.remaining, Shares::ZERO);
+    assert_eq!(
+        service.set_receipts(&Uuid::nil(), true).await,
+        Err(ServiceError::UserNotFound {
+            kind: IdentityKind::Internal
+        })
+    );
+}
+
+#[tokio::test]
+#[allow(clippy::too_many_lines)]
+async fn changes_publish_one_event() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let mut events = service.subscribe();
+    let abc = ticker("ABC");
+    let address = Address::try_from("k123456789").expect("Valid address");
+    let flake = |id| NonZeroU64::new(id).expect("Non-zero");
+
+    let owner = service
+        .register_account(Some(flake(1)), None)
+        .await
+        .expect("Registered")
+        .id();
+    assert!(matches!(
+        published(&mut events)[..],
+        [Event::UserRegistered { id, .. }] if id == owner
+    ));
+    service
+        .register_account(Some(flake(1)), None)
+        .await
+        .expect("Registered");
+    assert!(published(&mut events).is_empty());
+
+    let other = service
+        .register_account(Some(flake(2)), None)
+        .await
+        .expect("Registered")
+        .id();
+    let _ = published(&mut events);
+
+    service
+        .grant(&owner, Decimal::from(100), &Actor::System)
+        .await
+        .expect("Granted");
+    assert!(matches!(
+        published(&mut events)[..],
+        [Event::Granted { balance, .. }] if balance == Decimal::from(100)
+    ));
+    assert!(
+        service
+            .grant(&Uuid::nil(), Decimal::ONE, &Actor::System)
+            .await
+            .is_err()
+    );
+    assert!(published(&mut events).is_empty());
+
+    service
+        .create_stock(&abc, shares(100), &owner, &Actor::System)
+        .await
+        .expect("Listed");
+    assert!(matches!(
+        &published(&mut events)[..],
+        [Event::StockListed(info)] if info.ticker == abc
+    ));
+    assert!(
+        service
+            .create_stock(&abc, shares(100), &owner, &Actor::System)
+            .await
+            .is_err()
+    );
+    assert!(published(&mut events).is_empty());
+
+    assert_eq!(service.set_listing_price(&abc, price(10)).await, Ok(true));
+    assert!(matches!(
+        published(&mut events)[..],
+        [Event::ListingPriceSet { .. }]
+    ));
+    assert_eq!(service.set_listing_price(&abc, price(12)).await, Ok(false));
+    assert!(published(&mut events).is_empty());
+
+    let (ask, _) = service
+        .place_order(&owner, &abc, Side::Sell, price(10), shares(10), None)
+        .await
+        .expect("Placed");
+    assert!(matches!(
+        &published(&mut events)[..],
+        [Event::OrderPlaced { fills, .. }] if fills.is_empty()
+    ));
+    assert!(
+        service
+            .place_order(&other, &abc, Side::Buy, price(10), shares(4), None)
+            .await
+            .is_err()
+    );
+    assert!(published(&mut events).is_empty());
+
+    service
+        .grant(&other, Decimal::from(100), &Actor::System)
+        .await
+        .expect("Granted");
+    let _ = published(&mut events);
+    service
+        .place_order(&other, &abc, Side::Buy, price(10), shares(4), None)
+        .await
+        .expect("Placed");
+    assert!(matches!(
+        &published(&mut events)[..],
+        [Event::OrderPlaced { fills, .. }] if fills.len() == 1 && fills[0].seller == owner
+    ));
+
+    service
+        .cancel_order(ask.id, &owner)
+        .await
+        .expect("Cancelled");
+    assert!(matches!(
+        published(&mut events)[..],
+        [Event::OrderCancelled { order, .. }] if order.id == ask.id
+    ));
+    assert!(service.cancel_order(ask.id, &owner).await.is_err());
+    assert!(published(&mut events).is_empty());
+
+    service
+        .issue_shares(&abc, shares(5), &owner)
+        .await
+        .expect("Issued");
+    assert!(matches!(
+        published(&mut events)[..],
+        [Event::SharesIssued { .. }]
+    ));
+    assert!(service.issue_shares(&abc, shares(5), &other).await.is_err());
+    assert!(published(&mut events).is_empty());
+
+    service
+        .buyback(&abc, shares(5), &owner)
+        .await
+        .expect("Bought back");
+    assert!(matches!(
+        published(&mut events)[..],
+        [Event::SharesBoughtBack { .. }]
+    ));
+
+    service
+        .pay_dividend(&abc, Decimal::ONE, &owner)
+        .await
+        .expect("Paid");
+    assert!(matches!(
+        published(&mut events)[..],
+        [Event::DividendPaid { dividend, .. }] if dividend.holders == 1
+    ));
+    assert!(
+        service
+            .pay_dividend(&abc, Decimal::ONE, &other)
+            .await
+            .is_err()
+    );
+    assert!(published(&mut events).is_empty());
+
+    service
+        .transfer_ownership(&abc, &owner, &other)
+        .await
+        .expect("Transferred");
+    assert!(matches!(
+        published(&mut events)[..],
+        [Event::OwnershipTransferred { to, .. }] if to == other
+    ));
+    assert!(
+        service
+            .transfer_ownership(&abc, &owner, &other)
+            .await
+            .is_err()
+    );
+    assert!(published(&mut events).is_empty());
+
+    service
+        .set_stock_status(&abc, StockStatus::Halted, &Actor::System)
+        .await
+        .expect("Halted");
+    assert!(matches!(
+        published(&mut events)[..],
+        [Event::StockStatusChanged {
+            status: StockStatus::Halted,
+            ..
+        }]
+    ));
+    assert!(
+        service
+            .set_stock_status(&ticker("XYZ"), StockStatus::Halted, &Actor::System)
+            .await
+            .is_err()
+    );
+    assert!(published(&mut events).is_empty());
+
+    let actor = Actor::Account(other);
+    let approved = service
+        .request_withdrawal(&other, Decimal::from(10), &address, &actor)
+        .await
+        .expect("Requested");
+    let denied = service
+        .request_withdrawal(&other, Decimal::from(10), &address, &actor)
+        .await
+        .expect("Requested");
+    assert!(matches!(
+        published(&mut events)[..],
+        [Event::WithdrawalRequested(_), Event::WithdrawalRequested(_)]
+    ));
+    assert!(
+        service
+            .request_withdrawal(&other, Decimal::from(1000), &address, &actor)
+            .await
+            .is_err()
+    );
+    assert!(published(&mut events).is_empty());
+
+    service
+        .approve_withdrawal(approved.id, &Actor::System)
+        .await
+        .expect("Approved");
+    service
+        .deny_withdrawal(denied.id, &Actor::System)
+        .await
+        .expect("Denied");
+    assert!(matches!(
+        published(&mut events)[..],
+        [Event::WithdrawalApproved(_), Event::WithdrawalDenied(_)]
+    ));
+    assert!(
+        service
+            .approve_withdrawal(approved.id, &Actor::System)
+            .await
+            .is_err()
+    );
+    assert!(published(&mut events).is_empty());
+}
+
+#[tokio::test]
+async fn expired_orders_are_swept() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+    let now = Utc::now();
+
+    let mut ask = order(owner, abc, Side::Sell, 2, 10);
+    ask.expires_at = Some(now + TimeDelta::hours(1));
+    db.repo
+        .place_order(&ask, None, Utc::now())
+        .await
+        .expect("Placed");
+
+    assert_eq!(db.repo.expire_orders(now, 10).await.map(|v| v.len()), Ok(0));
+
+    let expired = db
+        .repo
+        .expire_orders(now + TimeDelta::hours(2), 10)
+        .await
+        .expect("Swept");
+    assert_eq!(expired.len(), 1);
+    assert_eq!(expired[0].status, OrderStatus::Expired);
+
+    let Page {
+        items: open, total, ..
+    } = db
+        .repo
+        .open_orders(&owner, &Pager::new(0, 10))
+        .await
+        .expect("Lookup");
+    assert!(open.is_empty());
+    assert_eq!(total, 0);
+}
+
+#[tokio::test]
+async fn orders_expired_by_the_service_clock_are_not_matched() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+    let buyer = account(&db.repo, 2).await;
+    fund(&db.pool, &buyer, 100).await;
+
+    let start = Utc::now();
+    let clock = MockClock::new(start);
+    let service = Service::new(db.repo.clone()).with_clock(clock.clone());
+
+    let (ask, _) = service
+        .place_order(
+            &owner,
+            &abc,
+            Side::Sell,
+            price(2),
+            shares(10),
+            Some(start + TimeDelta::hours(1)),
+        )
+        .await
+        .expect("Placed");
+
+    // Not swept yet, but past its expiry as far as the service is concerned
+    clock.advance(TimeDelta::hours(2));
+
+    let (bid, fills) = service
+        .place_order(&buyer, &abc, Side::Buy, price(2), shares(4), None)
+        .await
+        .expect("Placed");
+    assert!(fills.is_empty());
+    assert_eq!(bid.status, OrderStatus::Open);
+
+    let ask = db
+        .repo
+        .open_orders(&owner, &Pager::new(0, 10))
+        .await
+        .expect("Lookup")
+        .items
+        .into_iter()
+        .find(|order| order.id == ask.id)
+        .expect("Still resting");
+    assert_eq!(ask.remaining, shares(10));
+}
+
+#[tokio::test]
+async fn closed_accounts_unlink_and_pay_out() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let seller = listed(&db.repo, abc).await;
+    let user = account(&db.repo, 2).await;
+    let flake = NonZeroU64::new(2).expect("Non-zero");
+    let address = Address::try_from("k123456789").expect("Valid address");
+    fund(&db.pool, &user, 50).await;
+    let key = db.repo.create_api_key(&user).await.expect("Created");
+
+    let placed = db
+        .repo
+        .place_order(&order(user, abc, Side::Buy, 5, 2), None, Utc::now())
+        .await
+        .expect("Placed");
+    assert!(matches!(
+        db.repo
+            .close_account(&user, Some(&address), false, &Actor::Account(user))
+            .await,
+        Err(Error::AccountNotEmpty { open_orders: 1, .. })
+    ));
+
+    db.repo
+        .cancel_order(placed.0.id, &user)
+        .await
+        .expect("Cancelled");
+    assert!(matches!(
+        db.repo
+            .close_account(&user, None, false, &Actor::Account(user))
+            .await,
+        Err(Error::AccountNotEmpty { balance, .. }) if balance == Decimal::from(50)
+    ));
+
+    let closed = db
+        .repo
+        .close_account(&user, Some(&address), false, &Actor::Account(user))
+        .await
+        .expect("Closed");
+    assert_eq!(closed.disc_id, Some(flake));
+    assert_eq!(
+        closed.withdrawal.map(|v| (v.amount, v.status)),
+        Some((Decimal::from(50), WithdrawalStatus::Pending))
+    );
+
+    let info = db
+        .repo
+        .user_info(&user)
+        .await
+        .expect("Lookup")
+        .expect("Kept");
+    assert!(info.closed_at.is_some());
+    assert_eq!(info.available_balance, Some(Decimal::ZERO));
+    assert_eq!(info.disc_id, None);
+    assert_eq!(db.repo.discord_to_id(flake).await, Ok(None));
+    assert_eq!(db.repo.api_key_owner(&key).await, Ok(None));
+    assert_eq!(
+        db.repo.create_api_key(&user).await.map(|_| ()),
+        Err(Error::AccountNotFound { id: user })
+    );
+    assert_eq!(
+        audited(&db.repo, Action::UnlinkIdentity, &user.to_string()).await,
+        [serde_json::json!({ "disc_id": flake, "by": "close_account" })]
+    );
+
+    assert_eq!(
+        db.repo
+            .close_account(&user, None, false, &Actor::Account(user))
+            .await
+            .map(|_| ()),
+        Err(Error::AccountNotFound { id: user })
+    );
+    assert_eq!(
+        db.repo
+            .close_account(&seller, None, false, &Actor::Account(seller))
+            .await
+            .map(|_| ()),
+        Err(Error::AccountNotEmpty {
+            open_orders: 0,
+            holdings: 1,
+            balance: Decimal::ZERO
+        })
+    );
+
+    let again = account(&db.repo, 2).await;
+    assert_ne!(again, user);
+}
+
+#[tokio::test]
+async fn forced_closure_liquidates_at_last_price() {
+    let Some(db) = test_db().await else { return };
+    let (abc, xyz) = (ticker("ABC"), ticker("XYZ"));
+    let seller = listed(&db.repo, abc).await;
+    let user = account(&db.repo, 2).await;
+    let address = Address::try_from("k123456789").expect("Valid address");
+    let service = Service::new(db.repo.clone());
+    let mut events = service.subscribe();
+    fund(&db.pool, &user, 100).await;
+
+    db.repo
+        .place_order(&order(seller, abc, Side::Sell, 10, 10), None, Utc::now())
+        .await
+        .expect("Placed");
+    db.repo
+        .place_order(&order(user, abc, Side::Buy, 10, 10), None, Utc::now())
+        .await
+        .expect("Placed");
+    db.repo
+        .place_order(&order(user, abc, Side::Sell, 20, 4), None, Utc::now())
+        .await
+        .expect("Placed");
+    db.repo
+        .create_stock(&xyz, shares(100), &user, &Actor::System)
+        .await
+        .expect("Listed");
+    published(&mut events);
+
+    // Every share of XYZ is theirs, and a stock can't be left without shares
+    assert!(matches!(
+        service
+            .admin_close_account(&user, None, true, &Actor::System)
+            .await,
+        Err(ServiceError::AccountNotEmpty { holdings: 1, .. })
+    ));
+    sqlx::query("UPDATE holdings SET user_id = $2 WHERE user_id = $1 AND ticker = 'XYZ'")
+        .bind(user)
+        .bind(seller)
+        .execute(&db.pool)
+        .await
+        .expect("Moved");
+
+    let closed = service
+        .admin_close_account(&user, Some(&address), true, &Actor::System)
+        .await
+        .expect("Closed");
+    assert_eq!(closed.cancelled.len(), 1);
+    assert_eq!(closed.liquidated, Decimal::from(100));
+    assert_eq!(
+        closed.withdrawal.map(|v| v.amount),
+        Some(Decimal::from(100))
+    );
+    assert_eq!(
+        db.repo.stock_info(&abc).await.map(|v| v.shares),
+        Ok(shares(90))
+    );
+    assert_eq!(
+        db.repo
+            .user_info(&user)
+            .await
+            .expect("Lookup")
+            .and_then(|v| v.available_balance),
+        Some(Decimal::ZERO)
+    );
+    assert!(matches!(
+        &published(&mut events)[..],
+        [
+            Event::OrderCancelled { .. },
+            Event::WithdrawalRequested(_),
+            Event::AccountClosed { id, .. },
+        ] if *id == user
+    ));
+}
+
+/// The shares and cost basis `user` holds of `ticker`
+async fn holding(pool: &PgPool, user: &Uuid, ticker: Ticker) -> (i32, Option<Decimal>) {
+    sqlx::query_as("SELECT shares, avg_cost FROM holdings WHERE user_id = $1 AND ticker = $2")
+        .bind(user)
+        .bind(ticker.as_str())
+        .fetch_optional(pool)
+        .await
+        .expect("Lookup")
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+#[allow(clippy::too_many_lines)]
+async fn merging_sums_overlapping_holdings() {
+    let Some(db) = test_db().await else { return };
+    let (abc, xyz) = (ticker("ABC"), ticker("XYZ"));
+    let service = Service::new(db.repo.clone());
+    let seller = listed(&db.repo, abc).await;
+    let survivor = account(&db.repo, 2).await;
+    let mc_id = Uuid::from_u128(42);
+    let casualty = db
+        .repo
+        .register_user(None, Some(&mc_id), &Actor::Minecraft(mc_id))
+        .await
+        .expect("Registered")
+        .id();
+    for user in [survivor, casualty] {
+        service
+            .grant(&user, Decimal::from(100), &Actor::System)
+            .await
+            .expect("Granted");
+    }
+
+    for (buyer, price, quantity) in [(survivor, 2, 10), (casualty, 4, 5)] {
+        db.repo
+            .place_order(
+                &order(seller, abc, Side::Sell, price, quantity),
+                None,
+                Utc::now(),
+            )
+            .await
+            .expect("Placed");
+        db.repo
+            .place_order(
+                &order(buyer, abc, Side::Buy, price, quantity),
+                None,
+                Utc::now(),
+            )
+            .await
+            .expect("Placed");
+    }
+    db.repo
+        .create_stock(&xyz, shares(50), &casualty, &Actor::System)
+        .await
+        .expect("Listed");
+
+    let balance = |id| {
+        let repo = db.repo.clone();
+        async move {
+            repo.user_info(&id)
+                .await
+                .expect("Lookup")
+                .and_then(|v| v.balance)
+        }
+    };
+    let before = (balance(survivor).await, balance(casualty).await);
+
+    // A dry run shows what would move without moving it
+    let preview = service
+        .merge_accounts(&survivor, &casualty, true, &Actor::System)
+        .await
+        .expect("Previewed");
+    assert_eq!(preview.holdings, [(abc, shares(5)), (xyz, shares(50))]);
+    assert_eq!((preview.trades, preview.stocks), (1, 1));
+    assert_eq!((preview.disc_id, preview.mc_id), (None, Some(mc_id)));
+    assert_eq!(holding(&db.pool, &casualty, abc).await.0, 5);
+    assert_eq!(db.repo.mc_to_id(&mc_id).await, Ok(Some(casualty)));
+
+    let merged = service
+        .merge_accounts(&survivor, &casualty, false, &Actor::System)
+        .await
+        .expect("Merged");
+    assert_eq!(merged, preview);
+
+    let (survivor_balance, casualty_balance) = before;
+    let expected = survivor_balance
+        .zip(casualty_balance)
+        .map(|(kept, moved)| kept + moved);
+    assert_eq!(balance(survivor).await, expected);
+
+    // Ten shares at 2 and five at 4 average out to 40 / 15
+    let (held, avg_cost) = holding(&db.pool, &survivor, abc).await;
+    assert_eq!(held, 15);
+    assert_eq!(
+        avg_cost.map(|cost| cost.round_dp(4)),
+        Some(Decimal::new(26_667, 4))
+    );
+    assert_eq!(holding(&db.pool, &survivor, xyz).await.0, 50);
+    assert_eq!(holding(&db.pool, &casualty, abc).await, (0, None));
+    assert_eq!(
+        db.repo.stock_info(&xyz).await.map(|v| v.owner),
+        Ok(Some(survivor))
+    );
+
+    let moved: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM ledger WHERE user_id = $1 AND merged_from = $2")
+            .bind(survivor)
+            .bind(casualty)
+            .fetch_one(&db.pool)
+            .await
+            .expect("Counted");
+    assert_eq!(moved.cast_unsigned(), merged.ledger_entries);
+    assert!(moved > 0);
+
+    assert_eq!(db.repo.mc_to_id(&mc_id).await, Ok(Some(survivor)));
+    assert_eq!(
+        audited(&db.repo, Action::UnlinkIdentity, &casualty.to_string()).await,
+        [serde_json::json!({ "mc_id": mc_id, "by": "merge_accounts" })]
+    );
+    let closed = db.repo.user_info(&casualty).await.expect("Lookup");
+    assert!(closed.is_some_and(|v| v.closed_at.is_some() && v.mc_id.is_none()));
+    assert_eq!(
+        service.reconcile().await.expect("Reconciled").discrepancies,
+        []
+    );
+}
+
+#[tokio::test]
+async fn merging_conflicting_identities_changes_nothing() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+    let other = account(&db.repo, 2).await;
+    service
+        .grant(&other, Decimal::from(100), &Actor::System)
+        .await
+        .expect("Granted");
+
+    let account = |id| {
+        let repo = db.repo.clone();
+        async move {
+            repo.user_info(&id)
+                .await
+                .expect("Lookup")
+                .map(|v| (v.balance, v.disc_id, v.mc_id, v.closed_at))
+        }
+    };
+    let snapshot = || async {
+        (
+            account(owner).await,
+            account(other).await,
+            holding(&db.pool, &owner, abc).await,
+        )
+    };
+    let before = snapshot().await;
+
+    for dry_run in [true, false] {
+        assert_eq!(
+            service
+                .merge_accounts(&owner, &other, dry_run, &Actor::System)
+                .await,
+            Err(ServiceError::MergeConflict {
+                reason: MergeConflict::BothDiscord
+            })
+        );
+    }
+    assert_eq!(
+        service
+            .merge_accounts(&owner, &owner, false, &Actor::System)
+            .await,
+        Err(ServiceError::MergeConflict {
+            reason: MergeConflict::SameAccount
+        })
+    );
+
+    assert_eq!(snapshot().await, before);
+    assert_eq!(
+        db.repo
+            .discord_to_id(NonZeroU64::new(2).expect("Non-zero"))
+            .await,
+        Ok(Some(other))
+    );
+}
+
+#[tokio::test]
+async fn ownership_transfers() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+    let other = account(&db.repo, 2).await;
+
+    assert_eq!(
+        db.repo.stock_info(&ticker("XYZ")).await.map(|_| ()),
+        Err(Error::StockNotFound {
+            ticker: ticker("XYZ")
+        })
+    );
+    assert_eq!(
+        db.repo
+            .transfer_ownership(&abc, &other, &owner)
+            .await
+            .map(|_| ()),
+        Err(Error::NotStockOwner { ticker: abc })
+    );
+    assert_eq!(
+        db.repo
+            .transfer_ownership(&abc, &owner, &Uuid::nil())
+            .await
+            .map(|_| ()),
+        Err(Error::AccountNotFound { id: Uuid::nil() })
+    );
+
+    let info = db
+        .repo
+        .transfer_ownership(&abc, &owner, &other)
+        .await
+        .expect("Transferred");
+    assert_eq!(info.owner, Some(other));
+    assert_eq!(
+        db.repo.stock_info(&abc).await.map(|v| v.owner),
+        Ok(Some(other))
+    );
+}
+
+#[tokio::test]
+async fn owners_describe_their_stocks() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+    let other = account(&db.repo, 2).await;
+
+    let metadata = |name: &str, description: &str, icon_url: &str| StockMetadata {
+        name: Some(name.to_owned()),
+        description: Some(description.to_owned()),
+        icon_url: Some(icon_url.to_owned()),
+    };
+
+    assert_eq!(
+        db.repo.stock_info(&abc).await.map(|v| v.metadata),
+        Ok(StockMetadata::default())
+    );
+    assert_eq!(
+        service
+            .update_stock_metadata(&abc, &other, StockMetadata::default())
+            .await
+            .map(|_| ()),
+        Err(ServiceError::NotStockOwner { ticker: abc })
+    );
+    for bad in [
+        metadata(&"a".repeat(65), "", ""),
+        metadata("", &"é".repeat(501), ""),
+        metadata("", "", "http://example.com/a.png"),
+        metadata("", "", "https://"),
+        metadata("", "", "https://example.com/a b.png"),
+    ] {
+        assert!(
+            matches!(
+                service.update_stock_metadata(&abc, &owner, bad).await,
+                Err(ServiceError::InvalidMetadata { .. })
+            ),
+            "accepted invalid details"
+        );
+    }
+
+    let info = service
+        .update_stock_metadata(
+            &abc,
+            &owner,
+            metadata(
+                "  Alpha Co  ",
+                &"é".repeat(500),
+                "https://example.com/a.png",
+            ),
+        )
+        .await
+        .expect("Updated");
+    assert_eq!(info.metadata.name.as_deref(), Some("Alpha Co"));
+    assert_eq!(
+        db.repo.stock_info(&abc).await.map(|v| v.metadata),
+        Ok(info.metadata)
+    );
+
+    let Page { items: stocks, .. } = db
+        .repo
+        .list_stocks(&Pager::new(0, 1), StockOrdering::Ticker, "")
+        .await
+        .expect("Lookup")
+        .expect("Stocks exist");
+    assert_eq!(stocks[0].4.as_deref(), Some("Alpha Co"));
+
+    // Blank fields are cleared rather than stored
+    let info = service
+        .update_stock_metadata(&abc, &owner, metadata(" ", "", "https://example.com/b.png"))
+        .await
+        .expect("Updated");
+    assert_eq!(
+        info.metadata,
+        StockMetadata {
+            icon_url: Some("https://example.com/b.png".to_owned()),
+            ..StockMetadata::default()
+        }
+    );
+
+    let filter = AuditFilter {
+        action: Some(Action::UpdateStockMetadata),
+        ..AuditFilter::default()
+    };
+    let Page { items: entries, .. } = db
+        .repo
+        .audit_log(&Pager::new(0, 10), &filter)
+        .await
+        .expect("Lookup");
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].actor, Actor::Account(owner));
+    assert_eq!(entries[0].target.as_deref(), Some("ABC"));
+}
+
+#[tokio::test]
+async fn issuance_is_capped() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+
+    let info = db
+        .repo
+        .issue_shares(&abc, shares(6), &owner, 10)
+        .await
+        .expect("Issued");
+    assert_eq!(info.shares, shares(106));
+
+    assert_eq!(
+        db.repo
+            .issue_shares(&abc, shares(5), &owner, 10)
+            .await
+            .map(|_| ()),
+        Err(Error::IssuanceCapExceeded { available: 4 })
+    );
+
+    let info = db
+        .repo
+        .buyback(&abc, shares(50), &owner)
+        .await
+        .expect("Bought back");
+    assert_eq!(info.shares, shares(56));
+
+    assert_eq!(
+        db.repo
+            .buyback(&abc, shares(1000), &owner)
+            .await
+            .map(|_| ()),
+        Err(Error::InsufficientShares)
+    );
+}
+
+#[tokio::test]
+async fn dividends_pay_other_holders() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+    let holder = account(&db.repo, 2).await;
+    let half = Decimal::new(5, 1);
+
+    assert_eq!(
+        db.repo.pay_dividend(&abc, half, &owner).await.map(|_| ()),
+        Err(Error::NoShareholders { ticker: abc })
+    );
+
+    fund(&db.pool, &holder, 20).await;
+    db.repo
+        .place_order(&order(owner, abc, Side::Sell, 1, 10), None, Utc::now())
+        .await
+        .expect("Placed");
+    db.repo
+        .place_order(&order(holder, abc, Side::Buy, 1, 10), None, Utc::now())
+        .await
+        .expect("Placed");
+
+    let dividend = db
+        .repo
+        .pay_dividend(&abc, half, &owner)
+        .await
+        .expect("Paid");
+    assert_eq!(dividend.holders, 1);
+    assert_eq!(dividend.shares, 10);
+    assert_eq!(dividend.total, Decimal::from(5));
+
+    let info = db
+        .repo
+        .user_info(&holder)
+        .await
+        .expect("Lookup")
+        .expect("Registered");
+    assert_eq!(info.balance, Some(Decimal::from(15)));
+    assert_eq!(
+        audited(&db.repo, Action::PayDividend, "ABC").await,
+        [serde_json::json!({ "per_share": "0.5", "shares": 10, "holders": 1, "total": "5.0" })]
+    );
+
+    assert_eq!(
+        db.repo
+            .pay_dividend(&abc, Decimal::from(100), &owner)
+            .await
+            .map(|_| ()),
+        Err(Error::InsufficientFunds)
+    );
+}
+
+#[tokio::test]
+async fn seeds_apply_once() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let owner = NonZeroU64::new(1).expect("Non-zero");
+    let seed = Seed {
+        users: vec![SeedUser {
+            disc_id: owner,
+            balance: Decimal::from(250),
+        }],
+        stocks: vec![
+            SeedStock {
+                ticker: "ABC".to_owned(),
+                shares: 100,
+                owner,
+                price: Some(Decimal::from(12)),
+            },
+            SeedStock {
+                ticker: "XYZ".to_owned(),
+                shares: 10,
+                owner: NonZeroU64::new(2).expect("Non-zero"),
+                price: None,
+            },
+            SeedStock {
+                ticker: "B4D".to_owned(),
+                shares: 10,
+                owner,
+                price: None,
+            },
+        ],
+    };
+
+    let report = service.apply_seed(&seed).await;
+    assert_eq!(report.created, ["user 1", "stock ABC", "stock XYZ"]);
+    assert!(report.skipped.is_empty());
+    assert!(matches!(
+        report.failed.as_slice(),
+        [(entry, ServiceError::InvalidTicker { .. })] if entry == "stock B4D"
+    ));
+
+    let id = service
+        .resolve(&Identity::Discord(owner))
+        .await
+        .expect("Registered");
+    let info = service
+        .get_account_info(&id, Some(&id))
+        .await
+        .expect("Exists");
+    assert_eq!(info.balance, Some(Decimal::from(250)));
+
+    let Page {
+        items: holdings, ..
+    } = service
+        .get_holdings(&id, &Pager::new(0, 10), HoldingOrdering::Ticker, Some(&id))
+        .await
+        .expect("Lookup");
+    assert_eq!(holdings, [(ticker("ABC"), shares(100), Some(price(12)))]);
+    assert_eq!(db.repo.trade_history(&id, None, 10).await, Ok(Vec::new()));
+
+    let report = service.apply_seed(&seed).await;
+    assert!(report.created.is_empty());
+    assert_eq!(report.skipped, ["user 1", "stock ABC", "stock XYZ"]);
+    assert_eq!(report.failed.len(), 1);
+
+    let info = service
+        .get_account_info(&id, Some(&id))
+        .await
+        .expect("Exists");
+    assert_eq!(info.balance, Some(Decimal::from(250)));
+
+    // Once a stock has a price, a listing price can't move it
+    assert_eq!(
+        service.set_listing_price(&ticker("ABC"), price(99)).await,
+        Ok(false)
+    );
+}
+
+#[tokio::test]
+async fn audit_log_filters() {
+    let Some(db) = test_db().await else { return };
+    account(&db.repo, 1).await;
+
+    db.repo
+        .record_audit(&NewAuditEntry {
+            actor: Actor::System,
+            action: Action::AdminCommand,
+            target: Some("freeze".to_owned()),
+            details: serde_json::json!({}),
+        })
+        .await
+        .expect("Recorded");
+
+    let Page {
+        items: entries,
+        total,
+        ..
+    } = db
+        .repo
+        .audit_log(&Pager::new(0, 10), &AuditFilter::default())
+        .await
+        .expect("Lookup");
+    assert_eq!(total, 2);
+    assert_eq!(entries[0].action, Action::AdminCommand);
+    assert_eq!(entries[1].action, Action::Register);
+
+    let filter = AuditFilter {
+        action: Some(Action::AdminCommand),
+        ..AuditFilter::default()
+    };
+    let Page {
+        items: entries,
+        total,
+        ..
+    } = db
+        .repo
+        .audit_log(&Pager::new(0, 10), &filter)
+        .await
+        .expect("Lookup");
+    assert_eq!(total, 1);
+    assert_eq!(entries[0].target.as_deref(), Some("freeze"));
+}
+
+#[tokio::test]
+async fn admins_register_players_once() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let admin = Actor::Discord(NonZeroU64::new(1).expect("Non zero"));
+    let mc = Uuid::from_u128(7);
+
+    let id = service
+        .register_player(&mc, &admin)
+        .await
+        .expect("Registered");
+    assert_eq!(service.resolve(&Identity::Minecraft(mc)).await, Ok(id));
+    assert_eq!(
+        service.register_player(&mc, &admin).await,
+        Err(ServiceError::PlayerRegistered { id })
+    );
+
+    // Only the first registration did anything, on the admin's behalf
+    let entries = db
+        .repo
+        .audit_log(&Pager::new(0, 10), &AuditFilter::default())
+        .await
+        .expect("Lookup")
+        .items;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        (entries[0].action, entries[0].actor),
+        (Action::Register, admin)
+    );
+}
+
+#[tokio::test]
+async fn about_counts_what_users_see() {
+    let Some(db) = test_db().await else { return };
+    let latest = sqlx::migrate!("../migrations")
+        .iter()
+        .map(|migration| migration.version)
+        .max();
+    assert_eq!(db.repo.schema_version().await, Ok(latest));
+
+    let owner = listed(&db.repo, ticker("ABC")).await;
+    db.repo
+        .create_stock(&ticker("XYZ"), shares(100), &owner, &Actor::System)
+        .await
+        .expect("Listed");
+    db.repo
+        .set_stock_status(&ticker("XYZ"), StockStatus::Delisted, &Actor::System)
+        .await
+        .expect("Delisted");
+    db.repo
+        .register_user(None, Some(&Uuid::from_u128(7)), &Actor::System)
+        .await
+        .expect("Registered");
+    db.repo
+        .ensure_system_account(&Uuid::from_u128(8))
+        .await
+        .expect("Created");
+
+    // The exchange's own accounts and delisted stocks aren't counted
+    assert_eq!(
+        db.repo.exchange_counts().await,
+        Ok(ExchangeCounts {
+            accounts: 2,
+            stocks: 1
+        })
+    );
+}
+
+#[tokio::test]
+async fn users_are_browsed_by_links() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let mc = Uuid::from_u128(7);
+
+    let discord = account(&db.repo, 1).await;
+    let minecraft = db
+        .repo
+        .register_user(None, Some(&mc), &Actor::System)
+        .await
+        .expect("Registered")
+        .id();
+    let both = db
+        .repo
+        .register_user(
+            NonZeroU64::new(3),
+            Some(&Uuid::from_u128(8)),
+            &Actor::System,
+        )
+        .await
+        .expect("Registered")
+        .id();
+    fund(&db.pool, &discord, 50).await;
+    fund(&db.pool, &both, 10).await;
+
+    for (links, order, expected) in [
+        (
+            UserLinks::Any,
+            UserOrdering::Newest,
+            &[both, minecraft, discord][..],
+        ),
+        (
+            UserLinks::Any,
+            UserOrdering::Balance,
+            &[discord, both, minecraft],
+        ),
+        (UserLinks::Discord, UserOrdering::Newest, &[both, discord]),
+        (
+            UserLinks::Minecraft,
+            UserOrdering::Newest,
+            &[both, minecraft],
+        ),
+        (
+            UserLinks::OneSided,
+            UserOrdering::Balance,
+            &[discord, minecraft],
+        ),
+    ] {
+        let filter = UserFilter { links, order };
+        let Page {
+            items: users,
+            total,
+            ..
+        } = db
+            .repo
+            .list_users(&Pager::new(0, 10), filter)
+            .await
+            .expect("Lookup");
+        let ids: Vec<_> = users.iter().map(|v| v.id).collect();
+
+        assert_eq!(ids, expected, "{filter:?}");
+        assert_eq!(usize::try_from(total), Ok(expected.len()), "{filter:?}");
+    }
+
+    let Page { items: users, .. } = service
+        .list_users(&Pager::new(0, 10), UserFilter::default(), false)
+        .await
+        .expect("Lookup");
+    assert!(users.iter().all(|v| v.balance.is_none()));
+
+    let Page { items: users, .. } = service
+        .list_users(&Pager::new(0, 10), UserFilter::default(), true)
+        .await
+        .expect("Lookup");
+    assert_eq!(users[2].balance, Some(Decimal::from(50)));
+
+    let Page {
+        items: users,
+        total,
+        ..
+    } = db
+        .repo
+        .list_users(&Pager::new(4, 2), UserFilter::default())
+        .await
+        .expect("Lookup");
+    assert!(users.is_empty());
+    assert_eq!(total, 3);
+}
+
+#[tokio::test]
+async fn daily_closes_end_at_midnight_utc() {
+    let Some(db) = test_db().await else { return };
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+    let now = Utc::now();
+    let today = now.date_naive();
+    let midnight = |days_ago| {
+        (today - TimeDelta::days(days_ago))
+            .and_time(NaiveTime::MIN)
+            .and_utc()
+    };
+
+    trade(
+        &db.pool,
+        &owner,
+        abc,
+        10,
+        3,
+        midnight(2) - TimeDelta::seconds(1),
+    )
+    .await;
+    trade(&db.pool, &owner, abc, 12, 5, midnight(2)).await;
+    // Always at least a day ago, so it's what the change would be measured from without closes
+    trade(&db.pool, &owner, abc, 16, 1, midnight(1)).await;
+
+    let closes = |date| {
+        sqlx::query_as::<_, (Decimal, i64)>(
+            "SELECT close, volume FROM daily_closes WHERE ticker = $1 AND date = $2",
+        )
+        .bind(abc.as_str())
+        .bind(date)
+        .fetch_optional(&db.pool)
+    };
+
+    assert_eq!(
+        db.repo
+            .record_daily_closes(today - TimeDelta::days(3))
+            .await,
+        Ok(1)
+    );
+    assert_eq!(
+        db.repo
+            .record_daily_closes(today - TimeDelta::days(3))
+            .await,
+        Ok(0)
+    );
+    assert_eq!(
+        db.repo
+            .record_daily_closes(today - TimeDelta::days(2))
+            .await,
+        Ok(1)
+    );
+
+    assert_eq!(
+        closes(today - TimeDelta::days(3)).await.expect("Lookup"),
+        Some((Decimal::from(10), 3))
+    );
+    assert_eq!(
+        closes(today - TimeDelta::days(2)).await.expect("Lookup"),
+        Some((Decimal::from(12), 5))
+    );
+
+    let change = db
+        .repo
+        .price_change(&abc, now)
+        .await
+        .expect("Lookup")
+        .expect("Traded");
+    assert_eq!(
+        change,
+        PriceChange {
+            last: Decimal::from(16),
+            reference: Decimal::from(12),
+            since_close: true,
+        }
+    );
+
+    let movers = db.repo.top_movers(today, now, 5).await.expect("Lookup");
+    assert_eq!(movers.gainers.len(), 1);
+    assert_eq!(
+        movers.gainers[0].pct_change.round_dp(2),
+        change.pct().round_dp(2)
+    );
+}
+
+#[tokio::test]
+async fn stocks_without_a_close_change_over_the_last_day() {
+    let Some(db) = test_db().await else { return };
+    let (abc, new) = (ticker("ABC"), ticker("NEW"));
+    let owner = listed(&db.repo, abc).await;
+    db.repo
+        .create_stock(&new, shares(100), &owner, &Actor::System)
+        .await
+        .expect("Listed");
+    let now = Utc::now();
+
+    assert_eq!(db.repo.price_change(&new, now).await, Ok(None));
+
+    trade(&db.pool, &owner, abc, 20, 1, now - TimeDelta::days(2)).await;
+    trade(&db.pool, &owner, abc, 30, 1, now - TimeDelta::hours(1)).await;
+    // Listed and first traded after the last close
+    trade(&db.pool, &owner, new, 10, 1, now - TimeDelta::hours(2)).await;
+    trade(&db.pool, &owner, new, 11, 1, now - TimeDelta::hours(1)).await;
+
+    assert_eq!(
+        db.repo.price_change(&new, now).await,
+        Ok(Some(PriceChange {
+            last: Decimal::from(11),
+            reference: Decimal::from(10),
+            since_close: false,
+        }))
+    );
+    assert_eq!(
+        db.repo.price_change(&abc, now).await,
+        Ok(Some(PriceChange {
+            last: Decimal::from(30),
+            reference: Decimal::from(20),
+            since_close: false,
+        }))
+    );
+
+    let movers = db
+        .repo
+        .top_movers(now.date_naive(), now, 5)
+        .await
+        .expect("Lookup");
+    let gainers: Vec<_> = movers
+        .gainers
+        .iter()
+        .map(|mover| (mover.ticker, mover.pct_change))
+        .collect();
+    assert_eq!(
+        gainers,
+        vec![(abc, Decimal::from(50)), (new, Decimal::from(10))]
+    );
+}
+
+#[tokio::test]
+async fn imported_prices_are_recorded_once() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let abc = ticker("ABC");
+    let owner = listed(&db.repo, abc).await;
+    let balance = || async {
+        db.repo
+            .user_info(&owner)
+            .await
+            .expect("Lookup")
+            .and_then(|info| info.balance)
+    };
+    let before = balance().await;
+    let today = Utc::now().date_naive();
+    let at = |days_ago, hour| {
+        ((today - TimeDelta::days(days_ago)).and_time(NaiveTime::MIN) + TimeDelta::hours(hour))
+            .and_utc()
+            .to_rfc3339()
+    };
+
+    let data = format!(
+        "ticker,timestamp,price,volume\n\
+        ABC,{},5,10\n\
+        ABC,{},6,4\n\
+        ZZZ,{},1,1\n\
+        ABC,{},7.50,1\n",
+        at(3, 1),
+        at(3, 2),
+        at(3, 3),
+        at(2, 1)
+    );
+    let file = || PriceFile::parse(data.as_bytes(), 10, Utc::now()).expect("Within the cap");
+
+    let report = service.import_prices(file()).await.expect("Imported");
+    assert_eq!((report.inserted, report.duplicates), (3, 0));
+    assert_eq!(report.invalid.len(), 1);
+    assert_eq!(report.invalid[0].line, 4);
+
+    let report = service.import_prices(file()).await.expect("Imported");
+    assert_eq!((report.inserted, report.duplicates), (0, 3));
+
+    let latest = db.repo.latest_prices(Some(&[abc])).await.expect("Lookup");
+    assert_eq!(latest[0].price.get(), Decimal::new(750, 2));
+
+    let closes = sqlx::query_as::<_, (NaiveDate, Decimal, i64)>(
+        "SELECT date, close, volume FROM daily_closes WHERE ticker = $1 ORDER BY date",
+    )
+    .bind(abc.as_str())
+    .fetch_all(&db.pool)
+    .await
+    .expect("Lookup");
+    assert_eq!(
+        closes,
+        vec![
+            (today - TimeDelta::days(3), Decimal::from(6), 14),
+            (today - TimeDelta::days(2), Decimal::new(750, 2), 1),
+        ]
+    );
+
+    // Only prices were recorded
+    assert_eq!(balance().await, before);
+    assert!(
+        db.repo
+            .trade_history(&owner, None, 10)
+            .await
+            .expect("Lookup")
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn movers_and_summaries() {
+    let Some(db) = test_db().await else { return };
+    let (up, down) = (ticker("UPP"), ticker("DWN"));
+    let owner = listed(&db.repo, up).await;
+    db.repo
+        .create_stock(&down, shares(100), &owner, &Actor::System)
+        .await
+        .expect("Listed");
+    let now = Utc::now();
+
+    for (ticker, price, ago) in [(up, 10, 2), (up, 15, 1), (down, 10, 2), (down, 7, 1)] {
+        trade(
+            &db.pool,
+            &owner,
+            ticker,
+            price,
+            1,
+            now - TimeDelta::minutes(ago),
+        )
+        .await;
+    }
+
+    let movers = db
+        .repo
+        .top_movers(now.date_naive(), now, 5)
+        .await
+        .expect("Lookup");
+    assert_eq!(movers.gainers.len(), 1);
+    assert_eq!(movers.gainers[0].ticker, up);
+    assert_eq!(movers.gainers[0].pct_change, Decimal::from(50));
+    assert_eq!(movers.losers.len(), 1);
+    assert_eq!(movers.losers[0].ticker, down);
+    assert_eq!(movers.losers[0].pct_change, Decimal::from(-30));
+
+    // Trades a few minutes ago might fall on yesterday just after midnight
+    let date = (now - TimeDelta::minutes(2)).date_naive();
+    let summary = db.repo.daily_summary(date).await.expect("Summarized");
+    assert!(summary.trades >= 2, "{summary:?}");
+
+    assert_eq!(db.repo.summary_sent(date).await, Ok(false));
+    db.repo.record_summary_sent(date).await.expect("Recorded");
+    db.repo
+        .record_summary_sent(date)
+        .await
+        .expect("Recorded again");
+    assert_eq!(db.repo.summary_sent(date).await, Ok(true));
+}
+
+#[tokio::test]
+async fn portfolio_snapshots_are_taken_once_a_day() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let abc = ticker("ABC");
+    let first = NaiveDate::from_ymd_opt(2025, 1, 1).expect("Valid date");
+    let second = first + TimeDelta::days(1);
+
+    let owner = listed(&db.repo, abc).await;
+    fund(&db.pool, &owner, 20).await;
+    trade(&db.pool, &owner, abc, 5, 1, Utc::now()).await;
+
+    let closed = account(&db.repo, 2).await;
+    db.repo
+        .close_account(&closed, None, false, &Actor::Account(closed))
+        .await
+        .expect("Closed");
+
+    assert_eq!(service.record_portfolio_snapshots(first).await, Ok(1));
+    assert_eq!(service.record_portfolio_snapshots(first).await, Ok(0));
+
+    let late = account(&db.repo, 3).await;
+    assert_eq!(service.record_portfolio_snapshots(second).await, Ok(2));
+
+    let history = service
+        .portfolio_history(&owner, first)
+        .await
+        .expect("Lookup");
+    let snapshot = |date| PortfolioSnapshot {
+        date,
+        balance: Decimal::from(20),
+        holdings_value: Decimal::from(500),
+    };
+    assert_eq!(history, [snapshot(first), snapshot(second)]);
+    assert_eq!(history[0].net_worth(), Decimal::from(520));
+
+    assert_eq!(
+        service.portfolio_history(&owner, second).await,
+        Ok(vec![snapshot(second)])
+    );
+    assert_eq!(
+        service
+            .portfolio_history(&late, first)
+            .await
+            .expect("Lookup")
+            .iter()
+            .map(|snapshot| snapshot.date)
+            .collect::<Vec<_>>(),
+        [second]
+    );
+    assert_eq!(service.portfolio_history(&closed, first).await, Ok(vec![]));
+}
+
+#[tokio::test]
+async fn stocks_are_found_by_their_details() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let (melon, mine, pct) = (ticker("MELON"), ticker("MINE"), ticker("PCT"));
+
+    let owner = listed(&db.repo, melon).await;
+    for ticker in [mine, pct] {
+        db.repo
+            .create_stock(&ticker, shares(100), &owner, &Actor::System)
+            .await
+            .expect("Listed");
+    }
+    trade(&db.pool, &owner, mine, 7, 1, Utc::now()).await;
+
+    let describe = |ticker, name: &str, description: &str| {
+        let metadata = StockMetadata {
+            name: Some(name.to_owned()),
+            description: Some(description.to_owned()),
+            icon_url: None,
+        };
+        let repo = db.repo.clone();
+        async move {
+            repo.update_stock_metadata(&ticker, &metadata, &owner)
+                .await
+                .expect("Described");
+        }
+    };
+    describe(melon, "Melon Farm", "Fresh melons from spawn").await;
+    describe(
+        mine,
+        "Deep Mining Co",
+        "We dig for diamonds, sometimes melons",
+    )
+    .await;
+    describe(pct, "Percent", "Always 100% honest").await;
+
+    let page = Pager::new(0, 10);
+    let search = |query| service.search_stocks(query, &page);
+    let found = |page: Page<StockMatch>| {
+        page.items
+            .iter()
+            .map(|found| (found.ticker, found.matched))
+            .collect::<Vec<_>>()
+    };
+
+    let melons = search("melon").await.expect("Searched");
+    assert_eq!(melons.total, 2);
+    assert_eq!(
+        found(melons.clone()),
+        [
+            (melon, SearchField::Ticker),
+            (mine, SearchField::Description)
+        ]
+    );
+    assert_eq!(melons.items[1].price, Some(price(7)));
+
+    assert_eq!(
+        found(search("farm").await.expect("Searched")),
+        [(melon, SearchField::Name)]
+    );
+    // Close enough to a word in the name
+    assert_eq!(
+        found(search("minning").await.expect("Searched")),
+        [(mine, SearchField::Name)]
+    );
+
+    // Wildcards are matched as they are
+    assert_eq!(
+        found(search("0%").await.expect("Searched")),
+        [(pct, SearchField::Description)]
+    );
+    assert_eq!(search("%_").await.expect("Searched").total, 0);
+    assert_eq!(search(" m ").await.expect("Searched").total, 0);
+}
+
+#[tokio::test]
+async fn tickers_are_suggested_by_prefix() {
+    let Some(db) = test_db().await else { return };
+    let service = Service::new(db.repo.clone());
+    let (melon, mine, pct) = (ticker("MELON"), ticker("MINE"), ticker("PCT"));
+
+    let owner = listed(&db.repo, mine).await;
+    for ticker in [pct, melon] {
+        db.repo
+            .create_stock(&ticker, shares(100), &owner, &Actor::System)
+            .await
+            .expect("Listed");
+    }
+    let metadata = StockMetadata {
+        name: Some("Melon Farm".to_owned()),
+        description: None,
+        icon_url: None,
+    };
+    db.repo
+        .update_stock_metadata(&melon, &metadata, &owner)
+        .await
+        .expect("Named");
+
+    assert_eq!(
+        service.search_tickers(" $m").await,
+        Ok(vec![(melon, Some("Melon Farm".to_owned())), (mine, None)])
+    );
+    assert_eq!(
+        service.search_tickers("").await.map(|found| found.len()),
+        Ok(3)
+    );
+    assert_eq!(service.search_tickers("mines").await, Ok(vec![]));
+    // Nothing could start with these
+    assert_eq!(service.search_tickers("m1").await, Ok(vec![]));
+    assert_eq!(service.search_tickers("melons").await, Ok(vec![]));
+}
@@ -0,0 +1,92 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Embeds every message catalog in `locales/`, so adding a locale only takes a new file, and bakes
+//! in the commit being built as `RSE_GIT_HASH`
+
+use std::{env, fmt::Write, fs, path::PathBuf, process::Command};
+
+fn main() {
+    embed_locales();
+    embed_git_hash();
+}
+
+fn embed_locales() {
+    let dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("Set by cargo")).join("locales");
+    println!("cargo::rerun-if-changed={}", dir.display());
+
+    let mut locales: Vec<_> = fs::read_dir(&dir)
+        .expect("Could not read locales")
+        .map(|entry| entry.expect("Could not read locale").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+    locales.sort();
+
+    let mut buff = String::from("&[\n");
+
+    for path in locales {
+        let locale = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .expect("Locale files are named after their locale");
+
+        let path = path.to_str().expect("Locale paths are UTF-8");
+
+        writeln!(buff, "    ({locale:?}, include_str!({path:?})),").expect("Never fails");
+    }
+
+    buff.push(']');
+
+    let out = PathBuf::from(env::var("OUT_DIR").expect("Set by cargo")).join("locales.rs");
+    fs::write(out, buff).expect("Could not write locales");
+}
+
+/// Builds without the repository, such as in a container, can pass the hash in `RSE_GIT_HASH`
+/// instead, and are `unknown` otherwise
+fn embed_git_hash() {
+    println!("cargo::rerun-if-env-changed=RSE_GIT_HASH");
+
+    let hash = env::var("RSE_GIT_HASH").ok().or_else(|| {
+        let root = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("Set by cargo"))
+            .join("..")
+            .join(".git");
+
+        // Only watched when present, as cargo reruns the script every build for missing paths
+        for path in ["HEAD", "refs/heads", "packed-refs"] {
+            let path = root.join(path);
+            if path.exists() {
+                println!("cargo::rerun-if-changed={}", path.display());
+            }
+        }
+
+        let output = Command::new("git")
+            .args(["rev-parse", "--short", "HEAD"])
+            .output()
+            .ok()
+            .filter(|output| output.status.success())?;
+
+        String::from_utf8(output.stdout)
+            .ok()
+            .map(|hash| hash.trim().to_owned())
+    });
+
+    println!(
+        "cargo::rustc-env=RSE_GIT_HASH={}",
+        hash.as_deref().unwrap_or("unknown")
+    );
+}
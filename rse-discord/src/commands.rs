@@ -15,10 +15,244 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::{fmt::Write, str::FromStr};
+
+use poise::serenity_prelude::AutocompleteChoice;
+use rse_core::model::{
+    Mover, Price, Shares,
+    ticker::{self, Ticker},
+    withdrawal::Address,
+};
+use rse_core::repo::StockRepository;
+use rust_decimal::Decimal;
+use snafu::{ResultExt, ensure};
+use uuid::Uuid;
+
+use crate::{
+    Context, Error,
+    error::{
+        InvalidAddressSnafu, InvalidPriceSnafu, InvalidTickerSnafu, OutOfRangeSnafu,
+        PlayerLookupSnafu, TradingDisabledSnafu,
+    },
+};
+
+pub(crate) use defer::defer_ephemeral_or_log;
+
+pub use about::about;
+pub use admin::admin;
+pub use api_key::api_key;
+pub use close_account::close_account;
+pub use company::company;
+pub use deposit::deposit;
+pub use dividend::dividend;
+pub use export::export;
+pub use find::find;
+pub use link::link;
+pub use me::me;
+pub use order::order;
+pub use orderbook::orderbook;
+pub use performance::performance;
 pub use portfolio::portfolio;
+pub use privacy::privacy;
 pub use register::register;
+pub use settings::settings;
+pub use statement::statement;
+pub use status::status;
 pub use stocks::stocks;
+pub use top::top;
+pub use trade::{buy, sell};
+pub use withdraw::withdraw;
 
+mod about;
+mod admin;
+mod api_key;
+mod budget;
+mod close_account;
+mod company;
+mod confirm;
+mod defer;
+mod deposit;
+mod dividend;
+mod export;
+mod find;
+mod link;
+mod me;
+mod order;
+mod orderbook;
+mod performance;
+mod permission;
 mod portfolio;
+mod presses;
+mod privacy;
 mod register;
+mod settings;
+mod statement;
+mod status;
 mod stocks;
+mod top;
+mod trade;
+mod withdraw;
+
+/// Fails if the server the command was invoked in disabled trading
+pub(crate) async fn ensure_trading<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
+    if let Some(guild) = ctx.guild_id() {
+        let settings = ctx.data().service().guild_settings(guild.into()).await?;
+        ensure!(settings.trading_enabled, TradingDisabledSnafu);
+    }
+
+    Ok(())
+}
+
+/// A ticker passed in by a user, in any case and with or without a leading `$`. Parsed by poise
+/// before the command runs, so a typo is reported as such without running it
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TickerArg(Ticker);
+
+impl std::ops::Deref for TickerArg {
+    type Target = Ticker;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl FromStr for TickerArg {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_ticker(s).map(Self)
+    }
+}
+
+/// Suggests tickers starting with what the user has typed so far, labelled with the stock's name.
+/// Shared by every command that takes a [`TickerArg`]
+pub(crate) async fn autocomplete_ticker<R: StockRepository>(
+    ctx: Context<'_, R>,
+    partial: &str,
+) -> Vec<AutocompleteChoice> {
+    let stocks = match ctx.data().service().search_tickers(partial).await {
+        Ok(stocks) => stocks,
+        Err(err) => {
+            // Typing the ticker out in full still works
+            tracing::warn!(%err, "Couldn't suggest tickers");
+            return Vec::new();
+        }
+    };
+
+    stocks
+        .into_iter()
+        .map(|(ticker, name)| {
+            let label = match name {
+                Some(name) => format!("${ticker} · {name}"),
+                None => format!("${ticker}"),
+            };
+
+            AutocompleteChoice::new(label, ticker.to_string())
+        })
+        .collect()
+}
+
+/// Parses a ticker passed in by a user, ignoring a leading `$`
+#[allow(clippy::result_large_err)] // Only ever returned to poise, which expects this error type
+fn parse_ticker(input: &str) -> Result<Ticker, Error> {
+    let trimmed = input.trim();
+
+    Ticker::try_from(trimmed.strip_prefix('$').unwrap_or(trimmed)).context(InvalidTickerSnafu {
+        input: trimmed.to_owned(),
+    })
+}
+
+/// Parses a Kromer address passed in by a user
+#[allow(clippy::result_large_err)] // Only ever returned to poise, which expects this error type
+fn parse_address(input: &str) -> Result<Address, Error> {
+    let trimmed = input.trim();
+
+    Address::try_from(trimmed).context(InvalidAddressSnafu { input: trimmed })
+}
+
+/// Resolves a Minecraft player passed in by a user, either by UUID or by username. Usernames are
+/// looked up through Mojang unless they were resolved recently.
+async fn resolve_player<R: StockRepository>(
+    ctx: Context<'_, R>,
+    input: &str,
+) -> Result<Uuid, Error> {
+    let trimmed = input.trim();
+
+    if let Ok(id) = Uuid::parse_str(trimmed) {
+        return Ok(id);
+    }
+
+    let service = ctx.data().service();
+
+    if let Some(id) = service.mc_username_to_uuid(trimmed).await? {
+        return Ok(id);
+    }
+
+    let profile = ctx
+        .data()
+        .mojang()
+        .profile(trimmed)
+        .await
+        .context(PlayerLookupSnafu)?;
+
+    // Only saves a lookup next time, so not worth failing over
+    if let Err(err) = service.cache_mc_username(trimmed, &profile.id).await {
+        tracing::warn!(%err, "Couldn't cache Minecraft username");
+    }
+
+    Ok(profile.id)
+}
+
+/// Parses a price per share passed in by a user
+#[allow(clippy::result_large_err)] // Only ever returned to poise, which expects this error type
+fn parse_price(input: &str) -> Result<Price, Error> {
+    let trimmed = input.trim();
+    let price = Decimal::from_str(trimmed).context(InvalidPriceSnafu { input: trimmed })?;
+
+    Price::new(price).context(OutOfRangeSnafu {
+        input: trimmed,
+        what: "price",
+    })
+}
+
+/// Checks a number of shares passed in by a user
+#[allow(clippy::result_large_err)] // Only ever returned to poise, which expects this error type
+fn parse_shares(input: u32) -> Result<Shares, Error> {
+    Shares::new(input).context(OutOfRangeSnafu {
+        input: input.to_string(),
+        what: "number of shares",
+    })
+}
+
+/// Parses the start of a ticker passed in by a user to filter by, ignoring a leading `$`
+#[allow(clippy::result_large_err)] // Only ever returned to poise, which expects this error type
+fn parse_ticker_prefix(input: &str) -> Result<String, Error> {
+    let trimmed = input.trim();
+
+    ticker::parse_prefix(trimmed.strip_prefix('$').unwrap_or(trimmed)).context(InvalidTickerSnafu {
+        input: trimmed.to_owned(),
+    })
+}
+
+/// Renders movers in a diff code block, so Discord colours gainers green and losers red
+pub(crate) fn movers_column(movers: &[Mover], gainers: bool) -> String {
+    if movers.is_empty() {
+        return "None".to_owned();
+    }
+
+    let (sign, arrow) = if gainers { ('+', '▲') } else { ('-', '▼') };
+    let mut buff = String::from("```diff\n");
+
+    for mover in movers {
+        writeln!(
+            buff,
+            "{sign} {arrow} ${} {:.2} ({:+.1}%)",
+            mover.ticker, mover.last_price, mover.pct_change
+        )
+        .expect("Never fails");
+    }
+
+    buff.push_str("```");
+
+    buff
+}
@@ -0,0 +1,121 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed},
+};
+use rse_core::{error::Error as RscError, model::ExchangeCounts, repo::StockRepository};
+
+use crate::{
+    Context, Error,
+    gateway::STARTED,
+    i18n::{self, t},
+};
+
+/// Shows what the bot is, which version is running and how big the exchange is
+#[poise::command(slash_command, prefix_command, guild_only)]
+pub async fn about<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
+    let locale = &i18n::locale(ctx).await;
+    let service = ctx.data().service();
+
+    let (schema, counts) = tokio::join!(service.schema_version(), service.exchange_counts());
+
+    send_reply(
+        ctx,
+        CreateReply::default()
+            .ephemeral(true)
+            .embed(about_embed(schema, counts, locale)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Lays out everything `/about` shows. Lookups that failed are shown as unavailable rather than
+/// failing the whole command, as it's often used to check on a bot that isn't working
+fn about_embed(
+    schema: Result<Option<i64>, RscError>,
+    counts: Result<ExchangeCounts, RscError>,
+    locale: &str,
+) -> CreateEmbed {
+    let unavailable = |err: RscError| {
+        tracing::warn!(%err, "Couldn't look up part of /about");
+        t!(locale, "about.unavailable")
+    };
+
+    let schema = schema.map_or_else(unavailable, |version| {
+        version.map_or_else(|| t!(locale, "about.no_migrations"), |v| v.to_string())
+    });
+    let (accounts, stocks) = counts.map_or_else(
+        |err| {
+            let unavailable = unavailable(err);
+            (unavailable.clone(), unavailable)
+        },
+        |counts| (counts.accounts.to_string(), counts.stocks.to_string()),
+    );
+
+    CreateEmbed::new()
+        .title(t!(locale, "about.title"))
+        .description(t!(locale, "about.description"))
+        .field(
+            t!(locale, "about.version"),
+            format!("{} ({})", env!("CARGO_PKG_VERSION"), env!("RSE_GIT_HASH")),
+            true,
+        )
+        .field(t!(locale, "about.schema"), schema, true)
+        .field(
+            t!(locale, "about.up_since"),
+            format!("<t:{}:R>", STARTED.timestamp()),
+            true,
+        )
+        .field(t!(locale, "about.accounts"), accounts, true)
+        .field(t!(locale, "about.stocks"), stocks, true)
+        .color(Color::BLITZ_BLUE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_lookups_are_shown_as_unavailable() {
+        let counts = ExchangeCounts {
+            accounts: 12,
+            stocks: 3,
+        };
+        let down = RscError::DatabaseError {
+            source: rse_core::repo::Error::Unavailable,
+        };
+
+        let embed = serde_json::to_value(about_embed(Err(down), Ok(counts), "en")).expect("Valid");
+        let values: Vec<_> = embed["fields"]
+            .as_array()
+            .expect("Has fields")
+            .iter()
+            .map(|field| field["value"].as_str().expect("Set"))
+            .collect();
+        assert_eq!(values[1], "Unavailable");
+        assert_eq!(&values[3..], ["12", "3"]);
+
+        let embed = serde_json::to_value(about_embed(Ok(Some(7)), Err(down), "en")).expect("Valid");
+        assert_eq!(embed["fields"][1]["value"], "7");
+        assert_eq!(embed["fields"][3]["value"], "Unavailable");
+        assert_eq!(embed["fields"][4]["value"], "Unavailable");
+    }
+}
@@ -0,0 +1,1554 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    io::Write as _,
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use flate2::{Compression, write::GzEncoder};
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{
+        Attachment, ButtonStyle, Color, CreateActionRow, CreateAttachment, CreateButton,
+        CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+        CreateInteractionResponseMessage, GuildChannel, Role,
+    },
+};
+use rse_core::{
+    display::{format_kromer, format_signed},
+    error::Error as RscErr,
+    import::{ImportReport, PriceFile},
+    model::{
+        AccountMerge, Page, Pager, StockStatus, UserFilter, UserInfo, UserLinks, UserOrdering,
+        adjustment::{Adjustment, AdjustmentReason},
+        audit::{Action, Actor, AuditEntry, AuditFilter, NewAuditEntry},
+        guild::{GuildSettings, Permission},
+        link::Identity,
+        ticker::Ticker,
+        usage::{CommandStats, UsageTotals},
+        withdrawal::Withdrawal,
+    },
+    repo::StockRepository,
+    snapshot::SnapshotCounts,
+};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{
+    Context, Error,
+    commands::{
+        TickerArg, autocomplete_ticker,
+        budget::{EmbedBudget, MAX_DESCRIPTION, MAX_FIELD_VALUE},
+        confirm::confirm,
+        defer_ephemeral_or_log, parse_address,
+        permission::{
+            can_halt, can_manage_balances, can_manage_stocks, can_view_admin, is_admin, is_staff,
+            require,
+        },
+        presses::{PageCursor, Presses},
+        resolve_player,
+    },
+    error::{
+        ForbiddenSnafu, InvalidOptionsSnafu, InvalidPriceSnafu, InvalidUuidSnafu,
+        SnapshotFileSnafu, SnapshotSnafu,
+    },
+    i18n,
+    notify::reconciliation_embed,
+};
+use snafu::{ResultExt, ensure};
+
+/// The identities linked to accounts, keyed by account
+type Identities = HashMap<Uuid, (Option<NonZeroU64>, Option<Uuid>)>;
+
+/// Which linked identities to list accounts by
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum LinksChoice {
+    /// Every account
+    Any,
+    /// Accounts linked to Discord
+    Discord,
+    /// Accounts linked to Minecraft
+    Minecraft,
+    /// Accounts linked to only one of the two
+    #[name = "One side only"]
+    OneSided,
+}
+
+impl From<LinksChoice> for UserLinks {
+    fn from(value: LinksChoice) -> Self {
+        match value {
+            LinksChoice::Any => Self::Any,
+            LinksChoice::Discord => Self::Discord,
+            LinksChoice::Minecraft => Self::Minecraft,
+            LinksChoice::OneSided => Self::OneSided,
+        }
+    }
+}
+
+/// What to sort accounts by
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum UserSortChoice {
+    /// Most recently registered first
+    Newest,
+    /// Largest balance first
+    Balance,
+}
+
+impl From<UserSortChoice> for UserOrdering {
+    fn from(value: UserSortChoice) -> Self {
+        match value {
+            UserSortChoice::Newest => Self::Newest,
+            UserSortChoice::Balance => Self::Balance,
+        }
+    }
+}
+
+/// Why a balance is being adjusted
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum ReasonChoice {
+    /// Paying back Kromer lost to a bug or a mistake
+    Refund,
+    /// Fixing a balance that is wrong for any other reason
+    Correction,
+    /// Paying out a prize
+    Prize,
+    /// Taking Kromer away as a punishment
+    Penalty,
+}
+
+impl From<ReasonChoice> for AdjustmentReason {
+    fn from(value: ReasonChoice) -> Self {
+        match value {
+            ReasonChoice::Refund => Self::Refund,
+            ReasonChoice::Correction => Self::Correction,
+            ReasonChoice::Prize => Self::Prize,
+            ReasonChoice::Penalty => Self::Penalty,
+        }
+    }
+}
+
+/// Privileged commands, for configured admins and members of roles granted their permissions
+#[poise::command(
+    slash_command,
+    check = "is_staff",
+    ephemeral,
+    subcommands(
+        "adjust",
+        "audit",
+        "botstats",
+        "close",
+        "halt",
+        "import_prices",
+        "merge",
+        "player",
+        "reconcile",
+        "register_mc",
+        "resume",
+        "reverse",
+        "settings",
+        "snapshot",
+        "users",
+        "withdrawals"
+    ),
+    subcommand_required
+)]
+#[allow(clippy::unused_async)] // Poise requires commands to be async
+pub async fn admin<R: StockRepository>(_ctx: Context<'_, R>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Records the invocation of an admin command in the audit log
+pub(super) async fn record_invocation<R: StockRepository>(
+    ctx: Context<'_, R>,
+    details: serde_json::Value,
+) -> Result<(), Error> {
+    let entry = NewAuditEntry {
+        actor: Actor::Discord(ctx.author().id.into()),
+        action: Action::AdminCommand,
+        target: None,
+        details: serde_json::json!({
+            "command": ctx.command().qualified_name,
+            "args": details,
+        }),
+    };
+
+    ctx.data().service().record_audit(&entry).await?;
+
+    Ok(())
+}
+
+/// Adds Kromer to an account's balance by hand, or takes it away with a negative delta
+#[poise::command(slash_command, check = "can_manage_balances", ephemeral)]
+async fn adjust<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The UUID of the account to adjust"] account: String,
+    #[description = "The Kromer to add, negative to take it away"] delta: String,
+    #[description = "Why the balance is being adjusted"] reason: ReasonChoice,
+    #[description = "Let the balance go below zero. Only for configured admins"]
+    allow_negative: Option<bool>,
+) -> Result<(), Error> {
+    let allow_negative = allow_negative.unwrap_or_default();
+    ensure_may_overdraw(ctx, allow_negative)?;
+    let id = Uuid::parse_str(account.trim()).context(InvalidUuidSnafu { input: &account })?;
+    let delta = Decimal::from_str(delta.trim()).context(InvalidPriceSnafu {
+        input: delta.trim(),
+    })?;
+    let reason = AdjustmentReason::from(reason);
+
+    record_invocation(
+        ctx,
+        serde_json::json!({
+            "account": id,
+            "delta": delta,
+            "reason": reason.as_str(),
+            "allow_negative": allow_negative,
+        }),
+    )
+    .await?;
+
+    let summary = CreateEmbed::new()
+        .title("Adjust balance?")
+        .description(format!(
+            "`{}` to `{id}` as a {reason}",
+            format_signed(delta)
+        ))
+        .color(Color::GOLD);
+
+    if !confirm(ctx, summary, Duration::from_mins(2)).await? {
+        return Ok(());
+    }
+
+    let adjustment = ctx
+        .data()
+        .service()
+        .admin_adjust_balance(
+            &id,
+            delta,
+            reason,
+            allow_negative,
+            &Actor::Discord(ctx.author().id.into()),
+        )
+        .await?;
+
+    send_reply(
+        ctx,
+        CreateReply::default().embed(adjustment_embed("Balance adjusted", &adjustment)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Only configured admins may let a balance go below zero, not roles granted permissions
+#[allow(clippy::result_large_err)] // Only ever returned to poise, which expects this error type
+fn ensure_may_overdraw<R: StockRepository>(
+    ctx: Context<'_, R>,
+    allow_negative: bool,
+) -> Result<(), Error> {
+    ensure!(
+        !allow_negative || ctx.data().is_admin(ctx.author().id),
+        ForbiddenSnafu {
+            reason: "Only configured admins can let a balance go below zero",
+        }
+    );
+
+    Ok(())
+}
+
+fn adjustment_embed(title: &str, adjustment: &Adjustment) -> CreateEmbed {
+    let mut embed = CreateEmbed::new()
+        .title(title)
+        .description(format!(
+            "`{}` to `{}` as a {}",
+            format_signed(adjustment.delta),
+            adjustment.user,
+            adjustment.reason
+        ))
+        .field("Adjustment", format!("`#{}`", adjustment.id), true)
+        .field("New balance", format_kromer(adjustment.balance), true)
+        .color(Color::DARK_GOLD);
+
+    if let Some(reverses) = adjustment.reverses {
+        embed = embed.field("Reverses", format!("`#{reverses}`"), true);
+    }
+
+    embed
+}
+
+/// Lists recent audit log entries
+#[poise::command(slash_command, check = "can_view_admin", ephemeral)]
+async fn audit<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "Only show entries concerning this target"] target: Option<String>,
+) -> Result<(), Error> {
+    const PAGE_SIZE: u64 = 10;
+    let ctx_id = ctx.id();
+    let stock_service = ctx.data().service();
+
+    record_invocation(ctx, serde_json::json!({ "target": target })).await?;
+
+    let filter = AuditFilter {
+        target,
+        ..Default::default()
+    };
+
+    let entries = stock_service
+        .get_audit_log(
+            &Pager::new(0, PAGE_SIZE.cast_signed()),
+            Some(filter.clone()),
+        )
+        .await?;
+    let identities = resolve_actors(ctx, &entries.items).await?;
+    let mut cursor = PageCursor::new(entries.total, PAGE_SIZE);
+
+    if cursor.pages() == 1 {
+        send_reply(
+            ctx,
+            CreateReply::default().embed(into_embed(&entries.items, &identities)),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let prev_button_id = format!("{ctx_id}prev");
+    let next_button_id = format!("{ctx_id}next");
+
+    let reply = {
+        let components = CreateActionRow::Buttons(vec![
+            CreateButton::new(&prev_button_id).emoji('◀'),
+            CreateButton::new(&next_button_id).emoji('▶'),
+        ]);
+
+        CreateReply::default()
+            .embed(
+                into_embed(&entries.items, &identities).footer(CreateEmbedFooter::new(format!(
+                    "Page: 1/{}",
+                    cursor.pages()
+                ))),
+            )
+            .components(vec![components])
+    };
+
+    send_reply(ctx, reply).await?;
+
+    let mut presses = Presses::new(ctx);
+
+    while let Some(press) = presses.next().await {
+        if press.data.custom_id == prev_button_id {
+            cursor.prev();
+        } else if press.data.custom_id == next_button_id {
+            cursor.next();
+        } else {
+            // Unrelated interaction
+            continue;
+        }
+
+        let new_entries = stock_service
+            .get_audit_log(&cursor.pager(), Some(filter.clone()))
+            .await?;
+
+        if new_entries.total != entries.total {
+            presses
+                .finish(
+                    &press,
+                    "New entries have been logged, please call this command again",
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let identities = resolve_actors(ctx, &new_entries.items).await?;
+
+        press
+            .create_response(
+                ctx.serenity_context(),
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new().embed(
+                        into_embed(&new_entries.items, &identities).footer(CreateEmbedFooter::new(
+                            format!("Page: {}/{}", cursor.number(), cursor.pages()),
+                        )),
+                    ),
+                ),
+            )
+            .await?;
+    }
+
+    presses.expire(&i18n::locale(ctx).await).await;
+
+    Ok(())
+}
+
+/// Shows how often each command was used over the last day and week, and how often it failed
+#[poise::command(slash_command, check = "can_view_admin", ephemeral)]
+async fn botstats<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
+    record_invocation(ctx, serde_json::json!({})).await?;
+
+    let stats = ctx.data().service().command_stats().await?;
+
+    send_reply(ctx, CreateReply::default().embed(usage_embed(&stats))).await?;
+
+    Ok(())
+}
+
+/// Closes an account, optionally cancelling its orders and buying out its shares first
+#[poise::command(slash_command, check = "can_manage_balances", ephemeral)]
+async fn close<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The UUID of the account to close"] account: String,
+    #[description = "The Kromer address to send its remaining balance to"] address: Option<String>,
+    #[description = "Cancel its orders and retire its shares at the last price first"]
+    force: Option<bool>,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let force = force.unwrap_or_default();
+    if force {
+        // Retires shares, which is beyond managing balances alone
+        require(ctx, Permission::ManageStocks).await?;
+    }
+    let id = Uuid::parse_str(account.trim()).context(InvalidUuidSnafu { input: &account })?;
+    let address = address.as_deref().map(parse_address).transpose()?;
+
+    record_invocation(
+        ctx,
+        serde_json::json!({
+            "account": id,
+            "address": address.map(|v| v.to_string()),
+            "force": force,
+        }),
+    )
+    .await?;
+
+    let description = if force {
+        format!(
+            "`{id}` will have its orders cancelled and its shares retired at the last traded \
+            price before being closed"
+        )
+    } else {
+        format!("`{id}` will be closed if it has no open orders or shares left")
+    };
+    let summary = CreateEmbed::new()
+        .title("Close account?")
+        .description(description)
+        .color(Color::GOLD);
+
+    if !confirm(ctx, summary, Duration::from_mins(2)).await? {
+        return Ok(());
+    }
+
+    let closed = stock_service
+        .admin_close_account(
+            &id,
+            address.as_ref(),
+            force,
+            &Actor::Discord(ctx.author().id.into()),
+        )
+        .await?;
+
+    let mut embed = CreateEmbed::new()
+        .title("Account closed")
+        .description(format!("`{id}` was closed and unlinked"))
+        .field("Orders cancelled", closed.cancelled.len().to_string(), true)
+        .field("Liquidated for", format_kromer(closed.liquidated), true)
+        .color(Color::DARK_GOLD);
+
+    if let Some(withdrawal) = closed.withdrawal {
+        embed = embed.field(
+            "Final withdrawal",
+            format!(
+                "`#{}` **{}** to `{}`",
+                withdrawal.id, withdrawal.amount, withdrawal.address
+            ),
+            false,
+        );
+    }
+
+    send_reply(ctx, CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Halts trading in a stock. Resting orders stay on the book, but nothing matches until resumed
+#[poise::command(slash_command, check = "can_halt", ephemeral)]
+async fn halt<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The stock to halt"]
+    #[autocomplete = "autocomplete_ticker"]
+    ticker: TickerArg,
+) -> Result<(), Error> {
+    set_status(ctx, *ticker, StockStatus::Halted).await
+}
+
+/// Imports a stock's price history from a CSV file of `ticker,timestamp,price,volume` rows
+#[poise::command(
+    slash_command,
+    check = "can_manage_stocks",
+    ephemeral,
+    rename = "import-prices"
+)]
+async fn import_prices<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "A CSV file of prices, as exported from the market being migrated"]
+    file: Attachment,
+) -> Result<(), Error> {
+    defer_ephemeral_or_log(ctx).await;
+
+    let data = file.download().await?;
+    let max_rows = ctx.data().config().import_max_rows.get();
+    let service = ctx.data().service();
+    let prices = PriceFile::parse(
+        &data,
+        max_rows.try_into().unwrap_or(usize::MAX),
+        service.now(),
+    )?;
+    let report = service.import_prices(prices).await?;
+
+    record_invocation(
+        ctx,
+        serde_json::json!({
+            "file": file.filename,
+            "inserted": report.inserted,
+            "duplicates": report.duplicates,
+            "invalid": report.invalid.len(),
+        }),
+    )
+    .await?;
+
+    send_reply(ctx, CreateReply::default().embed(import_embed(&report))).await?;
+
+    Ok(())
+}
+
+/// Summarises an import, listing the first few rows that were left out
+fn import_embed(report: &ImportReport) -> CreateEmbed {
+    const SHOWN: usize = 10;
+
+    let mut embed = CreateEmbed::new()
+        .title("Prices imported")
+        .field("Inserted", report.inserted.to_string(), true)
+        .field("Duplicates", report.duplicates.to_string(), true)
+        .field("Invalid", report.invalid.len().to_string(), true)
+        .color(if report.invalid.is_empty() {
+            Color::DARK_GREEN
+        } else {
+            Color::GOLD
+        });
+
+    if !report.invalid.is_empty() {
+        let mut rows = String::new();
+
+        for row in report.invalid.iter().take(SHOWN) {
+            writeln!(rows, "Line {}: {}", row.line, row.reason).expect("Never fails");
+        }
+
+        if report.invalid.len() > SHOWN {
+            write!(rows, "...and {} more", report.invalid.len() - SHOWN).expect("Never fails");
+        }
+
+        embed = embed.field("Left out", rows, false);
+    }
+
+    embed
+}
+
+/// Merges a duplicate account into another, moving everything it has before closing it
+#[poise::command(slash_command, check = "is_admin", ephemeral)]
+async fn merge<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The UUID of the account to keep"] survivor: String,
+    #[description = "The UUID of the account to merge into it and close"] casualty: String,
+    #[description = "Only show what would move, without changing anything"] dry_run: Option<bool>,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let dry_run = dry_run.unwrap_or_default();
+    let survivor =
+        Uuid::parse_str(survivor.trim()).context(InvalidUuidSnafu { input: &survivor })?;
+    let casualty =
+        Uuid::parse_str(casualty.trim()).context(InvalidUuidSnafu { input: &casualty })?;
+    let actor = Actor::Discord(ctx.author().id.into());
+
+    record_invocation(
+        ctx,
+        serde_json::json!({
+            "survivor": survivor,
+            "casualty": casualty,
+            "dry_run": dry_run,
+        }),
+    )
+    .await?;
+
+    // Previewed either way, so conflicts come up before anyone is asked to confirm
+    let preview = stock_service
+        .merge_accounts(&survivor, &casualty, true, &actor)
+        .await?;
+
+    if dry_run {
+        let embed = merge_embed("Merge preview", &preview).color(Color::BLURPLE);
+        send_reply(ctx, CreateReply::default().embed(embed)).await?;
+
+        return Ok(());
+    }
+
+    let summary = merge_embed("Merge accounts?", &preview).color(Color::GOLD);
+
+    if !confirm(ctx, summary, Duration::from_mins(2)).await? {
+        return Ok(());
+    }
+
+    let merged = stock_service
+        .merge_accounts(&survivor, &casualty, false, &actor)
+        .await?;
+
+    send_reply(
+        ctx,
+        CreateReply::default()
+            .embed(merge_embed("Accounts merged", &merged).color(Color::DARK_GOLD)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// What merging one account into another moves
+fn merge_embed(title: &str, merge: &AccountMerge) -> CreateEmbed {
+    let holdings: Vec<_> = merge
+        .holdings
+        .iter()
+        .map(|(ticker, shares)| format!("`${ticker}` {shares} shares"))
+        .collect();
+    let mut holdings = EmbedBudget::new(i18n::FALLBACK).lines(MAX_FIELD_VALUE, "", &holdings, "");
+
+    if holdings.is_empty() {
+        holdings.push_str("None");
+    }
+
+    let links = [
+        merge.disc_id.map(|id| format!("Discord <@{id}>")),
+        merge.mc_id.map(|id| format!("Minecraft `{id}`")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+    let links = if links.is_empty() {
+        "None".to_owned()
+    } else {
+        links.join("\n")
+    };
+
+    CreateEmbed::new()
+        .title(title)
+        .description(format!(
+            "`{}` into `{}`, which is kept",
+            merge.casualty, merge.survivor
+        ))
+        .field("Kromer", format_kromer(merge.balance), true)
+        .field("Ledger entries", merge.ledger_entries.to_string(), true)
+        .field("Trades", merge.trades.to_string(), true)
+        .field("Stocks owned", merge.stocks.to_string(), true)
+        .field("Links", links, true)
+        .field("Shares", holdings, false)
+}
+
+/// Finds the account linked to a Minecraft player
+#[poise::command(slash_command, check = "can_view_admin", ephemeral)]
+async fn player<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The player's username or UUID"] player: String,
+) -> Result<(), Error> {
+    record_invocation(ctx, serde_json::json!({ "player": player })).await?;
+
+    let mc_id = resolve_player(ctx, &player).await?;
+
+    let description = match ctx
+        .data()
+        .service()
+        .resolve(&Identity::Minecraft(mc_id))
+        .await
+    {
+        Ok(id) => format!(
+            "`{}` (`{mc_id}`) is linked to account `{id}`",
+            player.trim()
+        ),
+        Err(RscErr::UserNotFound { .. }) => {
+            format!(
+                "`{}` (`{mc_id}`) is not linked to an account",
+                player.trim()
+            )
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    send_reply(
+        ctx,
+        CreateReply::default().embed(
+            CreateEmbed::new()
+                .title("Minecraft player")
+                .description(description)
+                .color(Color::BLURPLE),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Checks that balances, holdings and escrow add up, without fixing anything
+#[poise::command(slash_command, check = "can_view_admin", ephemeral)]
+async fn reconcile<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
+    record_invocation(ctx, serde_json::json!({})).await?;
+    defer_ephemeral_or_log(ctx).await;
+
+    let report = ctx.data().service().reconcile().await?;
+
+    send_reply(
+        ctx,
+        CreateReply::default().embed(reconciliation_embed(&report)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Registers an account for a Minecraft player who isn't on Discord
+#[poise::command(slash_command, check = "is_admin", ephemeral, rename = "register-mc")]
+async fn register_mc<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The player's username or UUID"] player: String,
+) -> Result<(), Error> {
+    record_invocation(ctx, serde_json::json!({ "player": player })).await?;
+
+    let stock_service = ctx.data().service();
+    let mc_id = resolve_player(ctx, &player).await?;
+    let id = stock_service
+        .register_player(&mc_id, &Actor::Discord(ctx.author().id.into()))
+        .await?;
+    let (code, expires_at) = stock_service.create_link_code(&id).await?;
+
+    let embed = CreateEmbed::new()
+        .title("Player registered")
+        .description(format!(
+            "`{}` (`{mc_id}`) now has account `{id}`. They can link their Discord account to it \
+             with `/link code:{code}`, which works once and expires <t:{}:R>",
+            player.trim(),
+            expires_at.timestamp()
+        ))
+        .color(Color::DARK_GREEN);
+    send_reply(ctx, CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Resumes trading in a halted stock
+#[poise::command(slash_command, check = "can_halt", ephemeral)]
+async fn resume<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The stock to resume"]
+    #[autocomplete = "autocomplete_ticker"]
+    ticker: TickerArg,
+) -> Result<(), Error> {
+    set_status(ctx, *ticker, StockStatus::Active).await
+}
+
+/// Undoes a balance adjustment with an equal and opposite one. Each can only be reversed once
+#[poise::command(slash_command, check = "can_manage_balances", ephemeral)]
+async fn reverse<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The ID of the adjustment to reverse"] adjustment: i64,
+    #[description = "Let the balance go below zero. Only for configured admins"]
+    allow_negative: Option<bool>,
+) -> Result<(), Error> {
+    let allow_negative = allow_negative.unwrap_or_default();
+    ensure_may_overdraw(ctx, allow_negative)?;
+
+    record_invocation(
+        ctx,
+        serde_json::json!({
+            "adjustment": adjustment,
+            "allow_negative": allow_negative,
+        }),
+    )
+    .await?;
+
+    let summary = CreateEmbed::new()
+        .title("Reverse adjustment?")
+        .description(format!(
+            "Adjustment `#{adjustment}` will be undone. This can't be reversed again"
+        ))
+        .color(Color::GOLD);
+
+    if !confirm(ctx, summary, Duration::from_mins(2)).await? {
+        return Ok(());
+    }
+
+    let reversal = ctx
+        .data()
+        .service()
+        .reverse_adjustment(
+            adjustment,
+            allow_negative,
+            &Actor::Discord(ctx.author().id.into()),
+        )
+        .await?;
+
+    send_reply(
+        ctx,
+        CreateReply::default().embed(adjustment_embed("Adjustment reversed", &reversal)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+async fn set_status<R: StockRepository>(
+    ctx: Context<'_, R>,
+    ticker: Ticker,
+    status: StockStatus,
+) -> Result<(), Error> {
+    record_invocation(ctx, serde_json::json!({ "ticker": ticker.as_str() })).await?;
+
+    ctx.data()
+        .service()
+        .set_stock_status(&ticker, status, &Actor::Discord(ctx.author().id.into()))
+        .await?;
+
+    let description = if status.is_tradable() {
+        format!("Trading in ${ticker} has resumed")
+    } else {
+        format!("Trading in ${ticker} is now halted")
+    };
+
+    send_reply(
+        ctx,
+        CreateReply::default().embed(
+            CreateEmbed::new()
+                .title("Stock status updated")
+                .description(description)
+                .color(Color::DARK_GOLD),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// A permission that can be granted to a role
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum PermissionChoice {
+    /// Look at the audit log, usage, accounts and the data of other users
+    #[name = "View admin"]
+    ViewAdmin,
+    /// Retire shares held by others, such as when forcibly closing an account
+    #[name = "Manage stocks"]
+    ManageStocks,
+    /// Close accounts and review withdrawals
+    #[name = "Manage balances"]
+    ManageBalances,
+    /// Halt and resume trading in a stock
+    Halt,
+}
+
+impl From<PermissionChoice> for Permission {
+    fn from(value: PermissionChoice) -> Self {
+        match value {
+            PermissionChoice::ViewAdmin => Self::ViewAdmin,
+            PermissionChoice::ManageStocks => Self::ManageStocks,
+            PermissionChoice::ManageBalances => Self::ManageBalances,
+            PermissionChoice::Halt => Self::Halt,
+        }
+    }
+}
+
+/// A server setting that can be reset to its default
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum SettingChoice {
+    /// Stop posting market announcements in this server
+    #[name = "Market feed"]
+    MarketFeed,
+    /// Fall back to English when a user's locale isn't translated
+    Locale,
+    /// Only configured admins may run privileged commands
+    #[name = "Admin role"]
+    AdminRole,
+}
+
+/// Shows this server's settings, changing any that are passed in
+#[poise::command(slash_command, check = "is_admin", guild_only, ephemeral)]
+#[allow(clippy::too_many_arguments)] // Every setting is its own option
+async fn settings<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The channel to post market announcements in"]
+    #[channel_types("Text", "News")]
+    market_feed: Option<GuildChannel>,
+    #[description = "Whether orders may be placed from this server"] trading: Option<bool>,
+    #[description = "The locale to reply in when a user's own isn't translated, e.g. fr"]
+    locale: Option<String>,
+    #[description = "Members with this role may run every admin command"] admin_role: Option<Role>,
+    #[description = "A setting to reset to its default"] reset: Option<SettingChoice>,
+    #[description = "The role to grant or revoke a permission for"] role: Option<Role>,
+    #[description = "A permission to grant to the role"] grant: Option<PermissionChoice>,
+    #[description = "A permission to revoke from the role"] revoke: Option<PermissionChoice>,
+) -> Result<(), Error> {
+    let guild = ctx.guild_id().expect("Guild only");
+    let locale = locale.map(|locale| locale.trim().to_owned());
+    let grant = grant.map(Permission::from);
+    let revoke = revoke.map(Permission::from);
+
+    record_invocation(
+        ctx,
+        serde_json::json!({
+            "market_feed": market_feed.as_ref().map(|channel| channel.id),
+            "trading": trading,
+            "locale": locale,
+            "admin_role": admin_role.as_ref().map(|role| role.id),
+            "reset": reset.map(|reset| format!("{reset:?}")),
+            "role": role.as_ref().map(|role| role.id),
+            "grant": grant.map(|permission| permission.as_str()),
+            "revoke": revoke.map(|permission| permission.as_str()),
+        }),
+    )
+    .await?;
+
+    if role.is_none() && (grant.is_some() || revoke.is_some()) {
+        return InvalidOptionsSnafu {
+            reason: "Pick the role to grant or revoke a permission for",
+        }
+        .fail();
+    }
+
+    if let Some(locale) = &locale
+        && !i18n::is_supported(locale)
+    {
+        return InvalidOptionsSnafu {
+            reason: "There are no translations for that locale",
+        }
+        .fail();
+    }
+
+    let service = ctx.data().service();
+    let mut settings = service.guild_settings(guild.into()).await?;
+    let before = settings.clone();
+
+    match reset {
+        Some(SettingChoice::MarketFeed) => settings.market_feed_channel = None,
+        Some(SettingChoice::Locale) => settings.locale = None,
+        Some(SettingChoice::AdminRole) => settings.admin_role = None,
+        None => {}
+    }
+    if let Some(channel) = market_feed {
+        settings.market_feed_channel = Some(channel.id.into());
+    }
+    if let Some(trading) = trading {
+        settings.trading_enabled = trading;
+    }
+    if let Some(locale) = locale {
+        settings.locale = Some(locale);
+    }
+    if let Some(role) = admin_role {
+        settings.admin_role = Some(role.id.into());
+    }
+
+    let mut changes = Vec::new();
+    if let Some(role) = &role {
+        let granted = settings.role_permissions.entry(role.id.into()).or_default();
+
+        if let Some(permission) = grant
+            && granted.insert(permission)
+        {
+            changes.push((Action::GrantPermission, permission));
+        }
+        if let Some(permission) = revoke
+            && granted.remove(&permission)
+        {
+            changes.push((Action::RevokePermission, permission));
+        }
+        if granted.is_empty() {
+            settings.role_permissions.remove(&role.id.into());
+        }
+    }
+
+    let title = if settings == before {
+        "Server settings"
+    } else {
+        service.set_guild_settings(guild.into(), &settings).await?;
+        "Server settings updated"
+    };
+
+    for (action, permission) in changes {
+        let entry = NewAuditEntry {
+            actor: Actor::Discord(ctx.author().id.into()),
+            action,
+            target: role.as_ref().map(|role| format!("role:{}", role.id)),
+            details: serde_json::json!({
+                "guild": guild,
+                "permission": permission.as_str(),
+            }),
+        };
+        service.record_audit(&entry).await?;
+    }
+
+    send_reply(
+        ctx,
+        CreateReply::default().embed(settings_embed(title, &settings)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+fn settings_embed(title: &str, settings: &GuildSettings) -> CreateEmbed {
+    let market_feed = settings
+        .market_feed_channel
+        .map_or_else(|| "Not set".to_owned(), |channel| format!("<#{channel}>"));
+    let trading = if settings.trading_enabled {
+        "Enabled"
+    } else {
+        "Disabled"
+    };
+    let locale = settings.locale.as_ref().map_or_else(
+        || format!("Not set, `{}`", i18n::FALLBACK),
+        |locale| format!("`{locale}`"),
+    );
+    let admin_role = settings
+        .admin_role
+        .map_or_else(|| "Not set".to_owned(), |role| format!("<@&{role}>"));
+
+    let mut role_permissions = String::new();
+    for (role, permissions) in &settings.role_permissions {
+        let permissions: Vec<_> = permissions
+            .iter()
+            .map(|permission| format!("`{permission}`"))
+            .collect();
+        writeln!(role_permissions, "<@&{role}> {}", permissions.join(", ")).expect("Never fails");
+    }
+    if role_permissions.is_empty() {
+        role_permissions.push_str("None granted");
+    }
+
+    CreateEmbed::new()
+        .title(title)
+        .field("Market feed", market_feed, true)
+        .field("Trading", trading, true)
+        .field("Fallback locale", locale, true)
+        .field("Admin role", admin_role, true)
+        .field("Role permissions", role_permissions, false)
+        .color(Color::DARK_GOLD)
+}
+
+/// Where a compressed snapshot ended up
+enum SavedSnapshot {
+    /// Small enough to attach to the reply
+    Attach(Vec<u8>),
+    /// Written to the snapshot directory
+    Written(PathBuf),
+}
+
+/// Exports the whole exchange to a gzipped file, to keep before risky changes or restore on staging
+#[poise::command(slash_command, check = "is_admin", ephemeral)]
+async fn snapshot<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
+    record_invocation(ctx, serde_json::json!({})).await?;
+    defer_ephemeral_or_log(ctx).await;
+
+    let service = ctx.data().service();
+    let name = format!(
+        "rse-snapshot-{}.ndjson.gz",
+        service.now().format("%Y%m%dT%H%M%SZ")
+    );
+    let path = ctx.data().config().snapshot_dir.join(&name);
+
+    let mut data = Vec::new();
+    let counts = service
+        .export_snapshot(&mut data)
+        .await
+        .context(SnapshotSnafu)?;
+
+    let target = path.clone();
+    let saved = tokio::task::spawn_blocking(move || save_snapshot(&data, &target))
+        .await
+        .map_err(std::io::Error::other)
+        .and_then(|saved| saved)
+        .context(SnapshotFileSnafu { path })?;
+
+    let reply = CreateReply::default();
+    let reply = match saved {
+        SavedSnapshot::Attach(bytes) => reply
+            .embed(snapshot_embed(&counts, None))
+            .attachment(CreateAttachment::bytes(bytes, name)),
+        SavedSnapshot::Written(path) => reply.embed(snapshot_embed(&counts, Some(&path))),
+    };
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
+
+/// Compresses a snapshot, writing it to `path` if it's too large to attach
+fn save_snapshot(data: &[u8], path: &Path) -> std::io::Result<SavedSnapshot> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    let bytes = encoder.finish()?;
+
+    if bytes.len() <= super::export::MAX_BYTES {
+        return Ok(SavedSnapshot::Attach(bytes));
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, bytes)?;
+
+    Ok(SavedSnapshot::Written(path.to_owned()))
+}
+
+/// Lists how many rows a snapshot holds, and where it was written if it wasn't attached
+fn snapshot_embed(counts: &SnapshotCounts, path: Option<&Path>) -> CreateEmbed {
+    let embed = CreateEmbed::new()
+        .title("Snapshot taken")
+        .field("Users", counts.users.to_string(), true)
+        .field("Stocks", counts.stocks.to_string(), true)
+        .field("Holdings", counts.holdings.to_string(), true)
+        .field("Orders", counts.orders.to_string(), true)
+        .field("Prices", counts.prices.to_string(), true)
+        .color(Color::DARK_GOLD);
+
+    match path {
+        Some(path) => embed.description(format!(
+            "Too large to attach, written to `{}` on the server",
+            path.display()
+        )),
+        None => embed,
+    }
+}
+
+/// Browses accounts and the identities linked to them
+#[poise::command(slash_command, check = "can_view_admin", ephemeral)]
+async fn users<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "Only show accounts linked this way"] links: Option<LinksChoice>,
+    #[description = "What to sort accounts by"] sort: Option<UserSortChoice>,
+    #[description = "Show each account's balance, which is recorded in the audit log"]
+    show_balances: Option<bool>,
+) -> Result<(), Error> {
+    const PAGE_SIZE: u64 = 10;
+    let ctx_id = ctx.id();
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+    let show_balances = show_balances.unwrap_or_default();
+    let filter = UserFilter {
+        links: links.map(UserLinks::from).unwrap_or_default(),
+        order: sort.map(UserOrdering::from).unwrap_or_default(),
+    };
+
+    record_invocation(
+        ctx,
+        serde_json::json!({
+            "links": filter.links.as_str(),
+            "sort": filter.order.as_str(),
+            "show_balances": show_balances,
+        }),
+    )
+    .await?;
+
+    if show_balances {
+        let entry = NewAuditEntry {
+            actor: Actor::Discord(ctx.author().id.into()),
+            action: Action::ViewBalances,
+            target: None,
+            details: serde_json::json!({
+                "links": filter.links.as_str(),
+                "sort": filter.order.as_str(),
+            }),
+        };
+        stock_service.record_audit(&entry).await?;
+    }
+
+    let users = stock_service
+        .list_users(
+            &Pager::new(0, PAGE_SIZE.cast_signed()),
+            filter,
+            show_balances,
+        )
+        .await?;
+    let values = holdings_values(ctx, &users, show_balances).await?;
+    let mut cursor = PageCursor::new(users.total, PAGE_SIZE);
+
+    if cursor.pages() == 1 {
+        send_reply(
+            ctx,
+            CreateReply::default().embed(users_embed(&users, &values)),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let prev_button_id = format!("{ctx_id}prev");
+    let next_button_id = format!("{ctx_id}next");
+
+    let reply = {
+        let components = CreateActionRow::Buttons(vec![
+            CreateButton::new(&prev_button_id).emoji('◀'),
+            CreateButton::new(&next_button_id).emoji('▶'),
+        ]);
+
+        CreateReply::default()
+            .embed(
+                users_embed(&users, &values).footer(CreateEmbedFooter::new(format!(
+                    "Page: 1/{}",
+                    cursor.pages()
+                ))),
+            )
+            .components(vec![components])
+    };
+
+    send_reply(ctx, reply).await?;
+
+    let mut presses = Presses::new(ctx);
+
+    while let Some(press) = presses.next().await {
+        if press.data.custom_id == prev_button_id {
+            cursor.prev();
+        } else if press.data.custom_id == next_button_id {
+            cursor.next();
+        } else {
+            // Unrelated interaction
+            continue;
+        }
+
+        // Accounts may have registered since the last page, in which case the page may have to move
+        let users = loop {
+            let users = stock_service
+                .list_users(&cursor.pager(), filter, show_balances)
+                .await?;
+
+            if !cursor.sync(users.total) {
+                break users;
+            }
+        };
+        let values = holdings_values(ctx, &users, show_balances).await?;
+
+        let footer = format!("Page: {}/{}", cursor.number(), cursor.pages());
+
+        press
+            .create_response(
+                ctx.serenity_context(),
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new().embed(
+                        users_embed(&users, &values)
+                            .footer(CreateEmbedFooter::new(cursor.footer(footer, locale))),
+                    ),
+                ),
+            )
+            .await?;
+    }
+
+    presses.expire(locale).await;
+
+    Ok(())
+}
+
+/// Values the holdings of everyone on a page of accounts in one go, if balances are being shown
+async fn holdings_values<R: StockRepository>(
+    ctx: Context<'_, R>,
+    users: &Page<UserInfo>,
+    show_balances: bool,
+) -> Result<HashMap<Uuid, Decimal>, Error> {
+    if !show_balances {
+        return Ok(HashMap::new());
+    }
+
+    let ids: Vec<Uuid> = users.items.iter().map(|user| user.id).collect();
+    let values = ctx.data().service().portfolio_values(&ids).await?;
+
+    Ok(values.into_iter().collect())
+}
+
+fn users_embed(users: &Page<UserInfo>, values: &HashMap<Uuid, Decimal>) -> CreateEmbed {
+    let mut lines = Vec::with_capacity(users.items.len());
+
+    for user in &users.items {
+        let id = user.id.simple().to_string();
+        let mut buff = format!("`{}` <t:{}:d>", &id[..8], user.created_at.timestamp());
+
+        if let Some(balance) = user.balance {
+            write!(buff, " **{balance}**").expect("Never fails");
+        }
+        if let Some(value) = values.get(&user.id).filter(|value| !value.is_zero()) {
+            write!(buff, " + {value} in stocks").expect("Never fails");
+        }
+        if let Some(disc_id) = user.disc_id {
+            write!(buff, "\n> Discord: <@{disc_id}>").expect("Never fails");
+        }
+        if let Some(mc_id) = user.mc_id {
+            write!(buff, "\n> Minecraft: `{mc_id}`").expect("Never fails");
+        }
+        if let Some(closed_at) = user.closed_at {
+            write!(buff, "\n> Closed <t:{}:d>", closed_at.timestamp()).expect("Never fails");
+        } else if user.disc_id.is_none() && user.mc_id.is_none() {
+            buff.push_str("\n> Not linked");
+        }
+
+        lines.push(buff);
+    }
+
+    let mut buff = EmbedBudget::new(i18n::FALLBACK).lines(MAX_DESCRIPTION, "", &lines, "");
+
+    if buff.is_empty() {
+        buff.push_str("No accounts match");
+    }
+
+    CreateEmbed::new()
+        .title(format!("Accounts ({})", users.total))
+        .color(Color::DARK_GOLD)
+        .description(buff)
+}
+
+/// Reviews pending withdrawal requests, oldest first
+#[poise::command(slash_command, check = "can_manage_balances", ephemeral)]
+async fn withdrawals<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
+    /// One action row per request, and Discord allows at most five rows on a message
+    const PAGE_SIZE: i64 = 5;
+    let ctx_id = ctx.id();
+    let stock_service = ctx.data().service();
+    let actor = Actor::Discord(ctx.author().id.into());
+    let pager = Pager::new(0, PAGE_SIZE);
+
+    record_invocation(ctx, serde_json::json!({})).await?;
+
+    let pending = stock_service.pending_withdrawals(&pager).await?;
+
+    send_reply(
+        ctx,
+        CreateReply::default()
+            .embed(withdrawals_embed(&pending))
+            .components(withdrawal_buttons(ctx_id, &pending.items)),
+    )
+    .await?;
+
+    if pending.items.is_empty() {
+        return Ok(());
+    }
+
+    let mut presses = Presses::new(ctx);
+
+    while let Some(press) = presses.next().await {
+        let Some(action) = press.data.custom_id.strip_prefix(&ctx_id.to_string()) else {
+            continue;
+        };
+
+        let res = match action.split_once(':') {
+            Some(("approve", id)) => match id.parse() {
+                Ok(id) => stock_service.approve_withdrawal(id, &actor).await,
+                Err(_) => continue,
+            },
+            Some(("deny", id)) => match id.parse() {
+                Ok(id) => stock_service.deny_withdrawal(id, &actor).await,
+                Err(_) => continue,
+            },
+            // Unrelated interaction
+            _ => continue,
+        };
+
+        match res {
+            // Someone else already handled it, the refreshed list shows it's gone
+            Ok(_) | Err(RscErr::WithdrawalNotFound { .. }) => {}
+            Err(err) => return Err(err.into()),
+        }
+
+        let pending = stock_service.pending_withdrawals(&pager).await?;
+
+        press
+            .create_response(
+                ctx.serenity_context(),
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .embed(withdrawals_embed(&pending))
+                        .components(withdrawal_buttons(ctx_id, &pending.items)),
+                ),
+            )
+            .await?;
+
+        if pending.items.is_empty() {
+            return Ok(());
+        }
+    }
+
+    presses.expire(&i18n::locale(ctx).await).await;
+
+    Ok(())
+}
+
+fn withdrawals_embed(pending: &Page<Withdrawal>) -> CreateEmbed {
+    let lines: Vec<_> = pending
+        .items
+        .iter()
+        .map(|withdrawal| {
+            format!(
+                "`#{}` {} **{}** to `{}` for `{}`",
+                withdrawal.id,
+                withdrawal.requested_at.format("%Y-%m-%d %H:%M"),
+                withdrawal.amount,
+                withdrawal.address,
+                withdrawal.user
+            )
+        })
+        .collect();
+
+    let mut buff = EmbedBudget::new(i18n::FALLBACK).lines(MAX_DESCRIPTION, "", &lines, "");
+
+    if buff.is_empty() {
+        buff.push_str("No pending withdrawals");
+    }
+
+    CreateEmbed::new()
+        .title("Pending withdrawals")
+        .color(Color::DARK_GOLD)
+        .description(buff)
+        .footer(CreateEmbedFooter::new(format!(
+            "Showing {} of {} pending",
+            pending.items.len(),
+            pending.total
+        )))
+}
+
+fn withdrawal_buttons(ctx_id: u64, pending: &[Withdrawal]) -> Vec<CreateActionRow> {
+    pending
+        .iter()
+        .map(|withdrawal| {
+            CreateActionRow::Buttons(vec![
+                CreateButton::new(format!("{ctx_id}approve:{}", withdrawal.id))
+                    .label(format!("Approve #{}", withdrawal.id))
+                    .style(ButtonStyle::Success),
+                CreateButton::new(format!("{ctx_id}deny:{}", withdrawal.id))
+                    .label(format!("Deny #{}", withdrawal.id))
+                    .style(ButtonStyle::Danger),
+            ])
+        })
+        .collect()
+}
+
+/// Resolves the identities linked to the accounts that performed `entries`, in one query
+async fn resolve_actors<R: StockRepository>(
+    ctx: Context<'_, R>,
+    entries: &[AuditEntry],
+) -> Result<Identities, Error> {
+    let mut ids: Vec<_> = entries
+        .iter()
+        .filter_map(|entry| match entry.actor {
+            Actor::Account(id) => Some(id),
+            _ => None,
+        })
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    if ids.is_empty() {
+        return Ok(Identities::new());
+    }
+
+    Ok(ctx
+        .data()
+        .service()
+        .resolve_identities(&ids)
+        .await?
+        .into_iter()
+        .map(|(id, disc_id, mc_id)| (id, (disc_id, mc_id)))
+        .collect())
+}
+
+/// Shows an actor as a mention when they are linked to a Discord account
+fn actor_label(actor: &Actor, identities: &Identities) -> String {
+    match actor {
+        Actor::Discord(id) => format!("<@{id}>"),
+        Actor::Account(id) => match identities.get(id) {
+            Some((Some(disc_id), _)) => format!("<@{disc_id}>"),
+            Some((None, Some(mc_id))) => format!("`{}`", Actor::Minecraft(*mc_id)),
+            _ => format!("`{actor}`"),
+        },
+        Actor::System | Actor::Minecraft(_) => format!("`{actor}`"),
+    }
+}
+
+fn into_embed(v: &[AuditEntry], identities: &Identities) -> CreateEmbed {
+    let lines: Vec<_> = v
+        .iter()
+        .map(|entry| {
+            let mut line = format!(
+                "`#{}` {} **{}** by {}",
+                entry.id,
+                entry.time.format("%Y-%m-%d %H:%M"),
+                entry.action,
+                actor_label(&entry.actor, identities)
+            );
+
+            if let Some(target) = &entry.target {
+                write!(line, "\n> Target: `{target}`").expect("Never fails");
+            }
+
+            line
+        })
+        .collect();
+
+    let mut buff = EmbedBudget::new(i18n::FALLBACK).lines(MAX_DESCRIPTION, "", &lines, "");
+
+    if buff.is_empty() {
+        buff.push_str("No entries to display");
+    }
+
+    CreateEmbed::new()
+        .title("Audit log")
+        .color(Color::DARK_GOLD)
+        .description(buff)
+}
+
+/// Formats a command's uses and error rate over some period
+fn usage_label(totals: UsageTotals) -> String {
+    match totals.error_rate() {
+        Some(rate) => format!("{} ({}% failed)", totals.uses, rate.round_dp(1).normalize()),
+        None => "0".to_owned(),
+    }
+}
+
+/// Lists every command's usage, one line each
+fn usage_embed(stats: &[CommandStats]) -> CreateEmbed {
+    let lines: Vec<_> = stats
+        .iter()
+        .map(|stat| {
+            format!(
+                "`/{}` 24h: {}, 7d: {}",
+                stat.command,
+                usage_label(stat.day),
+                usage_label(stat.week)
+            )
+        })
+        .collect();
+
+    let mut buff = EmbedBudget::new(i18n::FALLBACK).lines(MAX_DESCRIPTION, "", &lines, "");
+
+    if buff.is_empty() {
+        buff.push_str("No commands were used in the last 7 days");
+    }
+
+    CreateEmbed::new()
+        .title("Command usage")
+        .color(Color::DARK_GOLD)
+        .description(buff)
+}
@@ -0,0 +1,47 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed},
+};
+use rse_core::{model::link::Identity, repo::StockRepository};
+
+use crate::{
+    Context, Error,
+    i18n::{self, t},
+};
+
+/// Get a new key to trade through the REST API with, replacing your old one
+#[poise::command(slash_command, ephemeral)]
+pub async fn api_key<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+    let key = stock_service.create_api_key(&user_id).await?;
+
+    let embed = CreateEmbed::new()
+        .title(t!(locale, "api_key.title"))
+        .description(t!(locale, "api_key.key", key = key.as_str()))
+        .color(Color::BLITZ_BLUE);
+    send_reply(ctx, CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
@@ -0,0 +1,257 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Keeping embeds within the limits Discord puts on them, which it otherwise enforces by rejecting
+//! the whole message
+
+use crate::i18n::t;
+
+/// Most fields an embed may have
+const MAX_FIELDS: usize = 25;
+
+/// Most characters in a field's name
+const MAX_FIELD_NAME: usize = 256;
+
+/// Most characters in a field's value
+pub(crate) const MAX_FIELD_VALUE: usize = 1024;
+
+/// Most characters in an embed's description
+pub(crate) const MAX_DESCRIPTION: usize = 4096;
+
+/// Most characters in an embed's title, description, fields, footer and author combined
+const MAX_TOTAL: usize = 6000;
+
+/// Left over for the footer, which paginated replies add once the rest of the embed is built
+const FOOTER_ROOM: usize = 256;
+
+/// What is left of an embed's limits while it is built. Lists that don't fit are cut short with a
+/// marker saying how many entries were left out, and a warning is logged so page sizes can be tuned.
+#[derive(Debug)]
+pub(crate) struct EmbedBudget<'a> {
+    fields: usize,
+    chars: usize,
+    locale: &'a str,
+}
+
+impl<'a> EmbedBudget<'a> {
+    /// The budget of an empty embed, writing markers in `locale`
+    pub const fn new(locale: &'a str) -> Self {
+        Self {
+            fields: MAX_FIELDS,
+            chars: MAX_TOTAL - FOOTER_ROOM,
+            locale,
+        }
+    }
+
+    /// Accounts for text always shown on the embed, such as its title
+    pub fn spend(&mut self, text: &str) {
+        self.chars = self.chars.saturating_sub(text.chars().count());
+    }
+
+    /// Joins as many `entries` as fit into `limit` characters, each on its own line and all
+    /// between `open` and `close`, such as the fences of a code block. Any left out are counted in
+    /// a marker after `close`.
+    pub fn lines(&mut self, limit: usize, open: &str, entries: &[String], close: &str) -> String {
+        let room = limit.min(self.chars);
+        let mut buff = String::from(open);
+        let mut used = open.chars().count() + close.chars().count();
+        let mut kept = 0;
+
+        for entry in entries {
+            let cost = entry.chars().count() + 1;
+            let rest = entries.len() - kept - 1;
+            let marker = if rest == 0 {
+                0
+            } else {
+                self.marker(rest).chars().count() + 1
+            };
+
+            if used + cost + marker > room {
+                break;
+            }
+
+            buff.push_str(entry);
+            buff.push('\n');
+            used += cost;
+            kept += 1;
+        }
+
+        buff.push_str(close);
+
+        if kept < entries.len() {
+            buff.push('\n');
+            buff.push_str(&self.cut(entries.len() - kept));
+        }
+
+        self.spend(&buff);
+        buff
+    }
+
+    /// Keeps as many `fields` as fit, each as `(name, value, inline)`, shortening names and values
+    /// that are too long by themselves. Any left out are counted in a marker field at the end.
+    pub fn fields(&mut self, fields: Vec<(String, String, bool)>) -> Vec<(String, String, bool)> {
+        let total = fields.len();
+        let mut kept = Vec::with_capacity(total.min(self.fields));
+
+        for (name, value, inline) in fields {
+            let name = shorten(name, MAX_FIELD_NAME);
+            let value = shorten(value, MAX_FIELD_VALUE);
+            let cost = name.chars().count() + value.chars().count();
+
+            // Room for the marker is kept while more fields could follow
+            let rest = total - kept.len() - 1;
+            let (slots, marker) = if rest == 0 {
+                (1, 0)
+            } else {
+                (2, self.marker(rest).chars().count() + 1)
+            };
+
+            if self.fields < slots || self.chars < cost + marker {
+                break;
+            }
+
+            self.fields -= 1;
+            self.chars -= cost;
+            kept.push((name, value, inline));
+        }
+
+        if kept.len() < total {
+            let marker = self.cut(total - kept.len());
+            self.fields -= 1;
+            self.spend(&marker);
+            // Field names can't be empty, so a zero-width space stands in
+            kept.push(("\u{200b}".to_owned(), marker, false));
+        }
+
+        kept
+    }
+
+    /// Says that `count` entries didn't fit
+    fn marker(&self, count: usize) -> String {
+        t!(self.locale, "pages.more", count = count)
+    }
+
+    /// Logs that `count` entries were left out, returning the marker saying so
+    fn cut(&self, count: usize) -> String {
+        tracing::warn!(
+            left_out = count,
+            "Embed cut short to stay within Discord's limits"
+        );
+        self.marker(count)
+    }
+}
+
+/// Cuts `text` down to `max` characters, ending it with an ellipsis if anything was removed
+fn shorten(text: String, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text;
+    }
+
+    let mut short: String = text.chars().take(max - 1).collect();
+    short.push('…');
+    short
+}
+
+/// Counts the fields of `embed` and every character Discord limits in total
+#[cfg(test)]
+pub(crate) fn embed_size(embed: &poise::serenity_prelude::CreateEmbed) -> (usize, usize) {
+    let embed = serde_json::to_value(embed).expect("Serializes");
+    let len = |value: &serde_json::Value| value.as_str().map_or(0, |text| text.chars().count());
+    let fields = embed["fields"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+
+    let chars = len(&embed["title"])
+        + len(&embed["description"])
+        + len(&embed["footer"]["text"])
+        + len(&embed["author"]["name"])
+        + fields
+            .iter()
+            .map(|field| len(&field["name"]) + len(&field["value"]))
+            .sum::<usize>();
+
+    (fields.len(), chars)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn long_lists_end_with_what_was_left_out() {
+        let entries: Vec<_> = (0..1000).map(|i| format!("Entry number {i}")).collect();
+
+        let text = EmbedBudget::new("en").lines(MAX_FIELD_VALUE, "```\n", &entries, "```");
+
+        assert!(text.chars().count() <= MAX_FIELD_VALUE);
+        assert!(text.starts_with("```\nEntry number 0\n"));
+
+        let (table, marker) = text.rsplit_once('\n').expect("Has a marker");
+        let kept = table.lines().count() - 2;
+        assert!(table.ends_with("```"));
+        assert_eq!(marker, format!("…and {} more (next page)", 1000 - kept));
+    }
+
+    #[test]
+    fn short_lists_are_left_alone() {
+        let entries = ["a".to_owned(), "b".to_owned()];
+
+        assert_eq!(
+            EmbedBudget::new("en").lines(MAX_DESCRIPTION, "", &entries, ""),
+            "a\nb\n"
+        );
+    }
+
+    #[test]
+    fn fields_stay_within_every_limit() {
+        let fields = (0..40)
+            .map(|i| (format!("{i}").repeat(300), "x".repeat(2000), true))
+            .collect();
+
+        let mut budget = EmbedBudget::new("en");
+        budget.spend("A title");
+        let fields = budget.fields(fields);
+
+        assert!(fields.len() <= MAX_FIELDS);
+        assert!(fields.iter().all(|(name, value, _)| {
+            name.chars().count() <= MAX_FIELD_NAME && value.chars().count() <= MAX_FIELD_VALUE
+        }));
+
+        let total: usize = fields
+            .iter()
+            .map(|(name, value, _)| name.chars().count() + value.chars().count())
+            .sum();
+        assert!(total + "A title".len() <= MAX_TOTAL - FOOTER_ROOM);
+
+        let (_, marker, _) = fields.last().expect("Has a marker");
+        assert_eq!(
+            *marker,
+            format!("…and {} more (next page)", 40 - (fields.len() - 1))
+        );
+    }
+
+    #[test]
+    fn fields_fill_every_slot_when_they_fit() {
+        let fields = (0..25)
+            .map(|i| (i.to_string(), "x".to_owned(), true))
+            .collect();
+
+        assert_eq!(EmbedBudget::new("en").fields(fields).len(), MAX_FIELDS);
+    }
+}
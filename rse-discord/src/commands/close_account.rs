@@ -0,0 +1,135 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::time::Duration;
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed, Timestamp},
+};
+use rse_core::{model::link::Identity, repo::StockRepository};
+use rust_decimal::Decimal;
+
+use crate::{
+    Context, Error,
+    commands::{confirm::confirm, parse_address},
+    i18n::{self, t},
+};
+
+/// Close your account for good, sending whatever Kromer is left to an address
+#[poise::command(slash_command, ephemeral, rename = "close-account")]
+pub async fn close_account<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The Kromer address to send your remaining balance to"] address: Option<String>,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+    let address = address.as_deref().map(parse_address).transpose()?;
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+
+    let (info, summary) = tokio::try_join!(
+        stock_service.get_account_info(&user_id, Some(&user_id)),
+        stock_service.account_summary(&user_id)
+    )?;
+    // Always visible to the account itself. Pending withdrawals go out either way, so only what's
+    // available is left to pay out
+    let balance = info.available_balance.unwrap_or_default();
+
+    // Caught again when closing, but there's no point asking twice for something that will fail
+    let blocker = if summary.open_orders > 0 || summary.stocks_held > 0 {
+        Some(t!(
+            locale,
+            "close_account.not_empty",
+            orders = summary.open_orders,
+            stocks = summary.stocks_held
+        ))
+    } else if balance > Decimal::ZERO && address.is_none() {
+        Some(t!(locale, "close_account.needs_address", balance = balance))
+    } else {
+        None
+    };
+
+    if let Some(blocker) = blocker {
+        let reply = CreateReply::default().embed(
+            CreateEmbed::new()
+                .title(t!(locale, "close_account.blocked_title"))
+                .description(blocker)
+                .color(Color::RED),
+        );
+        send_reply(ctx, reply).await?;
+
+        return Ok(());
+    }
+
+    let payout = match &address {
+        Some(address) if balance > Decimal::ZERO => t!(
+            locale,
+            "close_account.payout",
+            balance = balance,
+            address = address
+        ),
+        _ => t!(locale, "close_account.no_payout"),
+    };
+
+    let summary = CreateEmbed::new()
+        .title(t!(locale, "close_account.confirm_title"))
+        .description(t!(locale, "close_account.confirm", payout = payout))
+        .color(Color::GOLD);
+
+    if !confirm(ctx, summary, Duration::from_mins(2)).await? {
+        return Ok(());
+    }
+
+    let last_chance = CreateEmbed::new()
+        .title(t!(locale, "close_account.final_title"))
+        .description(t!(locale, "close_account.final"))
+        .color(Color::RED);
+
+    if !confirm(ctx, last_chance, Duration::from_mins(1)).await? {
+        return Ok(());
+    }
+
+    let closed = stock_service
+        .close_account(&user_id, address.as_ref())
+        .await?;
+
+    let description = match closed.withdrawal {
+        Some(withdrawal) => t!(
+            locale,
+            "close_account.closed_withdrawal",
+            id = withdrawal.id,
+            amount = withdrawal.amount,
+            address = withdrawal.address
+        ),
+        None => t!(locale, "close_account.closed"),
+    };
+
+    let reply = CreateReply::default().embed(
+        CreateEmbed::new()
+            .title(t!(locale, "close_account.closed_title"))
+            .description(description)
+            .color(Color::DARK_GREEN)
+            .timestamp(Timestamp::now()),
+    );
+
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
@@ -0,0 +1,333 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::time::Duration;
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed, CreateEmbedFooter, Timestamp, User},
+};
+use rse_core::{
+    Service,
+    model::{PriceChange, StockInfo, StockMetadata, link::Identity},
+    repo::StockRepository,
+};
+use uuid::Uuid;
+
+use crate::{
+    Context, Error,
+    commands::{TickerArg, autocomplete_ticker, confirm::confirm, parse_shares},
+};
+
+/// View and manage the stocks you own
+#[poise::command(
+    slash_command,
+    ephemeral,
+    subcommands("info", "edit", "transfer", "issue", "buyback"),
+    subcommand_required
+)]
+#[allow(clippy::unused_async)] // Poise requires commands to be async
+pub async fn company<R: StockRepository>(_ctx: Context<'_, R>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Show who owns a stock and how its shares are held
+#[poise::command(slash_command, ephemeral)]
+async fn info<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The stock to look up"]
+    #[autocomplete = "autocomplete_ticker"]
+    ticker: TickerArg,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let ticker = *ticker;
+
+    let info = stock_service.get_stock_info(&ticker).await?;
+    let shareholders = stock_service.get_shareholders(&ticker).await?;
+    let change = stock_service.price_change(&ticker).await?;
+
+    let owner = match info.owner {
+        Some(owner) => describe_account(stock_service, &owner).await?,
+        None => "Nobody".to_owned(),
+    };
+
+    let owner_held = info.owner.map_or(0, |owner| shareholders.held_by(&owner));
+    let held: u64 = shareholders
+        .holders
+        .iter()
+        .map(|(_, shares)| u64::from(*shares))
+        .sum();
+
+    let mut embed = described(CreateEmbed::new(), &info);
+
+    if let Some(opens_at) = stock_service.market_opens_at() {
+        embed = embed.field(
+            "🌙 After hours",
+            format!("The market opens <t:{}:R>", opens_at.timestamp()),
+            false,
+        );
+    }
+
+    let reply = CreateReply::default().embed(
+        embed
+            .field("Owner", owner, false)
+            .field("Issued shares", info.shares.to_string(), true)
+            .field("Held by owner", owner_held.to_string(), true)
+            .field(
+                "Held by others",
+                (held - u64::from(owner_held)).to_string(),
+                true,
+            )
+            .field("Holders", shareholders.holders.len().to_string(), true)
+            .field("Change", change_label(change), true)
+            .field(
+                "Listed",
+                format!("<t:{}:D>", info.created_at.timestamp()),
+                true,
+            )
+            .color(Color::BLURPLE),
+    );
+
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
+
+/// Describes how a stock moved since its latest close, or over the last day before it first closes
+fn change_label(change: Option<PriceChange>) -> String {
+    match change {
+        Some(change) if change.since_close => {
+            format!("{:+.2}% since close", change.pct())
+        }
+        Some(change) => format!("{:+.2}% over 24h", change.pct()),
+        None => "Never traded".to_owned(),
+    }
+}
+
+/// Change the name, description or icon of one of your stocks
+#[poise::command(slash_command, ephemeral)]
+async fn edit<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The stock to describe"]
+    #[autocomplete = "autocomplete_ticker"]
+    ticker: TickerArg,
+    #[description = "The name of the company"]
+    #[max_length = 64]
+    name: Option<String>,
+    #[description = "What the company does"]
+    #[max_length = 500]
+    description: Option<String>,
+    #[description = "An https:// link to the company's logo"] icon_url: Option<String>,
+    #[description = "Clear the details you leave out instead of keeping them"] clear: Option<bool>,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let ticker = *ticker;
+
+    let owner = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+
+    let metadata = if clear.unwrap_or(false) {
+        StockMetadata {
+            name,
+            description,
+            icon_url,
+        }
+    } else {
+        let current = stock_service.get_stock_info(&ticker).await?.metadata;
+        StockMetadata {
+            name: name.or(current.name),
+            description: description.or(current.description),
+            icon_url: icon_url.or(current.icon_url),
+        }
+    };
+
+    let info = stock_service
+        .update_stock_metadata(&ticker, &owner, metadata)
+        .await?;
+
+    let reply = CreateReply::default().embed(
+        described(CreateEmbed::new(), &info)
+            .footer(CreateEmbedFooter::new("Details updated"))
+            .color(Color::DARK_GREEN)
+            .timestamp(Timestamp::now()),
+    );
+
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
+
+/// Hand one of your stocks to another user
+#[poise::command(slash_command, ephemeral)]
+async fn transfer<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The stock to transfer"]
+    #[autocomplete = "autocomplete_ticker"]
+    ticker: TickerArg,
+    #[description = "The new owner"] user: User,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let ticker = *ticker;
+
+    let owner = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+    stock_service.assert_stock_owner(&owner, &ticker).await?;
+
+    // Refuses users without an account before asking for confirmation
+    let new_owner = stock_service
+        .resolve(&Identity::Discord(user.id.into()))
+        .await?;
+
+    let summary = CreateEmbed::new()
+        .title(format!("Transfer ${ticker}?"))
+        .description(format!(
+            "<@{}> will own ${ticker}. Your shares stay with you, but you will no longer be able \
+            to manage it",
+            user.id
+        ))
+        .color(Color::GOLD);
+
+    if !confirm(ctx, summary, Duration::from_mins(2)).await? {
+        return Ok(());
+    }
+
+    stock_service
+        .transfer_ownership(&ticker, &owner, &new_owner)
+        .await?;
+
+    let reply = CreateReply::default().embed(
+        CreateEmbed::new()
+            .title(format!("Transferred ${ticker}"))
+            .description(format!("<@{}> now owns ${ticker}", user.id))
+            .color(Color::DARK_GREEN)
+            .timestamp(Timestamp::now()),
+    );
+
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
+
+/// Issue new shares of one of your stocks to yourself
+#[poise::command(slash_command, ephemeral)]
+async fn issue<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The stock to issue"]
+    #[autocomplete = "autocomplete_ticker"]
+    ticker: TickerArg,
+    #[description = "How many shares to issue"]
+    #[min = 1]
+    quantity: u32,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let ticker = *ticker;
+
+    let quantity = parse_shares(quantity)?;
+
+    let owner = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+    let info = stock_service
+        .issue_shares(&ticker, quantity, &owner)
+        .await?;
+
+    let reply = CreateReply::default().embed(
+        CreateEmbed::new()
+            .title(format!("Issued {quantity} shares of ${ticker}"))
+            .field("Issued shares", info.shares.to_string(), true)
+            .color(Color::DARK_GREEN)
+            .timestamp(Timestamp::now()),
+    );
+
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
+
+/// Retire some of your shares of one of your stocks
+#[poise::command(slash_command, ephemeral)]
+async fn buyback<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The stock to buy back"]
+    #[autocomplete = "autocomplete_ticker"]
+    ticker: TickerArg,
+    #[description = "How many of your shares to retire"]
+    #[min = 1]
+    quantity: u32,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let ticker = *ticker;
+
+    let quantity = parse_shares(quantity)?;
+
+    let owner = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+    let info = stock_service.buyback(&ticker, quantity, &owner).await?;
+
+    let reply = CreateReply::default().embed(
+        CreateEmbed::new()
+            .title(format!("Bought back {quantity} shares of ${ticker}"))
+            .field("Issued shares", info.shares.to_string(), true)
+            .color(Color::DARK_GREEN)
+            .timestamp(Timestamp::now()),
+    );
+
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
+
+/// Titles an embed with a stock's name and shows its description and icon, leaving out whichever
+/// the owner hasn't set
+fn described(embed: CreateEmbed, info: &StockInfo) -> CreateEmbed {
+    let (ticker, metadata) = (info.ticker, &info.metadata);
+
+    let mut embed = embed.title(match &metadata.name {
+        Some(name) => format!("{name} (${ticker})"),
+        None => format!("${ticker}"),
+    });
+    if let Some(description) = &metadata.description {
+        embed = embed.description(description);
+    }
+    if let Some(icon_url) = &metadata.icon_url {
+        embed = embed.thumbnail(icon_url);
+    }
+
+    embed
+}
+
+/// Describes an account by whichever of its links can be shown in Discord
+async fn describe_account<R: StockRepository>(
+    stock_service: &Service<R>,
+    id: &Uuid,
+) -> Result<String, Error> {
+    let info = stock_service.get_account_info(id, None).await?;
+
+    if info.closed_at.is_some() {
+        return Ok("A closed account".to_owned());
+    }
+
+    Ok(match (info.disc_id, info.mc_id) {
+        (Some(disc_id), _) => format!("<@{disc_id}>"),
+        (None, Some(mc_id)) => format!("Minecraft player `{mc_id}`"),
+        (None, None) => "The exchange".to_owned(),
+    })
+}
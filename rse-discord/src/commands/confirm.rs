@@ -0,0 +1,115 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Asking the invoker to confirm an action before it is taken
+
+use std::time::Duration;
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{
+        ButtonStyle, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter,
+        CreateInteractionResponse, CreateInteractionResponseMessage,
+        collector::ComponentInteractionCollector,
+    },
+};
+use rse_core::repo::StockRepository;
+
+use crate::{Context, Error};
+
+/// Sends `embed` with Confirm and Cancel buttons, returning whether the invoker confirmed within
+/// `timeout`. Presses from anyone else are ignored, and running out of time counts as cancelling.
+/// Either way, the message is edited to show the outcome and its buttons are disabled, so the
+/// result of the action itself should be sent as a new reply.
+///
+/// # Errors
+/// Returns an error if the message could not be sent or updated
+pub async fn confirm<R: StockRepository>(
+    ctx: Context<'_, R>,
+    embed: CreateEmbed,
+    timeout: Duration,
+) -> Result<bool, Error> {
+    let ctx_id = ctx.id();
+    let confirm_id = format!("{ctx_id}confirm");
+    let cancel_id = format!("{ctx_id}cancel");
+
+    let handle = send_reply(
+        ctx,
+        CreateReply::default()
+            .embed(embed.clone())
+            .components(vec![buttons(&confirm_id, &cancel_id, false)]),
+    )
+    .await?;
+
+    let ids = [confirm_id.clone(), cancel_id.clone()];
+    let press = ComponentInteractionCollector::new(ctx)
+        .author_id(ctx.author().id)
+        .filter(move |press| ids.contains(&press.data.custom_id))
+        .timeout(timeout)
+        .await;
+
+    let confirmed = press
+        .as_ref()
+        .is_some_and(|press| press.data.custom_id == confirm_id);
+
+    let outcome = match &press {
+        Some(_) if confirmed => "Confirmed",
+        Some(_) => "Cancelled",
+        None => "Timed out, nothing was done",
+    };
+    let embed = embed.footer(CreateEmbedFooter::new(outcome));
+    let components = vec![buttons(&confirm_id, &cancel_id, true)];
+
+    match press {
+        Some(press) => {
+            press
+                .create_response(
+                    ctx.serenity_context(),
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new()
+                            .embed(embed)
+                            .components(components),
+                    ),
+                )
+                .await?;
+        }
+        None => {
+            handle
+                .edit(
+                    ctx,
+                    CreateReply::default().embed(embed).components(components),
+                )
+                .await?;
+        }
+    }
+
+    Ok(confirmed)
+}
+
+fn buttons(confirm_id: &str, cancel_id: &str, disabled: bool) -> CreateActionRow {
+    CreateActionRow::Buttons(vec![
+        CreateButton::new(confirm_id)
+            .label("Confirm")
+            .style(ButtonStyle::Success)
+            .disabled(disabled),
+        CreateButton::new(cancel_id)
+            .label("Cancel")
+            .style(ButtonStyle::Danger)
+            .disabled(disabled),
+    ])
+}
@@ -0,0 +1,101 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Acknowledging interactions before doing slow work on them
+
+use std::fmt::Display;
+
+use rse_core::repo::StockRepository;
+use tracing::warn;
+
+use crate::Context;
+
+/// Defers the ephemeral reply to `ctx`, so Discord keeps waiting for it past the 3 seconds it
+/// gives an interaction to be acknowledged.
+///
+/// Await this before starting the work the reply reports on rather than racing the two: if the
+/// work finishes first, its reply goes out as the initial response and the late defer then fails
+/// on an interaction that was already acknowledged. A failed defer is only logged, as the reply
+/// can still land and the work behind it may already be committed.
+pub(crate) async fn defer_ephemeral_or_log<R: StockRepository>(ctx: Context<'_, R>) {
+    or_log(ctx.defer_ephemeral()).await;
+}
+
+/// Awaits `defer`, logging rather than returning its failure
+async fn or_log<E: Display>(defer: impl Future<Output = Result<(), E>>) {
+    if let Err(err) = defer.await {
+        warn!(%err, "Couldn't defer the reply");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Mutex, time::Duration};
+
+    use super::*;
+
+    /// Stands in for an interaction, recording what happens to it in order
+    #[derive(Default)]
+    struct Interaction {
+        events: Mutex<Vec<&'static str>>,
+    }
+
+    impl Interaction {
+        fn push(&self, event: &'static str) {
+            self.events.lock().expect("Not poisoned").push(event);
+        }
+
+        async fn defer(&self, delay: Duration, fails: bool) -> Result<(), &'static str> {
+            tokio::time::sleep(delay).await;
+            self.push("defer");
+
+            if fails {
+                Err("Unknown interaction")
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Runs a command the way `/register` does: defer, do the work, then reply
+        async fn run(&self, delay: Duration, fails: bool) -> Vec<&'static str> {
+            or_log(self.defer(delay, fails)).await;
+            self.push("work");
+            self.push("reply");
+
+            self.events.lock().expect("Not poisoned").clone()
+        }
+    }
+
+    #[tokio::test]
+    async fn slow_defers_land_before_the_reply() {
+        let events = Interaction::default()
+            .run(Duration::from_millis(50), false)
+            .await;
+
+        assert_eq!(events, ["defer", "work", "reply"]);
+    }
+
+    #[tokio::test]
+    async fn failed_defers_still_reply() {
+        let events = Interaction::default()
+            .run(Duration::from_millis(50), true)
+            .await;
+
+        assert_eq!(events, ["defer", "work", "reply"]);
+    }
+}
@@ -0,0 +1,53 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed},
+};
+use rse_core::{model::link::Identity, repo::StockRepository};
+
+use crate::{
+    Context, Error,
+    i18n::{self, t},
+};
+
+/// Get the address to send Kromer to to fund your account
+#[poise::command(slash_command, ephemeral)]
+pub async fn deposit<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+
+    let embed = match stock_service.deposit_address(&user_id) {
+        Some(address) => CreateEmbed::new()
+            .title(t!(locale, "deposit.title"))
+            .description(t!(locale, "deposit.address", address = address))
+            .color(Color::DARK_GREEN),
+        None => CreateEmbed::new()
+            .title(t!(locale, "deposit.title"))
+            .description(t!(locale, "deposit.disabled"))
+            .color(Color::GOLD),
+    };
+
+    send_reply(ctx, CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
@@ -0,0 +1,89 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::{str::FromStr, time::Duration};
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed, Timestamp},
+};
+use rse_core::{model::link::Identity, repo::StockRepository};
+use rust_decimal::Decimal;
+use snafu::ResultExt;
+
+use crate::{
+    Context, Error,
+    commands::{TickerArg, autocomplete_ticker, confirm::confirm},
+    error::InvalidPriceSnafu,
+};
+
+/// Pay a dividend to everyone else holding a stock you control
+#[poise::command(slash_command, ephemeral)]
+pub async fn dividend<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The stock to pay a dividend on"]
+    #[autocomplete = "autocomplete_ticker"]
+    ticker: TickerArg,
+    #[description = "The Kromer paid for each share held"] per_share: String,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let ticker = *ticker;
+    let per_share = Decimal::from_str(per_share.trim()).context(InvalidPriceSnafu {
+        input: per_share.clone(),
+    })?;
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+
+    let plan = stock_service
+        .quote_dividend(&ticker, per_share, &user_id)
+        .await?;
+
+    let summary = CreateEmbed::new()
+        .title(format!("Pay a dividend on ${ticker}?"))
+        .description(format!(
+            "{per_share} per share on {} shares, paid to {} holders\nTotal cost: {:.2}",
+            plan.shares,
+            plan.payouts.len(),
+            plan.total
+        ))
+        .color(Color::GOLD);
+
+    if !confirm(ctx, summary, Duration::from_mins(2)).await? {
+        return Ok(());
+    }
+
+    let dividend = stock_service
+        .pay_dividend(&ticker, per_share, &user_id)
+        .await?;
+
+    let reply = CreateReply::default().embed(
+        CreateEmbed::new()
+            .title(format!("Paid a dividend on ${ticker}"))
+            .description(format!(
+                "{} per share on {} shares, paid to {} holders\nTotal cost: {:.2}",
+                dividend.per_share, dividend.shares, dividend.holders, dividend.total
+            ))
+            .color(Color::DARK_GREEN)
+            .timestamp(Timestamp::now()),
+    );
+
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
@@ -0,0 +1,441 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Exports of a user's data as files they can open elsewhere
+
+use std::io::Write;
+
+use chrono::{DateTime, Utc};
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{CreateAttachment, User},
+};
+use rse_core::{
+    Service,
+    error::Error as RscError,
+    model::{
+        HoldingOrdering, Pager, guild::Permission, link::Identity, order::UserTrade,
+        view::HoldingView,
+    },
+    repo::StockRepository,
+};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    Context, Error,
+    commands::{admin::record_invocation, defer_ephemeral_or_log, permission::require},
+};
+
+/// How many rows are fetched at a time
+const CHUNK: i64 = 500;
+
+/// Discord's limit on the size of an attachment
+pub(super) const MAX_BYTES: usize = 8 * 1024 * 1024;
+
+/// Room kept free for the truncation warning and the end of the file, which never take this much
+const HEADROOM: usize = 4 * 1024;
+
+/// Explains why an export stops short
+const TRUNCATED: &str = "Export too large for Discord, later rows were left out";
+
+const IN_MEMORY: &str = "Writing plain records to memory never fails";
+
+/// The format to export in
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum FormatChoice {
+    /// Comma-separated values, for spreadsheets
+    #[name = "CSV"]
+    Csv,
+    /// JSON, for scripts
+    #[name = "JSON"]
+    Json,
+}
+
+impl FormatChoice {
+    const fn extension(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+/// The data to export
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum DataChoice {
+    /// Every current holding
+    Holdings,
+    /// Every trade ever made
+    History,
+}
+
+impl DataChoice {
+    const fn key(self) -> &'static str {
+        match self {
+            Self::Holdings => "holdings",
+            Self::History => "history",
+        }
+    }
+}
+
+/// A row of a trade history export
+#[derive(Debug, Serialize)]
+struct TradeRecord<'a> {
+    id: i32,
+    time: DateTime<Utc>,
+    ticker: &'a str,
+    side: String,
+    shares: u32,
+    price: Decimal,
+    fee: Decimal,
+    realized_pl: Option<Decimal>,
+}
+
+impl<'a> From<&'a UserTrade> for TradeRecord<'a> {
+    fn from(value: &'a UserTrade) -> Self {
+        Self {
+            id: value.id,
+            time: value.time,
+            ticker: value.ticker.as_str(),
+            side: value.side.to_string(),
+            shares: value.shares.into(),
+            price: value.price.get(),
+            fee: value.fee,
+            realized_pl: value.realized_pl,
+        }
+    }
+}
+
+/// Export your holdings or trade history as a file
+#[poise::command(slash_command, ephemeral)]
+pub async fn export<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "File format"] format: FormatChoice,
+    #[description = "What to export"] what: DataChoice,
+    #[description = "Export another user's data instead, needs the view admin permission"]
+    user: Option<User>,
+) -> Result<(), Error> {
+    let author = ctx.author();
+    let target = user.as_ref().unwrap_or(author);
+
+    if target.id != author.id {
+        require(ctx, Permission::ViewAdmin).await?;
+
+        record_invocation(
+            ctx,
+            serde_json::json!({ "user": target.id, "what": what.key() }),
+        )
+        .await?;
+    }
+
+    defer_ephemeral_or_log(ctx).await;
+
+    let service = ctx.data().service();
+    let user_id = service
+        .resolve(&Identity::Discord(target.id.into()))
+        .await?;
+    let mut export = Export::new(format, what, &user_id, Utc::now(), MAX_BYTES);
+
+    match what {
+        DataChoice::Holdings => export_holdings(service, &user_id, &mut export).await?,
+        DataChoice::History => export_history(service, &user_id, &mut export).await?,
+    }
+
+    let content = if export.truncated {
+        TRUNCATED.to_owned()
+    } else {
+        format!("Here is {}'s {}", target.name, what.key())
+    };
+    let file = format!("{}-{user_id}.{}", what.key(), format.extension());
+
+    send_reply(
+        ctx,
+        CreateReply::default()
+            .content(content)
+            .attachment(CreateAttachment::bytes(export.finish(), file)),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Adds every one of a user's holdings to `export`, until it fills up
+async fn export_holdings<R: StockRepository>(
+    service: &Service<R>,
+    user_id: &Uuid,
+    export: &mut Export,
+) -> Result<(), RscError> {
+    let mut page = Pager::new(0, CHUNK);
+
+    loop {
+        let holdings = service
+            .get_holdings_pl(user_id, &page, HoldingOrdering::Ticker, Some(user_id))
+            .await?;
+
+        for holding in &holdings.items {
+            if !export.push(&HoldingView::from(holding)) {
+                return Ok(());
+            }
+        }
+
+        if holdings.is_last() {
+            return Ok(());
+        }
+
+        page.add_offset(CHUNK);
+    }
+}
+
+/// Adds every trade a user made to `export`, until it fills up
+async fn export_history<R: StockRepository>(
+    service: &Service<R>,
+    user_id: &Uuid,
+    export: &mut Export,
+) -> Result<(), RscError> {
+    let mut after = None;
+
+    loop {
+        let trades = service.trade_history(user_id, after, CHUNK).await?;
+
+        for trade in &trades {
+            if !export.push(&TradeRecord::from(trade)) {
+                return Ok(());
+            }
+        }
+
+        let Some(last) = trades.last() else {
+            return Ok(());
+        };
+        after = Some(last.id);
+    }
+}
+
+/// An export being written to memory, which stops taking rows before it outgrows its limit
+struct Export {
+    out: Output,
+    limit: usize,
+    truncated: bool,
+}
+
+/// Where an export is written to
+enum Output {
+    Csv(Box<csv::Writer<Vec<u8>>>),
+    Json { buff: Vec<u8>, rows: usize },
+}
+
+impl Export {
+    /// Starts an export of `what` belonging to `account`, with a header saying who and when it is
+    /// for
+    fn new(
+        format: FormatChoice,
+        what: DataChoice,
+        account: &Uuid,
+        generated_at: DateTime<Utc>,
+        limit: usize,
+    ) -> Self {
+        let generated_at = generated_at.to_rfc3339();
+
+        let out = match format {
+            FormatChoice::Csv => {
+                // Rows after the header have a different number of fields
+                let mut writer = csv::WriterBuilder::new()
+                    .flexible(true)
+                    .from_writer(Vec::new());
+                writer
+                    .write_record(["# account", &account.to_string()])
+                    .expect(IN_MEMORY);
+                writer
+                    .write_record(["# generated_at", &generated_at])
+                    .expect(IN_MEMORY);
+                Output::Csv(Box::new(writer))
+            }
+            FormatChoice::Json => {
+                let mut buff = Vec::new();
+                // Neither value needs escaping. The array is closed in `finish`
+                write!(
+                    buff,
+                    r#"{{"account":"{account}","generated_at":"{generated_at}","{}":["#,
+                    what.key()
+                )
+                .expect(IN_MEMORY);
+                Output::Json { buff, rows: 0 }
+            }
+        };
+
+        Self {
+            out,
+            limit,
+            truncated: false,
+        }
+    }
+
+    /// Appends a row, unless the export is close enough to its limit that it might not fit.
+    /// Returns whether the row was added
+    fn push(&mut self, row: &impl Serialize) -> bool {
+        if self.truncated || self.len() + HEADROOM > self.limit {
+            self.truncated = true;
+            return false;
+        }
+
+        match &mut self.out {
+            Output::Csv(writer) => {
+                writer.serialize(row).expect(IN_MEMORY);
+                // So the length is exact
+                writer.flush().expect(IN_MEMORY);
+            }
+            Output::Json { buff, rows } => {
+                if *rows > 0 {
+                    buff.push(b',');
+                }
+                serde_json::to_writer(&mut *buff, row).expect(IN_MEMORY);
+                *rows += 1;
+            }
+        }
+
+        true
+    }
+
+    fn len(&self) -> usize {
+        match &self.out {
+            Output::Csv(writer) => writer.get_ref().len(),
+            Output::Json { buff, .. } => buff.len(),
+        }
+    }
+
+    /// Closes off the export, adding a warning if any rows were left out
+    fn finish(self) -> Vec<u8> {
+        match self.out {
+            Output::Csv(mut writer) => {
+                if self.truncated {
+                    writer
+                        .write_record(["# truncated", TRUNCATED])
+                        .expect(IN_MEMORY);
+                }
+                writer.into_inner().expect(IN_MEMORY)
+            }
+            Output::Json { mut buff, .. } => {
+                buff.extend_from_slice(b"]");
+                if self.truncated {
+                    write!(buff, r#","warning":"{TRUNCATED}""#).expect(IN_MEMORY);
+                }
+                write!(buff, r#","truncated":{}}}"#, self.truncated).expect(IN_MEMORY);
+                buff
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rse_core::model::{HoldingPl, Price, Shares, ticker::Ticker};
+
+    use super::*;
+
+    fn holding() -> HoldingPl {
+        HoldingPl {
+            ticker: Ticker::try_from("ABC").expect("Valid ticker"),
+            shares: Shares::new(3).expect("Valid shares"),
+            avg_cost: Some(Decimal::from(2)),
+            price: Some(Price::new(Decimal::from(5)).expect("Valid price")),
+        }
+    }
+
+    /// Fills an export until it stops taking rows, returning how many it took
+    fn fill(export: &mut Export) -> usize {
+        let holding = holding();
+        let mut rows = 0;
+
+        while export.push(&HoldingView::from(&holding)) {
+            rows += 1;
+        }
+
+        rows
+    }
+
+    #[test]
+    fn json_exports_are_valid() {
+        let account = Uuid::from_u128(1);
+        let mut export = Export::new(
+            FormatChoice::Json,
+            DataChoice::Holdings,
+            &account,
+            Utc::now(),
+            MAX_BYTES,
+        );
+        assert!(export.push(&HoldingView::from(&holding())));
+        assert!(export.push(&HoldingView::from(&holding())));
+
+        let value: serde_json::Value =
+            serde_json::from_slice(&export.finish()).expect("Valid JSON");
+
+        assert_eq!(value["account"], account.to_string());
+        assert_eq!(value["holdings"].as_array().map(Vec::len), Some(2));
+        assert_eq!(value["holdings"][0]["value"], "15");
+        assert_eq!(value["truncated"], false);
+    }
+
+    #[test]
+    fn csv_exports_start_with_a_header() {
+        let account = Uuid::from_u128(1);
+        let mut export = Export::new(
+            FormatChoice::Csv,
+            DataChoice::Holdings,
+            &account,
+            Utc::now(),
+            MAX_BYTES,
+        );
+        assert!(export.push(&HoldingView::from(&holding())));
+
+        let out = String::from_utf8(export.finish()).expect("Valid UTF-8");
+        let lines: Vec<_> = out.lines().collect();
+
+        assert_eq!(lines[0], format!("# account,{account}"));
+        assert!(lines[1].starts_with("# generated_at,"));
+        assert_eq!(lines[2], "ticker,shares,avg_cost,price,value,unrealized_pl");
+        assert_eq!(lines[3], "ABC,3,2,5,15,9");
+        assert_eq!(lines.len(), 4);
+    }
+
+    #[test]
+    fn exports_are_truncated_within_the_limit() {
+        let limit = 2 * HEADROOM;
+
+        for format in [FormatChoice::Csv, FormatChoice::Json] {
+            let mut export = Export::new(
+                format,
+                DataChoice::Holdings,
+                &Uuid::nil(),
+                Utc::now(),
+                limit,
+            );
+            let rows = fill(&mut export);
+            assert!(rows > 0, "{format:?}");
+
+            let out = export.finish();
+            assert!(out.len() <= limit, "{format:?}");
+            assert!(
+                String::from_utf8(out)
+                    .expect("Valid UTF-8")
+                    .contains(TRUNCATED),
+                "{format:?}"
+            );
+        }
+    }
+}
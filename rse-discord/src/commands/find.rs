@@ -0,0 +1,223 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::{
+    Context, Error,
+    commands::{
+        budget::EmbedBudget,
+        presses::{PageCursor, Presses},
+    },
+    error::InvalidOptionsSnafu,
+    i18n::{self, t},
+};
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{
+        Color, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter,
+        CreateInteractionResponse, CreateInteractionResponseMessage,
+    },
+};
+use rse_core::{
+    MIN_SEARCH_LEN,
+    display::format_kromer,
+    model::{Pager, SearchField, StockMatch},
+    repo::StockRepository,
+};
+
+/// How many characters of a stock's description are shown under it
+const SNIPPET_LEN: usize = 80;
+
+#[poise::command(slash_command, ephemeral)]
+pub async fn find<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "A ticker, or words from a stock's name or description"]
+    #[min_length = 2]
+    #[max_length = 100]
+    query: String,
+    #[description = "Show the reply to everyone in the channel"] public: Option<bool>,
+) -> Result<(), Error> {
+    const PAGE_SIZE: u64 = 10;
+    let ctx_id = ctx.id();
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+    let public = public.unwrap_or_default();
+
+    // Discord counts the spaces towards the minimum length, the search doesn't
+    if query.trim().chars().count() < MIN_SEARCH_LEN {
+        return InvalidOptionsSnafu {
+            reason: "Search for at least 2 characters, not counting spaces",
+        }
+        .fail();
+    }
+
+    let matches = stock_service
+        .search_stocks(&query, &Pager::new(0, PAGE_SIZE.cast_signed()))
+        .await?;
+
+    let mut cursor = PageCursor::new(matches.total, PAGE_SIZE);
+
+    if cursor.pages() <= 1 {
+        send_reply(
+            ctx,
+            CreateReply::default()
+                .ephemeral(!public)
+                .embed(into_embed(&matches.items, locale)),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let prev_button_id = format!("{ctx_id}prev");
+    let next_button_id = format!("{ctx_id}next");
+
+    let reply = {
+        let components = CreateActionRow::Buttons(vec![
+            CreateButton::new(&prev_button_id).emoji('◀'),
+            CreateButton::new(&next_button_id).emoji('▶'),
+        ]);
+
+        CreateReply::default()
+            .ephemeral(!public)
+            .embed(
+                into_embed(&matches.items, locale).footer(CreateEmbedFooter::new(t!(
+                    locale,
+                    "find.page",
+                    page = cursor.number(),
+                    pages = cursor.pages()
+                ))),
+            )
+            .components(vec![components])
+    };
+
+    send_reply(ctx, reply).await?;
+
+    let mut presses = Presses::new(ctx);
+
+    while let Some(press) = presses.next().await {
+        if press.data.custom_id == prev_button_id {
+            cursor.prev();
+        } else if press.data.custom_id == next_button_id {
+            cursor.next();
+        } else {
+            // Unrelated interaction
+            continue;
+        }
+
+        // Stocks may have been renamed since the last page, in which case the page may have to move
+        let matches = loop {
+            let matches = stock_service.search_stocks(&query, &cursor.pager()).await?;
+
+            if !cursor.sync(matches.total) {
+                break matches;
+            }
+        };
+
+        let footer = t!(
+            locale,
+            "find.page",
+            page = cursor.number(),
+            pages = cursor.pages()
+        );
+
+        press
+            .create_response(
+                ctx.serenity_context(),
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new().embed(
+                        into_embed(&matches.items, locale)
+                            .footer(CreateEmbedFooter::new(cursor.footer(footer, locale))),
+                    ),
+                ),
+            )
+            .await?;
+    }
+
+    presses.expire(locale).await;
+
+    Ok(())
+}
+
+fn into_embed(matches: &[StockMatch], locale: &str) -> CreateEmbed {
+    let embed = CreateEmbed::new().color(Color::BLURPLE);
+
+    if matches.is_empty() {
+        return embed.description(t!(locale, "find.no_match"));
+    }
+
+    let mut budget = EmbedBudget::new(locale);
+
+    let fields = matches
+        .iter()
+        .map(|found| {
+            let title = match &found.name {
+                Some(name) => format!("{} · {name}", found.ticker),
+                None => found.ticker.to_string(),
+            };
+
+            let price = found
+                .price
+                .map_or_else(|| "—".to_owned(), |price| format_kromer(price.get()));
+
+            let field = match found.matched {
+                SearchField::Ticker => t!(locale, "find.by_ticker"),
+                SearchField::Name => t!(locale, "find.by_name"),
+                SearchField::Description => t!(locale, "find.by_description"),
+            };
+
+            let mut value = t!(locale, "find.entry", price = price, field = field);
+
+            if let Some(snippet) = found.description.as_deref().and_then(snippet) {
+                value = format!("{snippet}\n{value}");
+            }
+
+            (title, value, false)
+        })
+        .collect();
+
+    embed.fields(budget.fields(fields))
+}
+
+/// The first line of `description`, cut short at [`SNIPPET_LEN`] characters. `None` if it is blank
+fn snippet(description: &str) -> Option<String> {
+    let line = description
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())?;
+
+    if line.chars().count() <= SNIPPET_LEN {
+        return Some(line.to_owned());
+    }
+
+    let cut: String = line.chars().take(SNIPPET_LEN - 1).collect();
+    Some(format!("{}…", cut.trim_end()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snippets_are_one_short_line() {
+        assert_eq!(snippet("\n  Melons  \nand more"), Some("Melons".to_owned()));
+        assert_eq!(snippet(" \n\t"), None);
+
+        let long = snippet(&"grows melons ".repeat(20)).expect("Not blank");
+        assert!(long.chars().count() <= SNIPPET_LEN);
+        assert!(long.ends_with('…'));
+    }
+}
@@ -0,0 +1,89 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed},
+};
+use rse_core::{
+    error::Error as RscErr,
+    model::link::{Identity, LinkCode},
+    repo::StockRepository,
+};
+
+use crate::{
+    Context, Error,
+    i18n::{self, t},
+};
+
+/// Link another identity to your account
+#[poise::command(
+    slash_command,
+    ephemeral,
+    subcommands("minecraft"),
+    subcommand_required
+)]
+#[allow(clippy::unused_async)] // Poise requires commands to be async
+pub async fn link<R: StockRepository>(_ctx: Context<'_, R>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Get a code to link your Minecraft player, or use one given to you in Minecraft
+#[poise::command(slash_command, ephemeral)]
+async fn minecraft<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "A code given to you in Minecraft, to link this Discord account to that player"]
+    code: Option<String>,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+
+    // Registered in Minecraft first, so the code proves this Discord user is that player
+    if let Some(code) = code {
+        let code: LinkCode = code.parse().map_err(|_| RscErr::InvalidLinkCode)?;
+        stock_service
+            .consume_discord_link_code(&code, ctx.author().id.into())
+            .await?;
+
+        let embed = CreateEmbed::new()
+            .title(t!(locale, "link.linked_title"))
+            .description(t!(locale, "link.linked_discord"))
+            .color(Color::DARK_GREEN);
+        send_reply(ctx, CreateReply::default().embed(embed)).await?;
+
+        return Ok(());
+    }
+
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+    let (code, expires_at) = stock_service.create_link_code(&user_id).await?;
+
+    let embed = CreateEmbed::new()
+        .title(t!(locale, "link.code_title"))
+        .description(t!(
+            locale,
+            "link.code",
+            code = code,
+            expires = expires_at.timestamp()
+        ))
+        .color(Color::BLITZ_BLUE);
+    send_reply(ctx, CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
@@ -0,0 +1,154 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fmt::Write;
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed, CreateEmbedAuthor, Timestamp},
+};
+use rse_core::{
+    display::{format_kromer, format_signed},
+    error::Error as RscError,
+    model::{Transaction, TransactionKind, link::Identity},
+    repo::StockRepository,
+};
+
+use crate::{
+    Context, Error,
+    i18n::{self, t},
+};
+
+/// See your balance, holdings, open orders and latest activity at a glance
+#[poise::command(slash_command, ephemeral)]
+pub async fn me<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+
+    let user_id = match stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await
+    {
+        Ok(id) => id,
+        Err(RscError::UserNotFound { .. }) => {
+            let reply = CreateReply::default().embed(
+                CreateEmbed::new()
+                    .title(t!(locale, "error.title"))
+                    .description(t!(locale, "me.no_account"))
+                    .color(Color::RED),
+            );
+            send_reply(ctx, reply).await?;
+
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let (info, summary) = tokio::try_join!(
+        stock_service.get_account_info(&user_id, Some(&user_id)),
+        stock_service.account_summary(&user_id)
+    )?;
+    // Always visible to the account itself
+    let balance = info.balance.unwrap_or_default();
+    let available = info.available_balance.unwrap_or_default();
+    let held = info.held_balance.unwrap_or_default();
+
+    let mut activity = String::new();
+    for transaction in &summary.recent {
+        writeln!(activity, "{}", transaction_line(transaction, locale)).expect("Never fails");
+    }
+    if activity.is_empty() {
+        activity = t!(locale, "me.no_activity");
+    }
+
+    let mut embed = CreateEmbed::new()
+        .title(t!(locale, "me.title"))
+        .author(
+            CreateEmbedAuthor::new(user_id.to_string())
+                .icon_url(ctx.author().avatar_url().unwrap_or_default()),
+        )
+        .field(t!(locale, "me.available"), format_kromer(available), true)
+        .field(t!(locale, "me.on_hold"), format_kromer(held), true)
+        .field(
+            t!(locale, "me.holdings"),
+            t!(
+                locale,
+                "me.holdings_value",
+                value = format_kromer(summary.holdings_value),
+                stocks = summary.stocks_held
+            ),
+            true,
+        )
+        .field(
+            t!(locale, "me.net_worth"),
+            format_kromer(balance + summary.holdings_value),
+            true,
+        )
+        .field(
+            t!(locale, "me.open_orders"),
+            summary.open_orders.to_string(),
+            true,
+        )
+        .color(Color::BLITZ_BLUE)
+        .timestamp(Timestamp::now());
+
+    if let Some(eligible_at) = stock_service.matures_at(&info) {
+        embed = embed.field(
+            t!(locale, "me.maturity"),
+            t!(locale, "me.unlocks", eligible = eligible_at.timestamp()),
+            false,
+        );
+    }
+
+    embed = embed.field(t!(locale, "me.recent"), activity, false);
+
+    send_reply(ctx, CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Renders a transaction as a single line, e.g. `−12.50 KRO` Bought $ABC 5 minutes ago
+fn transaction_line(transaction: &Transaction, locale: &str) -> String {
+    let ticker = transaction
+        .ticker
+        .map(|ticker| format!(" ${ticker}"))
+        .unwrap_or_default();
+
+    format!(
+        "`{}` {}{ticker} <t:{}:R>",
+        format_signed(transaction.delta),
+        kind_label(transaction.kind, locale),
+        transaction.time.timestamp()
+    )
+}
+
+/// Describes what caused a transaction, e.g. Bought
+pub(super) fn kind_label(kind: TransactionKind, locale: &str) -> String {
+    match kind {
+        TransactionKind::Buy => t!(locale, "me.kind_buy"),
+        TransactionKind::Sell => t!(locale, "me.kind_sell"),
+        TransactionKind::Dividend => t!(locale, "me.kind_dividend"),
+        TransactionKind::Grant => t!(locale, "me.kind_grant"),
+        TransactionKind::Deposit => t!(locale, "me.kind_deposit"),
+        TransactionKind::Withdrawal => t!(locale, "me.kind_withdrawal"),
+        TransactionKind::WithdrawalReleased => t!(locale, "me.kind_withdrawal_released"),
+        TransactionKind::Liquidation => t!(locale, "me.kind_liquidation"),
+        TransactionKind::Fee => t!(locale, "me.kind_fee"),
+        TransactionKind::Adjustment => t!(locale, "me.kind_adjustment"),
+    }
+}
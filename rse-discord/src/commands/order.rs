@@ -0,0 +1,359 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::{fmt::Write, time::Duration};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{
+        Color, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter,
+        CreateInteractionResponse, CreateInteractionResponseMessage, Timestamp,
+    },
+};
+use rse_core::{
+    model::{
+        Pager, Shares,
+        link::Identity,
+        order::{Fill, Order, OrderStatus, Side},
+    },
+    repo::StockRepository,
+};
+use rust_decimal::Decimal;
+
+use crate::{
+    Context, Error,
+    commands::{
+        TickerArg, autocomplete_ticker,
+        budget::{EmbedBudget, MAX_DESCRIPTION},
+        confirm::confirm,
+        ensure_trading, parse_price, parse_shares,
+        presses::{PageCursor, Presses},
+    },
+    i18n,
+};
+
+/// Which side of the book to place an order on
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum SideChoice {
+    /// Buy shares
+    Buy,
+    /// Sell shares
+    Sell,
+}
+
+impl From<SideChoice> for Side {
+    fn from(value: SideChoice) -> Self {
+        match value {
+            SideChoice::Buy => Self::Buy,
+            SideChoice::Sell => Self::Sell,
+        }
+    }
+}
+
+/// How long an order may rest on the book before it expires
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum DurationChoice {
+    /// Expires after an hour
+    #[name = "1 hour"]
+    Hour,
+    /// Expires after a day
+    #[name = "1 day"]
+    Day,
+    /// Expires after a week
+    #[name = "7 days"]
+    Week,
+    /// Rests until filled or cancelled
+    #[name = "Good 'til cancelled"]
+    Gtc,
+}
+
+impl DurationChoice {
+    /// When an order placed at `now` with this duration expires
+    fn expires_at(self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self {
+            Self::Hour => Some(now + TimeDelta::hours(1)),
+            Self::Day => Some(now + TimeDelta::days(1)),
+            Self::Week => Some(now + TimeDelta::weeks(1)),
+            Self::Gtc => None,
+        }
+    }
+}
+
+/// Place, cancel, and list limit orders
+#[poise::command(
+    slash_command,
+    ephemeral,
+    subcommands("place", "cancel", "list"),
+    subcommand_required
+)]
+#[allow(clippy::unused_async)] // Poise requires commands to be async
+pub async fn order<R: StockRepository>(_ctx: Context<'_, R>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Place a limit order, which rests on the book until it is filled, cancelled, or expires
+#[poise::command(slash_command, ephemeral)]
+async fn place<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The stock to trade"]
+    #[autocomplete = "autocomplete_ticker"]
+    ticker: TickerArg,
+    #[description = "Whether to buy or sell"] side: SideChoice,
+    #[description = "The worst price per share you will accept"] price: String,
+    #[description = "The number of shares"]
+    #[min = 1]
+    quantity: u32,
+    #[description = "How long the order may rest on the book. Defaults to good 'til cancelled"]
+    duration: Option<DurationChoice>,
+) -> Result<(), Error> {
+    ensure_trading(ctx).await?;
+
+    let stock_service = ctx.data().service();
+    let ticker = *ticker;
+    let price = parse_price(&price)?;
+    let quantity = parse_shares(quantity)?;
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+
+    let (order, fills) = stock_service
+        .place_order(
+            &user_id,
+            &ticker,
+            side.into(),
+            price,
+            quantity,
+            duration
+                .unwrap_or(DurationChoice::Gtc)
+                .expires_at(Utc::now()),
+        )
+        .await?;
+
+    let filled: u32 = fills.iter().map(|f| f.shares.get()).sum();
+    let mut description = format!(
+        "{} {} ${} @ {}",
+        order.side, order.quantity, order.ticker, order.price
+    );
+
+    if filled > 0 {
+        let value: Decimal = fills.iter().map(Fill::notional).sum();
+        write!(
+            description,
+            "\nFilled {filled} shares at an average of {:.2}",
+            value / Decimal::from(filled)
+        )
+        .expect("Never fails");
+
+        // Only buyers pay fees
+        let fee: Decimal = fills.iter().map(|f| f.fee).sum();
+        if order.side == Side::Buy && !fee.is_zero() {
+            write!(description, "\nFee: {fee:.2}").expect("Never fails");
+        }
+    }
+
+    if order.status == OrderStatus::Queued {
+        write!(description, "\nQueued until the market opens").expect("Never fails");
+
+        if let Some(opens_at) = stock_service.market_opens_at() {
+            write!(description, " <t:{}:R>", opens_at.timestamp()).expect("Never fails");
+        }
+
+        if let Some(expires_at) = order.expires_at {
+            write!(description, ", expiring <t:{}:R>", expires_at.timestamp())
+                .expect("Never fails");
+        }
+    } else if order.remaining > Shares::ZERO {
+        write!(
+            description,
+            "\n{} shares are resting on the book",
+            order.remaining
+        )
+        .expect("Never fails");
+
+        if let Some(expires_at) = order.expires_at {
+            write!(description, ", expiring <t:{}:R>", expires_at.timestamp())
+                .expect("Never fails");
+        }
+    }
+
+    let reply = CreateReply::default().embed(
+        CreateEmbed::new()
+            .title(format!("Order #{}", order.id))
+            .description(description)
+            .color(Color::DARK_GREEN)
+            .timestamp(Timestamp::now()),
+    );
+
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
+
+/// Cancel one of your open orders
+#[poise::command(slash_command, ephemeral)]
+async fn cancel<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The ID of the order"] id: i32,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+
+    let summary = CreateEmbed::new()
+        .title(format!("Cancel order #{id}?"))
+        .description("Any shares it hasn't filled yet will be taken off the book")
+        .color(Color::GOLD);
+
+    if !confirm(ctx, summary, Duration::from_mins(1)).await? {
+        return Ok(());
+    }
+
+    let order = stock_service.cancel_order(id, &user_id).await?;
+
+    let reply = CreateReply::default().embed(
+        CreateEmbed::new()
+            .title(format!("Cancelled order #{}", order.id))
+            .description(format!(
+                "{} {} ${} @ {}, {} shares unfilled",
+                order.side, order.quantity, order.ticker, order.price, order.remaining
+            ))
+            .color(Color::BLITZ_BLUE)
+            .timestamp(Timestamp::now()),
+    );
+
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
+
+/// List your open orders
+#[poise::command(slash_command, ephemeral)]
+async fn list<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
+    const PAGE_SIZE: u64 = 10;
+    let ctx_id = ctx.id();
+    let stock_service = ctx.data().service();
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+
+    let orders = stock_service
+        .open_orders(&user_id, &Pager::new(0, PAGE_SIZE.cast_signed()))
+        .await?;
+    let mut cursor = PageCursor::new(orders.total, PAGE_SIZE);
+
+    if cursor.pages() == 1 {
+        send_reply(ctx, CreateReply::default().embed(into_embed(&orders.items))).await?;
+        return Ok(());
+    }
+
+    let prev_button_id = format!("{ctx_id}prev");
+    let next_button_id = format!("{ctx_id}next");
+
+    let reply = {
+        let components = CreateActionRow::Buttons(vec![
+            CreateButton::new(&prev_button_id).emoji('◀'),
+            CreateButton::new(&next_button_id).emoji('▶'),
+        ]);
+
+        CreateReply::default()
+            .embed(
+                into_embed(&orders.items).footer(CreateEmbedFooter::new(format!(
+                    "Page: 1/{}",
+                    cursor.pages()
+                ))),
+            )
+            .components(vec![components])
+    };
+
+    send_reply(ctx, reply).await?;
+
+    let mut presses = Presses::new(ctx);
+
+    while let Some(press) = presses.next().await {
+        if press.data.custom_id == prev_button_id {
+            cursor.prev();
+        } else if press.data.custom_id == next_button_id {
+            cursor.next();
+        } else {
+            // Unrelated interaction
+            continue;
+        }
+
+        let new_orders = stock_service.open_orders(&user_id, &cursor.pager()).await?;
+
+        if new_orders.total != orders.total {
+            presses
+                .finish(
+                    &press,
+                    "Your open orders have changed, please call this command again",
+                )
+                .await?;
+            return Ok(());
+        }
+
+        press
+            .create_response(
+                ctx.serenity_context(),
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new().embed(
+                        into_embed(&new_orders.items).footer(CreateEmbedFooter::new(format!(
+                            "Page: {}/{}",
+                            cursor.number(),
+                            cursor.pages()
+                        ))),
+                    ),
+                ),
+            )
+            .await?;
+    }
+
+    presses.expire(&i18n::locale(ctx).await).await;
+
+    Ok(())
+}
+
+fn into_embed(v: &[Order]) -> CreateEmbed {
+    let lines: Vec<_> = v
+        .iter()
+        .map(|order| {
+            let mut line = format!(
+                "`#{}` {} {}/{} ${} @ {}",
+                order.id, order.side, order.remaining, order.quantity, order.ticker, order.price
+            );
+
+            if order.status == OrderStatus::Queued {
+                line.push_str(" (queued)");
+            }
+
+            line
+        })
+        .collect();
+
+    let mut buff = EmbedBudget::new(i18n::FALLBACK).lines(MAX_DESCRIPTION, "", &lines, "");
+
+    if buff.is_empty() {
+        buff.push_str("You have no open orders");
+    }
+
+    CreateEmbed::new()
+        .title("Open orders")
+        .color(Color::BLURPLE)
+        .description(buff)
+}
@@ -0,0 +1,128 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fmt::Write;
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed, Timestamp},
+};
+use rse_core::{
+    model::order::{Book, BookLevel},
+    repo::StockRepository,
+};
+use rust_decimal::Decimal;
+
+use crate::{
+    Context, Error,
+    commands::{TickerArg, autocomplete_ticker},
+};
+
+const NONE: &str = "—";
+
+/// Show the open orders on a stock's book
+#[poise::command(slash_command, ephemeral)]
+pub async fn orderbook<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The stock to show"]
+    #[autocomplete = "autocomplete_ticker"]
+    ticker: TickerArg,
+    #[description = "How many price levels to show on each side"]
+    #[min = 1]
+    #[max = 20]
+    depth: Option<u8>,
+) -> Result<(), Error> {
+    let ticker = *ticker;
+    let book = ctx
+        .data()
+        .service()
+        .order_book(&ticker, depth.unwrap_or(10).clamp(1, 20))
+        .await?;
+
+    let title = format!(
+        "${ticker} — Spread: {} | Mid: {}",
+        display(book.spread()),
+        display(book.mid_price())
+    );
+
+    let description = if book.bids.is_empty() && book.asks.is_empty() {
+        format!(
+            "There are no open orders for this stock\nLast trade: {}",
+            display(book.last_price)
+        )
+    } else {
+        into_columns(&book)
+    };
+
+    send_reply(
+        ctx,
+        CreateReply::default().embed(
+            CreateEmbed::new()
+                .title(title)
+                .description(description)
+                .color(Color::BLURPLE)
+                .timestamp(Timestamp::now()),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+fn display(v: Option<Decimal>) -> String {
+    v.map_or_else(|| NONE.to_owned(), |v| format!("{v:.2}"))
+}
+
+/// Renders bids and asks as two aligned columns in a code block, best prices at the top, with the
+/// last trade price between them
+fn into_columns(book: &Book) -> String {
+    let cell = |level: Option<&BookLevel>, bid: bool| {
+        level.map_or_else(String::new, |level| {
+            if bid {
+                format!("({}) {} @ {:.2}", level.orders, level.shares, level.price)
+            } else {
+                format!("{:.2} @ {} ({})", level.price, level.shares, level.orders)
+            }
+        })
+    };
+
+    let rows = book.bids.len().max(book.asks.len());
+    let bids: Vec<_> = (0..rows).map(|i| cell(book.bids.get(i), true)).collect();
+    let asks: Vec<_> = (0..rows).map(|i| cell(book.asks.get(i), false)).collect();
+    let last = display(book.last_price);
+
+    let bid_w = bids
+        .iter()
+        .map(String::len)
+        .max()
+        .unwrap_or_default()
+        .max(4);
+    let last_w = last.len().max(4);
+
+    let mut buff = String::from("```\n");
+
+    writeln!(buff, "{:>bid_w$} │ {:^last_w$} │ Asks", "Bids", "Last").expect("Never fails");
+    for (i, (bid, ask)) in bids.iter().zip(&asks).enumerate() {
+        let middle = if i == 0 { last.as_str() } else { "" };
+        writeln!(buff, "{bid:>bid_w$} │ {middle:^last_w$} │ {ask}").expect("Never fails");
+    }
+
+    buff.push_str("```");
+
+    buff
+}
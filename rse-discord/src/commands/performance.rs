@@ -0,0 +1,227 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+use chrono::{NaiveDate, TimeDelta};
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed, Timestamp},
+};
+use rse_core::{
+    display::{format_kromer, format_signed},
+    error::Error as RscError,
+    model::{PortfolioSnapshot, link::Identity},
+    repo::StockRepository,
+};
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+
+use crate::{
+    Context, Error,
+    i18n::{self, t},
+};
+
+/// The most points a chart is drawn with. Longer ranges are squeezed to fit
+const CHART_WIDTH: i64 = 30;
+
+/// The bars a chart is drawn with, lowest first
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Drawn for stretches of days without a snapshot, such as while the exchange was down
+const GAP: char = '·';
+
+/// How far back to look
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum RangeChoice {
+    /// The last 7 days
+    Week,
+    /// The last 30 days
+    Month,
+    /// The last 90 days
+    Quarter,
+    /// The last 365 days
+    Year,
+}
+
+impl RangeChoice {
+    const fn days(self) -> i64 {
+        match self {
+            Self::Week => 7,
+            Self::Month => 30,
+            Self::Quarter => 90,
+            Self::Year => 365,
+        }
+    }
+}
+
+/// See how your net worth has changed, day by day
+#[poise::command(slash_command, ephemeral)]
+pub async fn performance<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "How far back to look, a month by default"] range: Option<RangeChoice>,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+
+    let user_id = match stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await
+    {
+        Ok(id) => id,
+        Err(RscError::UserNotFound { .. }) => {
+            let reply = CreateReply::default().embed(
+                CreateEmbed::new()
+                    .title(t!(locale, "error.title"))
+                    .description(t!(locale, "me.no_account"))
+                    .color(Color::RED),
+            );
+            send_reply(ctx, reply).await?;
+
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let days = range.unwrap_or(RangeChoice::Month).days();
+    let since = stock_service.now().date_naive() - TimeDelta::days(days);
+    let history = stock_service.portfolio_history(&user_id, since).await?;
+
+    let embed = performance_embed(&history, locale)
+        .color(Color::BLITZ_BLUE)
+        .timestamp(Timestamp::now());
+    send_reply(ctx, CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Charts `history` with how much the net worth changed over it. Empty until the first snapshot,
+/// which is taken the night after an account registers
+fn performance_embed(history: &[PortfolioSnapshot], locale: &str) -> CreateEmbed {
+    let embed = CreateEmbed::new().title(t!(locale, "performance.title"));
+
+    let (Some(first), Some(last)) = (history.first(), history.last()) else {
+        return embed.description(t!(locale, "performance.empty"));
+    };
+
+    let (start, end) = (first.net_worth(), last.net_worth());
+    let change = end - start;
+    let change = if start.is_zero() {
+        format_signed(change)
+    } else {
+        let pct = change / start * Decimal::ONE_HUNDRED;
+        format!("{} ({pct:+.2}%)", format_signed(change))
+    };
+
+    embed
+        .description(format!("```\n{}\n```", chart(history)))
+        .field(
+            t!(locale, "performance.start"),
+            t!(
+                locale,
+                "performance.value_on",
+                value = format_kromer(start),
+                date = first.date
+            ),
+            true,
+        )
+        .field(
+            t!(locale, "performance.latest"),
+            t!(
+                locale,
+                "performance.value_on",
+                value = format_kromer(end),
+                date = last.date
+            ),
+            true,
+        )
+        .field(t!(locale, "performance.change"), change, true)
+}
+
+/// Draws `history`'s net worth as a line of bars from its first day to its last. Each bar covers
+/// an equal stretch of days and shows the last snapshot in it, or a gap if there was none.
+fn chart(history: &[PortfolioSnapshot]) -> String {
+    let (Some(first), Some(last)) = (history.first(), history.last()) else {
+        return String::new();
+    };
+
+    let days = (last.date - first.date).num_days() + 1;
+    let width = days.min(CHART_WIDTH);
+    let bucket = |date: NaiveDate| (date - first.date).num_days() * width / days;
+
+    let mut points = vec![None; usize::try_from(width).unwrap_or_default()];
+    for snapshot in history {
+        if let Some(point) = usize::try_from(bucket(snapshot.date))
+            .ok()
+            .and_then(|bucket| points.get_mut(bucket))
+        {
+            *point = Some(snapshot.net_worth());
+        }
+    }
+
+    let min = points.iter().flatten().min().copied().unwrap_or_default();
+    let max = points.iter().flatten().max().copied().unwrap_or_default();
+    let top = BARS.len() - 1;
+
+    points
+        .into_iter()
+        .map(|point| match point {
+            None => GAP,
+            // A flat line sits in the middle
+            Some(_) if max == min => BARS[top / 2],
+            Some(value) => {
+                let level = (value - min) / (max - min) * Decimal::from(top);
+                BARS[level.round().to_usize().unwrap_or_default().min(top)]
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(day: u32, net_worth: i64) -> PortfolioSnapshot {
+        PortfolioSnapshot {
+            date: NaiveDate::from_ymd_opt(2025, 1, day).expect("Valid date"),
+            balance: Decimal::from(net_worth),
+            holdings_value: Decimal::ZERO,
+        }
+    }
+
+    #[test]
+    fn missing_days_are_gaps() {
+        let history = [snapshot(1, 0), snapshot(2, 70), snapshot(5, 35)];
+
+        assert_eq!(chart(&history), "▁█··▅");
+    }
+
+    #[test]
+    fn flat_lines_sit_in_the_middle() {
+        assert_eq!(chart(&[snapshot(1, 5), snapshot(2, 5)]), "▄▄");
+        assert_eq!(chart(&[snapshot(1, 5)]), "▄");
+        assert_eq!(chart(&[]), "");
+    }
+
+    #[test]
+    fn long_ranges_are_squeezed() {
+        let history: Vec<_> = (1..=31).map(|day| snapshot(day, i64::from(day))).collect();
+        let chart = chart(&history);
+
+        assert_eq!(chart.chars().count(), 30);
+        assert!(chart.starts_with('▁'));
+        assert!(chart.ends_with('█'));
+        assert!(!chart.contains(GAP));
+    }
+}
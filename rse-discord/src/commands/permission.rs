@@ -0,0 +1,216 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Who may run privileged commands. Configured admins may do everything anywhere, and each server
+//! decides what members of its roles may do
+
+use std::collections::BTreeSet;
+
+use poise::serenity_prelude::RoleId;
+use rse_core::{
+    model::guild::{GuildSettings, Permission},
+    repo::StockRepository,
+};
+use snafu::ensure;
+
+use crate::{
+    Context, Error,
+    error::{ForbiddenSnafu, MissingPermissionSnafu},
+};
+
+/// Works out what a member holding `roles` may do in a server with `settings`. `listed` is whether
+/// they are a configured admin, who may do everything
+fn resolve(listed: bool, settings: &GuildSettings, roles: &[RoleId]) -> BTreeSet<Permission> {
+    let is_admin = settings
+        .admin_role
+        .is_some_and(|role| roles.contains(&RoleId::from(role)));
+
+    if listed || is_admin {
+        return Permission::ALL.into();
+    }
+
+    settings
+        .role_permissions
+        .iter()
+        .filter(|(role, _)| roles.contains(&RoleId::from(**role)))
+        .flat_map(|(_, permissions)| permissions.iter().copied())
+        .collect()
+}
+
+/// Everything the invoking user may do where they invoked the command. Outside servers, only
+/// configured admins may do anything
+pub(crate) async fn permissions<R: StockRepository>(
+    ctx: Context<'_, R>,
+) -> Result<BTreeSet<Permission>, Error> {
+    let listed = ctx.data().is_admin(ctx.author().id);
+
+    let (settings, roles) = match ctx.guild_id() {
+        Some(guild) if !listed => (
+            ctx.data().service().guild_settings(guild.into()).await?,
+            ctx.author_member()
+                .await
+                .map(|member| member.roles.clone())
+                .unwrap_or_default(),
+        ),
+        _ => (GuildSettings::default(), Vec::new()),
+    };
+
+    Ok(resolve(listed, &settings, &roles))
+}
+
+/// Whether the invoking user may do what `permission` allows
+pub(crate) async fn has<R: StockRepository>(
+    ctx: Context<'_, R>,
+    permission: Permission,
+) -> Result<bool, Error> {
+    Ok(permissions(ctx).await?.contains(&permission))
+}
+
+/// Fails with an error naming `permission` unless the invoking user holds it. Commands declare
+/// theirs through one of the checks below, which poise can't pass arguments to
+pub(crate) async fn require<R: StockRepository>(
+    ctx: Context<'_, R>,
+    permission: Permission,
+) -> Result<bool, Error> {
+    ensure!(
+        has(ctx, permission).await?,
+        MissingPermissionSnafu { permission }
+    );
+
+    Ok(true)
+}
+
+/// Poise check for [`Permission::ViewAdmin`]
+pub(crate) async fn can_view_admin<R: StockRepository>(ctx: Context<'_, R>) -> Result<bool, Error> {
+    require(ctx, Permission::ViewAdmin).await
+}
+
+/// Poise check for [`Permission::ManageBalances`]
+pub(crate) async fn can_manage_balances<R: StockRepository>(
+    ctx: Context<'_, R>,
+) -> Result<bool, Error> {
+    require(ctx, Permission::ManageBalances).await
+}
+
+/// Poise check for [`Permission::ManageStocks`]
+pub(crate) async fn can_manage_stocks<R: StockRepository>(
+    ctx: Context<'_, R>,
+) -> Result<bool, Error> {
+    require(ctx, Permission::ManageStocks).await
+}
+
+/// Poise check for [`Permission::Halt`]
+pub(crate) async fn can_halt<R: StockRepository>(ctx: Context<'_, R>) -> Result<bool, Error> {
+    require(ctx, Permission::Halt).await
+}
+
+/// Poise check passing anyone with at least one permission, for commands grouping others that
+/// check their own
+pub(crate) async fn is_staff<R: StockRepository>(ctx: Context<'_, R>) -> Result<bool, Error> {
+    ensure!(
+        !permissions(ctx).await?.is_empty(),
+        ForbiddenSnafu {
+            reason: "Only admins can run these commands",
+        }
+    );
+
+    Ok(true)
+}
+
+/// Poise check passing only those with every permission, such as for handing permissions out
+pub(crate) async fn is_admin<R: StockRepository>(ctx: Context<'_, R>) -> Result<bool, Error> {
+    let held = permissions(ctx).await?;
+
+    match Permission::ALL
+        .into_iter()
+        .find(|permission| !held.contains(permission))
+    {
+        Some(permission) => MissingPermissionSnafu { permission }.fail(),
+        None => Ok(true),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::{BTreeMap, BTreeSet},
+        num::NonZeroU64,
+    };
+
+    use super::*;
+
+    const ADMINS: u64 = 1;
+    const BROKERS: u64 = 2;
+    const HALTERS: u64 = 3;
+    const EVERYONE: u64 = 4;
+
+    fn settings() -> GuildSettings {
+        GuildSettings {
+            admin_role: NonZeroU64::new(ADMINS),
+            role_permissions: BTreeMap::from([
+                (
+                    NonZeroU64::new(BROKERS).expect("Non-zero"),
+                    BTreeSet::from([Permission::ViewAdmin, Permission::Halt]),
+                ),
+                (
+                    NonZeroU64::new(HALTERS).expect("Non-zero"),
+                    BTreeSet::from([Permission::Halt]),
+                ),
+            ]),
+            ..GuildSettings::default()
+        }
+    }
+
+    fn roles(ids: &[u64]) -> Vec<RoleId> {
+        ids.iter().copied().map(RoleId::new).collect()
+    }
+
+    #[test]
+    fn configured_admins_may_do_everything_without_roles() {
+        assert_eq!(
+            resolve(true, &GuildSettings::default(), &[]),
+            BTreeSet::from(Permission::ALL)
+        );
+    }
+
+    #[test]
+    fn the_admin_role_grants_everything() {
+        assert_eq!(
+            resolve(false, &settings(), &roles(&[EVERYONE, ADMINS])),
+            BTreeSet::from(Permission::ALL)
+        );
+    }
+
+    #[test]
+    fn granted_roles_add_up() {
+        assert_eq!(
+            resolve(false, &settings(), &roles(&[HALTERS])),
+            BTreeSet::from([Permission::Halt])
+        );
+        assert_eq!(
+            resolve(false, &settings(), &roles(&[HALTERS, BROKERS])),
+            BTreeSet::from([Permission::ViewAdmin, Permission::Halt])
+        );
+    }
+
+    #[test]
+    fn members_without_granted_roles_may_do_nothing() {
+        assert!(resolve(false, &settings(), &roles(&[EVERYONE])).is_empty());
+        assert!(resolve(false, &GuildSettings::default(), &roles(&[ADMINS])).is_empty());
+    }
+}
@@ -20,143 +20,254 @@ use poise::{
     CreateReply, send_reply,
     serenity_prelude::{
         Color, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter,
-        CreateInteractionResponse, CreateInteractionResponseFollowup,
-        CreateInteractionResponseMessage, User,
+        CreateInteractionResponse, CreateInteractionResponseMessage, User,
     },
 };
 use rse_core::{
-    model::{Pager, ticker::Ticker},
+    Service,
+    display::{format_amount, format_kromer},
+    error::Error as RscError,
+    model::UserInfo,
+    model::link::Identity,
+    model::{HoldingOrdering, HoldingPl, Pager, Price, Shares, guild::Permission, ticker::Ticker},
     repo::StockRepository,
 };
-use std::{fmt::Write, ops::Rem};
+use rust_decimal::Decimal;
+use snafu::{ResultExt, ensure};
+use std::fmt::Write;
+use uuid::Uuid;
 
-use crate::{Context, Error};
+use crate::{
+    Context, Error,
+    commands::{
+        budget::{EmbedBudget, MAX_FIELD_VALUE},
+        permission::has,
+        presses::{PageCursor, Presses},
+    },
+    error::{ForbiddenSnafu, InvalidOptionsSnafu, InvalidUuidSnafu},
+    i18n::{self, t},
+};
+
+/// What to sort holdings by
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum SortChoice {
+    /// Alphabetically by ticker
+    Ticker,
+    /// Most shares first
+    Shares,
+    /// Most valuable first
+    Value,
+}
+
+impl From<SortChoice> for HoldingOrdering {
+    fn from(value: SortChoice) -> Self {
+        match value {
+            SortChoice::Ticker => Self::Ticker,
+            SortChoice::Shares => Self::Shares,
+            SortChoice::Value => Self::Value,
+        }
+    }
+}
 
 #[poise::command(slash_command, ephemeral)]
 #[allow(clippy::too_many_lines)]
 pub async fn portfolio<R: StockRepository>(
     ctx: Context<'_, R>,
     #[description = "Selected user"] user: Option<User>,
+    #[description = "Internal account ID to look up instead of a user"] uuid: Option<String>,
+    #[description = "Show cost basis and profit/loss for each holding"] detailed: Option<bool>,
+    #[description = "What to sort holdings by"] sort: Option<SortChoice>,
+    #[description = "Show the reply to everyone in the channel, if the user allows it"]
+    public: Option<bool>,
 ) -> Result<(), Error> {
-    const PAGE_SIZE: i64 = 16;
-    let stock_service = ctx.data();
-    let user = user.unwrap_or(ctx.author().clone());
-    let user_id = ctx.data().disc_to_id(user.id.into()).await?;
+    const PAGE_SIZE: u64 = 16;
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+    let detailed = detailed.unwrap_or_default();
+    let public = public.unwrap_or_default();
+    let order = sort.map(HoldingOrdering::from).unwrap_or_default();
+
+    let identity = match (&user, uuid) {
+        (Some(user), None) => Identity::Discord(user.id.into()),
+        (None, Some(input)) => {
+            Identity::Internal(Uuid::parse_str(input.trim()).context(InvalidUuidSnafu { input })?)
+        }
+        _ => {
+            return InvalidOptionsSnafu {
+                reason: "Provide exactly one of `user` or `uuid`",
+            }
+            .fail();
+        }
+    };
+    let user_id = stock_service.resolve(&identity).await?;
+    let viewer = viewer(ctx, &user_id).await?;
     let ctx_id = ctx.id();
 
     let prev_button_id = format!("{ctx_id}prev");
     let next_button_id = format!("{ctx_id}next");
 
-    let mut page = Pager::new(0, PAGE_SIZE);
+    let first = Pager::new(0, PAGE_SIZE.cast_signed());
 
-    let ((holdings, num_entries), info) = tokio::try_join!(
-        stock_service.get_holdings(&user_id, &page),
-        stock_service.get_account_info(&user_id)
-    )?;
-
-    let mut current_page: i64 = 0;
-    let total_pages = num_entries / PAGE_SIZE + num_entries.rem(PAGE_SIZE).clamp(0, 1);
-
-    // Fucking serenity will make me clone this every time because it doesn't like references :(
-    let reply_embed = CreateEmbed::new()
-        .color(Color::BLITZ_BLUE)
-        .thumbnail(user.avatar_url().unwrap_or_default())
-        .author(CreateEmbedAuthor::new(user.display_name()))
-        .field("Balance", info.balance.to_string(), true)
-        .field(
-            "Created",
-            info.created_at.format("%Y-%m-%d %H:%M").to_string(),
-            true,
-        )
-        .description(into_page(&holdings));
-
-    match total_pages {
-        0 => {
-            send_reply(
-                ctx,
-                CreateReply::default().embed(
-                    reply_embed
-                        .field(
-                            "Holdings",
-                            "This user has not purchased any stocks yet",
-                            false,
-                        )
-                        .footer(CreateEmbedFooter::new(user_id)),
-                ),
-            )
-            .await?;
+    let ((holdings, num_entries), info, holdings_value) = match tokio::try_join!(
+        holdings_page(
+            stock_service,
+            &user_id,
+            &first,
+            order,
+            detailed,
+            viewer.as_ref(),
+            locale
+        ),
+        stock_service.get_account_info(&user_id, viewer.as_ref()),
+        stock_service.get_holdings_value(&user_id, viewer.as_ref())
+    ) {
+        Ok(res) => res,
+        Err(RscError::PrivateAccount) => {
+            let reply = CreateReply::default().embed(
+                CreateEmbed::new()
+                    .title(t!(locale, "portfolio.private_title"))
+                    .description(t!(locale, "portfolio.private"))
+                    .color(Color::DARK_GREY),
+            );
+            send_reply(ctx, reply).await?;
 
             return Ok(());
         }
-        1 => {
-            send_reply(
-                ctx,
-                CreateReply::default().embed(
-                    reply_embed
-                        .field("Holdings", into_page(&holdings), false)
-                        .footer(CreateEmbedFooter::new(user_id)),
-                ),
-            )
-            .await?;
+        Err(err) => return Err(err.into()),
+    };
 
-            return Ok(());
+    // Anyone may show their own portfolio, but someone else's only if they opted in
+    let own = match &user {
+        Some(user) => user.id == ctx.author().id,
+        None => info.disc_id == Some(ctx.author().id.into()),
+    };
+
+    ensure!(
+        !public || own || info.public_portfolio,
+        ForbiddenSnafu {
+            reason: "This user's portfolio is private. They can make it public with `/privacy`",
         }
-        _ => {
-            let components = CreateActionRow::Buttons(vec![
-                CreateButton::new(&prev_button_id).emoji('◀'),
-                CreateButton::new(&next_button_id).emoji('▶'),
-            ]);
-
-            send_reply(
-                ctx,
-                CreateReply::default()
-                    .embed(
-                        reply_embed
-                            .clone()
-                            .field("Holdings", into_page(&holdings), false)
-                            .footer(CreateEmbedFooter::new(format!(
-                                "Page: {}/{} - {user_id}",
-                                current_page + 1,
-                                total_pages + 1
-                            ))),
+    );
+
+    let mut cursor = PageCursor::new(num_entries, PAGE_SIZE);
+
+    let (reply_embed, label) = header(user.as_ref(), &info, locale);
+
+    // Fucking serenity will make me clone this every time because it doesn't like references :(
+    let reply_embed = account_fields(
+        reply_embed.color(Color::BLITZ_BLUE),
+        &info,
+        holdings_value,
+        locale,
+    );
+
+    if num_entries == 0 {
+        send_reply(
+            ctx,
+            CreateReply::default().ephemeral(!public).embed(
+                reply_embed
+                    .field(
+                        t!(locale, "portfolio.holdings"),
+                        t!(locale, "portfolio.no_holdings"),
+                        false,
                     )
-                    .components(vec![components]),
-            )
-            .await?;
-        }
+                    .footer(CreateEmbedFooter::new(&label)),
+            ),
+        )
+        .await?;
+
+        return Ok(());
     }
 
-    while let Some(press) =
-        poise::serenity_prelude::collector::ComponentInteractionCollector::new(ctx)
-            .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
-            .timeout(std::time::Duration::from_secs(1800))
-            .await
-    {
+    if cursor.pages() == 1 {
+        send_reply(
+            ctx,
+            CreateReply::default().ephemeral(!public).embed(
+                reply_embed
+                    .field(t!(locale, "portfolio.holdings"), holdings, false)
+                    .footer(CreateEmbedFooter::new(&label)),
+            ),
+        )
+        .await?;
+
+        return Ok(());
+    }
+
+    let components = CreateActionRow::Buttons(vec![
+        CreateButton::new(&prev_button_id).emoji('◀'),
+        CreateButton::new(&next_button_id).emoji('▶'),
+    ]);
+
+    send_reply(
+        ctx,
+        CreateReply::default()
+            .ephemeral(!public)
+            .embed(
+                reply_embed
+                    .clone()
+                    .field(t!(locale, "portfolio.holdings"), holdings, false)
+                    .footer(CreateEmbedFooter::new(t!(
+                        locale,
+                        "portfolio.page",
+                        page = cursor.number(),
+                        pages = cursor.pages(),
+                        label = label
+                    ))),
+            )
+            .components(vec![components]),
+    )
+    .await?;
+
+    let mut presses = Presses::new(ctx);
+
+    while let Some(press) = presses.next().await {
         tracing::info!("Pressed! {}", press.data.custom_id);
         if press.data.custom_id == prev_button_id {
-            current_page = current_page.checked_sub(1).unwrap_or(total_pages);
+            cursor.prev();
         } else if press.data.custom_id == next_button_id {
-            current_page += 1;
-            current_page %= total_pages + 1;
+            cursor.next();
         } else {
             // Unrelated interaction
             continue;
         }
 
-        page.set_offset(current_page * PAGE_SIZE);
+        // Holdings may have changed since the last page, in which case the page may have to move
+        let holdings = loop {
+            match holdings_page(
+                stock_service,
+                &user_id,
+                &cursor.pager(),
+                order,
+                detailed,
+                viewer.as_ref(),
+                locale,
+            )
+            .await
+            {
+                Ok((holdings, entries)) if !cursor.sync(entries) => break holdings,
+                Ok(_) => {}
+                Err(RscError::UserNotFound { .. }) => {
+                    presses.finish(&press, t!(locale, "portfolio.gone")).await?;
+                    return Ok(());
+                }
+                Err(err) => return Err(err.into()),
+            }
+        };
+
+        let holdings = if holdings.is_empty() {
+            t!(locale, "portfolio.no_holdings")
+        } else {
+            holdings
+        };
 
-        let (holdings, new_entries) = stock_service.get_holdings(&user_id, &page).await?;
+        let footer = t!(
+            locale,
+            "portfolio.page",
+            page = cursor.number(),
+            pages = cursor.pages(),
+            label = label
+        );
 
-        if new_entries != num_entries {
-            press
-                .create_followup(
-                    ctx.serenity_context(),
-                    CreateInteractionResponseFollowup::new()
-                        .content("User's holdings have changed, please call again"),
-                )
-                .await?;
-            break;
-        }
         press
             .create_response(
                 ctx.serenity_context(),
@@ -164,26 +275,280 @@ pub async fn portfolio<R: StockRepository>(
                     CreateInteractionResponseMessage::new().embed(
                         reply_embed
                             .clone()
-                            .footer(CreateEmbedFooter::new(format!(
-                                "Page: {}/{} - {user_id}",
-                                current_page + 1,
-                                total_pages + 1
-                            )))
-                            .field("Holdings", into_page(&holdings), false),
+                            .footer(CreateEmbedFooter::new(cursor.footer(footer, locale)))
+                            .field(t!(locale, "portfolio.holdings"), holdings, false),
                     ),
                 ),
             )
             .await?;
     }
+
+    presses.expire(locale).await;
     Ok(())
 }
 
-fn into_page(v: &[(Ticker, u32)]) -> String {
-    let mut buff = String::new();
+/// Starts the embed off with who the portfolio belongs to, returning it alongside a label for its
+/// footer. Accounts looked up by ID are shown with the Discord account linked to them, if any.
+fn header(user: Option<&User>, info: &UserInfo, locale: &str) -> (CreateEmbed, String) {
+    match user {
+        Some(user) => (
+            CreateEmbed::new()
+                .thumbnail(user.avatar_url().unwrap_or_default())
+                .author(CreateEmbedAuthor::new(user.display_name())),
+            user.tag(),
+        ),
+        None => (
+            CreateEmbed::new()
+                .author(CreateEmbedAuthor::new(info.id.to_string()))
+                .description(info.disc_id.map_or_else(
+                    || t!(locale, "portfolio.not_linked"),
+                    |disc_id| t!(locale, "portfolio.linked", user = disc_id),
+                )),
+            t!(locale, "portfolio.by_account_id"),
+        ),
+    }
+}
+
+/// Adds the account's balances, creation date and total value to `embed`. Whatever the account's
+/// privacy hid from the viewer is shown as hidden, with the creation date going along with the
+/// balance
+fn account_fields(
+    embed: CreateEmbed,
+    info: &UserInfo,
+    holdings_value: Decimal,
+    locale: &str,
+) -> CreateEmbed {
+    let hidden = || t!(locale, "portfolio.hidden");
+
+    embed
+        .field(
+            t!(locale, "portfolio.available"),
+            info.available_balance.map_or_else(hidden, format_kromer),
+            true,
+        )
+        .field(
+            t!(locale, "portfolio.on_hold"),
+            info.held_balance.map_or_else(hidden, format_kromer),
+            true,
+        )
+        .field(
+            t!(locale, "portfolio.created"),
+            info.balance.map_or_else(hidden, |_| {
+                info.created_at.format("%Y-%m-%d %H:%M").to_string()
+            }),
+            true,
+        )
+        .field(
+            t!(locale, "portfolio.total_value"),
+            info.balance
+                .map_or_else(hidden, |b| format_kromer(b + holdings_value)),
+            true,
+        )
+}
+
+/// Fetches and renders a page of holdings, returning it alongside the total number of holdings
+async fn holdings_page<R: StockRepository>(
+    service: &Service<R>,
+    user_id: &Uuid,
+    page: &Pager,
+    order: HoldingOrdering,
+    detailed: bool,
+    viewer: Option<&Uuid>,
+    locale: &str,
+) -> Result<(String, u64), RscError> {
+    if detailed {
+        let holdings = service
+            .get_holdings_pl(user_id, page, order, viewer)
+            .await?;
+        Ok((into_detailed_page(&holdings.items, locale), holdings.total))
+    } else {
+        let holdings = service.get_holdings(user_id, page, order, viewer).await?;
+        Ok((into_page(&holdings.items, locale), holdings.total))
+    }
+}
+
+/// Works out who is looking at `user_id`'s portfolio. Admins see it as its owner would, and
+/// anyone without an account as a stranger
+async fn viewer<R: StockRepository>(
+    ctx: Context<'_, R>,
+    user_id: &Uuid,
+) -> Result<Option<Uuid>, Error> {
+    if has(ctx, Permission::ViewAdmin).await? {
+        return Ok(Some(*user_id));
+    }
+
+    match ctx
+        .data()
+        .service()
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await
+    {
+        Ok(id) => Ok(Some(id)),
+        Err(RscError::UserNotFound { .. }) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+const UNKNOWN: &str = "—";
+
+/// Renders rows in a code block, right-aligning every column after the first so they line up.
+/// `seps` are placed before each column after the first. Rows that would overflow the holdings
+/// field are left out.
+fn into_table<const N: usize>(rows: &[[String; N]], seps: [&str; N], locale: &str) -> String {
+    let mut widths = [0; N];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut lines = Vec::with_capacity(rows.len());
+
+    for row in rows {
+        let mut buff = String::new();
+
+        for (i, cell) in row.iter().enumerate() {
+            let width = widths[i];
+            if i == 0 {
+                write!(buff, "{cell:<width$}").expect("Never fails");
+            } else {
+                write!(buff, "{}{cell:>width$}", seps[i]).expect("Never fails");
+            }
+        }
+        lines.push(buff);
+    }
+
+    EmbedBudget::new(locale).lines(MAX_FIELD_VALUE, "```\n", &lines, "```")
+}
+
+/// Renders holdings as `$ABC — 40 sh @ 12.50 = 500.00`
+fn into_page(v: &[(Ticker, Shares, Option<Price>)], locale: &str) -> String {
+    let rows: Vec<_> = v
+        .iter()
+        .map(|(ticker, shares, price)| {
+            [
+                format!("${ticker}"),
+                shares.to_string(),
+                price.map_or_else(|| UNKNOWN.to_owned(), |price| format_amount(price.get())),
+                price.map_or_else(
+                    || UNKNOWN.to_owned(),
+                    |price| format_amount(price.notional(*shares)),
+                ),
+            ]
+        })
+        .collect();
+
+    into_table(&rows, ["", " — ", " sh @ ", " = "], locale)
+}
 
-    for (ticker, shares) in v {
-        writeln!(buff, "${}: {}", ticker.as_str(), shares).expect("Never fails");
+/// Renders holdings as `$ABC — 40 sh, avg 10.00, now 12.50, P/L +100.00`. Unknown cost bases are
+/// shown as `n/a`
+fn into_detailed_page(v: &[HoldingPl], locale: &str) -> String {
+    let rows: Vec<_> = v
+        .iter()
+        .map(|holding| {
+            [
+                format!("${}", holding.ticker),
+                holding.shares.to_string(),
+                holding
+                    .avg_cost
+                    .map_or_else(|| "n/a".to_owned(), format_amount),
+                holding
+                    .price
+                    .map_or_else(|| UNKNOWN.to_owned(), |price| format_amount(price.get())),
+                holding.unrealized_pl().map_or_else(
+                    || "n/a".to_owned(),
+                    |pl| {
+                        if pl.is_sign_negative() {
+                            format_amount(pl)
+                        } else {
+                            format!("+{}", format_amount(pl))
+                        }
+                    },
+                ),
+            ]
+        })
+        .collect();
+
+    into_table(&rows, ["", " — ", " sh, avg ", ", now ", ", P/L "], locale)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use rse_core::model::Privacy;
+
+    use super::*;
+
+    fn account(privacy: Privacy) -> UserInfo {
+        UserInfo {
+            id: Uuid::from_u128(1),
+            balance: Some(Decimal::from(100)),
+            available_balance: Some(Decimal::from(60)),
+            held_balance: Some(Decimal::from(40)),
+            created_at: Utc::now(),
+            mc_id: None,
+            disc_id: None,
+            privacy,
+            public_portfolio: false,
+            closed_at: None,
+        }
+    }
+
+    #[test]
+    fn strangers_only_see_public_balances() {
+        let owner = Uuid::from_u128(1);
+        let stranger = Uuid::from_u128(2);
+
+        for (privacy, viewer, shown) in [
+            (Privacy::Public, Some(owner), true),
+            (Privacy::Public, Some(stranger), true),
+            (Privacy::Public, None, true),
+            (Privacy::HoldingsOnly, Some(owner), true),
+            (Privacy::HoldingsOnly, Some(stranger), false),
+            (Privacy::HoldingsOnly, None, false),
+            (Privacy::Private, Some(owner), true),
+            (Privacy::Private, Some(stranger), false),
+            (Privacy::Private, None, false),
+        ] {
+            let mut info = account(privacy);
+            info.hide_from(viewer.as_ref());
+
+            let embed = account_fields(CreateEmbed::new(), &info, Decimal::from(25), "en");
+            let embed = serde_json::to_value(embed).expect("Serializes");
+            let values: Vec<_> = (0..4)
+                .map(|i| {
+                    embed["fields"][i]["value"]
+                        .as_str()
+                        .expect("Set")
+                        .to_owned()
+                })
+                .collect();
+
+            for value in &values {
+                assert_eq!(value == "Hidden", !shown, "{privacy:?} seen by {viewer:?}");
+            }
+            if shown {
+                assert_eq!(values[3], "125.00 KRO");
+            }
+        }
     }
 
-    buff
+    #[test]
+    fn long_pages_fit_in_a_field() {
+        let holdings: Vec<_> = (0..100)
+            .map(|_| {
+                (
+                    Ticker::try_from("ABCDE").expect("Valid ticker"),
+                    Shares::new(1_000_000).expect("Valid shares"),
+                    Some(Price::new(Decimal::new(123_456_789, 2)).expect("Valid price")),
+                )
+            })
+            .collect();
+
+        let page = into_page(&holdings, "en");
+
+        assert!(page.chars().count() <= MAX_FIELD_VALUE);
+        assert!(page.ends_with("more (next page)"));
+    }
 }
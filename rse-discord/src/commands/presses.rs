@@ -0,0 +1,267 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Button presses on paginated replies, and which page they have moved to
+
+use std::{sync::Arc, time::Duration};
+
+use futures_util::{FutureExt, StreamExt, stream::BoxStream};
+use poise::serenity_prelude::{
+    ComponentInteraction, CreateInteractionResponse, CreateInteractionResponseMessage,
+    EditInteractionResponse, Http, collector::ComponentInteractionCollector,
+};
+use rse_core::{model::Pager, repo::StockRepository};
+
+use crate::{Context, i18n::t};
+
+/// How long a paginated reply waits for its next button press before it stops listening. Kept
+/// under the 15 minutes Discord lets an interaction's reply be edited for, so the buttons can
+/// still be taken down once it expires
+const IDLE_TIMEOUT: Duration = Duration::from_mins(10);
+
+/// The button presses on a single paginated reply, public or ephemeral. Only the invoker's presses
+/// are collected. Presses made while the previous one was still being handled are acknowledged and
+/// dropped, so mashing a button renders one page rather than one per press.
+///
+/// Pages are only ever changed by editing the reply, so it keeps the visibility it was sent with.
+pub(crate) struct Presses {
+    stream: BoxStream<'static, ComponentInteraction>,
+    http: Arc<Http>,
+    /// The token of the latest interaction whose response is the paginated reply
+    token: Option<String>,
+}
+
+impl Presses {
+    /// Starts listening for presses on the buttons of the reply to `ctx`
+    pub fn new<R: StockRepository>(ctx: Context<'_, R>) -> Self {
+        let prefix = ctx.id().to_string();
+        let stream = ComponentInteractionCollector::new(ctx)
+            .author_id(ctx.author().id)
+            .filter(move |press| press.data.custom_id.starts_with(&prefix))
+            .stream()
+            .boxed();
+
+        let token = match ctx {
+            poise::Context::Application(ctx) => Some(ctx.interaction.token.clone()),
+            poise::Context::Prefix(_) => None,
+        };
+
+        Self {
+            stream,
+            http: ctx.serenity_context().http.clone(),
+            token,
+        }
+    }
+
+    /// Waits for the next press, first dropping any made since the last one was returned. Returns
+    /// `None` once nothing has been pressed for [`IDLE_TIMEOUT`], or as soon as the collector stops,
+    /// which happens when the shard it was listening on restarts. Either way the reply should be
+    /// [expired](Self::expire), as no more presses will arrive.
+    pub async fn next(&mut self) -> Option<ComponentInteraction> {
+        while let Some(Some(stale)) = self.stream.next().now_or_never() {
+            tracing::debug!(
+                "Dropping press {} made while rendering",
+                stale.data.custom_id
+            );
+
+            if let Err(err) = stale
+                .create_response(&self.http, CreateInteractionResponse::Acknowledge)
+                .await
+            {
+                tracing::warn!("Could not acknowledge dropped press: {err}");
+            }
+        }
+
+        let press = match tokio::time::timeout(IDLE_TIMEOUT, self.stream.next()).await {
+            Ok(Some(press)) => press,
+            Ok(None) => {
+                tracing::info!("Press collector closed, likely by a shard restart");
+                return None;
+            }
+            Err(_) => return None,
+        };
+
+        self.token = Some(press.token.clone());
+
+        Some(press)
+    }
+
+    /// Stops paginating in response to `press`, replacing the buttons with `notice`
+    ///
+    /// # Errors
+    /// Returns an error if the reply could not be edited
+    pub async fn finish(
+        self,
+        press: &ComponentInteraction,
+        notice: impl Into<String>,
+    ) -> Result<(), poise::serenity_prelude::Error> {
+        press
+            .create_response(
+                &self.http,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(notice)
+                        .components(Vec::new()),
+                ),
+            )
+            .await
+    }
+
+    /// Takes the buttons down once nobody has pressed them for [`IDLE_TIMEOUT`], saying the
+    /// session expired. Failing to is only logged, as the pages were already shown.
+    pub async fn expire(self, locale: &str) {
+        let Some(token) = self.token else {
+            return;
+        };
+
+        let edit = EditInteractionResponse::new()
+            .content(t!(locale, "pages.expired"))
+            .components(Vec::new());
+
+        if let Err(err) = self
+            .http
+            .edit_original_interaction_response(&token, &edit, Vec::new())
+            .await
+        {
+            tracing::warn!("Could not expire paginated reply: {err}");
+        }
+    }
+}
+
+/// Which page of a paginated reply is shown, for a given number of entries. Moving past either end
+/// wraps around. There is always at least one page, even when there are no entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct PageCursor {
+    page: u64,
+    size: u64,
+    entries: u64,
+    refreshed: bool,
+}
+
+impl PageCursor {
+    /// Starts on the first page of `entries`, split into pages of `size`
+    pub const fn new(entries: u64, size: u64) -> Self {
+        Self {
+            page: 0,
+            size,
+            entries,
+            refreshed: false,
+        }
+    }
+
+    /// The number of pages
+    pub fn pages(&self) -> u64 {
+        self.entries.div_ceil(self.size).max(1)
+    }
+
+    /// The number of the current page, counting from 1 for display
+    pub const fn number(&self) -> u64 {
+        self.page + 1
+    }
+
+    /// A [`Pager`] for the current page
+    pub fn pager(&self) -> Pager {
+        Pager::new(
+            (self.page * self.size).cast_signed(),
+            self.size.cast_signed(),
+        )
+    }
+
+    /// Moves to the previous page
+    pub fn prev(&mut self) {
+        self.refreshed = false;
+        self.page = self.page.checked_sub(1).unwrap_or(self.pages() - 1);
+    }
+
+    /// Moves to the next page
+    pub fn next(&mut self) {
+        self.refreshed = false;
+        self.page = (self.page + 1) % self.pages();
+    }
+
+    /// Takes in the number of entries there were when the current page was fetched. If it
+    /// changed, the pages are recounted and the current page is clamped into them. Returns whether
+    /// that moved the current page, in which case it has to be fetched again.
+    pub fn sync(&mut self, entries: u64) -> bool {
+        if entries == self.entries {
+            return false;
+        }
+
+        self.entries = entries;
+        self.refreshed = true;
+
+        let page = self.page.min(self.pages() - 1);
+        let moved = page != self.page;
+        self.page = page;
+
+        moved
+    }
+
+    /// Appends a note to `footer` saying the data was refreshed, if it was
+    pub fn footer(&self, footer: String, locale: &str) -> String {
+        if self.refreshed {
+            format!("{footer} · {}", t!(locale, "pages.refreshed"))
+        } else {
+            footer
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn there_is_always_a_page() {
+        assert_eq!(PageCursor::new(0, 16).pages(), 1);
+        assert_eq!(PageCursor::new(16, 16).pages(), 1);
+        assert_eq!(PageCursor::new(17, 16).pages(), 2);
+    }
+
+    #[test]
+    fn moves_wrap_around() {
+        let mut cursor = PageCursor::new(40, 16);
+
+        cursor.prev();
+        assert_eq!((cursor.number(), cursor.pager().offset()), (3, 32));
+        cursor.next();
+        assert_eq!((cursor.number(), cursor.pager().offset()), (1, 0));
+    }
+
+    #[test]
+    fn shrinking_clamps_the_current_page() {
+        let mut cursor = PageCursor::new(40, 16);
+        cursor.prev();
+
+        assert!(!cursor.sync(40));
+        assert_eq!(cursor.footer("Page".to_owned(), "en"), "Page");
+
+        assert!(cursor.sync(20));
+        assert_eq!(
+            cursor.footer("Page".to_owned(), "en"),
+            format!("Page · {}", t!("en", "pages.refreshed"))
+        );
+        assert_eq!((cursor.number(), cursor.pages()), (2, 2));
+
+        assert!(!cursor.sync(21));
+        assert_eq!(cursor.number(), 2);
+
+        cursor.next();
+        assert_eq!(cursor.footer("Page".to_owned(), "en"), "Page");
+    }
+}
@@ -0,0 +1,115 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed, Timestamp},
+};
+use rse_core::{
+    model::{Privacy, link::Identity},
+    repo::StockRepository,
+};
+
+use crate::{
+    Context, Error,
+    error::InvalidOptionsSnafu,
+    i18n::{self, t},
+};
+
+/// Who can see an account
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum PrivacyChoice {
+    /// Anyone can see your balance and holdings
+    Public,
+    /// Anyone can see your holdings, but only you your balance
+    #[name = "Holdings only"]
+    HoldingsOnly,
+    /// Only you can see your balance and holdings
+    Private,
+}
+
+impl From<PrivacyChoice> for Privacy {
+    fn from(value: PrivacyChoice) -> Self {
+        match value {
+            PrivacyChoice::Public => Self::Public,
+            PrivacyChoice::HoldingsOnly => Self::HoldingsOnly,
+            PrivacyChoice::Private => Self::Private,
+        }
+    }
+}
+
+/// Choose who can see your account, and whether others can show your portfolio publicly
+#[poise::command(slash_command, ephemeral)]
+pub async fn privacy<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "Who can see your balance and holdings"] account: Option<PrivacyChoice>,
+    #[description = "Let others show your portfolio to everyone in a channel"]
+    public_portfolio: Option<bool>,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+
+    if account.is_none() && public_portfolio.is_none() {
+        return InvalidOptionsSnafu {
+            reason: "Provide at least one of `account` or `public_portfolio`",
+        }
+        .fail();
+    }
+
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+    let mut lines = Vec::new();
+
+    if let Some(account) = account {
+        let privacy = Privacy::from(account);
+        stock_service.set_privacy(&user_id, privacy).await?;
+
+        lines.push(match privacy {
+            Privacy::Public => t!(locale, "privacy.account_public"),
+            Privacy::HoldingsOnly => t!(locale, "privacy.account_holdings_only"),
+            Privacy::Private => t!(locale, "privacy.account_private"),
+        });
+    }
+
+    if let Some(public_portfolio) = public_portfolio {
+        stock_service
+            .set_public_portfolio(&user_id, public_portfolio)
+            .await?;
+
+        lines.push(if public_portfolio {
+            t!(locale, "privacy.public")
+        } else {
+            t!(locale, "privacy.private")
+        });
+    }
+
+    let description = lines.join("\n");
+
+    let reply = CreateReply::default().embed(
+        CreateEmbed::new()
+            .title(t!(locale, "privacy.title"))
+            .description(description)
+            .color(Color::DARK_GREEN)
+            .timestamp(Timestamp::now()),
+    );
+
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
@@ -16,69 +16,54 @@
     along with this program.  If not, see <https://www.gnu.org/licenses/>.
 */
 
-use futures_util::TryFutureExt as FuTryFuturesExt;
 use poise::{
     CreateReply, send_reply,
     serenity_prelude::{Color, CreateEmbed, CreateEmbedAuthor, Timestamp},
 };
-use rse_core::{error::Error as RscError, repo::StockRepository};
-use snafu::futures::TryFutureExt;
+use rse_core::{model::Registered, repo::StockRepository};
+use snafu::ResultExt;
 
-use crate::{Context, Error, error::RegistrationSnafu};
+use crate::{
+    Context, Error,
+    commands::defer_ephemeral_or_log,
+    error::RegistrationSnafu,
+    i18n::{self, t},
+};
 
 #[poise::command(slash_command, ephemeral)]
 pub async fn register<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
-    let stock_service = ctx.data();
-
-    let registered_id = tokio::try_join!(
-        stock_service
-            .register_account(Some(ctx.author().id.into()), None)
-            .context(RegistrationSnafu),
-        ctx.defer().map_err(Error::from)
-    )
-    .map(|(id, ())| id);
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
 
-    match registered_id {
-        Ok(id) => {
-            let reply = CreateReply::default().embed(
-                CreateEmbed::default()
-                    .title("Success!")
-                    .description("Registered your account")
-                    .author(
-                        CreateEmbedAuthor::new(id)
-                            .icon_url(ctx.author().avatar_url().unwrap_or_default()),
-                    )
-                    .timestamp(Timestamp::now())
-                    .color(Color::DARK_GREEN),
-            );
+    defer_ephemeral_or_log(ctx).await;
 
-            ctx.send(reply).await?;
-            Ok(())
-        }
-        Err(Error::RegistrationError {
-            source: RscError::AccountExists,
-        }) => {
-            let id = stock_service.disc_to_id(ctx.author().id.into()).await.ok();
+    let registered = stock_service
+        .register_account(Some(ctx.author().id.into()), None)
+        .await
+        .context(RegistrationSnafu)?;
 
-            let reply = CreateReply::default()
-                .embed(
-                    CreateEmbed::default()
-                        .title("Already exists")
-                        .description("You already have an account")
-                        .author(
-                            CreateEmbedAuthor::new(id.unwrap_or_default())
-                                .icon_url(ctx.author().avatar_url().unwrap_or_default()),
-                        )
-                        .timestamp(Timestamp::now())
-                        .color(Color::BLITZ_BLUE),
-                )
-                .ephemeral(true);
+    let embed = match registered {
+        Registered::New(id) => CreateEmbed::default()
+            .title(t!(locale, "register.success_title"))
+            .description(t!(locale, "register.success"))
+            .author(
+                CreateEmbedAuthor::new(id).icon_url(ctx.author().avatar_url().unwrap_or_default()),
+            )
+            .color(Color::DARK_GREEN),
+        Registered::Existing(id) => CreateEmbed::default()
+            .title(t!(locale, "register.exists_title"))
+            .description(t!(locale, "register.exists"))
+            .author(
+                CreateEmbedAuthor::new(id).icon_url(ctx.author().avatar_url().unwrap_or_default()),
+            )
+            .color(Color::BLITZ_BLUE),
+    };
 
-            send_reply(ctx, reply).await?;
+    send_reply(
+        ctx,
+        CreateReply::default().embed(embed.timestamp(Timestamp::now())),
+    )
+    .await?;
 
-            Ok(())
-        }
-        // Pass on to global error handling
-        Err(error) => Err(error),
-    }
+    Ok(())
 }
@@ -0,0 +1,78 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed, Timestamp},
+};
+use rse_core::{model::link::Identity, repo::StockRepository};
+
+use crate::{
+    Context, Error,
+    i18n::{self, t},
+};
+
+/// Whether a setting is turned on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Toggle {
+    /// Turn it on
+    On,
+    /// Turn it off
+    Off,
+}
+
+/// Change how the bot treats your account
+#[poise::command(slash_command, ephemeral, subcommands("receipts"), subcommand_required)]
+#[allow(clippy::unused_async)] // Poise requires commands to be async
+pub async fn settings<R: StockRepository>(_ctx: Context<'_, R>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Choose whether you get a DM whenever one of your orders fills
+#[poise::command(slash_command, ephemeral)]
+async fn receipts<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "Whether to send you trade receipts"] state: Toggle,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+    let enabled = state == Toggle::On;
+    stock_service.set_receipts(&user_id, enabled).await?;
+
+    let description = if enabled {
+        t!(locale, "settings.receipts_on")
+    } else {
+        t!(locale, "settings.receipts_off")
+    };
+
+    let reply = CreateReply::default().embed(
+        CreateEmbed::new()
+            .title(t!(locale, "settings.title"))
+            .description(description)
+            .color(Color::DARK_GREEN)
+            .timestamp(Timestamp::now()),
+    );
+
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
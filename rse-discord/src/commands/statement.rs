@@ -0,0 +1,274 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{
+        Color, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter,
+        CreateInteractionResponse, CreateInteractionResponseMessage,
+    },
+};
+use rse_core::{
+    model::{LedgerKind, Pager, StatementEntry, link::Identity},
+    repo::StockRepository,
+};
+
+use crate::{
+    Context, Error,
+    commands::{
+        budget::{EmbedBudget, MAX_DESCRIPTION},
+        me::kind_label,
+        presses::{PageCursor, Presses},
+    },
+    i18n::{self, t},
+};
+
+/// Which kind of transactions to list
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum KindChoice {
+    /// Buying and selling shares
+    Trades,
+    /// Kromer granted to you
+    Deposits,
+    /// Dividends paid and received
+    Dividends,
+    /// Fees paid on purchases
+    Fees,
+    /// Withdrawals, and those returned
+    Withdrawals,
+}
+
+impl From<KindChoice> for LedgerKind {
+    fn from(value: KindChoice) -> Self {
+        match value {
+            KindChoice::Trades => Self::Trades,
+            KindChoice::Deposits => Self::Deposits,
+            KindChoice::Dividends => Self::Dividends,
+            KindChoice::Fees => Self::Fees,
+            KindChoice::Withdrawals => Self::Withdrawals,
+        }
+    }
+}
+
+/// See every change to your Kromer, newest first, with your balance after each
+#[poise::command(slash_command, ephemeral)]
+pub async fn statement<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "Only list transactions of this kind"] kind: Option<KindChoice>,
+) -> Result<(), Error> {
+    const PAGE_SIZE: u64 = 10;
+    let ctx_id = ctx.id();
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+    let filter = kind.map(LedgerKind::from);
+
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+
+    let first = stock_service
+        .get_ledger(
+            &user_id,
+            &Pager::new(0, PAGE_SIZE.cast_signed()),
+            filter,
+            None,
+        )
+        .await?;
+    // Every later page is cut off where the first was, so their balances follow on from it
+    let snapshot = Some(first.snapshot);
+    let mut cursor = PageCursor::new(first.page.total, PAGE_SIZE);
+
+    let embed = into_embed(&first.page.items, filter.is_some(), locale);
+
+    if cursor.pages() == 1 {
+        send_reply(ctx, CreateReply::default().embed(embed)).await?;
+        return Ok(());
+    }
+
+    let prev_button_id = format!("{ctx_id}prev");
+    let next_button_id = format!("{ctx_id}next");
+
+    let components = CreateActionRow::Buttons(vec![
+        CreateButton::new(&prev_button_id).emoji('◀'),
+        CreateButton::new(&next_button_id).emoji('▶'),
+    ]);
+
+    send_reply(
+        ctx,
+        CreateReply::default()
+            .embed(embed.footer(CreateEmbedFooter::new(t!(
+                locale,
+                "statement.page",
+                page = cursor.number(),
+                pages = cursor.pages()
+            ))))
+            .components(vec![components]),
+    )
+    .await?;
+
+    let mut presses = Presses::new(ctx);
+
+    while let Some(press) = presses.next().await {
+        if press.data.custom_id == prev_button_id {
+            cursor.prev();
+        } else if press.data.custom_id == next_button_id {
+            cursor.next();
+        } else {
+            // Unrelated interaction
+            continue;
+        }
+
+        let statement = stock_service
+            .get_ledger(&user_id, &cursor.pager(), filter, snapshot)
+            .await?;
+
+        press
+            .create_response(
+                ctx.serenity_context(),
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new().embed(
+                        into_embed(&statement.page.items, filter.is_some(), locale).footer(
+                            CreateEmbedFooter::new(t!(
+                                locale,
+                                "statement.page",
+                                page = cursor.number(),
+                                pages = cursor.pages()
+                            )),
+                        ),
+                    ),
+                ),
+            )
+            .await?;
+    }
+
+    presses.expire(locale).await;
+
+    Ok(())
+}
+
+/// Renders a page of the statement
+fn into_embed(entries: &[StatementEntry], filtered: bool, locale: &str) -> CreateEmbed {
+    let description = if entries.is_empty() {
+        if filtered {
+            t!(locale, "statement.no_match")
+        } else {
+            t!(locale, "statement.empty")
+        }
+    } else {
+        into_table(entries, locale)
+    };
+
+    CreateEmbed::new()
+        .title(t!(locale, "statement.title"))
+        .description(description)
+        .color(Color::BLITZ_BLUE)
+}
+
+/// Lays entries out in a `diff` block, so Discord shows credits in green and debits in red, with
+/// the balance each left behind in the last column
+fn into_table(entries: &[StatementEntry], locale: &str) -> String {
+    let rows: Vec<_> = entries
+        .iter()
+        .map(|entry| {
+            let transaction = &entry.transaction;
+            let sign = if transaction.delta.is_sign_negative() {
+                '-'
+            } else {
+                '+'
+            };
+            let what = match transaction.ticker {
+                Some(ticker) => format!("{} ${ticker}", kind_label(transaction.kind, locale)),
+                None => kind_label(transaction.kind, locale),
+            };
+
+            (
+                sign,
+                format!("{:.2}", transaction.delta.abs()),
+                what,
+                transaction.time.format("%Y-%m-%d %H:%M"),
+                format!("{:.2}", entry.balance),
+            )
+        })
+        .collect();
+
+    let amount_width = rows.iter().map(|row| row.1.len()).max().unwrap_or_default();
+    let what_width = rows
+        .iter()
+        .map(|row| row.2.chars().count())
+        .max()
+        .unwrap_or_default();
+    let balance_width = rows.iter().map(|row| row.4.len()).max().unwrap_or_default();
+
+    let lines: Vec<_> = rows
+        .into_iter()
+        .map(|(sign, amount, what, time, balance)| {
+            format!(
+                "{sign} {amount:>amount_width$}  {what:<what_width$}  {time}  {balance:>balance_width$}"
+            )
+        })
+        .collect();
+
+    EmbedBudget::new(locale).lines(MAX_DESCRIPTION, "```diff\n", &lines, "```")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use rse_core::model::{Transaction, TransactionKind, ticker::Ticker};
+    use rust_decimal::Decimal;
+
+    use super::*;
+
+    fn entry(
+        kind: TransactionKind,
+        delta: i64,
+        balance: i64,
+        ticker: Option<&str>,
+    ) -> StatementEntry {
+        StatementEntry {
+            transaction: Transaction {
+                time: Utc.with_ymd_and_hms(2025, 10, 15, 12, 30, 0).unwrap(),
+                kind,
+                delta: Decimal::from(delta),
+                ticker: ticker.map(|ticker| Ticker::try_from(ticker).expect("Valid ticker")),
+            },
+            balance: Decimal::from(balance),
+        }
+    }
+
+    #[test]
+    fn credits_and_debits_are_marked_for_diff_highlighting() {
+        let table = into_table(
+            &[
+                entry(TransactionKind::Fee, -1, 899, Some("ABC")),
+                entry(TransactionKind::Buy, -100, 900, Some("ABC")),
+                entry(TransactionKind::Grant, 1000, 1000, None),
+            ],
+            "en",
+        );
+
+        assert_eq!(
+            table,
+            "```diff\n\
+            -    1.00  Fee $ABC     2025-10-15 12:30   899.00\n\
+            -  100.00  Bought $ABC  2025-10-15 12:30   900.00\n\
+            + 1000.00  Grant        2025-10-15 12:30  1000.00\n\
+            ```"
+        );
+    }
+}
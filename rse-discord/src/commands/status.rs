@@ -0,0 +1,144 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::time::Duration;
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed},
+};
+use rse_core::{Service, repo::StockRepository};
+
+use crate::{
+    Context, Error,
+    dm::DmDispatcher,
+    gateway::{Gateway, STARTED},
+};
+
+/// How long the database has to answer before it is reported as unreachable
+const PING_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Checks on the health of the exchange and its connections
+// Only configured admins, as checking for the server's admin role needs the database
+#[poise::command(slash_command, owners_only, ephemeral)]
+pub async fn status<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
+    // Not recorded in the audit log, which would fail with the database this is meant to diagnose
+    let shards = Gateway::new(
+        ctx.framework().shard_manager.clone(),
+        ctx.data().sessions().clone(),
+    );
+    let (database, gateway, shards) = tokio::join!(
+        ctx.data().service().ping(PING_TIMEOUT),
+        ctx.ping(),
+        shards.health()
+    );
+
+    let (database, color) = match database {
+        Ok(latency) => (format!("{} ms", latency.as_millis()), Color::DARK_GREEN),
+        Err(err) => {
+            tracing::warn!(%err, "Database ping failed");
+            ("Unreachable".to_owned(), Color::RED)
+        }
+    };
+
+    // Zero until the shard's first heartbeat is acknowledged
+    let gateway = if gateway.is_zero() {
+        "Unknown".to_owned()
+    } else {
+        format!("{} ms", gateway.as_millis())
+    };
+
+    let color = if shards.is_connected() {
+        color
+    } else {
+        Color::GOLD
+    };
+
+    let embed = CreateEmbed::new()
+        .title("Status")
+        .field("Database", database, true)
+        .field("Gateway", gateway, true)
+        .field(
+            "Shards",
+            format!(
+                "{}/{} connected, {} reconnects",
+                shards.connected(),
+                shards.shards.len(),
+                shards.reconnects()
+            ),
+            true,
+        )
+        .field("Connections", connections(ctx.data().service()), true)
+        .field("Market", market(ctx.data().service()), true)
+        .field("DMs", dms(ctx.data().dms()), true)
+        .field("Up since", format!("<t:{}:R>", STARTED.timestamp()), true)
+        .field("Version", env!("CARGO_PKG_VERSION"), true)
+        .color(color);
+
+    send_reply(ctx, CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
+
+/// Describes how many of the database's pooled connections are in use
+fn connections<R: StockRepository>(service: &Service<R>) -> String {
+    let Some(pool) = service.pool_usage() else {
+        return "Not pooled".to_owned();
+    };
+
+    let usage = format!("{}/{} in use, {} idle", pool.in_use(), pool.max, pool.idle);
+
+    if pool.is_saturated() {
+        format!("{usage} (saturated)")
+    } else {
+        usage
+    }
+}
+
+/// Describes how DMs to users have fared since the bot started
+fn dms(dms: &DmDispatcher) -> String {
+    format!(
+        "{} sent, {} queued, {} failed, {} dropped",
+        dms.sent(),
+        dms.queued(),
+        dms.failed(),
+        dms.dropped()
+    )
+}
+
+/// Describes whether the market is open, and when that next changes
+fn market<R: StockRepository>(service: &Service<R>) -> String {
+    let Some(calendar) = service.calendar() else {
+        return "Always open".to_owned();
+    };
+
+    let now = service.now();
+
+    if calendar.is_open(now) {
+        format!(
+            "Open, closes <t:{}:R>",
+            calendar.next_close(now).timestamp()
+        )
+    } else {
+        format!(
+            "Closed, opens <t:{}:R> until <t:{}:t>",
+            calendar.next_open(now).timestamp(),
+            calendar.next_close(now).timestamp()
+        )
+    }
+}
@@ -1,39 +1,89 @@
-use std::ops::Rem;
-
-use crate::{Context, Error};
+use crate::{
+    Context, Error,
+    commands::{
+        budget::EmbedBudget,
+        parse_ticker_prefix,
+        presses::{PageCursor, Presses},
+    },
+    i18n::{self, t},
+};
 use chrono::{DateTime, Utc};
 use poise::{
     CreateReply, send_reply,
     serenity_prelude::{
         Color, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter,
-        CreateInteractionResponse, CreateInteractionResponseFollowup,
-        CreateInteractionResponseMessage,
+        CreateInteractionResponse, CreateInteractionResponseMessage,
     },
 };
 use rse_core::{
-    model::{Pager, ticker::Ticker},
+    display::format_kromer,
+    error::Error as RscError,
+    model::{Page, Pager, Price, Shares, StockOrdering, ticker::Ticker},
     repo::StockRepository,
 };
-use rust_decimal::Decimal;
+
+/// What to sort stocks by
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum SortChoice {
+    /// Alphabetically by ticker
+    Ticker,
+    /// Highest price first
+    Price,
+    /// Most shares traded over the last day first
+    Volume,
+    /// Biggest rise since the last close first
+    Change,
+}
+
+impl From<SortChoice> for StockOrdering {
+    fn from(value: SortChoice) -> Self {
+        match value {
+            SortChoice::Ticker => Self::Ticker,
+            SortChoice::Price => Self::Price,
+            SortChoice::Volume => Self::Volume,
+            SortChoice::Change => Self::Change,
+        }
+    }
+}
 
 #[poise::command(slash_command, ephemeral)]
-pub async fn stocks<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
-    const PAGE_SIZE: i64 = 16;
+#[allow(clippy::too_many_lines)]
+pub async fn stocks<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "What to sort stocks by"] sort: Option<SortChoice>,
+    #[description = "Only show tickers starting with this"]
+    #[max_length = 5]
+    filter: Option<String>,
+    #[description = "Show the reply to everyone in the channel"] public: Option<bool>,
+) -> Result<(), Error> {
+    const PAGE_SIZE: u64 = 16;
     let ctx_id = ctx.id();
-    let stock_service = ctx.data();
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+    let public = public.unwrap_or_default();
+    let order = sort.map(StockOrdering::from).unwrap_or_default();
+    let prefix = filter
+        .as_deref()
+        .map(parse_ticker_prefix)
+        .transpose()?
+        .unwrap_or_default();
 
-    let mut page = Pager::new(0, PAGE_SIZE);
-
-    let res = stock_service.list_stocks(&page).await;
+    let res = stock_service
+        .list_stocks(&Pager::new(0, PAGE_SIZE.cast_signed()), order, &prefix)
+        .await;
 
     if let Err(e) = res
-        && e == rse_core::error::Error::NoStocksExist
+        && e == RscError::NoStocksExist
     {
         send_reply(
             ctx,
-            CreateReply::default().embed(
+            CreateReply::default().ephemeral(!public).embed(
                 CreateEmbed::new()
-                    .description("No stock data to display")
+                    .description(if prefix.is_empty() {
+                        t!(locale, "stocks.empty")
+                    } else {
+                        t!(locale, "stocks.no_match")
+                    })
                     .color(Color::BLURPLE),
             ),
         )
@@ -42,16 +92,23 @@ pub async fn stocks<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error
         return Ok(());
     }
 
-    let (stocks, num_entries) = res?;
+    let stocks = res?;
 
-    let total_pages = num_entries / PAGE_SIZE + num_entries.rem(PAGE_SIZE).clamp(0, 1);
+    let mut cursor = PageCursor::new(stocks.total, PAGE_SIZE);
 
-    if total_pages == 1 {
-        send_reply(ctx, CreateReply::default().embed(into_embed(&stocks))).await?;
+    if cursor.pages() == 1 {
+        send_reply(
+            ctx,
+            CreateReply::default().ephemeral(!public).embed(into_embed(
+                &stocks.items,
+                stock_service.market_opens_at(),
+                locale,
+            )),
+        )
+        .await?;
         return Ok(());
     }
 
-    let mut current_page: i64 = 0;
     let prev_button_id = format!("{ctx_id}prev");
     let next_button_id = format!("{ctx_id}next");
 
@@ -62,70 +119,171 @@ pub async fn stocks<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error
         ]);
 
         CreateReply::default()
+            .ephemeral(!public)
             .embed(
-                into_embed(&stocks)
-                    .footer(CreateEmbedFooter::new(format!("Page: 1/{total_pages}"))),
+                into_embed(&stocks.items, stock_service.market_opens_at(), locale).footer(
+                    CreateEmbedFooter::new(t!(
+                        locale,
+                        "stocks.page",
+                        page = cursor.number(),
+                        pages = cursor.pages()
+                    )),
+                ),
             )
             .components(vec![components])
     };
 
     send_reply(ctx, reply).await?;
 
-    while let Some(press) =
-        poise::serenity_prelude::collector::ComponentInteractionCollector::new(ctx)
-            .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
-            .timeout(std::time::Duration::from_secs(1800))
-            .await
-    {
+    let mut presses = Presses::new(ctx);
+
+    while let Some(press) = presses.next().await {
         tracing::info!("Pressed! {}", press.data.custom_id);
         if press.data.custom_id == prev_button_id {
-            current_page = current_page.checked_sub(1).unwrap_or(total_pages);
+            cursor.prev();
         } else if press.data.custom_id == next_button_id {
-            current_page += 1;
-            current_page %= total_pages + 1;
+            cursor.next();
         } else {
             // Unrelated interaction
             continue;
         }
 
-        page.set_offset(current_page * PAGE_SIZE);
+        // Stocks may have been listed since the last page, in which case the page may have to move
+        let stocks = loop {
+            let page = cursor.pager();
 
-        let (stocks, new_entries) = stock_service.list_stocks(&page).await?;
+            // Past the last stock no count comes back, so start over from the first page
+            let stocks = match stock_service.list_stocks(&page, order, &prefix).await {
+                Ok(stocks) => stocks,
+                Err(RscError::NoStocksExist) => Page::new(Vec::new(), 0, &page),
+                Err(err) => return Err(err.into()),
+            };
 
-        if new_entries != num_entries {
-            press
-                .create_followup(
-                    ctx.serenity_context(),
-                    CreateInteractionResponseFollowup::new()
-                        .content("A new stock has been created, please call this command again"),
-                )
-                .await?;
-            break;
-        }
+            if !cursor.sync(stocks.total) {
+                break stocks;
+            }
+        };
+
+        let footer = t!(
+            locale,
+            "stocks.page",
+            page = cursor.number(),
+            pages = cursor.pages()
+        );
 
         press
             .create_response(
                 ctx.serenity_context(),
                 CreateInteractionResponse::UpdateMessage(
-                    CreateInteractionResponseMessage::new().embed(into_embed(&stocks).footer(
-                        CreateEmbedFooter::new(format!("Page: {current_page}/{total_pages}")),
-                    )),
+                    CreateInteractionResponseMessage::new().embed(
+                        into_embed(&stocks.items, stock_service.market_opens_at(), locale)
+                            .footer(CreateEmbedFooter::new(cursor.footer(footer, locale))),
+                    ),
                 ),
             )
             .await?;
     }
 
+    presses.expire(locale).await;
+
     Ok(())
 }
 
-fn into_embed(v: &[(Ticker, u32, Decimal, DateTime<Utc>)]) -> CreateEmbed {
-    let fields = v.iter().map(|(ticker, shares, value, time)| {
-        (
-            ticker.as_str(),
-            format!("Shares: {shares}\nPrice: {value}\nLast Sold: {time}"),
-            true,
-        )
-    });
+#[allow(clippy::type_complexity)]
+fn into_embed(
+    v: &[(
+        Ticker,
+        Shares,
+        Option<Price>,
+        Option<DateTime<Utc>>,
+        Option<String>,
+    )],
+    opens_at: Option<DateTime<Utc>>,
+    locale: &str,
+) -> CreateEmbed {
+    let mut description = Vec::new();
+
+    if let Some(opens_at) = opens_at {
+        description.push(t!(
+            locale,
+            "stocks.after_hours",
+            opens = opens_at.timestamp()
+        ));
+    }
+
+    if v.is_empty() {
+        description.push(t!(locale, "stocks.empty"));
+    }
+
+    let mut budget = EmbedBudget::new(locale);
+    let description = description.join("\n");
+    budget.spend(&description);
+
+    let fields = v
+        .iter()
+        .map(|(ticker, shares, value, time, name)| {
+            let (price, time) = match (value, time) {
+                (Some(value), Some(time)) => (format_kromer(value.get()), time.to_string()),
+                _ => ("—".to_owned(), t!(locale, "stocks.never")),
+            };
+
+            let title = match name {
+                Some(name) => format!("{ticker} · {name}"),
+                None => ticker.to_string(),
+            };
+
+            (
+                title,
+                t!(
+                    locale,
+                    "stocks.entry",
+                    shares = shares,
+                    price = price,
+                    time = time
+                ),
+                true,
+            )
+        })
+        .collect();
+
+    let embed = CreateEmbed::new()
+        .color(Color::BLURPLE)
+        .fields(budget.fields(fields));
+
+    if description.is_empty() {
+        embed
+    } else {
+        embed.description(description)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_decimal::Decimal;
 
-    CreateEmbed::new().color(Color::BLURPLE).fields(fields)
+    use super::*;
+    use crate::commands::budget::embed_size;
+
+    #[test]
+    fn long_pages_fit_in_an_embed() {
+        let stocks: Vec<_> = (b'A'..=b'Z')
+            .flat_map(|first| (b'A'..=b'D').map(move |second| [b'S', first, second]))
+            .map(|ticker| {
+                (
+                    Ticker::try_from(std::str::from_utf8(&ticker).expect("ASCII"))
+                        .expect("Valid ticker"),
+                    Shares::new(1_000_000).expect("Valid shares"),
+                    Some(Price::new(Decimal::new(123_456, 2)).expect("Valid price")),
+                    Some(Utc::now()),
+                    Some("A very long company name ".repeat(8)),
+                )
+            })
+            .collect();
+
+        let embed = into_embed(&stocks, Some(Utc::now()), "en");
+        let (fields, chars) = embed_size(&embed);
+
+        assert!(fields <= 25);
+        assert!(chars <= 6000);
+    }
 }
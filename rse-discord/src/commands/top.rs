@@ -0,0 +1,60 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The stocks whose prices moved the most recently
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed, Timestamp},
+};
+use rse_core::repo::StockRepository;
+
+use crate::{Context, Error, commands::movers_column};
+
+/// Show the biggest gainers and losers since the last close
+#[poise::command(slash_command, ephemeral)]
+pub async fn top<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "How many stocks to show on each side"]
+    #[min = 1]
+    #[max = 10]
+    count: Option<u8>,
+) -> Result<(), Error> {
+    let movers = ctx
+        .data()
+        .service()
+        .top_movers(count.unwrap_or(5).clamp(1, 10))
+        .await?;
+
+    let embed = CreateEmbed::new()
+        .title("Top movers — since the last close")
+        .color(Color::BLURPLE)
+        .timestamp(Timestamp::now());
+
+    let embed = if movers.gainers.is_empty() && movers.losers.is_empty() {
+        embed.description("No stock has moved since the last close")
+    } else {
+        embed
+            .field("Gainers", movers_column(&movers.gainers, true), true)
+            .field("Losers", movers_column(&movers.losers, false), true)
+    };
+
+    send_reply(ctx, CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
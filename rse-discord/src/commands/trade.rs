@@ -0,0 +1,146 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed, Timestamp},
+};
+use rse_core::{
+    display::format_kromer,
+    model::{
+        Shares,
+        link::Identity,
+        order::{Fill, Side},
+    },
+    repo::StockRepository,
+};
+use rust_decimal::Decimal;
+
+use crate::{
+    Context, Error,
+    commands::{TickerArg, autocomplete_ticker, ensure_trading, parse_shares},
+    i18n::{self, t},
+};
+
+/// Buy shares right away at the best prices on the book
+#[poise::command(slash_command, ephemeral)]
+pub async fn buy<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The stock to buy"]
+    #[autocomplete = "autocomplete_ticker"]
+    ticker: TickerArg,
+    #[description = "The number of shares"]
+    #[min = 1]
+    quantity: u32,
+) -> Result<(), Error> {
+    trade(ctx, ticker, Side::Buy, quantity).await
+}
+
+/// Sell shares right away at the best prices on the book
+#[poise::command(slash_command, ephemeral)]
+pub async fn sell<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The stock to sell"]
+    #[autocomplete = "autocomplete_ticker"]
+    ticker: TickerArg,
+    #[description = "The number of shares"]
+    #[min = 1]
+    quantity: u32,
+) -> Result<(), Error> {
+    trade(ctx, ticker, Side::Sell, quantity).await
+}
+
+/// Places a market order and replies with what it filled and the balance it left
+async fn trade<R: StockRepository>(
+    ctx: Context<'_, R>,
+    ticker: TickerArg,
+    side: Side,
+    quantity: u32,
+) -> Result<(), Error> {
+    ensure_trading(ctx).await?;
+
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+    let ticker = *ticker;
+    let quantity = parse_shares(quantity)?;
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+
+    let (order, fills) = stock_service
+        .place_market_order(&user_id, &ticker, side, quantity)
+        .await?;
+    let info = stock_service
+        .get_account_info(&user_id, Some(&user_id))
+        .await?;
+
+    let filled: u32 = fills.iter().map(|f| f.shares.get()).sum();
+    let value: Decimal = fills.iter().map(Fill::notional).sum();
+    // Only buyers pay fees
+    let fee: Decimal = match side {
+        Side::Buy => fills.iter().map(|f| f.fee).sum(),
+        Side::Sell => Decimal::ZERO,
+    };
+
+    let title = match side {
+        Side::Buy => t!(
+            locale,
+            "trade.bought",
+            shares = filled,
+            ticker = order.ticker
+        ),
+        Side::Sell => t!(locale, "trade.sold", shares = filled, ticker = order.ticker),
+    };
+
+    let mut embed = CreateEmbed::new()
+        .title(title)
+        .color(Color::DARK_GREEN)
+        .timestamp(Timestamp::now());
+
+    if filled > 0 {
+        embed = embed.field(
+            t!(locale, "trade.average"),
+            format_kromer(value / Decimal::from(filled)),
+            true,
+        );
+    }
+
+    if !fee.is_zero() {
+        embed = embed.field(t!(locale, "trade.fee"), format_kromer(fee), true);
+    }
+
+    // Always visible to the account itself
+    embed = embed.field(
+        t!(locale, "trade.balance"),
+        format_kromer(info.available_balance.unwrap_or_default()),
+        true,
+    );
+
+    if order.remaining > Shares::ZERO {
+        embed = embed.description(t!(
+            locale,
+            "trade.partial",
+            filled = filled,
+            quantity = order.quantity
+        ));
+    }
+
+    send_reply(ctx, CreateReply::default().embed(embed)).await?;
+
+    Ok(())
+}
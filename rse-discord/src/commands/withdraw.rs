@@ -0,0 +1,86 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::{str::FromStr, time::Duration};
+
+use poise::{
+    CreateReply, send_reply,
+    serenity_prelude::{Color, CreateEmbed, Timestamp},
+};
+use rse_core::{model::link::Identity, repo::StockRepository};
+use rust_decimal::Decimal;
+use snafu::ResultExt;
+
+use crate::{
+    Context, Error,
+    commands::{confirm::confirm, parse_address},
+    error::InvalidPriceSnafu,
+    i18n::{self, t},
+};
+
+/// Withdraw Kromer from the exchange to an address, once an admin has reviewed it
+#[poise::command(slash_command, ephemeral)]
+pub async fn withdraw<R: StockRepository>(
+    ctx: Context<'_, R>,
+    #[description = "The Kromer to withdraw"] amount: String,
+    #[description = "The Kromer address to send it to"] address: String,
+) -> Result<(), Error> {
+    let stock_service = ctx.data().service();
+    let locale = &i18n::locale(ctx).await;
+    let amount = Decimal::from_str(amount.trim()).context(InvalidPriceSnafu {
+        input: amount.clone(),
+    })?;
+    let address = parse_address(&address)?;
+    let user_id = stock_service
+        .resolve(&Identity::Discord(ctx.author().id.into()))
+        .await?;
+
+    let summary = CreateEmbed::new()
+        .title(t!(locale, "withdraw.confirm_title"))
+        .description(t!(
+            locale,
+            "withdraw.confirm",
+            amount = amount,
+            address = address
+        ))
+        .color(Color::GOLD);
+
+    if !confirm(ctx, summary, Duration::from_mins(2)).await? {
+        return Ok(());
+    }
+
+    let withdrawal = stock_service.withdraw(&user_id, amount, &address).await?;
+
+    let reply = CreateReply::default().embed(
+        CreateEmbed::new()
+            .title(t!(locale, "withdraw.requested_title"))
+            .description(t!(
+                locale,
+                "withdraw.requested",
+                id = withdrawal.id,
+                amount = withdrawal.amount,
+                address = withdrawal.address
+            ))
+            .color(Color::DARK_GREEN)
+            .timestamp(Timestamp::now()),
+    );
+
+    send_reply(ctx, reply).await?;
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Data shared by every command invocation
+
+use std::{collections::HashSet, sync::Arc};
+
+use poise::serenity_prelude::UserId;
+use rse_config::DiscordConfig;
+use rse_core::{Service, repo::StockRepository};
+
+use crate::{dm::DmDispatcher, gateway::Sessions};
+
+/// Poise's user data, reached through [`Context::data`](poise::Context::data)
+#[derive(Debug)]
+pub struct BotData<R: StockRepository> {
+    service: Service<R>,
+    config: DiscordConfig,
+    admins: HashSet<UserId>,
+    mojang: rse_mojang::Client,
+    sessions: Arc<Sessions>,
+    dms: DmDispatcher,
+}
+
+impl<R: StockRepository> BotData<R> {
+    /// Creates the data for a bot configured by `config`, whose admins are its `admin_ids`, sending
+    /// DMs through `dms`
+    pub(crate) fn new(service: Service<R>, config: DiscordConfig, dms: DmDispatcher) -> Self {
+        let admins = config.admin_ids.iter().copied().map(UserId::from).collect();
+
+        Self {
+            service,
+            config,
+            admins,
+            mojang: rse_mojang::Client::default(),
+            sessions: Arc::default(),
+            dms,
+        }
+    }
+
+    /// The service commands act on
+    pub const fn service(&self) -> &Service<R> {
+        &self.service
+    }
+
+    /// The configuration the bot was started with
+    pub const fn config(&self) -> &DiscordConfig {
+        &self.config
+    }
+
+    /// Every user allowed to run privileged commands
+    pub const fn admins(&self) -> &HashSet<UserId> {
+        &self.admins
+    }
+
+    /// The client Minecraft usernames are resolved with
+    pub const fn mojang(&self) -> &rse_mojang::Client {
+        &self.mojang
+    }
+
+    /// The sessions each shard has started with the gateway
+    pub(crate) const fn sessions(&self) -> &Arc<Sessions> {
+        &self.sessions
+    }
+
+    /// Where DMs to users are queued
+    pub(crate) const fn dms(&self) -> &DmDispatcher {
+        &self.dms
+    }
+
+    /// Whether `user` may run privileged commands
+    pub fn is_admin(&self, user: UserId) -> bool {
+        self.admins.contains(&user)
+    }
+}
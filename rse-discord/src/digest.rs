@@ -0,0 +1,129 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The daily market summary posted to the market feed channels
+
+use std::{sync::Arc, time::Duration};
+
+use chrono::{NaiveDate, NaiveTime, TimeDelta, Utc};
+use poise::serenity_prelude::{Color, CreateEmbed, CreateMessage, Http, Timestamp};
+use rse_core::{Service, model::summary::DailySummary, repo::StockRepository};
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::{Error, commands::movers_column, notify::Feeds};
+
+/// How long to wait before trying again when a summary couldn't be posted
+const RETRY_DELAY: Duration = Duration::from_mins(10);
+
+/// Posts a summary of the previous UTC day to `feeds` at `hour` UTC each day, until cancelled.
+/// Days already posted are skipped, so restarting doesn't post the same day twice.
+pub(crate) async fn run<R: StockRepository>(
+    service: Service<R>,
+    http: Arc<Http>,
+    feeds: Feeds<R>,
+    hour: u8,
+    c_token: CancellationToken,
+) {
+    let post_at = NaiveTime::from_hms_opt(hour.into(), 0, 0).expect("Validated by config");
+
+    loop {
+        let now = Utc::now();
+        let today = now.date_naive();
+        let due = today.and_time(post_at).and_utc();
+
+        let next = if now < due {
+            due
+        } else if let Err(err) = post(&service, &http, &feeds, today - TimeDelta::days(1)).await {
+            warn!(%err, "Couldn't post daily summary");
+            now + RETRY_DELAY
+        } else {
+            due + TimeDelta::days(1)
+        };
+
+        let wait = (next - Utc::now()).to_std().unwrap_or_default();
+
+        tokio::select! {
+            () = c_token.cancelled() => return,
+            () = tokio::time::sleep(wait) => {}
+        }
+    }
+}
+
+/// Posts the summary of `date`, unless it has been already. Days with no feed to post to are
+/// left unmarked.
+async fn post<R: StockRepository>(
+    service: &Service<R>,
+    http: &Http,
+    feeds: &Feeds<R>,
+    date: NaiveDate,
+) -> Result<(), Error> {
+    if service.summary_sent(date).await? {
+        return Ok(());
+    }
+
+    let summary = service.daily_summary(date).await?;
+
+    let message = if summary.is_quiet() {
+        CreateMessage::new().content(format!("The market was quiet on {date}, nothing traded"))
+    } else {
+        CreateMessage::new().embed(into_embed(&summary))
+    };
+
+    if feeds.post(http, message).await? == 0 {
+        return Ok(());
+    }
+    service.record_summary_sent(date).await?;
+
+    info!(%date, "Posted daily summary");
+
+    Ok(())
+}
+
+fn into_embed(summary: &DailySummary) -> CreateEmbed {
+    let biggest = summary.biggest_trade.map_or_else(
+        || "—".to_owned(),
+        |trade| {
+            format!(
+                "{} ${} @ {:.2} ({:.2})",
+                trade.shares,
+                trade.ticker,
+                trade.price,
+                trade.value()
+            )
+        },
+    );
+
+    CreateEmbed::new()
+        .title(format!("Market summary for {}", summary.date))
+        .field("Trades", summary.trades.to_string(), true)
+        .field("Volume", format!("{:.2}", summary.volume), true)
+        .field("Biggest trade", biggest, true)
+        .field(
+            "Top gainers",
+            movers_column(&summary.movers.gainers, true),
+            true,
+        )
+        .field(
+            "Top losers",
+            movers_column(&summary.movers.losers, false),
+            true,
+        )
+        .color(Color::BLURPLE)
+        .timestamp(Timestamp::now())
+}
@@ -0,0 +1,441 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Direct messages to users, queued from anywhere in the bot and sent one at a time by a single
+//! worker, so however many features DM at once they can't trip Discord's rate limits between them
+
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use poise::serenity_prelude::{self as serenity, CreateMessage, Http, UserId};
+use tokio::{sync::mpsc, time::Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// How many DMs may wait to be sent before more are dropped
+pub(crate) const QUEUE_CAPACITY: usize = 256;
+
+/// How long the worker keeps sending queued DMs once shutdown starts
+pub(crate) const DRAIN_GRACE: Duration = Duration::from_secs(5);
+
+/// The least time between two DMs
+const PACE: Duration = Duration::from_millis(250);
+
+/// How long the same DM to the same user is skipped for after it was first sent
+const DEDUP_WINDOW: Duration = Duration::from_mins(1);
+
+/// How many times a DM is tried while Discord keeps rate limiting it
+const MAX_ATTEMPTS: u32 = 3;
+
+/// How long to wait after a rate limit Discord didn't say the length of
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Why a DM couldn't be sent
+#[derive(Debug)]
+pub(crate) enum SendError {
+    /// Discord asked to wait `retry_after` before trying again
+    RateLimited { retry_after: Duration },
+    /// Anything else, which trying again won't fix, such as the user not accepting DMs
+    Failed(serenity::Error),
+}
+
+/// Sends DMs, which is Discord's HTTP API outside of tests
+pub(crate) trait DmSender: Send + Sync + 'static {
+    /// Sends `message` to `user`
+    fn send(
+        &self,
+        user: UserId,
+        message: CreateMessage,
+    ) -> impl Future<Output = Result<(), SendError>> + Send;
+}
+
+impl DmSender for Arc<Http> {
+    async fn send(&self, user: UserId, message: CreateMessage) -> Result<(), SendError> {
+        match user.direct_message(self, message).await {
+            Ok(_) => Ok(()),
+            // Serenity waits out the rate limits it knows of by itself, and doesn't pass on how
+            // long the ones it didn't were
+            Err(serenity::Error::Http(err))
+                if err.status_code().is_some_and(|code| code.as_u16() == 429) =>
+            {
+                Err(SendError::RateLimited {
+                    retry_after: DEFAULT_RETRY_AFTER,
+                })
+            }
+            Err(err) => Err(SendError::Failed(err)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct DmStats {
+    sent: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+/// Queues DMs for the [`DmWorker`] to send. Queuing never waits: when the queue is full, the DM is
+/// dropped instead. Clones share the queue.
+#[derive(Debug, Clone)]
+pub(crate) struct DmDispatcher {
+    queue: mpsc::Sender<(UserId, CreateMessage)>,
+    stats: Arc<DmStats>,
+}
+
+impl DmDispatcher {
+    /// A dispatcher queuing up to `capacity` DMs, along with the worker sending them
+    pub fn new(capacity: usize) -> (Self, DmWorker) {
+        let (queue, receiver) = mpsc::channel(capacity);
+        let stats = Arc::new(DmStats::default());
+
+        let worker = DmWorker {
+            queue: receiver,
+            stats: stats.clone(),
+            recent: HashMap::new(),
+            next_send: Instant::now(),
+            in_flight: false,
+        };
+
+        (Self { queue, stats }, worker)
+    }
+
+    /// Queues `message` to be sent to `user`, returning whether it was. It isn't if the queue is
+    /// full or the worker has stopped, which is logged and counted as dropped.
+    pub fn send(&self, user: UserId, message: CreateMessage) -> bool {
+        match self.queue.try_send((user, message)) {
+            Ok(()) => true,
+            Err(err) => {
+                let reason = match err {
+                    mpsc::error::TrySendError::Full(_) => "the queue is full",
+                    mpsc::error::TrySendError::Closed(_) => "the worker has stopped",
+                };
+                warn!(%user, reason, "Dropping DM");
+                self.stats.dropped.fetch_add(1, Ordering::Relaxed);
+
+                false
+            }
+        }
+    }
+
+    /// How many DMs are waiting to be sent
+    pub fn queued(&self) -> usize {
+        self.queue.max_capacity() - self.queue.capacity()
+    }
+
+    /// How many DMs were sent
+    pub fn sent(&self) -> u64 {
+        self.stats.sent.load(Ordering::Relaxed)
+    }
+
+    /// How many DMs Discord refused, or kept rate limiting until they were given up on
+    pub fn failed(&self) -> u64 {
+        self.stats.failed.load(Ordering::Relaxed)
+    }
+
+    /// How many DMs were never tried, as the queue was full or shutdown cut them off
+    pub fn dropped(&self) -> u64 {
+        self.stats.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Sends the DMs queued with a [`DmDispatcher`], pacing them and skipping repeats
+#[derive(Debug)]
+pub(crate) struct DmWorker {
+    queue: mpsc::Receiver<(UserId, CreateMessage)>,
+    stats: Arc<DmStats>,
+    /// When each DM sent within the last [`DEDUP_WINDOW`] was, by user and fingerprint
+    recent: HashMap<(UserId, u64), Instant>,
+    next_send: Instant,
+    /// Whether a DM taken off the queue is being sent while draining it
+    in_flight: bool,
+}
+
+impl DmWorker {
+    /// Sends queued DMs through `sender` until cancelled, then keeps going for up to `grace` to
+    /// empty the queue. Whatever is left after that is dropped.
+    pub async fn run(mut self, sender: impl DmSender, grace: Duration, c_token: CancellationToken) {
+        loop {
+            let dm = tokio::select! {
+                biased;
+                () = c_token.cancelled() => break,
+                dm = self.queue.recv() => dm,
+            };
+
+            let Some((user, message)) = dm else {
+                // Every dispatcher is gone, so nothing more can be queued
+                return;
+            };
+            self.deliver(&sender, user, message).await;
+        }
+
+        self.queue.close();
+
+        if tokio::time::timeout(grace, self.drain(&sender))
+            .await
+            .is_err()
+        {
+            let mut left = u64::from(self.in_flight);
+            while self.queue.try_recv().is_ok() {
+                left += 1;
+            }
+
+            warn!(left, "Shut down before every DM was sent");
+            self.stats.dropped.fetch_add(left, Ordering::Relaxed);
+        }
+    }
+
+    async fn drain(&mut self, sender: &impl DmSender) {
+        while let Some((user, message)) = self.queue.recv().await {
+            self.in_flight = true;
+            self.deliver(sender, user, message).await;
+            self.in_flight = false;
+        }
+    }
+
+    /// Sends `message` to `user` once the pace allows, unless it was just sent to them, trying
+    /// again while Discord rate limits it
+    async fn deliver(&mut self, sender: &impl DmSender, user: UserId, message: CreateMessage) {
+        let now = Instant::now();
+        self.recent
+            .retain(|_, sent_at| now.duration_since(*sent_at) < DEDUP_WINDOW);
+
+        if self
+            .recent
+            .insert((user, fingerprint(&message)), now)
+            .is_some()
+        {
+            debug!(%user, "Skipping repeated DM");
+            return;
+        }
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            tokio::time::sleep_until(self.next_send).await;
+            let res = sender.send(user, message.clone()).await;
+            self.next_send = Instant::now() + PACE;
+
+            match res {
+                Ok(()) => {
+                    self.stats.sent.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+                Err(SendError::RateLimited { retry_after }) => {
+                    warn!(%user, attempt, ?retry_after, "DM was rate limited");
+                    self.next_send = Instant::now() + retry_after.max(PACE);
+                }
+                Err(SendError::Failed(err)) => {
+                    warn!(%user, %err, "Couldn't send DM");
+                    self.stats.failed.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
+
+        warn!(%user, "Giving up on DM that kept being rate limited");
+        self.stats.failed.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Identifies what a message says, leaving out embed timestamps, which are when it was rendered
+/// rather than part of it
+fn fingerprint(message: &CreateMessage) -> u64 {
+    let mut value = serde_json::to_value(message).unwrap_or_default();
+
+    if let Some(embeds) = value
+        .get_mut("embeds")
+        .and_then(serde_json::Value::as_array_mut)
+    {
+        for embed in embeds
+            .iter_mut()
+            .filter_map(serde_json::Value::as_object_mut)
+        {
+            embed.remove("timestamp");
+        }
+    }
+
+    let mut hasher = DefaultHasher::new();
+    value.to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{Mutex, PoisonError},
+    };
+
+    use poise::serenity_prelude::{CreateEmbed, Timestamp};
+
+    use super::*;
+
+    /// Records when each DM was sent, rate limiting those to a user as scripted first
+    #[derive(Debug, Clone, Default)]
+    struct MockSender {
+        sent: Arc<Mutex<Vec<(UserId, Instant)>>>,
+        script: Arc<Mutex<HashMap<UserId, VecDeque<Duration>>>>,
+    }
+
+    impl MockSender {
+        /// Rate limits the next DM to `user` that isn't already, for `retry_after`
+        fn rate_limit(&self, user: UserId, retry_after: Duration) {
+            self.script
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(user)
+                .or_default()
+                .push_back(retry_after);
+        }
+
+        fn sent(&self) -> Vec<(UserId, Instant)> {
+            self.sent
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .clone()
+        }
+    }
+
+    impl DmSender for MockSender {
+        async fn send(&self, user: UserId, _message: CreateMessage) -> Result<(), SendError> {
+            let limited = self
+                .script
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get_mut(&user)
+                .and_then(VecDeque::pop_front);
+
+            if let Some(retry_after) = limited {
+                return Err(SendError::RateLimited { retry_after });
+            }
+
+            self.sent
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push((user, Instant::now()));
+            Ok(())
+        }
+    }
+
+    fn text(content: &str) -> CreateMessage {
+        CreateMessage::new().content(content)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn dms_are_paced_and_repeats_skipped() {
+        let (dms, worker) = DmDispatcher::new(QUEUE_CAPACITY);
+        let sender = MockSender::default();
+        let c_token = CancellationToken::new();
+        let start = Instant::now();
+        let (alice, bob) = (UserId::new(1), UserId::new(2));
+
+        // Rendered a moment apart, but saying the same thing
+        let embed =
+            |time| CreateMessage::new().embed(CreateEmbed::new().title("Hi").timestamp(time));
+        assert!(dms.send(
+            alice,
+            embed(Timestamp::from_unix_timestamp(1).expect("Valid"))
+        ));
+        assert!(dms.send(
+            alice,
+            embed(Timestamp::from_unix_timestamp(2).expect("Valid"))
+        ));
+        assert!(dms.send(bob, text("Hi")));
+        assert!(dms.send(alice, text("Bye")));
+
+        let run = tokio::spawn(worker.run(sender.clone(), DRAIN_GRACE, c_token.clone()));
+        tokio::time::sleep(DEDUP_WINDOW + Duration::from_secs(1)).await;
+        assert!(dms.send(alice, text("Bye")));
+        tokio::time::sleep(PACE).await;
+        c_token.cancel();
+        run.await.expect("Worker ran");
+
+        let sent = sender.sent();
+        let users: Vec<_> = sent.iter().map(|(user, _)| *user).collect();
+        assert_eq!(users, [alice, bob, alice, alice]);
+        assert!(sent.windows(2).all(|pair| pair[1].1 - pair[0].1 >= PACE));
+        assert_eq!(sent[0].1, start);
+        assert_eq!((dms.sent(), dms.failed(), dms.dropped()), (4, 0, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rate_limits_are_waited_out() {
+        let (dms, worker) = DmDispatcher::new(QUEUE_CAPACITY);
+        let sender = MockSender::default();
+        let c_token = CancellationToken::new();
+        let start = Instant::now();
+
+        sender.rate_limit(UserId::new(1), Duration::from_secs(3));
+        assert!(dms.send(UserId::new(1), text("Patience")));
+        for _ in 0..MAX_ATTEMPTS {
+            sender.rate_limit(UserId::new(2), Duration::from_secs(1));
+        }
+        assert!(dms.send(UserId::new(2), text("Never arrives")));
+
+        let run = tokio::spawn(worker.run(sender.clone(), DRAIN_GRACE, c_token.clone()));
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        c_token.cancel();
+        run.await.expect("Worker ran");
+
+        assert_eq!(
+            sender.sent(),
+            [(UserId::new(1), start + Duration::from_secs(3))]
+        );
+        assert_eq!((dms.sent(), dms.failed(), dms.dropped()), (1, 1, 0));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn full_queues_drop_instead_of_waiting() {
+        let (dms, worker) = DmDispatcher::new(2);
+
+        assert!(dms.send(UserId::new(1), text("One")));
+        assert!(dms.send(UserId::new(1), text("Two")));
+        assert!(!dms.send(UserId::new(1), text("Three")));
+        assert_eq!((dms.queued(), dms.dropped()), (2, 1));
+
+        drop(worker);
+        assert!(!dms.send(UserId::new(1), text("Four")));
+        assert_eq!(dms.dropped(), 2);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_drains_for_the_grace_period() {
+        let (dms, worker) = DmDispatcher::new(QUEUE_CAPACITY);
+        let sender = MockSender::default();
+        let c_token = CancellationToken::new();
+
+        for n in 0..10 {
+            assert!(dms.send(UserId::new(1), text(&n.to_string())));
+        }
+
+        // Runs out just before the fourth DM is due
+        let grace = (PACE * 3).saturating_sub(Duration::from_millis(1));
+        c_token.cancel();
+        worker.run(sender.clone(), grace, c_token).await;
+
+        // One straight away, then one every pace until the grace period runs out
+        assert_eq!(sender.sent().len(), 3);
+        assert_eq!((dms.sent(), dms.dropped()), (3, 7));
+        assert!(!dms.send(UserId::new(1), text("Too late")));
+    }
+}
@@ -18,15 +18,22 @@
 
 //! Errors for the `RSE` Discord integration
 
+use std::time::Duration;
+
 use poise::{
     BoxFuture, CreateReply, FrameworkError,
-    serenity_prelude::{Color, CreateEmbed, Timestamp},
+    serenity_prelude::{Color, CreateEmbed, CreateEmbedFooter, Timestamp},
 };
-use snafu::Snafu;
+use snafu::{Report, Snafu};
 
 /// Poise result type
-use rse_core::{Service, error::Error as RscErr, repo::StockRepository};
-use tracing::Level;
+use rse_core::{error::Error as RscErr, model::link::IdentityKind, repo::StockRepository};
+use tracing::Instrument;
+
+use crate::{
+    BotData,
+    i18n::{self, t},
+};
 
 /// Errors emitted by the discord integration. Need to sanitize this so it can be exposed back to
 /// Discord users
@@ -46,66 +53,609 @@ pub enum Error {
 
     #[snafu(display("Could not register your account!"))]
     RegistrationError { source: RscErr },
+
+    /// A user passed something that is not a valid ticker
+    #[snafu(display(r#""{input}" is not a valid ticker. {source}"#))]
+    InvalidTicker {
+        input: String,
+        source: rse_core::model::ticker::ParseError,
+    },
+
+    /// A user passed something that is not a valid Kromer address
+    #[snafu(display(r#""{input}" is not a valid Kromer address. {source}"#))]
+    InvalidAddress {
+        input: String,
+        source: rse_core::model::withdrawal::ParseError,
+    },
+
+    /// A user passed something that is not a valid price
+    #[snafu(display(r#""{input}" is not a valid price"#))]
+    InvalidPrice {
+        input: String,
+        source: rust_decimal::Error,
+    },
+
+    /// A user passed a price or number of shares the exchange can't take
+    #[snafu(display(r#""{input}" is not a valid {what}, it {source}"#))]
+    OutOfRange {
+        input: String,
+        what: &'static str,
+        source: rse_core::model::ValueError,
+    },
+
+    /// A user passed something that is not a valid account ID
+    #[snafu(display(r#""{input}" is not a valid account ID"#))]
+    InvalidUuid { input: String, source: uuid::Error },
+
+    /// A user passed a Minecraft username that couldn't be resolved
+    #[snafu(display("{source}"))]
+    PlayerLookup { source: rse_mojang::Error },
+
+    /// A user passed a combination of options that the command doesn't accept
+    #[snafu(display("{reason}"))]
+    InvalidOptions { reason: &'static str },
+
+    /// A user asked for something only admins may do
+    #[snafu(display("{reason}"))]
+    Forbidden { reason: &'static str },
+
+    /// A user ran a command needing a permission they weren't granted
+    #[snafu(display("You need the {permission} permission to do this"))]
+    MissingPermission {
+        permission: rse_core::model::guild::Permission,
+    },
+
+    /// A user tried to trade from a server that disabled trading
+    #[snafu(display("Trading is disabled in this server"))]
+    TradingDisabled,
+
+    /// Too many DMs were waiting to be sent to queue another
+    #[snafu(display("The DM queue is full"))]
+    DmQueueFull,
+
+    /// A snapshot of the exchange couldn't be taken
+    #[snafu(display("Could not take a snapshot: {source}"))]
+    Snapshot {
+        source: rse_core::snapshot::SnapshotError,
+    },
+
+    /// A snapshot was taken but couldn't be compressed or saved
+    #[snafu(display("Could not save the snapshot to {}: {source}", path.display()))]
+    SnapshotFile {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
 }
 
 pub fn on_error<R: StockRepository>(
-    error: FrameworkError<'_, Service<R>, Error>,
+    error: FrameworkError<'_, BotData<R>, Error>,
 ) -> BoxFuture<'_, ()> {
-    Box::pin(async move {
-        let span = tracing::span!(Level::TRACE, "on_error", error = error.to_string());
-        let _guard = span.enter();
-        match error {
-            FrameworkError::Command { error, ctx, .. } => {
-                let mut reply_embed = CreateEmbed::new()
-                    .title("Error!")
-                    .color(Color::RED)
-                    .timestamp(Timestamp::now());
-                match error {
-                    Error::RegistrationError { source } => {
-                        reply_embed = reply_embed
-                            .description(
-                                "Could not register your account, please try again later! If the issue persists, contact support.",
-                            );
-                        tracing::error!("couldn't register account: {source:?}");
-                    }
-                    Error::ServiceError {
-                        source: RscErr::UserNotFound,
-                    } => {
-                        reply_embed = reply_embed.description("This user does not have an account");
-                    }
-                    _ => {
-                        reply_embed = reply_embed.description(
-                            "Experienced an unexpected internal error, please try again later! If the issue persists, contact support.",
-                        );
-                    }
-                }
-
-                if let Err(res_err) = ctx
-                    .send(CreateReply::default().embed(reply_embed).ephemeral(true))
-                    .await
-                {
-                    tracing::warn!("Could not error gracefully: {res_err}");
-                } else {
-                    tracing::trace!("Responded to error gracefully");
-                }
-            }
-            FrameworkError::CommandPanic { payload, ctx, .. } => {
-                tracing::error!({ payload = payload }, "Panicked inside command");
-                let reply = CreateReply::default().embed(CreateEmbed::new()
-                    .title("Error!")
-                    .color(Color::RED)
-                    .timestamp(Timestamp::now())
-                    .description(
-                            "Experienced an internal error, please try again later! If the issue persists, contact support.",
-                    )).ephemeral(true);
-
-                if let Err(res_err) = ctx.send(reply).await {
-                    tracing::warn!("Could not error gracefully: {res_err}");
-                } else {
-                    tracing::trace!("Responded to error gracefully");
-                }
+    let span = error
+        .ctx()
+        .map_or_else(tracing::Span::current, crate::command_span);
+
+    Box::pin(handle_error(error).instrument(span))
+}
+
+/// Tells a user how long they must wait before using a command again, rounded up to the second
+fn cooldown_message(remaining: Duration, locale: &str) -> String {
+    let seconds = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+
+    if seconds == 1 {
+        t!(locale, "error.cooldown_one", seconds = seconds)
+    } else {
+        t!(locale, "error.cooldown_other", seconds = seconds)
+    }
+}
+
+/// Describes a command error in a way that is safe to show the user. Errors the user caused are
+/// shown as is, in English, and the details of the rest are only logged
+fn user_message(error: &Error, locale: &str) -> String {
+    match error {
+        Error::RegistrationError { .. } => t!(locale, "error.registration_failed"),
+        Error::ServiceError {
+            source: RscErr::UserNotFound { kind },
+        } => match kind {
+            IdentityKind::Discord => t!(locale, "error.no_account"),
+            IdentityKind::Minecraft => t!(locale, "error.no_minecraft_account"),
+            IdentityKind::Internal => t!(locale, "error.unknown_account"),
+        },
+        Error::PlayerLookup { source } if source.is_unavailable() => {
+            tracing::warn!(%source, "couldn't resolve Minecraft username");
+            t!(locale, "error.player_lookup")
+        }
+        Error::TradingDisabled => t!(locale, "error.trading_disabled"),
+        Error::ServiceError {
+            source: RscErr::MarketClosed { opens_at },
+        } => t!(locale, "error.market_closed", opens = opens_at.timestamp()),
+        Error::ServiceError {
+            source: RscErr::AccountTooNew { eligible_at },
+        } => t!(
+            locale,
+            "error.account_too_new",
+            eligible = eligible_at.timestamp()
+        ),
+        Error::ServiceError {
+            source: RscErr::Busy,
+        } => t!(locale, "error.busy"),
+        Error::MissingPermission { permission } => {
+            t!(locale, "error.missing_permission", permission = permission)
+        }
+        // Caused by the user, and safe to show them as is
+        err @ (Error::InvalidTicker { .. }
+        | Error::InvalidAddress { .. }
+        | Error::InvalidPrice { .. }
+        | Error::OutOfRange { .. }
+        | Error::InvalidUuid { .. }
+        | Error::PlayerLookup { .. }
+        | Error::InvalidOptions { .. }
+        | Error::Forbidden { .. }
+        | Error::ServiceError {
+            source:
+                RscErr::InsufficientFunds
+                | RscErr::InsufficientShares
+                | RscErr::StockNotFound { .. }
+                | RscErr::StockHalted { .. }
+                | RscErr::OrderNotFound { .. }
+                | RscErr::InvalidOrder { .. }
+                | RscErr::PriceOutOfBand { .. }
+                | RscErr::InvalidDividend { .. }
+                | RscErr::InvalidWithdrawal { .. }
+                | RscErr::ImportTooLarge { .. }
+                | RscErr::InvalidMetadata { .. }
+                | RscErr::WithdrawalNotFound { .. }
+                | RscErr::InvalidAdjustment { .. }
+                | RscErr::AdjustmentNotFound { .. }
+                | RscErr::AlreadyReversed { .. }
+                | RscErr::InvalidLinkCode
+                | RscErr::AccountExists
+                | RscErr::PlayerRegistered { .. }
+                | RscErr::AccountNotEmpty { .. }
+                | RscErr::MergeConflict { .. }
+                | RscErr::NotStockOwner { .. }
+                | RscErr::PrivateAccount
+                | RscErr::StockExists { .. }
+                | RscErr::TickerReserved { .. }
+                | RscErr::InvalidStock { .. }
+                | RscErr::NoShareholders { .. }
+                | RscErr::IssuanceCapExceeded { .. },
+        }) => err.to_string(),
+        _ => t!(locale, "error.unexpected"),
+    }
+}
+
+impl Error {
+    /// A short, stable name for what went wrong, the exchange's own for errors that came from it
+    pub(crate) const fn code(&self) -> &'static str {
+        match self {
+            Self::ServiceError { source } | Self::RegistrationError { source } => source.code(),
+            Self::PoiseError { .. } => "discord_error",
+            Self::InvalidTicker { .. } => "invalid_ticker",
+            Self::InvalidAddress { .. } => "invalid_address",
+            Self::InvalidPrice { .. } => "invalid_price",
+            Self::OutOfRange { .. } => "out_of_range",
+            Self::InvalidUuid { .. } => "invalid_uuid",
+            Self::PlayerLookup { .. } => "player_lookup",
+            Self::InvalidOptions { .. } => "invalid_options",
+            Self::Forbidden { .. } => "forbidden",
+            Self::MissingPermission { .. } => "missing_permission",
+            Self::TradingDisabled => "trading_disabled",
+            Self::DmQueueFull => "dm_queue_full",
+            Self::Snapshot { .. } => "snapshot_failed",
+            Self::SnapshotFile { .. } => "snapshot_file",
+        }
+    }
+}
+
+/// A short reference shown with an error and logged alongside it, e.g. `RSE-5f3a9c`, so a
+/// screenshot of the error can be matched to its log line
+fn reference() -> String {
+    format!("RSE-{:06x}", fastrand::u32(..0x0100_0000))
+}
+
+/// Describes an option poise couldn't parse. Our own argument types fail with an [`Error`] saying
+/// what was wrong, while poise's only say why
+fn argument_message(
+    error: &(dyn std::error::Error + Send + Sync + 'static),
+    input: Option<&str>,
+    locale: &str,
+) -> String {
+    match error.downcast_ref::<Error>() {
+        Some(error) => user_message(error, locale),
+        None => t!(
+            locale,
+            "error.invalid_option",
+            input = input.unwrap_or_default(),
+            reason = error
+        ),
+    }
+}
+
+/// How long the user has to wait if the exchange turned them away for doing too much
+const fn rate_limited(error: &Error) -> Option<Duration> {
+    match error {
+        Error::ServiceError {
+            source: RscErr::RateLimited { retry_after },
+        }
+        | Error::RegistrationError {
+            source: RscErr::RateLimited { retry_after },
+        } => Some(*retry_after),
+        _ => None,
+    }
+}
+
+/// Replies to the command saying to wait `remaining` before trying again
+async fn reply_with_cooldown<R: StockRepository>(
+    ctx: poise::Context<'_, BotData<R>, Error>,
+    remaining: Duration,
+    locale: &str,
+) {
+    let reply = CreateReply::default()
+        .embed(
+            CreateEmbed::new()
+                .title(t!(locale, "error.cooldown_title"))
+                .color(Color::ORANGE)
+                .timestamp(Timestamp::now())
+                .description(cooldown_message(remaining, locale)),
+        )
+        .ephemeral(true);
+
+    if let Err(res_err) = ctx.send(reply).await {
+        tracing::warn!("Could not reply to cooldown: {res_err}");
+    }
+}
+
+/// Replies to the command with an error embed describing what went wrong, with `reference` in
+/// its footer
+async fn reply_with_error<R: StockRepository>(
+    ctx: poise::Context<'_, BotData<R>, Error>,
+    description: String,
+    reference: &str,
+    locale: &str,
+) {
+    let reply_embed = CreateEmbed::new()
+        .title(t!(locale, "error.title"))
+        .color(Color::RED)
+        .timestamp(Timestamp::now())
+        .description(description)
+        .footer(CreateEmbedFooter::new(format!("ref: {reference}")));
+
+    if let Err(res_err) = ctx
+        .send(CreateReply::default().embed(reply_embed).ephemeral(true))
+        .await
+    {
+        tracing::warn!("Could not error gracefully: {res_err}");
+    } else {
+        tracing::trace!("Responded to error gracefully");
+    }
+}
+
+async fn handle_error<R: StockRepository>(error: FrameworkError<'_, BotData<R>, Error>) {
+    if let Some(ctx) = error.ctx() {
+        crate::inflight::finish(ctx, false);
+    }
+
+    match error {
+        FrameworkError::Command { error, ctx, .. }
+        | FrameworkError::CommandCheckFailed {
+            error: Some(error),
+            ctx,
+            ..
+        } => {
+            let locale = &i18n::locale(ctx).await;
+
+            if let Some(retry_after) = rate_limited(&error) {
+                tracing::debug!(?retry_after, "Rate limited by the exchange");
+                reply_with_cooldown(ctx, retry_after, locale).await;
+            } else {
+                let reference = reference();
+                tracing::error!(
+                    reference,
+                    code = error.code(),
+                    error = %Report::from_error(&error),
+                    "Command failed"
+                );
+                reply_with_error(ctx, user_message(&error, locale), &reference, locale).await;
             }
-            _ => tracing::warn!("Experienced a Discord Error"),
         }
-    })
+        FrameworkError::ArgumentParse {
+            error, input, ctx, ..
+        } => {
+            let reference = reference();
+            tracing::error!(reference, input, %error, "Couldn't parse command options");
+            let locale = &i18n::locale(ctx).await;
+            reply_with_error(
+                ctx,
+                argument_message(&*error, input.as_deref(), locale),
+                &reference,
+                locale,
+            )
+            .await;
+        }
+        FrameworkError::CommandPanic { payload, ctx, .. } => {
+            let reference = reference();
+            tracing::error!(reference, payload, "Panicked inside command");
+            let locale = &i18n::locale(ctx).await;
+            reply_with_error(ctx, t!(locale, "error.panic"), &reference, locale).await;
+        }
+        FrameworkError::CooldownHit {
+            remaining_cooldown,
+            ctx,
+            ..
+        } => {
+            tracing::debug!(?remaining_cooldown, "Command on cooldown");
+            let locale = &i18n::locale(ctx).await;
+            reply_with_cooldown(ctx, remaining_cooldown, locale).await;
+        }
+        _ => tracing::warn!("Experienced a Discord Error: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+
+    use rse_core::{
+        Service,
+        model::{guild::Permission, link::Identity, ticker::Ticker},
+        repo::Error as RepError,
+        test_util::{ChaosRepo, Stub},
+    };
+    use snafu::ResultExt;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn service() -> (ChaosRepo<Stub>, Service<ChaosRepo<Stub>>) {
+        let chaos = ChaosRepo::new(Stub::default());
+        (chaos.clone(), Service::new(chaos))
+    }
+
+    fn ticker() -> Ticker {
+        Ticker::try_from("ABC").expect("Valid ticker")
+    }
+
+    #[tokio::test]
+    async fn failed_registration_asks_to_try_again() {
+        let (chaos, service) = service();
+        chaos.fail_next("register_user", RepError::Unspecified);
+
+        let err = service
+            .register_account(Some(NonZeroU64::MIN), None)
+            .await
+            .context(RegistrationSnafu)
+            .expect_err("Registration fails");
+
+        assert_eq!(
+            user_message(&err, "en"),
+            t!("en", "error.registration_failed")
+        );
+    }
+
+    #[tokio::test]
+    async fn missing_account_is_explained() {
+        let (_, service) = service();
+
+        let err = Error::from(
+            service
+                .resolve(&Identity::Discord(NonZeroU64::MIN))
+                .await
+                .expect_err("Not registered"),
+        );
+        assert_eq!(user_message(&err, "en"), t!("en", "error.no_account"));
+
+        let err = Error::from(
+            service
+                .resolve(&Identity::Minecraft(Uuid::nil()))
+                .await
+                .expect_err("Not linked"),
+        );
+        assert_eq!(
+            user_message(&err, "en"),
+            t!("en", "error.no_minecraft_account")
+        );
+
+        let err = Error::from(
+            service
+                .resolve(&Identity::Internal(Uuid::nil()))
+                .await
+                .expect_err("No such account"),
+        );
+        assert_eq!(user_message(&err, "en"), t!("en", "error.unknown_account"));
+    }
+
+    #[tokio::test]
+    async fn database_errors_are_hidden() {
+        let (chaos, service) = service();
+
+        for err in [RepError::Unavailable, RepError::Unspecified] {
+            chaos.fail_next("stock_info", err);
+
+            let err = Error::from(
+                service
+                    .get_stock_info(&ticker())
+                    .await
+                    .expect_err("Lookup fails"),
+            );
+
+            assert_eq!(user_message(&err, "en"), t!("en", "error.unexpected"));
+        }
+    }
+
+    #[tokio::test]
+    async fn conflicts_ask_to_try_again() {
+        let (chaos, service) = service();
+        chaos.fail_next("stock_info", RepError::Conflict);
+
+        let err = Error::from(
+            service
+                .get_stock_info(&ticker())
+                .await
+                .expect_err("Lookup fails"),
+        );
+
+        assert_eq!(user_message(&err, "en"), t!("en", "error.busy"));
+    }
+
+    #[test]
+    fn cooldowns_round_up_to_the_second() {
+        assert_eq!(
+            cooldown_message(Duration::from_millis(200), "en"),
+            "You're using this command too quickly, try again in 1 second"
+        );
+        assert_eq!(
+            cooldown_message(Duration::from_millis(4100), "en"),
+            "You're using this command too quickly, try again in 5 seconds"
+        );
+    }
+
+    #[tokio::test]
+    async fn user_errors_are_shown_as_is() {
+        let (chaos, service) = service();
+        chaos.fail_next("stock_info", RepError::StockNotFound { ticker: ticker() });
+
+        let err = Error::from(
+            service
+                .get_stock_info(&ticker())
+                .await
+                .expect_err("Lookup fails"),
+        );
+
+        assert_eq!(
+            user_message(&err, "fr"),
+            r#"The stock "ABC" does not exist"#
+        );
+    }
+
+    #[test]
+    fn missing_permissions_are_named() {
+        let err = Error::MissingPermission {
+            permission: Permission::Halt,
+        };
+
+        assert_eq!(
+            user_message(&err, "en"),
+            "You need the `halt` permission to do this"
+        );
+    }
+
+    #[test]
+    fn closed_market_says_when_it_opens() {
+        let err = Error::ServiceError {
+            source: RscErr::MarketClosed {
+                opens_at: chrono::DateTime::from_timestamp(1_760_968_800, 0).expect("Valid time"),
+            },
+        };
+
+        assert_eq!(
+            user_message(&err, "en"),
+            "The market is closed, it opens <t:1760968800:R>"
+        );
+    }
+
+    #[test]
+    fn new_accounts_are_told_when_they_unlock() {
+        let err = Error::ServiceError {
+            source: RscErr::AccountTooNew {
+                eligible_at: chrono::DateTime::from_timestamp(1_760_968_800, 0)
+                    .expect("Valid time"),
+            },
+        };
+
+        assert_eq!(
+            user_message(&err, "en"),
+            "Your account is too new for that, it unlocks <t:1760968800:R>"
+        );
+    }
+
+    #[test]
+    fn registered_players_name_their_account() {
+        let err = Error::ServiceError {
+            source: RscErr::PlayerRegistered {
+                id: uuid::Uuid::nil(),
+            },
+        };
+
+        assert_eq!(
+            user_message(&err, "en"),
+            "That Minecraft account is already registered to 00000000-0000-0000-0000-000000000000"
+        );
+    }
+
+    #[test]
+    fn failed_player_lookups_ask_for_the_uuid() {
+        let unavailable = Error::PlayerLookup {
+            source: rse_mojang::Error::RateLimited,
+        };
+        let missing = Error::PlayerLookup {
+            source: rse_mojang::Error::NotFound {
+                name: "nobody".to_owned(),
+            },
+        };
+
+        assert_eq!(
+            user_message(&unavailable, "en"),
+            t!("en", "error.player_lookup")
+        );
+        assert_eq!(
+            user_message(&missing, "en"),
+            r#"There is no Minecraft player named "nobody""#
+        );
+    }
+
+    #[test]
+    fn bad_tickers_are_caught_before_commands_run() {
+        use crate::commands::TickerArg;
+
+        for input in ["abc", "AbC", "$abc", " ABC "] {
+            let ticker: TickerArg = input.parse().expect("Valid ticker");
+            assert_eq!(ticker.as_str(), "ABC");
+        }
+
+        let error: Box<dyn std::error::Error + Send + Sync> =
+            Box::new("ab".parse::<TickerArg>().expect_err("Too short"));
+        assert_eq!(
+            argument_message(&*error, Some("ab"), "en"),
+            r#""ab" is not a valid ticker. Length must be between 3 and 5 characters"#
+        );
+
+        let error: Box<dyn std::error::Error + Send + Sync> =
+            Box::new("x".parse::<u32>().expect_err("Not a number"));
+        assert_eq!(
+            argument_message(&*error, Some("x"), "en"),
+            r#""x" isn't valid here. invalid digit found in string"#
+        );
+    }
+
+    #[test]
+    fn rate_limits_are_shown_as_cooldowns() {
+        let retry_after = Duration::from_secs(20);
+        let trade = Error::ServiceError {
+            source: RscErr::RateLimited { retry_after },
+        };
+        let registration = Error::RegistrationError {
+            source: RscErr::RateLimited { retry_after },
+        };
+        let busy = Error::ServiceError {
+            source: RscErr::Busy,
+        };
+
+        assert_eq!(rate_limited(&trade), Some(retry_after));
+        assert_eq!(rate_limited(&registration), Some(retry_after));
+        assert_eq!(rate_limited(&busy), None);
+    }
+
+    #[test]
+    fn references_are_short_and_codes_come_from_the_exchange() {
+        let reference = reference();
+        let suffix = reference.strip_prefix("RSE-").expect("Prefixed");
+        assert_eq!(suffix.len(), 6);
+        assert!(suffix.chars().all(|c| c.is_ascii_hexdigit()), "{reference}");
+
+        let err = Error::RegistrationError {
+            source: RscErr::AccountExists,
+        };
+        assert_eq!(err.code(), "account_exists");
+        assert_eq!(Error::TradingDisabled.code(), "trading_disabled");
+    }
 }
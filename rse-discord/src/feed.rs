@@ -0,0 +1,287 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Batching of trades posted to the market feed, so a busy market posts a summary every so often
+//! rather than a message per trade
+
+use std::time::Duration;
+
+use poise::serenity_prelude::{Color, CreateEmbed, CreateEmbedFooter};
+use rse_core::model::{Price, order::Fill, ticker::Ticker};
+use rust_decimal::Decimal;
+use tokio::time::Instant;
+
+/// The most fields Discord allows in a single embed
+const MAX_EMBED_FIELDS: usize = 25;
+
+/// Collects trades into batches, each closed once it is `window` old or holds `max_trades` trades,
+/// whichever comes first. Trades worth at least `large_trade` are left out of batches, to be
+/// posted on their own straight away. Time is passed in rather than read, so the window can be
+/// tested without waiting on it.
+#[derive(Debug)]
+pub(crate) struct TradeBatcher {
+    window: Duration,
+    max_trades: usize,
+    large_trade: Option<Decimal>,
+    /// When the first trade of the open batch arrived
+    opened: Option<Instant>,
+    trades: usize,
+    /// Activity per stock, in the order the stocks first traded
+    activity: Vec<(Ticker, Activity)>,
+}
+
+/// A stock's trades within a batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Activity {
+    /// The number of trades
+    pub trades: u32,
+    /// The number of shares traded
+    pub shares: u64,
+    /// The total value traded, excluding fees
+    pub volume: Decimal,
+    /// The price of the most recent trade
+    pub last: Price,
+}
+
+impl Activity {
+    /// The volume weighted average price
+    pub(crate) fn vwap(&self) -> Decimal {
+        if self.shares == 0 {
+            self.last.get()
+        } else {
+            self.volume / Decimal::from(self.shares)
+        }
+    }
+}
+
+impl TradeBatcher {
+    /// Creates a new [`TradeBatcher`]. A `max_trades` of 0 is treated as 1.
+    pub(crate) fn new(window: Duration, max_trades: usize, large_trade: Option<Decimal>) -> Self {
+        Self {
+            window,
+            max_trades: max_trades.max(1),
+            large_trade,
+            opened: None,
+            trades: 0,
+            activity: Vec::new(),
+        }
+    }
+
+    /// Adds `fills` on `ticker` that happened at `now` to the open batch, opening one if needed.
+    /// Returns the fills large enough to be posted on their own, which are not batched.
+    pub(crate) fn push(&mut self, ticker: Ticker, fills: &[Fill], now: Instant) -> Vec<Fill> {
+        let mut large = Vec::new();
+
+        for fill in fills {
+            if self.large_trade.is_some_and(|min| fill.notional() >= min) {
+                large.push(*fill);
+                continue;
+            }
+
+            self.opened.get_or_insert(now);
+            self.trades += 1;
+
+            let shares = u64::from(fill.shares.get());
+            match self.activity.iter_mut().find(|(t, _)| *t == ticker) {
+                Some((_, activity)) => {
+                    activity.trades += 1;
+                    activity.shares += shares;
+                    activity.volume += fill.notional();
+                    activity.last = fill.price;
+                }
+                None => self.activity.push((
+                    ticker,
+                    Activity {
+                        trades: 1,
+                        shares,
+                        volume: fill.notional(),
+                        last: fill.price,
+                    },
+                )),
+            }
+        }
+
+        large
+    }
+
+    /// When the open batch's window closes, or [`None`] if there is no open batch
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.opened.map(|opened| opened + self.window)
+    }
+
+    /// Whether the open batch should be posted at `now`
+    pub(crate) fn is_due(&self, now: Instant) -> bool {
+        self.trades >= self.max_trades || self.deadline().is_some_and(|deadline| deadline <= now)
+    }
+
+    /// Closes the open batch, returning each stock's activity in it. Returns [`None`] if nothing
+    /// traded since the last batch.
+    pub(crate) fn take(&mut self) -> Option<Vec<(Ticker, Activity)>> {
+        self.opened = None;
+        self.trades = 0;
+
+        if self.activity.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.activity))
+        }
+    }
+}
+
+/// A summary of a batch of trades, one field per stock
+pub(crate) fn batch_embed(batch: &[(Ticker, Activity)]) -> CreateEmbed {
+    let trades: u32 = batch.iter().map(|(_, activity)| activity.trades).sum();
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("{trades} recent trades"))
+        .color(Color::BLURPLE);
+
+    for (ticker, activity) in batch.iter().take(MAX_EMBED_FIELDS) {
+        embed = embed.field(
+            format!("${ticker}"),
+            format!(
+                "{} trades, {} shares\nVolume {:.2}\nVWAP {:.2}, last {}",
+                activity.trades,
+                activity.shares,
+                activity.volume,
+                activity.vwap(),
+                activity.last
+            ),
+            true,
+        );
+    }
+
+    if batch.len() > MAX_EMBED_FIELDS {
+        embed = embed.footer(CreateEmbedFooter::new(format!(
+            "And {} more stocks",
+            batch.len() - MAX_EMBED_FIELDS
+        )));
+    }
+
+    embed
+}
+
+/// The message for a trade posted on its own
+pub(crate) fn large_trade(ticker: Ticker, fill: &Fill) -> String {
+    format!(
+        "Large trade: {} ${ticker} @ {} ({:.2})",
+        fill.shares,
+        fill.price,
+        fill.notional()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn fill(price: i64, shares: u32) -> Fill {
+        Fill {
+            buy_order: 1,
+            sell_order: 2,
+            buyer: Uuid::nil(),
+            seller: Uuid::nil(),
+            price: Price::new(Decimal::from(price)).expect("Valid price"),
+            shares: rse_core::model::Shares::new(shares).expect("Valid shares"),
+            fee: Decimal::ZERO,
+        }
+    }
+
+    fn ticker(s: &str) -> Ticker {
+        Ticker::try_from(s).expect("Valid ticker")
+    }
+
+    #[test]
+    fn batches_close_after_the_window() {
+        let mut batcher = TradeBatcher::new(Duration::from_secs(10), 10, None);
+        let start = Instant::now();
+
+        assert_eq!(batcher.deadline(), None);
+        assert!(!batcher.is_due(start));
+        assert_eq!(batcher.take(), None);
+
+        batcher.push(ticker("ABC"), &[fill(10, 5)], start);
+        batcher.push(
+            ticker("ABC"),
+            &[fill(12, 5)],
+            start + Duration::from_secs(4),
+        );
+        assert_eq!(batcher.deadline(), Some(start + Duration::from_secs(10)));
+        assert!(!batcher.is_due(start + Duration::from_secs(9)));
+        assert!(batcher.is_due(start + Duration::from_secs(10)));
+
+        let batch = batcher.take().expect("Something traded");
+        assert_eq!(
+            batch,
+            [(
+                ticker("ABC"),
+                Activity {
+                    trades: 2,
+                    shares: 10,
+                    volume: Decimal::from(110),
+                    last: Price::new(Decimal::from(12)).expect("Valid price"),
+                }
+            )]
+        );
+        assert_eq!(batch[0].1.vwap(), Decimal::from(11));
+
+        // The next batch's window starts from its own first trade
+        assert_eq!(batcher.deadline(), None);
+        batcher.push(
+            ticker("XYZ"),
+            &[fill(1, 1)],
+            start + Duration::from_secs(30),
+        );
+        assert_eq!(batcher.deadline(), Some(start + Duration::from_secs(40)));
+    }
+
+    #[test]
+    fn batches_close_once_full() {
+        let mut batcher = TradeBatcher::new(Duration::from_secs(10), 3, None);
+        let start = Instant::now();
+
+        batcher.push(ticker("ABC"), &[fill(10, 1), fill(10, 1)], start);
+        assert!(!batcher.is_due(start));
+        batcher.push(ticker("XYZ"), &[fill(5, 1)], start);
+        assert!(batcher.is_due(start));
+
+        let batch = batcher.take().expect("Something traded");
+        let tickers: Vec<_> = batch.iter().map(|(t, _)| *t).collect();
+        assert_eq!(tickers, [ticker("ABC"), ticker("XYZ")]);
+        assert!(!batcher.is_due(start));
+    }
+
+    #[test]
+    fn large_trades_skip_the_batch() {
+        let mut batcher = TradeBatcher::new(Duration::from_secs(10), 10, Some(Decimal::from(100)));
+        let start = Instant::now();
+
+        let large = batcher.push(ticker("ABC"), &[fill(10, 5), fill(10, 10)], start);
+        assert_eq!(large, [fill(10, 10)]);
+
+        let batch = batcher.take().expect("Something traded");
+        assert_eq!(batch[0].1.trades, 1);
+
+        // A batch of only large trades never opens
+        batcher.push(ticker("ABC"), &[fill(50, 50)], start);
+        assert_eq!(batcher.deadline(), None);
+        assert_eq!(batcher.take(), None);
+    }
+}
@@ -0,0 +1,199 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Health of the bot's connection to the Discord gateway
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, LazyLock, Mutex, PoisonError},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::{ConnectionStage, ShardManager};
+
+/// When the bot was started, forced by [`start`](crate::start)
+pub(crate) static STARTED: LazyLock<DateTime<Utc>> = LazyLock::new(Utc::now);
+
+/// The sessions each shard has started with the gateway, recorded by the event handler as they
+/// become ready or resume
+#[derive(Debug, Default)]
+pub(crate) struct Sessions {
+    shards: Mutex<BTreeMap<u32, ShardSessions>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ShardSessions {
+    started: u64,
+    latest: DateTime<Utc>,
+}
+
+impl Sessions {
+    /// Records `shard` starting a session at `time`, returning how many times it has reconnected
+    /// since it first connected
+    pub fn record(&self, shard: u32, time: DateTime<Utc>) -> u64 {
+        let mut shards = self.shards.lock().unwrap_or_else(PoisonError::into_inner);
+        let sessions = shards
+            .entry(shard)
+            .and_modify(|sessions| sessions.started += 1)
+            .or_insert(ShardSessions {
+                started: 1,
+                latest: time,
+            });
+        sessions.latest = time;
+
+        sessions.started - 1
+    }
+
+    /// How many times `shard` reconnected, and when its current session started
+    fn get(&self, shard: u32) -> (u64, Option<DateTime<Utc>>) {
+        let shards = self.shards.lock().unwrap_or_else(PoisonError::into_inner);
+
+        shards.get(&shard).map_or((0, None), |sessions| {
+            (sessions.started - 1, Some(sessions.latest))
+        })
+    }
+}
+
+/// A handle on the bot's gateway connection, for checking that it is still up from outside the bot
+#[derive(Debug, Clone)]
+pub struct Gateway {
+    shards: Arc<ShardManager>,
+    sessions: Arc<Sessions>,
+}
+
+impl Gateway {
+    pub(crate) const fn new(shards: Arc<ShardManager>, sessions: Arc<Sessions>) -> Self {
+        Self { shards, sessions }
+    }
+
+    /// The state of every shard's connection right now
+    pub async fn health(&self) -> GatewayHealth {
+        let runners = self.shards.runners.lock().await;
+
+        let shards = runners
+            .iter()
+            .map(|(id, runner)| {
+                let (reconnects, connected_since) = self.sessions.get(id.0);
+
+                ShardHealth {
+                    id: id.0,
+                    connected: runner.stage == ConnectionStage::Connected,
+                    latency: runner.latency,
+                    reconnects,
+                    connected_since,
+                }
+            })
+            .collect();
+
+        GatewayHealth { shards }
+    }
+}
+
+/// The state of a single shard's connection to the gateway
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShardHealth {
+    /// The shard's ID
+    pub id: u32,
+    /// Whether the shard is connected, rather than connecting, resuming or disconnected
+    pub connected: bool,
+    /// The latest heartbeat round trip, if one has been measured yet
+    pub latency: Option<Duration>,
+    /// How many times the shard reconnected since it first connected
+    pub reconnects: u64,
+    /// When the shard's current session started, if it ever connected
+    pub connected_since: Option<DateTime<Utc>>,
+}
+
+/// A snapshot of the bot's connection to the gateway, across every shard
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GatewayHealth {
+    /// Every shard that has been started
+    pub shards: Vec<ShardHealth>,
+}
+
+impl GatewayHealth {
+    /// Whether every shard is connected. Not the case until the first shard has started
+    #[must_use]
+    pub fn is_connected(&self) -> bool {
+        !self.shards.is_empty() && self.shards.iter().all(|shard| shard.connected)
+    }
+
+    /// How many shards are connected
+    #[must_use]
+    pub fn connected(&self) -> usize {
+        self.shards.iter().filter(|shard| shard.connected).count()
+    }
+
+    /// The slowest heartbeat round trip of any shard, if one has been measured yet
+    #[must_use]
+    pub fn latency(&self) -> Option<Duration> {
+        self.shards.iter().filter_map(|shard| shard.latency).max()
+    }
+
+    /// How many times shards reconnected in total
+    #[must_use]
+    pub fn reconnects(&self) -> u64 {
+        self.shards.iter().map(|shard| shard.reconnects).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    #[test]
+    fn only_sessions_after_the_first_are_reconnects() {
+        let sessions = Sessions::default();
+        let start = Utc::now();
+        let later = start + TimeDelta::minutes(5);
+
+        assert_eq!(sessions.get(0), (0, None));
+        assert_eq!(sessions.record(0, start), 0);
+        assert_eq!(sessions.record(1, start), 0);
+        assert_eq!(sessions.record(0, later), 1);
+        assert_eq!(sessions.get(0), (1, Some(later)));
+        assert_eq!(sessions.get(1), (0, Some(start)));
+    }
+
+    #[test]
+    fn every_shard_must_be_connected() {
+        let shard = |id, connected, reconnects| ShardHealth {
+            id,
+            connected,
+            latency: Some(Duration::from_millis(u64::from(id) * 10)),
+            reconnects,
+            connected_since: None,
+        };
+
+        assert!(!GatewayHealth::default().is_connected());
+
+        let mut health = GatewayHealth {
+            shards: vec![shard(1, true, 2), shard(2, true, 0)],
+        };
+        assert!(health.is_connected());
+        assert_eq!(health.latency(), Some(Duration::from_millis(20)));
+        assert_eq!(health.reconnects(), 2);
+
+        health.shards.push(shard(3, false, 1));
+        assert!(!health.is_connected());
+        assert_eq!((health.connected(), health.reconnects()), (2, 3));
+    }
+}
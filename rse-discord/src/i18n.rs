@@ -0,0 +1,254 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Localized text for replies. Messages are looked up by key in catalogs embedded from
+//! `locales/<locale>.toml`, falling back to English for locales, or keys, that aren't translated.
+
+use std::{collections::HashMap, fmt::Display, sync::LazyLock};
+
+use rse_core::repo::StockRepository;
+
+use crate::Context;
+
+/// The locale used when neither the user's nor their server's is shipped, and for keys missing
+/// from theirs
+pub const FALLBACK: &str = "en";
+
+/// Every shipped catalog, as `(locale, contents)`
+const RESOURCES: &[(&str, &str)] = include!(concat!(env!("OUT_DIR"), "/locales.rs"));
+
+static MESSAGES: LazyLock<Messages> =
+    LazyLock::new(|| Messages::parse(RESOURCES).expect("Shipped locales are valid"));
+
+/// Looks up a message in the given locale, filling in its placeholders, e.g.
+/// `t!(locale, "stocks.page", page = 1, pages = 3)`
+macro_rules! t {
+    ($locale:expr, $key:literal $(, $name:ident = $value:expr)* $(,)?) => {
+        $crate::i18n::message($locale, $key, &[$((stringify!($name), &$value)),*])
+    };
+}
+
+pub(crate) use t;
+
+/// Message catalogs keyed by locale, each mapping a dotted key such as `error.title` to its text
+#[derive(Debug)]
+pub struct Messages {
+    catalogs: HashMap<String, HashMap<String, String>>,
+}
+
+impl Messages {
+    /// Parses catalogs from TOML, flattening nested tables into dotted keys
+    fn parse(resources: &[(&str, &str)]) -> Result<Self, String> {
+        let mut catalogs = HashMap::new();
+
+        for (locale, contents) in resources {
+            let table: toml::Table =
+                toml::from_str(contents).map_err(|err| format!("{locale}: {err}"))?;
+            let mut catalog = HashMap::new();
+            flatten("", table, &mut catalog).map_err(|err| format!("{locale}: {err}"))?;
+            catalogs.insert((*locale).to_owned(), catalog);
+        }
+
+        if !catalogs.contains_key(FALLBACK) {
+            return Err(format!("missing the `{FALLBACK}` fallback locale"));
+        }
+
+        Ok(Self { catalogs })
+    }
+
+    /// Whether `locale`, or its language without a region, has a catalog
+    fn supports(&self, locale: &str) -> bool {
+        let language = locale
+            .split_once('-')
+            .map_or(locale, |(language, _)| language);
+
+        self.catalogs.contains_key(locale) || self.catalogs.contains_key(language)
+    }
+
+    /// Gets the message for `key`, trying the exact locale, then its language without a region,
+    /// e.g. `en` for `en-GB`, then the fallback. Returns the key itself if no catalog has it.
+    fn get(&self, locale: &str, key: &str, args: &[(&str, &(dyn Display + Sync))]) -> String {
+        let language = locale
+            .split_once('-')
+            .map_or(locale, |(language, _)| language);
+
+        let template = [locale, language, FALLBACK]
+            .into_iter()
+            .find_map(|locale| self.catalogs.get(locale)?.get(key));
+
+        if let Some(template) = template {
+            interpolate(template, args)
+        } else {
+            tracing::warn!("Missing message `{key}`");
+            key.to_owned()
+        }
+    }
+}
+
+/// Gets the message for `key` in `locale`, replacing each `{name}` with the matching argument.
+/// Prefer the [`t!`] macro.
+pub fn message(locale: &str, key: &str, args: &[(&str, &(dyn Display + Sync))]) -> String {
+    MESSAGES.get(locale, key, args)
+}
+
+/// Whether replies can be written in `locale`, or at least its language
+pub fn is_supported(locale: &str) -> bool {
+    MESSAGES.supports(locale)
+}
+
+/// The locale the invoking user has Discord set to if it is shipped, otherwise the default of the
+/// server they're in, otherwise the fallback. Settings that can't be looked up count as unset, so
+/// this never fails.
+pub async fn locale<R: StockRepository>(ctx: Context<'_, R>) -> String {
+    if let Some(locale) = ctx.locale().filter(|locale| is_supported(locale)) {
+        return locale.to_owned();
+    }
+
+    if let Some(guild) = ctx.guild_id()
+        && let Ok(settings) = ctx.data().service().guild_settings(guild.into()).await
+        && let Some(locale) = settings.locale
+    {
+        return locale;
+    }
+
+    FALLBACK.to_owned()
+}
+
+fn flatten(
+    prefix: &str,
+    table: toml::Table,
+    catalog: &mut HashMap<String, String>,
+) -> Result<(), String> {
+    for (name, value) in table {
+        let key = if prefix.is_empty() {
+            name
+        } else {
+            format!("{prefix}.{name}")
+        };
+
+        match value {
+            toml::Value::String(text) => {
+                catalog.insert(key, text);
+            }
+            toml::Value::Table(table) => flatten(&key, table, catalog)?,
+            _ => return Err(format!("`{key}` must be a string or table")),
+        }
+    }
+
+    Ok(())
+}
+
+/// Replaces each `{name}` in `template` with the matching argument. Placeholders without an
+/// argument are left as is.
+fn interpolate(template: &str, args: &[(&str, &(dyn Display + Sync))]) -> String {
+    let mut buff = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        buff.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let arg = rest.find('}').and_then(|end| {
+            let name = &rest[1..end];
+            let (_, value) = args.iter().find(|(arg, _)| *arg == name)?;
+            Some((end, value))
+        });
+
+        if let Some((end, value)) = arg {
+            buff.push_str(&value.to_string());
+            rest = &rest[end + 1..];
+        } else {
+            buff.push('{');
+            rest = &rest[1..];
+        }
+    }
+
+    buff.push_str(rest);
+
+    buff
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    fn placeholders(template: &str) -> BTreeSet<&str> {
+        template
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}'))
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    #[test]
+    fn every_locale_matches_english() {
+        let english = &MESSAGES.catalogs[FALLBACK];
+
+        for (locale, catalog) in &MESSAGES.catalogs {
+            for (key, template) in english {
+                let translated = catalog
+                    .get(key)
+                    .unwrap_or_else(|| panic!("`{locale}` is missing `{key}`"));
+
+                assert_eq!(
+                    placeholders(translated),
+                    placeholders(template),
+                    "`{locale}` has different placeholders in `{key}`"
+                );
+            }
+
+            for key in catalog.keys() {
+                assert!(
+                    english.contains_key(key),
+                    "`{locale}` has unknown key `{key}`"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_locales_fall_back_to_english() {
+        let messages = Messages::parse(&[
+            ("en", "[a]\nb = \"English\"\nc = \"Only English\""),
+            ("fr", "[a]\nb = \"Français\""),
+        ])
+        .expect("Valid catalogs");
+
+        assert_eq!(messages.get("fr", "a.b", &[]), "Français");
+        assert_eq!(messages.get("fr", "a.c", &[]), "Only English");
+        assert_eq!(messages.get("en-GB", "a.b", &[]), "English");
+        assert_eq!(messages.get("ja", "a.b", &[]), "English");
+        assert_eq!(messages.get("ja", "a.missing", &[]), "a.missing");
+    }
+
+    #[test]
+    fn regional_locales_are_supported_by_language() {
+        assert!(is_supported("fr"));
+        assert!(is_supported("en-GB"));
+        assert!(!is_supported("ja"));
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        assert_eq!(t!("en", "stocks.page", page = 2, pages = 5), "Page: 2/5");
+        assert_eq!(interpolate("{a} and {b}, {", &[("a", &1)]), "1 and {b}, {");
+    }
+}
@@ -0,0 +1,107 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Tracking of command invocations that are still executing, so shutdown can wait on them, slow
+//! ones can be logged and finished ones recorded in the usage statistics
+
+use std::{
+    collections::HashMap,
+    num::NonZeroU64,
+    sync::{LazyLock, Mutex, OnceLock, PoisonError},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use poise::BoxFuture;
+use rse_core::{model::usage::CommandUse, repo::StockRepository};
+use tokio_util::task::{TaskTracker, task_tracker::TaskTrackerToken};
+
+use crate::Context;
+
+static IN_FLIGHT: LazyLock<InFlight> = LazyLock::new(InFlight::default);
+
+/// How long a command may take before it is logged as slow, set by [`start`](crate::start)
+static SLOW_COMMAND: OnceLock<Duration> = OnceLock::new();
+
+#[derive(Default)]
+struct InFlight {
+    tracker: TaskTracker,
+    /// Keyed by invocation, along with when each started
+    tokens: Mutex<HashMap<u64, (TaskTrackerToken, Instant)>>,
+}
+
+/// Logs a warning for every command invocation taking longer than `threshold`
+pub fn set_slow_threshold(threshold: Duration) {
+    let _ = SLOW_COMMAND.set(threshold);
+}
+
+/// Marks a command invocation as started. Used as poise's `pre_command` hook
+pub fn pre_command<R: StockRepository>(ctx: Context<'_, R>) -> BoxFuture<'_, ()> {
+    let token = IN_FLIGHT.tracker.token();
+    IN_FLIGHT
+        .tokens
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(ctx.id(), (token, Instant::now()));
+
+    Box::pin(async {})
+}
+
+/// Marks a command invocation as finished. Used as poise's `post_command` hook
+pub fn post_command<R: StockRepository>(ctx: Context<'_, R>) -> BoxFuture<'_, ()> {
+    crate::command_span(ctx).in_scope(|| finish(ctx, true));
+
+    Box::pin(async {})
+}
+
+/// Marks the invocation as finished, recording whether it `succeeded` and warning if it was slow.
+/// Needed on error paths, as poise only calls `post_command` for successful invocations. Should be
+/// called within the command's span, so the warning says which invocation it was.
+pub fn finish<R: StockRepository>(ctx: Context<'_, R>, succeeded: bool) {
+    let started = IN_FLIGHT
+        .tokens
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(&ctx.id());
+
+    // Invocations stopped by a check or cooldown never started
+    let Some((_, started)) = started else {
+        return;
+    };
+
+    let elapsed = started.elapsed();
+    crate::usage::record(CommandUse {
+        command: ctx.command().qualified_name.clone(),
+        guild: ctx
+            .guild_id()
+            .and_then(|guild| NonZeroU64::new(guild.get())),
+        success: succeeded,
+        duration: elapsed,
+        time: Utc::now(),
+    });
+
+    if SLOW_COMMAND.get().is_some_and(|&slow| elapsed > slow) {
+        tracing::warn!(?elapsed, "Slow command");
+    }
+}
+
+/// Waits for every command invocation that is currently executing to finish
+pub async fn wait() {
+    IN_FLIGHT.tracker.close();
+    IN_FLIGHT.tracker.wait().await;
+}
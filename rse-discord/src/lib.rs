@@ -18,50 +18,139 @@
 
 //! Discord adapters for the `RSE` program
 
-use poise::serenity_prelude::{self as serenity, GuildId, OnlineStatus};
-use rse_core::{Service, repo::StockRepository};
+use std::{
+    collections::BTreeMap,
+    sync::{LazyLock, PoisonError},
+    time::Duration,
+};
 
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use poise::serenity_prelude::{self as serenity, ChannelId, FullEvent, GuildId, OnlineStatus};
+use rse_config::DiscordConfig;
+use rse_core::{Service, outbox::DispatchPolicy, repo::StockRepository, task::TaskRegistry};
+use rust_decimal::Decimal;
+
+pub use data::BotData;
 pub use error::Error;
-use tokio::task::JoinHandle;
+pub use gateway::{Gateway, GatewayHealth, ShardHealth};
+pub use preflight::{PreflightError, preflight};
 use tokio_util::sync::CancellationToken;
-use tracing::info;
+use tracing::{Instrument, info, warn};
+
+use crate::{dm::DmDispatcher, feed::TradeBatcher};
 
 mod commands;
+mod data;
+mod digest;
+mod dm;
 mod error;
+mod feed;
+mod gateway;
+mod i18n;
+mod inflight;
+mod notify;
+mod preflight;
+mod presence;
+mod usage;
 
 /// Context of the discord runner
-pub type Context<'a, R> = poise::Context<'a, Service<R>, Error>;
+pub type Context<'a, R> = poise::Context<'a, BotData<R>, Error>;
+
+/// Creates a span identifying a command invocation. Every command runs inside one, see
+/// [`instrument_commands`], and it's recreated outside of the command itself such as in error
+/// handling.
+fn command_span<R: StockRepository>(ctx: Context<'_, R>) -> tracing::Span {
+    tracing::info_span!(
+        "command",
+        command = %ctx.command().qualified_name,
+        interaction = ctx.id(),
+        disc_id = %ctx.author().id
+    )
+}
 
-/// Start the discord bot task
+/// Start the discord bot task, registering it with `tasks`. Commands are registered in each of the
+/// configured guilds, or globally if there are none. On cancellation, the task waits for any
+/// commands that are still executing before it finishes.
+///
+/// A second task DMs users about service events that concern them, such as their orders expiring,
+/// and announces market-wide events in the market feed channels: the one configured, and those
+/// each server set for itself. Trades are announced in batches, except for large ones. A third
+/// task posts a summary of the previous day's trading to the same channels daily. Every DM is sent
+/// by one more task, which paces them and keeps sending those queued for a few seconds into
+/// shutdown.
+///
+/// Commands are rate limited per user by the configured cooldowns, which admins are exempt from.
+/// Those taking longer than the configured threshold are logged as slow. Every finished invocation
+/// is recorded in the usage statistics, which another task writes out in batches.
+///
+/// Whenever a shard connects or resumes its session, the configured activity and status are
+/// applied again and reconnects are counted, which the returned [`Gateway`] handle reports along
+/// with the rest of the bot's connection to Discord.
+#[allow(clippy::too_many_lines)]
 pub async fn start<R: StockRepository>(
     service: Service<R>,
+    config: DiscordConfig,
+    tasks: &TaskRegistry,
     c_token: CancellationToken,
-) -> JoinHandle<()> {
-    let token =
-        std::env::var("DISCORD_TOKEN").expect("'Discord_TOKEN' environment variable is not set");
+) -> Gateway {
+    LazyLock::force(&gateway::STARTED);
+
+    let (dms, dm_worker) = DmDispatcher::new(dm::QUEUE_CAPACITY);
+    let data = BotData::new(service.clone(), config.clone(), dms.clone());
+    let sessions = data.sessions().clone();
+
+    let DiscordConfig {
+        token,
+        guild_ids,
+        admin_ids: _,
+        market_feed_channel,
+        admin_channel,
+        daily_summary_hour,
+        trade_batch_window,
+        trade_batch_size,
+        large_trade_value,
+        cooldowns,
+        slow_command,
+        import_max_rows: _,
+        snapshot_dir: _,
+        activity: _,
+        status: _,
+        receipts,
+        currency,
+    } = config;
+
+    inflight::set_slow_threshold(slow_command);
+    rse_core::display::set_currency(currency);
 
     let intents = serenity::GatewayIntents::non_privileged();
+    let notifier = (service.clone(), service.subscribe());
 
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![
-                about(),
-                commands::register(),
-                commands::portfolio(),
-                commands::stocks(),
-            ],
+            commands: all_commands(cooldowns),
             on_error: error::on_error,
+            pre_command: inflight::pre_command,
+            post_command: inflight::post_command,
+            event_handler: on_event,
+            owners: data.admins().clone(),
+            // Exempts configured admins from cooldowns and checks, including the admin check
+            skip_checks_for_owners: true,
             ..Default::default()
         })
         .setup(|ctx, _ready, framework| {
             Box::pin(async move {
-                poise::builtins::register_in_guild(
-                    ctx,
-                    &framework.options().commands,
-                    GuildId::new(1_408_958_403_438_444_746),
-                )
-                .await?;
-                Ok(service)
+                let commands = &framework.options().commands;
+
+                if guild_ids.is_empty() {
+                    poise::builtins::register_globally(ctx, commands).await?;
+                } else {
+                    for guild_id in guild_ids {
+                        poise::builtins::register_in_guild(ctx, commands, GuildId::from(guild_id))
+                            .await?;
+                    }
+                }
+                Ok(data)
             })
         })
         .build();
@@ -72,8 +161,66 @@ pub async fn start<R: StockRepository>(
         .expect("Couldn't create client");
 
     let shard_manager = client.shard_manager.clone();
+    let gateway = Gateway::new(shard_manager.clone(), sessions);
+
+    let (service, events) = notifier;
+    let feeds = notify::Feeds::new(service.clone(), market_feed_channel.map(ChannelId::from));
+
+    tasks.spawn(
+        "discord-digest",
+        digest::run(
+            service.clone(),
+            client.http.clone(),
+            feeds.clone(),
+            daily_summary_hour,
+            c_token.clone(),
+        ),
+    );
+
+    tasks.spawn(
+        "discord-dms",
+        dm_worker.run(client.http.clone(), dm::DRAIN_GRACE, c_token.clone()),
+    );
 
-    tokio::spawn(async move {
+    tasks.spawn(
+        "discord-outbox",
+        notify::dispatch(
+            notify::OutboxNotifier {
+                service: service.clone(),
+                http: client.http.clone(),
+                dms: dms.clone(),
+                feeds: feeds.clone(),
+                receipts,
+            },
+            DispatchPolicy::default(),
+            c_token.clone(),
+        ),
+    );
+
+    tasks.spawn(
+        "discord-usage",
+        usage::run(service.clone(), c_token.clone()),
+    );
+
+    tasks.spawn(
+        "discord-notifier",
+        notify::run(
+            service,
+            events,
+            client.http.clone(),
+            dms,
+            feeds,
+            admin_channel.map(ChannelId::from),
+            TradeBatcher::new(
+                trade_batch_window,
+                trade_batch_size.get().try_into().unwrap_or(usize::MAX),
+                large_trade_value.map(|value| Decimal::from(value.get())),
+            ),
+            c_token.clone(),
+        ),
+    );
+
+    tasks.spawn("discord", async move {
         tokio::select! {
             () = c_token.cancelled() => {
                 {
@@ -85,6 +232,9 @@ pub async fn start<R: StockRepository>(
 
                 info!("Shutting down Discord bot");
                 shard_manager.shutdown_all().await;
+
+                info!("Waiting for running commands to finish");
+                inflight::wait().await;
             },
             res = client.start()  => {
                 if let Err(why) = res {
@@ -92,15 +242,168 @@ pub async fn start<R: StockRepository>(
                 }
             }
         }
+    });
+
+    gateway
+}
+
+/// Restores the bot's presence whenever a shard starts a session, as a new one starts without it,
+/// and logs reconnects
+fn on_event<'a, R: StockRepository>(
+    ctx: &'a serenity::Context,
+    event: &'a FullEvent,
+    _framework: poise::FrameworkContext<'a, BotData<R>, Error>,
+    data: &'a BotData<R>,
+) -> BoxFuture<'a, Result<(), Error>> {
+    Box::pin(async move {
+        if !matches!(event, FullEvent::Ready { .. } | FullEvent::Resume { .. }) {
+            return Ok(());
+        }
+
+        let shard = ctx.shard_id.0;
+        let reconnects = data.sessions().record(shard, Utc::now());
+
+        if reconnects > 0 {
+            let resumed = matches!(event, FullEvent::Resume { .. });
+            info!(
+                shard,
+                reconnects, resumed, "Reconnected to the Discord gateway"
+            );
+        }
+
+        let config = data.config();
+        ctx.set_presence(
+            config.activity.as_deref().and_then(presence::activity),
+            presence::status(config.status),
+        );
+
+        Ok(())
     })
 }
 
-#[poise::command(slash_command, prefix_command, guild_only)]
-async fn about<R: StockRepository>(ctx: Context<'_, R>) -> Result<(), Error> {
-    let reply = poise::CreateReply::default().ephemeral(true).content(
-        "A discord bot that manages the Reconnected Stock Exchange.\nLicensed under AGPL-3.0",
-    );
-    ctx.send(reply).await?;
+/// Every command the bot registers, with their configured cooldowns applied
+fn all_commands<R: StockRepository>(
+    mut cooldowns: BTreeMap<String, Duration>,
+) -> Vec<poise::Command<BotData<R>, Error>> {
+    let mut commands = vec![
+        commands::about(),
+        commands::register(),
+        commands::link(),
+        commands::me(),
+        commands::statement(),
+        commands::portfolio(),
+        commands::performance(),
+        commands::privacy(),
+        commands::settings(),
+        commands::stocks(),
+        commands::find(),
+        commands::buy(),
+        commands::sell(),
+        commands::order(),
+        commands::orderbook(),
+        commands::top(),
+        commands::dividend(),
+        commands::company(),
+        commands::export(),
+        commands::deposit(),
+        commands::api_key(),
+        commands::withdraw(),
+        commands::close_account(),
+        commands::status(),
+        commands::admin(),
+    ];
+
+    apply_cooldowns(&mut commands, "", &mut cooldowns);
+    instrument_commands(&mut commands);
+
+    for name in cooldowns.keys() {
+        warn!("Cooldown configured for unknown command `{name}`");
+    }
+
+    commands
+}
+
+/// A command's own slash action, kept in its `custom_data` while [`instrumented`] runs in its place
+struct SlashAction<R: StockRepository>(for<'a> fn(AppContext<'a, R>) -> ActionFuture<'a, R>);
+
+/// Context of a slash command, as poise hands it to the command's action
+type AppContext<'a, R> = poise::ApplicationContext<'a, BotData<R>, Error>;
+
+/// What a command's action returns
+type ActionFuture<'a, R> = BoxFuture<'a, Result<(), poise::FrameworkError<'a, BotData<R>, Error>>>;
+
+/// Runs each command, and recursively its subcommands, inside its [`command_span`]
+fn instrument_commands<R: StockRepository>(commands: &mut [poise::Command<BotData<R>, Error>]) {
+    for command in commands {
+        if let Some(action) = command.slash_action.replace(instrumented::<R>) {
+            command.custom_data = Box::new(SlashAction(action));
+        }
+
+        instrument_commands(&mut command.subcommands);
+    }
+}
+
+/// Runs the invoked command's own slash action inside its [`command_span`]
+fn instrumented<R: StockRepository>(ctx: AppContext<'_, R>) -> ActionFuture<'_, R> {
+    let SlashAction(action) = ctx
+        .command
+        .custom_data
+        .downcast_ref::<SlashAction<R>>()
+        .expect("Set along with the action");
+
+    Box::pin(action(ctx).instrument(command_span(ctx.into())))
+}
+
+/// Gives each command, and recursively its subcommands, the per-user cooldown configured for its
+/// full name. Entries are removed from `cooldowns` as they are used, leaving those that matched no
+/// command.
+fn apply_cooldowns<R: StockRepository>(
+    commands: &mut [poise::Command<BotData<R>, Error>],
+    parent: &str,
+    cooldowns: &mut BTreeMap<String, Duration>,
+) {
+    for command in commands {
+        let name = if parent.is_empty() {
+            command.name.clone()
+        } else {
+            format!("{parent} {}", command.name)
+        };
+
+        if let Some(cooldown) = cooldowns.remove(&name) {
+            command
+                .cooldown_config
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner)
+                .user = Some(cooldown);
+        }
+
+        apply_cooldowns(&mut command.subcommands, &name, cooldowns);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rse_core::test_util::Stub;
+
+    use super::*;
+
+    /// Checks `commands`, and recursively their subcommands, run inside their span
+    fn assert_instrumented(commands: &[poise::Command<BotData<Stub>, Error>]) {
+        for command in commands {
+            if command.slash_action.is_some() {
+                assert!(
+                    command.custom_data.is::<SlashAction<Stub>>(),
+                    "`{}` isn't instrumented",
+                    command.qualified_name
+                );
+            }
+
+            assert_instrumented(&command.subcommands);
+        }
+    }
 
-    Ok(())
+    #[test]
+    fn every_command_runs_in_its_span() {
+        assert_instrumented(&all_commands::<Stub>(BTreeMap::new()));
+    }
 }
@@ -0,0 +1,573 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Messages sent when something happens outside of a command, either directly to the users it
+//! concerns, to the market feed channels or to the admin channel
+
+use std::sync::Arc;
+
+use std::fmt::Write;
+
+use poise::serenity_prelude::{
+    ChannelId, Color, CreateEmbed, CreateEmbedFooter, CreateMessage, Http, Timestamp, UserId,
+};
+use rse_core::{
+    Service,
+    display::format_kromer,
+    event::Event,
+    model::{
+        StockStatus,
+        dividend::Dividend,
+        order::{Order, Receipt, Side},
+        outbox::Notice,
+        reconcile::ReconciliationReport,
+        withdrawal::{Withdrawal, WithdrawalStatus},
+    },
+    outbox::{DispatchPolicy, Notifier},
+    repo::StockRepository,
+};
+use snafu::ensure;
+use tokio::{
+    sync::broadcast::{Receiver, error::RecvError},
+    time::Instant,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::{
+    Error,
+    dm::DmDispatcher,
+    error::DmQueueFullSnafu,
+    feed::{TradeBatcher, batch_embed, large_trade},
+};
+
+/// The most discrepancies listed in a reconciliation report, which keeps it well within the size
+/// of an embed
+const MAX_DISCREPANCIES: usize = 20;
+
+/// The most fills listed on a trade receipt, which keeps it well within the size of an embed
+const MAX_RECEIPT_FILLS: usize = 20;
+
+/// Where market-wide announcements are posted: the market feed channel each server set for itself,
+/// along with the one in the config, if any
+#[derive(Debug, Clone)]
+pub(crate) struct Feeds<R: StockRepository> {
+    service: Service<R>,
+    configured: Option<ChannelId>,
+}
+
+impl<R: StockRepository> Feeds<R> {
+    pub(crate) const fn new(service: Service<R>, configured: Option<ChannelId>) -> Self {
+        Self {
+            service,
+            configured,
+        }
+    }
+
+    /// Every channel to post to, looked up afresh so servers' edits apply straight away
+    async fn channels(&self) -> Result<Vec<ChannelId>, Error> {
+        let mut channels: Vec<ChannelId> = self.configured.into_iter().collect();
+        channels.extend(
+            self.service
+                .all_guild_settings()
+                .await?
+                .into_iter()
+                .filter_map(|(_, settings)| settings.market_feed_channel)
+                .map(ChannelId::from),
+        );
+        channels.sort_unstable();
+        channels.dedup();
+
+        Ok(channels)
+    }
+
+    /// Posts `message` to every feed, returning how many it reached. A channel that can't be
+    /// posted to is logged and skipped, so it doesn't hold up the others, and this only fails if
+    /// the feeds can't be looked up or none of them could be posted to.
+    pub(crate) async fn post(&self, http: &Http, message: CreateMessage) -> Result<usize, Error> {
+        let mut posted = 0;
+        let mut last_err = None;
+
+        for channel in self.channels().await? {
+            match channel.send_message(http, message.clone()).await {
+                Ok(_) => posted += 1,
+                Err(err) => {
+                    warn!(%err, %channel, "Couldn't post to market feed");
+                    last_err = Some(err);
+                }
+            }
+        }
+
+        match last_err {
+            Some(err) if posted == 0 => Err(err.into()),
+            _ => Ok(posted),
+        }
+    }
+}
+
+/// Forwards service events to the users they concern through `dms`, announces market-wide ones in
+/// `feeds` and raises problems in `admin_channel`, until cancelled. Trades are posted to `feeds` in
+/// batches by `batcher`, with any open batch posted before returning.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn run<R: StockRepository>(
+    service: Service<R>,
+    mut events: Receiver<Event>,
+    http: Arc<Http>,
+    dms: DmDispatcher,
+    feeds: Feeds<R>,
+    admin_channel: Option<ChannelId>,
+    mut batcher: TradeBatcher,
+    c_token: CancellationToken,
+) {
+    loop {
+        let deadline = batcher.deadline();
+
+        let event = tokio::select! {
+            () = c_token.cancelled() => break,
+            () = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                post_batch(&http, &feeds, &mut batcher).await;
+                continue;
+            }
+            event = events.recv() => event,
+        };
+
+        let res = match event {
+            Ok(Event::OrderExpired(order)) => order_expired(&service, &dms, &order).await,
+            Ok(Event::OrderPlaced { order, fills }) if !fills.is_empty() => {
+                let now = Instant::now();
+                let mut res = Ok(());
+
+                for fill in batcher.push(order.ticker, &fills, now) {
+                    res = res.and(announce(&http, &feeds, large_trade(order.ticker, &fill)).await);
+                }
+                if batcher.is_due(now) {
+                    post_batch(&http, &feeds, &mut batcher).await;
+                }
+
+                res
+            }
+            Ok(Event::SharesIssued {
+                ticker,
+                quantity,
+                outstanding,
+                ..
+            }) => {
+                let content = format!(
+                    "${ticker} issued {quantity} new shares, {outstanding} are now outstanding"
+                );
+                announce(&http, &feeds, content).await
+            }
+            Ok(Event::SharesBoughtBack {
+                ticker,
+                quantity,
+                outstanding,
+                ..
+            }) => {
+                let content = format!(
+                    "${ticker} bought back {quantity} shares, {outstanding} are now outstanding"
+                );
+                announce(&http, &feeds, content).await
+            }
+            Ok(Event::StockStatusChanged { ticker, status, .. }) => {
+                let content = match status {
+                    StockStatus::Active => format!("Trading in ${ticker} has resumed"),
+                    StockStatus::Halted => format!("Trading in ${ticker} has been halted"),
+                    StockStatus::Delisted => format!("${ticker} has been delisted"),
+                };
+                announce(&http, &feeds, content).await
+            }
+            Ok(Event::ReconciliationFailed(report)) => match admin_channel {
+                Some(channel) => channel
+                    .send_message(
+                        &http,
+                        CreateMessage::new().embed(reconciliation_embed(&report)),
+                    )
+                    .await
+                    .map(|_| ())
+                    .map_err(Error::from),
+                None => Ok(()),
+            },
+            // Dividends and withdrawals are delivered through the outbox instead
+            Ok(_) => Ok(()),
+            Err(RecvError::Lagged(missed)) => {
+                warn!(missed, "Notifier fell behind, dropping events");
+                Ok(())
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        if let Err(err) = res {
+            warn!(%err, "Couldn't deliver notification");
+        }
+    }
+
+    post_batch(&http, &feeds, &mut batcher).await;
+}
+
+/// Posts the open batch of trades to `feeds`, if anything traded since the last one
+async fn post_batch<R: StockRepository>(http: &Http, feeds: &Feeds<R>, batcher: &mut TradeBatcher) {
+    let Some(batch) = batcher.take() else {
+        return;
+    };
+
+    if let Err(err) = feeds
+        .post(http, CreateMessage::new().embed(batch_embed(&batch)))
+        .await
+    {
+        warn!(%err, "Couldn't post batch of trades");
+    }
+}
+
+/// Delivers notices from the outbox, which unlike events are retried until they arrive. A notice
+/// delivered twice is posted twice, which is preferable to it going missing, though the same DM
+/// isn't sent twice in a row. DMs count as delivered once queued, and are retried while the queue
+/// is full.
+pub(crate) struct OutboxNotifier<R: StockRepository> {
+    pub service: Service<R>,
+    pub http: Arc<Http>,
+    pub dms: DmDispatcher,
+    pub feeds: Feeds<R>,
+    /// Whether trade receipts are sent at all, regardless of what users chose
+    pub receipts: bool,
+}
+
+impl<R: StockRepository> Notifier for OutboxNotifier<R> {
+    type Error = Error;
+
+    async fn deliver(&self, notice: &Notice) -> Result<(), Error> {
+        match notice {
+            Notice::DividendPaid(dividend) => {
+                dividend_paid(&self.http, &self.feeds, dividend).await
+            }
+            Notice::WithdrawalResolved(withdrawal) => {
+                withdrawal_resolved(&self.service, &self.dms, withdrawal).await
+            }
+            Notice::TradeReceipt(receipt) if self.receipts => {
+                trade_receipt(&self.service, &self.dms, receipt).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Dispatches the outbox through `notifier` every poll interval of `policy`, until cancelled
+pub(crate) async fn dispatch<R: StockRepository>(
+    notifier: OutboxNotifier<R>,
+    policy: DispatchPolicy,
+    c_token: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(policy.poll_interval);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            () = c_token.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        let now = notifier.service.now();
+
+        match notifier
+            .service
+            .dispatch_outbox(&notifier, &policy, now)
+            .await
+        {
+            Ok(0) => {}
+            Ok(count) => debug!(count, "Delivered notices"),
+            Err(err) => warn!(%err, "Couldn't dispatch outbox"),
+        }
+    }
+}
+
+/// Tells the owner of `order` it expired. Dropped if the DM queue is full, as it is only sent once
+async fn order_expired<R: StockRepository>(
+    service: &Service<R>,
+    dms: &DmDispatcher,
+    order: &Order,
+) -> Result<(), Error> {
+    let Some(disc_id) = service.get_account_info(&order.user, None).await?.disc_id else {
+        debug!(order = order.id, "Owner has no linked Discord account");
+        return Ok(());
+    };
+
+    let content = format!(
+        "Your order #{} to {} {} ${} @ {} expired with {} shares unfilled, which have been \
+        released from escrow",
+        order.id, order.side, order.quantity, order.ticker, order.price, order.remaining
+    );
+
+    dms.send(UserId::from(disc_id), CreateMessage::new().content(content));
+
+    Ok(())
+}
+
+async fn withdrawal_resolved<R: StockRepository>(
+    service: &Service<R>,
+    dms: &DmDispatcher,
+    withdrawal: &Withdrawal,
+) -> Result<(), Error> {
+    let Some(disc_id) = service
+        .get_account_info(&withdrawal.user, None)
+        .await?
+        .disc_id
+    else {
+        debug!(
+            withdrawal = withdrawal.id,
+            "Requester has no linked Discord account"
+        );
+        return Ok(());
+    };
+
+    let content = match withdrawal.status {
+        WithdrawalStatus::Denied => format!(
+            "Your withdrawal #{} of {} to {} was denied, and the Kromer has been returned to \
+            your balance",
+            withdrawal.id, withdrawal.amount, withdrawal.address
+        ),
+        _ => format!(
+            "Your withdrawal #{} of {} to {} was approved and is on its way",
+            withdrawal.id, withdrawal.amount, withdrawal.address
+        ),
+    };
+
+    ensure!(
+        dms.send(UserId::from(disc_id), CreateMessage::new().content(content)),
+        DmQueueFullSnafu
+    );
+
+    Ok(())
+}
+
+async fn trade_receipt<R: StockRepository>(
+    service: &Service<R>,
+    dms: &DmDispatcher,
+    receipt: &Receipt,
+) -> Result<(), Error> {
+    let Some(disc_id) = service.get_account_info(&receipt.user, None).await?.disc_id else {
+        debug!(order = receipt.order, "Owner has no linked Discord account");
+        return Ok(());
+    };
+
+    ensure!(
+        dms.send(
+            UserId::from(disc_id),
+            CreateMessage::new().embed(receipt_embed(receipt))
+        ),
+        DmQueueFullSnafu
+    );
+
+    Ok(())
+}
+
+/// A receipt for the fills of one order, listing each of them when there were several
+pub(crate) fn receipt_embed(receipt: &Receipt) -> CreateEmbed {
+    let (verb, color) = match receipt.side {
+        Side::Buy => ("Bought", Color::DARK_GREEN),
+        Side::Sell => ("Sold", Color::RED),
+    };
+
+    let mut embed = CreateEmbed::new()
+        .title(format!(
+            "{verb} {} ${} @ {}",
+            receipt.shares(),
+            receipt.ticker,
+            format_kromer(receipt.average_price())
+        ))
+        .field("Order", format!("#{}", receipt.order), true)
+        .field("Fee", format_kromer(receipt.fees()), true)
+        .field("Position", format!("{} shares", receipt.position), true)
+        .field("Still open", format!("{} shares", receipt.remaining), true)
+        .color(color)
+        .timestamp(Timestamp::now());
+
+    if receipt.fills.len() > 1 {
+        let mut description = String::new();
+
+        for fill in receipt.fills.iter().take(MAX_RECEIPT_FILLS) {
+            writeln!(
+                description,
+                "- {} @ {}",
+                fill.shares,
+                format_kromer(fill.price.get())
+            )
+            .expect("Never fails");
+        }
+
+        if let Some(more) = receipt.fills.len().checked_sub(MAX_RECEIPT_FILLS)
+            && more > 0
+        {
+            write!(description, "And {more} more fills").expect("Never fails");
+        }
+
+        embed = embed.description(description);
+    }
+
+    embed
+}
+
+async fn dividend_paid<R: StockRepository>(
+    http: &Http,
+    feeds: &Feeds<R>,
+    dividend: &Dividend,
+) -> Result<(), Error> {
+    let content = format!(
+        "${} paid a dividend of {} per share, {:.2} in total to {} holders",
+        dividend.ticker, dividend.per_share, dividend.total, dividend.holders
+    );
+
+    announce(http, feeds, content).await
+}
+
+/// Posts `content` to every market feed
+async fn announce<R: StockRepository>(
+    http: &Http,
+    feeds: &Feeds<R>,
+    content: String,
+) -> Result<(), Error> {
+    feeds
+        .post(http, CreateMessage::new().content(content))
+        .await?;
+
+    Ok(())
+}
+
+/// A reconciliation report, listing what didn't add up
+pub(crate) fn reconciliation_embed(report: &ReconciliationReport) -> CreateEmbed {
+    let (title, color) = if report.is_clean() {
+        ("The books reconcile", Color::DARK_GREEN)
+    } else {
+        ("The books don't reconcile", Color::RED)
+    };
+
+    let mut description = String::new();
+
+    for discrepancy in report.discrepancies.iter().take(MAX_DISCREPANCIES) {
+        writeln!(description, "- {discrepancy}").expect("Never fails");
+    }
+
+    if let Some(more) = report.discrepancies.len().checked_sub(MAX_DISCREPANCIES)
+        && more > 0
+    {
+        write!(description, "And {more} more, see the logs").expect("Never fails");
+    }
+
+    CreateEmbed::new()
+        .title(title)
+        .description(description)
+        .footer(CreateEmbedFooter::new(format!(
+            "Checked {} accounts and {} stocks",
+            report.accounts, report.stocks
+        )))
+        .timestamp(Timestamp::from(report.started_at))
+        .color(color)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+
+    use rse_core::{
+        model::{
+            Price, Shares, guild::GuildSettings, order::ReceiptFill, reconcile::Discrepancy,
+            ticker::Ticker,
+        },
+        test_util::Stub,
+    };
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn feeds_merge_every_servers_channel() {
+        let service = Service::new(Stub::default());
+        let feed = |id| GuildSettings {
+            market_feed_channel: NonZeroU64::new(id),
+            ..GuildSettings::default()
+        };
+
+        for (guild, settings) in [(1, feed(20)), (2, feed(10)), (3, feed(0)), (4, feed(30))] {
+            service
+                .set_guild_settings(NonZeroU64::new(guild).expect("Non-zero"), &settings)
+                .await
+                .expect("Saved");
+        }
+
+        let feeds = Feeds::new(service, Some(ChannelId::new(30)));
+
+        assert_eq!(
+            feeds.channels().await.expect("Lookup"),
+            [10, 20, 30].map(ChannelId::new)
+        );
+    }
+
+    #[test]
+    fn long_reports_are_cut_short() {
+        let mut report = ReconciliationReport::new(chrono::DateTime::UNIX_EPOCH);
+        report.discrepancies = vec![
+            Discrepancy::Escrow {
+                user: Uuid::nil(),
+                escrow: Decimal::ONE,
+                open_orders: Decimal::ZERO,
+            };
+            MAX_DISCREPANCIES + 3
+        ];
+
+        let embed = serde_json::to_value(reconciliation_embed(&report)).expect("Serializes");
+        let description = embed["description"].as_str().expect("Has a description");
+
+        assert_eq!(description.lines().count(), MAX_DISCREPANCIES + 1);
+        assert!(description.ends_with("And 3 more, see the logs"));
+    }
+
+    #[test]
+    fn receipts_list_coalesced_fills() {
+        let fill = |price: i64, shares| ReceiptFill {
+            price: Price::new(Decimal::from(price)).expect("Valid price"),
+            shares: Shares::new(shares).expect("Valid shares"),
+            fee: Decimal::ONE,
+        };
+        let mut receipt = Receipt {
+            user: Uuid::nil(),
+            order: 7,
+            ticker: Ticker::try_from("ABC").expect("Valid ticker"),
+            side: Side::Buy,
+            fills: vec![fill(2, 1)],
+            remaining: Shares::new(9).expect("Valid shares"),
+            position: Shares::new(1).expect("Valid shares"),
+        };
+
+        let embed = serde_json::to_value(receipt_embed(&receipt)).expect("Serializes");
+        assert!(embed.get("description").is_none());
+
+        receipt.merge(Receipt {
+            fills: vec![fill(5, 3); MAX_RECEIPT_FILLS + 1],
+            remaining: Shares::ZERO,
+            ..receipt.clone()
+        });
+
+        let embed = serde_json::to_value(receipt_embed(&receipt)).expect("Serializes");
+        let description = embed["description"].as_str().expect("Has a description");
+
+        assert_eq!(embed["title"], "Bought 64 $ABC @ 4.95 KRO");
+        assert_eq!(description.lines().count(), MAX_RECEIPT_FILLS + 1);
+        assert!(description.ends_with("And 2 more fills"));
+        assert_eq!(embed["fields"][1]["value"], "22.00 KRO");
+        assert_eq!(embed["fields"][3]["value"], "0 shares");
+    }
+}
@@ -0,0 +1,65 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Checks made before the bot is started, so that bad credentials fail startup rather than the
+//! bot's task once everything else is running
+
+use std::num::NonZeroU64;
+
+use poise::serenity_prelude::{self as serenity, GuildId, Http};
+use snafu::{ResultExt, Snafu};
+
+/// Why the bot can't be started with its configuration
+#[derive(Debug, Snafu)]
+#[allow(missing_docs)]
+pub enum PreflightError {
+    /// Discord didn't accept the token, or couldn't be reached to check it
+    #[snafu(display("Couldn't authenticate with Discord, is the token right? {source}"))]
+    Token { source: Box<serenity::Error> },
+    /// A configured guild doesn't exist, or the bot hasn't been invited to it
+    #[snafu(display("The bot can't see guild {guild_id}, has it been invited? {source}"))]
+    Guild {
+        guild_id: NonZeroU64,
+        source: Box<serenity::Error>,
+    },
+}
+
+/// Checks that Discord accepts `token` and that the bot can see every guild it registers commands
+/// in, returning the name of the bot's application.
+///
+/// # Errors
+/// * [`Token`](PreflightError::Token) - The token was rejected
+/// * [`Guild`](PreflightError::Guild) - The first guild the bot can't see
+pub async fn preflight(token: &str, guild_ids: &[NonZeroU64]) -> Result<String, PreflightError> {
+    let http = Http::new(token);
+
+    let app = http
+        .get_current_application_info()
+        .await
+        .map_err(Box::new)
+        .context(TokenSnafu)?;
+
+    for &guild_id in guild_ids {
+        http.get_guild(GuildId::from(guild_id))
+            .await
+            .map_err(Box::new)
+            .context(GuildSnafu { guild_id })?;
+    }
+
+    Ok(app.name)
+}
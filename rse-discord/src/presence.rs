@@ -0,0 +1,99 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The activity and status shown on the bot's profile
+
+use poise::serenity_prelude::{ActivityData, ActivityType, OnlineStatus};
+use rse_config::BotStatus;
+
+/// Turns a configured activity into one Discord can show. A leading `Playing`, `Watching`,
+/// `Listening to` or `Competing in` picks the kind of activity, anything else is a custom status.
+/// Returns [`None`] for an empty activity.
+pub(crate) fn activity(text: &str) -> Option<ActivityData> {
+    let text = text.trim();
+
+    if text.is_empty() {
+        return None;
+    }
+
+    let kinds = [
+        ("Playing ", ActivityType::Playing),
+        ("Watching ", ActivityType::Watching),
+        ("Listening to ", ActivityType::Listening),
+        ("Competing in ", ActivityType::Competing),
+    ];
+
+    let activity = kinds
+        .into_iter()
+        .find_map(|(prefix, kind)| {
+            let name = text.strip_prefix(prefix)?.trim_start();
+
+            (!name.is_empty()).then(|| ActivityData {
+                name: name.to_owned(),
+                kind,
+                state: None,
+                url: None,
+            })
+        })
+        .unwrap_or_else(|| ActivityData::custom(text));
+
+    Some(activity)
+}
+
+/// The Discord status for a configured one
+pub(crate) const fn status(status: BotStatus) -> OnlineStatus {
+    match status {
+        BotStatus::Online => OnlineStatus::Online,
+        BotStatus::Idle => OnlineStatus::Idle,
+        BotStatus::DoNotDisturb => OnlineStatus::DoNotDisturb,
+        BotStatus::Invisible => OnlineStatus::Invisible,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leading_verbs_pick_the_kind() {
+        let watching = activity("Watching the markets 📈").expect("Some activity");
+        assert_eq!(watching.kind, ActivityType::Watching);
+        assert_eq!(watching.name, "the markets 📈");
+
+        let listening = activity("  Listening to the bell").expect("Some activity");
+        assert_eq!(listening.kind, ActivityType::Listening);
+        assert_eq!(listening.name, "the bell");
+
+        let competing = activity("Competing in the top 10").expect("Some activity");
+        assert_eq!(competing.kind, ActivityType::Competing);
+    }
+
+    #[test]
+    fn anything_else_is_a_custom_status() {
+        let custom = activity("Buy low, sell high").expect("Some activity");
+        assert_eq!(custom.kind, ActivityType::Custom);
+        assert_eq!(custom.state.as_deref(), Some("Buy low, sell high"));
+
+        // Nothing follows the verb, so it is the whole status
+        assert_eq!(
+            activity("Watching ").map(|activity| activity.kind),
+            Some(ActivityType::Custom)
+        );
+        assert!(activity("   ").is_none());
+    }
+}
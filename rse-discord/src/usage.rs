@@ -0,0 +1,127 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Usage statistics for commands. Invocations are buffered in memory and written out in batches, so
+//! recording them never waits on the database.
+
+use std::{
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use rse_core::{Service, model::usage::CommandUse, repo::StockRepository};
+use tokio::time::MissedTickBehavior;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+/// How often buffered invocations are written out
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The most invocations buffered between writes. Any more are dropped, so the buffer can't grow
+/// without bound while the database is unreachable.
+const MAX_BUFFERED: usize = 10_000;
+
+static BUFFER: Buffer = Buffer::new(MAX_BUFFERED);
+
+/// Invocations waiting to be written out
+struct Buffer {
+    uses: Mutex<Vec<CommandUse>>,
+    capacity: usize,
+}
+
+impl Buffer {
+    const fn new(capacity: usize) -> Self {
+        Self {
+            uses: Mutex::new(Vec::new()),
+            capacity,
+        }
+    }
+
+    /// Adds an invocation, unless the buffer is full
+    fn push(&self, invocation: CommandUse) {
+        let mut uses = self.uses.lock().unwrap_or_else(PoisonError::into_inner);
+        if uses.len() < self.capacity {
+            uses.push(invocation);
+        }
+    }
+
+    /// Empties the buffer, returning what was in it
+    fn take(&self) -> Vec<CommandUse> {
+        std::mem::take(&mut *self.uses.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+/// Buffers a finished invocation to be written out with the next batch
+pub fn record(invocation: CommandUse) {
+    BUFFER.push(invocation);
+}
+
+/// Writes out every buffered invocation. Failures are only logged, and the batch dropped.
+async fn flush<R: StockRepository>(service: &Service<R>) {
+    let uses = BUFFER.take();
+
+    if let Err(err) = service.record_command_uses(&uses).await {
+        warn!(%err, dropped = uses.len(), "Couldn't record command usage");
+    }
+}
+
+/// Writes out buffered invocations every [`FLUSH_INTERVAL`]. On cancellation, waits for running
+/// commands to finish before writing out the last batch.
+pub(crate) async fn run<R: StockRepository>(service: Service<R>, c_token: CancellationToken) {
+    let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            () = c_token.cancelled() => break,
+            _ = interval.tick() => flush(&service).await,
+        }
+    }
+
+    crate::inflight::wait().await;
+    flush(&service).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn used(command: &str) -> CommandUse {
+        CommandUse {
+            command: command.to_owned(),
+            guild: None,
+            success: true,
+            duration: Duration::from_millis(5),
+            time: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn full_buffers_drop_new_invocations() {
+        let buffer = Buffer::new(2);
+        buffer.push(used("me"));
+        buffer.push(used("order"));
+        buffer.push(used("stocks"));
+
+        let taken: Vec<_> = buffer.take().into_iter().map(|used| used.command).collect();
+        assert_eq!(taken, ["me", "order"]);
+        assert!(buffer.take().is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Errors for the `RSE` REST API, and how they are answered
+
+use axum::{
+    Json,
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::{HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use rse_core::error::Error as RscErr;
+use serde::Serialize;
+use snafu::Snafu;
+
+/// Errors answered by the API. Each is sent as a status code and a JSON body with a stable `code`
+/// to branch on and a `message` that is safe to show to players
+#[derive(Debug, Snafu)]
+#[allow(missing_docs)]
+#[snafu(visibility(pub(crate)))]
+pub enum Error {
+    /// Errors returned by the exchange
+    #[snafu(transparent)]
+    Service { source: RscErr },
+
+    /// The path, query or body of a request couldn't be read
+    #[snafu(display("Invalid request: {reason}"))]
+    InvalidRequest { reason: String },
+
+    /// A request needing an account's API key didn't send one that is in use
+    #[snafu(display("A valid API key is required to do this"))]
+    Unauthorized,
+}
+
+/// Turns paths, queries and bodies axum couldn't read into an [`Error`], so they're answered like
+/// any other
+macro_rules! rejections {
+    ($($rejection:ty),*) => {
+        $(impl From<$rejection> for Error {
+            fn from(value: $rejection) -> Self {
+                Self::InvalidRequest {
+                    reason: value.body_text(),
+                }
+            }
+        })*
+    };
+}
+
+rejections!(JsonRejection, PathRejection, QueryRejection);
+
+/// The body of every error response
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    code: &'static str,
+    message: String,
+}
+
+impl Error {
+    /// A short, stable name for what went wrong, the exchange's own for errors that came from it
+    pub(crate) const fn code(&self) -> &'static str {
+        match self {
+            Self::Service { source } => source.code(),
+            Self::InvalidRequest { .. } => "invalid_request",
+            Self::Unauthorized => "unauthorized",
+        }
+    }
+
+    /// The status code the error is answered with
+    pub(crate) const fn status(&self) -> StatusCode {
+        let source = match self {
+            Self::Service { source } => source,
+            Self::InvalidRequest { .. } => return StatusCode::BAD_REQUEST,
+            Self::Unauthorized => return StatusCode::UNAUTHORIZED,
+        };
+
+        // No wildcard, so new variants can't be added without picking a status
+        match source {
+            RscErr::DatabaseError { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            RscErr::Busy => StatusCode::SERVICE_UNAVAILABLE,
+            RscErr::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            RscErr::UserNotFound { .. }
+            | RscErr::StockNotFound { .. }
+            | RscErr::OrderNotFound { .. }
+            | RscErr::WithdrawalNotFound { .. }
+            | RscErr::AdjustmentNotFound { .. }
+            | RscErr::NoStocksExist => StatusCode::NOT_FOUND,
+            RscErr::PrivateAccount
+            | RscErr::NotStockOwner { .. }
+            | RscErr::AccountTooNew { .. } => StatusCode::FORBIDDEN,
+            RscErr::AccountExists
+            | RscErr::PlayerRegistered { .. }
+            | RscErr::StockExists { .. }
+            | RscErr::StockHalted { .. }
+            | RscErr::MarketClosed { .. }
+            | RscErr::AlreadyReversed { .. }
+            | RscErr::AccountNotEmpty { .. }
+            | RscErr::MergeConflict { .. }
+            | RscErr::IdempotencyKeyReused => StatusCode::CONFLICT,
+            RscErr::InsufficientFunds
+            | RscErr::InsufficientShares
+            | RscErr::InvalidOrder { .. }
+            | RscErr::PriceOutOfBand { .. }
+            | RscErr::InvalidDividend { .. }
+            | RscErr::IssuanceCapExceeded { .. }
+            | RscErr::TickerReserved { .. }
+            | RscErr::InvalidStock { .. }
+            | RscErr::InvalidTicker { .. }
+            | RscErr::InvalidMetadata { .. }
+            | RscErr::InvalidGrant { .. }
+            | RscErr::InvalidAdjustment { .. }
+            | RscErr::InvalidWithdrawal { .. }
+            | RscErr::ImportTooLarge { .. }
+            | RscErr::NoShareholders { .. }
+            | RscErr::InvalidLinkCode => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = self.status();
+
+        if status.is_server_error() {
+            tracing::error!(err = %snafu::Report::from_error(&self), "Request failed");
+        }
+
+        let retry_after = match &self {
+            Self::Service {
+                source: RscErr::RateLimited { retry_after },
+            } => Some(retry_after.as_secs().max(1)),
+            _ => None,
+        };
+
+        let body = ErrorBody {
+            code: self.code(),
+            message: self.to_string(),
+        };
+        let mut response = (status, Json(body)).into_response();
+
+        if let Some(secs) = retry_after {
+            response
+                .headers_mut()
+                .insert(header::RETRY_AFTER, HeaderValue::from(secs));
+        }
+
+        response
+    }
+}
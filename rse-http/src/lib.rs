@@ -0,0 +1,386 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A REST API for the `RSE` program, so in-game `ComputerCraft` terminals can use the exchange
+//! without going through Discord. Stocks, accounts and holdings can be read by anyone, as players
+//! could through the bot, while orders are placed for the account whose API key was sent.
+
+use axum::{
+    Router,
+    http::{HeaderMap, header},
+    routing::{get, post},
+};
+use rse_core::{
+    Service,
+    model::{Page, Pager, api_key::ApiKey},
+    repo::StockRepository,
+};
+use serde::{Deserialize, Serialize};
+use snafu::OptionExt;
+use tokio::net::TcpListener;
+use tokio_util::sync::CancellationToken;
+use uuid::Uuid;
+
+pub use error::Error;
+
+use crate::error::UnauthorizedSnafu;
+
+mod error;
+mod orders;
+mod stocks;
+mod users;
+
+/// How many entries a page holds unless asked for otherwise
+const DEFAULT_LIMIT: u8 = 25;
+
+/// The most entries a page may hold
+const MAX_LIMIT: u8 = 100;
+
+/// What every request is handled with
+#[derive(Debug)]
+struct ApiState<R: StockRepository> {
+    service: Service<R>,
+}
+
+impl<R: StockRepository> Clone for ApiState<R> {
+    fn clone(&self) -> Self {
+        Self {
+            service: self.service.clone(),
+        }
+    }
+}
+
+impl<R: StockRepository> ApiState<R> {
+    /// Finds the account whose API key the request sent as a bearer token. Fails unless the key is
+    /// still in use
+    async fn authenticate(&self, headers: &HeaderMap) -> Result<Uuid, Error> {
+        let key = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .and_then(|v| v.parse::<ApiKey>().ok())
+            .context(UnauthorizedSnafu)?;
+
+        self.service
+            .api_key_owner(&key)
+            .await?
+            .context(UnauthorizedSnafu)
+    }
+}
+
+/// Which page of a listing to answer with
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+struct PageQuery {
+    /// How many entries to skip. Defaults to none
+    offset: Option<u32>,
+    /// How many entries to list, at most [`MAX_LIMIT`]. Defaults to [`DEFAULT_LIMIT`]
+    limit: Option<u8>,
+}
+
+impl PageQuery {
+    fn pager(self) -> Pager {
+        Pager::new(
+            self.offset.unwrap_or_default().into(),
+            self.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT).into(),
+        )
+    }
+}
+
+/// One page of a listing, and how many entries there are across every page
+#[derive(Debug, Serialize)]
+struct PageBody<T> {
+    items: Vec<T>,
+    total: u64,
+    offset: u64,
+}
+
+impl<T> PageBody<T> {
+    fn new<U>(page: Page<U>, f: impl FnMut(U) -> T) -> Self {
+        Self {
+            items: page.items.into_iter().map(f).collect(),
+            total: page.total,
+            offset: page.offset,
+        }
+    }
+}
+
+/// Creates the API's routes
+///
+/// * `GET /stocks` - A page of stocks, alphabetically by ticker, with the price each last traded at
+/// * `GET /stocks/{ticker}` - A stock, with the price it last traded at
+/// * `GET /users/{id}` - An account, with its balance unless its privacy hides it
+/// * `GET /users/{id}/holdings` - A page of an account's holdings, unless its privacy hides them
+/// * `POST /orders` - Places a limit order for the account whose API key is sent
+pub fn router<R: StockRepository>(service: Service<R>) -> Router {
+    let state = ApiState { service };
+
+    Router::new()
+        .route("/stocks", get(stocks::list::<R>))
+        .route("/stocks/{ticker}", get(stocks::get::<R>))
+        .route("/users/{id}", get(users::get::<R>))
+        .route("/users/{id}/holdings", get(users::holdings::<R>))
+        .route("/orders", post(orders::place::<R>))
+        .with_state(state)
+}
+
+/// Serves the API on `listener` until cancelled, letting requests in progress finish
+pub async fn serve<R: StockRepository>(
+    listener: TcpListener,
+    service: Service<R>,
+    c_token: CancellationToken,
+) {
+    if let Err(err) = axum::serve(listener, router(service))
+        .with_graceful_shutdown(c_token.cancelled_owned())
+        .await
+    {
+        tracing::error!(%err, "API server stopped");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{num::NonZeroU64, time::Duration};
+
+    use axum::{
+        body::Body,
+        http::{Request, StatusCode},
+        response::IntoResponse,
+    };
+    use http_body_util::BodyExt;
+    use rse_core::{
+        error::Error as RscErr,
+        model::{Shares, audit::Actor, ticker::Ticker},
+        repo::Error as RepError,
+        test_util::{ChaosRepo, Stub},
+    };
+    use serde_json::{Value, json};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    fn service() -> (ChaosRepo<Stub>, Service<ChaosRepo<Stub>>) {
+        let chaos = ChaosRepo::new(Stub::default());
+        (chaos.clone(), Service::new(chaos))
+    }
+
+    async fn send(router: Router, request: Request<Body>) -> (StatusCode, Value) {
+        let response = router.oneshot(request).await.expect("Infallible");
+        let status = response.status();
+        let body = response
+            .into_body()
+            .collect()
+            .await
+            .expect("Body is read")
+            .to_bytes();
+
+        (status, serde_json::from_slice(&body).expect("JSON body"))
+    }
+
+    /// Registers an account, gives it an API key and lists `ABC` and `XYZ` under it
+    async fn trader(chaos: &ChaosRepo<Stub>) -> (Uuid, ApiKey) {
+        let flake = NonZeroU64::new(1).expect("Non-zero");
+        let id = chaos
+            .register_user(Some(flake), None, &Actor::System)
+            .await
+            .expect("Registered")
+            .id();
+
+        for ticker in ["XYZ", "ABC"] {
+            let ticker = Ticker::try_from(ticker).expect("Valid ticker");
+            let shares = Shares::new(100).expect("Valid shares");
+            chaos
+                .create_stock(&ticker, shares, &id, &Actor::System)
+                .await
+                .expect("Listed");
+        }
+
+        (id, chaos.create_api_key(&id).await.expect("Created"))
+    }
+
+    fn get(uri: &str) -> Request<Body> {
+        Request::get(uri)
+            .body(Body::empty())
+            .expect("Valid request")
+    }
+
+    fn order(key: Option<&str>, idempotency_key: Option<&str>, quantity: u32) -> Request<Body> {
+        let body = json!({
+            "ticker": "ABC",
+            "side": "sell",
+            "price": "1.00",
+            "quantity": quantity,
+        });
+        let mut request = Request::post("/orders").header(header::CONTENT_TYPE, "application/json");
+
+        if let Some(key) = key {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {key}"));
+        }
+
+        if let Some(idempotency_key) = idempotency_key {
+            request = request.header("idempotency-key", idempotency_key);
+        }
+
+        request
+            .body(Body::from(body.to_string()))
+            .expect("Valid request")
+    }
+
+    #[tokio::test]
+    async fn orders_need_a_key_in_use() {
+        let (chaos, service) = service();
+        let (id, key) = trader(&chaos).await;
+        chaos.create_api_key(&id).await.expect("Replaced");
+        let unknown = ApiKey::generate();
+
+        for sent in [
+            None,
+            Some(""),
+            Some("secret"),
+            Some(unknown.as_str()),
+            Some(key.as_str()),
+        ] {
+            let (status, body) = send(router(service.clone()), order(sent, None, 1)).await;
+
+            assert_eq!(status, StatusCode::UNAUTHORIZED);
+            assert_eq!(body["code"], "unauthorized");
+        }
+
+        // Only keys that could have been given out are looked up
+        assert_eq!(chaos.calls("api_key_owner"), 2);
+        assert_eq!(chaos.calls("place_order"), 0);
+    }
+
+    #[tokio::test]
+    async fn stocks_are_listed_a_page_at_a_time() {
+        let (chaos, service) = service();
+        let (id, _) = trader(&chaos).await;
+
+        let (status, body) = send(router(service), get("/stocks?offset=1&limit=1")).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!((&body["total"], &body["offset"]), (&json!(2), &json!(1)));
+        let [stock] = body["items"].as_array().expect("A list").as_slice() else {
+            panic!("Expected one stock, got {body}");
+        };
+        assert_eq!(stock["ticker"], "XYZ");
+        assert_eq!(stock["owner"], json!(id));
+        assert_eq!(stock["shares"], 100);
+        assert_eq!(stock["price"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn stocks_are_looked_up_by_ticker() {
+        let (chaos, service) = service();
+        trader(&chaos).await;
+
+        let (status, body) = send(router(service), get("/stocks/ABC")).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["ticker"], "ABC");
+        assert_eq!(body["status"], "active");
+        assert_eq!(body["last_traded"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn accounts_are_shown_as_anyone_would_see_them() {
+        let (chaos, service) = service();
+        let (id, _) = trader(&chaos).await;
+
+        let (status, body) = send(router(service.clone()), get(&format!("/users/{id}"))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["id"], json!(id));
+        assert!(body.get("disc_id").is_none(), "{body}");
+
+        let (status, body) = send(router(service), get(&format!("/users/{id}/holdings"))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!({"items": [], "total": 0, "offset": 0}));
+    }
+
+    #[tokio::test]
+    async fn orders_are_placed_for_the_key_owner() {
+        let (chaos, service) = service();
+        let (id, key) = trader(&chaos).await;
+
+        let (status, body) = send(router(service), order(Some(key.as_str()), None, 5)).await;
+
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body["order"]["user"], json!(id));
+        assert_eq!(body["order"]["quantity"], 5);
+        assert_eq!(body["fills"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn retried_orders_are_only_placed_once() {
+        let (chaos, service) = service();
+        let (_, key) = trader(&chaos).await;
+        let key = Some(key.as_str());
+
+        let (status, placed) = send(router(service.clone()), order(key, Some("once"), 5)).await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, replayed) = send(router(service.clone()), order(key, Some("once"), 5)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(replayed, placed);
+
+        let (status, body) = send(router(service), order(key, Some("once"), 6)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], "idempotency_key_reused");
+    }
+
+    #[tokio::test]
+    async fn unreadable_requests_are_answered_like_any_error() {
+        let (chaos, service) = service();
+        let request = Request::get("/stocks/TOOLONG")
+            .body(Body::empty())
+            .expect("Valid request");
+
+        let (status, body) = send(router(service), request).await;
+
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], "invalid_request");
+        assert_eq!(chaos.total_calls(), 0);
+    }
+
+    #[tokio::test]
+    async fn internal_errors_keep_their_details_to_themselves() {
+        let (chaos, service) = service();
+        chaos.fail_next("list_stocks", RepError::Unspecified);
+        let request = Request::get("/stocks?limit=10")
+            .body(Body::empty())
+            .expect("Valid request");
+
+        let (status, body) = send(router(service), request).await;
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            body,
+            json!({"code": "internal_error", "message": "Encountered an internal error"})
+        );
+    }
+
+    #[test]
+    fn rate_limits_say_when_to_retry() {
+        let response = Error::from(RscErr::RateLimited {
+            retry_after: Duration::from_millis(200),
+        })
+        .into_response();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "1");
+    }
+}
@@ -0,0 +1,122 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Placing orders for the account whose API key was sent
+
+use axum::{
+    Json,
+    extract::{State, rejection::JsonRejection},
+    http::{HeaderMap, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use rse_core::{
+    model::{
+        Price, Shares,
+        idempotency::{IdempotencyKey, Idempotent},
+        order::{Fill, NewOrder, Order, Side},
+        ticker::Ticker,
+    },
+    repo::StockRepository,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{ApiState, Error};
+
+/// The header a retried request sends the same key in, so the order is only placed once
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+/// A limit order to place. The account it is placed for is the one the API key was given to
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct OrderBody {
+    ticker: Ticker,
+    side: Side,
+    price: Price,
+    quantity: Shares,
+    /// When the order expires if it hasn't filled. Rests until filled or cancelled when unset
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// An order that was placed, and what it filled against right away
+#[derive(Debug, Serialize)]
+pub(crate) struct PlacedBody {
+    order: Order,
+    fills: Vec<Fill>,
+}
+
+/// `POST /orders`. Answers `201` once placed, or `200` with what placing it returned the first time
+/// if the `Idempotency-Key` was already sent with the same order
+pub(crate) async fn place<R: StockRepository>(
+    State(state): State<ApiState<R>>,
+    headers: HeaderMap,
+    body: Result<Json<OrderBody>, JsonRejection>,
+) -> Result<(StatusCode, Json<PlacedBody>), Error> {
+    let user = state.authenticate(&headers).await?;
+    let Json(body) = body?;
+
+    let key = headers
+        .get(IDEMPOTENCY_KEY)
+        .map(|v| {
+            v.to_str()
+                .ok()
+                .and_then(|v| IdempotencyKey::new(v).ok())
+                .ok_or_else(|| Error::InvalidRequest {
+                    reason:
+                        "The Idempotency-Key header must be 1 to 255 printable ASCII characters"
+                            .to_owned(),
+                })
+        })
+        .transpose()?;
+
+    let placed = match key {
+        Some(key) => {
+            let order = NewOrder {
+                user,
+                ticker: body.ticker,
+                side: body.side,
+                price: body.price,
+                quantity: body.quantity,
+                expires_at: body.expires_at,
+                immediate_or_cancel: false,
+            };
+            state.service.place_order_once(&key, &order).await?
+        }
+        None => Idempotent::Executed(
+            state
+                .service
+                .place_order(
+                    &user,
+                    &body.ticker,
+                    body.side,
+                    body.price,
+                    body.quantity,
+                    body.expires_at,
+                )
+                .await?,
+        ),
+    };
+
+    let status = if placed.is_replay() {
+        StatusCode::OK
+    } else {
+        StatusCode::CREATED
+    };
+    let (order, fills) = placed.into_inner();
+
+    Ok((status, Json(PlacedBody { order, fills })))
+}
@@ -0,0 +1,96 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Listing and looking up stocks
+
+use axum::{
+    Json,
+    extract::{
+        Path, Query, State,
+        rejection::{PathRejection, QueryRejection},
+    },
+};
+use chrono::{DateTime, Utc};
+use futures_util::future::try_join_all;
+use rse_core::{
+    model::{Price, StockOrdering, ticker::Ticker, view::StockView},
+    repo::StockRepository,
+};
+use serde::Serialize;
+
+use crate::{ApiState, Error, PageBody, PageQuery};
+
+/// A stock and the price it last traded at
+#[derive(Debug, Serialize)]
+pub(crate) struct PricedStock {
+    #[serde(flatten)]
+    stock: StockView,
+    /// The price it last traded at, if it ever did
+    price: Option<Price>,
+    /// When it last traded, if it ever did
+    last_traded: Option<DateTime<Utc>>,
+}
+
+/// `GET /stocks`
+pub(crate) async fn list<R: StockRepository>(
+    State(state): State<ApiState<R>>,
+    query: Result<Query<PageQuery>, QueryRejection>,
+) -> Result<Json<PageBody<PricedStock>>, Error> {
+    let Query(query) = query?;
+    let page = state
+        .service
+        .list_stocks(&query.pager(), StockOrdering::Ticker, "")
+        .await?;
+    let mut infos = try_join_all(
+        page.items
+            .iter()
+            .map(|(ticker, ..)| state.service.get_stock_info(ticker)),
+    )
+    .await?
+    .into_iter();
+
+    Ok(Json(PageBody::new(
+        page,
+        |(_, _, price, last_traded, _)| PricedStock {
+            stock: infos.next().expect("One stock per entry").into(),
+            price,
+            last_traded,
+        },
+    )))
+}
+
+/// `GET /stocks/{ticker}`
+pub(crate) async fn get<R: StockRepository>(
+    State(state): State<ApiState<R>>,
+    ticker: Result<Path<Ticker>, PathRejection>,
+) -> Result<Json<PricedStock>, Error> {
+    let Path(ticker) = ticker?;
+    let tickers = [ticker];
+    let (info, prices) = tokio::try_join!(
+        state.service.get_stock_info(&ticker),
+        state.service.latest_prices(Some(&tickers))
+    )?;
+
+    let latest = prices.first();
+
+    Ok(Json(PricedStock {
+        stock: info.into(),
+        price: latest.map(|latest| latest.price),
+        last_traded: latest.map(|latest| latest.updated_at),
+    }))
+}
@@ -0,0 +1,67 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Looking up accounts
+
+use axum::{
+    Json,
+    extract::{
+        Path, Query, State,
+        rejection::{PathRejection, QueryRejection},
+    },
+};
+use rse_core::{
+    model::{
+        HoldingOrdering,
+        view::{HoldingView, PublicUserInfo},
+    },
+    repo::StockRepository,
+};
+use uuid::Uuid;
+
+use crate::{ApiState, Error, PageBody, PageQuery};
+
+/// `GET /users/{id}`. Looked up as anyone would, so the balance is left out unless the account's
+/// privacy shows it
+pub(crate) async fn get<R: StockRepository>(
+    State(state): State<ApiState<R>>,
+    id: Result<Path<Uuid>, PathRejection>,
+) -> Result<Json<PublicUserInfo>, Error> {
+    let Path(id) = id?;
+    let info = state.service.get_account_info(&id, None).await?;
+
+    Ok(Json(info.into()))
+}
+
+/// `GET /users/{id}/holdings`. Looked up as anyone would, so accounts that hide their holdings
+/// can't be read
+pub(crate) async fn holdings<R: StockRepository>(
+    State(state): State<ApiState<R>>,
+    id: Result<Path<Uuid>, PathRejection>,
+    query: Result<Query<PageQuery>, QueryRejection>,
+) -> Result<Json<PageBody<HoldingView>>, Error> {
+    let (Path(id), Query(query)) = (id?, query?);
+    let page = state
+        .service
+        .get_holdings_pl(&id, &query.pager(), HoldingOrdering::Ticker, None)
+        .await?;
+
+    Ok(Json(PageBody::new(page, |holding| {
+        HoldingView::from(&holding)
+    })))
+}
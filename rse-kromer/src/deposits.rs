@@ -0,0 +1,315 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Turning transfers to the exchange's wallet into deposits, and crediting them as they arrive
+
+use std::time::Duration;
+
+use rse_core::{
+    Service,
+    error::Error as ServiceError,
+    model::{
+        deposit::{self, Deposit},
+        withdrawal::Address,
+    },
+    repo::StockRepository,
+};
+use tokio::select;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn};
+
+use crate::{Client, Transaction, Transport};
+
+/// Reads the deposit a transaction makes, if it's a transfer to a metaname of `name` naming an
+/// account. Kromer sent to the wallet any other way isn't anyone's to credit.
+#[must_use]
+pub fn deposit_of(tx: &Transaction, name: &str) -> Option<Deposit> {
+    if tx.kind != "transfer" || tx.sent_name.as_deref() != Some(name) {
+        return None;
+    }
+
+    Some(Deposit {
+        transaction: tx.id,
+        user: deposit::parse_metaname(tx.sent_metaname.as_deref()?)?,
+        amount: tx.value,
+        from: Address::try_from(tx.from.as_deref()?).ok()?,
+        time: tx.time,
+    })
+}
+
+/// Periodically credits deposits sent to metanames of `name`, owned by `address`, until
+/// cancelled. Picks up after the last transaction scanned, as saved by the previous run, so
+/// deposits sent while the exchange was down are still credited. On the very first run it starts
+/// from the newest transaction, so history from before deposits were taken is left alone.
+pub async fn watch_deposits<R: StockRepository, T: Transport>(
+    service: Service<R>,
+    client: Client<T>,
+    address: Address,
+    name: String,
+    every: Duration,
+    c_token: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    // The newest transaction already looked at and saved, whether or not it was a deposit
+    let mut cursor = None;
+
+    loop {
+        select! {
+            () = c_token.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        let after = match cursor {
+            Some(after) => after,
+            None => match start(&service, &client, &address).await {
+                Ok(after) => *cursor.insert(after),
+                Err(err) => {
+                    error!(%err, "Couldn't find where to start watching for deposits");
+                    continue;
+                }
+            },
+        };
+
+        let transactions = match client.transactions_after(&address, after).await {
+            Ok(transactions) => transactions,
+            Err(err) => {
+                warn!(%err, "Couldn't fetch transactions");
+                continue;
+            }
+        };
+
+        let mut scanned = after;
+
+        for tx in transactions {
+            if let Some(deposit) = deposit_of(&tx, &name) {
+                match service.credit_deposit(&address, &deposit).await {
+                    Ok(Some(balance)) => info!(
+                        transaction = deposit.transaction,
+                        user = %deposit.user,
+                        amount = %deposit.amount,
+                        %balance,
+                        "Credited deposit"
+                    ),
+                    Ok(None) => debug!(transaction = deposit.transaction, "Already credited"),
+                    // Nothing can be credited, so it's audited for an admin to send back. Tried
+                    // again next time if that fails, so it's never passed over unrecorded.
+                    Err(
+                        err @ (ServiceError::UserNotFound { .. }
+                        | ServiceError::InvalidGrant { .. }),
+                    ) => {
+                        warn!(
+                            transaction = deposit.transaction,
+                            user = %deposit.user,
+                            from = %deposit.from,
+                            %err,
+                            "Couldn't credit deposit"
+                        );
+
+                        if let Err(err) =
+                            service.record_uncredited_deposit(&address, &deposit).await
+                        {
+                            error!(transaction = deposit.transaction, %err, "Couldn't record uncredited deposit");
+                            break;
+                        }
+                    }
+                    // Tried again next time, as crediting a deposit twice does nothing
+                    Err(err) => {
+                        error!(transaction = deposit.transaction, %err, "Couldn't credit deposit");
+                        break;
+                    }
+                }
+            }
+
+            scanned = tx.id;
+        }
+
+        // Deposits move the saved cursor as they're credited or recorded, but whatever came after
+        // the last one wasn't. Scanned again next time if it can't be saved, which credits nothing twice.
+        if scanned > after {
+            match service.advance_kromer_cursor(&address, scanned).await {
+                Ok(()) => cursor = Some(scanned),
+                Err(err) => warn!(scanned, %err, "Couldn't save how far deposits were scanned"),
+            }
+        }
+    }
+}
+
+/// The transaction to start watching after: the last one scanned, or the newest transaction if
+/// the wallet has never been scanned, which is saved straight away so it's only done once
+async fn start<R: StockRepository, T: Transport>(
+    service: &Service<R>,
+    client: &Client<T>,
+    address: &Address,
+) -> Result<i64, BoxError> {
+    if let Some(cursor) = service.kromer_cursor(address).await? {
+        return Ok(cursor);
+    }
+
+    let latest = client.latest_transaction(address).await?.unwrap_or(0);
+    service.advance_kromer_cursor(address, latest).await?;
+    info!(
+        latest,
+        "Wallet never scanned before, only taking deposits newer than its latest transaction"
+    );
+
+    Ok(latest)
+}
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroU64;
+
+    use rse_core::{
+        model::audit::Actor,
+        repo::Error as RepoError,
+        test_util::{ChaosRepo, Stub},
+    };
+    use rust_decimal::Decimal;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::tests::{Fake, exchange, tx};
+
+    fn sent(metaname: Option<&str>, name: Option<&str>) -> Transaction {
+        Transaction {
+            sent_metaname: metaname.map(str::to_owned),
+            sent_name: name.map(str::to_owned),
+            ..tx(7)
+        }
+    }
+
+    #[test]
+    fn transfers_to_account_metanames_are_deposits() {
+        let user = Uuid::from_u128(7);
+        let metaname = deposit::metaname(&user);
+
+        assert_eq!(
+            deposit_of(&sent(Some(&metaname), Some("rse")), "rse"),
+            Some(Deposit {
+                transaction: 7,
+                user,
+                amount: Decimal::new(15, 1),
+                from: Address::try_from("kabcdefghi").expect("Valid address"),
+                time: tx(7).time,
+            })
+        );
+
+        assert_eq!(
+            deposit_of(&sent(Some(&metaname), Some("shop")), "rse"),
+            None
+        );
+        assert_eq!(deposit_of(&sent(Some(&metaname), None), "rse"), None);
+        assert_eq!(deposit_of(&sent(Some("someone"), Some("rse")), "rse"), None);
+        assert_eq!(deposit_of(&sent(None, Some("rse")), "rse"), None);
+        assert_eq!(
+            deposit_of(
+                &Transaction {
+                    kind: "name_purchase".to_owned(),
+                    ..sent(Some(&metaname), Some("rse"))
+                },
+                "rse"
+            ),
+            None
+        );
+    }
+
+    /// Runs the watcher over one round of transactions, then stops it
+    async fn scan<R: StockRepository>(service: &Service<R>, client: &Client<Fake>) {
+        let c_token = CancellationToken::new();
+        let watcher = tokio::spawn(watch_deposits(
+            service.clone(),
+            client.clone(),
+            exchange(),
+            "rse".to_owned(),
+            Duration::from_secs(30),
+            c_token.clone(),
+        ));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        c_token.cancel();
+        watcher.await.expect("Watcher doesn't panic");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn deposits_sent_while_down_are_credited_after_a_restart() {
+        let repo = Stub::default();
+        let service = Service::new(repo.clone());
+        let user = repo
+            .register_user(NonZeroU64::new(1), None, &Actor::System)
+            .await
+            .expect("Registered")
+            .id();
+        let deposit = |id| Transaction {
+            sent_metaname: Some(deposit::metaname(&user)),
+            sent_name: Some("rse".to_owned()),
+            ..tx(id)
+        };
+
+        let (client, fake) = Fake::client(&[1], 10);
+        fake.push(deposit(2));
+
+        let balance = async || {
+            repo.user_info(&user)
+                .await
+                .expect("Fetched")
+                .and_then(|info| info.balance)
+        };
+
+        // History from before deposits were taken is left alone
+        scan(&service, &client).await;
+        assert_eq!(service.kromer_cursor(&exchange()).await, Ok(Some(2)));
+        assert_eq!(balance().await, Some(Decimal::ZERO));
+
+        fake.push(deposit(3));
+        fake.push(tx(4));
+
+        scan(&service, &client).await;
+        assert_eq!(balance().await, Some(Decimal::new(15, 1)));
+        assert_eq!(service.kromer_cursor(&exchange()).await, Ok(Some(4)));
+
+        // Nothing is credited twice however many times it restarts
+        scan(&service, &client).await;
+        assert_eq!(balance().await, Some(Decimal::new(15, 1)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn uncredited_deposits_are_not_passed_over_unrecorded() {
+        let chaos = ChaosRepo::new(Stub::default());
+        let service = Service::new(chaos.clone());
+        let (client, fake) = Fake::client(&[1], 10);
+        scan(&service, &client).await;
+
+        fake.push(Transaction {
+            sent_metaname: Some(deposit::metaname(&Uuid::from_u128(1))),
+            sent_name: Some("rse".to_owned()),
+            ..tx(2)
+        });
+        fake.push(tx(3));
+
+        chaos.fail_next("record_uncredited_deposit", RepoError::Unspecified);
+        scan(&service, &client).await;
+        assert_eq!(service.kromer_cursor(&exchange()).await, Ok(Some(1)));
+
+        scan(&service, &client).await;
+        assert_eq!(chaos.calls("record_uncredited_deposit"), 2);
+        assert_eq!(service.kromer_cursor(&exchange()).await, Ok(Some(3)));
+    }
+}
@@ -0,0 +1,581 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A small client for a Kromer node, used to watch the exchange's wallet for deposits and pay out
+//! approved withdrawals from it. Kromer speaks the Krist API, so only the parts of it needed to
+//! read an address's transactions and make new ones are covered.
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use chrono::{DateTime, Utc};
+use rse_core::model::withdrawal::Address;
+use rust_decimal::{Decimal, prelude::ToPrimitive};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use snafu::{ResultExt, Snafu, ensure};
+
+pub use deposits::{deposit_of, watch_deposits};
+pub use payouts::pay_withdrawals;
+
+mod deposits;
+mod payouts;
+
+/// The Kromer node run for `ReconnectedCC`
+pub const DEFAULT_NODE: &str = "https://kromer.reconnected.cc/api/krist";
+
+/// The most transactions the node hands out in one page
+pub const MAX_PAGE: u32 = 1000;
+
+/// How long a request to the node may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors thrown while talking to a Kromer node
+#[derive(Debug, Snafu)]
+#[allow(missing_docs)]
+pub enum Error {
+    /// The node couldn't be reached
+    #[snafu(display("Couldn't reach the Kromer node: {source}"))]
+    Request { source: BoxError },
+    /// The node answered with a status we don't expect
+    #[snafu(display("Unexpected response from the Kromer node: {status}"))]
+    UnexpectedStatus { status: u16 },
+    /// The node answered with a body we don't understand
+    #[snafu(display("Couldn't decode the Kromer node's response: {source}"))]
+    Decode { source: serde_json::Error },
+    /// The node understood the request but refused it
+    #[snafu(display("The Kromer node refused the request: {error}"))]
+    Refused { error: String },
+}
+
+/// A boxed error from a [`Transport`]
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A response to a request, as much of it as the client needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    /// The HTTP status code
+    pub status: u16,
+    /// The response body
+    pub body: String,
+}
+
+/// How the client talks HTTP. Swapped out in tests
+pub trait Transport: Clone + Send + Sync + 'static {
+    /// Makes a GET request to `url`
+    ///
+    /// # Errors
+    /// Whatever stopped the request from getting a response
+    fn get(&self, url: &str) -> impl Future<Output = Result<Response, BoxError>> + Send;
+
+    /// Makes a POST request to `url` with a JSON `body`
+    ///
+    /// # Errors
+    /// Whatever stopped the request from getting a response
+    fn post(
+        &self,
+        url: &str,
+        body: String,
+    ) -> impl Future<Output = Result<Response, BoxError>> + Send;
+}
+
+/// A [`Transport`] making real requests
+#[derive(Debug, Clone)]
+pub struct Http(reqwest::Client);
+
+impl Default for Http {
+    fn default() -> Self {
+        Self(
+            reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        )
+    }
+}
+
+impl Transport for Http {
+    async fn get(&self, url: &str) -> Result<Response, BoxError> {
+        let res = self.0.get(url).send().await?;
+
+        Ok(Response {
+            status: res.status().as_u16(),
+            body: res.text().await?,
+        })
+    }
+
+    async fn post(&self, url: &str, body: String) -> Result<Response, BoxError> {
+        let res = self
+            .0
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await?;
+
+        Ok(Response {
+            status: res.status().as_u16(),
+            body: res.text().await?,
+        })
+    }
+}
+
+/// A transaction on the Kromer network
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Transaction {
+    /// The transaction's ID, which only ever goes up
+    pub id: i64,
+    /// The address Kromer was sent from, missing for Kromer created out of thin air
+    pub from: Option<String>,
+    /// The address Kromer was sent to
+    pub to: Option<String>,
+    /// How much was sent
+    pub value: Decimal,
+    /// When it was sent
+    pub time: DateTime<Utc>,
+    /// What sort of transaction it is, such as `transfer`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// The name it was sent to, without the `.kro`, if it was sent to one
+    pub sent_name: Option<String>,
+    /// The metaname it was sent to, as in `<metaname>@<name>.kro`
+    pub sent_metaname: Option<String>,
+}
+
+/// A page of an address's transactions, newest first
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TransactionPage {
+    /// How many transactions the address has in total
+    pub total: u64,
+    /// The transactions on this page
+    pub transactions: Vec<Transaction>,
+}
+
+/// The envelope every response from the node comes in
+#[derive(Deserialize)]
+struct Envelope {
+    ok: bool,
+    error: Option<String>,
+}
+
+/// A transaction to make from the wallet owning `privatekey`
+#[derive(Serialize)]
+struct NewTransaction<'a> {
+    privatekey: &'a str,
+    to: &'a str,
+    amount: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<&'a str>,
+}
+
+/// The node's answer to a new transaction
+#[derive(Deserialize)]
+struct Made {
+    transaction: Transaction,
+}
+
+/// Reads a response from the node, which says why in the usual envelope when it refuses
+fn decode<T: DeserializeOwned>(res: &Response) -> Result<T, Error> {
+    // Refusals come with a 4xx status, but say why in the usual envelope
+    if let Ok(Envelope {
+        ok: false,
+        error: Some(error),
+    }) = serde_json::from_str(&res.body)
+    {
+        return RefusedSnafu { error }.fail();
+    }
+
+    ensure!(
+        res.status == 200,
+        UnexpectedStatusSnafu { status: res.status }
+    );
+
+    serde_json::from_str(&res.body).context(DecodeSnafu)
+}
+
+/// A cheaply cloneable client for a Kromer node
+#[derive(Debug, Clone)]
+pub struct Client<T: Transport = Http> {
+    transport: T,
+    node: Arc<str>,
+}
+
+impl Client {
+    /// Creates a client for the node at `node`, such as [`DEFAULT_NODE`]
+    #[must_use]
+    pub fn http(node: &str) -> Self {
+        Self::new(Http::default(), node)
+    }
+}
+
+impl<T: Transport> Client<T> {
+    /// Creates a client for the node at `node`, making its requests through `transport`
+    pub fn new(transport: T, node: &str) -> Self {
+        Self {
+            transport,
+            node: node.trim_end_matches('/').into(),
+        }
+    }
+
+    /// Lists up to `limit` of the transactions to or from `address`, newest first, skipping the
+    /// `offset` newest. The node hands out at most [`MAX_PAGE`] at once.
+    ///
+    /// # Errors
+    /// * [`Request`](Error::Request), [`UnexpectedStatus`](Error::UnexpectedStatus),
+    ///   [`Decode`](Error::Decode) - The node couldn't be asked or gave an answer we don't
+    ///   understand
+    /// * [`Refused`](Error::Refused) - The node refused to list them
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub async fn transactions(
+        &self,
+        address: &Address,
+        offset: u64,
+        limit: u32,
+    ) -> Result<TransactionPage, Error> {
+        let url = format!(
+            "{}/addresses/{address}/transactions?offset={offset}&limit={}",
+            self.node,
+            limit.min(MAX_PAGE)
+        );
+        let res = self.transport.get(&url).await.context(RequestSnafu)?;
+
+        decode(&res)
+    }
+
+    /// Sends `amount` from the wallet owning `private_key` to `to`, returning the transaction it
+    /// was sent in. `metadata` is attached to the transaction for anyone looking it up.
+    ///
+    /// # Errors
+    /// * [`Refused`](Error::Refused) - The node refused to send it, such as when the wallet
+    ///   can't cover it. Nothing was sent
+    /// * [`Request`](Error::Request), [`UnexpectedStatus`](Error::UnexpectedStatus),
+    ///   [`Decode`](Error::Decode) - The node couldn't be asked or gave an answer we don't
+    ///   understand. It may or may not have been sent
+    #[tracing::instrument(skip(self, private_key), level = "debug")]
+    pub async fn make_transaction(
+        &self,
+        private_key: &str,
+        to: &Address,
+        amount: Decimal,
+        metadata: Option<&str>,
+    ) -> Result<Transaction, Error> {
+        let body = NewTransaction {
+            privatekey: private_key,
+            to: to.as_str(),
+            amount: amount
+                .to_f64()
+                .expect("Every decimal is close to some float"),
+            metadata,
+        };
+        let body = serde_json::to_string(&body).expect("Plain structs always serialize");
+
+        let url = format!("{}/transactions", self.node);
+        let res = self
+            .transport
+            .post(&url, body)
+            .await
+            .context(RequestSnafu)?;
+
+        decode::<Made>(&res).map(|made| made.transaction)
+    }
+
+    /// Fetches every transaction to or from `address` newer than `after`, oldest first
+    ///
+    /// # Errors
+    /// See [`transactions`](Self::transactions)
+    pub async fn transactions_after(
+        &self,
+        address: &Address,
+        after: i64,
+    ) -> Result<Vec<Transaction>, Error> {
+        let mut newer = Vec::new();
+
+        loop {
+            let page = self
+                .transactions(address, newer.len() as u64, MAX_PAGE)
+                .await?;
+            let fetched = page.transactions.len();
+
+            let reached = page.transactions.iter().any(|tx| tx.id <= after);
+            newer.extend(page.transactions.into_iter().filter(|tx| tx.id > after));
+
+            if reached || fetched == 0 || newer.len() as u64 >= page.total {
+                break;
+            }
+        }
+
+        newer.reverse();
+        Ok(newer)
+    }
+
+    /// Fetches the ID of the newest transaction to or from `address`, if it has any
+    ///
+    /// # Errors
+    /// See [`transactions`](Self::transactions)
+    pub async fn latest_transaction(&self, address: &Address) -> Result<Option<i64>, Error> {
+        let page = self.transactions(address, 0, 1).await?;
+        Ok(page.transactions.first().map(|tx| tx.id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        Mutex, PoisonError,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    };
+
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    /// Serves an address's transactions from a list, newest first, as the node would, and adds
+    /// the ones made through it to the front
+    #[derive(Clone)]
+    pub(crate) struct Fake {
+        pub(crate) transactions: Arc<Mutex<Vec<Transaction>>>,
+        pub(crate) requests: Arc<AtomicUsize>,
+        /// The bodies of the transactions asked for, refused or not
+        pub(crate) made: Arc<Mutex<Vec<Value>>>,
+        /// Whether new transactions are refused as the wallet can't cover them
+        pub(crate) broke: Arc<AtomicBool>,
+        page: usize,
+    }
+
+    impl Fake {
+        /// A client over `ids`, each a transfer of 1.5 KRO, handing out `page` at a time
+        pub(crate) fn client(ids: &[i64], page: usize) -> (Client<Self>, Self) {
+            let fake = Self {
+                transactions: Arc::new(Mutex::new(ids.iter().rev().map(|&id| tx(id)).collect())),
+                requests: Arc::new(AtomicUsize::new(0)),
+                made: Arc::new(Mutex::new(Vec::new())),
+                broke: Arc::new(AtomicBool::new(false)),
+                page,
+            };
+
+            (Client::new(fake.clone(), "http://node/"), fake)
+        }
+
+        /// Adds `tx` as the newest transaction
+        pub(crate) fn push(&self, tx: Transaction) {
+            self.transactions
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(0, tx);
+        }
+    }
+
+    /// A transaction as the node writes it
+    fn encode(tx: &Transaction) -> Value {
+        json!({
+            "id": tx.id,
+            "from": tx.from,
+            "to": tx.to,
+            "value": tx.value.to_f64(),
+            "time": tx.time,
+            "name": null,
+            "metadata": null,
+            "sent_metaname": tx.sent_metaname,
+            "sent_name": tx.sent_name,
+            "type": tx.kind,
+        })
+    }
+
+    impl Transport for Fake {
+        async fn get(&self, url: &str) -> Result<Response, BoxError> {
+            assert!(url.starts_with("http://node/addresses/kexchange0/transactions?"));
+            self.requests.fetch_add(1, Ordering::SeqCst);
+
+            let param = |name: &str| -> usize {
+                url.split(['?', '&'])
+                    .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))
+                    .and_then(|v| v.parse().ok())
+                    .expect("Param given")
+            };
+            let (offset, limit) = (param("offset"), param("limit").min(self.page));
+
+            let transactions = self
+                .transactions
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let page: Vec<_> = transactions
+                .iter()
+                .skip(offset)
+                .take(limit)
+                .map(encode)
+                .collect();
+
+            Ok(Response {
+                status: 200,
+                body: json!({
+                    "ok": true,
+                    "count": page.len(),
+                    "total": transactions.len(),
+                    "transactions": page,
+                })
+                .to_string(),
+            })
+        }
+
+        async fn post(&self, url: &str, body: String) -> Result<Response, BoxError> {
+            assert_eq!(url, "http://node/transactions");
+            let body: Value = serde_json::from_str(&body)?;
+            self.made
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(body.clone());
+
+            if self.broke.load(Ordering::SeqCst) {
+                return Ok(Response {
+                    status: 403,
+                    body: r#"{"ok":false,"error":"insufficient_funds"}"#.to_owned(),
+                });
+            }
+
+            let mut transactions = self
+                .transactions
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let made = Transaction {
+                id: transactions.first().map_or(1, |tx| tx.id + 1),
+                from: Some("kexchange0".to_owned()),
+                to: body["to"].as_str().map(str::to_owned),
+                value: body["amount"].to_string().parse()?,
+                kind: "transfer".to_owned(),
+                ..tx(0)
+            };
+            transactions.insert(0, made.clone());
+
+            Ok(Response {
+                status: 200,
+                body: json!({ "ok": true, "transaction": encode(&made) }).to_string(),
+            })
+        }
+    }
+
+    pub(crate) fn tx(id: i64) -> Transaction {
+        Transaction {
+            id,
+            from: Some("kabcdefghi".to_owned()),
+            to: Some("kexchange0".to_owned()),
+            value: Decimal::new(15, 1),
+            time: "2025-10-15T12:00:00Z".parse().expect("Valid time"),
+            kind: "transfer".to_owned(),
+            sent_name: None,
+            sent_metaname: None,
+        }
+    }
+
+    pub(crate) fn exchange() -> Address {
+        Address::try_from("kexchange0").expect("Valid address")
+    }
+
+    #[tokio::test]
+    async fn transactions_are_decoded() {
+        let (client, _) = Fake::client(&[1, 2], 10);
+
+        let page = client
+            .transactions(&exchange(), 0, 10)
+            .await
+            .expect("Listed");
+
+        assert_eq!(page.total, 2);
+        assert_eq!(page.transactions, vec![tx(2), tx(1)]);
+    }
+
+    #[tokio::test]
+    async fn newer_transactions_are_paged_through_oldest_first() {
+        let (client, fake) = Fake::client(&(1..=10).collect::<Vec<_>>(), 3);
+
+        let newer = client
+            .transactions_after(&exchange(), 4)
+            .await
+            .expect("Listed");
+
+        assert_eq!(
+            newer.iter().map(|tx| tx.id).collect::<Vec<_>>(),
+            vec![5, 6, 7, 8, 9, 10]
+        );
+        // Stops at the page reaching back to the cursor
+        assert_eq!(fake.requests.load(Ordering::SeqCst), 3);
+
+        let all = client
+            .transactions_after(&exchange(), 0)
+            .await
+            .expect("Listed");
+        assert_eq!(all.len(), 10);
+        assert_eq!(
+            client.latest_transaction(&exchange()).await.ok(),
+            Some(Some(10))
+        );
+    }
+
+    #[derive(Clone)]
+    struct Refusing;
+
+    impl Transport for Refusing {
+        async fn get(&self, _url: &str) -> Result<Response, BoxError> {
+            Ok(Response {
+                status: 400,
+                body: r#"{"ok":false,"error":"invalid_parameter","parameter":"address"}"#
+                    .to_owned(),
+            })
+        }
+
+        async fn post(&self, url: &str, _body: String) -> Result<Response, BoxError> {
+            self.get(url).await
+        }
+    }
+
+    #[tokio::test]
+    async fn refusals_say_why() {
+        let client = Client::new(Refusing, DEFAULT_NODE);
+
+        let err = client.transactions(&exchange(), 0, 1).await;
+
+        assert!(matches!(err, Err(Error::Refused { error }) if error == "invalid_parameter"));
+    }
+
+    #[tokio::test]
+    async fn transactions_are_made_from_the_wallet() {
+        let (client, fake) = Fake::client(&[1, 2], 10);
+        let to = Address::try_from("kabcdefghi").expect("Valid address");
+
+        let made = client
+            .make_transaction("secret", &to, Decimal::new(1250, 2), Some("withdrawal=7"))
+            .await
+            .expect("Made");
+
+        assert_eq!(made.id, 3);
+        assert_eq!(made.value, Decimal::new(1250, 2));
+        assert_eq!(made.to.as_deref(), Some("kabcdefghi"));
+        assert_eq!(
+            fake.made.lock().unwrap_or_else(PoisonError::into_inner)[..],
+            [json!({
+                "privatekey": "secret",
+                "to": "kabcdefghi",
+                "amount": 12.5,
+                "metadata": "withdrawal=7",
+            })]
+        );
+
+        fake.broke.store(true, Ordering::SeqCst);
+        let err = client
+            .make_transaction("secret", &to, Decimal::ONE, None)
+            .await;
+        assert!(matches!(err, Err(Error::Refused { error }) if error == "insufficient_funds"));
+    }
+}
@@ -0,0 +1,221 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Paying out approved withdrawals from the exchange's wallet
+
+use std::time::Duration;
+
+use rse_core::{Service, model::withdrawal::Withdrawal, repo::StockRepository};
+use tokio::select;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+use crate::{Client, Error, Transport};
+
+/// Periodically pays out withdrawals admins have approved from the wallet owning `private_key`,
+/// until cancelled. Each withdrawal is claimed before it is sent, so one is never paid twice. A
+/// payout the node refuses is tried again later, while one whose outcome is unknown is left
+/// claimed for an admin to check by hand.
+pub async fn pay_withdrawals<R: StockRepository, T: Transport>(
+    service: Service<R>,
+    client: Client<T>,
+    private_key: String,
+    every: Duration,
+    c_token: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        select! {
+            () = c_token.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        loop {
+            let withdrawal = match service.claim_payout().await {
+                Ok(Some(withdrawal)) => withdrawal,
+                Ok(None) => break,
+                Err(err) => {
+                    error!(%err, "Couldn't claim a withdrawal to pay out");
+                    break;
+                }
+            };
+
+            if !pay(&service, &client, &private_key, &withdrawal).await {
+                break;
+            }
+        }
+    }
+}
+
+/// Sends a claimed withdrawal and records the transaction it went out in. Returns whether to go
+/// on to the next one, which is pointless once the node has refused one.
+async fn pay<R: StockRepository, T: Transport>(
+    service: &Service<R>,
+    client: &Client<T>,
+    private_key: &str,
+    withdrawal: &Withdrawal,
+) -> bool {
+    let metadata = format!("withdrawal={}", withdrawal.id);
+    let sent = client
+        .make_transaction(
+            private_key,
+            &withdrawal.address,
+            withdrawal.amount,
+            Some(&metadata),
+        )
+        .await;
+
+    match sent {
+        Ok(tx) => {
+            info!(
+                withdrawal = withdrawal.id,
+                transaction = tx.id,
+                user = %withdrawal.user,
+                amount = %withdrawal.amount,
+                "Paid out withdrawal"
+            );
+
+            // Still claimed, so it isn't sent again either way
+            if let Err(err) = service.record_payout(withdrawal.id, tx.id).await {
+                error!(
+                    withdrawal = withdrawal.id,
+                    transaction = tx.id,
+                    %err,
+                    "Couldn't record a payout that was sent"
+                );
+            }
+
+            true
+        }
+        // Nothing was sent, so it's safe to try again once the wallet can cover it
+        Err(Error::Refused { error }) => {
+            warn!(withdrawal = withdrawal.id, %error, "Payout refused, trying again later");
+
+            if let Err(err) = service.release_payout(withdrawal.id).await {
+                error!(withdrawal = withdrawal.id, %err, "Couldn't release a refused payout");
+            }
+
+            false
+        }
+        Err(err) => {
+            error!(
+                withdrawal = withdrawal.id,
+                %err,
+                "Couldn't tell whether a payout was sent, leaving it for an admin to check"
+            );
+
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        num::NonZeroU64,
+        sync::{PoisonError, atomic::Ordering},
+    };
+
+    use rse_core::model::{audit::Actor, deposit::Deposit, withdrawal::Address};
+    use rse_core::test_util::Stub;
+    use rust_decimal::Decimal;
+
+    use super::*;
+    use crate::tests::{Fake, exchange, tx};
+
+    #[tokio::test(start_paused = true)]
+    async fn approved_withdrawals_are_paid_out_once() {
+        let repo = Stub::default();
+        let service = Service::new(repo.clone());
+        let user = repo
+            .register_user(NonZeroU64::new(1), None, &Actor::System)
+            .await
+            .expect("Registered")
+            .id();
+        let to = Address::try_from("kabcdefghi").expect("Valid address");
+
+        let deposit = Deposit {
+            transaction: 1,
+            user,
+            amount: Decimal::TEN,
+            from: to,
+            time: tx(1).time,
+        };
+        service
+            .credit_deposit(&exchange(), &deposit)
+            .await
+            .expect("Credited");
+
+        let approved = service
+            .withdraw(&user, Decimal::new(4, 0), &to)
+            .await
+            .expect("Requested");
+        service
+            .approve_withdrawal(approved.id, &Actor::System)
+            .await
+            .expect("Approved");
+        service
+            .withdraw(&user, Decimal::ONE, &to)
+            .await
+            .expect("Requested");
+
+        let (client, fake) = Fake::client(&[1], 10);
+        fake.broke.store(true, Ordering::SeqCst);
+
+        let c_token = CancellationToken::new();
+        let payer = tokio::spawn(pay_withdrawals(
+            service.clone(),
+            client,
+            "secret".to_owned(),
+            Duration::from_secs(30),
+            c_token.clone(),
+        ));
+        let made = || {
+            fake.made
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .len()
+        };
+
+        // Refused, so tried again once the wallet can cover it
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(made(), 1);
+
+        fake.broke.store(false, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_secs(30)).await;
+        assert_eq!(made(), 2);
+
+        // Pending withdrawals aren't paid, and paid ones aren't paid again
+        tokio::time::sleep(Duration::from_mins(1)).await;
+        assert_eq!(made(), 2);
+        assert_eq!(service.claim_payout().await, Ok(None));
+
+        let paid = fake
+            .transactions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)[0]
+            .clone();
+        assert_eq!(paid.to.as_deref(), Some("kabcdefghi"));
+        assert_eq!(paid.value, Decimal::new(4, 0));
+
+        c_token.cancel();
+        payer.await.expect("Payer doesn't panic");
+    }
+}
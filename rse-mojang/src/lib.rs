@@ -0,0 +1,386 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A small client for Mojang's API, used to resolve Minecraft usernames to the UUIDs accounts are
+//! linked by. Requests are spaced out to stay within Mojang's rate limit, and back off entirely
+//! for a while once it is hit.
+
+use std::{
+    future::Future,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use snafu::{ResultExt, Snafu, ensure};
+use uuid::Uuid;
+
+/// Where profiles are looked up by username
+pub const PROFILE_API: &str = "https://api.mojang.com/users/profiles/minecraft";
+
+/// The longest a Minecraft username can be
+pub const MAX_USERNAME: usize = 16;
+
+/// The least time between two requests. Mojang allows around 600 requests every 10 minutes
+const MIN_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The longest a request waits for its turn before giving up as rate limited
+const MAX_WAIT: Duration = Duration::from_secs(2);
+
+/// How long requests are held off after Mojang rate limits us, if it doesn't say
+const DEFAULT_BACKOFF: Duration = Duration::from_mins(1);
+
+/// How long a request to Mojang may take
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Errors thrown while resolving a username
+#[derive(Debug, Snafu)]
+#[allow(missing_docs)]
+pub enum Error {
+    /// The name can't belong to any Minecraft player, so Mojang wasn't asked
+    #[snafu(display(r#""{name}" is not a valid Minecraft username"#))]
+    InvalidUsername { name: String },
+    /// No player has this username
+    #[snafu(display(r#"There is no Minecraft player named "{name}""#))]
+    NotFound { name: String },
+    /// Too many requests were made recently, either by us or according to Mojang
+    #[snafu(display("Rate limited by Mojang"))]
+    RateLimited,
+    /// Mojang couldn't be reached
+    #[snafu(display("Couldn't reach Mojang: {source}"))]
+    Request { source: BoxError },
+    /// Mojang answered with a status we don't expect
+    #[snafu(display("Unexpected response from Mojang: {status}"))]
+    UnexpectedStatus { status: u16 },
+    /// Mojang answered with a body we don't understand
+    #[snafu(display("Couldn't decode Mojang's response: {source}"))]
+    Decode { source: serde_json::Error },
+}
+
+impl Error {
+    /// Whether the lookup failed because of Mojang rather than the username, in which case the
+    /// player's UUID is the only way to identify them for now
+    #[must_use]
+    pub const fn is_unavailable(&self) -> bool {
+        !matches!(self, Self::InvalidUsername { .. } | Self::NotFound { .. })
+    }
+}
+
+/// A boxed error from a [`Transport`]
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// A response to a request, as much of it as the client needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    /// The HTTP status code
+    pub status: u16,
+    /// How long to wait before trying again, from the `Retry-After` header
+    pub retry_after: Option<Duration>,
+    /// The response body
+    pub body: String,
+}
+
+/// How the client talks HTTP. Swapped out in tests
+pub trait Transport: Clone + Send + Sync + 'static {
+    /// Makes a GET request to `url`
+    ///
+    /// # Errors
+    /// Whatever stopped the request from getting a response
+    fn get(&self, url: &str) -> impl Future<Output = Result<Response, BoxError>> + Send;
+}
+
+/// A [`Transport`] making real requests
+#[derive(Debug, Clone)]
+pub struct Http(reqwest::Client);
+
+impl Default for Http {
+    fn default() -> Self {
+        Self(
+            reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+        )
+    }
+}
+
+impl Transport for Http {
+    async fn get(&self, url: &str) -> Result<Response, BoxError> {
+        let res = self.0.get(url).send().await?;
+
+        let retry_after = res
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok()?.parse().ok())
+            .map(Duration::from_secs);
+
+        Ok(Response {
+            status: res.status().as_u16(),
+            retry_after,
+            body: res.text().await?,
+        })
+    }
+}
+
+/// A Minecraft player's profile
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Profile {
+    /// The player's UUID
+    pub id: Uuid,
+    /// The player's username, as they capitalise it
+    pub name: String,
+}
+
+/// A cheaply cloneable client for Mojang's API
+#[derive(Debug, Clone)]
+pub struct Client<T: Transport = Http> {
+    transport: T,
+    limiter: Arc<Mutex<Limiter>>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new(Http::default())
+    }
+}
+
+impl<T: Transport> Client<T> {
+    /// Creates a client making its requests through `transport`
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            limiter: Arc::new(Mutex::new(Limiter::new(MIN_INTERVAL))),
+        }
+    }
+
+    /// Looks up the profile of the player currently using `name`. Usernames are case insensitive.
+    ///
+    /// # Errors
+    /// * [`InvalidUsername`](Error::InvalidUsername) - `name` can't be a username
+    /// * [`NotFound`](Error::NotFound) - No player is using `name`
+    /// * [`RateLimited`](Error::RateLimited) - Too many lookups were made recently
+    /// * [`Request`](Error::Request), [`UnexpectedStatus`](Error::UnexpectedStatus),
+    ///   [`Decode`](Error::Decode) - Mojang couldn't be asked or gave an answer we don't understand
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub async fn profile(&self, name: &str) -> Result<Profile, Error> {
+        ensure!(is_valid_username(name), InvalidUsernameSnafu { name });
+
+        let wait = self
+            .limiter
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .acquire(Instant::now())?;
+        tokio::time::sleep(wait).await;
+
+        let res = self
+            .transport
+            .get(&format!("{PROFILE_API}/{name}"))
+            .await
+            .context(RequestSnafu)?;
+
+        match res.status {
+            200 => serde_json::from_str(&res.body).context(DecodeSnafu),
+            204 | 404 => NotFoundSnafu { name }.fail(),
+            429 => {
+                let backoff = res.retry_after.unwrap_or(DEFAULT_BACKOFF);
+                tracing::warn!(?backoff, "Rate limited by Mojang");
+
+                self.limiter
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .block(Instant::now() + backoff);
+                RateLimitedSnafu.fail()
+            }
+            status => UnexpectedStatusSnafu { status }.fail(),
+        }
+    }
+}
+
+/// Whether `name` could be a Minecraft username, so is worth looking up
+#[must_use]
+pub fn is_valid_username(name: &str) -> bool {
+    (1..=MAX_USERNAME).contains(&name.len())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Spaces requests out, and holds them off entirely after being rate limited
+#[derive(Debug)]
+struct Limiter {
+    interval: Duration,
+    /// When the next request may be made
+    next: Option<Instant>,
+    /// When requests may be made again after being rate limited
+    blocked_until: Option<Instant>,
+}
+
+impl Limiter {
+    const fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: None,
+            blocked_until: None,
+        }
+    }
+
+    /// Reserves the next slot for a request, returning how long to wait for it. Fails rather than
+    /// waiting too long.
+    fn acquire(&mut self, now: Instant) -> Result<Duration, Error> {
+        if self.blocked_until.is_some_and(|until| now < until) {
+            return RateLimitedSnafu.fail();
+        }
+
+        let slot = self.next.map_or(now, |next| next.max(now));
+        let wait = slot - now;
+        ensure!(wait <= MAX_WAIT, RateLimitedSnafu);
+
+        self.next = Some(slot + self.interval);
+        Ok(wait)
+    }
+
+    /// Holds off every request until `until`
+    const fn block(&mut self, until: Instant) {
+        self.blocked_until = Some(until);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// Answers every request with the same response, counting them
+    #[derive(Clone)]
+    struct Fake {
+        response: Response,
+        requests: Arc<AtomicUsize>,
+    }
+
+    impl Fake {
+        fn client(status: u16, body: &str) -> (Client<Self>, Arc<AtomicUsize>) {
+            let requests = Arc::new(AtomicUsize::new(0));
+            let fake = Self {
+                response: Response {
+                    status,
+                    retry_after: None,
+                    body: body.to_owned(),
+                },
+                requests: requests.clone(),
+            };
+
+            (Client::new(fake), requests)
+        }
+    }
+
+    impl Transport for Fake {
+        async fn get(&self, url: &str) -> Result<Response, BoxError> {
+            assert!(url.starts_with(PROFILE_API));
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            Ok(self.response.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn profiles_are_decoded() {
+        let (client, _) = Fake::client(
+            200,
+            r#"{"id":"069a79f444e94726a5befca90e38aaf5","name":"Notch"}"#,
+        );
+
+        let profile = client.profile("notch").await.expect("Found");
+
+        assert_eq!(
+            profile,
+            Profile {
+                id: Uuid::parse_str("069a79f4-44e9-4726-a5be-fca90e38aaf5").expect("Valid"),
+                name: "Notch".to_owned(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn no_content_is_not_found() {
+        let (client, _) = Fake::client(204, "");
+
+        let err = client.profile("nobody").await;
+
+        assert!(matches!(err, Err(Error::NotFound { name }) if name == "nobody"));
+    }
+
+    #[tokio::test]
+    async fn missing_profiles_are_not_found() {
+        let (client, _) = Fake::client(404, r#"{"errorMessage":"Couldn't find any profile"}"#);
+
+        let err = client.profile("nobody").await.expect_err("Missing");
+
+        assert!(matches!(err, Error::NotFound { .. }));
+        assert!(!err.is_unavailable());
+    }
+
+    #[tokio::test]
+    async fn rate_limits_hold_off_later_requests() {
+        let (client, requests) = Fake::client(429, "");
+
+        let first = client.profile("notch").await.expect_err("Limited");
+        let second = client.profile("jeb_").await.expect_err("Held off");
+
+        assert!(matches!(first, Error::RateLimited));
+        assert!(matches!(second, Error::RateLimited));
+        assert!(first.is_unavailable());
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn invalid_usernames_are_not_looked_up() {
+        let (client, requests) = Fake::client(200, "");
+
+        for name in ["", "has space", "seventeen_chars_x", "../admin"] {
+            let err = client.profile(name).await;
+            assert!(matches!(err, Err(Error::InvalidUsername { .. })), "{name}");
+        }
+
+        assert_eq!(requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn unexpected_statuses_are_unavailable() {
+        let (client, _) = Fake::client(503, "");
+
+        let err = client.profile("notch").await.expect_err("Down");
+
+        assert!(matches!(err, Error::UnexpectedStatus { status: 503 }));
+        assert!(err.is_unavailable());
+    }
+
+    #[test]
+    fn requests_are_spaced_out() {
+        let mut limiter = Limiter::new(Duration::from_secs(1));
+        let now = Instant::now();
+
+        assert_eq!(limiter.acquire(now).expect("Free"), Duration::ZERO);
+        assert_eq!(
+            limiter.acquire(now).expect("Queued"),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            limiter.acquire(now).expect("Queued"),
+            Duration::from_secs(2)
+        );
+        assert!(matches!(limiter.acquire(now), Err(Error::RateLimited)));
+    }
+}
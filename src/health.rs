@@ -0,0 +1,149 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A minimal HTTP server answering liveness and readiness probes from an orchestrator
+
+use std::time::Duration;
+
+use rse_core::{Service, model::PoolUsage, repo::StockRepository};
+use rse_discord::Gateway;
+use serde_json::json;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    select,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn};
+
+/// How long each dependency has to answer a readiness check before it is reported as failing
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a client has to send its request before the connection is dropped
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The most of a request that is read, which is plenty for the request line
+const MAX_REQUEST: usize = 1024;
+
+/// Serves probes on `listener` until cancelled.
+///
+/// * `/healthz` - Always OK while the process is up
+/// * `/readyz` - OK if the database answers and, when the bot is running, it is connected to the
+///   Discord gateway. Otherwise `503`, naming the failing dependencies. Either way, the body
+///   reports how busy the pool of database connections is
+pub async fn serve<R: StockRepository>(
+    listener: TcpListener,
+    service: Service<R>,
+    gateway: Option<Gateway>,
+    c_token: CancellationToken,
+) {
+    loop {
+        let stream = select! {
+            () = c_token.cancelled() => return,
+            res = listener.accept() => match res {
+                Ok((stream, _)) => stream,
+                Err(err) => {
+                    warn!(%err, "Couldn't accept health check connection");
+                    continue;
+                }
+            }
+        };
+
+        let (service, gateway) = (service.clone(), gateway.clone());
+        tokio::spawn(async move {
+            if let Err(err) = respond(stream, &service, gateway.as_ref()).await {
+                debug!(%err, "Health check connection failed");
+            }
+        });
+    }
+}
+
+/// Answers the single request sent on `stream`, then closes it
+async fn respond<R: StockRepository>(
+    mut stream: TcpStream,
+    service: &Service<R>,
+    gateway: Option<&Gateway>,
+) -> std::io::Result<()> {
+    let mut buf = [0; MAX_REQUEST];
+    let len = tokio::time::timeout(READ_TIMEOUT, stream.read(&mut buf))
+        .await
+        .map_err(|_| std::io::ErrorKind::TimedOut)??;
+
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let mut parts = request.split_whitespace();
+
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/healthz")) => ("200 OK", json!({ "status": "ok" })),
+        (Some("GET"), Some("/readyz")) => readiness(service, gateway).await,
+        (Some("GET"), _) => ("404 Not Found", json!({ "error": "not found" })),
+        _ => (
+            "405 Method Not Allowed",
+            json!({ "error": "method not allowed" }),
+        ),
+    };
+
+    let body = body.to_string();
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Checks every dependency at once, returning the status line and body to answer with
+async fn readiness<R: StockRepository>(
+    service: &Service<R>,
+    gateway: Option<&Gateway>,
+) -> (&'static str, serde_json::Value) {
+    let discord = async {
+        match gateway {
+            Some(gateway) => tokio::time::timeout(CHECK_TIMEOUT, gateway.health())
+                .await
+                .is_ok_and(|health| health.is_connected()),
+            // Not running, so nothing to wait on
+            None => true,
+        }
+    };
+
+    let (database, discord) = tokio::join!(service.ping(CHECK_TIMEOUT), discord);
+
+    let failing: Vec<&str> = [("database", database.is_ok()), ("discord", discord)]
+        .into_iter()
+        .filter_map(|(name, ok)| (!ok).then_some(name))
+        .collect();
+
+    // Reported rather than failed on, as a busy pool drains by itself and pulling the server out
+    // of rotation would only move the load elsewhere
+    let pool = service.pool_usage();
+
+    if pool.is_some_and(PoolUsage::is_saturated) {
+        warn!(?pool, "Database pool is saturated");
+    }
+
+    if failing.is_empty() {
+        ("200 OK", json!({ "status": "ready", "pool": pool }))
+    } else {
+        warn!(?failing, "Not ready");
+        (
+            "503 Service Unavailable",
+            json!({ "status": "unavailable", "failing": failing, "pool": pool }),
+        )
+    }
+}
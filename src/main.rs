@@ -18,18 +18,43 @@
 
 #![allow(missing_docs)]
 
-use rse_core::{Service, repo::PgPort};
+use std::{fs::File, io::BufReader, path::PathBuf, time::Duration};
+
+use chrono::{NaiveTime, TimeDelta};
+use color_eyre::eyre::{OptionExt, bail};
+use flate2::read::GzDecoder;
+
+use rse_core::{
+    Service,
+    blocklist::TickerBlocklist,
+    calendar::{AfterHours, MarketCalendar},
+    limiter::{AccountMaturity, Rate, RateLimits},
+    matching::PriceBand,
+    model::{fee::FeeSchedule, withdrawal::Address},
+    repo::{CachedRepo, PgPort, RetryPolicy, RetryingRepo, StockRepository},
+    seed::Seed,
+    task::TaskRegistry,
+};
 use tokio::{
     select,
     signal::unix::{SignalKind, signal},
 };
 use tokio_util::sync::CancellationToken;
-use tracing::info;
-use tracing_subscriber::layer::SubscriberExt;
+use tracing::{debug, error, info, warn};
+use tracing_subscriber::{Layer, layer::SubscriberExt};
+
+mod health;
+mod startup;
 
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
-    let fmt_layer = tracing_subscriber::fmt::Layer::default();
+    // `RUST_LOG_FORMAT=json` switches to structured output for log collectors
+    let json_logs = std::env::var("RUST_LOG_FORMAT").is_ok_and(|v| v.eq_ignore_ascii_case("json"));
+    let fmt_layer = if json_logs {
+        tracing_subscriber::fmt::Layer::default().json().boxed()
+    } else {
+        tracing_subscriber::fmt::Layer::default().boxed()
+    };
 
     let subscriber = tracing_subscriber::registry().with(fmt_layer);
 
@@ -41,15 +66,169 @@ async fn main() -> color_eyre::Result<()> {
 
     dotenvy::dotenv().ok();
 
-    let db_url = std::env::var("DATABASE_URL").expect("'DATABASE_URL' not set");
+    let one_off = one_off()?;
+    let (config, pool) = startup::run(one_off.is_some()).await?;
+
+    let retry = RetryPolicy {
+        max_attempts: config.retry.max_attempts,
+        base_delay: config.retry.base_delay,
+    };
+
+    let mut service = Service::new(CachedRepo::new(RetryingRepo::new(
+        PgPort::new(pool).with_slow_query(config.slow_query),
+        retry,
+    )))
+    .with_issuance_cap(config.trading.daily_issuance_cap_pct)
+    .with_ticker_blocklist(TickerBlocklist::new(&config.trading.blocked_tickers))
+    .with_admins(config.discord.admin_ids.iter().copied())
+    .with_rate_limits(RateLimits {
+        trades: Rate::per_minute(config.rate_limits.trades_per_minute),
+        lookups: Rate::per_minute(config.rate_limits.lookups_per_minute),
+        registrations: Rate::per_hour(config.rate_limits.registrations_per_hour),
+    });
+
+    if let Some(treasury) = config.trading.treasury_account {
+        service = service.with_fees(FeeSchedule {
+            bps: config.trading.fee_bps,
+            treasury,
+        });
+    }
+
+    if let Some(pct) = config.trading.price_band_pct {
+        service = service.with_price_band(PriceBand {
+            pct,
+            lookback: config.trading.price_band_lookback,
+        });
+    }
+
+    if let Some(hours) = config.trading.new_account_hours {
+        service = service.with_account_maturity(AccountMaturity {
+            age: TimeDelta::hours(hours.get().into()),
+            order_cap: config.trading.new_account_order_cap.into(),
+        });
+    }
+
+    if let Some(hours) = &config.trading.hours {
+        let calendar = MarketCalendar::new(hours.days.iter().copied(), hours.open, hours.close)
+            .ok_or_eyre("Trading hours never open")?
+            .with_holidays(hours.holidays.iter().copied());
+        let after_hours = if hours.queue_orders {
+            AfterHours::Queue
+        } else {
+            AfterHours::Reject
+        };
+
+        service = service.with_calendar(calendar, after_hours);
+    }
+
+    if config.features.kromer {
+        service = service.with_deposit_name(&config.kromer.name);
+    }
+
+    // Restored before the treasury is created, as snapshots only restore into an empty database
+    if let Some(OneOff::ImportSnapshot(path)) = one_off {
+        return import_snapshot(&service, path).await;
+    }
+
+    service.ensure_treasury().await?;
+
+    if let Some(OneOff::Seed(path)) = one_off {
+        return apply_seed(&service, path).await;
+    }
 
-    let pool = sqlx::PgPool::connect(&db_url).await?;
+    let tasks = TaskRegistry::new();
 
-    sqlx::migrate!("./migrations").run(&pool).await?;
+    tasks.spawn(
+        "order-sweeper",
+        sweep_expired_orders(
+            service.clone(),
+            config.order_sweep_interval,
+            cancel_token.clone(),
+        ),
+    );
 
-    let service = Service::new(PgPort::new(pool));
+    tasks.spawn(
+        "reconciler",
+        reconcile(
+            service.clone(),
+            config.reconcile_interval,
+            cancel_token.clone(),
+        ),
+    );
 
-    let disc_handle = rse_discord::start(service, cancel_token.clone()).await;
+    tasks.spawn(
+        "end-of-day",
+        close_days(service.clone(), cancel_token.clone()),
+    );
+
+    if config.features.kromer {
+        let address = Address::try_from(config.kromer.address.as_str())?;
+        info!(%address, name = %config.kromer.name, "Taking deposits");
+
+        tasks.spawn(
+            "deposits",
+            rse_kromer::watch_deposits(
+                service.clone(),
+                rse_kromer::Client::http(&config.kromer.node_url),
+                address,
+                config.kromer.name,
+                config.kromer.poll_interval,
+                cancel_token.clone(),
+            ),
+        );
+
+        if let Some(private_key) = config.kromer.private_key {
+            info!(%address, "Paying out approved withdrawals");
+
+            tasks.spawn(
+                "payouts",
+                rse_kromer::pay_withdrawals(
+                    service.clone(),
+                    rse_kromer::Client::http(&config.kromer.node_url),
+                    private_key,
+                    config.kromer.poll_interval,
+                    cancel_token.clone(),
+                ),
+            );
+        } else {
+            info!("No wallet key, so approved withdrawals are left to send by hand");
+        }
+    }
+
+    let gateway = if config.features.discord {
+        Some(
+            rse_discord::start(
+                service.clone(),
+                config.discord,
+                &tasks,
+                cancel_token.clone(),
+            )
+            .await,
+        )
+    } else {
+        info!("Discord bot disabled");
+        None
+    };
+
+    if config.features.api {
+        let listener = tokio::net::TcpListener::bind(config.api.bind).await?;
+        info!(bind = %config.api.bind, "Serving the API");
+
+        tasks.spawn(
+            "api",
+            rse_http::serve(listener, service.clone(), cancel_token.clone()),
+        );
+    }
+
+    if config.features.http {
+        let listener = tokio::net::TcpListener::bind(config.http.bind).await?;
+        info!(bind = %config.http.bind, "Serving health checks");
+
+        tasks.spawn(
+            "health",
+            health::serve(listener, service, gateway, cancel_token.clone()),
+        );
+    }
 
     // Graceful shutdown stuff
     let mut sigterm = signal(SignalKind::terminate()).expect("Couldn't create handle for SIGTERM");
@@ -61,9 +240,224 @@ async fn main() -> color_eyre::Result<()> {
 
     cancel_token.cancel();
 
-    disc_handle.await?;
+    if let Err(unfinished) = tasks.shutdown(config.shutdown_timeout).await {
+        warn!(
+            ?unfinished,
+            "Tasks did not finish within {:?}, forcing exit", config.shutdown_timeout
+        );
+    } else {
+        info!("Gracefully shutdown");
+    }
+
+    Ok(())
+}
+
+/// Periodically takes expired orders off the book, matches orders queued while the market was
+/// closed once it opens, and forgets expired idempotency keys, link codes and idle rate limits
+/// until cancelled
+async fn sweep_expired_orders<R: StockRepository>(
+    service: Service<R>,
+    every: Duration,
+    c_token: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        select! {
+            () = c_token.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        let now = service.now();
+
+        match service.expire_orders(now).await {
+            Ok(0) => {}
+            Ok(count) => debug!(count, "Expired orders"),
+            Err(err) => error!(%err, "Couldn't expire orders"),
+        }
+
+        match service.release_queued_orders().await {
+            Ok(0) => {}
+            Ok(count) => info!(count, "Released queued orders"),
+            Err(err) => error!(%err, "Couldn't release queued orders"),
+        }
+
+        match service.purge_idempotency_keys(now).await {
+            Ok(0) => {}
+            Ok(count) => debug!(count, "Purged idempotency keys"),
+            Err(err) => error!(%err, "Couldn't purge idempotency keys"),
+        }
+
+        match service.purge_link_codes(now).await {
+            Ok(0) => {}
+            Ok(count) => debug!(count, "Purged link codes"),
+            Err(err) => error!(%err, "Couldn't purge link codes"),
+        }
+
+        let pruned = service.prune_rate_limits();
+        if pruned > 0 {
+            debug!(pruned, "Pruned idle rate limits");
+        }
+    }
+}
+
+/// Periodically checks that the books add up until cancelled. Discrepancies are logged and
+/// published by the service itself
+async fn reconcile<R: StockRepository>(
+    service: Service<R>,
+    every: Duration,
+    c_token: CancellationToken,
+) {
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        select! {
+            () = c_token.cancelled() => return,
+            _ = interval.tick() => {}
+        }
+
+        match service.reconcile().await {
+            Ok(report) if report.is_clean() => debug!(
+                accounts = report.accounts,
+                stocks = report.stocks,
+                "Books reconciled"
+            ),
+            Ok(report) => error!(
+                discrepancies = report.discrepancies.len(),
+                "Books don't reconcile"
+            ),
+            Err(err) => error!(%err, "Couldn't reconcile books"),
+        }
+    }
+}
+
+/// Records the closes of the previous UTC day, and snapshots what every portfolio is worth, at
+/// each midnight UTC until cancelled. Yesterday is closed on startup too, in case the exchange was
+/// down at midnight, since closing a day twice does nothing
+async fn close_days<R: StockRepository>(service: Service<R>, c_token: CancellationToken) {
+    /// How long to wait before trying again when a day couldn't be closed
+    const RETRY_DELAY: Duration = Duration::from_mins(10);
+
+    loop {
+        let now = service.now();
+        let today = now.date_naive();
+        let yesterday = today - TimeDelta::days(1);
+
+        let closed = match service.record_daily_closes(yesterday).await {
+            Ok(count) => {
+                debug!(count, "Recorded daily closes");
+                true
+            }
+            Err(err) => {
+                error!(%err, "Couldn't record daily closes");
+                false
+            }
+        };
+
+        let snapshotted = match service.record_portfolio_snapshots(yesterday).await {
+            Ok(count) => {
+                debug!(count, "Recorded portfolio snapshots");
+                true
+            }
+            Err(err) => {
+                error!(%err, "Couldn't record portfolio snapshots");
+                false
+            }
+        };
+
+        let next = if closed && snapshotted {
+            (today + TimeDelta::days(1))
+                .and_time(NaiveTime::MIN)
+                .and_utc()
+        } else {
+            now + RETRY_DELAY
+        };
+
+        let wait = (next - service.now()).to_std().unwrap_or_default();
+
+        select! {
+            () = c_token.cancelled() => return,
+            () = tokio::time::sleep(wait) => {}
+        }
+    }
+}
+
+/// Something done instead of starting the exchange, picked by the command line
+enum OneOff {
+    /// `--seed <file>` applies a seed file
+    Seed(PathBuf),
+    /// `--import-snapshot <file>` restores a snapshot taken with `/admin snapshot` into an empty
+    /// database, such as to reproduce production on staging
+    ImportSnapshot(PathBuf),
+}
+
+/// Reads the one-off task passed on the command line, if any
+fn one_off() -> color_eyre::Result<Option<OneOff>> {
+    let mut args = std::env::args_os().skip(1);
+
+    match args.next() {
+        None => Ok(None),
+        Some(arg) if arg == "--seed" => {
+            let path = args.next().ok_or_eyre("--seed needs a file")?;
+            Ok(Some(OneOff::Seed(path.into())))
+        }
+        Some(arg) if arg == "--import-snapshot" => {
+            let path = args.next().ok_or_eyre("--import-snapshot needs a file")?;
+            Ok(Some(OneOff::ImportSnapshot(path.into())))
+        }
+        Some(arg) => bail!("Unknown argument {}", arg.display()),
+    }
+}
+
+/// Restores a snapshot, gzipped if its name ends in `.gz`, into an empty database
+async fn import_snapshot<R: StockRepository>(
+    service: &Service<R>,
+    path: PathBuf,
+) -> color_eyre::Result<()> {
+    let file = File::open(&path)?;
+    let counts = if path.extension().is_some_and(|ext| ext == "gz") {
+        service
+            .import_snapshot(BufReader::new(GzDecoder::new(file)))
+            .await?
+    } else {
+        service.import_snapshot(BufReader::new(file)).await?
+    };
+
+    info!(path = %path.display(), "Restored snapshot: {counts}");
+    Ok(())
+}
+
+/// Applies a TOML or JSON seed file, failing if any of its entries couldn't be set up
+async fn apply_seed<R: StockRepository>(
+    service: &Service<R>,
+    path: PathBuf,
+) -> color_eyre::Result<()> {
+    let contents = std::fs::read_to_string(&path)?;
+    let seed: Seed = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&contents)?
+    } else {
+        toml::from_str(&contents)?
+    };
+
+    let report = service.apply_seed(&seed).await;
+
+    for entry in &report.created {
+        info!(entry, "Created");
+    }
+    for entry in &report.skipped {
+        info!(entry, "Skipped, already exists");
+    }
+    for (entry, err) in &report.failed {
+        error!(entry, %err, "Failed");
+    }
+
+    info!(path = %path.display(), "Applied seed: {report}");
 
-    info!("Gracefully shutdown");
+    if !report.failed.is_empty() {
+        bail!("{} seed entries failed", report.failed.len());
+    }
 
     Ok(())
 }
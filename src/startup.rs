@@ -0,0 +1,385 @@
+/*
+    This file is part of the Reconnected Stock Exchange (RSE)
+    Copyright (C) 2025 The RSE Team
+
+    This program is free software: you can redistribute it and/or modify
+    it under the terms of the GNU Affero General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    This program is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU Affero General Public License for more details.
+
+    You should have received a copy of the GNU Affero General Public License
+    along with this program.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Checks run before anything is started. Every check that can run does, so a misconfigured server
+//! reports all of its problems at once rather than the first one it happens to hit.
+
+use std::{fmt::Write, future::Future, time::Duration};
+
+use rse_config::{Config, PoolConfig};
+use snafu::{ResultExt, Snafu};
+use sqlx::{
+    Executor, PgPool,
+    migrate::{AppliedMigration, Migrate, MigrateError, Migration, Migrator},
+    postgres::PgPoolOptions,
+};
+use tracing::info;
+
+/// How long each external dependency has to answer before its check fails
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// A single failed startup check
+#[derive(Debug, Snafu)]
+pub enum StartupError {
+    #[snafu(display("{source}"))]
+    Config { source: rse_config::Error },
+    #[snafu(display("Couldn't connect to the database: {source}"))]
+    Database { source: sqlx::Error },
+    #[snafu(display("Couldn't connect to the database within {timeout:?}"))]
+    DatabaseTimeout { timeout: Duration },
+    #[snafu(display("Couldn't read which migrations are applied: {source}"))]
+    MigrationStatus { source: MigrateError },
+    #[snafu(display("Migration {version} was left partially applied, and must be fixed by hand"))]
+    DirtyMigration { version: i64 },
+    #[snafu(display(
+        "Migration {version} is applied but unknown to this build, is the server out of date?"
+    ))]
+    UnknownMigration { version: i64 },
+    #[snafu(display("Migration {version} ({description}) was changed after it was applied"))]
+    ModifiedMigration { version: i64, description: String },
+    #[snafu(display("Couldn't apply migrations: {source}"))]
+    Migrate { source: MigrateError },
+    #[snafu(display("{source}"))]
+    Discord { source: rse_discord::PreflightError },
+    #[snafu(display("Couldn't reach Discord within {timeout:?}"))]
+    DiscordTimeout { timeout: Duration },
+}
+
+/// Every startup check that failed
+#[derive(Debug)]
+pub struct Report(Vec<StartupError>);
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut buff = String::from("Startup checks failed:");
+
+        for err in &self.0 {
+            write!(buff, "\n  - {err}")?;
+        }
+
+        f.write_str(&buff)
+    }
+}
+
+impl std::error::Error for Report {}
+
+impl From<StartupError> for Report {
+    fn from(value: StartupError) -> Self {
+        Self(vec![value])
+    }
+}
+
+/// Loads the config and checks everything it points at, then applies any pending migrations. The
+/// Discord token is only checked when the bot is enabled and the server isn't just `seeding` or
+/// restoring a snapshot.
+pub async fn run(seeding: bool) -> Result<(Config, PgPool), Report> {
+    let config = check_config(Config::load())?;
+
+    let database = async {
+        let connect = pool_options(&config.pool).connect(&config.database_url);
+        let pool = check_database(connect, CHECK_TIMEOUT).await?;
+        let pending = migration_status(&pool).await?;
+        Ok((pool, pending))
+    };
+
+    let discord = async {
+        if seeding || !config.features.discord {
+            return Ok(());
+        }
+
+        let discord = &config.discord;
+        check_discord(
+            rse_discord::preflight(&discord.token, &discord.guild_ids),
+            CHECK_TIMEOUT,
+        )
+        .await
+    };
+
+    let (database, discord) = tokio::join!(database, discord);
+
+    let (pool, pending) = match (database, discord) {
+        (Ok(database), Ok(())) => database,
+        (database, discord) => {
+            return Err(Report(
+                [database.err(), discord.err()]
+                    .into_iter()
+                    .flatten()
+                    .collect(),
+            ));
+        }
+    };
+
+    for version in pending {
+        info!(version, "Applying migration");
+    }
+
+    MIGRATOR.run(&pool).await.context(MigrateSnafu)?;
+
+    Ok((config, pool))
+}
+
+/// Checks that the config loaded
+fn check_config(config: Result<Config, rse_config::Error>) -> Result<Config, StartupError> {
+    let config = config.context(ConfigSnafu)?;
+    info!("Loaded configuration");
+    Ok(config)
+}
+
+/// Builds the options for the pool of connections to the database. Every connection it opens has
+/// the configured statement timeout set before it is used.
+fn pool_options(config: &PoolConfig) -> PgPoolOptions {
+    let statement_timeout = format!(
+        "SET statement_timeout = {}",
+        config.statement_timeout.as_millis()
+    );
+
+    PgPoolOptions::new()
+        .max_connections(config.max_connections.get())
+        .min_connections(config.min_connections)
+        .acquire_timeout(config.acquire_timeout)
+        .idle_timeout(config.idle_timeout)
+        .after_connect(move |conn, _| {
+            let statement_timeout = statement_timeout.clone();
+            Box::pin(async move {
+                conn.execute(statement_timeout.as_str()).await?;
+                Ok(())
+            })
+        })
+}
+
+/// Checks that `connect` connects to the database within `timeout`
+async fn check_database<F>(connect: F, timeout: Duration) -> Result<PgPool, StartupError>
+where
+    F: Future<Output = Result<PgPool, sqlx::Error>>,
+{
+    let pool = tokio::time::timeout(timeout, connect)
+        .await
+        .map_err(|_| StartupError::DatabaseTimeout { timeout })?
+        .context(DatabaseSnafu)?;
+
+    info!("Connected to the database");
+    Ok(pool)
+}
+
+/// Checks the migrations applied to the database against those in this build, returning the
+/// versions still to be applied
+async fn migration_status(pool: &PgPool) -> Result<Vec<i64>, StartupError> {
+    let mut conn = pool.acquire().await.context(DatabaseSnafu)?;
+
+    conn.ensure_migrations_table()
+        .await
+        .context(MigrationStatusSnafu)?;
+    let dirty = conn.dirty_version().await.context(MigrationStatusSnafu)?;
+    let applied = conn
+        .list_applied_migrations()
+        .await
+        .context(MigrationStatusSnafu)?;
+
+    let pending = check_migrations(MIGRATOR.iter(), &applied, dirty)?;
+
+    info!(pending = pending.len(), "Checked migrations");
+    Ok(pending)
+}
+
+/// Checks that every migration in `applied` is one of `known` and unchanged since, and that none
+/// failed partway. Returns the versions of those in `known` that are yet to be applied.
+fn check_migrations<'m>(
+    known: impl IntoIterator<Item = &'m Migration>,
+    applied: &[AppliedMigration],
+    dirty: Option<i64>,
+) -> Result<Vec<i64>, StartupError> {
+    if let Some(version) = dirty {
+        return DirtyMigrationSnafu { version }.fail();
+    }
+
+    let known: Vec<_> = known
+        .into_iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .collect();
+
+    for applied in applied {
+        let Some(migration) = known.iter().find(|m| m.version == applied.version) else {
+            return UnknownMigrationSnafu {
+                version: applied.version,
+            }
+            .fail();
+        };
+
+        if migration.checksum != applied.checksum {
+            return ModifiedMigrationSnafu {
+                version: migration.version,
+                description: migration.description.clone(),
+            }
+            .fail();
+        }
+    }
+
+    Ok(known
+        .iter()
+        .map(|migration| migration.version)
+        .filter(|version| !applied.iter().any(|applied| applied.version == *version))
+        .collect())
+}
+
+/// Checks that `preflight` passes within `timeout`
+async fn check_discord<F>(preflight: F, timeout: Duration) -> Result<(), StartupError>
+where
+    F: Future<Output = Result<String, rse_discord::PreflightError>>,
+{
+    let app = tokio::time::timeout(timeout, preflight)
+        .await
+        .map_err(|_| StartupError::DiscordTimeout { timeout })?
+        .context(DiscordSnafu)?;
+
+    info!(app, "Authenticated with Discord");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, num::NonZeroU32};
+
+    use sqlx::migrate::MigrationType;
+
+    use super::*;
+
+    fn migration(version: i64, sql: &'static str) -> Migration {
+        Migration::new(
+            version,
+            Cow::Borrowed("test"),
+            MigrationType::Simple,
+            Cow::Borrowed(sql),
+            false,
+        )
+    }
+
+    fn applied(migration: &Migration) -> AppliedMigration {
+        AppliedMigration {
+            version: migration.version,
+            checksum: migration.checksum.clone(),
+        }
+    }
+
+    #[test]
+    fn unapplied_migrations_are_pending() {
+        let known = [migration(1, "SELECT 1"), migration(2, "SELECT 2")];
+
+        let pending = check_migrations(&known, &[applied(&known[0])], None).expect("Valid");
+
+        assert_eq!(pending, [2]);
+    }
+
+    #[test]
+    fn unknown_migrations_are_reported() {
+        let known = [migration(1, "SELECT 1")];
+        let newer = migration(2, "SELECT 2");
+
+        let err = check_migrations(&known, &[applied(&known[0]), applied(&newer)], None);
+
+        assert!(matches!(
+            err,
+            Err(StartupError::UnknownMigration { version: 2 })
+        ));
+    }
+
+    #[test]
+    fn modified_migrations_are_reported() {
+        let known = [migration(1, "SELECT 1")];
+        let original = migration(1, "SELECT 0");
+
+        let err = check_migrations(&known, &[applied(&original)], None);
+
+        assert!(matches!(
+            err,
+            Err(StartupError::ModifiedMigration { version: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn dirty_migrations_are_reported() {
+        let known = [migration(1, "SELECT 1")];
+
+        let err = check_migrations(&known, &[], Some(1));
+
+        assert!(matches!(
+            err,
+            Err(StartupError::DirtyMigration { version: 1 })
+        ));
+    }
+
+    #[test]
+    fn pool_options_follow_the_config() {
+        let config = PoolConfig {
+            max_connections: NonZeroU32::new(25).expect("Non zero"),
+            min_connections: 2,
+            acquire_timeout: Duration::from_secs(3),
+            idle_timeout: Duration::from_secs(120),
+            statement_timeout: Duration::from_secs(15),
+        };
+
+        let options = pool_options(&config);
+
+        assert_eq!(options.get_max_connections(), 25);
+        assert_eq!(options.get_min_connections(), 2);
+        assert_eq!(options.get_acquire_timeout(), Duration::from_secs(3));
+        assert_eq!(options.get_idle_timeout(), Some(Duration::from_secs(120)));
+    }
+
+    #[tokio::test]
+    async fn unresponsive_databases_time_out() {
+        let err = check_database(std::future::pending(), Duration::from_millis(10)).await;
+
+        assert!(matches!(err, Err(StartupError::DatabaseTimeout { .. })));
+    }
+
+    #[tokio::test]
+    async fn unreachable_databases_are_reported() {
+        let err = check_database(
+            std::future::ready(Err(sqlx::Error::PoolTimedOut)),
+            Duration::from_secs(1),
+        )
+        .await;
+
+        assert!(matches!(err, Err(StartupError::Database { .. })));
+    }
+
+    #[tokio::test]
+    async fn unresponsive_discord_times_out() {
+        let err = check_discord(std::future::pending(), Duration::from_millis(10)).await;
+
+        assert!(matches!(err, Err(StartupError::DiscordTimeout { .. })));
+    }
+
+    #[test]
+    fn reports_list_every_failure() {
+        let report = Report(vec![
+            StartupError::DatabaseTimeout {
+                timeout: Duration::from_secs(10),
+            },
+            StartupError::UnknownMigration { version: 3 },
+        ]);
+
+        assert_eq!(
+            report.to_string(),
+            "Startup checks failed:\n  - Couldn't connect to the database within 10s\n  - \
+             Migration 3 is applied but unknown to this build, is the server out of date?"
+        );
+    }
+}